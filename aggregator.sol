@@ -0,0 +1,39 @@
+//SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/**
+ * @title PurchaseAggregator
+ * @notice Executes several `atomicMatch_` calls of the Wyvern exchange in a single
+ * transaction, reverting all of them if any fails. The buy orders of the calls must
+ * have this contract as their maker, and the buyer as the recipient in their calldata.
+ */
+contract PurchaseAggregator {
+  address public immutable exchange;
+
+  /**
+   * @param _exchange the Wyvern exchange
+   */
+  constructor(address _exchange) {
+    exchange = _exchange;
+  }
+
+  /**
+   * @param _calls calldata of each `atomicMatch_` call
+   * @param _values ETH to send with each call, summing to msg.value
+   */
+  function batchBuy(bytes[] calldata _calls, uint256[] calldata _values) external payable {
+    require(_calls.length == _values.length, "PurchaseAggregator: Mismatched calls");
+    uint256 _total = 0;
+    for (uint256 i = 0; i < _calls.length; i++) {
+      _total += _values[i];
+      (bool ok, bytes memory ret) = exchange.call{value: _values[i]}(_calls[i]);
+      if (!ok) {
+        // bubble up the revert reason
+        assembly {
+          revert(add(ret, 32), mload(ret))
+        }
+      }
+    }
+    require(_total == msg.value, "PurchaseAggregator: Wrong value");
+  }
+}
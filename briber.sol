@@ -57,4 +57,41 @@ contract NFTRevert {
     (bool sent, ) = payable(block.coinbase).call{value: msg.value}("");
     require(sent, "NFTRevert: Unable to bribe miner");
   }
+  /**
+   * @notice Pays the bribe only if all the checks hold. Each check is a static call
+   * whose first returned word is compared to its expected value.
+   * @param _targets contracts to call
+   * @param _data calldata of each call
+   * @param _comparisons 0 for equal, 1 for greater or equal, 2 for less or equal (as uint256)
+   * @param _expected expected value of each returned word
+   */
+  function verifyAndPay(
+    address[] calldata _targets,
+    bytes[] calldata _data,
+    uint256[] calldata _comparisons,
+    bytes32[] calldata _expected
+  ) external payable {
+    require(
+      _targets.length == _data.length &&
+        _targets.length == _comparisons.length &&
+        _targets.length == _expected.length,
+      "NFTRevert: Mismatched checks"
+    );
+    for (uint256 i = 0; i < _targets.length; i++) {
+      (bool ok, bytes memory ret) = _targets[i].staticcall(_data[i]);
+      require(ok && ret.length >= 32, "NFTRevert: Check call failed");
+      bytes32 word = abi.decode(ret, (bytes32));
+      bool holds;
+      if (_comparisons[i] == 0) {
+        holds = word == _expected[i];
+      } else if (_comparisons[i] == 1) {
+        holds = uint256(word) >= uint256(_expected[i]);
+      } else if (_comparisons[i] == 2) {
+        holds = uint256(word) <= uint256(_expected[i]);
+      }
+      require(holds, "NFTRevert: Check failed");
+    }
+    (bool sent, ) = payable(block.coinbase).call{value: msg.value}("");
+    require(sent, "NFTRevert: Unable to bribe miner");
+  }
 }
@@ -0,0 +1,157 @@
+//! Harness of the integration tests, run against an anvil fork of mainnet at a pinned
+//! block, so that they are deterministic: the tokens and their owners are the same on
+//! every run, and the listings are fabricated and signed locally (see
+//! [`opensea::testing::Listing`]) instead of depending on live ones.
+//!
+//! `anvil` must be installed (see https://getfoundry.sh), and `ETH_RPC_URL` set to an
+//! archive node of mainnet. The tests are `#[ignore]`d so that `cargo test` skips them,
+//! run them with `ETH_RPC_URL=<url> cargo test -p it -- --ignored`: they fail if
+//! `ETH_RPC_URL` is unset, so that CI cannot pass without running them.
+use ethers::{
+    providers::{Http, JsonRpcClient, Middleware, Provider},
+    types::{Address, BlockNumber, TransactionRequest, U256},
+};
+use opensea::NFT;
+use std::{
+    convert::TryFrom,
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The block the fork is pinned at, before OpenSea moved from Wyvern to Seaport
+pub const FORK_BLOCK: u64 = 13_037_331;
+
+/// How long anvil may take to fetch the state of the fork block and start serving
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The number of blocks scanned per `eth_getLogs`, within the limits of most providers
+const LOGS_RANGE: u64 = 2_000;
+
+/// An anvil fork of mainnet at [`FORK_BLOCK`], killed on drop
+#[derive(Debug)]
+pub struct Fork {
+    anvil: Child,
+    pub provider: Arc<Provider<Http>>,
+}
+
+impl Fork {
+    /// Spawns anvil on a free port and waits for it to serve requests. Panics if
+    /// `ETH_RPC_URL` is unset.
+    pub async fn spawn() -> Self {
+        let fork_url = std::env::var("ETH_RPC_URL")
+            .expect("ETH_RPC_URL must be set to the URL of a mainnet archive node");
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("no free port")
+            .port();
+        let anvil = Command::new("anvil")
+            .args(["--fork-url", &fork_url])
+            .args(["--fork-block-number", &FORK_BLOCK.to_string()])
+            .args(["--port", &port.to_string()])
+            // so that the mainnet exchange is expected by the signature checks
+            .args(["--chain-id", "1"])
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("could not spawn anvil, is it installed?");
+        let provider = Provider::<Http>::try_from(format!("http://127.0.0.1:{}", port)).unwrap();
+        let fork = Self {
+            anvil,
+            provider: Arc::new(provider),
+        };
+
+        let started = Instant::now();
+        while fork.provider.get_block_number().await.is_err() {
+            assert!(
+                started.elapsed() < STARTUP_TIMEOUT,
+                "anvil did not start within {:?}",
+                STARTUP_TIMEOUT
+            );
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        fork
+    }
+
+    /// Unlocks the account and funds it with 1 ETH for the gas
+    pub async fn impersonate(&self, account: Address) {
+        let node: &Http = (*self.provider).as_ref();
+        let _: () = node
+            .request("anvil_impersonateAccount", [account])
+            .await
+            .unwrap();
+        let _: () = node
+            .request("anvil_setBalance", (account, U256::exp10(18)))
+            .await
+            .unwrap();
+    }
+
+    /// Hands the ERC721 token over to `to`, impersonating its owner
+    pub async fn give_erc721(&self, nft: Address, id: U256, to: Address) {
+        let contract = NFT::new(nft, self.provider.clone());
+        let owner = contract.owner_of(id).call().await.unwrap();
+        let data = contract.transfer_from(owner, to, id).calldata().unwrap();
+        self.send_as(owner, nft, data).await;
+    }
+
+    /// Hands `quantity` of the ERC1155 token over to `to`, impersonating the most recent
+    /// recipient of the token which still holds as many
+    pub async fn give_erc1155(&self, nft: Address, id: U256, quantity: U256, to: Address) {
+        let contract = NFT::new(nft, self.provider.clone());
+        let holder = self.erc1155_holder(&contract, id, quantity).await;
+        let data = contract
+            .safe_transfer_from(holder, to, id, quantity, Default::default())
+            .calldata()
+            .unwrap();
+        self.send_as(holder, nft, data).await;
+    }
+
+    async fn erc1155_holder(
+        &self,
+        contract: &NFT<Provider<Http>>,
+        id: U256,
+        quantity: U256,
+    ) -> Address {
+        let mut to_block = FORK_BLOCK;
+        // up to a year of blocks
+        while to_block > FORK_BLOCK - 2_400_000 {
+            let from_block = to_block - LOGS_RANGE;
+            let transfers = contract
+                .transfer_single_filter()
+                .from_block(BlockNumber::Number(from_block.into()))
+                .to_block(BlockNumber::Number(to_block.into()))
+                .query()
+                .await
+                .unwrap();
+            for transfer in transfers.iter().rev().filter(|transfer| transfer.id == id) {
+                let balance = contract.balance_of(transfer.to, id).call().await.unwrap();
+                if balance >= quantity {
+                    return transfer.to;
+                }
+            }
+            to_block = from_block - 1;
+        }
+        panic!("no holder of {} of token {} found", quantity, id)
+    }
+
+    async fn send_as(&self, from: Address, to: Address, data: ethers::types::Bytes) {
+        self.impersonate(from).await;
+        let tx = TransactionRequest::new().from(from).to(to).data(data);
+        let receipt = self
+            .provider
+            .send_transaction(tx, None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.status, Some(1.into()), "transfer reverted");
+    }
+}
+
+impl Drop for Fork {
+    fn drop(&mut self) {
+        let _ = self.anvil.kill();
+        let _ = self.anvil.wait();
+    }
+}
@@ -0,0 +1,94 @@
+//! The full buy flow against a fork: a listing is signed by a local maker holding the
+//! token, then found via `Client::buy`, whose call is sent by the taker and transfers
+//! the token. See `src/lib.rs` to run them.
+use ethers::{
+    signers::Signer,
+    types::{Address, U256},
+};
+use it::Fork;
+use opensea::{
+    testing::{anvil_wallet, approve_maker, Listing},
+    BuyArgs, Client, MockOrderProvider, TokenStandard, NFT,
+};
+
+/// A listing of `quantity` of the token made by the second anvil account, and the
+/// client to buy it with
+async fn listed(
+    fork: &Fork,
+    nft: Address,
+    id: U256,
+    standard: TokenStandard,
+    quantity: U256,
+) -> Client<ethers::providers::Provider<ethers::providers::Http>, MockOrderProvider> {
+    let maker = anvil_wallet(1);
+    match standard {
+        TokenStandard::Erc721 => fork.give_erc721(nft, id, maker.address()).await,
+        TokenStandard::Erc1155 => fork.give_erc1155(nft, id, quantity, maker.address()).await,
+    }
+    let client = Client::with_api(fork.provider.clone(), MockOrderProvider::default());
+    approve_maker(&client, maker.address(), nft).await.unwrap();
+
+    let sell = Listing::new(nft, id, standard, U256::exp10(17))
+        .quantity(quantity)
+        .sign(&maker)
+        .await
+        .unwrap();
+    Client::with_api(fork.provider.clone(), MockOrderProvider::new(vec![sell]))
+}
+
+fn args(nft: Address, id: U256, taker: Address) -> BuyArgs {
+    BuyArgs {
+        token_id: id,
+        taker,
+        token: nft,
+        recipient: taker,
+        timestamp: None,
+        quantity: None,
+        max_price: None,
+        total_budget: None,
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn buys_an_erc721() {
+    let fork = Fork::spawn().await;
+    let nft = "0x91f7bb6900d65d004a659f34205beafc3b4e136c"
+        .parse::<Address>()
+        .unwrap();
+    let id = U256::from(1126);
+    let client = listed(&fork, nft, id, TokenStandard::Erc721, U256::one()).await;
+    let taker = anvil_wallet(0).address();
+
+    let buy = client.buy(args(nft, id, taker), 1).await.unwrap().remove(0);
+    let receipt = buy.call.send().await.unwrap().await.unwrap().unwrap();
+    assert_eq!(receipt.status, Some(1.into()));
+
+    let contract = NFT::new(nft, fork.provider.clone());
+    assert_eq!(contract.owner_of(id).call().await.unwrap(), taker);
+}
+
+#[tokio::test]
+#[ignore]
+async fn buys_an_erc1155() {
+    let fork = Fork::spawn().await;
+    let nft = "0x47e22659d9ae152975e6cbfa2eed5dc8b75ac545"
+        .parse::<Address>()
+        .unwrap();
+    let id = U256::one();
+    let client = listed(&fork, nft, id, TokenStandard::Erc1155, U256::one()).await;
+    let taker = anvil_wallet(0).address();
+    let contract = NFT::new(nft, fork.provider.clone());
+    // the account may have been airdropped some on mainnet
+    let balance = contract.balance_of(taker, id).call().await.unwrap();
+
+    let buy = client.buy(args(nft, id, taker), 1).await.unwrap().remove(0);
+    assert_eq!(buy.quantity, U256::one());
+    let receipt = buy.call.send().await.unwrap().await.unwrap().unwrap();
+    assert_eq!(receipt.status, Some(1.into()));
+
+    assert_eq!(
+        contract.balance_of(taker, id).call().await.unwrap(),
+        balance + 1
+    );
+}
@@ -0,0 +1,11 @@
+//! Embeds the compiled briber contract if its artifact is checked in, see `src/briber.rs`
+use std::path::Path;
+
+fn main() {
+    let artifact = Path::new("artifacts/briber.json");
+    println!("cargo:rerun-if-changed={}", artifact.display());
+    println!("cargo:rustc-check-cfg=cfg(briber_artifact)");
+    if artifact.is_file() {
+        println!("cargo:rustc-cfg=briber_artifact");
+    }
+}
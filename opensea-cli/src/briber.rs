@@ -0,0 +1,66 @@
+//! The ABI and bytecode of the `NFTRevert` contract of `briber.sol`. They are embedded
+//! from `artifacts/briber.json`, generated via
+//!
+//! ```bash
+//! solc --combined-json abi,bin briber.sol > opensea-cli/artifacts/briber.json
+//! ```
+//!
+//! With the `recompile` feature, or if the artifact is not checked in, `briber.sol` is
+//! compiled at runtime instead, which requires `solc` in the `PATH`.
+use ethers::{abi::Abi, types::Bytes};
+
+const CONTRACT: &str = "NFTRevert";
+
+#[cfg(all(briber_artifact, not(feature = "recompile")))]
+pub fn compiled() -> color_eyre::Result<(Abi, Bytes)> {
+    parse_artifact(include_str!("../artifacts/briber.json"))
+}
+
+#[cfg(any(not(briber_artifact), feature = "recompile"))]
+pub fn compiled() -> color_eyre::Result<(Abi, Bytes)> {
+    // relative to the crate instead of the working directory
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../briber.sol");
+    let output = std::process::Command::new("solc")
+        .args(["--combined-json", "abi,bin", path])
+        .output()
+        .map_err(|err| {
+            color_eyre::eyre::eyre!(
+                "No briber artifact was embedded, and could not run solc to compile {}: {}",
+                path,
+                err
+            )
+        })?;
+    if !output.status.success() {
+        color_eyre::eyre::bail!(
+            "Could not compile {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    parse_artifact(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the output of `solc --combined-json abi,bin`, whose ABI is a JSON string
+/// in older solc versions
+fn parse_artifact(artifact: &str) -> color_eyre::Result<(Abi, Bytes)> {
+    let artifact: serde_json::Value = serde_json::from_str(artifact)?;
+    let contract = artifact["contracts"]
+        .as_object()
+        .and_then(|contracts| {
+            contracts
+                .iter()
+                .find(|(name, _)| name.rsplit(':').next() == Some(CONTRACT))
+        })
+        .map(|(_, contract)| contract)
+        .ok_or_else(|| color_eyre::eyre::eyre!("{} not found in the artifact", CONTRACT))?;
+
+    let abi = match contract["abi"] {
+        serde_json::Value::String(ref abi) => serde_json::from_str(abi)?,
+        ref abi => serde_json::from_value(abi.clone())?,
+    };
+    let bin = contract["bin"]
+        .as_str()
+        .ok_or_else(|| color_eyre::eyre::eyre!("{} has no bytecode", CONTRACT))?;
+    let bytecode = hex::decode(bin.trim_start_matches("0x"))?;
+    Ok((abi, bytecode.into()))
+}
@@ -0,0 +1,343 @@
+//! The `opensea.toml` config file, holding defaults for the flags. Each value is
+//! passed as its flag (e.g. `url` of the `[eth]` table as `--eth.url`) to the
+//! subcommands which accept it, unless the flag was given on the command line.
+//!
+//! ```toml
+//! [eth]
+//! url = "http://localhost:8545"
+//! ws_url = "ws://localhost:8546"
+//!
+//! [api]
+//! key = "..."
+//!
+//! [fees]
+//! history = true
+//!
+//! [flashbots]
+//! relays = ["https://relay.flashbots.net", "https://builder0x69.io"]
+//! bribe = "0.01"
+//!
+//! [safe]
+//! allow_collections = ["0x..."]
+//!
+//! [labels]
+//! "0x..." = "Vault"
+//! ```
+use ethers::types::Address;
+use opensea::address_book;
+use serde::Deserialize;
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+/// Read if present and no `--config` is given
+const DEFAULT_CONFIG_PATH: &str = "opensea.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub eth: EthConfig,
+    pub api: ApiConfig,
+    pub fees: FeesConfig,
+    pub flashbots: FlashbotsConfig,
+    pub safe: SafeConfig,
+    /// The labels of addresses shown instead of them in the output, e.g. of one's own
+    /// wallets, on top of the library's address book
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EthConfig {
+    pub url: Option<String>,
+    pub ws_url: Option<String>,
+    pub keystore: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub key: Option<String>,
+    /// More keys, rotated along with the `key`
+    pub keys: Vec<String>,
+    pub key_rotation: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub connect_timeout_ms: Option<u64>,
+    pub proxy: Option<String>,
+    pub base_url: Option<String>,
+    pub orderbook_url: Option<String>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub http2_prior_knowledge: bool,
+    pub tcp_keepalive_ms: Option<u64>,
+}
+
+/// Amounts are in Wei, as strings since they may not fit TOML integers
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeesConfig {
+    pub history: bool,
+    pub history_blocks: Option<u64>,
+    pub percentile: Option<f64>,
+    pub max_base_fee: Option<String>,
+    pub priority_fee: Option<String>,
+    pub escalate_blocks: Option<u64>,
+    pub escalate_percent: Option<u64>,
+    pub max_gas_price: Option<String>,
+}
+
+/// Amounts are in ETH, e.g. `"0.01"`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlashbotsConfig {
+    pub relays: Vec<String>,
+    pub bribe: Option<String>,
+    pub resale_value: Option<String>,
+    pub margin_bps: Option<u64>,
+    pub bribe_receiver: Option<String>,
+    pub target_blocks: Option<u64>,
+    pub atomicity: Option<String>,
+}
+
+/// The allowlists and denylists of the buys, see `--safe.allow_collection`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafeConfig {
+    pub allow_collections: Vec<String>,
+    pub deny_collections: Vec<String>,
+    pub allow_makers: Vec<String>,
+    pub deny_makers: Vec<String>,
+}
+
+/// A flag set by the config: a switch if it has no values, an option repeated for
+/// each value otherwise
+struct Flag {
+    name: &'static str,
+    values: Vec<String>,
+    /// The flag turning the switch back off on the command line
+    negation: Option<&'static str>,
+}
+
+impl Config {
+    pub fn load(path: &std::path::Path) -> color_eyre::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| color_eyre::eyre::eyre!("Could not read {}: {}", path.display(), err))?;
+        toml::from_str(&text)
+            .map_err(|err| color_eyre::eyre::eyre!("Invalid config {}: {}", path.display(), err))
+    }
+
+    /// Registers the labels in the address book
+    fn register_labels(&self) -> color_eyre::Result<()> {
+        for (address, label) in &self.labels {
+            let address: Address = address.parse().map_err(|err| {
+                color_eyre::eyre::eyre!("Invalid address {} in [labels]: {}", address, err)
+            })?;
+            address_book::register(address, label.clone());
+        }
+        Ok(())
+    }
+
+    /// The flags set by the config which the subcommand accepts
+    fn flags(&self, subcommand: &str) -> Vec<Flag> {
+        let mut flags = Vec::new();
+        let mut option = |name, value: Option<String>| {
+            if let Some(value) = value {
+                flags.push(Flag {
+                    name,
+                    values: vec![value],
+                    negation: None,
+                });
+            }
+        };
+
+        let (eth, api, buy) = match subcommand {
+            "buy" => (true, true, true),
+            "cancel" => (true, true, false),
+            "deploy" | "transfer" => (true, false, false),
+            "quote" | "prices" | "watch" => (false, true, false),
+            _ => (false, false, false),
+        };
+        if eth {
+            option("eth.url", self.eth.url.clone());
+            option("eth.ws_url", self.eth.ws_url.clone());
+            option(
+                "eth.keystore",
+                self.eth
+                    .keystore
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            );
+        }
+        if api {
+            option("api.key", self.api.key.clone());
+            option("api.key_rotation", self.api.key_rotation.clone());
+            option(
+                "api.timeout_ms",
+                self.api.timeout_ms.map(|ms| ms.to_string()),
+            );
+            option(
+                "api.connect_timeout_ms",
+                self.api.connect_timeout_ms.map(|ms| ms.to_string()),
+            );
+            option("api.proxy", self.api.proxy.clone());
+            option("api.base_url", self.api.base_url.clone());
+            option("api.orderbook_url", self.api.orderbook_url.clone());
+            option(
+                "api.pool_max_idle_per_host",
+                self.api.pool_max_idle_per_host.map(|n| n.to_string()),
+            );
+            option(
+                "api.tcp_keepalive_ms",
+                self.api.tcp_keepalive_ms.map(|ms| ms.to_string()),
+            );
+        }
+        if buy {
+            option(
+                "fees.history_blocks",
+                self.fees.history_blocks.map(|n| n.to_string()),
+            );
+            option(
+                "fees.percentile",
+                self.fees.percentile.map(|p| p.to_string()),
+            );
+            option("fees.max_base_fee", self.fees.max_base_fee.clone());
+            option("fees.priority_fee", self.fees.priority_fee.clone());
+            option(
+                "fees.escalate_blocks",
+                self.fees.escalate_blocks.map(|n| n.to_string()),
+            );
+            option(
+                "fees.escalate_percent",
+                self.fees.escalate_percent.map(|p| p.to_string()),
+            );
+            option("fees.max_gas_price", self.fees.max_gas_price.clone());
+            option("flashbots.bribe", self.flashbots.bribe.clone());
+            option(
+                "flashbots.resale_value",
+                self.flashbots.resale_value.clone(),
+            );
+            option(
+                "flashbots.margin_bps",
+                self.flashbots.margin_bps.map(|bps| bps.to_string()),
+            );
+            option(
+                "flashbots.bribe_receiver",
+                self.flashbots.bribe_receiver.clone(),
+            );
+            option(
+                "flashbots.target_blocks",
+                self.flashbots.target_blocks.map(|n| n.to_string()),
+            );
+            option("flashbots.atomicity", self.flashbots.atomicity.clone());
+            if self.fees.history {
+                flags.push(Flag {
+                    name: "fees.history",
+                    values: Vec::new(),
+                    negation: Some("fees.no_history"),
+                });
+            }
+            if !self.flashbots.relays.is_empty() {
+                flags.push(Flag {
+                    name: "flashbots.relay",
+                    values: self.flashbots.relays.clone(),
+                    negation: None,
+                });
+            }
+            let lists = [
+                ("safe.allow_collection", &self.safe.allow_collections),
+                ("safe.deny_collection", &self.safe.deny_collections),
+                ("safe.allow_maker", &self.safe.allow_makers),
+                ("safe.deny_maker", &self.safe.deny_makers),
+            ];
+            for (name, values) in lists {
+                if !values.is_empty() {
+                    flags.push(Flag {
+                        name,
+                        values: values.clone(),
+                        negation: None,
+                    });
+                }
+            }
+        }
+        if api && !self.api.keys.is_empty() {
+            flags.push(Flag {
+                name: "api.key",
+                values: self.api.keys.clone(),
+                negation: None,
+            });
+        }
+        if api && self.api.http2_prior_knowledge {
+            flags.push(Flag {
+                name: "api.http2_prior_knowledge",
+                values: Vec::new(),
+                negation: None,
+            });
+        }
+        flags
+    }
+}
+
+/// Returns the command line args, extended with the flags of the config file which
+/// were not given on the command line, and registers the labels of the config. The
+/// config is read from the `--config` path, or from `opensea.toml` if it exists.
+pub fn args() -> color_eyre::Result<Vec<OsString>> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let strings = args
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    let path = match config_path(&strings) {
+        Some(path) => PathBuf::from(path),
+        None if std::path::Path::new(DEFAULT_CONFIG_PATH).is_file() => {
+            PathBuf::from(DEFAULT_CONFIG_PATH)
+        }
+        None => return Ok(args),
+    };
+    let config = Config::load(&path)?;
+    config.register_labels()?;
+
+    // the subcommand is the first positional arg
+    let mut iter = strings.iter().skip(1);
+    let mut subcommand = None;
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            iter.next();
+        } else if !arg.starts_with('-') {
+            subcommand = Some(arg.as_str());
+            break;
+        }
+    }
+    let subcommand = match subcommand {
+        Some(subcommand) => subcommand,
+        None => return Ok(args),
+    };
+
+    for flag in config.flags(subcommand) {
+        let long = format!("--{}", flag.name);
+        let given = strings
+            .iter()
+            .any(|arg| *arg == long || arg.starts_with(&format!("{}=", long)));
+        let negated = flag
+            .negation
+            .is_some_and(|negation| strings.contains(&format!("--{}", negation)));
+        if given || negated {
+            continue;
+        }
+        if flag.values.is_empty() {
+            args.push(long.clone().into());
+        }
+        for value in flag.values {
+            args.push(long.clone().into());
+            args.push(value.into());
+        }
+    }
+    Ok(args)
+}
+
+fn config_path(args: &[String]) -> Option<&str> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--config" {
+            args.get(i + 1).map(|path| path.as_str())
+        } else {
+            arg.strip_prefix("--config=")
+        }
+    })
+}
@@ -1,170 +1,260 @@
-use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction};
-use ethers_flashbots::{BundleRequest, FlashbotsMiddleware};
-use opensea::{api::OpenSeaApiConfig, BuyArgs, Client};
-use std::sync::Arc;
-
-use crate::opts::{BuyOpts, DeployOpts, NftOpts};
-
-ethers::contract::abigen!(
-    NFT,
-    r#"[
-        function ownerOf(uint256) view returns (address)
-        function balanceOf(address,uint256) view returns (uint256)
-    ]"#
-);
-
-impl<M: Middleware + 'static> NFT<M> {
-    /// Helper function for logging information about the owner(s) of the nfts
-    pub async fn log(
-        &self,
-        ids: &[U256],
-        recipient: Address,
-        erc1155: bool,
-    ) -> color_eyre::Result<()> {
-        for id in ids {
-            if erc1155 {
-                let balance = self.balance_of(recipient, *id).call().await?;
-                println!(
-                    "{:?} owns {:?} ERC1155 NFTs with token id {:?}",
-                    recipient, balance, id
-                );
-            } else {
-                let owner = self.owner_of(*id).call().await?;
-                println!("Owner of ERC721 NFTs with token id {:?}: {:?}", id, owner);
-            }
+use ethers::prelude::*;
+use futures::Future;
+use opensea::{
+    abort_on_price_moves,
+    api::OrderQuery,
+    display::message_writer,
+    ownership::token_standard,
+    recording::{Recorded, RecordedPlan},
+    say, sign_listing,
+    types::{MinimalOrder, OrderSide},
+    units::format_eth,
+    AbortHandle, AbortReason, Bribe, BribeStrategy, BundleSimulationReport, BuyJournal, Client,
+    Deadline, EventFeed, ListingArgs, ListingMode, OpenSeaApi, Ownership, OwnershipQuery,
+    PriceMonitor, RunRecorder, SnipeError, SnipeOutcome, SnipePlan, SnipeStrategy, SnipeSummary,
+    SnipeTarget, StaticCall, TokenStandard, TransferWatch, NFT,
+};
+use std::{
+    io::Write,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    briber, ens,
+    error::CliError,
+    opts::{BuyOpts, CancelOpts, DeployOpts, EthereumOpts, ListOpts, TransferOpts},
+    output::{self, Record},
+};
+
+/// Evaluates `$body` with `$signer` bound to the signer selected by the
+/// [`EthereumOpts`], configured for the chain. Each signer has its own type, so the
+/// body is instantiated for each of them.
+macro_rules! with_signer {
+    ($eth:expr, $chain_id:expr, |$signer:ident| $body:expr) => {{
+        let eth: &EthereumOpts = $eth;
+        let chain_id: u64 = $chain_id;
+        eth.check_signer_features()?;
+
+        #[cfg(feature = "ledger")]
+        if eth.ledger {
+            let $signer = eth.ledger(chain_id).await?;
+            return $body;
+        }
+        #[cfg(feature = "aws")]
+        if let Some($signer) = eth.aws_signer(chain_id).await? {
+            return $body;
         }
 
-        Ok(())
-    }
+        let $signer = eth.signer()?.with_chain_id(chain_id);
+        $body
+    }};
 }
 
-ethers::contract::abigen!(
-    Briber,
-    r#"[
-        function verifyOwnershipAndPay721(address _nftContract, address _owner, uint256[] calldata _nftIds) external payable
-        function verifyOwnershipAndPay1155(address _nftContract, address _owner, uint256[] calldata _nftIds, uint256[] calldata _expectedBalances ) external payable
-    ]"#
-);
-
-/// Deploys the `briber.sol` contract.
-pub async fn deploy(opts: DeployOpts) -> color_eyre::Result<Address> {
-    // instantiate the provider with the signer
-    let provider = {
-        let provider = opts.eth.provider()?;
-        let chain_id = provider.get_chainid().await?.as_u64();
-        let signer = opts.eth.signer()?;
-        let signer = signer.with_chain_id(chain_id);
-        let provider = SignerMiddleware::new(provider.clone(), signer);
-        Arc::new(provider)
+/// Helper function for logging information about the owner(s) of the nfts. Collections
+/// whose standard cannot be detected are queried as ERC721s, unless `erc1155` is set.
+async fn log_ownership<M: Middleware + 'static>(
+    opensea: &Client<M>,
+    nft: Address,
+    erc1155: bool,
+    ids: &[U256],
+    recipient: Address,
+) -> color_eyre::Result<()> {
+    let erc1155 = erc1155 || opensea.token_standard(nft).await == Some(TokenStandard::Erc1155);
+    let query = if erc1155 {
+        OwnershipQuery::Erc1155 { holder: recipient }
+    } else {
+        OwnershipQuery::Erc721
     };
+    let snapshot = opensea.ownership_snapshot(nft, ids, query).await?;
+    let provider = opensea.provider.as_ref();
+    for id in ids {
+        let (owner, balance) = match snapshot.get(id) {
+            Some(Ownership::Balance(balance)) => (Some(recipient), Some(*balance)),
+            Some(Ownership::Owner(owner)) => (Some(*owner), None),
+            None => (None, None),
+        };
+        output::record(&Record::Ownership {
+            token: nft,
+            token_id: *id,
+            owner,
+            balance,
+        });
+        match snapshot.get(id) {
+            Some(Ownership::Balance(balance)) => say!(
+                "{} owns {:?} ERC1155 NFTs with token id {:?}",
+                ens::display(provider, recipient).await,
+                balance,
+                id
+            ),
+            Some(Ownership::Owner(owner)) => say!(
+                "Owner of ERC721 NFTs with token id {:?}: {}",
+                id,
+                ens::display(provider, *owner).await
+            ),
+            None => say!("Could not query ownership of token id {:?}", id),
+        }
+    }
 
-    // compile the briber contract each time
-    let solc = ethers::utils::Solc::new("briber.sol").build()?;
-    let contract = solc.get("NFTRevert").expect("could not find contract");
-    let briber = ContractFactory::new(contract.abi.clone(), contract.bytecode.clone(), provider);
-
-    // deploy it
-    let call = briber.deploy(())?;
-    let contract = call.send().await?;
-    println!("Bribe contract deployed: {:?}", contract.address());
-
-    Ok(contract.address())
+    Ok(())
 }
 
-use opensea::{get_n_cheapest_orders, OpenSeaApi};
-/// Queries the Opensea API the prices about an NFT and prints all prices as csv
-pub async fn prices(opts: NftOpts) -> color_eyre::Result<()> {
-    let api = OpenSeaApi::new(OpenSeaApiConfig::default());
-    println!("token_id,price");
-    let (ids, quantities) = opts.tokens()?;
-    for (id, _) in ids.iter().zip(&quantities) {
-        let orders = get_n_cheapest_orders(&api, opts.address, *id, 10).await?;
-        for order in orders {
-            println!("{:?},{:?}", *id, order.current_price);
+/// Logs the ownership of the targets of each collection, `nft` being the default one,
+/// which is an ERC1155 if `erc1155` is set
+async fn log_targets_ownership<M: Middleware + 'static>(
+    opensea: &Client<M>,
+    nft: Address,
+    erc1155: bool,
+    targets: &[SnipeTarget],
+    recipient: Address,
+) -> color_eyre::Result<()> {
+    let mut collections: Vec<Address> = Vec::new();
+    for target in targets {
+        let collection = target.nft.unwrap_or(nft);
+        if !collections.contains(&collection) {
+            collections.push(collection);
         }
     }
+
+    for collection in collections {
+        let ids: Vec<_> = targets
+            .iter()
+            .filter(|target| target.nft.unwrap_or(nft) == collection)
+            .map(|target| target.token_id)
+            .collect();
+        let erc1155 = erc1155 && collection == nft;
+        log_ownership(opensea, collection, erc1155, &ids, recipient).await?;
+    }
     Ok(())
 }
 
-/// Builds a list of unsigned transactions for purchasing the specified token ids
-/// at the specified quantities
-async fn create_transactions<M: Middleware + 'static>(
+/// Records the receipt of the tx, if it was mined, and returns whether it succeeded
+fn record_receipt(tx_hash: H256, receipt: Option<TransactionReceipt>) -> bool {
+    let success = matches!(receipt, Some(ref receipt) if receipt.status == Some(1.into()));
+    output::record(&Record::Receipt {
+        tx_hash,
+        block: receipt.and_then(|receipt| receipt.block_number),
+        success,
+    });
+    success
+}
+
+/// Prints the simulation of the bundle as a table of its txs, the bribe and payment txs
+/// having no token id
+fn print_simulation(simulation: &BundleSimulationReport) {
+    say!(
+        "== Simulation at block {:?} ==",
+        simulation.simulation_block
+    );
+    say!(
+        "{:<68} {:<24} {:>10} {:>20} {:>24} Status",
+        "Tx",
+        "Token Id",
+        "Gas",
+        "Wei per gas",
+        "Coinbase diff"
+    );
+    for tx in &simulation.txs {
+        say!(
+            "{:<68} {:<24} {:>10} {:>20} {:>24} {}",
+            format!("{:?}", tx.tx_hash),
+            tx.token_id
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "-".to_owned()),
+            tx.gas_used.to_string(),
+            tx.effective_gas_price.to_string(),
+            format_eth(tx.coinbase_diff),
+            tx.error.as_deref().unwrap_or("ok"),
+        );
+    }
+    say!(
+        "{:<68} {:<24} {:>10} {:>20} {:>24}",
+        "Total",
+        "",
+        simulation.gas_used.to_string(),
+        simulation.effective_gas_price.to_string(),
+        format_eth(simulation.coinbase_diff),
+    );
+}
+
+/// Runs the health check of the client before anything is sent, erroring if one of
+/// its checks failed
+async fn check_health<M: Middleware>(
     opensea: &Client<M>,
-    ids: &[U256],
-    quantities: &[usize],
-    max_base_fee: U256,
-    taker: Address,
-    args: &BuyArgs,
-) -> color_eyre::Result<(Vec<Eip1559TransactionRequest>, U256)> {
-    let mut nonce = opensea
-        .contracts
-        .client()
-        .get_transaction_count(taker, Some(BlockNumber::Pending.into()))
-        .await?;
-    let mut txs = Vec::new();
-    for (id, quantity) in ids.iter().zip(quantities) {
-        let mut args = args.clone();
-        args.token_id = id.into();
-        let buy_calls = opensea.buy(args, *quantity).await?;
-
-        for call in buy_calls {
-            // get the 1559 inner tx to configure the basefee
-            let mut tx = match call.tx {
-                TypedTransaction::Eip1559(inner) => inner,
-                _ => panic!("Did not expect non-1559 tx"),
-            };
-
-            // initialize the max base fee value, without any priority fee
-            tx.max_fee_per_gas = Some(max_base_fee);
-
-            // set & increment the nonce
-            tx.nonce = Some(nonce);
-            nonce += 1.into();
-
-            txs.push(tx)
-        }
+    account: Option<Address>,
+    skip: bool,
+) -> color_eyre::Result<()> {
+    if skip {
+        return Ok(());
     }
-    Ok((txs, nonce))
+    let report = opensea.health_check_of(account).await;
+    let _ = write!(message_writer(), "== Health check ==\n{}", report);
+    if !report.is_healthy() {
+        color_eyre::eyre::bail!(
+            "The health check failed, pass --skip_health_check to go on anyway"
+        );
+    }
+    Ok(())
 }
 
-// Create the signed txs bundle
-async fn sign_bundle<M: Middleware + 'static, S: Signer + 'static>(
-    provider: Arc<SignerMiddleware<M, S>>,
-    txs: &[Eip1559TransactionRequest],
-    ids: &[U256],
-) -> color_eyre::Result<BundleRequest> {
-    let mut bundle = ethers_flashbots::BundleRequest::new();
-    let mut sum = U256::from(0);
-    for (i, tx) in txs.iter().enumerate() {
-        if let Some(id) = ids.get(i) {
-            println!(
-                "[TokenId = {:?}] Signing bundle tx with {:?} Wei (max-priority-fee: {:?}, max-total-fee: {:?}, gas-limit: {:?})",
-                id,
-                tx.value.unwrap_or_default(),
-                tx.max_priority_fee_per_gas.unwrap_or_default(),
-                tx.max_fee_per_gas.unwrap_or_default(),
-                tx.gas.unwrap_or_default(),
-            );
-        } else {
-            println!(
-                "Signing bribe tx with {:?} Wei (max-priority-fee: {:?}, max-total-fee: {:?}, gas-limit: {:?})",
-                tx.value.unwrap_or_default(),
-                tx.max_priority_fee_per_gas.unwrap_or_default(),
-                tx.max_fee_per_gas.unwrap_or_default(),
-                tx.gas.unwrap_or_default(),
-            );
+/// Prints the summary of the transactions and asks whether to submit them. The answer
+/// is read on its own thread, so that an abort of the snipe (e.g. a Ctrl-C) drops the
+/// prompt instead of waiting for it.
+fn confirm(summary: &SnipeSummary) -> impl Future<Output = bool> {
+    say!("== Summary ==");
+    say!("{}", summary);
+    let mut out = message_writer();
+    let _ = write!(out, "Submit the transactions? [y/N] ");
+    let _ = out.flush();
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut answer = String::new();
+        let yes = std::io::stdin().read_line(&mut answer).is_ok()
+            && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes");
+        let _ = tx.send(yes);
+    });
+    async move { rx.await.unwrap_or(false) }
+}
+
+/// Deploys the `briber.sol` contract, unless it is already deployed at the given
+/// `--briber-address`
+pub async fn deploy(opts: DeployOpts) -> color_eyre::Result<Address> {
+    let provider = opts.eth.provider()?;
+    if let Some(ref address) = opts.briber_address {
+        let address = ens::resolve(provider.as_ref(), address).await?;
+        if !provider.get_code(address, None).await?.as_ref().is_empty() {
+            say!("Reusing the bribe contract deployed at {:?}", address);
+            output::record(&Record::ContractDeployed { address });
+            return Ok(address);
         }
+        say!("No contract deployed at {:?}, deploying it", address);
+    }
 
-        sum += tx.value.unwrap_or_default();
+    let chain_id = provider.get_chainid().await?.as_u64();
+    with_signer!(&opts.eth, chain_id, |signer| deploy_with(provider, signer)
+        .await)
+}
 
-        let tx = tx.clone().into();
-        let signature = provider.signer().sign_transaction(&tx).await?;
-        let chain_id = provider.signer().chain_id();
-        let rlp = tx.rlp_signed(chain_id, &signature);
-        bundle = bundle.push_transaction(rlp);
-    }
-    println!("Total Wei required: {:?}", sum);
-    Ok(bundle)
+async fn deploy_with<S: Signer + 'static>(
+    provider: Arc<Provider<Http>>,
+    signer: S,
+) -> color_eyre::Result<Address> {
+    // instantiate the provider with the signer
+    let provider = Arc::new(SignerMiddleware::new(provider, signer));
+
+    let (abi, bytecode) = briber::compiled()?;
+    let briber = ContractFactory::new(abi, bytecode, provider);
+
+    // deploy it
+    let call = briber.deploy(())?;
+    let contract = call.send().await?;
+    say!("Bribe contract deployed: {:?}", contract.address());
+    output::record(&Record::ContractDeployed {
+        address: contract.address(),
+    });
+
+    Ok(contract.address())
 }
 
 /// Purchases a set of tokens
@@ -173,174 +263,530 @@ pub async fn buy(opts: BuyOpts) -> color_eyre::Result<()> {
     let provider = opts.eth.provider()?;
     let chain_id = provider.get_chainid().await?.as_u64();
 
-    // read-only connection to the nft
-    let nft = NFT::new(opts.nft.address, provider.clone());
-
     // configure the signer's chain id
-    let signer = opts.eth.signer()?.with_chain_id(chain_id);
+    with_signer!(&opts.eth, chain_id, |signer| buy_with(
+        &opts, provider, signer
+    )
+    .await)
+}
+
+async fn buy_with<S: Signer + 'static>(
+    opts: &BuyOpts,
+    provider: Arc<Provider<Http>>,
+    signer: S,
+) -> color_eyre::Result<()> {
     let taker = signer.address();
 
-    println!("Sending txs from {:?}", taker);
-    println!("Balance: {:?}", provider.get_balance(taker, None).await?);
+    say!(
+        "Sending txs from {}",
+        ens::display(provider.as_ref(), taker).await
+    );
+    say!(
+        "Balance: {}",
+        format_eth(provider.get_balance(taker, None).await?)
+    );
+
+    let mut opensea = Client::new(provider.clone(), opts.api.config()).await?;
+    if let Some(ref dir) = opts.record {
+        let recorder = RunRecorder::create(dir)?;
+        opensea.api = recorder.record_api(opensea.api);
+        output::start_recording(recorder);
+        say!("Recording the run to {:?}", dir);
+    }
+    check_health(&opensea, Some(taker), opts.skip_health_check).await?;
+
+    // read the targets, whose collection defaults to the first one's
+    let targets = opts.snipe_targets()?;
+    let first_collection = targets.first().and_then(|target| target.nft);
+    let nft = match first_collection {
+        Some(nft) if opts.nft.address.is_none() && opts.nft.collection.is_none() => nft,
+        _ => {
+            opts.nft
+                .resolve_address(provider.as_ref(), Some(&opensea.api))
+                .await?
+        }
+    };
+    let bribe_receiver = match opts.flashbots.bribe_receiver {
+        Some(ref receiver) => Some(ens::resolve(provider.as_ref(), receiver).await?),
+        None => None,
+    };
+    for target in &targets {
+        say!(
+            "Collection: {:?}. Id: {:?}. Quantity: {:?}",
+            target.nft.unwrap_or(nft),
+            target.token_id,
+            target.quantity
+        );
+    }
+
+    opensea.listing_filter.exclude_flagged = opts.exclude_flagged;
+    opensea.onchain_price_tolerance_bps = opts.onchain_price_tolerance_bps;
+    opensea.safe_mode = opts.safe.safe_mode();
+    let ack_timeout = opts.events.ack_timeout_ms.map(Duration::from_millis);
+    let socket = match opts.events.socket {
+        Some(ref socket) => Some(EventFeed::connect_unix(socket, ack_timeout).await?),
+        None => None,
+    };
+    if output::is_json() || output::is_recording() {
+        opensea = opensea.with_events(output::event_feed(socket, ack_timeout));
+    } else if let Some(feed) = socket {
+        opensea = opensea.with_events(feed);
+    }
 
-    // set up the args
-    let block = provider.get_block(BlockNumber::Latest).await?.unwrap();
-    let timestamp = block.timestamp.as_u64();
+    let mut plan = SnipePlan::new(nft)
+        .targets(targets.clone())
+        .dry_run(opts.dry_run);
+    // otherwise detected
+    if opts.nft.erc1155 {
+        plan = plan.erc1155(true);
+    }
+    if let Some(cfg) = opts.swap.config() {
+        plan = plan.swap(cfg);
+    }
+    if let Some(max_price) = opts.max_price {
+        plan = plan.max_price(max_price);
+    }
+    if let Some(total_budget) = opts.total_budget {
+        plan = plan.total_budget(total_budget);
+    }
+    if let Some(ref path) = opts.resume {
+        plan = plan.resume(BuyJournal::open(path)?);
+    }
+    if let Some(ws) = opts.eth.ws_provider().await? {
+        plan = plan.watch_transfers(TransferWatch::new(ws));
+    }
+    if !opts.yes {
+        plan = plan.confirm(confirm);
+    }
 
-    let args = BuyArgs {
-        token_id: 0.into(),
+    let bribe = match (opts.flashbots.resale_value, opts.flashbots.bribe) {
+        (Some(resale_value), _) => {
+            say!(
+                "Using Flashbots. Bribe sized for a resale value of {} with a margin of {} bps",
+                format_eth(resale_value),
+                opts.flashbots.margin_bps
+            );
+            Some(Bribe::Profit(BribeStrategy::new(
+                resale_value,
+                opts.flashbots.margin_bps,
+            )))
+        }
+        (None, Some(bribe)) => {
+            say!(
+                "Using Flashbots. Bribe {}. Bribe Receiver {:?}",
+                format_eth(bribe),
+                bribe_receiver
+            );
+            // if an address is explicitly specified to receive the bribe, add an extra
+            // tx to the bundle, if not, spread the tx fee evenly across all txs' fee field
+            Some(match bribe_receiver {
+                Some(address) => Bribe::Contract {
+                    address,
+                    amount: bribe,
+                },
+                None => Bribe::PriorityFee(bribe),
+            })
+        }
+        (None, None) => None,
+    };
+    plan = match bribe {
+        Some(bribe) => plan
+            .bribe(bribe)
+            .strategy(SnipeStrategy::Bundle {
+                relays: opts.flashbots.relays.clone(),
+                target_blocks: opts.flashbots.target_blocks,
+                atomicity: opts.flashbots.atomicity,
+            })
+            .watch_cancellations(opts.flashbots.watch_cancellations),
+        None => plan.strategy(SnipeStrategy::Mempool {
+            simulate: opts.simulate,
+        }),
+    };
+    if let Some(escalator) = opts.fees.escalator() {
+        plan = plan.escalator(escalator);
+    }
+    if let Some(secs) = opts.deadline_secs {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        plan = plan.deadline(Deadline::Timestamp(now + secs));
+    }
+    if let Some(secs) = opts.bid_on_auctions {
+        plan = plan.bid_on_english_auctions(Duration::from_secs(secs));
+    }
+    if let Some(bps) = opts.requote_bps {
+        plan = plan.requote(bps);
+    }
+
+    say!("Querying current owners...");
+    log_targets_ownership(&opensea, nft, opts.nft.erc1155, &targets, taker).await?;
+
+    output::record_run(Recorded::Plan(RecordedPlan {
         taker,
-        token: opts.nft.address,
         recipient: taker,
-        timestamp: Some(timestamp - 100),
+        nft,
+        targets: targets.clone(),
+        max_price: opts.max_price,
+        filter: opensea.listing_filter,
+    }));
+
+    // a Ctrl-C stops the snipe, reporting the txs it already sent, and a second one
+    // exits right away
+    let abort = AbortHandle::new();
+    let plan = plan.abort_handle(abort.clone());
+    let interrupt = tokio::spawn({
+        let abort = abort.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                abort.abort(AbortReason::Interrupted);
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    let price_watch = opts.abort_on_price_moves.map(|secs| {
+        let monitor = PriceMonitor::new(
+            Arc::new(opensea.api.clone()),
+            nft,
+            Duration::from_secs(secs),
+        );
+        let events = monitor.subscribe();
+        let token_ids: Vec<_> = targets
+            .iter()
+            .filter(|target| target.nft.unwrap_or(nft) == nft)
+            .map(|target| target.token_id)
+            .collect();
+        let abort = abort.clone();
+        (
+            monitor.spawn(),
+            tokio::spawn(
+                async move { abort_on_price_moves(events, nft, &token_ids, &abort).await },
+            ),
+        )
+    });
+    let outcome = if let Some(fees) = opts.fees.fixed() {
+        opensea.snipe(&plan.fee_estimator(fees), signer).await
+    } else if opts.fees.history {
+        let fees = opts.fees.history();
+        opensea.snipe(&plan.fee_estimator(fees), signer).await
+    } else {
+        opensea.snipe(&plan, signer).await
     };
-
-    // get the max basefee 5 blocks in the future, just in case
-    let base_fee = block.base_fee_per_gas.expect("No basefee found");
-    println!("Current base fee {:?}", base_fee);
-    let mut max_base_fee = base_fee;
-    for _ in 0..5 {
-        max_base_fee *= 1125;
-        max_base_fee /= 1000;
+    // the signals get their default handling back
+    interrupt.abort();
+    if let Some((monitor, trigger)) = price_watch {
+        monitor.abort();
+        trigger.abort();
+    }
+    if let Err(SnipeError::Aborted {
+        ref reason,
+        ref progress,
+    }) = outcome
+    {
+        output::record(&Record::SnipeAborted {
+            reason: reason.clone(),
+            progress: (**progress).clone(),
+        });
+    }
+    let outcome = outcome?;
+    match outcome {
+        SnipeOutcome::DryRun(txs) => {
+            let json = serde_json::to_string_pretty(&txs)?;
+            output::record(&Record::DryRun { txs: txs.clone() });
+            match opts.dry_run_output {
+                Some(ref path) => {
+                    std::fs::write(path, json)?;
+                    say!("Wrote {} txs to {:?}", txs.len(), path);
+                }
+                None => say!("{}", json),
+            }
+            return Ok(());
+        }
+        SnipeOutcome::Included(bundle) => {
+            say!("Bundle included in block {:?}", bundle.block);
+            print_simulation(&bundle.simulation);
+            output::record(&Record::BundleIncluded {
+                block: bundle.block,
+                simulation: bundle.simulation,
+            });
+        }
+        SnipeOutcome::IncludedBundles(bundles) => {
+            for bundle in bundles.iter().flatten() {
+                say!("Bundle included in block {:?}", bundle.block);
+                print_simulation(&bundle.simulation);
+            }
+            let blocks: Vec<_> = bundles
+                .iter()
+                .map(|bundle| bundle.as_ref().map(|bundle| bundle.block))
+                .collect();
+            output::record(&Record::BundlesIncluded {
+                blocks: blocks.clone(),
+                simulations: bundles
+                    .into_iter()
+                    .map(|bundle| bundle.map(|bundle| bundle.simulation))
+                    .collect(),
+            });
+            let included = blocks.iter().filter(|block| block.is_some()).count();
+            say!(
+                "{}/{} bundles included: {:?}",
+                included,
+                blocks.len(),
+                blocks
+            );
+            if included == 0 {
+                return Err(CliError::NotIncluded(color_eyre::eyre::eyre!(
+                    "None of the {} bundles was included",
+                    blocks.len()
+                ))
+                .into());
+            }
+        }
+        SnipeOutcome::Sent(sent) => {
+            let tx_hashes: Vec<_> = sent.iter().map(|sent| sent.tx_hash).collect();
+            say!("Sent {} txs", tx_hashes.len());
+            output::record(&Record::TxsSent { tx_hashes });
+        }
+        SnipeOutcome::BidsPlaced(bids) => {
+            let order_hashes: Vec<_> = bids.iter().map(MinimalOrder::hash).collect();
+            say!(
+                "Nothing was bought: placed {} bids on English auctions {:?}",
+                bids.len(),
+                order_hashes
+            );
+            output::record(&Record::BidsPlaced { order_hashes });
+            return Ok(());
+        }
     }
-    println!("Max base fee {:?}", max_base_fee);
 
-    // read the token ids
-    let (ids, quantities) = opts.nft.tokens()?;
-    println!("Ids: {:?}", ids);
-    println!("Quantities: {:?}", quantities);
+    say!("== Ownership after ==");
+    log_targets_ownership(&opensea, nft, opts.nft.erc1155, &targets, taker).await?;
 
-    let opensea = Client::new(provider.clone(), OpenSeaApiConfig::default());
+    Ok(())
+}
 
-    // 1. construct the transactions w/ pre-calculated nonces
+/// Cancels the signer's listings selected by hash or token id
+pub async fn cancel(opts: CancelOpts) -> color_eyre::Result<()> {
+    let provider = opts.eth.provider()?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    with_signer!(&opts.eth, chain_id, |signer| cancel_with(
+        &opts, provider, signer
+    )
+    .await)
+}
 
-    let (txs, next_nonce) =
-        create_transactions(&opensea, &ids, &quantities, max_base_fee, taker, &args).await?;
+async fn cancel_with<S: Signer + 'static>(
+    opts: &CancelOpts,
+    provider: Arc<Provider<Http>>,
+    signer: S,
+) -> color_eyre::Result<()> {
+    if opts.order_hashes.is_empty() && opts.token_ids.is_empty() {
+        color_eyre::eyre::bail!("Pass the listings to cancel via --order-hash or --token-id");
+    }
+    let maker = signer.address();
+    let nft = ens::resolve(provider.as_ref(), &opts.address).await?;
+    let provider = Arc::new(SignerMiddleware::new(provider, signer));
+    let opensea = Client::new(provider, opts.api.config()).await?;
+    check_health(&opensea, Some(maker), opts.skip_health_check).await?;
+
+    // the listings of the maker, of the given tokens if any
+    let query = OrderQuery::new()
+        .side(OrderSide::Sell)
+        .asset_contract_address(nft)
+        .maker(maker)
+        .token_ids(&opts.token_ids)
+        .limit(50);
+    let orders = opensea.api.query_orders(query).await?;
+    let orders = orders
+        .into_iter()
+        .filter(|order| {
+            opts.order_hashes.is_empty() || opts.order_hashes.contains(&order.order_hash)
+        })
+        .collect::<Vec<_>>();
+    for hash in &opts.order_hashes {
+        if !orders.iter().any(|order| order.order_hash == *hash) {
+            say!(
+                "Listing {:?} of {} not found",
+                hash,
+                ens::display(opensea.provider.as_ref(), maker).await
+            );
+        }
+    }
 
-    println!("Querying current owners...");
-    nft.log(&ids, args.recipient, opts.nft.erc1155).await?;
+    for order in orders {
+        let hash = order.order_hash;
+        say!("{}", order);
+        let call = opensea.cancel_order(MinimalOrder::from(order));
+        let pending_tx = call.send().await?;
+        let tx_hash = *pending_tx;
+        say!("Cancelling listing {:?} in tx {:?}", hash, tx_hash);
+        output::record(&Record::CancellationSent {
+            order_hash: hash,
+            tx_hash,
+        });
+        if record_receipt(tx_hash, pending_tx.await?) {
+            say!("Listing {:?} cancelled", hash)
+        } else {
+            say!("Could not cancel listing {:?}", hash)
+        }
+    }
 
-    if let Some(bribe) = opts.flashbots.bribe {
-        println!(
-            "Using Flashbots. Bribe {:?}. Bribe Receiver {:?}",
-            bribe, opts.flashbots.bribe_receiver
-        );
+    Ok(())
+}
 
-        // Add signer and Flashbots middleware. The signer middleware MUST be
-        // inside the Flashbots Middleware, as shown in the docs:
-        // https://github.com/onbjerg/ethers-flashbots/blob/4a4e7a52b27122aedded6cd770545aefe06683f1/examples/advanced.rs#L19-L26
-        let bundle_signer = LocalWallet::new(&mut ethers::core::rand::thread_rng());
-        let provider = FlashbotsMiddleware::new(
-            provider,
-            url::Url::parse("https://relay.flashbots.net")?,
-            bundle_signer,
-        );
-        let provider = SignerMiddleware::new(provider, signer);
-        let provider = Arc::new(provider);
-
-        // if an address is explicitly specified to receive the bribe, add an extra
-        // tx to the bundle, if not, spread the tx fee evenly across all txs' fee field
-        let mut txs = txs;
-        match opts.flashbots.bribe_receiver {
-            Some(bribe_receiver) => {
-                println!(
-                    "Adding bribe tx to the bundle. Bribe Receiver {:?}, Amount: {:?}",
-                    bribe_receiver, bribe
-                );
-
-                // Construct the bribe transaction
-                let mut tx = Eip1559TransactionRequest::new()
-                    .to(bribe_receiver)
-                    // TODO: Can we remove this?
-                    .gas(200_000)
-                    .max_fee_per_gas(max_base_fee)
-                    // use the bumped nonce
-                    .nonce(next_nonce)
-                    .value(bribe);
-
-                // briber.sol has a different method call depending on erc1155 or 721s
-                // being sniped
-                let briber = Briber::new(bribe_receiver, provider.clone());
-                if opts.nft.erc1155 {
-                    tx.data = briber
-                        .verify_ownership_and_pay_1155(
-                            args.token,
-                            args.recipient,
-                            ids.clone(),
-                            quantities.iter().cloned().map(Into::into).collect(),
-                        )
-                        .calldata();
-                } else {
-                    tx.data = briber
-                        .verify_ownership_and_pay_721(args.token, args.recipient, ids.clone())
-                        .calldata();
-                };
-
-                txs.push(tx);
-            }
-            None => {
-                let priority_fee_per_tx = bribe / opts.nft.ids.len();
-                println!(
-                    "Splitting bribe across {:?} txs in the bundle. Amount per tx: {:?}",
-                    opts.nft.ids.len(),
-                    priority_fee_per_tx
-                );
-
-                txs.iter_mut().for_each(|tx| {
-                    // bump the max base fee by the priority fee
-                    if let Some(ref mut max_fee_per_gas) = tx.max_fee_per_gas {
-                        *max_fee_per_gas += priority_fee_per_tx;
-                    }
-                    tx.max_priority_fee_per_gas = Some(priority_fee_per_tx);
-                })
-            }
-        };
+/// Signs a listing and posts it. With `--offline`, the node is never connected to.
+pub async fn list(opts: ListOpts) -> color_eyre::Result<()> {
+    if opts.offline {
+        let chain_id = opts.api.network.chain_id();
+        return with_signer!(&opts.eth, chain_id, |signer| list_offline(&opts, signer)
+            .await);
+    }
+    let provider = opts.eth.provider()?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    with_signer!(&opts.eth, chain_id, |signer| list_with(
+        &opts, provider, signer
+    )
+    .await)
+}
 
-        let bundle = sign_bundle(provider.clone(), &txs, &ids).await?;
+fn listing_args(opts: &ListOpts, token: Address) -> ListingArgs {
+    let (standard, quantity) = if opts.erc1155 {
+        (TokenStandard::Erc1155, opts.quantity)
+    } else {
+        (TokenStandard::Erc721, U256::one())
+    };
+    ListingArgs {
+        token,
+        token_id: opts.id,
+        standard,
+        quantity,
+        price: opts.price,
+        fee_bps: opts.fee_bps,
+        expiration_time: opts.expiration,
+        timestamp: None,
+        static_call: opts.static_target.map(|target| {
+            StaticCall::new(target, opts.static_extradata.clone().unwrap_or_default())
+        }),
+    }
+}
 
-        if opts.dry_run {
-            return Ok(());
-        }
+async fn list_with<S: Signer + 'static>(
+    opts: &ListOpts,
+    provider: Arc<Provider<Http>>,
+    signer: S,
+) -> color_eyre::Result<()> {
+    let token = ens::resolve(provider.as_ref(), &opts.address).await?;
+    let opensea = Client::new(provider, opts.api.config()).await?;
+    // signing a listing costs no gas
+    check_health(&opensea, None, opts.skip_health_check).await?;
+    let args = listing_args(opts, token);
+    let sell = opensea
+        .create_listing(&args, &signer, ListingMode::Checked)
+        .await?;
+    say!("Posted {} ({:?})", sell, sell.hash());
+    output::record(&Record::ListingPosted {
+        order_hash: sell.hash(),
+    });
+    Ok(())
+}
 
-        // set the block bundle
-        let num = provider.get_block_number().await?;
-        let bundle = bundle.set_block(num + 5).set_simulation_block(num);
-        println!(
-            "Current block {:?}. Waiting for bundle until block {:?}",
-            num,
-            num + 5
-        );
+async fn list_offline<S: Signer + 'static>(opts: &ListOpts, signer: S) -> color_eyre::Result<()> {
+    let token = match opts.address {
+        NameOrAddress::Address(address) => address,
+        NameOrAddress::Name(ref name) => color_eyre::eyre::bail!(
+            "Invalid --nft.address {}, ENS names cannot be resolved with --offline",
+            name
+        ),
+    };
+    let network = opts.api.network;
+    let deployment = network.deployment().ok_or_else(|| {
+        color_eyre::eyre::eyre!("OpenSea has no Wyvern exchange on {:?}", network)
+    })?;
+    let args = listing_args(opts, token);
+    let sell = sign_listing(deployment.exchange, &args, &signer).await?;
+    say!(
+        "Warning: the Wyvern proxy of {:?} and its approval of {:?} are not checked, the listing cannot be filled without them",
+        sell.maker, token
+    );
+    say!("{}", serde_json::to_string_pretty(&sell)?);
+    output::record(&Record::ListingSigned {
+        order_hash: sell.hash(),
+        order: Box::new(sell.clone()),
+    });
+    if !opts.no_post {
+        OpenSeaApi::new(opts.api.config())?
+            .post_order(sell.clone())
+            .await?;
+        say!("Posted {} ({:?})", sell, sell.hash());
+        output::record(&Record::ListingPosted {
+            order_hash: sell.hash(),
+        });
+    }
+    Ok(())
+}
 
-        // 4. Send it!
-        println!("Simulating bundle");
-        let simulated_bundle = provider.inner().simulate_bundle(&bundle).await?;
-        println!("Simulated bundle: {:?}", simulated_bundle);
-        let pending_bundle = provider.inner().send_bundle(&bundle).await?;
-        let res = pending_bundle.await?;
-        println!("Bundle executed: {:?}", res);
-    } else {
-        let provider = SignerMiddleware::new(provider, signer);
-        let provider = Arc::new(provider);
+/// Transfers tokens from the signer to the receiver
+pub async fn transfer(opts: TransferOpts) -> color_eyre::Result<()> {
+    let provider = opts.eth.provider()?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    with_signer!(&opts.eth, chain_id, |signer| transfer_with(
+        &opts, provider, signer
+    )
+    .await)
+}
 
-        for (tx, id) in txs.into_iter().zip(&ids) {
-            let tx: TransactionRequest = tx.into();
+async fn transfer_with<S: Signer + 'static>(
+    opts: &TransferOpts,
+    provider: Arc<Provider<Http>>,
+    signer: S,
+) -> color_eyre::Result<()> {
+    let from = signer.address();
+    let address = opts.nft.resolve_address(provider.as_ref(), None).await?;
+    let to = ens::resolve(provider.as_ref(), &opts.to).await?;
+    let receiver = ens::display(provider.as_ref(), to).await;
+    let provider = Arc::new(SignerMiddleware::new(provider, signer));
+    let standard = if opts.nft.erc1155 {
+        TokenStandard::Erc1155
+    } else {
+        token_standard(provider.clone(), address)
+            .await
+            .ok_or_else(|| {
+                color_eyre::eyre::eyre!(
+                    "{:?} is neither an ERC721 nor an ERC1155, pass --nft.erc1155 for an ERC1155",
+                    address
+                )
+            })?
+    };
+    let nft = NFT::new(address, provider);
 
-            if opts.dry_run {
-                return Ok(());
+    let (ids, quantities) = opts.nft.tokens()?;
+    for (id, quantity) in ids.into_iter().zip(quantities) {
+        let call = match standard {
+            TokenStandard::Erc1155 => {
+                nft.safe_transfer_from(from, to, id, quantity.into(), Default::default())
             }
-
-            println!(
-                "[Token Id = {:?}] Sending tx with {:?} Wei ",
-                id,
-                tx.value.unwrap()
-            );
-            let pending_tx = provider.send_transaction(tx, None).await?;
-            println!("[Token Id = {:?}] Sent tx {:?}", id, *pending_tx);
+            TokenStandard::Erc721 => nft.transfer_from(from, to, id),
+        };
+        let pending_tx = call.send().await?;
+        let tx_hash = *pending_tx;
+        say!(
+            "Transferring {} of token id {:?} to {} in tx {:?}",
+            quantity,
+            id,
+            receiver,
+            tx_hash
+        );
+        output::record(&Record::TransferSent {
+            token: address,
+            token_id: id,
+            quantity,
+            to,
+            tx_hash,
+        });
+        if record_receipt(tx_hash, pending_tx.await?) {
+            say!("Token id {:?} transferred", id)
+        } else {
+            say!("Could not transfer token id {:?}", id)
         }
     }
 
-    println!("== Ownership after ==");
-    nft.log(&ids, args.recipient, opts.nft.erc1155).await?;
-
     Ok(())
 }
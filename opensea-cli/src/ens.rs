@@ -0,0 +1,41 @@
+//! ENS names in the flags taking an address, resolved through the provider, and in the
+//! addresses printed by the subcommands
+use ethers::{
+    providers::Middleware,
+    types::{Address, NameOrAddress},
+};
+use opensea::address_book;
+use std::str::FromStr;
+
+/// Parses a flag as an address, or as an ENS name if it is not one
+pub fn parse_name_or_address(s: &str) -> NameOrAddress {
+    match Address::from_str(s) {
+        Ok(address) => NameOrAddress::Address(address),
+        Err(_) => NameOrAddress::Name(s.to_owned()),
+    }
+}
+
+pub async fn resolve<M: Middleware>(
+    provider: &M,
+    name: &NameOrAddress,
+) -> color_eyre::Result<Address> {
+    match name {
+        NameOrAddress::Address(address) => Ok(*address),
+        NameOrAddress::Name(name) => provider
+            .resolve_name(name)
+            .await
+            .map_err(|err| color_eyre::eyre::eyre!("Could not resolve {}: {}", name, err)),
+    }
+}
+
+/// Formats the address along with its label in the address book, or else its ENS
+/// name, if it has a reverse record
+pub async fn display<M: Middleware>(provider: &M, address: Address) -> String {
+    if let Some(label) = address_book::label(address) {
+        return format!("{} ({:?})", label, address);
+    }
+    match provider.lookup_address(address).await {
+        Ok(name) => format!("{} ({:?})", name, address),
+        Err(_) => format!("{:?}", address),
+    }
+}
@@ -0,0 +1,119 @@
+//! The failures of the subcommands, each with its own exit code so that the scripts
+//! running the CLI can branch on them:
+//!
+//! | Code | Failure |
+//! |------|---------|
+//! | 1 | Any other error, e.g. invalid arguments or a node error |
+//! | 3 | The OpenSea API request failed |
+//! | 4 | No orders were found to fill |
+//! | 5 | The wallet cannot pay for the transactions |
+//! | 6 | The bundle(s) were not included |
+//! | 7 | The user declined the transactions, or interrupted the snipe |
+use color_eyre::Report;
+use opensea::{ClientError, OpenSeaApiError, SnipeError};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("OpenSea API failure: {0}")]
+    Api(Report),
+    #[error("No orders found: {0}")]
+    NoOrders(Report),
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(Report),
+    #[error("Bundle not included: {0}")]
+    NotIncluded(Report),
+    #[error("Aborted: {0}")]
+    Aborted(Report),
+    #[error("{0}")]
+    Other(Report),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Other(_) => 1,
+            CliError::Api(_) => 3,
+            CliError::NoOrders(_) => 4,
+            CliError::InsufficientBalance(_) => 5,
+            CliError::NotIncluded(_) => 6,
+            CliError::Aborted(_) => 7,
+        }
+    }
+
+    /// The underlying error, with its causes
+    pub fn report(&self) -> &Report {
+        match self {
+            CliError::Api(report)
+            | CliError::NoOrders(report)
+            | CliError::InsufficientBalance(report)
+            | CliError::NotIncluded(report)
+            | CliError::Aborted(report)
+            | CliError::Other(report) => report,
+        }
+    }
+}
+
+/// The variant of [`CliError`] wrapping the error
+type Variant = fn(Report) -> CliError;
+
+impl From<Report> for CliError {
+    /// Classifies the error returned by a subcommand. The errors of the library wrap
+    /// each other transparently, so they are matched explicitly rather than through
+    /// the chain of causes.
+    fn from(report: Report) -> Self {
+        let report = match report.downcast::<CliError>() {
+            Ok(err) => return err,
+            Err(report) => report,
+        };
+        let variant = if let Some(err) = report.downcast_ref::<SnipeError>() {
+            snipe_variant(err)
+        } else if let Some(err) = report.downcast_ref::<ClientError>() {
+            client_variant(err)
+        } else if let Some(err) = report.downcast_ref::<OpenSeaApiError>() {
+            api_variant(err)
+        } else if is_insufficient_funds(&report.to_string()) {
+            CliError::InsufficientBalance
+        } else {
+            CliError::Other
+        };
+        variant(report)
+    }
+}
+
+fn snipe_variant(err: &SnipeError) -> Variant {
+    match err {
+        SnipeError::Client(err) => client_variant(err),
+        SnipeError::OverBudget { .. } | SnipeError::InsufficientBalance { .. } => {
+            CliError::InsufficientBalance
+        }
+        SnipeError::NotIncluded(_) | SnipeError::Cancelled { .. } => CliError::NotIncluded,
+        SnipeError::Declined | SnipeError::Aborted { .. } => CliError::Aborted,
+        SnipeError::NoListings => CliError::NoOrders,
+        _ => CliError::Other,
+    }
+}
+
+fn client_variant(err: &ClientError) -> Variant {
+    match err {
+        ClientError::OpenSeaApiError(err) => api_variant(err),
+        ClientError::ListingUnavailable(_) => CliError::NoOrders,
+        ClientError::BudgetExceeded { .. } => CliError::InsufficientBalance,
+        ClientError::Provider(message) if is_insufficient_funds(message) => {
+            CliError::InsufficientBalance
+        }
+        _ => CliError::Other,
+    }
+}
+
+fn api_variant(err: &OpenSeaApiError) -> Variant {
+    match err {
+        OpenSeaApiError::OrderNotFound { .. } => CliError::NoOrders,
+        _ => CliError::Api,
+    }
+}
+
+/// Whether the node rejected a tx for its sender's balance
+fn is_insufficient_funds(message: &str) -> bool {
+    message.to_lowercase().contains("insufficient funds")
+}
@@ -0,0 +1,227 @@
+//! The `--nft.ids_path` file of the tokens to buy, quote or transfer. Either CSV rows of
+//! `id,quantity,max_price`, the last two optional, with an optional header and `#`
+//! comments:
+//!
+//! ```csv
+//! id,quantity,max_price
+//! 1
+//! 2,5,0.1
+//! 3,,0.25
+//! ```
+//!
+//! or, for a `.toml` file, a `[[tokens]]` table per token:
+//!
+//! ```toml
+//! [[tokens]]
+//! id = "2"
+//! quantity = 5
+//! max_price = "0.1"
+//! ```
+//!
+//! The ids listed twice are only kept once, with a warning.
+use ethers::types::U256;
+use opensea::say;
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+/// A token of the `--nft.address` collection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenEntry {
+    pub id: U256,
+    /// 1 unless given, the quantity of ERC1155 tokens
+    pub quantity: usize,
+    /// The max price of the token (in Wei), overriding `--max_price`
+    pub max_price: Option<U256>,
+}
+
+impl TokenEntry {
+    pub fn new(id: U256) -> Self {
+        Self {
+            id,
+            quantity: 1,
+            max_price: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlIds {
+    tokens: Vec<TomlToken>,
+}
+
+/// Numbers as strings, since token ids may not fit TOML integers
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlToken {
+    id: String,
+    quantity: Option<usize>,
+    max_price: Option<String>,
+}
+
+/// Reads the tokens of the ids file, as CSV unless it has a `.toml` extension
+pub fn read_ids(path: &Path) -> color_eyre::Result<Vec<TokenEntry>> {
+    let contents = std::fs::read_to_string(path)?;
+    let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+    let entries = if is_toml {
+        parse_toml(&contents).map_err(|err| format!("{}: {}", path.display(), err))
+    } else {
+        // prefixed with the line number, as in `ids.csv:3: invalid token id`
+        parse_csv(&contents).map_err(|err| format!("{}:{}", path.display(), err))
+    }
+    .map_err(|err| color_eyre::eyre::eyre!(err))?;
+    Ok(dedupe(entries))
+}
+
+/// The entries with the line (or for TOML the table) each comes from
+fn parse_csv(contents: &str) -> Result<Vec<(String, TokenEntry)>, String> {
+    let mut entries = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let columns = line.split(',').map(str::trim).collect::<Vec<_>>();
+        if entries.is_empty() && columns[0].eq_ignore_ascii_case("id") {
+            // the header
+            continue;
+        }
+        let entry = match columns.as_slice() {
+            [id] => parse_entry(id, None, None),
+            [id, quantity] => parse_entry(id, Some(quantity), None),
+            [id, quantity, max_price] => parse_entry(id, Some(quantity), Some(max_price)),
+            _ => Err(format!(
+                "expected `id,quantity,max_price`, found {} columns",
+                columns.len()
+            )),
+        }
+        .map_err(|err| format!("{}: {}", line_number, err))?;
+        entries.push((format!("line {}", line_number), entry));
+    }
+    Ok(entries)
+}
+
+fn parse_toml(contents: &str) -> Result<Vec<(String, TokenEntry)>, String> {
+    let ids: TomlIds = toml::from_str(contents).map_err(|err| err.to_string())?;
+    ids.tokens
+        .iter()
+        .enumerate()
+        .map(|(idx, token)| {
+            let quantity = token.quantity.map(|quantity| quantity.to_string());
+            parse_entry(&token.id, quantity.as_deref(), token.max_price.as_deref())
+                .map(|entry| (format!("tokens[{}]", idx), entry))
+                .map_err(|err| format!("tokens[{}]: {}", idx, err))
+        })
+        .collect()
+}
+
+/// Empty quantities and max prices are left to their defaults
+fn parse_entry(
+    id: &str,
+    quantity: Option<&str>,
+    max_price: Option<&str>,
+) -> Result<TokenEntry, String> {
+    let mut entry =
+        TokenEntry::new(U256::from_dec_str(id).map_err(|_| format!("invalid token id `{}`", id))?);
+    if let Some(quantity) = quantity.filter(|quantity| !quantity.is_empty()) {
+        entry.quantity = match usize::from_str(quantity) {
+            Ok(0) | Err(_) => return Err(format!("invalid quantity `{}`", quantity)),
+            Ok(quantity) => quantity,
+        };
+    }
+    if let Some(max_price) = max_price.filter(|price| !price.is_empty()) {
+        entry.max_price = Some(
+            crate::opts::parse_eth(max_price)
+                .map_err(|err| format!("invalid max price `{}`: {}", max_price, err))?,
+        );
+    }
+    Ok(entry)
+}
+
+/// Keeps the first entry of each id, warning about the others
+fn dedupe(entries: Vec<(String, TokenEntry)>) -> Vec<TokenEntry> {
+    let mut first = HashMap::new();
+    let mut tokens = Vec::new();
+    for (location, entry) in entries {
+        match first.get(&entry.id) {
+            Some(first) => say!(
+                "Warning: token id {} is listed again on {} (first on {}), ignoring it",
+                entry.id,
+                location,
+                first
+            ),
+            None => {
+                first.insert(entry.id, location);
+                tokens.push(entry);
+            }
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(contents: &str) -> Result<Vec<TokenEntry>, String> {
+        parse_csv(contents).map(dedupe)
+    }
+
+    #[test]
+    fn skips_the_header_and_comments() {
+        let contents = "# the targets\nid,quantity,max_price\n\n1 # the first\n2,5\n";
+        let tokens = parsed(contents).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenEntry::new(1.into()),
+                TokenEntry {
+                    quantity: 5,
+                    ..TokenEntry::new(2.into())
+                },
+            ]
+        );
+        // only the first row can be a header
+        assert_eq!(
+            parsed("1\nid").unwrap_err(),
+            "2: invalid token id `id`".to_owned()
+        );
+    }
+
+    #[test]
+    fn prefixes_the_errors_with_their_line() {
+        let contents = "id,quantity\n1\n\n3,0\n";
+        assert_eq!(
+            parsed(contents).unwrap_err(),
+            "4: invalid quantity `0`".to_owned()
+        );
+        assert_eq!(
+            parsed("1,1,0.1,2").unwrap_err(),
+            "1: expected `id,quantity,max_price`, found 4 columns".to_owned()
+        );
+        assert!(parsed("1\n2,,abc")
+            .unwrap_err()
+            .starts_with("2: invalid max price `abc`"));
+    }
+
+    #[test]
+    fn reads_the_max_price_of_each_id() {
+        let tokens = parsed("1,,0.25\n2\n3,2,1").unwrap();
+        let max_prices: Vec<_> = tokens.iter().map(|token| token.max_price).collect();
+        assert_eq!(
+            max_prices,
+            vec![Some(U256::exp10(17) * 25 / 10), None, Some(U256::exp10(18))]
+        );
+        assert_eq!(tokens[2].quantity, 2);
+    }
+
+    #[test]
+    fn keeps_the_first_entry_of_each_id() {
+        let tokens = parsed("1,,0.1\n2\n1,,0.2\n").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].id, 1.into());
+        assert_eq!(tokens[0].max_price, Some(U256::exp10(17)));
+        assert_eq!(tokens[1].id, 2.into());
+    }
+}
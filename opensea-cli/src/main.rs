@@ -1,14 +1,50 @@
 use structopt::StructOpt;
 
+mod briber;
+
+mod config;
+
+mod ens;
+
+mod error;
+use error::CliError;
+
+mod ids;
+
 mod opts;
 use opts::{Opts, Subcommands};
 
+mod output;
+use output::Record;
+
 pub mod contracts;
-use contracts::{buy, deploy, prices};
+use contracts::{buy, cancel, deploy, list, transfer};
+
+mod quote;
+use quote::quote;
+
+mod replay;
+use replay::replay;
+
+mod watch;
+use watch::watch;
 
 #[tokio::main]
-async fn main() -> color_eyre::Result<()> {
-    let opts = Opts::from_args();
+async fn main() {
+    if let Err(err) = run().await {
+        let err = CliError::from(err);
+        eprintln!("Error: {:?}", err.report());
+        output::record(&Record::Error {
+            message: err.report().to_string(),
+            exit_code: err.exit_code(),
+        });
+        std::process::exit(err.exit_code());
+    }
+}
+
+async fn run() -> color_eyre::Result<()> {
+    let opts = Opts::from_iter(config::args()?);
+    output::init(opts.sub.output_mode())?;
     match opts.sub {
         Subcommands::Buy(inner) => {
             buy(inner).await?;
@@ -16,8 +52,23 @@ async fn main() -> color_eyre::Result<()> {
         Subcommands::Deploy(inner) => {
             deploy(inner).await?;
         }
-        Subcommands::Prices(inner) => {
-            prices(inner.nft).await?;
+        Subcommands::Quote(inner) => {
+            quote(inner).await?;
+        }
+        Subcommands::Cancel(inner) => {
+            cancel(inner).await?;
+        }
+        Subcommands::List(inner) => {
+            list(inner).await?;
+        }
+        Subcommands::Transfer(inner) => {
+            transfer(inner).await?;
+        }
+        Subcommands::Replay(inner) => {
+            replay(inner).await?;
+        }
+        Subcommands::Watch(inner) => {
+            watch(inner).await?;
         }
     };
 
@@ -1,22 +1,72 @@
+use eth_keystore::KeystoreError;
 use ethers::types::{Address, U256};
 use ethers::{prelude::*, signers::coins_bip39::English};
+use opensea::{
+    api::{KeyRotation, OpenSeaApiConfig},
+    types::Network,
+    units, Atomicity, Escalator, FeeHistoryEstimator, Fees, FixedFees, OpenSeaApi, SafeMode,
+    SnipeTarget, SwapConfig,
+};
+
+use crate::{
+    ens::{self, parse_name_or_address},
+    ids::{self, TokenEntry},
+    output::OutputMode,
+    quote::OutputFormat,
+};
+use std::collections::HashSet;
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use structopt::StructOpt;
+use url::Url;
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "Choose what NFT subcommand you want to execute")]
+#[allow(clippy::large_enum_variant)]
 pub enum Subcommands {
     Buy(BuyOpts),
     Deploy(DeployOpts),
-    Prices(PricesOpts),
+    #[structopt(alias = "prices")]
+    Quote(QuoteOpts),
+    Cancel(CancelOpts),
+    List(ListOpts),
+    Transfer(TransferOpts),
+    Replay(ReplayOpts),
+    Watch(WatchOpts),
+}
+
+impl Subcommands {
+    /// The `--output` mode of the subcommand. `quote` has its own `--format`.
+    pub fn output_mode(&self) -> OutputMode {
+        match self {
+            Subcommands::Buy(opts) => opts.output.mode,
+            Subcommands::Deploy(opts) => opts.output.mode,
+            Subcommands::Cancel(opts) => opts.output.mode,
+            Subcommands::List(opts) => opts.output.mode,
+            Subcommands::Transfer(opts) => opts.output.mode,
+            Subcommands::Quote(_) | Subcommands::Replay(_) | Subcommands::Watch(_) => {
+                OutputMode::Text
+            }
+        }
+    }
 }
 
 #[derive(Debug, StructOpt)]
 pub struct Opts {
     #[structopt(subcommand)]
     pub sub: Subcommands,
+
+    /// Only declared for `--help`, the config is loaded by `config::args` before the
+    /// args are parsed
+    #[allow(dead_code)]
+    #[structopt(
+        long,
+        global = true,
+        help = "Config file holding defaults for the flags, see `config.rs`. Defaults to ./opensea.toml if it exists"
+    )]
+    pub config: Option<PathBuf>,
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -24,18 +74,48 @@ pub struct EthereumOpts {
     #[structopt(long = "eth.url", short, help = "The tracing / archival node's URL")]
     pub url: String,
 
+    #[structopt(
+        long = "eth.ws_url",
+        help = "The node's websocket URL. When buying, the transfers of the tokens to the recipient are subscribed to, to confirm the purchases as soon as they land"
+    )]
+    pub ws_url: Option<String>,
+
     #[structopt(long = "eth.private_key", help = "Your private key string")]
     pub private_key: Option<String>,
 
     #[structopt(long = "eth.mnemonic", help = "Path to your mnemonic file")]
     pub mnemonic_path: Option<String>,
 
+    #[structopt(
+        long = "eth.keystore",
+        help = "Path to your encrypted JSON keystore. Its password is read from the ETH_KEYSTORE_PASSWORD env var, or prompted for"
+    )]
+    pub keystore: Option<PathBuf>,
+
     #[structopt(
         long = "eth.hd_index",
         help = "your index in the standard hd path",
         default_value = "0"
     )]
     pub index: u32,
+
+    #[structopt(
+        long = "eth.ledger",
+        help = "Sign with a Ledger, at the `--eth.hd_path` or the Ledger Live path of `--eth.hd_index`. Requires the `ledger` feature"
+    )]
+    pub ledger: bool,
+
+    #[structopt(
+        long = "eth.hd_path",
+        help = "The full derivation path of the Ledger account, e.g. m/44'/60'/0'/0/0"
+    )]
+    pub hd_path: Option<String>,
+
+    #[structopt(
+        long = "eth.aws_kms_key_id",
+        help = "Sign with this AWS KMS key, in the region of the AWS_DEFAULT_REGION env var. Requires the `aws` feature"
+    )]
+    pub aws_kms_key_id: Option<String>,
 }
 
 // TODO: Improve these so that we return a middleware trait object
@@ -45,7 +125,18 @@ impl EthereumOpts {
         Ok(Arc::new(Provider::try_from(self.url.as_str())?))
     }
 
-    /// Returns a [`LocalWallet`] corresponding to the provided private key or mnemonic
+    /// Connects to the `--eth.ws_url`, if any
+    pub async fn ws_provider(&self) -> color_eyre::Result<Option<Arc<Provider<Ws>>>> {
+        match self.ws_url {
+            Some(ref url) => Ok(Some(Arc::new(Provider::new(
+                Ws::connect(url.as_str()).await?,
+            )))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a [`LocalWallet`] corresponding to the provided private key, mnemonic or
+    /// keystore
     pub fn signer(&self) -> color_eyre::Result<LocalWallet> {
         if let Some(ref private_key) = self.private_key {
             Ok(LocalWallet::from_str(private_key)?)
@@ -55,81 +146,616 @@ impl EthereumOpts {
                 .phrase(mnemonic.as_str())
                 .index(self.index)?
                 .build()?)
+        } else if let Some(ref keystore) = self.keystore {
+            decrypt_keystore(keystore)
         } else {
-            panic!("Expected mnemonic or private key");
+            color_eyre::eyre::bail!(
+                "Pass --eth.private_key, --eth.mnemonic or --eth.keystore to sign the transactions"
+            );
         }
     }
+
+    /// Returns the Ledger signer of the configured derivation path
+    #[cfg(feature = "ledger")]
+    pub async fn ledger(&self, chain_id: u64) -> color_eyre::Result<Ledger> {
+        let derivation = match self.hd_path {
+            Some(ref path) => HDPath::Other(path.clone()),
+            None => HDPath::LedgerLive(self.index as usize),
+        };
+        Ok(Ledger::new(derivation, chain_id).await?)
+    }
+
+    /// Returns the signer of the configured AWS KMS key. The KMS client is leaked, as
+    /// the signer borrows it for the lifetime of the process.
+    #[cfg(feature = "aws")]
+    pub async fn aws_signer(
+        &self,
+        chain_id: u64,
+    ) -> color_eyre::Result<Option<AwsSigner<'static>>> {
+        let key_id = match self.aws_kms_key_id {
+            Some(ref key_id) => key_id,
+            None => return Ok(None),
+        };
+        let kms = Box::leak(Box::new(rusoto_kms::KmsClient::new(
+            rusoto_core::Region::default(),
+        )));
+        Ok(Some(AwsSigner::new(kms, key_id, chain_id).await?))
+    }
+
+    /// Errors if a signer was selected whose feature was not compiled in
+    pub fn check_signer_features(&self) -> color_eyre::Result<()> {
+        if self.ledger && !cfg!(feature = "ledger") {
+            color_eyre::eyre::bail!("--eth.ledger requires building with the `ledger` feature");
+        }
+        if self.aws_kms_key_id.is_some() && !cfg!(feature = "aws") {
+            color_eyre::eyre::bail!(
+                "--eth.aws_kms_key_id requires building with the `aws` feature"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The env var holding the keystore password, which is prompted for otherwise
+const KEYSTORE_PASSWORD_ENV: &str = "ETH_KEYSTORE_PASSWORD";
+
+fn decrypt_keystore(path: &Path) -> color_eyre::Result<LocalWallet> {
+    if !path.is_file() {
+        color_eyre::eyre::bail!("Keystore {} not found", path.display());
+    }
+    let password = match std::env::var(KEYSTORE_PASSWORD_ENV) {
+        Ok(password) => password,
+        Err(_) => rpassword::prompt_password(format!("Password of {}: ", path.display()))?,
+    };
+    LocalWallet::decrypt_keystore(path, password).map_err(|err| match err {
+        WalletError::EthKeystoreError(KeystoreError::MacMismatch) => {
+            color_eyre::eyre::eyre!("Wrong password for keystore {}", path.display())
+        }
+        err => color_eyre::eyre::eyre!("Could not decrypt keystore {}: {}", path.display(), err),
+    })
 }
 
 #[derive(StructOpt, Debug, Clone)]
 pub struct FlashBotsOpts {
     #[structopt(
         long = "flashbots.bribe_receiver",
-        help = "The address that will receive the bribe. Ideally it should be a smart contract with a block.coinbase transfer"
+        help = "The address or ENS name that will receive the bribe. Ideally it should be a smart contract with a block.coinbase transfer",
+        parse(from_str = parse_name_or_address)
     )]
-    pub bribe_receiver: Option<Address>,
+    pub bribe_receiver: Option<NameOrAddress>,
 
-    #[structopt(long = "flashbots.bribe", parse(from_str = parse_u256), help = "The amount to be sent to the miner")]
+    #[structopt(long = "flashbots.bribe", parse(try_from_str = parse_eth), help = "The amount to be sent to the miner, in ETH (e.g. `0.01`)")]
     pub bribe: Option<U256>,
+
+    #[structopt(
+        long = "flashbots.resale_value",
+        parse(try_from_str = parse_eth),
+        help = "The expected resale value of all the purchases (e.g. the floor price times the number of tokens), in ETH. Sizes the priority fee to leave `--flashbots.margin_bps` of it as profit, instead of `--flashbots.bribe`"
+    )]
+    pub resale_value: Option<U256>,
+
+    #[structopt(
+        long = "flashbots.margin_bps",
+        help = "The profit margin left by `--flashbots.resale_value`, in basis points of the resale value",
+        default_value = "1000"
+    )]
+    pub margin_bps: u64,
+
+    #[structopt(
+        long = "flashbots.relay",
+        help = "The URL of a relay / builder to send the bundle to. Can be repeated to send it to several builders concurrently, the first one is also used for simulating it",
+        default_value = "https://relay.flashbots.net"
+    )]
+    pub relays: Vec<Url>,
+
+    #[structopt(
+        long = "flashbots.target_blocks",
+        alias = "target-blocks",
+        help = "The number of consecutive blocks to target with the bundle until it is included",
+        default_value = "5"
+    )]
+    pub target_blocks: u64,
+
+    #[structopt(
+        long = "flashbots.atomicity",
+        help = "Whether the purchases are sent in a single bundle (`all-or-nothing`), a bundle each (`best-effort`) or bundles of up to n purchases (`chunked:<n>`). Split bundles cannot pay a contract bribe",
+        default_value = "all-or-nothing"
+    )]
+    pub atomicity: Atomicity,
+
+    #[structopt(
+        long = "flashbots.watch_cancellations",
+        help = "Watch the pending txs for cancellations of the listings being bought and replace their bundles without them"
+    )]
+    pub watch_cancellations: bool,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct ApiOpts {
+    #[structopt(
+        long = "api.key",
+        help = "Your OpenSea API key. Can be repeated to rotate several keys, switching to the next one when a request is throttled"
+    )]
+    pub keys: Vec<String>,
+
+    #[structopt(
+        long = "api.key_rotation",
+        help = "How several API keys are rotated: round-robin, or least-recently-throttled to use a key until it is throttled",
+        default_value = "round-robin",
+        possible_values = &["round-robin", "least-recently-throttled"]
+    )]
+    pub key_rotation: KeyRotation,
+
+    #[structopt(
+        long = "api.timeout_ms",
+        help = "Timeout of each OpenSea API request, in milliseconds"
+    )]
+    pub timeout_ms: Option<u64>,
+
+    #[structopt(
+        long = "api.connect_timeout_ms",
+        help = "Timeout for connecting to the OpenSea API, in milliseconds"
+    )]
+    pub connect_timeout_ms: Option<u64>,
+
+    #[structopt(
+        long = "api.proxy",
+        help = "HTTP or SOCKS5 proxy for the OpenSea API requests, e.g. socks5://host:port"
+    )]
+    pub proxy: Option<Url>,
+
+    #[structopt(
+        long = "api.base_url",
+        help = "Host of the OpenSea API requests instead of the network's, e.g. a caching proxy"
+    )]
+    pub base_url: Option<Url>,
+
+    #[structopt(
+        long = "api.orderbook_url",
+        help = "Host of the OpenSea orderbook requests instead of the network's"
+    )]
+    pub orderbook_url: Option<Url>,
+
+    #[structopt(
+        long = "api.pool_max_idle_per_host",
+        help = "The most idle connections to the OpenSea API kept open for reuse"
+    )]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    #[structopt(
+        long = "api.http2_prior_knowledge",
+        help = "Speak HTTP/2 to the OpenSea API without negotiating it first"
+    )]
+    pub http2_prior_knowledge: bool,
+
+    #[structopt(
+        long = "api.tcp_keepalive_ms",
+        help = "Interval of the TCP keepalive probes of the OpenSea API connections, in milliseconds"
+    )]
+    pub tcp_keepalive_ms: Option<u64>,
+
+    #[structopt(
+        long = "api.network",
+        default_value = "mainnet",
+        help = "The network of the orders and of the provider: mainnet, rinkeby, arbitrum or optimism"
+    )]
+    pub network: Network,
+}
+
+impl ApiOpts {
+    pub fn config(&self) -> OpenSeaApiConfig {
+        OpenSeaApiConfig {
+            api_key: None,
+            api_keys: self.keys.clone(),
+            key_rotation: self.key_rotation,
+            timeout: self.timeout_ms.map(Duration::from_millis),
+            connect_timeout: self.connect_timeout_ms.map(Duration::from_millis),
+            proxy: self.proxy.clone(),
+            api_base: self.base_url.clone(),
+            orderbook_base: self.orderbook_url.clone(),
+            pool_max_idle_per_host: self.pool_max_idle_per_host,
+            http2_prior_knowledge: self.http2_prior_knowledge,
+            tcp_keepalive: self.tcp_keepalive_ms.map(Duration::from_millis),
+            network: self.network,
+            // the CLI only uses the parsed fields
+            keep_raw: false,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct EventsOpts {
+    #[structopt(
+        long = "events.socket",
+        help = "Unix socket of an external risk system to which each action is reported as a JSON line"
+    )]
+    pub socket: Option<PathBuf>,
+
+    #[structopt(
+        long = "events.ack_timeout_ms",
+        help = "If set, wait this long for the risk system to approve or veto each action"
+    )]
+    pub ack_timeout_ms: Option<u64>,
 }
 
 #[derive(StructOpt, Debug, Clone)]
-#[structopt(about = "Get OpenSea orderbook information about the token")]
-pub struct PricesOpts {
+pub struct OutputOpts {
+    #[structopt(
+        long = "output",
+        help = "text, or json to write the events and results to stdout as JSON lines, the other messages going to stderr",
+        default_value = "text",
+        possible_values = &["text", "json"]
+    )]
+    pub mode: OutputMode,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct ReplayOpts {
+    #[structopt(
+        help = "The directory of a run recorded with `buy --record`, whose listings are selected again against the recorded API responses"
+    )]
+    pub dir: PathBuf,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(
+    about = "Stream the moves of the cheapest listing of each token of the collection, with the time left until it expires"
+)]
+pub struct WatchOpts {
     #[structopt(flatten)]
     pub nft: NftOpts,
+
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+
+    #[structopt(
+        long,
+        help = "Poll the listings every this many seconds. The listings are also dropped the moment they expire, and their replacements queried",
+        default_value = "10"
+    )]
+    pub interval: u64,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct SafeOpts {
+    #[structopt(
+        long = "safe.allow_collection",
+        help = "Only buy from this collection. Can be repeated"
+    )]
+    pub allow_collections: Vec<Address>,
+
+    #[structopt(
+        long = "safe.deny_collection",
+        help = "Never buy from this collection. Can be repeated"
+    )]
+    pub deny_collections: Vec<Address>,
+
+    #[structopt(
+        long = "safe.allow_maker",
+        help = "Only buy the listings of this maker. Can be repeated"
+    )]
+    pub allow_makers: Vec<Address>,
+
+    #[structopt(
+        long = "safe.deny_maker",
+        help = "Never buy the listings of this maker. Can be repeated"
+    )]
+    pub deny_makers: Vec<Address>,
+}
+
+impl SafeOpts {
+    /// The lists enforced on the listings, an empty allowlist allowing everything
+    pub fn safe_mode(&self) -> SafeMode {
+        let allowlist = |addresses: &Vec<Address>| {
+            Some(addresses.iter().copied().collect()).filter(|set: &HashSet<_>| !set.is_empty())
+        };
+        SafeMode {
+            allowed_collections: allowlist(&self.allow_collections),
+            denied_collections: self.deny_collections.iter().copied().collect(),
+            allowed_makers: allowlist(&self.allow_makers),
+            denied_makers: self.deny_makers.iter().copied().collect(),
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct FeeOpts {
+    #[structopt(
+        long = "fees.history",
+        help = "Estimate the fees via eth_feeHistory instead of projecting the latest basefee"
+    )]
+    pub history: bool,
+
+    #[structopt(
+        long = "fees.no_history",
+        help = "Project the latest basefee even if the config file sets `fees.history`",
+        conflicts_with = "history"
+    )]
+    pub no_history: bool,
+
+    #[structopt(
+        long = "fees.history_blocks",
+        help = "The number of past blocks to query the fee history of",
+        default_value = "10"
+    )]
+    pub history_blocks: u64,
+
+    #[structopt(
+        long = "fees.percentile",
+        help = "The percentile of the priority fees paid in each past block to use",
+        default_value = "50"
+    )]
+    pub percentile: f64,
+
+    #[structopt(
+        long = "fees.max_base_fee",
+        parse(try_from_str = parse_u256),
+        help = "Override the estimation with a fixed max basefee (in Wei)"
+    )]
+    pub max_base_fee: Option<U256>,
+
+    #[structopt(
+        long = "fees.priority_fee",
+        parse(try_from_str = parse_u256),
+        help = "The priority fee (in Wei) paid along with a fixed max basefee",
+        default_value = "0"
+    )]
+    pub priority_fee: U256,
+
+    #[structopt(
+        long = "fees.escalate_blocks",
+        requires = "max-gas-price",
+        help = "Rebroadcast the transactions still pending every this many blocks with a bumped gas price (non-Flashbots only)"
+    )]
+    pub escalate_blocks: Option<u64>,
+
+    #[structopt(
+        long = "fees.escalate_percent",
+        help = "The percentage by which the gas price is bumped at each rebroadcast, at least 10",
+        default_value = "12"
+    )]
+    pub escalate_percent: u64,
+
+    #[structopt(
+        long = "fees.max_gas_price",
+        parse(try_from_str = parse_u256),
+        help = "The gas price (in Wei) the transactions are never rebroadcast above"
+    )]
+    pub max_gas_price: Option<U256>,
+}
+
+impl FeeOpts {
+    /// Returns the fixed fees, if overridden
+    pub fn fixed(&self) -> Option<FixedFees> {
+        self.max_base_fee.map(|max_base_fee| {
+            FixedFees(Fees {
+                max_base_fee,
+                max_priority_fee_per_gas: self.priority_fee,
+            })
+        })
+    }
+
+    /// Returns the escalation policy of the pending transactions, if enabled
+    pub fn escalator(&self) -> Option<Escalator> {
+        Some(Escalator::new(
+            self.escalate_blocks?,
+            self.escalate_percent,
+            self.max_gas_price?,
+        ))
+    }
+
+    pub fn history(&self) -> FeeHistoryEstimator {
+        FeeHistoryEstimator {
+            history_blocks: self.history_blocks,
+            percentile: self.percentile,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct SwapOpts {
+    #[structopt(
+        long = "swap",
+        help = "Swap ETH for the listings' ERC20 payment tokens via Uniswap V3 if the wallet lacks them"
+    )]
+    pub enabled: bool,
+
+    #[structopt(
+        long = "swap.fee",
+        help = "The fee tier of the Uniswap V3 WETH pool to swap through",
+        default_value = "3000"
+    )]
+    pub pool_fee: u32,
+
+    #[structopt(
+        long = "swap.slippage_bps",
+        help = "The maximum slippage over the quoted ETH amount, in basis points",
+        default_value = "50"
+    )]
+    pub slippage_bps: u64,
+}
+
+impl SwapOpts {
+    /// Returns the swap configuration, if swapping is enabled
+    pub fn config(&self) -> Option<SwapConfig> {
+        if !self.enabled {
+            return None;
+        }
+        Some(SwapConfig {
+            pool_fee: self.pool_fee,
+            slippage_bps: self.slippage_bps,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Get OpenSea orderbook statistics about the token(s)")]
+pub struct QuoteOpts {
+    #[structopt(flatten)]
+    pub nft: NftOpts,
+
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+
+    #[structopt(
+        long,
+        help = "Also query the offers (buy orders) side of the orderbook"
+    )]
+    pub offers: bool,
+
+    #[structopt(
+        long,
+        help = "Query the orders of 30 tokens per request, several requests at once, for large collections. The orders of a request are paged through"
+    )]
+    pub bulk: bool,
+
+    #[structopt(
+        long,
+        help = "The max number of batches of tokens in flight with --bulk",
+        default_value = "4"
+    )]
+    pub concurrency: usize,
+
+    #[structopt(
+        long,
+        help = "The max number of API requests per second with --bulk, 0 for no limit. The requests are also held once the quota of the API key is nearly exhausted",
+        default_value = "4"
+    )]
+    pub requests_per_sec: f64,
+
+    #[structopt(
+        long,
+        help = "The output format: table, csv or json",
+        default_value = "table",
+        possible_values = &["table", "csv", "json"]
+    )]
+    pub format: OutputFormat,
+
+    #[structopt(
+        long,
+        help = "Also export the listings of the whole collection to this file, as JSON with `--format json` and as CSV otherwise"
+    )]
+    pub output: Option<PathBuf>,
 }
 
 #[derive(StructOpt, Debug, Clone)]
 pub struct NftOpts {
     #[structopt(
-        long = "nft.erc1155",
+        long = "nft.address",
         short,
-        help = "Whether the token you chose is an ERC1155 token, so that we make the correct ownership call check"
+        help = "The NFT address (or ENS name) you want to buy. Optional when buying from a --targets file",
+        parse(from_str = parse_name_or_address)
     )]
-    pub erc1155: bool,
+    pub address: Option<NameOrAddress>,
 
-    #[structopt(long = "nft.address", short, help = "The NFT address you want to buy")]
-    pub address: Address,
+    #[structopt(
+        long = "nft.collection",
+        conflicts_with = "address",
+        help = "The OpenSea slug of the collection (e.g. boredapeyachtclub), resolved to its contract instead of passing --nft.address"
+    )]
+    pub collection: Option<String>,
 
-    #[structopt(long = "nft.ids", help = "The NFT id(s) you want to buy", parse(from_str = parse_u256))]
+    #[structopt(long = "nft.ids", help = "The NFT id(s) you want to buy", parse(try_from_str = parse_u256))]
     pub ids: Vec<U256>,
 
     #[structopt(
         long = "nft.ids_path",
-        help = "The file containing the NFT id(s) you want to buy"
+        help = "The file of the NFT id(s) you want to buy, as CSV rows of `id,quantity,max_price` (the last two optional) or as TOML `[[tokens]]` tables for a .toml file"
     )]
     pub ids_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "nft.erc1155",
+        help = "Treat the collection as an ERC1155 instead of detecting its standard via ERC165, e.g. if it does not implement supportsInterface"
+    )]
+    pub erc1155: bool,
 }
 
 use std::fs::File;
 use std::io::BufRead;
 impl NftOpts {
+    /// The address, for the subcommands which are not connected to a node to resolve
+    /// ENS names
+    pub async fn address(&self, api: &OpenSeaApi) -> color_eyre::Result<Address> {
+        if let Some(address) = self.collection_address(Some(api)).await? {
+            return Ok(address);
+        }
+        match self.address {
+            Some(NameOrAddress::Address(address)) => Ok(address),
+            Some(NameOrAddress::Name(ref name)) => Err(color_eyre::eyre::eyre!(
+                "Invalid --nft.address {}, ENS names are not supported by this subcommand",
+                name
+            )),
+            None => Err(color_eyre::eyre::eyre!(
+                "--nft.address or --nft.collection is required"
+            )),
+        }
+    }
+
+    /// The address, resolved through the provider if it is an ENS name, or through the
+    /// `api` if it is given as a collection slug
+    pub async fn resolve_address<M: Middleware>(
+        &self,
+        provider: &M,
+        api: Option<&OpenSeaApi>,
+    ) -> color_eyre::Result<Address> {
+        if let Some(address) = self.collection_address(api).await? {
+            return Ok(address);
+        }
+        match self.address {
+            Some(ref name) => ens::resolve(provider, name).await,
+            None => Err(color_eyre::eyre::eyre!(
+                "--nft.address or --nft.collection is required"
+            )),
+        }
+    }
+
+    /// The contract of the `--nft.collection`, if given. Collections with several
+    /// contracts must be passed by address instead.
+    async fn collection_address(
+        &self,
+        api: Option<&OpenSeaApi>,
+    ) -> color_eyre::Result<Option<Address>> {
+        let slug = match self.collection {
+            Some(ref slug) => slug,
+            None => return Ok(None),
+        };
+        let api = api.ok_or_else(|| {
+            color_eyre::eyre::eyre!("--nft.collection is not supported by this subcommand")
+        })?;
+        let contracts = api.resolve_collection(slug).await?;
+        match contracts.as_slice() {
+            [address] => Ok(Some(*address)),
+            [] => Err(color_eyre::eyre::eyre!(
+                "Collection {} has no contract on the network",
+                slug
+            )),
+            _ => Err(color_eyre::eyre::eyre!(
+                "Collection {} has several contracts ({:?}), pass one as --nft.address",
+                slug,
+                contracts
+            )),
+        }
+    }
+
+    /// The tokens of the `--nft.ids_path` file if given, else of the `--nft.ids`, see
+    /// [`ids`](crate::ids) for the format of the file
+    pub fn token_entries(&self) -> color_eyre::Result<Vec<TokenEntry>> {
+        match self.ids_path {
+            Some(ref ids_path) => ids::read_ids(ids_path),
+            // assume 1 copy of each token if given via the cli
+            None => Ok(self.ids.iter().copied().map(TokenEntry::new).collect()),
+        }
+    }
+
     /// Returns a vector of token ids and quantities to check for
     pub fn tokens(&self) -> color_eyre::Result<(Vec<U256>, Vec<usize>)> {
-        // read from a csv if a file is given
-        Ok(if let Some(ref ids_path) = self.ids_path {
-            let file = File::open(ids_path)?;
-            let lines = std::io::BufReader::new(file).lines();
-            let mut ids = Vec::new();
-            let mut quantities = Vec::new();
-            for line in lines {
-                let line = line?;
-                let mut line = line.split(',');
-                let id = line.next().expect("no id found");
-                let id = U256::from_dec_str(id)?;
-                let quantity = match line.next() {
-                    Some(inner) => usize::from_str(inner).unwrap_or(1),
-                    None => 1,
-                };
-                ids.push(id);
-                quantities.push(quantity);
-            }
-            (ids, quantities)
-        } else {
-            // assume 1 copy of each token if given via the cli
-            (self.ids.clone(), vec![1; self.ids.len()])
-        })
+        Ok(self
+            .token_entries()?
+            .into_iter()
+            .map(|entry| (entry.id, entry.quantity))
+            .unzip())
     }
 }
 
@@ -140,6 +766,161 @@ impl NftOpts {
 pub struct DeployOpts {
     #[structopt(flatten)]
     pub eth: EthereumOpts,
+
+    #[structopt(flatten)]
+    pub output: OutputOpts,
+
+    #[structopt(
+        long = "briber-address",
+        help = "A previously deployed bribe contract (address or ENS name), which is reused instead of deploying a new one",
+        parse(from_str = parse_name_or_address)
+    )]
+    pub briber_address: Option<NameOrAddress>,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Cancel your listings on-chain")]
+pub struct CancelOpts {
+    #[structopt(flatten)]
+    pub eth: EthereumOpts,
+
+    #[structopt(flatten)]
+    pub output: OutputOpts,
+
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+
+    #[structopt(
+        long = "nft.address",
+        short,
+        help = "The NFT address (or ENS name) of the listings",
+        parse(from_str = parse_name_or_address)
+    )]
+    pub address: NameOrAddress,
+
+    #[structopt(long = "order-hash", help = "The hash(es) of the listings to cancel")]
+    pub order_hashes: Vec<H256>,
+
+    #[structopt(
+        long = "token-id",
+        help = "Cancel all your listings of these token id(s)",
+        parse(try_from_str = parse_u256)
+    )]
+    pub token_ids: Vec<U256>,
+
+    #[structopt(
+        long = "skip_health_check",
+        help = "Do not check the node, the exchange contract, the API and the balance before starting"
+    )]
+    pub skip_health_check: bool,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Sign a fixed price listing and post it to the orderbook")]
+pub struct ListOpts {
+    #[structopt(flatten)]
+    pub eth: EthereumOpts,
+
+    #[structopt(flatten)]
+    pub output: OutputOpts,
+
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+
+    #[structopt(
+        long = "nft.address",
+        short,
+        help = "The NFT address (or ENS name, unless --offline) to list",
+        parse(from_str = parse_name_or_address)
+    )]
+    pub address: NameOrAddress,
+
+    #[structopt(long = "nft.id", help = "The NFT id to list", parse(try_from_str = parse_u256))]
+    pub id: U256,
+
+    #[structopt(
+        long,
+        help = "Whether you're listing an ERC721 or an ERC1155 (true for 1155)"
+    )]
+    pub erc1155: bool,
+
+    #[structopt(
+        long,
+        parse(try_from_str = parse_u256),
+        default_value = "1",
+        help = "The number of ERC1155 tokens to list"
+    )]
+    pub quantity: U256,
+
+    #[structopt(long, parse(try_from_str = parse_eth), help = "The price of all the listed tokens, in ETH (e.g. `0.25`)")]
+    pub price: U256,
+
+    #[structopt(
+        long,
+        default_value = "250",
+        help = "The seller fee paid to OpenSea, in basis points of the price. It must cover the collection's OpenSea and creator fees for the listing to be accepted"
+    )]
+    pub fee_bps: u64,
+
+    #[structopt(
+        long,
+        default_value = "0",
+        help = "The unix timestamp the listing expires at, 0 for never"
+    )]
+    pub expiration: u64,
+
+    #[structopt(
+        long,
+        help = "Never connect to the node: skip checking the Wyvern proxy and its approval, e.g. to pre-sign listings of items still being minted. --eth.url is unused"
+    )]
+    pub offline: bool,
+
+    #[structopt(
+        long,
+        requires = "offline",
+        help = "Print the signed listing as JSON without posting it"
+    )]
+    pub no_post: bool,
+
+    #[structopt(
+        long = "static_call.target",
+        help = "The contract whose `staticCall` predicate must succeed for the listing to be filled"
+    )]
+    pub static_target: Option<Address>,
+
+    #[structopt(
+        long = "static_call.extradata",
+        requires = "static-target",
+        parse(try_from_str = parse_hex),
+        help = "The hex data the transfer's calldata is appended to when calling the predicate"
+    )]
+    pub static_extradata: Option<Bytes>,
+
+    #[structopt(
+        long = "skip_health_check",
+        help = "Do not check the node, the exchange contract, the API and the balance before starting"
+    )]
+    pub skip_health_check: bool,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Transfer NFTs directly through the NFT contract")]
+pub struct TransferOpts {
+    #[structopt(flatten)]
+    pub eth: EthereumOpts,
+
+    #[structopt(flatten)]
+    pub output: OutputOpts,
+
+    #[structopt(flatten)]
+    pub nft: NftOpts,
+
+    #[structopt(
+        long,
+        help = "The receiver (address or ENS name) of the NFTs",
+        parse(from_str = parse_name_or_address)
+    )]
+    pub to: NameOrAddress,
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -148,20 +929,205 @@ pub struct BuyOpts {
     #[structopt(flatten)]
     pub eth: EthereumOpts,
 
+    #[structopt(flatten)]
+    pub output: OutputOpts,
+
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+
     #[structopt(flatten)]
     pub flashbots: FlashBotsOpts,
 
     #[structopt(flatten)]
     pub nft: NftOpts,
 
+    #[structopt(
+        long,
+        help = "CSV file of `contract,token_id,quantity,max_price` rows to buy, possibly spanning several collections. The quantity defaults to 1 and the max price (in ETH) to --max_price"
+    )]
+    pub targets: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub swap: SwapOpts,
+
+    #[structopt(flatten)]
+    pub fees: FeeOpts,
+
+    #[structopt(flatten)]
+    pub events: EventsOpts,
+
+    #[structopt(flatten)]
+    pub safe: SafeOpts,
+
+    #[structopt(
+        long = "max_price",
+        parse(try_from_str = parse_eth),
+        help = "The max price to pay for each listing, in ETH (e.g. `0.25`). Listings whose price rose above it abort the purchase"
+    )]
+    pub max_price: Option<U256>,
+
+    #[structopt(
+        long = "total_budget",
+        parse(try_from_str = parse_eth),
+        help = "The max price to pay for all the listings, in ETH (e.g. `1.5`). Listings past it abort the purchase"
+    )]
+    pub total_budget: Option<U256>,
+
+    #[structopt(
+        long = "exclude_flagged",
+        help = "Skip the listings of assets which cannot be resold on OpenSea, e.g. flagged as stolen"
+    )]
+    pub exclude_flagged: bool,
+
+    #[structopt(
+        long = "onchain_price_tolerance_bps",
+        help = "Double-check the API's price of each listing with the exchange, paying the on-chain price when they differ by more than these basis points"
+    )]
+    pub onchain_price_tolerance_bps: Option<u64>,
+
     #[structopt(
         long,
         help = "Whether you're buying an ERC721 or an ERC1155 (true for 1155)"
     )]
-    #[structopt(long, help = "Create and log the transactions without submitting them")]
+    #[structopt(
+        long,
+        help = "Create the transactions without submitting them, and print them as JSON"
+    )]
     pub dry_run: bool,
+
+    #[structopt(
+        long = "dry_run.output",
+        requires = "dry_run",
+        help = "Write the transactions of the dry run to this JSON file instead of stdout"
+    )]
+    pub dry_run_output: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        help = "Simulate each transaction via eth_call and only submit the ones that would succeed (non-Flashbots only)"
+    )]
+    pub simulate: bool,
+
+    #[structopt(
+        long = "deadline_secs",
+        help = "Cancel the transactions still pending this many seconds after sending them, by replacing them with 0-value self-transfers (non-Flashbots only)"
+    )]
+    pub deadline_secs: Option<u64>,
+
+    #[structopt(
+        long = "bid_on_auctions",
+        help = "If none of the targets has a listing which can be bought, bid --max_price on their English auctions instead, the bids expiring after this many seconds. Nothing is bought then"
+    )]
+    pub bid_on_auctions: Option<u64>,
+
+    #[structopt(
+        long = "abort_on_price_moves",
+        help = "Poll the listings of the collection every this many seconds while sniping, aborting once the cheapest listing of a target gets cheaper, is delisted or expires"
+    )]
+    pub abort_on_price_moves: Option<u64>,
+
+    #[structopt(
+        long = "requote_bps",
+        help = "Re-fetch the listings by hash right before sending the txs, rebuilding the purchases whose price moved by at most this many basis points and failing if one moved by more or is gone"
+    )]
+    pub requote_bps: Option<u64>,
+
+    #[structopt(
+        long,
+        help = "JSON journal of the purchases sent via the mempool, created if missing. Rerunning with the same journal skips the targets whose purchases were mined and re-syncs the nonces from the chain"
+    )]
+    pub resume: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        help = "Directory to record the run to, for post-mortems: the API responses, the constructed txs, the bundle simulations and the receipts, with their timestamps. See the `replay` subcommand"
+    )]
+    pub record: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        short = "y",
+        help = "Submit the transactions without asking for confirmation of their summary"
+    )]
+    pub yes: bool,
+
+    #[structopt(
+        long = "skip_health_check",
+        help = "Do not check the node, the exchange contract, the API and the balance before starting"
+    )]
+    pub skip_health_check: bool,
+}
+
+impl BuyOpts {
+    /// The tokens to buy, read from the `--targets` file if given. Tokens of the
+    /// `--nft.address` collection are returned without a collection.
+    pub fn snipe_targets(&self) -> color_eyre::Result<Vec<SnipeTarget>> {
+        let path = match self.targets {
+            Some(ref path) => path,
+            None => {
+                let entries = self.nft.token_entries()?;
+                return Ok(entries
+                    .into_iter()
+                    .map(|entry| {
+                        let target = SnipeTarget::new(entry.id, entry.quantity);
+                        match entry.max_price {
+                            Some(max_price) => target.max_price(max_price),
+                            None => target,
+                        }
+                    })
+                    .collect());
+            }
+        };
+
+        let file = File::open(path)?;
+        let mut targets = Vec::new();
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut columns = line.split(',').map(str::trim);
+            let nft = Address::from_str(columns.next().unwrap_or_default())?;
+            let id = columns
+                .next()
+                .ok_or_else(|| color_eyre::eyre::eyre!("no token id in row `{}`", line))?;
+            let quantity = match columns.next() {
+                Some(quantity) if !quantity.is_empty() => usize::from_str(quantity)?,
+                _ => 1,
+            };
+            let mut target = SnipeTarget::new(U256::from_dec_str(id)?, quantity);
+            if Some(NameOrAddress::Address(nft)) != self.nft.address {
+                target = target.nft(nft);
+            }
+            if let Some(max_price) = columns.next().filter(|price| !price.is_empty()) {
+                target = target
+                    .max_price(parse_eth(max_price).map_err(|err| color_eyre::eyre::eyre!(err))?);
+            }
+            targets.push(target);
+        }
+        Ok(targets)
+    }
+}
+
+fn parse_u256(s: &str) -> Result<U256, String> {
+    U256::from_dec_str(s).map_err(|err| format!("invalid decimal number {}: {:?}", s, err))
+}
+
+/// An amount of ETH, e.g. `0.25`, see [`units::parse_eth`] for the other units
+pub(crate) fn parse_eth(s: &str) -> Result<U256, String> {
+    let amount = units::parse_eth(s).map_err(|err| err.to_string())?;
+    // a Wei amount without its unit, as the flags used to take
+    if amount >= U256::exp10(24) {
+        return Err(format!(
+            "{} is more than a million ETH, append `wei` to amounts in Wei",
+            s
+        ));
+    }
+    Ok(amount)
 }
 
-fn parse_u256(s: &str) -> U256 {
-    U256::from_dec_str(s).unwrap()
+fn parse_hex(s: &str) -> Result<Bytes, String> {
+    hex::decode(s.trim_start_matches("0x"))
+        .map(Bytes::from)
+        .map_err(|err| format!("invalid hex data {}: {}", s, err))
 }
@@ -0,0 +1,204 @@
+//! `--output json`: the events of the library and the results of the subcommands are
+//! written to stdout as JSON lines, each tagged with its `type`, for other tools to
+//! consume. The human-readable messages, printed via [`opensea::say!`], are then moved
+//! to stderr.
+//!
+//! `buy --record <dir>`: the events and the records are also appended to the run file
+//! of the recording, see [`opensea::recording`].
+use ethers::types::{Address, Eip1559TransactionRequest, H256, U256, U64};
+use once_cell::sync::OnceCell;
+use opensea::{
+    events::EventEnvelope, recording::Recorded, types::MinimalOrder, AbortReason,
+    BundleSimulationReport, ClientError, EventFeed, RunRecorder, SnipeProgress, Verdict,
+};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// How long the library waits for each event to be written when no risk system has a
+/// say in it
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Set if the output is JSON
+static JSON: OnceCell<()> = OnceCell::new();
+
+/// The recording of the run, if any
+static RECORDER: OnceCell<RunRecorder> = OnceCell::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputMode::Text),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!("unknown output mode {}", s)),
+        }
+    }
+}
+
+/// A result of a subcommand. The `type`s do not overlap with those of the library's
+/// [`opensea::Event`]s.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    /// The `owner` of an ERC721, or the `balance` of an ERC1155 held by the `owner`
+    Ownership {
+        token: Address,
+        token_id: U256,
+        owner: Option<Address>,
+        balance: Option<U256>,
+    },
+    /// The transactions of a dry run, unsigned
+    DryRun {
+        txs: Vec<Eip1559TransactionRequest>,
+    },
+    /// The included bundle, along with its simulation
+    BundleIncluded {
+        block: U64,
+        simulation: BundleSimulationReport,
+    },
+    /// The block and simulation of each split bundle, if it was included
+    BundlesIncluded {
+        blocks: Vec<Option<U64>>,
+        simulations: Vec<Option<BundleSimulationReport>>,
+    },
+    /// The transactions sent via the mempool
+    TxsSent {
+        tx_hashes: Vec<H256>,
+    },
+    ContractDeployed {
+        address: Address,
+    },
+    CancellationSent {
+        order_hash: H256,
+        tx_hash: H256,
+    },
+    TransferSent {
+        token: Address,
+        token_id: U256,
+        quantity: usize,
+        to: Address,
+        tx_hash: H256,
+    },
+    /// The receipt of a transaction sent by the subcommand
+    Receipt {
+        tx_hash: H256,
+        block: Option<U64>,
+        success: bool,
+    },
+    ListingSigned {
+        order_hash: H256,
+        order: Box<MinimalOrder>,
+    },
+    ListingPosted {
+        order_hash: H256,
+    },
+    /// Nothing was bought, the bids were placed on the English auctions instead
+    BidsPlaced {
+        order_hashes: Vec<H256>,
+    },
+    /// The snipe was stopped midway, the `progress` listing the txs it already sent
+    SnipeAborted {
+        reason: AbortReason,
+        progress: SnipeProgress,
+    },
+    /// The subcommand failed, see `error.rs` for the exit codes
+    Error {
+        message: String,
+        exit_code: i32,
+    },
+}
+
+/// Sets the output mode. With JSON, the stdout is kept for the JSON lines and the
+/// human-readable messages, including those of the library, go to the stderr, see
+/// [`opensea::display::messages_to_stderr`].
+pub fn init(mode: OutputMode) -> io::Result<()> {
+    if mode == OutputMode::Text {
+        return Ok(());
+    }
+    io::stdout().flush()?;
+    opensea::display::messages_to_stderr();
+    let _ = JSON.set(());
+    Ok(())
+}
+
+pub fn is_json() -> bool {
+    JSON.get().is_some()
+}
+
+/// Records the events and the records to the recording as well, from now on
+pub fn start_recording(recorder: RunRecorder) {
+    let _ = RECORDER.set(recorder);
+}
+
+pub fn is_recording() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Appends the entry to the recording, if any
+pub fn record_run(entry: Recorded) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.record(entry);
+    }
+}
+
+/// Writes the record as a JSON line, if the output is JSON, and to the recording
+pub fn record(record: &impl Serialize) {
+    write_json(record);
+    if let (Some(recorder), Ok(record)) = (RECORDER.get(), serde_json::to_value(record)) {
+        recorder.record(Recorded::Record { record });
+    }
+}
+
+/// Writes the record (or event) as a JSON line, if the output is JSON
+fn write_json(record: &impl Serialize) {
+    if !is_json() {
+        return;
+    }
+    if let Ok(mut line) = serde_json::to_string(record) {
+        line.push('\n');
+        // a single write of the locked stdout, so that the lines do not interleave
+        let _ = io::stdout().lock().write_all(line.as_bytes());
+    }
+}
+
+/// The event feed of the library when the output is JSON or recorded: each event is
+/// written (and recorded) before being forwarded to the risk system's `socket` feed, if
+/// any, whose verdict is returned. The library waits for each event to be written, so
+/// none is lost on exit.
+pub fn event_feed(socket: Option<EventFeed>, ack_timeout: Option<Duration>) -> EventFeed {
+    let (feed, rx) = EventFeed::channel();
+    tokio::spawn(forward_events(rx, socket));
+    feed.with_acks(ack_timeout.unwrap_or(WRITE_TIMEOUT))
+}
+
+async fn forward_events(mut rx: mpsc::UnboundedReceiver<EventEnvelope>, socket: Option<EventFeed>) {
+    while let Some(envelope) = rx.recv().await {
+        write_json(&envelope.event);
+        record_run(Recorded::Event {
+            event: envelope.event.clone(),
+        });
+        let verdict = match socket {
+            Some(ref socket) => match socket.emit(envelope.event.clone()).await {
+                Ok(()) => Verdict::Approve,
+                Err(ClientError::Vetoed(reason)) => Verdict::Veto { reason },
+                Err(err) => Verdict::Veto {
+                    reason: err.to_string(),
+                },
+            },
+            None => Verdict::Approve,
+        };
+        envelope.respond(verdict);
+    }
+}
@@ -0,0 +1,529 @@
+use async_trait::async_trait;
+use ethers::types::{Address, H256, U256};
+use futures::stream::{self, StreamExt};
+use opensea::{
+    api::Quota,
+    get_n_cheapest_orders, get_n_cheapest_orders_by_token, say, snapshot,
+    types::{CollectionAsset, MinimalOrder, Order, OrderSide},
+    AssetsRequest, OpenSeaApi, OpenSeaApiError, OrderProvider, OrderRequest, PaymentTokens,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::opts::QuoteOpts;
+
+/// The maximum number of orders the API returns per query
+const MAX_ORDERS: usize = 50;
+
+/// The number of tokens whose orders are queried at once with `--bulk`
+const BULK_BATCH_SIZE: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format {}", s)),
+        }
+    }
+}
+
+/// Orderbook statistics about a token. Prices are normalized to 18 decimals (Wei for
+/// ETH, see [`Order::normalized_price`]), as decimal strings. The orders in unknown
+/// payment tokens are counted but not priced.
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenQuote {
+    pub token_id: String,
+    pub listings: usize,
+    pub best_price: Option<String>,
+    pub p25: Option<String>,
+    pub median: Option<String>,
+    pub p75: Option<String>,
+    /// Number of listings per payment token (the zero address is ETH)
+    pub payment_tokens: BTreeMap<Address, usize>,
+    pub offers: Option<usize>,
+    pub best_offer: Option<String>,
+}
+
+/// Returns the nearest-rank percentile of the sorted prices
+fn percentile(sorted: &[U256], pct: usize) -> Option<String> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100);
+    let idx = rank.saturating_sub(1).min(sorted.len() - 1);
+    Some(sorted[idx].to_string())
+}
+
+/// Queries the OpenSea orderbook for each token and prints its statistics as they come
+pub async fn quote(opts: QuoteOpts) -> color_eyre::Result<()> {
+    let api = OpenSeaApi::new(opts.api.config())?;
+    let nft = opts.nft.address(&api).await?;
+    let (ids, _) = opts.nft.tokens()?;
+    let mut tokens = PaymentTokens::of(api.network());
+    tokens.refresh(&api).await?;
+
+    let mut writer = QuoteWriter::new(std::io::stdout(), opts.format)?;
+    if opts.bulk {
+        let concurrency = opts.concurrency.max(1);
+        let quota_api = api.clone();
+        let limiter = RateLimiter::new(opts.requests_per_sec)
+            .with_quota(concurrency as u64, move || quota_api.quota());
+        let api = RateLimited::new(&api, &limiter);
+        let mut batches = stream::iter(ids.chunks(BULK_BATCH_SIZE))
+            .map(|batch| quote_batch(&api, nft, batch, opts.offers, &tokens))
+            .buffered(concurrency);
+        while let Some(quotes) = batches.next().await {
+            for quote in quotes? {
+                writer.write(&quote)?;
+            }
+        }
+    } else {
+        for id in ids {
+            // sorted by price, without dust listings
+            let listings = get_n_cheapest_orders(&api, nft, id, MAX_ORDERS).await?;
+            let offers = if opts.offers {
+                let req = OrderRequest {
+                    side: OrderSide::Buy,
+                    token_ids: vec![id.to_string()],
+                    contract_address: nft,
+                    limit: MAX_ORDERS as u64,
+                    offset: 0,
+                };
+                Some(api.get_orders(req).await?)
+            } else {
+                None
+            };
+            writer.write(&token_quote(id, &listings, offers.as_deref(), &tokens))?;
+        }
+    }
+    writer.finish()?;
+
+    if let Some(ref output) = opts.output {
+        let rows = snapshot::orderbook_snapshot(&api, nft, OrderSide::Sell).await?;
+        match opts.format {
+            OutputFormat::Json => snapshot::write_json_file(&rows, output)?,
+            _ => snapshot::write_csv_file(&rows, output)?,
+        }
+        say!("Exported {} listings to {:?}", rows.len(), output);
+    }
+
+    Ok(())
+}
+
+/// The quotes of a batch of tokens, in the order of the batch, from the pages of a
+/// query of their listings (and of one of their offers)
+async fn quote_batch<P: OrderProvider>(
+    api: &P,
+    nft: Address,
+    batch: &[U256],
+    offers: bool,
+    tokens: &PaymentTokens,
+) -> color_eyre::Result<Vec<TokenQuote>> {
+    let listings = get_n_cheapest_orders_by_token(api, nft, batch, MAX_ORDERS).await?;
+    let offers = if offers {
+        let req = OrderRequest {
+            side: OrderSide::Buy,
+            token_ids: batch.iter().map(|id| id.to_string()).collect(),
+            contract_address: nft,
+            limit: MAX_ORDERS as u64,
+            offset: 0,
+        };
+        let mut by_token: HashMap<U256, Vec<Order>> = HashMap::new();
+        for order in api.get_all_orders(req).await? {
+            if let Some(metadata) = order.asset_metadata() {
+                by_token.entry(metadata.asset.id).or_default().push(order);
+            }
+        }
+        Some(by_token)
+    } else {
+        None
+    };
+
+    Ok(batch
+        .iter()
+        .map(|id| {
+            let listings = listings.get(id).map(Vec::as_slice).unwrap_or_default();
+            let offers = offers
+                .as_ref()
+                .map(|offers| offers.get(id).map(Vec::as_slice).unwrap_or_default());
+            token_quote(*id, listings, offers, tokens)
+        })
+        .collect())
+}
+
+type QuotaFn = dyn Fn() -> Option<Quota> + Send + Sync;
+
+/// Spaces the requests of `--bulk` to stay under `--requests_per_sec`, and holds them
+/// until the window resets once the API's quota is nearly exhausted, see
+/// [`OpenSeaApi::quota`]
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+    quota: Option<(u64, Arc<QuotaFn>)>,
+}
+
+impl RateLimiter {
+    /// A limiter of `per_sec` requests per second, unlimited if 0
+    fn new(per_sec: f64) -> Self {
+        let interval = if per_sec > 0. {
+            Duration::from_secs_f64(1. / per_sec)
+        } else {
+            Duration::default()
+        };
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+            quota: None,
+        }
+    }
+
+    /// Holds the requests once the quota has at most `reserve` requests remaining, e.g.
+    /// the ones which may already be in flight
+    fn with_quota(
+        mut self,
+        reserve: u64,
+        quota: impl Fn() -> Option<Quota> + Send + Sync + 'static,
+    ) -> Self {
+        self.quota = Some((reserve, Arc::new(quota)));
+        self
+    }
+
+    /// Waits for the turn of the next request
+    async fn acquire(&self) {
+        let mut next = self.next.lock().await;
+        if let Some((reserve, ref quota)) = self.quota {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            if let Some(wait) = quota().and_then(|quota| quota_wait(&quota, reserve, now)) {
+                *next = (*next).max(Instant::now() + wait);
+            }
+        }
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.interval;
+    }
+}
+
+/// How long to wait for the quota's window to reset, if at most `reserve` requests
+/// remain. Waits an extra second for the clocks' drift.
+fn quota_wait(quota: &Quota, reserve: u64, now: u64) -> Option<Duration> {
+    if quota.remaining > reserve {
+        return None;
+    }
+    quota
+        .resets_in(now)
+        .map(|secs| Duration::from_secs(secs + 1))
+}
+
+/// An order provider whose requests wait for their turn of the [`RateLimiter`]. Each
+/// page of a paged query is a request.
+struct RateLimited<'a, P> {
+    inner: &'a P,
+    limiter: &'a RateLimiter,
+}
+
+impl<'a, P> RateLimited<'a, P> {
+    fn new(inner: &'a P, limiter: &'a RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl<P: OrderProvider> OrderProvider for RateLimited<'_, P> {
+    async fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        self.limiter.acquire().await;
+        self.inner.get_orders(req).await
+    }
+
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        self.limiter.acquire().await;
+        self.inner.get_order_by_hash(order_hash).await
+    }
+
+    async fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        self.limiter.acquire().await;
+        self.inner.post_order(order).await
+    }
+
+    async fn get_assets(
+        &self,
+        req: AssetsRequest,
+    ) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        self.limiter.acquire().await;
+        self.inner.get_assets(req).await
+    }
+}
+
+/// The statistics of the token's listings and of its offers if they were queried, by
+/// their prices normalized by the `tokens`
+fn token_quote(
+    id: U256,
+    listings: &[Order],
+    offers: Option<&[Order]>,
+    tokens: &PaymentTokens,
+) -> TokenQuote {
+    let mut prices = listings
+        .iter()
+        .filter_map(|order| order.normalized_price(tokens))
+        .collect::<Vec<_>>();
+    prices.sort_unstable();
+    let mut payment_tokens = BTreeMap::new();
+    for order in listings {
+        *payment_tokens.entry(order.payment_token).or_default() += 1;
+    }
+    let best_offer = offers
+        .and_then(|offers| {
+            offers
+                .iter()
+                .filter_map(|order| order.normalized_price(tokens))
+                .max()
+        })
+        .map(|price| price.to_string());
+
+    TokenQuote {
+        token_id: id.to_string(),
+        listings: listings.len(),
+        best_price: prices.first().map(|price| price.to_string()),
+        p25: percentile(&prices, 25),
+        median: percentile(&prices, 50),
+        p75: percentile(&prices, 75),
+        payment_tokens,
+        offers: offers.map(<[Order]>::len),
+        best_offer,
+    }
+}
+
+/// Writes the quotes one by one in the `--format`, flushing each so that they can be
+/// followed while the others are queried. The JSON quotes are written as an array.
+struct QuoteWriter<W: Write> {
+    out: W,
+    format: OutputFormat,
+    written: usize,
+}
+
+impl<W: Write> QuoteWriter<W> {
+    /// Writes the header of the format
+    fn new(mut out: W, format: OutputFormat) -> color_eyre::Result<Self> {
+        match format {
+            OutputFormat::Json => writeln!(out, "[")?,
+            OutputFormat::Csv => writeln!(
+                out,
+                "token_id,listings,best_price,p25,median,p75,payment_tokens,offers,best_offer"
+            )?,
+            OutputFormat::Table => writeln!(
+                out,
+                "{:<12} {:>8} {:>24} {:>24} {:>24} {:>24} {:>7} {:>24}",
+                "token_id",
+                "listings",
+                "best_price",
+                "p25",
+                "median",
+                "p75",
+                "offers",
+                "best_offer"
+            )?,
+        }
+        out.flush()?;
+        Ok(Self {
+            out,
+            format,
+            written: 0,
+        })
+    }
+
+    fn write(&mut self, quote: &TokenQuote) -> color_eyre::Result<()> {
+        let opt = |val: &Option<String>| val.clone().unwrap_or_else(|| "-".to_owned());
+        let tokens = quote
+            .payment_tokens
+            .iter()
+            .map(|(token, count)| format!("{:?}:{}", token, count))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let out = &mut self.out;
+        match self.format {
+            OutputFormat::Json => {
+                if self.written > 0 {
+                    writeln!(out, ",")?;
+                }
+                write!(out, "{}", serde_json::to_string_pretty(quote)?)?;
+            }
+            OutputFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                quote.token_id,
+                quote.listings,
+                opt(&quote.best_price),
+                opt(&quote.p25),
+                opt(&quote.median),
+                opt(&quote.p75),
+                tokens,
+                quote.offers.map(|n| n.to_string()).unwrap_or_default(),
+                opt(&quote.best_offer),
+            )?,
+            OutputFormat::Table => {
+                writeln!(
+                    out,
+                    "{:<12} {:>8} {:>24} {:>24} {:>24} {:>24} {:>7} {:>24}",
+                    quote.token_id,
+                    quote.listings,
+                    opt(&quote.best_price),
+                    opt(&quote.p25),
+                    opt(&quote.median),
+                    opt(&quote.p75),
+                    quote
+                        .offers
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "-".to_owned()),
+                    opt(&quote.best_offer),
+                )?;
+                if quote.payment_tokens.len() > 1 {
+                    writeln!(out, "{:<12} payment tokens: {}", "", tokens)?;
+                }
+            }
+        }
+        out.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Closes the JSON array
+    fn finish(mut self) -> color_eyre::Result<()> {
+        if self.format == OutputFormat::Json {
+            if self.written > 0 {
+                writeln!(self.out)?;
+            }
+            writeln!(self.out, "]")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opensea::MockOrderProvider;
+
+    fn listings(num: usize, side: OrderSide) -> Vec<Order> {
+        let order = opensea::testing::order();
+        (0..num)
+            .map(|i| {
+                let mut order = order.clone();
+                order.order_hash = H256::from_low_u64_be(i as u64 + 1);
+                order.side = side;
+                order
+            })
+            .collect()
+    }
+
+    #[test]
+    fn takes_the_nearest_rank_percentiles() {
+        let prices: Vec<U256> = (1..=10u64).map(U256::from).collect();
+        assert_eq!(percentile(&prices, 25), Some("3".to_owned()));
+        assert_eq!(percentile(&prices, 50), Some("5".to_owned()));
+        assert_eq!(percentile(&prices, 75), Some("8".to_owned()));
+        assert_eq!(percentile(&prices[..1], 25), Some("1".to_owned()));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[tokio::test]
+    async fn pages_the_orders_of_a_batch() {
+        let mut orders = listings(70, OrderSide::Sell);
+        orders.extend(listings(60, OrderSide::Buy));
+        let id = orders[0].asset_metadata().unwrap().asset.id;
+        let nft = orders[0].asset_metadata().unwrap().asset.address;
+        let api = MockOrderProvider::new(orders);
+        let limiter = RateLimiter::new(0.);
+        let api = RateLimited::new(&api, &limiter);
+
+        let quotes = quote_batch(&api, nft, &[id, 0.into()], true, &PaymentTokens::default())
+            .await
+            .unwrap();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes[0].token_id, id.to_string());
+        assert_eq!(quotes[0].listings, 50);
+        assert_eq!(quotes[0].offers, Some(60));
+        assert_eq!(quotes[1].listings, 0);
+        assert_eq!(quotes[1].offers, Some(0));
+    }
+
+    #[test]
+    fn quotes_the_normalized_prices() {
+        let mut listings = listings(3, OrderSide::Sell);
+        let id = listings[0].asset_metadata().unwrap().asset.id;
+        // 100 USDC, fewer units than the 80 ETH of the others but more once normalized
+        listings[0].payment_token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        listings[0].current_price = U256::exp10(8);
+        // not priced
+        listings[1].payment_token = Address::random();
+        listings[1].current_price = U256::one();
+        let mut offers = self::listings(2, OrderSide::Buy);
+        offers[0].payment_token = "0x6b175474e89094c44da98b954eedeac495271d0f"
+            .parse()
+            .unwrap();
+        offers[0].current_price = U256::exp10(20);
+
+        let quote = token_quote(id, &listings, Some(&offers), &PaymentTokens::default());
+        let eth = listings[2].current_price;
+        assert_eq!(quote.listings, 3);
+        assert_eq!(quote.best_price, Some(eth.to_string()));
+        assert_eq!(quote.p25, Some(eth.to_string()));
+        assert_eq!(quote.p75, Some(U256::exp10(20).to_string()));
+        assert_eq!(quote.payment_tokens.len(), 3);
+        assert_eq!(quote.offers, Some(2));
+        assert_eq!(quote.best_offer, Some(U256::exp10(20).to_string()));
+    }
+
+    #[tokio::test]
+    async fn spaces_the_requests() {
+        let limiter = RateLimiter::new(50.);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        // the first request goes right away
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn holds_the_requests_once_the_quota_is_exhausted() {
+        let quota = Quota {
+            limit: Some(240),
+            remaining: 4,
+            reset_at: Some(1_700_000_010),
+        };
+        assert_eq!(
+            quota_wait(&quota, 4, 1_700_000_000),
+            Some(Duration::from_secs(11))
+        );
+        assert_eq!(quota_wait(&quota, 3, 1_700_000_000), None);
+        // without a reset time there is nothing to wait for
+        let quota = Quota {
+            reset_at: None,
+            ..quota
+        };
+        assert_eq!(quota_wait(&quota, 4, 1_700_000_000), None);
+    }
+}
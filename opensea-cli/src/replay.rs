@@ -0,0 +1,194 @@
+use ethers::types::H256;
+use opensea::{
+    recording::{Recorded, RecordedEntry, Recording, ReplayedOrder},
+    say,
+    units::format_eth,
+    Event,
+};
+use std::collections::HashMap;
+
+use crate::opts::ReplayOpts;
+
+/// Prints the timeline of a recorded run, then the listings selected again against its
+/// API responses next to the run's own decisions
+pub async fn replay(opts: ReplayOpts) -> color_eyre::Result<()> {
+    let recording = Recording::load(&opts.dir)?;
+    let start = recording
+        .entries
+        .first()
+        .map(|entry| entry.timestamp_ms)
+        .unwrap_or_default();
+
+    say!("== Timeline ==");
+    for entry in &recording.entries {
+        say!(
+            "+{:>8}ms {}",
+            entry.timestamp_ms.saturating_sub(start),
+            summary(entry)
+        );
+    }
+
+    let recorded = run_decisions(&recording);
+    say!("== Replay ==");
+    let replayed = recording.replay().await?;
+    say!(
+        "{:<24} {:<68} {:>24} {:<32} Run",
+        "Token Id",
+        "Order",
+        "Price",
+        "Replayed"
+    );
+    for order in &replayed {
+        let run = recorded.get(&order.order_hash);
+        let replayed = match order.rejected {
+            Some(ref reason) => format!("rejected: {}", reason),
+            None => "selected".to_owned(),
+        };
+        say!(
+            "{:<24} {:<68} {:>24} {:<32} {}",
+            order.token_id.to_string(),
+            format!("{:?}", order.order_hash),
+            format_eth(order.price),
+            replayed,
+            run.map(String::as_str).unwrap_or("-"),
+        );
+    }
+    say!(
+        "{} listings replayed, {} decided otherwise by the run",
+        replayed.len(),
+        mismatches(&replayed, &recorded)
+    );
+    Ok(())
+}
+
+/// The run's decision on each order, the last one winning
+fn run_decisions(recording: &Recording) -> HashMap<H256, String> {
+    let mut recorded = HashMap::new();
+    for entry in &recording.entries {
+        match entry.entry {
+            Recorded::Event {
+                event: Event::OrderSelected { order_hash, .. },
+            } => {
+                recorded.insert(order_hash, "selected".to_owned());
+            }
+            Recorded::Event {
+                event:
+                    Event::OrderSkipped {
+                        order_hash,
+                        ref reason,
+                    },
+            } => {
+                recorded.insert(order_hash, format!("skipped: {}", reason));
+            }
+            _ => {}
+        }
+    }
+    recorded
+}
+
+/// The number of replayed listings the run decided otherwise on. The run may skip a
+/// selected listing after its on-chain checks, which are not replayed.
+fn mismatches(replayed: &[ReplayedOrder], recorded: &HashMap<H256, String>) -> usize {
+    replayed
+        .iter()
+        .filter(|order| order.rejected.is_none() != recorded.contains_key(&order.order_hash))
+        .count()
+}
+
+/// A line summarizing the entry
+fn summary(entry: &RecordedEntry) -> String {
+    match entry.entry {
+        Recorded::Plan(ref plan) => format!(
+            "Plan: {} targets from {:?}, max price {}",
+            plan.targets.len(),
+            plan.taker,
+            plan.max_price
+                .map(format_eth)
+                .unwrap_or_else(|| "-".to_owned())
+        ),
+        Recorded::ApiResponse {
+            ref endpoint,
+            status,
+            ref body,
+        } => format!("API {}: {} ({} bytes)", endpoint, status, body.len()),
+        Recorded::Event { ref event } => match event {
+            Event::TxBuilt { tx } => format!(
+                "Tx built: nonce {:?}, to {:?}, value {:?}, max fee {:?}",
+                tx.nonce, tx.to, tx.value, tx.max_fee_per_gas
+            ),
+            Event::BundleSimulated { simulation } => format!(
+                "Bundle simulated at block {:?}: {:?} gas, {} reverted txs",
+                simulation.simulation_block,
+                simulation.gas_used,
+                simulation.reverted().count()
+            ),
+            event => format!("{:?}", event),
+        },
+        Recorded::Record { ref record } => format!(
+            "Result {}: {}",
+            record["type"].as_str().unwrap_or_default(),
+            record
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+    use opensea::{
+        recording::{RecordedPlan, RunRecorder},
+        snipe::SnipeTarget,
+        testing, ListingFilter,
+    };
+
+    #[tokio::test]
+    async fn replays_the_recorded_run() {
+        let dir = std::env::temp_dir().join(format!("opensea-cli-replay-{}", std::process::id()));
+        let order = testing::order();
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let recorder = RunRecorder::create(&dir).unwrap();
+        recorder.record(Recorded::Plan(RecordedPlan {
+            taker: Address::random(),
+            recipient: Address::random(),
+            nft: asset.address,
+            targets: vec![SnipeTarget::new(asset.id, 1)],
+            max_price: Some(order.current_price),
+            filter: ListingFilter::default(),
+        }));
+        let body = format!(r#"{{"count": 1, "orders": [{}]}}"#, testing::ORDER_JSON);
+        recorder.record(Recorded::ApiResponse {
+            endpoint: "orders".to_owned(),
+            status: 200,
+            body: body.clone(),
+        });
+        recorder.record(Recorded::Event {
+            event: Event::OrderSkipped {
+                order_hash: order.order_hash,
+                reason: "maker balance too low".to_owned(),
+            },
+        });
+        let recording = Recording::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            summary(&recording.entries[1]),
+            format!("API orders: 200 ({} bytes)", body.len())
+        );
+
+        let replayed = recording.replay().await.unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].nft, asset.address);
+        assert_eq!(replayed[0].token_id, asset.id);
+        assert_eq!(replayed[0].order_hash, order.order_hash);
+        assert_eq!(replayed[0].price, order.current_price);
+        assert_eq!(replayed[0].rejected, None);
+
+        let recorded = run_decisions(&recording);
+        assert_eq!(
+            recorded[&order.order_hash],
+            "skipped: maker balance too low"
+        );
+        assert_eq!(mismatches(&replayed, &recorded), 0);
+        assert_eq!(mismatches(&replayed, &HashMap::new()), 1);
+    }
+}
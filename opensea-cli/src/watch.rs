@@ -0,0 +1,129 @@
+//! Streams the moves of the cheapest listing of each token of a collection, as polled by
+//! a [`PriceMonitor`], along with the time left until each listing expires
+use ethers::types::{Address, U256};
+use opensea::{
+    display::format_amount, say, snapshot::OrderbookRow, OpenSeaApi, PaymentTokens, PriceEvent,
+    PriceMonitor,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::opts::WatchOpts;
+
+/// Prints the price events of the collection until interrupted
+pub async fn watch(opts: WatchOpts) -> color_eyre::Result<()> {
+    let api = OpenSeaApi::new(opts.api.config())?;
+    let nft = opts.nft.address(&api).await?;
+    let tokens = PaymentTokens::of(api.network());
+
+    let monitor = PriceMonitor::new(Arc::new(api), nft, Duration::from_secs(opts.interval));
+    let mut events = monitor.subscribe();
+    let handle = monitor.spawn();
+    say!(
+        "Watching the listings of {:?} every {}s",
+        nft,
+        opts.interval
+    );
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        match event {
+            Ok(event) => say!("{}", describe(&event, &tokens, now)),
+            Err(RecvError::Lagged(missed)) => say!("Missed {} events", missed),
+            Err(RecvError::Closed) => break,
+        }
+    }
+    handle.abort();
+    Ok(())
+}
+
+/// A one-liner of the event, e.g. `[TokenId = 87] Price drop from 1 ETH to 0.8 ETH by
+/// 0x3ee4…c20b, expires in 2h 05m 00s`
+fn describe(event: &PriceEvent, tokens: &PaymentTokens, now: u64) -> String {
+    let (what, row) = match event {
+        PriceEvent::NewListing(row) => ("Listed".to_owned(), row),
+        PriceEvent::PriceDrop { old_price, listing } => (
+            format!(
+                "Price drop from {}",
+                format_price(*old_price, listing.payment_token, tokens)
+            ),
+            listing,
+        ),
+        PriceEvent::Delisted(row) => ("Delisted".to_owned(), row),
+        PriceEvent::Expired(row) => ("Expired".to_owned(), row),
+    };
+    let mut line = format!(
+        "[TokenId = {}] {} at {} by {:?}",
+        row.token_id,
+        what,
+        format_price(row.price, row.payment_token, tokens),
+        row.maker
+    );
+    if let PriceEvent::NewListing(_) | PriceEvent::PriceDrop { .. } = event {
+        line.push_str(", ");
+        line.push_str(&format_time_to_expiry(row, now));
+    }
+    line
+}
+
+fn format_price(amount: U256, payment_token: Address, tokens: &PaymentTokens) -> String {
+    match tokens.get(payment_token) {
+        Some(token) => format!("{} {}", format_amount(amount, token.decimals), token.symbol),
+        None => format!("{} of token {:?}", amount, payment_token),
+    }
+}
+
+/// The countdown of the listing at the `now` unix timestamp, e.g. `expires in 1h 02m 03s`
+fn format_time_to_expiry(row: &OrderbookRow, now: u64) -> String {
+    match row.time_to_expiry(now) {
+        None => "never expires".to_owned(),
+        Some(left) => {
+            let secs = left.as_secs();
+            format!(
+                "expires in {}h {:02}m {:02}s",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_down_to_the_expiry() {
+        let mut row = OrderbookRow {
+            token_id: 87.into(),
+            price: U256::exp10(18),
+            payment_token: Address::zero(),
+            maker: Address::zero(),
+            expiration: 0,
+        };
+        assert_eq!(format_time_to_expiry(&row, 100), "never expires");
+        row.expiration = 100 + 3723;
+        assert_eq!(format_time_to_expiry(&row, 100), "expires in 1h 02m 03s");
+
+        let tokens = PaymentTokens::default();
+        let event = PriceEvent::PriceDrop {
+            old_price: U256::exp10(18) * 2,
+            listing: row,
+        };
+        assert_eq!(
+            describe(&event, &tokens, 100),
+            format!(
+                "[TokenId = 87] Price drop from 2 ETH at 1 ETH by {:?}, expires in 1h 02m 03s",
+                Address::zero()
+            )
+        );
+    }
+}
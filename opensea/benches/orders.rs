@@ -0,0 +1,31 @@
+//! Parsing of the orders API responses, polled for many collections at once. Run with
+//! `cargo bench -p opensea --bench orders`, see the README to compare against a
+//! baseline.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use opensea::{api::parse_orders, types::Order};
+
+/// An orders response with `n` copies of the fixture order
+fn response(n: usize) -> String {
+    let order: serde_json::Value = serde_json::from_str(include_str!("../../order.json")).unwrap();
+    serde_json::json!({ "count": n, "orders": vec![order; n] }).to_string()
+}
+
+fn orders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_orders");
+    for n in [1, 50] {
+        let text = response(n);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &text, |b, text| {
+            b.iter(|| parse_orders(black_box(text)).unwrap())
+        });
+    }
+    group.finish();
+
+    let order = include_str!("../../order.json");
+    c.bench_function("order", |b| {
+        b.iter(|| serde_json::from_str::<Order>(black_box(order)).unwrap())
+    });
+}
+
+criterion_group!(benches, orders);
+criterion_main!(benches);
@@ -0,0 +1,183 @@
+//! Aborting a snipe in flight, see [`AbortHandle`]. The whole pipeline of a snipe
+//! (fetching the listings, building, simulating and submitting the txs) runs within the
+//! future of [`Client::snipe`](crate::Client::snipe), so dropping it on abort stops
+//! all of its in-flight work: no task is left running detached.
+use ethers::types::{Address, H256, U256, U64};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Why a snipe was aborted
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AbortReason {
+    /// The process was interrupted, e.g. by a Ctrl-C
+    Interrupted,
+    /// The price of a target moved, e.g. as reported by a
+    /// [`PriceMonitor`](crate::monitor::PriceMonitor)
+    PriceMoved {
+        nft: Address,
+        token_id: U256,
+        price: U256,
+    },
+    /// A pending tx cancels one of the orders being bought
+    CancellationDetected {
+        order_hash: H256,
+        tx_hash: H256,
+    },
+    Other {
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbortReason::Interrupted => write!(f, "interrupted"),
+            AbortReason::PriceMoved {
+                nft,
+                token_id,
+                price,
+            } => write!(f, "the price of {:?} #{} moved to {}", nft, token_id, price),
+            AbortReason::CancellationDetected { order_hash, .. } => {
+                write!(f, "order {:?} is being cancelled", order_hash)
+            }
+            AbortReason::Other { reason } => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// The stages of the pipeline of a snipe, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnipeStage {
+    /// Querying the listings of the targets and the fees
+    Fetching,
+    /// Building the purchase txs, with the payments and the bribe
+    Building,
+    /// Simulating the bundles or the txs
+    Simulating,
+    /// Sending the bundles to the relays or the txs to the mempool
+    Submitting,
+}
+
+/// How far a snipe went
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SnipeProgress {
+    /// The last stage reached
+    pub stage: SnipeStage,
+    /// The txs sent to the mempool, still pending or mined
+    pub sent: Vec<H256>,
+    /// The blocks in which the bundles already sent were included
+    pub included: Vec<U64>,
+}
+
+impl Default for SnipeProgress {
+    fn default() -> Self {
+        Self {
+            stage: SnipeStage::Fetching,
+            sent: Vec::new(),
+            included: Vec::new(),
+        }
+    }
+}
+
+/// Aborts the snipes of the plans it was given to (see
+/// [`SnipePlan::abort_handle`](crate::SnipePlan::abort_handle)), and tracks their
+/// progress. Its clones abort the same snipes, e.g. from a Ctrl-C handler or from a
+/// task watching the prices. Only the first abort's reason is kept.
+#[derive(Clone, Debug)]
+pub struct AbortHandle {
+    state: Arc<Mutex<(Option<AbortReason>, SnipeProgress)>>,
+    notify: Arc<watch::Sender<bool>>,
+    aborted: watch::Receiver<bool>,
+}
+
+impl Default for AbortHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AbortHandle {
+    pub fn new() -> Self {
+        let (notify, aborted) = watch::channel(false);
+        Self {
+            state: Default::default(),
+            notify: Arc::new(notify),
+            aborted,
+        }
+    }
+
+    pub fn abort(&self, reason: AbortReason) {
+        let mut state = self.state.lock().unwrap();
+        if state.0.is_none() {
+            state.0 = Some(reason);
+            let _ = self.notify.send(true);
+        }
+    }
+
+    pub fn reason(&self) -> Option<AbortReason> {
+        self.state.lock().unwrap().0.clone()
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    pub fn progress(&self) -> SnipeProgress {
+        self.state.lock().unwrap().1.clone()
+    }
+
+    /// Resolves once aborted, with the reason
+    pub async fn aborted(&self) -> AbortReason {
+        // cloned before checking, so that an abort in between is seen as a change
+        let mut aborted = self.aborted.clone();
+        loop {
+            if let Some(reason) = self.reason() {
+                return reason;
+            }
+            if aborted.changed().await.is_err() {
+                // the sender is held by `self`, so this is unreachable
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Starts a new snipe, clearing the progress of the previous one
+    pub(crate) fn reset_progress(&self) {
+        self.state.lock().unwrap().1 = SnipeProgress::default();
+    }
+
+    pub(crate) fn stage(&self, stage: SnipeStage) {
+        self.state.lock().unwrap().1.stage = stage;
+    }
+
+    pub(crate) fn sent(&self, tx_hash: H256) {
+        self.state.lock().unwrap().1.sent.push(tx_hash);
+    }
+
+    pub(crate) fn included(&self, block: U64) {
+        self.state.lock().unwrap().1.included.push(block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_the_first_reason() {
+        let handle = AbortHandle::new();
+        let clone = handle.clone();
+        assert!(!handle.is_aborted());
+        let aborted = tokio::spawn(async move { clone.aborted().await });
+
+        handle.abort(AbortReason::Interrupted);
+        handle.abort(AbortReason::Other {
+            reason: "too late".to_owned(),
+        });
+        assert_eq!(aborted.await.unwrap(), AbortReason::Interrupted);
+        assert_eq!(handle.aborted().await, AbortReason::Interrupted);
+    }
+}
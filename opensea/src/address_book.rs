@@ -0,0 +1,128 @@
+//! Labels of the well-known addresses (the exchange, its fee recipient, popular
+//! collections...), shown instead of the addresses in the summaries of the orders and
+//! of the snipes, so that e.g. a listing paying an unexpected fee recipient stands out.
+//! More labels can be registered, e.g. one's own wallets, see [`register`].
+use ethers::types::Address;
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::{constants, display::short_address};
+
+/// The labels of the addresses, see [`AddressBook::known`] for the default ones
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddressBook {
+    labels: HashMap<Address, String>,
+}
+
+impl AddressBook {
+    /// The contracts this crate interacts with, OpenSea's fee recipient and a few
+    /// popular collections and tokens, on mainnet
+    pub fn known() -> Self {
+        let mut book = Self::default();
+        let contracts = [
+            (*constants::OPENSEA_ADDRESS, "OpenSea Wyvern Exchange"),
+            (*constants::WYVERN_V2_3_ADDRESS, "Wyvern Exchange v2.3"),
+            (*constants::ATOMICIZER_ADDRESS, "Wyvern Atomicizer"),
+            (
+                *constants::MERKLE_VALIDATOR_ADDRESS,
+                "OpenSea Merkle Validator",
+            ),
+            (*constants::OPENSEA_FEE_RECIPIENT, "OpenSea Wallet"),
+            (*constants::SEAPORT_ADDRESS, "Seaport"),
+            (*constants::OPENSEA_CONDUIT_ADDRESS, "OpenSea Conduit"),
+            (*constants::LOOKSRARE_EXCHANGE_ADDRESS, "LooksRare Exchange"),
+            (*constants::PROXY_REGISTRY_ADDRESS, "Wyvern Proxy Registry"),
+            (
+                *constants::TOKEN_TRANSFER_PROXY_ADDRESS,
+                "Wyvern Token Transfer Proxy",
+            ),
+            (
+                *constants::SHARED_STOREFRONT_ADDRESS,
+                "OpenSea Shared Storefront",
+            ),
+            (*constants::MULTICALL_ADDRESS, "Multicall"),
+            (*constants::MULTICALL3_ADDRESS, "Multicall3"),
+            (*constants::WETH_ADDRESS, "WETH"),
+            (*constants::UNISWAP_V3_ROUTER_ADDRESS, "Uniswap V3 Router"),
+            (*constants::UNISWAP_V3_QUOTER_ADDRESS, "Uniswap V3 Quoter"),
+        ];
+        for (address, label) in contracts {
+            book.insert(address, label);
+        }
+        let popular = [
+            (
+                "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d",
+                "Bored Ape Yacht Club",
+            ),
+            (
+                "0x60e4d786628fea6478f785a6d7e704777c86a7c6",
+                "Mutant Ape Yacht Club",
+            ),
+            ("0xb47e3cd837ddf8e4c57f05d70ab865de6e193bbb", "CryptoPunks"),
+            ("0xed5af388653567af2f388e6224dc7c4b3241c544", "Azuki"),
+            ("0x8a90cab2b38dba80c64b7734e58ee1db38b8992e", "Doodles"),
+            ("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC"),
+        ];
+        for (address, label) in popular {
+            book.insert(address.parse().unwrap(), label);
+        }
+        book
+    }
+
+    /// Labels the address, replacing its previous label
+    pub fn insert(&mut self, address: Address, label: impl Into<String>) {
+        self.labels.insert(address, label.into());
+    }
+
+    pub fn get(&self, address: Address) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// The label of the address, or its short form (see [`short_address`])
+    pub fn format(&self, address: Address) -> String {
+        match self.get(address) {
+            Some(label) => label.to_owned(),
+            None => short_address(address),
+        }
+    }
+}
+
+/// The address book used by the summaries, starting as [`AddressBook::known`]
+static ADDRESS_BOOK: Lazy<RwLock<AddressBook>> = Lazy::new(|| RwLock::new(AddressBook::known()));
+
+/// Labels the address in the summaries from now on, e.g. with the labels of a config
+pub fn register(address: Address, label: impl Into<String>) {
+    ADDRESS_BOOK.write().unwrap().insert(address, label);
+}
+
+/// The label of the address, if it is known or was registered
+pub fn label(address: Address) -> Option<String> {
+    ADDRESS_BOOK.read().unwrap().get(address).map(str::to_owned)
+}
+
+/// The label of the address, or its short form, e.g. `OpenSea Wallet` or `0x3ee4…c20b`
+pub fn format_address(address: Address) -> String {
+    ADDRESS_BOOK.read().unwrap().format(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_the_known_and_registered_addresses() {
+        assert_eq!(
+            format_address(*constants::OPENSEA_FEE_RECIPIENT),
+            "OpenSea Wallet"
+        );
+        let bayc = "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d"
+            .parse()
+            .unwrap();
+        assert_eq!(label(bayc).as_deref(), Some("Bored Ape Yacht Club"));
+
+        let vault = Address::random();
+        assert_eq!(format_address(vault), short_address(vault));
+        register(vault, "Vault");
+        assert_eq!(format_address(vault), "Vault");
+    }
+}
@@ -0,0 +1,168 @@
+//! Purchases of several listings in a single transaction via the `aggregator.sol`
+//! contract, which reverts all of them if any fails. The aggregator is the maker of
+//! the buy orders, which transfer the NFTs to the buyer directly. Listings priced in
+//! ERC20 tokens cannot be bought through it.
+use ethers::{
+    contract::{builders::ContractCall, ContractFactory},
+    providers::Middleware,
+    types::{Address, Bytes, U256},
+};
+use std::sync::Arc;
+
+use crate::{
+    contracts::{PurchaseAggregator, PURCHASEAGGREGATOR_ABI},
+    BuyArgs, Client, ClientError, OrderProvider,
+};
+
+/// The gas of each `atomicMatch_` in a batch, as for the standalone buy calls
+const GAS_PER_MATCH: u64 = 300_000;
+
+/// Deploys the aggregator of the `exchange`, e.g. the client's
+/// [`Deployment::exchange`](crate::constants::Deployment::exchange), given the bytecode
+/// of `aggregator.sol` as compiled by solc
+pub async fn deploy_aggregator<M: Middleware>(
+    client: Arc<M>,
+    bytecode: Bytes,
+    exchange: Address,
+) -> Result<PurchaseAggregator<M>, ClientError> {
+    let factory = ContractFactory::new(PURCHASEAGGREGATOR_ABI.clone(), bytecode, client.clone());
+    let contract = factory
+        .deploy(exchange)
+        .map_err(|err| ClientError::Contract(err.to_string()))?
+        .send()
+        .await
+        .map_err(|err| ClientError::Contract(err.to_string()))?;
+    Ok(PurchaseAggregator::new(contract.address(), client))
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Same as [`Client::buy`] for each of the tokens, but returns a single call of the
+    /// deployed `aggregator` buying all the listings atomically. It is sent by the
+    /// `taker` of the args, and the NFTs go to their `recipient`.
+    pub async fn buy_batch_atomic(
+        &self,
+        aggregator: Address,
+        args: Vec<BuyArgs>,
+        n: usize,
+    ) -> Result<ContractCall<M, ()>, ClientError> {
+        let chain_id = self.chain_id().await?;
+        let mut spent = U256::zero();
+        let mut calls = Vec::new();
+        let mut values = Vec::new();
+        let mut from = None;
+        for args in args {
+            from = Some(args.taker);
+            let sells = self
+                .cheapest_listings(args.token, args.token_id, n)
+                .await?
+                .into_iter()
+                .filter(|sell| {
+                    let eth = sell.payment_token.is_zero();
+                    if !eth {
+                        say!(
+                            "[Token Id = {:?}] Skipping ERC20 listing {:?}",
+                            args.token_id,
+                            sell.order_hash
+                        );
+                    }
+                    eth
+                })
+                .collect();
+
+            // the aggregator makes the buy orders
+            let args = BuyArgs {
+                taker: aggregator,
+                ..args
+            };
+            for buy in self.buy_orders(&args, sells, chain_id, &mut spent).await? {
+                let call = buy.call;
+                let data = call
+                    .calldata()
+                    .ok_or_else(|| ClientError::Contract("buy call without data".to_owned()))?;
+                calls.push(data.to_vec());
+                values.push(call.tx.value().copied().unwrap_or_default());
+            }
+        }
+
+        let value = values.iter().fold(U256::zero(), |acc, value| acc + value);
+        let gas = GAS_PER_MATCH * calls.len() as u64 + 50_000;
+        let aggregator = PurchaseAggregator::new(aggregator, self.provider.clone());
+        let call = aggregator
+            .method::<_, ()>("batchBuy", (calls, values))
+            .map_err(|err| ClientError::Contract(err.to_string()))?
+            .value(value)
+            .gas(gas);
+        Ok(match from {
+            Some(from) => call.from(from),
+            None => call,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::{
+        abi::{self, ParamType, Token},
+        providers::Provider,
+        types::U64,
+    };
+
+    #[tokio::test]
+    async fn batches_purchases_in_a_single_call() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let mut client = Client::with_api(Arc::new(provider), api);
+        client.check_fulfillable = false;
+
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (aggregator, taker) = (Address::random(), Address::random());
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+
+        let call = client
+            .buy_batch_atomic(aggregator, vec![args], 5)
+            .await
+            .unwrap();
+        assert_eq!(call.tx.to(), Some(&aggregator.into()));
+        assert_eq!(call.tx.from(), Some(&taker));
+        assert_eq!(call.tx.value(), Some(&sell.current_price));
+
+        let data = call.calldata().unwrap();
+        let tokens = abi::decode(
+            &[
+                ParamType::Array(Box::new(ParamType::Bytes)),
+                ParamType::Array(Box::new(ParamType::Uint(256))),
+            ],
+            &data.as_ref()[4..],
+        )
+        .unwrap();
+        assert_eq!(
+            tokens[1],
+            Token::Array(vec![Token::Uint(sell.current_price)])
+        );
+        let calls = tokens[0].clone().into_array().unwrap();
+        assert_eq!(calls.len(), 1);
+        let atomic_match = calls[0].clone().into_bytes().unwrap();
+        assert_eq!(
+            atomic_match[..4],
+            client
+                .contracts
+                .abi()
+                .function("atomicMatch_")
+                .unwrap()
+                .short_signature()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use ethers::types::{H256, U256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// The capital assigned to a strategy
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Allocation {
+    /// Relative weight used when splitting (and re-splitting) the capital
+    pub weight: u64,
+    /// The budget slice of the strategy
+    pub budget: U256,
+    /// Capital spent on committed purchases
+    pub spent: U256,
+    /// Capital held by pending order reservations
+    pub reserved: U256,
+}
+
+impl Allocation {
+    fn used(&self) -> U256 {
+        self.spent + self.reserved
+    }
+
+    /// The capital still available to the strategy
+    pub fn available(&self) -> U256 {
+        self.budget.saturating_sub(self.used())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AllocationError {
+    #[error("Unknown strategy {0}")]
+    UnknownStrategy(String),
+    #[error("Order {order_hash:?} is already reserved by strategy {strategy}")]
+    OrderTaken { order_hash: H256, strategy: String },
+    #[error("Strategy {strategy} has {available} Wei available, {required} Wei required")]
+    InsufficientBudget {
+        strategy: String,
+        available: U256,
+        required: U256,
+    },
+}
+
+#[derive(Debug, Default)]
+struct AllocatorState {
+    total: U256,
+    strategies: HashMap<String, Allocation>,
+    /// order hash -> strategy which reserved it
+    orders: HashMap<H256, String>,
+}
+
+impl AllocatorState {
+    /// Splits the capital not yet spent across all strategies by weight
+    fn split(&mut self) {
+        let total_weight: u64 = self.strategies.values().map(|s| s.weight).sum();
+        if total_weight == 0 {
+            return;
+        }
+        let spent = self
+            .strategies
+            .values()
+            .fold(U256::zero(), |acc, s| acc + s.spent);
+        let remaining = self.total.saturating_sub(spent);
+        for allocation in self.strategies.values_mut() {
+            allocation.budget = allocation.spent + remaining * allocation.weight / total_weight;
+        }
+    }
+}
+
+/// Assigns each of several concurrently running strategies a slice of the capital,
+/// and prevents them from competing for the same orders. Cloning it shares the
+/// underlying state.
+#[derive(Clone, Debug, Default)]
+pub struct CapitalAllocator {
+    state: Arc<Mutex<AllocatorState>>,
+}
+
+impl CapitalAllocator {
+    /// Instantiates the allocator with the total capital (in Wei) to split
+    pub fn new(total: U256) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(AllocatorState {
+                total,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Registers a strategy with a relative weight, re-splitting the unspent capital
+    pub fn add_strategy(&self, name: impl Into<String>, weight: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.strategies.insert(
+            name.into(),
+            Allocation {
+                weight,
+                ..Default::default()
+            },
+        );
+        state.split();
+    }
+
+    /// Returns the current allocation of the strategy
+    pub fn allocation(&self, strategy: &str) -> Option<Allocation> {
+        self.state.lock().unwrap().strategies.get(strategy).cloned()
+    }
+
+    /// Reserves the order and its price out of the strategy's budget. The reservation
+    /// is released when dropped unless it is committed.
+    pub fn reserve(
+        &self,
+        strategy: &str,
+        order_hash: H256,
+        price: U256,
+    ) -> Result<OrderReservation, AllocationError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(owner) = state.orders.get(&order_hash) {
+            return Err(AllocationError::OrderTaken {
+                order_hash,
+                strategy: owner.clone(),
+            });
+        }
+
+        let allocation = state
+            .strategies
+            .get_mut(strategy)
+            .ok_or_else(|| AllocationError::UnknownStrategy(strategy.to_owned()))?;
+        let available = allocation.available();
+        if available < price {
+            return Err(AllocationError::InsufficientBudget {
+                strategy: strategy.to_owned(),
+                available,
+                required: price,
+            });
+        }
+        allocation.reserved += price;
+        state.orders.insert(order_hash, strategy.to_owned());
+
+        Ok(OrderReservation {
+            allocator: self.clone(),
+            strategy: strategy.to_owned(),
+            order_hash,
+            price,
+            committed: false,
+        })
+    }
+
+    /// Moves half of the unused budget of every strategy which used less than
+    /// `underuse_bps` (in basis points) of its budget to the other strategies, by weight
+    pub fn rebalance(&self, underuse_bps: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        let underusers = state
+            .strategies
+            .iter()
+            .filter(|(_, allocation)| allocation.used() * 10_000 < allocation.budget * underuse_bps)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let receivers_weight: u64 = state
+            .strategies
+            .iter()
+            .filter(|(name, _)| !underusers.contains(name))
+            .map(|(_, allocation)| allocation.weight)
+            .sum();
+        if underusers.is_empty() || receivers_weight == 0 {
+            return;
+        }
+
+        let mut freed = U256::zero();
+        for name in &underusers {
+            let allocation = state.strategies.get_mut(name).expect("strategy exists");
+            let released = allocation.available() / 2;
+            allocation.budget -= released;
+            freed += released;
+        }
+
+        for (name, allocation) in state.strategies.iter_mut() {
+            if !underusers.contains(name) {
+                allocation.budget += freed * allocation.weight / receivers_weight;
+            }
+        }
+    }
+
+    fn release(&self, strategy: &str, order_hash: H256, price: U256, spent: bool) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(allocation) = state.strategies.get_mut(strategy) {
+            allocation.reserved = allocation.reserved.saturating_sub(price);
+            if spent {
+                allocation.spent += price;
+            }
+        }
+        // spent orders stay reserved so that no other strategy picks them up
+        if !spent {
+            state.orders.remove(&order_hash);
+        }
+    }
+}
+
+/// An order reserved by a strategy, holding its price out of the strategy's budget
+#[derive(Debug)]
+pub struct OrderReservation {
+    allocator: CapitalAllocator,
+    strategy: String,
+    order_hash: H256,
+    price: U256,
+    committed: bool,
+}
+
+impl OrderReservation {
+    pub fn order_hash(&self) -> H256 {
+        self.order_hash
+    }
+
+    /// Marks the reserved capital as spent
+    pub fn commit(mut self) {
+        self.allocator
+            .release(&self.strategy, self.order_hash, self.price, true);
+        self.committed = true;
+    }
+}
+
+impl Drop for OrderReservation {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.allocator
+                .release(&self.strategy, self.order_hash, self.price, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_do_not_compete() {
+        let allocator = CapitalAllocator::new(100.into());
+        allocator.add_strategy("floor", 3);
+        allocator.add_strategy("rare", 1);
+        assert_eq!(allocator.allocation("floor").unwrap().budget, 75.into());
+        assert_eq!(allocator.allocation("rare").unwrap().budget, 25.into());
+
+        let order = H256::random();
+        let reservation = allocator.reserve("floor", order, 50.into()).unwrap();
+        assert!(matches!(
+            allocator.reserve("rare", order, 10.into()),
+            Err(AllocationError::OrderTaken { .. })
+        ));
+        assert!(matches!(
+            allocator.reserve("floor", H256::random(), 30.into()),
+            Err(AllocationError::InsufficientBudget { .. })
+        ));
+
+        // abandoned reservations free the order and the budget
+        drop(reservation);
+        allocator
+            .reserve("rare", order, 10.into())
+            .unwrap()
+            .commit();
+        assert_eq!(allocator.allocation("rare").unwrap().spent, 10.into());
+        assert!(allocator.reserve("floor", order, 10.into()).is_err());
+    }
+
+    #[test]
+    fn rebalances_underused_capital() {
+        let allocator = CapitalAllocator::new(100.into());
+        allocator.add_strategy("a", 1);
+        allocator.add_strategy("b", 1);
+        allocator
+            .reserve("b", H256::random(), 40.into())
+            .unwrap()
+            .commit();
+
+        // `a` used nothing, half of its 50 moves to `b`
+        allocator.rebalance(5_000);
+        assert_eq!(allocator.allocation("a").unwrap().budget, 25.into());
+        assert_eq!(allocator.allocation("b").unwrap().budget, 75.into());
+    }
+}
@@ -0,0 +1,169 @@
+//! Daily price history of a collection reconstructed from its past sales, e.g. for
+//! backtesting snipe strategies against the floor price
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use ethers::types::{Address, U256};
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+
+use crate::{
+    api::{OpenSeaApiError, SaleEvent},
+    payment_tokens::PaymentTokens,
+    OpenSeaApi,
+};
+
+/// The stats of the sales of a day. Prices are per token, in Wei.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DailyStats {
+    pub date: NaiveDate,
+    /// The cheapest sale
+    pub floor: U256,
+    pub median: U256,
+    /// The sum of the sales' total prices
+    pub volume: U256,
+    pub num_sales: usize,
+}
+
+/// Returns the daily stats of the sales of the collection from the `from` day until
+/// the `to` one, both included. Days without sales are omitted.
+pub async fn daily_history(
+    api: &OpenSeaApi,
+    collection: Address,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<DailyStats>, OpenSeaApiError> {
+    let start = midnight(from);
+    let end = to.succ_opt().map(midnight).unwrap_or(i64::MAX as u64);
+    let mut sales = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = api
+            .get_sale_events(collection, start, end, cursor.as_deref())
+            .await?;
+        sales.extend(page.asset_events);
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    Ok(daily_stats(&sales))
+}
+
+/// Groups the sales by day. Only the sales of single tokens in ETH or WETH are
+/// accounted for, the prices in other tokens not being comparable.
+pub fn daily_stats(sales: &[SaleEvent]) -> Vec<DailyStats> {
+    let tokens = PaymentTokens::default();
+    let mut days: BTreeMap<NaiveDate, Vec<(U256, U256)>> = BTreeMap::new();
+    for sale in sales {
+        let symbol = sale
+            .payment_token
+            .as_ref()
+            .and_then(|token| tokens.get(token.address))
+            .map(|token| token.symbol.as_str());
+        if sale.asset.is_none() || !matches!(symbol, Some("ETH") | Some("WETH")) {
+            continue;
+        }
+        let date = match NaiveDateTime::parse_from_str(&sale.created_date, "%Y-%m-%dT%H:%M:%S%.f") {
+            Ok(time) => time.date(),
+            Err(_) => continue,
+        };
+        let quantity = sale.quantity.max(U256::one());
+        days.entry(date)
+            .or_default()
+            .push((sale.total_price / quantity, sale.total_price));
+    }
+
+    days.into_iter()
+        .map(|(date, mut sales)| {
+            sales.sort();
+            let mid = sales.len() / 2;
+            let median = if sales.len() % 2 == 0 {
+                (sales[mid - 1].0 + sales[mid].0) / 2
+            } else {
+                sales[mid].0
+            };
+            DailyStats {
+                date,
+                floor: sales[0].0,
+                median,
+                volume: sales
+                    .iter()
+                    .fold(U256::zero(), |acc, (_, total)| acc + total),
+                num_sales: sales.len(),
+            }
+        })
+        .collect()
+}
+
+fn midnight(date: NaiveDate) -> u64 {
+    let time = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+    Utc.from_utc_datetime(&time).timestamp() as u64
+}
+
+/// Writes the stats as CSV, with a header line
+pub fn write_csv<W: Write>(stats: &[DailyStats], mut writer: W) -> Result<(), OpenSeaApiError> {
+    writeln!(writer, "date,floor,median,volume,num_sales")?;
+    for day in stats {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            day.date, day.floor, day.median, day.volume, day.num_sales
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the stats to a CSV file
+pub fn write_csv_file(stats: &[DailyStats], path: impl AsRef<Path>) -> Result<(), OpenSeaApiError> {
+    write_csv(stats, File::create(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_daily_sales() {
+        let sale = |date: &str, price: u64, quantity: u64, token: &str| {
+            serde_json::from_value::<SaleEvent>(serde_json::json!({
+                "asset": { "token_id": "1" },
+                "total_price": price.to_string(),
+                "payment_token": { "address": token, "symbol": "", "decimals": 18 },
+                "quantity": quantity.to_string(),
+                "created_date": date,
+            }))
+            .unwrap()
+        };
+        let eth = "0x0000000000000000000000000000000000000000";
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+        let dai = "0x6b175474e89094c44da98b954eedeac495271d0f";
+        let mut bundle = sale("2021-08-18T10:00:00", 1, 1, eth);
+        bundle.asset = None;
+        let sales = vec![
+            sale("2021-08-18T15:25:15.123456", 300, 1, eth),
+            sale("2021-08-18T09:00:00", 100, 1, weth),
+            sale("2021-08-18T23:59:59", 400, 2, eth),
+            sale("2021-08-18T12:00:00", 5, 1, dai),
+            bundle,
+            sale("2021-08-20T00:00:00", 50, 1, eth),
+        ];
+
+        let stats = daily_stats(&sales);
+        assert_eq!(
+            stats[0],
+            DailyStats {
+                date: NaiveDate::from_ymd_opt(2021, 8, 18).unwrap(),
+                floor: 100.into(),
+                median: 200.into(),
+                volume: 800.into(),
+                num_sales: 3,
+            }
+        );
+        assert_eq!(stats[1].date, NaiveDate::from_ymd_opt(2021, 8, 20).unwrap());
+        assert_eq!(stats.len(), 2);
+
+        let mut csv = Vec::new();
+        write_csv(&stats, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(1).unwrap(), "2021-08-18,100,200,800,3");
+        assert_eq!(midnight(stats[1].date), 1_629_417_600);
+    }
+}
@@ -0,0 +1,220 @@
+//! The metadata of a single asset (its name, traits and media), see
+//! [`OpenSeaApi::get_asset_metadata`], and a cache of its media on disk, see
+//! [`MediaCache`]
+use ethers::{
+    core::utils::keccak256,
+    types::{Address, U256},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{OpenSeaApi, OpenSeaApiError};
+use crate::types::{u256_from_dec_str, ApiVersion, AssetTrait};
+
+/// The gateway the `ipfs://` media are downloaded through
+const IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+
+/// The name, traits and media of an asset as returned by the asset API. The fields the
+/// collection does not set are `None`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetDetails {
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub token_id: U256,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub traits: Vec<AssetTrait>,
+    /// The image as resized by OpenSea
+    #[serde(default)]
+    pub image_url: Option<String>,
+    /// The image as set by the collection, e.g. on IPFS
+    #[serde(default)]
+    pub image_original_url: Option<String>,
+    #[serde(default)]
+    pub animation_url: Option<String>,
+    #[serde(default)]
+    pub animation_original_url: Option<String>,
+    /// The page of the asset on OpenSea
+    #[serde(default)]
+    pub permalink: Option<String>,
+}
+
+impl AssetDetails {
+    /// The URLs of the image and the animation, OpenSea's copies first as the original
+    /// hosts are often slow or gone
+    pub fn media_urls(&self) -> Vec<&str> {
+        let image = self.image_url.as_ref().or(self.image_original_url.as_ref());
+        let animation = self
+            .animation_url
+            .as_ref()
+            .or(self.animation_original_url.as_ref());
+        image
+            .into_iter()
+            .chain(animation)
+            .map(String::as_str)
+            .filter(|url| !url.is_empty())
+            .collect()
+    }
+}
+
+impl OpenSeaApi {
+    /// Returns the name, traits and media URLs of the token
+    pub async fn get_asset_metadata(
+        &self,
+        contract: Address,
+        token_id: U256,
+    ) -> Result<AssetDetails, OpenSeaApiError> {
+        let url = format!(
+            "{}/asset/{:?}/{}/",
+            self.api_url(ApiVersion::V1),
+            contract,
+            token_id
+        );
+        let text = self.send("asset", self.client.get(url)).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// Downloads media to a directory, each URL once: a URL already downloaded is served
+/// from the directory. The requests are not sent with the API key.
+#[derive(Clone, Debug)]
+pub struct MediaCache {
+    dir: PathBuf,
+    client: reqwest::Client,
+}
+
+impl MediaCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Downloads with the given client instead, e.g. to set a timeout or a proxy
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the media of the URL is saved: the hash of the URL, with the extension of
+    /// its path if any
+    pub fn path(&self, url: &str) -> PathBuf {
+        let name = hex::encode(&keccak256(url.as_bytes())[..16]);
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        // the host is not a file name
+        let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+        let extension = path
+            .split_once('/')
+            .and_then(|(_, path)| path.rsplit('/').next())
+            .and_then(|file| file.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+            .filter(|extension| {
+                extension.len() <= 5 && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+        match extension {
+            Some(extension) => self.dir.join(format!("{}.{}", name, extension)),
+            None => self.dir.join(name),
+        }
+    }
+
+    /// Returns the path of the media of the URL, downloading it unless it was already.
+    /// `ipfs://` URLs are downloaded through a public gateway.
+    pub async fn download(&self, url: &str) -> Result<PathBuf, OpenSeaApiError> {
+        let path = self.path(url);
+        if path.exists() {
+            return Ok(path);
+        }
+        let source = match url.strip_prefix("ipfs://") {
+            Some(cid) => format!("{}{}", IPFS_GATEWAY, cid.trim_start_matches("ipfs/")),
+            None => url.to_owned(),
+        };
+        let res = self.client.get(&source).send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(OpenSeaApiError::Api {
+                status: status.as_u16(),
+                message: format!("could not download {}", source),
+                body: String::new(),
+            });
+        }
+        let bytes = res.bytes().await?;
+        std::fs::create_dir_all(&self.dir)?;
+        // written aside then moved, so that an interrupted download is not cached
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, &bytes)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    /// Downloads the image and the animation of the asset, see [`AssetDetails::media_urls`]
+    pub async fn download_asset(
+        &self,
+        asset: &AssetDetails,
+    ) -> Result<Vec<PathBuf>, OpenSeaApiError> {
+        let mut paths = Vec::new();
+        for url in asset.media_urls() {
+            paths.push(self.download(url).await?);
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn parses_and_caches_the_media() {
+        let asset: AssetDetails = serde_json::from_value(serde_json::json!({
+            "id": 1234,
+            "token_id": "87",
+            "name": "Ape #87",
+            "description": null,
+            "image_url": "https://lh3.googleusercontent.com/abc",
+            "image_original_url": "ipfs://QmYx/87.png",
+            "animation_url": null,
+            "animation_original_url": "",
+            "traits": [
+                { "trait_type": "Fur", "value": "Golden" },
+                { "trait_type": "Level", "value": 3 }
+            ],
+            "permalink": "https://opensea.io/assets/0x.../87"
+        }))
+        .unwrap();
+        assert_eq!(asset.token_id, 87.into());
+        assert_eq!(asset.name.as_deref(), Some("Ape #87"));
+        assert_eq!(asset.traits.len(), 2);
+        assert_eq!(
+            asset.media_urls(),
+            vec!["https://lh3.googleusercontent.com/abc"]
+        );
+
+        let dir = std::env::temp_dir().join(format!("opensea-media-{}", std::process::id()));
+        let cache = MediaCache::new(&dir);
+        let png = cache.path("ipfs://QmYx/87.png?size=large");
+        assert_eq!(png.extension().unwrap(), "png");
+        assert_eq!(png, cache.path("ipfs://QmYx/87.png?size=large"));
+        assert_ne!(png, cache.path("ipfs://QmYx/88.png"));
+        assert_eq!(
+            cache.path("https://example.com/a.b/image").extension(),
+            None
+        );
+        assert_eq!(cache.path("https://example.com").extension(), None);
+
+        // served from the directory without a request, nothing listening there
+        let url = "http://127.0.0.1:1/87.png";
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(cache.path(url), b"png").unwrap();
+        let downloaded = cache.download(url).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(downloaded.unwrap(), cache.path(url));
+        assert!(cache.download("http://127.0.0.1:1/88.png").await.is_err());
+    }
+}
@@ -0,0 +1,807 @@
+//! The collection and trait offers of the v2 API: Seaport criteria offers for any token
+//! of a collection, or for any token with a given trait. They are built and posted
+//! here once signed elsewhere, and accepted by the holders of a matching token via the
+//! fulfillment data of the API, see
+//! [`Client::accept_collection_offer`](crate::Client::accept_collection_offer).
+use ethers::{
+    abi::{encode, Token},
+    core::utils::id,
+    types::{Address, Bytes, H256, U256},
+};
+use reqwest::header;
+use serde::{de, Deserialize, Serialize};
+
+use super::{OpenSeaApi, OpenSeaApiError};
+use crate::types::{u256_from_dec_str, ApiVersion};
+
+/// The Seaport `OfferItem` tuple
+const OFFER_ITEM: &str = "(uint8,address,uint256,uint256,uint256)";
+/// The Seaport `ConsiderationItem` tuple
+const CONSIDERATION_ITEM: &str = "(uint8,address,uint256,uint256,uint256,address)";
+/// The Seaport `CriteriaResolver` tuple
+const CRITERIA_RESOLVER: &str = "(uint256,uint8,uint256,uint256,bytes32[])";
+/// The Seaport `Fulfillment` tuple, made of `FulfillmentComponent`s
+const FULFILLMENT: &str = "((uint256,uint256)[],(uint256,uint256)[])";
+
+/// The Seaport `AdvancedOrder` tuple
+fn advanced_order() -> String {
+    format!(
+        "((address,address,{}[],{}[],uint8,uint256,uint256,bytes32,uint256,bytes32,uint256),uint120,uint120,bytes,bytes)",
+        OFFER_ITEM, CONSIDERATION_ITEM
+    )
+}
+
+/// The signature of Seaport's `fulfillAdvancedOrder`
+pub fn fulfill_advanced_order() -> String {
+    format!(
+        "fulfillAdvancedOrder({},{}[],bytes32,address)",
+        advanced_order(),
+        CRITERIA_RESOLVER
+    )
+}
+
+/// The signature of Seaport's `matchAdvancedOrders`
+pub fn match_advanced_orders() -> String {
+    format!(
+        "matchAdvancedOrders({}[],{}[],{}[],address)",
+        advanced_order(),
+        CRITERIA_RESOLVER,
+        FULFILLMENT
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionSlug {
+    pub slug: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractCriteria {
+    pub address: Address,
+}
+
+/// The trait the tokens must have, e.g. `Background: Blue`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraitCriteria {
+    #[serde(rename = "type")]
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// The tokens an offer is for
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfferCriteria {
+    pub collection: CollectionSlug,
+    pub contract: ContractCriteria,
+    /// Set for the trait offers
+    #[serde(rename = "trait", default, skip_serializing_if = "Option::is_none")]
+    pub trait_criteria: Option<TraitCriteria>,
+}
+
+/// The price of an offer, for its whole quantity
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfferPrice {
+    /// e.g. `WETH`
+    pub currency: String,
+    pub decimals: u8,
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub value: U256,
+}
+
+/// A collection or trait offer as returned by the v2 API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionOffer {
+    pub order_hash: H256,
+    /// e.g. `ethereum`
+    pub chain: String,
+    pub criteria: OfferCriteria,
+    #[serde(default)]
+    pub price: Option<OfferPrice>,
+    /// The Seaport contract
+    pub protocol_address: Address,
+    /// The Seaport order parameters and signature
+    pub protocol_data: serde_json::Value,
+}
+
+impl CollectionOffer {
+    pub fn is_trait_offer(&self) -> bool {
+        self.criteria.trait_criteria.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionOffersPage {
+    pub offers: Vec<CollectionOffer>,
+    /// The cursor of the next page, if any
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// The offer to be built by the API, whose Seaport parameters are then completed and
+/// signed by the offerer
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildOfferRequest {
+    pub offerer: Address,
+    /// The number of tokens the offer buys
+    pub quantity: u64,
+    pub criteria: OfferCriteria,
+    pub protocol_address: Address,
+}
+
+/// A signed collection or trait offer, to be posted to the API
+#[derive(Clone, Debug, Serialize)]
+pub struct CollectionOfferPayload {
+    /// The Seaport order `parameters` and `signature`
+    pub protocol_data: serde_json::Value,
+    pub criteria: OfferCriteria,
+    pub protocol_address: Address,
+}
+
+/// An extra consideration item paid by the fulfiller on top of the order's, e.g. a
+/// referral or charity tip, see [`FulfillmentTransaction::with_tips`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tip {
+    /// The ERC20 paid, or the zero address for ether
+    pub token: Address,
+    pub amount: U256,
+    pub recipient: Address,
+}
+
+impl Tip {
+    /// The Seaport item type of the tip: `NATIVE` or `ERC20`
+    fn item_type(&self) -> U256 {
+        if self.token.is_zero() {
+            U256::zero()
+        } else {
+            U256::one()
+        }
+    }
+}
+
+/// The Seaport call accepting an offer, as returned by the API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FulfillmentData {
+    /// e.g. `seaport1.5`
+    pub protocol: String,
+    pub fulfillment_data: Fulfillment,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fulfillment {
+    pub transaction: FulfillmentTransaction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FulfillmentTransaction {
+    /// The signature of the Seaport function to call
+    pub function: String,
+    pub chain: u64,
+    pub to: Address,
+    #[serde(deserialize_with = "u256_lenient")]
+    pub value: U256,
+    /// The arguments of the function, by name
+    pub input_data: serde_json::Value,
+}
+
+impl FulfillmentTransaction {
+    /// Appends the tips to the consideration of the fulfilled order, adding the ether
+    /// ones to the value of the call. Seaport only takes tips in `fulfillAdvancedOrder`,
+    /// after the `totalOriginalConsiderationItems` signed by the offerer, so this errors
+    /// with `OpenSeaApiError::TipsNotAllowed` for the other calls, or if the order
+    /// already has fewer items than it was signed with.
+    pub fn with_tips(mut self, tips: &[Tip]) -> Result<Self, OpenSeaApiError> {
+        if tips.is_empty() {
+            return Ok(self);
+        }
+        if self.function != fulfill_advanced_order() {
+            return Err(OpenSeaApiError::TipsNotAllowed(format!(
+                "{} takes no extra consideration items",
+                self.function
+            )));
+        }
+        let input: FulfillAdvancedOrderInput = serde_json::from_value(self.input_data.clone())?;
+        let parameters = &input.advanced_order.parameters;
+        let original = parameters.total_original_consideration_items;
+        if original > parameters.consideration.len().into() {
+            return Err(OpenSeaApiError::TipsNotAllowed(format!(
+                "the order has {} consideration items, less than its {} original ones",
+                parameters.consideration.len(),
+                original
+            )));
+        }
+
+        let consideration = self
+            .input_data
+            .pointer_mut("/advancedOrder/parameters/consideration")
+            .and_then(serde_json::Value::as_array_mut)
+            .expect("the order was parsed");
+        for tip in tips {
+            consideration.push(serde_json::json!({
+                "itemType": tip.item_type().as_u64(),
+                "token": tip.token,
+                "identifierOrCriteria": "0",
+                "startAmount": tip.amount.to_string(),
+                "endAmount": tip.amount.to_string(),
+                "recipient": tip.recipient,
+            }));
+            if tip.token.is_zero() {
+                self.value += tip.amount;
+            }
+        }
+        Ok(self)
+    }
+
+    /// The calldata of the call. Only `fulfillAdvancedOrder` and `matchAdvancedOrders`
+    /// are supported, erroring with `OpenSeaApiError::UnsupportedFulfillment` otherwise.
+    pub fn calldata(&self) -> Result<Bytes, OpenSeaApiError> {
+        let args = if self.function == fulfill_advanced_order() {
+            let input: FulfillAdvancedOrderInput = serde_json::from_value(self.input_data.clone())?;
+            vec![
+                input.advanced_order.into_token(),
+                Token::Array(into_tokens(input.criteria_resolvers)),
+                Token::FixedBytes(input.fulfiller_conduit_key.as_bytes().to_vec()),
+                Token::Address(input.recipient),
+            ]
+        } else if self.function == match_advanced_orders() {
+            let input: MatchAdvancedOrdersInput = serde_json::from_value(self.input_data.clone())?;
+            vec![
+                Token::Array(into_tokens(input.orders)),
+                Token::Array(into_tokens(input.criteria_resolvers)),
+                Token::Array(into_tokens(input.fulfillments)),
+                Token::Address(input.recipient),
+            ]
+        } else {
+            return Err(OpenSeaApiError::UnsupportedFulfillment(
+                self.function.clone(),
+            ));
+        };
+        Ok([&id(&self.function)[..], &encode(&args)].concat().into())
+    }
+}
+
+/// The Seaport structs of the fulfillment's `input_data`, as tokens of the call
+trait IntoToken {
+    fn into_token(self) -> Token;
+}
+
+fn into_tokens<T: IntoToken>(items: Vec<T>) -> Vec<Token> {
+    items.into_iter().map(IntoToken::into_token).collect()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FulfillAdvancedOrderInput {
+    advanced_order: AdvancedOrder,
+    criteria_resolvers: Vec<CriteriaResolver>,
+    fulfiller_conduit_key: H256,
+    recipient: Address,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MatchAdvancedOrdersInput {
+    orders: Vec<AdvancedOrder>,
+    criteria_resolvers: Vec<CriteriaResolver>,
+    fulfillments: Vec<SeaportFulfillment>,
+    recipient: Address,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdvancedOrder {
+    parameters: OrderParameters,
+    #[serde(deserialize_with = "u256_lenient")]
+    numerator: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    denominator: U256,
+    signature: Bytes,
+    #[serde(default)]
+    extra_data: Bytes,
+}
+
+impl IntoToken for AdvancedOrder {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            self.parameters.into_token(),
+            Token::Uint(self.numerator),
+            Token::Uint(self.denominator),
+            Token::Bytes(self.signature.to_vec()),
+            Token::Bytes(self.extra_data.to_vec()),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrderParameters {
+    offerer: Address,
+    zone: Address,
+    offer: Vec<OfferItem>,
+    consideration: Vec<ConsiderationItem>,
+    #[serde(deserialize_with = "u256_lenient")]
+    order_type: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    start_time: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    end_time: U256,
+    zone_hash: H256,
+    #[serde(deserialize_with = "u256_lenient")]
+    salt: U256,
+    conduit_key: H256,
+    #[serde(deserialize_with = "u256_lenient")]
+    total_original_consideration_items: U256,
+}
+
+impl IntoToken for OrderParameters {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Address(self.offerer),
+            Token::Address(self.zone),
+            Token::Array(into_tokens(self.offer)),
+            Token::Array(into_tokens(self.consideration)),
+            Token::Uint(self.order_type),
+            Token::Uint(self.start_time),
+            Token::Uint(self.end_time),
+            Token::FixedBytes(self.zone_hash.as_bytes().to_vec()),
+            Token::Uint(self.salt),
+            Token::FixedBytes(self.conduit_key.as_bytes().to_vec()),
+            Token::Uint(self.total_original_consideration_items),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OfferItem {
+    #[serde(deserialize_with = "u256_lenient")]
+    item_type: U256,
+    token: Address,
+    #[serde(deserialize_with = "u256_lenient")]
+    identifier_or_criteria: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    start_amount: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    end_amount: U256,
+}
+
+impl IntoToken for OfferItem {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Uint(self.item_type),
+            Token::Address(self.token),
+            Token::Uint(self.identifier_or_criteria),
+            Token::Uint(self.start_amount),
+            Token::Uint(self.end_amount),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConsiderationItem {
+    #[serde(flatten)]
+    item: OfferItem,
+    recipient: Address,
+}
+
+impl IntoToken for ConsiderationItem {
+    fn into_token(self) -> Token {
+        match self.item.into_token() {
+            Token::Tuple(mut tokens) => {
+                tokens.push(Token::Address(self.recipient));
+                Token::Tuple(tokens)
+            }
+            _ => unreachable!("an offer item is a tuple"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CriteriaResolver {
+    #[serde(deserialize_with = "u256_lenient")]
+    order_index: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    side: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    index: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    identifier: U256,
+    #[serde(default)]
+    criteria_proof: Vec<H256>,
+}
+
+impl IntoToken for CriteriaResolver {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Uint(self.order_index),
+            Token::Uint(self.side),
+            Token::Uint(self.index),
+            Token::Uint(self.identifier),
+            Token::Array(
+                self.criteria_proof
+                    .iter()
+                    .map(|node| Token::FixedBytes(node.as_bytes().to_vec()))
+                    .collect(),
+            ),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeaportFulfillment {
+    offer_components: Vec<FulfillmentComponent>,
+    consideration_components: Vec<FulfillmentComponent>,
+}
+
+impl IntoToken for SeaportFulfillment {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Array(into_tokens(self.offer_components)),
+            Token::Array(into_tokens(self.consideration_components)),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FulfillmentComponent {
+    #[serde(deserialize_with = "u256_lenient")]
+    order_index: U256,
+    #[serde(deserialize_with = "u256_lenient")]
+    item_index: U256,
+}
+
+impl IntoToken for FulfillmentComponent {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Uint(self.order_index),
+            Token::Uint(self.item_index),
+        ])
+    }
+}
+
+/// The API serializes the Seaport uints as numbers, decimal strings or hex strings
+fn u256_lenient<'de, D>(deserializer: D) -> Result<U256, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(n) => n
+            .as_u64()
+            .map(U256::from)
+            .ok_or_else(|| de::Error::custom(format!("invalid uint {}", n))),
+        serde_json::Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(de::Error::custom),
+            None => U256::from_dec_str(&s).map_err(de::Error::custom),
+        },
+        value => Err(de::Error::custom(format!("invalid uint {}", value))),
+    }
+}
+
+impl OpenSeaApi {
+    /// Returns a page of the collection offers of the collection with the given slug
+    pub async fn get_collection_offers(
+        &self,
+        slug: &str,
+        cursor: Option<String>,
+    ) -> Result<CollectionOffersPage, OpenSeaApiError> {
+        let url = format!(
+            "{}/offers/collection/{}",
+            self.api_url(ApiVersion::V2),
+            slug
+        );
+        let query: Vec<_> = cursor.into_iter().map(|cursor| ("next", cursor)).collect();
+        let text = self
+            .send("collection_offers", self.client.get(url).query(&query))
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Returns the offers for the tokens of the collection with the given trait
+    pub async fn get_trait_offers(
+        &self,
+        slug: &str,
+        trait_criteria: &TraitCriteria,
+    ) -> Result<CollectionOffersPage, OpenSeaApiError> {
+        let url = format!(
+            "{}/offers/collection/{}/traits",
+            self.api_url(ApiVersion::V2),
+            slug
+        );
+        let query = [
+            ("type", trait_criteria.trait_type.as_str()),
+            ("value", trait_criteria.value.as_str()),
+        ];
+        let text = self
+            .send("trait_offers", self.client.get(url).query(&query))
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Returns the partial Seaport parameters of the offer (e.g. the consideration of
+    /// the criteria and the fees, and the zone), to be completed and signed by the
+    /// offerer, then posted with [`OpenSeaApi::post_collection_offer`]
+    pub async fn build_collection_offer(
+        &self,
+        req: &BuildOfferRequest,
+    ) -> Result<serde_json::Value, OpenSeaApiError> {
+        let url = format!("{}/offers/build", self.api_url(ApiVersion::V2));
+        let text = self.post_json("build_offer", url, req).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Posts the collection or trait offer signed by its offerer, returning it as
+    /// stored by the API
+    pub async fn post_collection_offer(
+        &self,
+        payload: &CollectionOfferPayload,
+    ) -> Result<CollectionOffer, OpenSeaApiError> {
+        let url = format!("{}/offers", self.api_url(ApiVersion::V2));
+        let text = self
+            .post_json("post_collection_offer", url, payload)
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Returns the Seaport call selling the `token_id` of the `nft` collection to the
+    /// offer, sent by the `fulfiller`
+    pub async fn get_fulfillment_data(
+        &self,
+        offer: &CollectionOffer,
+        fulfiller: Address,
+        nft: Address,
+        token_id: U256,
+    ) -> Result<FulfillmentData, OpenSeaApiError> {
+        let url = format!("{}/offers/fulfillment_data", self.api_url(ApiVersion::V2));
+        let body = serde_json::json!({
+            "offer": {
+                "hash": offer.order_hash,
+                "chain": offer.chain,
+                "protocol_address": offer.protocol_address,
+            },
+            "fulfiller": { "address": fulfiller },
+            "consideration": {
+                "asset_contract_address": nft,
+                "token_id": token_id.to_string(),
+            },
+        });
+        let text = self.post_json("fulfillment_data", url, &body).await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    async fn post_json(
+        &self,
+        endpoint: &str,
+        url: String,
+        body: &impl Serialize,
+    ) -> Result<String, OpenSeaApiError> {
+        let req = self
+            .client
+            .post(url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(body)?);
+        self.send(endpoint, req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{decode, ParamType};
+
+    /// The param types of the signatures, as ethabi's reader cannot parse nested tuples
+    fn advanced_order_type() -> ParamType {
+        use ParamType::*;
+        let item = vec![Uint(8), Address, Uint(256), Uint(256), Uint(256)];
+        let consideration = [item.clone(), vec![Address]].concat();
+        let parameters = Tuple(vec![
+            Address,
+            Address,
+            Array(Box::new(Tuple(item))),
+            Array(Box::new(Tuple(consideration))),
+            Uint(8),
+            Uint(256),
+            Uint(256),
+            FixedBytes(32),
+            Uint(256),
+            FixedBytes(32),
+            Uint(256),
+        ]);
+        Tuple(vec![parameters, Uint(120), Uint(120), Bytes, Bytes])
+    }
+
+    fn criteria_resolvers_type() -> ParamType {
+        use ParamType::*;
+        Array(Box::new(Tuple(vec![
+            Uint(256),
+            Uint(8),
+            Uint(256),
+            Uint(256),
+            Array(Box::new(FixedBytes(32))),
+        ])))
+    }
+
+    #[test]
+    fn parses_collection_offers() {
+        let text = r#"{
+            "offers": [{
+                "order_hash": "0x5c369f5a19a33838b80af6f823eb224c192c937a7e9227929a8ad07963b2a279",
+                "chain": "ethereum",
+                "criteria": {
+                    "collection": { "slug": "boredapeyachtclub" },
+                    "contract": { "address": "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d" },
+                    "trait": { "type": "Background", "value": "Blue" }
+                },
+                "price": { "currency": "WETH", "decimals": 18, "value": "10000000000000000" },
+                "protocol_data": { "parameters": {}, "signature": "0x" },
+                "protocol_address": "0x00000000000000adc04c56bf30ac9d3c0aaf14dc"
+            }],
+            "next": "cj0xJnA9MjAyMi0wOC0yMA=="
+        }"#;
+        let page: CollectionOffersPage = serde_json::from_str(text).unwrap();
+        assert!(page.next.is_some());
+        let offer = &page.offers[0];
+        assert!(offer.is_trait_offer());
+        assert_eq!(offer.criteria.collection.slug, "boredapeyachtclub");
+        assert_eq!(offer.price.as_ref().unwrap().value, U256::exp10(16));
+    }
+
+    #[test]
+    fn encodes_the_fulfillment() {
+        let offerer = Address::random();
+        let seller = Address::random();
+        let nft = Address::random();
+        let order = serde_json::json!({
+            "parameters": {
+                "offerer": offerer,
+                "zone": Address::zero(),
+                "offer": [{
+                    "itemType": 1,
+                    "token": Address::random(),
+                    "identifierOrCriteria": "0",
+                    "startAmount": "10000000000000000",
+                    "endAmount": "10000000000000000"
+                }],
+                "consideration": [{
+                    "itemType": 4,
+                    "token": nft,
+                    "identifierOrCriteria": "0",
+                    "startAmount": 1,
+                    "endAmount": 1,
+                    "recipient": offerer
+                }],
+                "orderType": 2,
+                "startTime": "1660989600",
+                "endTime": "1663668000",
+                "zoneHash": H256::zero(),
+                "salt": "0x360c6ebe",
+                "conduitKey": H256::random(),
+                "totalOriginalConsiderationItems": 1
+            },
+            "numerator": 1,
+            "denominator": 1,
+            "signature": "0x1234",
+            "extraData": "0x"
+        });
+        let resolvers = serde_json::json!([{
+            "orderIndex": 0,
+            "side": 1,
+            "index": 0,
+            "identifier": "87",
+            "criteriaProof": []
+        }]);
+        let tip = Tip {
+            token: Address::zero(),
+            amount: U256::exp10(15),
+            recipient: Address::random(),
+        };
+        let mut tx = FulfillmentTransaction {
+            function: fulfill_advanced_order(),
+            chain: 1,
+            to: Address::random(),
+            value: U256::zero(),
+            input_data: serde_json::json!({
+                "advancedOrder": order,
+                "criteriaResolvers": resolvers,
+                "fulfillerConduitKey": H256::zero(),
+                "recipient": seller
+            }),
+        };
+
+        let calldata = tx.calldata().unwrap();
+        assert_eq!(&calldata.as_ref()[..4], &id(&tx.function)[..]);
+        let params = [
+            advanced_order_type(),
+            criteria_resolvers_type(),
+            ParamType::FixedBytes(32),
+            ParamType::Address,
+        ];
+        let args = decode(&params, &calldata.as_ref()[4..]).unwrap();
+        assert_eq!(args[3], Token::Address(seller));
+        let resolver = match args[1] {
+            Token::Array(ref resolvers) => resolvers[0].clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            resolver,
+            Token::Tuple(vec![
+                Token::Uint(0.into()),
+                Token::Uint(1.into()),
+                Token::Uint(0.into()),
+                Token::Uint(87.into()),
+                Token::Array(vec![]),
+            ])
+        );
+
+        let tipped = tx.clone().with_tips(&[tip]).unwrap();
+        assert_eq!(tipped.value, tip.amount);
+        let args = decode(&params, &tipped.calldata().unwrap().as_ref()[4..]).unwrap();
+        let parameters = match args[0] {
+            Token::Tuple(ref order) => match order[0] {
+                Token::Tuple(ref parameters) => parameters.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert_eq!(parameters[10], Token::Uint(1.into()));
+        let consideration = match parameters[3] {
+            Token::Array(ref items) => items.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            consideration[1],
+            Token::Tuple(vec![
+                Token::Uint(0.into()),
+                Token::Address(Address::zero()),
+                Token::Uint(0.into()),
+                Token::Uint(tip.amount),
+                Token::Uint(tip.amount),
+                Token::Address(tip.recipient),
+            ])
+        );
+        let mut truncated = tx.clone();
+        truncated.input_data["advancedOrder"]["parameters"]["totalOriginalConsiderationItems"] =
+            2.into();
+        assert!(matches!(
+            truncated.with_tips(&[tip]),
+            Err(OpenSeaApiError::TipsNotAllowed(_))
+        ));
+
+        tx.function = match_advanced_orders();
+        tx.input_data = serde_json::json!({
+            "orders": [order.clone(), order],
+            "criteriaResolvers": resolvers,
+            "fulfillments": [{
+                "offerComponents": [{ "orderIndex": 0, "itemIndex": 0 }],
+                "considerationComponents": [{ "orderIndex": 1, "itemIndex": 0 }]
+            }],
+            "recipient": seller
+        });
+        let calldata = tx.calldata().unwrap();
+        let component = ParamType::Tuple(vec![ParamType::Uint(256), ParamType::Uint(256)]);
+        let components = ParamType::Array(Box::new(component));
+        let params = [
+            ParamType::Array(Box::new(advanced_order_type())),
+            criteria_resolvers_type(),
+            ParamType::Array(Box::new(ParamType::Tuple(vec![
+                components.clone(),
+                components,
+            ]))),
+            ParamType::Address,
+        ];
+        let args = decode(&params, &calldata.as_ref()[4..]).unwrap();
+        assert!(matches!(args[0], Token::Array(ref orders) if orders.len() == 2));
+        assert!(matches!(
+            tx.clone().with_tips(&[tip]),
+            Err(OpenSeaApiError::TipsNotAllowed(_))
+        ));
+
+        tx.function = "fulfillBasicOrder(bytes)".to_owned();
+        assert!(matches!(
+            tx.calldata(),
+            Err(OpenSeaApiError::UnsupportedFulfillment(_))
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+//! Hooks on the HTTP requests of [`OpenSeaApi`](super::OpenSeaApi), e.g. to inject
+//! custom headers or to log the raw response bodies
+use reqwest::RequestBuilder;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Modifies each request before it is sent
+pub type RequestHook = Arc<dyn Fn(RequestBuilder) -> RequestBuilder + Send + Sync>;
+
+/// Observes each response, successful or not
+pub type ResponseObserver = Arc<dyn Fn(&ApiResponse) + Send + Sync>;
+
+/// A response of the API, as passed to the [`ResponseObserver`]s
+#[derive(Clone, Debug)]
+pub struct ApiResponse<'a> {
+    /// The endpoint, as labelled in the metrics, e.g. `orders` or `seaport_listings`
+    pub endpoint: &'a str,
+    pub status: u16,
+    /// The raw body
+    pub body: &'a str,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    requests: Vec<RequestHook>,
+    responses: Vec<ResponseObserver>,
+}
+
+impl Hooks {
+    pub(crate) fn push_request(&mut self, hook: RequestHook) {
+        self.requests.push(hook);
+    }
+
+    pub(crate) fn push_response(&mut self, observer: ResponseObserver) {
+        self.responses.push(observer);
+    }
+
+    /// Applies the request hooks, in the order they were added
+    pub(crate) fn request(&self, req: RequestBuilder) -> RequestBuilder {
+        self.requests.iter().fold(req, |req, hook| hook(req))
+    }
+
+    pub(crate) fn response(&self, res: &ApiResponse) {
+        for observer in &self.responses {
+            observer(res);
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("requests", &self.requests.len())
+            .field("responses", &self.responses.len())
+            .finish()
+    }
+}
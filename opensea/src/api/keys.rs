@@ -0,0 +1,125 @@
+//! Rotation of several API keys, to stay under the rate limit of each, see
+//! [`OpenSeaApiConfig::api_keys`](super::OpenSeaApiConfig::api_keys). A request
+//! throttled with a 429 is retried with the next key.
+use reqwest::header::HeaderValue;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use super::OpenSeaApiError;
+
+/// How the key of each request is picked out of the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyRotation {
+    /// Each request uses the next key in turn
+    #[default]
+    RoundRobin,
+    /// The requests use the same key until it is throttled, then the key throttled the
+    /// longest ago (or never)
+    LeastRecentlyThrottled,
+}
+
+impl FromStr for KeyRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(KeyRotation::RoundRobin),
+            "least-recently-throttled" => Ok(KeyRotation::LeastRecentlyThrottled),
+            _ => Err(format!("unknown key rotation {}", s)),
+        }
+    }
+}
+
+/// The API keys, along with their rotation state shared by the clones of the API client
+#[derive(Clone, Debug)]
+pub(crate) struct KeyPool {
+    keys: Vec<HeaderValue>,
+    rotation: KeyRotation,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    /// The next key of the round robin
+    next: usize,
+    /// When each key was last throttled
+    throttled_at: Vec<Option<Instant>>,
+}
+
+impl KeyPool {
+    pub(crate) fn new(keys: Vec<String>, rotation: KeyRotation) -> Result<Self, OpenSeaApiError> {
+        let keys = keys
+            .iter()
+            .map(|key| HeaderValue::from_str(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let state = PoolState {
+            next: 0,
+            throttled_at: vec![None; keys.len()],
+        };
+        Ok(Self {
+            keys,
+            rotation,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The index and value of the key of the next request
+    pub(crate) fn select(&self) -> (usize, HeaderValue) {
+        let mut state = self.state.lock().unwrap();
+        let index = match self.rotation {
+            KeyRotation::RoundRobin => {
+                let index = state.next;
+                state.next = (index + 1) % self.keys.len();
+                index
+            }
+            // `None` sorts first, and the first of the least recently throttled is kept
+            KeyRotation::LeastRecentlyThrottled => (0..self.keys.len())
+                .min_by_key(|index| state.throttled_at[*index])
+                .unwrap_or_default(),
+        };
+        (index, self.keys[index].clone())
+    }
+
+    /// Records that the request sent with the key was throttled
+    pub(crate) fn throttled(&self, index: usize) {
+        self.state.lock().unwrap().throttled_at[index] = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<String> {
+        vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]
+    }
+
+    #[test]
+    fn rotates_the_keys() {
+        let pool = KeyPool::new(keys(), KeyRotation::RoundRobin).unwrap();
+        let selected: Vec<_> = (0..4).map(|_| pool.select().0).collect();
+        assert_eq!(selected, vec![0, 1, 2, 0]);
+
+        // the key is kept until throttled, then the one throttled the longest ago
+        let pool = KeyPool::new(keys(), KeyRotation::LeastRecentlyThrottled).unwrap();
+        assert_eq!(pool.select().0, 0);
+        assert_eq!(pool.select().0, 0);
+        pool.throttled(0);
+        assert_eq!(pool.select().0, 1);
+        pool.throttled(1);
+        pool.throttled(2);
+        assert_eq!(pool.select(), (0, HeaderValue::from_static("a")));
+
+        // the clones share the state
+        let clone = pool.clone();
+        clone.throttled(0);
+        assert_eq!(pool.select().0, 1);
+    }
+}
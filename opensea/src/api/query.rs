@@ -0,0 +1,271 @@
+//! Typed query of the orderbook's `/orders` endpoint
+use ethers::types::{Address, H256};
+use serde::{Deserialize, Serialize};
+
+use super::OrderRequest;
+use crate::types::OrderSide;
+
+/// The field by which the orders are sorted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderBy {
+    CreatedDate,
+    EthPrice,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
+/// Query of the orderbook, with all the filters it supports. Unset filters are not
+/// sent.
+///
+/// ```
+/// use opensea::{api::{OrderBy, OrderDirection, OrderQuery}, types::OrderSide};
+///
+/// let query = OrderQuery::new()
+///     .side(OrderSide::Sell)
+///     .asset_contract_address("0x7d256d82b32d8003d1ca1a1526ed211e6e0da9e2".parse().unwrap())
+///     .bundled(false)
+///     .order_by(OrderBy::EthPrice)
+///     .order_direction(OrderDirection::Asc)
+///     .limit(50);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderQuery {
+    pub order_hash: Option<H256>,
+    pub side: Option<OrderSide>,
+    pub asset_contract_address: Option<Address>,
+    /// Sent as a repeated `token_ids` parameter
+    pub token_ids: Vec<String>,
+    pub maker: Option<Address>,
+    pub taker: Option<Address>,
+    pub payment_token_address: Option<Address>,
+    pub is_english: Option<bool>,
+    pub bundled: Option<bool>,
+    /// Unix timestamp
+    pub listed_after: Option<u64>,
+    /// Unix timestamp
+    pub listed_before: Option<u64>,
+    pub order_by: Option<OrderBy>,
+    pub order_direction: Option<OrderDirection>,
+    /// At most 50
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl OrderQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn order_hash(mut self, order_hash: H256) -> Self {
+        self.order_hash = Some(order_hash);
+        self
+    }
+
+    pub fn side(mut self, side: OrderSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    pub fn asset_contract_address(mut self, address: Address) -> Self {
+        self.asset_contract_address = Some(address);
+        self
+    }
+
+    pub fn token_id(mut self, token_id: impl ToString) -> Self {
+        self.token_ids.push(token_id.to_string());
+        self
+    }
+
+    pub fn token_ids<T: ToString>(mut self, token_ids: impl IntoIterator<Item = T>) -> Self {
+        self.token_ids
+            .extend(token_ids.into_iter().map(|id| id.to_string()));
+        self
+    }
+
+    pub fn maker(mut self, maker: Address) -> Self {
+        self.maker = Some(maker);
+        self
+    }
+
+    pub fn taker(mut self, taker: Address) -> Self {
+        self.taker = Some(taker);
+        self
+    }
+
+    /// The zero address is ETH
+    pub fn payment_token_address(mut self, token: Address) -> Self {
+        self.payment_token_address = Some(token);
+        self
+    }
+
+    pub fn is_english(mut self, is_english: bool) -> Self {
+        self.is_english = Some(is_english);
+        self
+    }
+
+    pub fn bundled(mut self, bundled: bool) -> Self {
+        self.bundled = Some(bundled);
+        self
+    }
+
+    pub fn listed_after(mut self, timestamp: u64) -> Self {
+        self.listed_after = Some(timestamp);
+        self
+    }
+
+    pub fn listed_before(mut self, timestamp: u64) -> Self {
+        self.listed_before = Some(timestamp);
+        self
+    }
+
+    pub fn order_by(mut self, order_by: OrderBy) -> Self {
+        self.order_by = Some(order_by);
+        self
+    }
+
+    pub fn order_direction(mut self, direction: OrderDirection) -> Self {
+        self.order_direction = Some(direction);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// The url query parameters of the set filters
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let address = |address: &Address| format!("{:?}", address);
+        let mut params = Vec::new();
+        let mut push = |key, value: Option<String>| {
+            if let Some(value) = value {
+                params.push((key, value));
+            }
+        };
+        push(
+            "order_hash",
+            self.order_hash.map(|hash| format!("{:?}", hash)),
+        );
+        push("side", self.side.map(|side| u8::from(side).to_string()));
+        push(
+            "asset_contract_address",
+            self.asset_contract_address.as_ref().map(address),
+        );
+        for token_id in &self.token_ids {
+            push("token_ids", Some(token_id.clone()));
+        }
+        push("maker", self.maker.as_ref().map(address));
+        push("taker", self.taker.as_ref().map(address));
+        push(
+            "payment_token_address",
+            self.payment_token_address.as_ref().map(address),
+        );
+        push("is_english", self.is_english.map(|value| value.to_string()));
+        push("bundled", self.bundled.map(|value| value.to_string()));
+        push(
+            "listed_after",
+            self.listed_after.map(|value| value.to_string()),
+        );
+        push(
+            "listed_before",
+            self.listed_before.map(|value| value.to_string()),
+        );
+        push(
+            "order_by",
+            self.order_by.map(|order_by| match order_by {
+                OrderBy::CreatedDate => "created_date".to_owned(),
+                OrderBy::EthPrice => "eth_price".to_owned(),
+            }),
+        );
+        push(
+            "order_direction",
+            self.order_direction.map(|direction| match direction {
+                OrderDirection::Asc => "asc".to_owned(),
+                OrderDirection::Desc => "desc".to_owned(),
+            }),
+        );
+        push("limit", self.limit.map(|value| value.to_string()));
+        push("offset", self.offset.map(|value| value.to_string()));
+        params
+    }
+}
+
+impl From<OrderRequest> for OrderQuery {
+    fn from(req: OrderRequest) -> Self {
+        OrderQuery::new()
+            .side(req.side)
+            .asset_contract_address(req.contract_address)
+            .token_ids(req.token_ids)
+            .limit(req.limit)
+            .offset(req.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_set_filters() {
+        let maker = Address::repeat_byte(0x11);
+        let query = OrderQuery::new()
+            .side(OrderSide::Buy)
+            .token_ids([1, 2])
+            .maker(maker)
+            .is_english(false)
+            .listed_after(100)
+            .order_by(OrderBy::EthPrice)
+            .order_direction(OrderDirection::Desc);
+        assert_eq!(
+            query.to_params(),
+            vec![
+                ("side", "0".to_owned()),
+                ("token_ids", "1".to_owned()),
+                ("token_ids", "2".to_owned()),
+                ("maker", format!("{:?}", maker)),
+                ("is_english", "false".to_owned()),
+                ("listed_after", "100".to_owned()),
+                ("order_by", "eth_price".to_owned()),
+                ("order_direction", "desc".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_by_order_hash() {
+        let hash = H256::repeat_byte(0xab);
+        let query = OrderQuery::new().order_hash(hash).limit(1);
+        assert_eq!(
+            query.to_params(),
+            vec![
+                ("order_hash", format!("{:?}", hash)),
+                ("limit", "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_order_requests() {
+        let req = OrderRequest {
+            side: OrderSide::Sell,
+            token_ids: Vec::new(),
+            contract_address: Address::zero(),
+            limit: 50,
+            offset: 0,
+        };
+        let query = OrderQuery::from(req);
+        assert!(query.token_ids.is_empty());
+        assert_eq!(query.limit, Some(50));
+    }
+}
@@ -0,0 +1,97 @@
+//! The rate limit quota of the API key, as reported by the `x-ratelimit-*` headers of
+//! the API's responses, for throttling before the requests get rejected
+use reqwest::header::HeaderMap;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Called with the quota once it is nearly exhausted, see
+/// [`OpenSeaApi::with_quota_warning`](super::OpenSeaApi::with_quota_warning)
+pub type QuotaObserver = Arc<dyn Fn(&Quota) + Send + Sync>;
+
+/// Resets later than this are unix timestamps rather than seconds from now
+const MIN_TIMESTAMP: u64 = 1_000_000_000;
+
+/// The quota of the last response which reported one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quota {
+    /// The requests allowed per window
+    pub limit: Option<u64>,
+    /// The requests left in the current window
+    pub remaining: u64,
+    /// The unix timestamp at which the window resets, if reported
+    pub reset_at: Option<u64>,
+}
+
+impl Quota {
+    /// Parses the `x-ratelimit-limit`, `x-ratelimit-remaining` and `x-ratelimit-reset`
+    /// headers. `None` without a remaining count, e.g. for requests without API key.
+    pub fn from_headers(headers: &HeaderMap, now: u64) -> Option<Self> {
+        let header =
+            |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+        let reset_at = header("x-ratelimit-reset").map(|reset| {
+            if reset >= MIN_TIMESTAMP {
+                reset
+            } else {
+                now + reset
+            }
+        });
+        Some(Self {
+            limit: header("x-ratelimit-limit"),
+            remaining: header("x-ratelimit-remaining")?,
+            reset_at,
+        })
+    }
+
+    /// The seconds until the window resets, if reported
+    pub fn resets_in(&self, now: u64) -> Option<u64> {
+        self.reset_at.map(|reset_at| reset_at.saturating_sub(now))
+    }
+}
+
+/// Keeps the last quota, shared by the clones of the API client
+#[derive(Clone, Default)]
+pub(crate) struct QuotaTracker {
+    last: Arc<Mutex<Option<Quota>>>,
+    warnings: Vec<(u64, QuotaObserver)>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn push_warning(&mut self, threshold: u64, observer: QuotaObserver) {
+        self.warnings.push((threshold, observer));
+    }
+
+    pub(crate) fn get(&self) -> Option<Quota> {
+        *self.last.lock().unwrap()
+    }
+
+    /// Records the quota of the response, if any, and calls the observers whose
+    /// threshold it is at or below
+    pub(crate) fn update(&self, headers: &HeaderMap) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let quota = match Quota::from_headers(headers, now) {
+            Some(quota) => quota,
+            None => return,
+        };
+        *self.last.lock().unwrap() = Some(quota);
+        for (threshold, observer) in &self.warnings {
+            if quota.remaining <= *threshold {
+                observer(&quota);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for QuotaTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaTracker")
+            .field("last", &self.get())
+            .field("warnings", &self.warnings.len())
+            .finish()
+    }
+}
@@ -0,0 +1,171 @@
+//! Signed Wyvern orders in the orderbook's JSON format, e.g. handed over by other tools to
+//! be posted with [`OpenSeaApi::post_signed_order`](super::OpenSeaApi::post_signed_order)
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::types::{FeeMethod, HowToCall, MinimalOrder, OrderSide, SaleKind};
+
+/// A signed Wyvern order as accepted by the orderbook and produced by e.g. opensea-js'
+/// `orderToJSON`: camelCase fields, with the uints as decimal strings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedOrder {
+    pub exchange: Address,
+    pub maker: Address,
+    pub taker: Address,
+    pub fee_recipient: Address,
+    pub target: Address,
+    pub static_target: Address,
+    pub payment_token: Address,
+
+    #[serde(with = "dec_str")]
+    pub maker_relayer_fee: U256,
+    #[serde(with = "dec_str")]
+    pub taker_relayer_fee: U256,
+    #[serde(with = "dec_str")]
+    pub maker_protocol_fee: U256,
+    #[serde(with = "dec_str")]
+    pub taker_protocol_fee: U256,
+
+    #[serde(with = "dec_str")]
+    pub base_price: U256,
+    #[serde(with = "dec_str")]
+    pub extra: U256,
+    #[serde(with = "dec_str")]
+    pub listing_time: U256,
+    #[serde(with = "dec_str")]
+    pub expiration_time: U256,
+    #[serde(with = "dec_str")]
+    pub salt: U256,
+
+    pub fee_method: FeeMethod,
+    pub side: OrderSide,
+    pub sale_kind: SaleKind,
+    pub how_to_call: HowToCall,
+
+    pub calldata: Bytes,
+    pub replacement_pattern: Bytes,
+    pub static_extradata: Bytes,
+
+    /// The hash of the fields above, filled in when posting if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<H256>,
+    pub v: u8,
+    pub r: H256,
+    pub s: H256,
+
+    /// The fields which are not hashed, e.g. the `metadata` of the asset or the
+    /// `quantity`, posted as is
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl From<MinimalOrder> for SignedOrder {
+    fn from(order: MinimalOrder) -> Self {
+        Self {
+            hash: Some(order.hash()),
+            exchange: order.exchange,
+            maker: order.maker,
+            taker: order.taker,
+            fee_recipient: order.fee_recipient,
+            target: order.target,
+            static_target: order.static_target,
+            payment_token: order.payment_token,
+            maker_relayer_fee: order.maker_relayer_fee,
+            taker_relayer_fee: order.taker_relayer_fee,
+            maker_protocol_fee: order.maker_protocol_fee,
+            taker_protocol_fee: order.taker_protocol_fee,
+            base_price: order.base_price,
+            extra: order.extra,
+            listing_time: order.listing_time,
+            expiration_time: order.expiration_time,
+            salt: order.salt,
+            fee_method: order.fee_method,
+            side: order.side,
+            sale_kind: order.sale_kind,
+            how_to_call: order.how_to_call,
+            calldata: order.calldata,
+            replacement_pattern: order.replacement_pattern,
+            static_extradata: order.static_extradata,
+            v: order.v,
+            r: order.r,
+            s: order.s,
+            other: Map::new(),
+        }
+    }
+}
+
+impl From<SignedOrder> for MinimalOrder {
+    /// The `current_price` is not part of the payload, it is set to the `base_price`
+    fn from(order: SignedOrder) -> Self {
+        Self {
+            exchange: order.exchange,
+            maker: order.maker,
+            taker: order.taker,
+            fee_recipient: order.fee_recipient,
+            target: order.target,
+            static_target: order.static_target,
+            payment_token: order.payment_token,
+            maker_relayer_fee: order.maker_relayer_fee,
+            taker_relayer_fee: order.taker_relayer_fee,
+            maker_protocol_fee: order.maker_protocol_fee,
+            taker_protocol_fee: order.taker_protocol_fee,
+            base_price: order.base_price,
+            current_price: order.base_price,
+            extra: order.extra,
+            listing_time: order.listing_time,
+            expiration_time: order.expiration_time,
+            salt: order.salt,
+            fee_method: order.fee_method,
+            side: order.side,
+            sale_kind: order.sale_kind,
+            how_to_call: order.how_to_call,
+            calldata: order.calldata,
+            replacement_pattern: order.replacement_pattern,
+            static_extradata: order.static_extradata,
+            v: order.v,
+            r: order.r,
+            s: order.s,
+        }
+    }
+}
+
+/// (De)serializes the uints as decimal strings
+mod dec_str {
+    use ethers::types::U256;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(n: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(n)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        crate::types::u256_from_dec_str(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips_the_hashed_fields() {
+        let order = crate::testing::order();
+        let order = MinimalOrder::from(order);
+        let signed = SignedOrder::from(order.clone());
+        assert_eq!(signed.hash, Some(order.hash()));
+
+        let json = serde_json::to_value(&signed).unwrap();
+        assert_eq!(json["basePrice"], order.base_price.to_string());
+        assert_eq!(json["feeRecipient"], format!("{:?}", order.fee_recipient));
+        assert_eq!(json["saleKind"], 0);
+
+        // the unknown fields are kept
+        let mut json = json;
+        json["metadata"] = serde_json::json!({ "asset": { "id": "1" } });
+        let parsed: SignedOrder = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.other["metadata"]["asset"]["id"], "1");
+        assert_eq!(MinimalOrder::from(parsed).hash(), order.hash());
+    }
+}
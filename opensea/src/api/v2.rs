@@ -0,0 +1,179 @@
+//! The v2 API, serving the Seaport listings and offers. The Wyvern orders remain
+//! served by the v1 endpoints of [`OpenSeaApi`].
+use ethers::types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use super::{OpenSeaApi, OpenSeaApiError};
+use crate::types::{u256_from_dec_str, ApiVersion};
+
+/// The orders of the given tokens of a collection
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SeaportOrdersRequest {
+    pub asset_contract_address: Address,
+    /// Empty to query all the tokens of the collection
+    pub token_ids: Vec<U256>,
+    /// At most 50
+    pub limit: u64,
+    /// The `next` cursor of the previous page
+    pub cursor: Option<String>,
+}
+
+/// A page of Seaport orders
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeaportOrdersPage {
+    /// The cursor of the next page, if any
+    pub next: Option<String>,
+    pub previous: Option<String>,
+    pub orders: Vec<SeaportOrder>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeaportSide {
+    /// A listing
+    Ask,
+    /// An offer
+    Bid,
+}
+
+/// A Seaport order as returned by the v2 API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeaportOrder {
+    pub order_hash: H256,
+    pub listing_time: u64,
+    pub expiration_time: u64,
+    pub maker: Account,
+    pub taker: Option<Account>,
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub current_price: U256,
+    pub side: SeaportSide,
+    /// e.g. `basic`, `english` or `criteria`
+    pub order_type: String,
+    pub cancelled: bool,
+    pub finalized: bool,
+    pub marked_invalid: bool,
+    /// The Seaport contract
+    pub protocol_address: Address,
+    /// The Seaport order parameters and signature, to be passed to the contract as is
+    pub protocol_data: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub address: Address,
+}
+
+impl OpenSeaApi {
+    /// Returns a page of the Seaport listings of the tokens
+    pub async fn get_listings_v2(
+        &self,
+        req: SeaportOrdersRequest,
+    ) -> Result<SeaportOrdersPage, OpenSeaApiError> {
+        self.get_seaport_orders("listings", req).await
+    }
+
+    /// Returns a page of the Seaport offers for the tokens
+    pub async fn get_offers_v2(
+        &self,
+        req: SeaportOrdersRequest,
+    ) -> Result<SeaportOrdersPage, OpenSeaApiError> {
+        self.get_seaport_orders("offers", req).await
+    }
+
+    /// Returns the Seaport listing (`SeaportSide::Ask`) or offer (`SeaportSide::Bid`)
+    /// with the given hash
+    pub async fn get_order_by_hash_v2(
+        &self,
+        order_hash: H256,
+        side: SeaportSide,
+    ) -> Result<SeaportOrder, OpenSeaApiError> {
+        let kind = match side {
+            SeaportSide::Ask => "listings",
+            SeaportSide::Bid => "offers",
+        };
+        let query = vec![("order_hash", format!("{:?}", order_hash))];
+        self.query_seaport_orders(kind, &query)
+            .await?
+            .orders
+            .into_iter()
+            .find(|order| order.order_hash == order_hash)
+            .ok_or(OpenSeaApiError::OrderHashNotFound(order_hash))
+    }
+
+    async fn get_seaport_orders(
+        &self,
+        kind: &str,
+        req: SeaportOrdersRequest,
+    ) -> Result<SeaportOrdersPage, OpenSeaApiError> {
+        // the token ids are passed as a repeated parameter
+        let mut query = vec![
+            (
+                "asset_contract_address",
+                format!("{:?}", req.asset_contract_address),
+            ),
+            ("limit", req.limit.to_string()),
+        ];
+        query.extend(req.token_ids.iter().map(|id| ("token_ids", id.to_string())));
+        if let Some(cursor) = req.cursor {
+            query.push(("cursor", cursor));
+        }
+        self.query_seaport_orders(kind, &query).await
+    }
+
+    async fn query_seaport_orders(
+        &self,
+        kind: &str,
+        query: &[(&str, String)],
+    ) -> Result<SeaportOrdersPage, OpenSeaApiError> {
+        let url = format!(
+            "{}/orders/{}/seaport/{}",
+            self.api_url(ApiVersion::V2),
+            self.network.chain(),
+            kind
+        );
+        let endpoint = format!("seaport_{}", kind);
+        let text = self
+            .send(&endpoint, self.client.get(url).query(query))
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_seaport_orders() {
+        let text = r#"{
+            "next": "cj0xJnA9MjAyMi0wOC0yMA==",
+            "previous": null,
+            "orders": [{
+                "created_date": "2022-08-20T10:00:00.000000",
+                "closing_date": "2022-09-20T10:00:00",
+                "listing_time": 1660989600,
+                "expiration_time": 1663668000,
+                "order_hash": "0x5c369f5a19a33838b80af6f823eb224c192c937a7e9227929a8ad07963b2a279",
+                "protocol_data": { "parameters": {}, "signature": "0x" },
+                "protocol_address": "0x00000000006c3852cbef3e08e8df289169ede581",
+                "maker": { "user": 12345, "profile_img_url": "", "address": "0x3ee4c73b42c89d5e99d19400fd14950f4e87c20b", "config": "" },
+                "taker": null,
+                "current_price": "10000000000000000",
+                "maker_fees": [],
+                "taker_fees": [],
+                "side": "ask",
+                "order_type": "basic",
+                "cancelled": false,
+                "finalized": false,
+                "marked_invalid": false
+            }]
+        }"#;
+        let page: SeaportOrdersPage = serde_json::from_str(text).unwrap();
+        assert!(page.next.is_some());
+        assert_eq!(page.orders.len(), 1);
+        let order = &page.orders[0];
+        assert_eq!(order.side, SeaportSide::Ask);
+        assert_eq!(order.current_price, U256::exp10(16));
+        assert!(order.taker.is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Address, U256},
+};
+use thiserror::Error;
+
+use crate::{
+    contracts::{OwnableDelegateProxy, ProxyRegistry, ERC20, NFT},
+    types::Order,
+    Client, ClientError,
+};
+
+/// Why a listing can no longer be filled
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum UnfulfillableReason {
+    #[error("the maker {maker:?} no longer owns the token, {owner:?} does")]
+    NotOwner { maker: Address, owner: Address },
+    #[error("the maker {maker:?} holds {balance} of the {required} tokens")]
+    InsufficientBalance {
+        maker: Address,
+        balance: U256,
+        required: U256,
+    },
+    #[error("the maker {0:?} has no Wyvern proxy")]
+    NoProxy(Address),
+    #[error("the maker {0:?} has not approved their Wyvern proxy")]
+    NotApproved(Address),
+    #[error("the maker {0:?} revoked the access of the exchange to their Wyvern proxy")]
+    ProxyRevoked(Address),
+    #[error("the Wyvern proxy of the maker {0:?} is not of the registry's implementation")]
+    ProxyOutdated(Address),
+    #[error("the exchange rejects matching the order (`ordersCanMatch_`)")]
+    CannotMatch,
+    #[error("the `staticCall` predicate {0:?} of the orders rejects the match")]
+    StaticCallFailed(Address),
+    #[error("checking the order failed: {0}")]
+    CheckFailed(String),
+}
+
+/// The Wyvern user proxy (an `OwnableDelegateProxy`) of an account, through which the
+/// exchange transfers its tokens
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UserProxy {
+    pub address: Address,
+    /// Whether the account revoked the access of the exchange to the proxy
+    pub revoked: bool,
+    /// Whether the proxy delegates to the registry's current implementation, which the
+    /// exchange requires
+    pub current_implementation: bool,
+}
+
+impl UserProxy {
+    /// Whether the exchange can transfer the account's tokens through the proxy
+    pub fn is_usable(&self) -> bool {
+        !self.revoked && self.current_implementation
+    }
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Returns the call approving the Wyvern token transfer proxy to spend `amount` of
+    /// `owner`'s `token`, or `None` if the current allowance is already sufficient
+    pub async fn ensure_erc20_approval(
+        &self,
+        token: Address,
+        owner: Address,
+        amount: U256,
+    ) -> Result<Option<ContractCall<M, bool>>, ClientError> {
+        let spender = self.deployment.token_transfer_proxy;
+        let token = ERC20::new(token, self.provider.clone());
+        let allowance = token
+            .allowance(owner, spender)
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        if allowance >= amount {
+            return Ok(None);
+        }
+
+        Ok(Some(token.approve(spender, amount).from(owner)))
+    }
+
+    /// Returns the call approving `owner`'s Wyvern user proxy to transfer all of their
+    /// tokens of the `nft` collection, or `None` if it is already approved.
+    /// Errors with `ClientError::ProxyNotRegistered` if the owner has no proxy yet,
+    /// see [`Client::register_proxy`].
+    pub async fn ensure_nft_approval(
+        &self,
+        nft: Address,
+        owner: Address,
+    ) -> Result<Option<ContractCall<M, ()>>, ClientError> {
+        let proxy = self
+            .user_proxy(owner)
+            .await?
+            .ok_or(ClientError::ProxyNotRegistered(owner))?;
+
+        let nft = NFT::new(nft, self.provider.clone());
+        let approved = nft
+            .is_approved_for_all(owner, proxy)
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        if approved {
+            return Ok(None);
+        }
+
+        Ok(Some(nft.set_approval_for_all(proxy, true).from(owner)))
+    }
+
+    /// Returns the call registering a Wyvern user proxy for `owner`, or `None` if
+    /// one is already registered
+    pub async fn register_proxy(
+        &self,
+        owner: Address,
+    ) -> Result<Option<ContractCall<M, Address>>, ClientError> {
+        if self.user_proxy(owner).await?.is_some() {
+            return Ok(None);
+        }
+
+        let registry = self.proxy_registry();
+        Ok(Some(registry.register_proxy().from(owner)))
+    }
+
+    /// Returns the Wyvern user proxy registered for `owner`, if any
+    pub async fn user_proxy(&self, owner: Address) -> Result<Option<Address>, ClientError> {
+        let proxy = self
+            .proxy_registry()
+            .proxies(owner)
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        Ok(if proxy.is_zero() { None } else { Some(proxy) })
+    }
+
+    /// Same as [`Client::user_proxy`], but also reads whether the exchange can still use
+    /// the proxy, see [`UserProxy::is_usable`]
+    pub async fn resolve_user_proxy(
+        &self,
+        owner: Address,
+    ) -> Result<Option<UserProxy>, ClientError> {
+        let address = match self.user_proxy(owner).await? {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        let proxy = OwnableDelegateProxy::new(address, self.provider.clone());
+        let implementation = proxy
+            .implementation()
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        let current_implementation = self
+            .proxy_registry()
+            .delegate_proxy_implementation()
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        let revoked = proxy
+            .revoked()
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        Ok(Some(UserProxy {
+            address,
+            revoked,
+            current_implementation: implementation == current_implementation,
+        }))
+    }
+
+    /// Checks that the listing can still be filled: its maker still owns the token
+    /// (`ownerOf` for ERC721, a `balanceOf` of at least `quantity` for ERC1155) and has
+    /// approved its Wyvern proxy to transfer it, the proxy being usable by the exchange
+    /// (see [`Client::resolve_user_proxy`]). Listings often go stale after the maker
+    /// transferred the token elsewhere. Orders without asset metadata (e.g. bundles) or
+    /// with an unsupported schema are not checked. A failing check call (e.g. the
+    /// `ownerOf` of a burnt token reverting) makes the order unfulfillable with
+    /// [`UnfulfillableReason::CheckFailed`] rather than aborting the buy.
+    pub async fn verify_fulfillable(
+        &self,
+        sell: &Order,
+        quantity: U256,
+    ) -> Result<(), ClientError> {
+        let metadata = match sell.asset_metadata() {
+            Some(metadata) => metadata,
+            None => return Ok(()),
+        };
+        let maker = sell.maker.address;
+        let unfulfillable = |reason| ClientError::Unfulfillable {
+            order_hash: sell.order_hash,
+            reason,
+        };
+        let failed = |err: &dyn std::fmt::Display| {
+            unfulfillable(UnfulfillableReason::CheckFailed(err.to_string()))
+        };
+
+        let nft = NFT::new(metadata.asset.address, self.provider.clone());
+        match metadata.resolved_schema() {
+            "ERC721" => {
+                let owner = nft
+                    .owner_of(metadata.asset.id)
+                    .call()
+                    .await
+                    .map_err(|err| failed(&err))?;
+                if owner != maker {
+                    return Err(unfulfillable(UnfulfillableReason::NotOwner {
+                        maker,
+                        owner,
+                    }));
+                }
+            }
+            "ERC1155" => {
+                let balance = nft
+                    .balance_of(maker, metadata.asset.id)
+                    .call()
+                    .await
+                    .map_err(|err| failed(&err))?;
+                if balance < quantity {
+                    return Err(unfulfillable(UnfulfillableReason::InsufficientBalance {
+                        maker,
+                        balance,
+                        required: quantity,
+                    }));
+                }
+            }
+            _ => return Ok(()),
+        }
+
+        let proxy = self
+            .resolve_user_proxy(maker)
+            .await
+            .map_err(|err| failed(&err))?
+            .ok_or_else(|| unfulfillable(UnfulfillableReason::NoProxy(maker)))?;
+        if proxy.revoked {
+            return Err(unfulfillable(UnfulfillableReason::ProxyRevoked(maker)));
+        }
+        if !proxy.current_implementation {
+            return Err(unfulfillable(UnfulfillableReason::ProxyOutdated(maker)));
+        }
+        let approved = nft
+            .is_approved_for_all(maker, proxy.address)
+            .call()
+            .await
+            .map_err(|err| failed(&err))?;
+        if !approved {
+            return Err(unfulfillable(UnfulfillableReason::NotApproved(maker)));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn proxy_registry(&self) -> ProxyRegistry<M> {
+        ProxyRegistry::new(self.deployment.proxy_registry, self.provider.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::{
+        abi::{encode, Token},
+        providers::{MockProvider, Provider},
+        types::{Bytes, NameOrAddress},
+    };
+    use std::sync::Arc;
+
+    fn client() -> (
+        Client<Provider<MockProvider>, MockOrderProvider>,
+        MockProvider,
+    ) {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        (client, mock)
+    }
+
+    fn encoded(token: Token) -> Bytes {
+        encode(&[token]).into()
+    }
+
+    #[tokio::test]
+    async fn approves_missing_erc20_allowance() {
+        let (client, mock) = client();
+        let (token, owner) = (Address::random(), Address::random());
+        mock.push(encoded(Token::Uint(100.into()))).unwrap();
+        let call = client.ensure_erc20_approval(token, owner, 100.into());
+        assert!(call.await.unwrap().is_none());
+
+        mock.push(encoded(Token::Uint(99.into()))).unwrap();
+        let call = client
+            .ensure_erc20_approval(token, owner, 100.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(call.tx.to(), Some(&NameOrAddress::Address(token)));
+        assert_eq!(call.tx.from(), Some(&owner));
+    }
+
+    #[tokio::test]
+    async fn approves_the_proxy_of_the_owner() {
+        let (client, mock) = client();
+        let (nft, owner, proxy) = (Address::random(), Address::random(), Address::random());
+        // without a proxy
+        mock.push(encoded(Token::Address(Address::zero()))).unwrap();
+        match client.ensure_nft_approval(nft, owner).await {
+            Err(ClientError::ProxyNotRegistered(account)) => assert_eq!(account, owner),
+            res => panic!(
+                "approved without a proxy: {:?}",
+                res.map(|call| call.is_some())
+            ),
+        }
+
+        // in reverse: isApprovedForAll, then proxies
+        mock.push(encoded(Token::Bool(true))).unwrap();
+        mock.push(encoded(Token::Address(proxy))).unwrap();
+        assert!(client
+            .ensure_nft_approval(nft, owner)
+            .await
+            .unwrap()
+            .is_none());
+
+        mock.push(encoded(Token::Bool(false))).unwrap();
+        mock.push(encoded(Token::Address(proxy))).unwrap();
+        let call = client
+            .ensure_nft_approval(nft, owner)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(call.tx.to(), Some(&NameOrAddress::Address(nft)));
+    }
+
+    #[tokio::test]
+    async fn registers_a_missing_proxy() {
+        let (client, mock) = client();
+        let owner = Address::random();
+        mock.push(encoded(Token::Address(Address::random())))
+            .unwrap();
+        assert!(client.register_proxy(owner).await.unwrap().is_none());
+
+        mock.push(encoded(Token::Address(Address::zero()))).unwrap();
+        let call = client.register_proxy(owner).await.unwrap().unwrap();
+        assert_eq!(
+            call.tx.to(),
+            Some(&NameOrAddress::Address(client.deployment.proxy_registry))
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Bids on English auctions. Their listings cannot be bought via `atomicMatch_`, the
+//! bids are submitted to the orderbook and OpenSea matches the highest one with the
+//! listing once the auction ends.
+use ethers::{providers::Middleware, signers::Signer, types::U256};
+
+use crate::{
+    types::{BuyArgs, MinimalOrder, Order, OrderMatchError},
+    Client, ClientError, OrderProvider,
+};
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Signs a bid of `amount` (in the listing's payment token) on the English auction
+    /// and submits it to the orderbook. The bid expires at the `expiration_time` unix
+    /// timestamp, or never if 0. Returns the submitted bid.
+    pub async fn place_english_auction_bid<S: Signer>(
+        &self,
+        sell: &Order,
+        amount: U256,
+        expiration_time: u64,
+        signer: &S,
+    ) -> Result<MinimalOrder, ClientError> {
+        if !sell.is_english_auction() {
+            return Err(ClientError::NotEnglishAuction(sell.order_hash));
+        }
+        if amount < sell.current_price {
+            return Err(ClientError::BidTooLow {
+                bid: amount,
+                minimum: sell.current_price,
+            });
+        }
+        let asset = &sell
+            .asset_metadata()
+            .ok_or(OrderMatchError::MissingMetadata(sell.order_hash))?
+            .asset;
+
+        let taker = signer.address();
+        let mut bid = sell.buy_order(BuyArgs {
+            taker,
+            recipient: taker,
+            token: asset.address,
+            token_id: asset.id,
+            timestamp: None,
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        })?;
+        // the listing has no fee recipient, so the bid must have one
+        bid.fee_recipient = self.deployment.fee_recipient;
+        bid.base_price = amount;
+        bid.current_price = amount;
+        bid.expiration_time = expiration_time.into();
+
+        let signature = signer
+            .sign_message(bid.hash().as_bytes())
+            .await
+            .map_err(|err| ClientError::Signer(err.to_string()))?;
+        bid.v = signature.v as u8;
+        signature.r.to_big_endian(bid.r.as_bytes_mut());
+        signature.s.to_big_endian(bid.s.as_bytes_mut());
+
+        self.api.post_order(bid.clone()).await?;
+        Ok(bid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::OPENSEA_FEE_RECIPIENT, get_n_cheapest_orders, MockOrderProvider};
+    use ethers::{providers::Provider, signers::LocalWallet, types::Address};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn bids_on_english_auctions() {
+        let mut sell = crate::testing::order();
+        sell.fee_recipient.address = Address::zero();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+
+        let (provider, _) = Provider::mocked();
+        let api = MockOrderProvider::new(vec![sell.clone()]);
+        let client = Client::with_api(Arc::new(provider), api);
+
+        // the auction cannot be bought
+        let orders = get_n_cheapest_orders(&client.api, asset.address, asset.id, 1)
+            .await
+            .unwrap();
+        assert!(orders.is_empty());
+
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let err = client
+            .place_english_auction_bid(&sell, sell.current_price - 1, 0, &wallet)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::BidTooLow { .. }));
+
+        let bid = client
+            .place_english_auction_bid(&sell, sell.current_price, 0, &wallet)
+            .await
+            .unwrap();
+        assert_eq!(bid.maker, wallet.address());
+        assert_eq!(bid.fee_recipient, *OPENSEA_FEE_RECIPIENT);
+        bid.verify_signature(1).unwrap();
+        assert_eq!(client.api.posted().len(), 1);
+    }
+}
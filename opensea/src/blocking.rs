@@ -0,0 +1,190 @@
+//! Blocking wrappers of the API and of the client, for the callers without an async
+//! runtime, e.g. scripts or sync codebases. Like `reqwest::blocking`, each wrapper
+//! drives the async implementation on a runtime of its own, so its methods must not be
+//! called from within an async runtime (they panic).
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Address, H256, U256},
+};
+use std::{future::Future, sync::Arc};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    api::{OpenSeaApiConfig, OpenSeaApiError},
+    order_provider::OrderProvider,
+    payment_tokens::PaymentToken,
+    types::{CollectionAsset, MinimalOrder, Order, TraitFilter},
+    AssetsRequest, BuyArgs, Client, ClientError, OpenSeaApi, OrderRequest, PreparedBuy,
+};
+
+fn runtime() -> Result<Arc<Runtime>, std::io::Error> {
+    Ok(Arc::new(
+        Builder::new_current_thread().enable_all().build()?,
+    ))
+}
+
+/// The blocking version of [`OpenSeaApi`]
+#[derive(Clone, Debug)]
+pub struct OpenSeaApiBlocking {
+    inner: OpenSeaApi,
+    runtime: Arc<Runtime>,
+}
+
+impl OpenSeaApiBlocking {
+    pub fn new(cfg: OpenSeaApiConfig) -> Result<Self, OpenSeaApiError> {
+        Self::from_api(OpenSeaApi::new(cfg)?)
+    }
+
+    /// Wraps an already configured API, e.g. with hooks or a key rotation
+    pub fn from_api(inner: OpenSeaApi) -> Result<Self, OpenSeaApiError> {
+        Ok(Self {
+            inner,
+            runtime: runtime()?,
+        })
+    }
+
+    /// The async API, for the queries which are not wrapped
+    pub fn inner(&self) -> &OpenSeaApi {
+        &self.inner
+    }
+
+    /// Runs the future to completion on the wrapper's runtime, e.g. a query of
+    /// [`OpenSeaApiBlocking::inner`]
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// See [`OpenSeaApi::get_orders`]
+    pub fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        self.block_on(self.inner.get_orders(req))
+    }
+
+    /// See [`OpenSeaApi::get_order`]
+    pub fn get_order(&self, req: OrderRequest) -> Result<Order, OpenSeaApiError> {
+        self.block_on(self.inner.get_order(req))
+    }
+
+    /// See [`OpenSeaApi::get_order_by_hash`]
+    pub fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        self.block_on(self.inner.get_order_by_hash(order_hash))
+    }
+
+    /// See [`OpenSeaApi::get_assets`]
+    pub fn get_assets(&self, req: AssetsRequest) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        self.block_on(self.inner.get_assets(req))
+    }
+
+    /// See [`OpenSeaApi::resolve_collection`]
+    pub fn resolve_collection(&self, slug: &str) -> Result<Vec<Address>, OpenSeaApiError> {
+        self.block_on(self.inner.resolve_collection(slug))
+    }
+
+    /// See [`OpenSeaApi::get_payment_tokens`]
+    pub fn get_payment_tokens(&self) -> Result<Vec<PaymentToken>, OpenSeaApiError> {
+        self.block_on(self.inner.get_payment_tokens())
+    }
+
+    /// See [`OpenSeaApi::post_order`]
+    pub fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        self.block_on(self.inner.post_order(order))
+    }
+}
+
+/// The blocking version of the buy calls construction of [`Client`]. The calls are
+/// sent with [`ClientBlocking::block_on`], e.g. `client.block_on(call.send())`.
+pub struct ClientBlocking<M, P = OpenSeaApi> {
+    inner: Client<M, P>,
+    runtime: Arc<Runtime>,
+}
+
+impl<M: Middleware> ClientBlocking<M> {
+    /// See [`Client::new`]
+    pub fn new(provider: Arc<M>, cfg: OpenSeaApiConfig) -> Result<Self, ClientError> {
+        let runtime = runtime().map_err(OpenSeaApiError::from)?;
+        let inner = runtime.block_on(Client::new(provider, cfg))?;
+        Ok(Self { inner, runtime })
+    }
+}
+
+impl<M: Middleware, P: OrderProvider> ClientBlocking<M, P> {
+    /// Wraps an already configured client, e.g. with a signer or a listing filter
+    pub fn from_client(inner: Client<M, P>) -> Result<Self, ClientError> {
+        let runtime = runtime().map_err(OpenSeaApiError::from)?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// The async client, for the methods which are not wrapped
+    pub fn inner(&self) -> &Client<M, P> {
+        &self.inner
+    }
+
+    /// Runs the future to completion on the wrapper's runtime, e.g. to send a buy call
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// See [`Client::buy`]
+    pub fn buy(&self, args: BuyArgs, n: usize) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        self.block_on(self.inner.buy(args, n))
+    }
+
+    /// See [`Client::buy_by_traits`]
+    pub fn buy_by_traits(
+        &self,
+        args: BuyArgs,
+        filters: &[TraitFilter],
+        n: usize,
+    ) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        self.block_on(self.inner.buy_by_traits(args, filters, n))
+    }
+
+    /// See [`Client::buy_one`]
+    pub fn buy_one(&self, args: BuyArgs) -> Result<ContractCall<M, ()>, ClientError> {
+        self.block_on(self.inner.buy_one(args))
+    }
+
+    /// See [`Client::token_ids_by_traits`]
+    pub fn token_ids_by_traits(
+        &self,
+        collection: Address,
+        filters: &[TraitFilter],
+    ) -> Result<Vec<U256>, ClientError> {
+        self.block_on(self.inner.token_ids_by_traits(collection, filters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::providers::Provider;
+
+    #[test]
+    fn blocks_on_the_async_client() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let assets = serde_json::from_value(serde_json::json!([
+            { "token_id": asset.id.to_string(), "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+        ]))
+        .unwrap();
+        let api = MockOrderProvider::new(vec![sell]).with_assets(assets);
+        let (provider, _mock) = Provider::mocked();
+        let client =
+            ClientBlocking::from_client(Client::with_api(Arc::new(provider), api)).unwrap();
+
+        let filters = ["Fur=Gold".parse().unwrap()];
+        assert_eq!(
+            client.token_ids_by_traits(asset.address, &filters).unwrap(),
+            vec![asset.id]
+        );
+
+        // nothing listens there
+        let cfg = OpenSeaApiConfig {
+            api_base: Some("http://127.0.0.1:1/".parse().unwrap()),
+            ..Default::default()
+        };
+        let api = OpenSeaApiBlocking::new(cfg).unwrap();
+        assert!(api.get_payment_tokens().is_err());
+    }
+}
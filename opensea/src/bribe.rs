@@ -0,0 +1,311 @@
+//! Conditions verified by `briber.sol` before it pays the bribe. Each condition is a
+//! static call whose returned word is compared to an expected value, and the bribe is
+//! only paid if all of them hold. [`BribeStrategy`] instead sizes the bribe from the
+//! expected profit of the purchases.
+//!
+//! ```
+//! use opensea::bribe::BribeChecks;
+//! use ethers::types::Address;
+//!
+//! let (nft, token, buyer) = (Address::random(), Address::random(), Address::random());
+//! let checks = BribeChecks::new()
+//!     .erc721_owner(nft, 1.into(), buyer)
+//!     .erc721_owner(nft, 2.into(), buyer)
+//!     .erc20_balance_at_least(token, buyer, 1000.into());
+//! assert_eq!(checks.checks().len(), 3);
+//! ```
+use ethers::{
+    abi::{self, Token},
+    types::{Address, Bytes, H256, U256},
+    utils::id,
+};
+use thiserror::Error;
+
+/// The `briber.sol` function verifying the checks before paying the bribe
+const VERIFY_AND_PAY: &str = "verifyAndPay(address[],bytes[],uint256[],bytes32[])";
+
+/// How the word returned by a check's call is compared to its expected value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    /// As uint256
+    Gte,
+    /// As uint256
+    Lte,
+}
+
+impl From<Comparison> for U256 {
+    fn from(comparison: Comparison) -> Self {
+        match comparison {
+            Comparison::Eq => 0,
+            Comparison::Gte => 1,
+            Comparison::Lte => 2,
+        }
+        .into()
+    }
+}
+
+/// A static call of `target` with `data`, whose first returned word must compare to
+/// `expected`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub target: Address,
+    pub data: Bytes,
+    pub comparison: Comparison,
+    pub expected: H256,
+}
+
+/// Builder of the checks which must all hold for the bribe to be paid
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BribeChecks {
+    checks: Vec<Check>,
+}
+
+impl BribeChecks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an arbitrary check
+    pub fn check(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// `ownerOf(token_id)` of the ERC721 is `owner`
+    pub fn erc721_owner(self, nft: Address, token_id: U256, owner: Address) -> Self {
+        self.check(Check {
+            target: nft,
+            data: encode_call("ownerOf(uint256)", &[Token::Uint(token_id)]),
+            comparison: Comparison::Eq,
+            expected: owner.into(),
+        })
+    }
+
+    /// `balanceOf(holder, token_id)` of the ERC1155 is exactly `balance`
+    pub fn erc1155_balance(
+        self,
+        nft: Address,
+        holder: Address,
+        token_id: U256,
+        balance: U256,
+    ) -> Self {
+        self.check(erc1155_balance(
+            nft,
+            holder,
+            token_id,
+            Comparison::Eq,
+            balance,
+        ))
+    }
+
+    /// `balanceOf(holder, token_id)` of the ERC1155 is at least `min`
+    pub fn erc1155_balance_at_least(
+        self,
+        nft: Address,
+        holder: Address,
+        token_id: U256,
+        min: U256,
+    ) -> Self {
+        self.check(erc1155_balance(nft, holder, token_id, Comparison::Gte, min))
+    }
+
+    /// `balanceOf(holder)` of the ERC20 is at least `min`
+    pub fn erc20_balance_at_least(self, token: Address, holder: Address, min: U256) -> Self {
+        self.check(Check {
+            target: token,
+            data: encode_call("balanceOf(address)", &[Token::Address(holder)]),
+            comparison: Comparison::Gte,
+            expected: word(min),
+        })
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    /// The calldata of the `verifyAndPay` call of `briber.sol` verifying the checks
+    pub fn calldata(&self) -> Bytes {
+        let targets = self
+            .checks
+            .iter()
+            .map(|check| Token::Address(check.target))
+            .collect();
+        let data = self
+            .checks
+            .iter()
+            .map(|check| Token::Bytes(check.data.to_vec()))
+            .collect();
+        let comparisons = self
+            .checks
+            .iter()
+            .map(|check| Token::Uint(check.comparison.into()))
+            .collect();
+        let expected = self
+            .checks
+            .iter()
+            .map(|check| Token::FixedBytes(check.expected.as_bytes().to_vec()))
+            .collect();
+        encode_call(
+            VERIFY_AND_PAY,
+            &[
+                Token::Array(targets),
+                Token::Array(data),
+                Token::Array(comparisons),
+                Token::Array(expected),
+            ],
+        )
+    }
+}
+
+/// Sizes the priority fee per gas of the purchases from their expected resale value
+/// (e.g. the floor price of the collection times the number of tokens), so that
+/// `margin_bps` of that value is left as profit after the purchases and their gas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BribeStrategy {
+    pub resale_value: U256,
+    /// In basis points of the resale value
+    pub margin_bps: u64,
+    pub max_priority_fee: Option<U256>,
+}
+
+#[derive(Debug, Error)]
+#[error("Resale value of {resale_value} Wei does not cover the cost of {cost} Wei and the margin of {margin} Wei")]
+pub struct Unprofitable {
+    pub resale_value: U256,
+    /// Of the purchases and of their gas at the max base fee
+    pub cost: U256,
+    pub margin: U256,
+}
+
+impl BribeStrategy {
+    pub fn new(resale_value: U256, margin_bps: u64) -> Self {
+        Self {
+            resale_value,
+            margin_bps,
+            max_priority_fee: None,
+        }
+    }
+
+    /// Caps the priority fee per gas
+    pub fn max_priority_fee(mut self, max_priority_fee: U256) -> Self {
+        self.max_priority_fee = Some(max_priority_fee);
+        self
+    }
+
+    /// The highest priority fee per gas leaving the margin, when buying for `cost` Wei
+    /// with txs using `gas` gas at up to `max_base_fee`
+    pub fn priority_fee(
+        &self,
+        cost: U256,
+        gas: U256,
+        max_base_fee: U256,
+    ) -> Result<U256, Unprofitable> {
+        let margin = self.resale_value * self.margin_bps / 10_000;
+        let cost = cost + gas * max_base_fee;
+        let tips = self
+            .resale_value
+            .checked_sub(cost + margin)
+            .ok_or(Unprofitable {
+                resale_value: self.resale_value,
+                cost,
+                margin,
+            })?;
+        let priority_fee = tips.checked_div(gas).unwrap_or_default();
+        Ok(match self.max_priority_fee {
+            Some(max) => priority_fee.min(max),
+            None => priority_fee,
+        })
+    }
+}
+
+fn erc1155_balance(
+    nft: Address,
+    holder: Address,
+    token_id: U256,
+    comparison: Comparison,
+    value: U256,
+) -> Check {
+    Check {
+        target: nft,
+        data: encode_call(
+            "balanceOf(address,uint256)",
+            &[Token::Address(holder), Token::Uint(token_id)],
+        ),
+        comparison,
+        expected: word(value),
+    }
+}
+
+fn encode_call(signature: &str, args: &[Token]) -> Bytes {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data.into()
+}
+
+fn word(value: U256) -> H256 {
+    let mut word = H256::zero();
+    value.to_big_endian(word.as_bytes_mut());
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Briber;
+    use ethers::providers::Provider;
+    use std::sync::Arc;
+
+    #[test]
+    fn encodes_checks_like_the_bindings() {
+        let (nft, token, buyer) = (Address::random(), Address::random(), Address::random());
+        let checks = BribeChecks::new()
+            .erc721_owner(nft, 1.into(), buyer)
+            .erc1155_balance(nft, buyer, 2.into(), 3.into())
+            .erc20_balance_at_least(token, buyer, 1000.into());
+        assert_eq!(checks.checks()[1].comparison, Comparison::Eq);
+        assert_eq!(checks.checks()[2].expected, word(1000.into()));
+
+        let (provider, _) = Provider::mocked();
+        let briber = Briber::new(Address::zero(), Arc::new(provider));
+        let call = briber
+            .method::<_, ()>(
+                "verifyAndPay",
+                (
+                    vec![nft, nft, token],
+                    checks
+                        .checks()
+                        .iter()
+                        .map(|check| check.data.to_vec())
+                        .collect::<Vec<_>>(),
+                    vec![U256::zero(), U256::zero(), U256::one()],
+                    checks
+                        .checks()
+                        .iter()
+                        .map(|check| check.expected)
+                        .collect::<Vec<_>>(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(call.calldata().unwrap(), checks.calldata());
+    }
+
+    #[test]
+    fn sizes_the_priority_fee_for_the_margin() {
+        // 10 ETH resale value with a 10% margin, bought for 8 ETH
+        let eth = U256::exp10(18);
+        let strategy = BribeStrategy::new(eth * 10, 1_000);
+        let (cost, gas, base_fee) = (eth * 8, U256::from(500_000), U256::from(100e9 as u64));
+
+        // 1 ETH left for the gas, of which 0.05 ETH for the base fee
+        let priority_fee = strategy.priority_fee(cost, gas, base_fee).unwrap();
+        assert_eq!(priority_fee, U256::from(1_900e9 as u64));
+        // less gas used leaves more per gas
+        let priority_fee = strategy.priority_fee(cost, gas / 2, base_fee).unwrap();
+        assert_eq!(priority_fee, U256::from(3_900e9 as u64));
+
+        let capped = strategy.max_priority_fee(10.into());
+        assert_eq!(capped.priority_fee(cost, gas, base_fee).unwrap(), 10.into());
+        assert!(strategy.priority_fee(eth * 9, gas, base_fee).is_err());
+    }
+}
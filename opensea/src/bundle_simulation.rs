@@ -0,0 +1,161 @@
+//! Typed results of the simulation of a bundle by a relay, with the gas of each tx
+//! attributed to the token it buys, see [`IncludedBundle`](crate::snipe::IncludedBundle)
+use ethers::types::{H256, U256, U64};
+use ethers_flashbots::SimulatedBundle;
+use serde::{Deserialize, Serialize};
+
+/// The simulation of a tx of the bundle
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxSimulation {
+    pub tx_hash: H256,
+    /// The token bought by the tx, `None` for the payment and bribe txs
+    pub token_id: Option<U256>,
+    pub gas_used: U256,
+    /// The increase of the coinbase's balance per gas, i.e. including the tips sent to
+    /// the coinbase
+    pub effective_gas_price: U256,
+    /// The increase of the coinbase's balance, gas fees and tips included
+    pub coinbase_diff: U256,
+    /// Why the tx reverted, if it did
+    pub error: Option<String>,
+}
+
+impl TxSimulation {
+    pub fn reverted(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// The simulation of a bundle, per tx
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleSimulationReport {
+    /// The block on whose state the bundle was simulated
+    pub simulation_block: U64,
+    pub gas_used: U256,
+    pub effective_gas_price: U256,
+    pub coinbase_diff: U256,
+    /// In the order of the bundle
+    pub txs: Vec<TxSimulation>,
+}
+
+impl BundleSimulationReport {
+    /// The report of the `simulated` bundle whose i-th tx buys the i-th of the
+    /// `token_ids`, as signed by the bundle's txs. The txs past their end, e.g. the
+    /// bribe tx, buy none.
+    pub fn new(simulated: &SimulatedBundle, token_ids: &[Option<U256>]) -> Self {
+        let txs = simulated
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(i, tx)| TxSimulation {
+                tx_hash: tx.hash,
+                token_id: token_ids.get(i).copied().flatten(),
+                gas_used: tx.gas_used,
+                effective_gas_price: effective_gas_price(tx.coinbase_diff, tx.gas_used),
+                coinbase_diff: tx.coinbase_diff,
+                error: tx.error.clone(),
+            })
+            .collect();
+        Self {
+            simulation_block: simulated.simulation_block,
+            gas_used: simulated.gas_used,
+            effective_gas_price: effective_gas_price(simulated.coinbase_diff, simulated.gas_used),
+            coinbase_diff: simulated.coinbase_diff,
+            txs,
+        }
+    }
+
+    /// The gas used by the txs buying each token, in the order of their first tx
+    pub fn gas_by_token(&self) -> Vec<(U256, U256)> {
+        let mut gas: Vec<(U256, U256)> = Vec::new();
+        for tx in &self.txs {
+            let token_id = match tx.token_id {
+                Some(token_id) => token_id,
+                None => continue,
+            };
+            match gas.iter_mut().find(|(id, _)| *id == token_id) {
+                Some((_, used)) => *used += tx.gas_used,
+                None => gas.push((token_id, tx.gas_used)),
+            }
+        }
+        gas
+    }
+
+    /// The gas used by the txs which do not buy a token, e.g. the bribe tx
+    pub fn overhead_gas(&self) -> U256 {
+        self.txs
+            .iter()
+            .filter(|tx| tx.token_id.is_none())
+            .fold(U256::zero(), |gas, tx| gas + tx.gas_used)
+    }
+
+    pub fn reverted(&self) -> impl Iterator<Item = &TxSimulation> {
+        self.txs.iter().filter(|tx| tx.reverted())
+    }
+}
+
+/// The relays report no gas for some failed txs
+fn effective_gas_price(coinbase_diff: U256, gas_used: U256) -> U256 {
+    if gas_used.is_zero() {
+        return U256::zero();
+    }
+    coinbase_diff / gas_used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+    use ethers_flashbots::SimulatedTransaction;
+
+    fn simulated_tx(tx_hash: u64, gas_used: u64, error: Option<&str>) -> SimulatedTransaction {
+        SimulatedTransaction {
+            hash: H256::from_low_u64_be(tx_hash),
+            coinbase_diff: (gas_used * 2).into(),
+            coinbase_tip: U256::zero(),
+            gas_price: 2.into(),
+            gas_used: gas_used.into(),
+            gas_fees: (gas_used * 2).into(),
+            from: Address::random(),
+            to: Some(Address::random()),
+            value: U256::zero(),
+            error: error.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn attributes_the_gas_to_the_tokens() {
+        let simulated = SimulatedBundle {
+            hash: H256::zero(),
+            coinbase_diff: 1_000_000.into(),
+            coinbase_tip: 800_000.into(),
+            gas_price: 10.into(),
+            gas_used: 100_000.into(),
+            gas_fees: 200_000.into(),
+            simulation_block: 100.into(),
+            transactions: vec![
+                simulated_tx(1, 60_000, None),
+                simulated_tx(2, 0, Some("execution reverted")),
+                simulated_tx(3, 40_000, None),
+            ],
+        };
+        let report = BundleSimulationReport::new(&simulated, &[Some(1.into()), Some(2.into())]);
+
+        assert_eq!(report.simulation_block, 100.into());
+        assert_eq!(report.effective_gas_price, 10.into());
+        assert_eq!(report.txs[0].token_id, Some(1.into()));
+        assert_eq!(report.txs[0].effective_gas_price, 2.into());
+        // the last tx is the bribe
+        assert_eq!(report.txs[2].token_id, None);
+        assert_eq!(
+            report.gas_by_token(),
+            vec![(1.into(), 60_000.into()), (2.into(), 0.into())]
+        );
+        assert_eq!(report.overhead_gas(), 40_000.into());
+
+        let reverted: Vec<_> = report.reverted().collect();
+        assert_eq!(reverted.len(), 1);
+        assert_eq!(reverted[0].tx_hash, H256::from_low_u64_be(2));
+        assert_eq!(reverted[0].effective_gas_price, U256::zero());
+    }
+}
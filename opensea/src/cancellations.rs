@@ -0,0 +1,145 @@
+//! Detection of the pending `cancelOrder_` txs of the listings being bought, with which
+//! sellers front-run the purchases, see [`SnipePlan::watch_cancellations`](crate::SnipePlan::watch_cancellations)
+use ethers::{
+    abi::Token,
+    providers::Middleware,
+    types::{Address, Transaction, H256},
+};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+
+use crate::{contracts, types::MinimalOrder, Client};
+
+/// How many pending txs are looked up concurrently
+const LOOKUP_BATCH: usize = 32;
+
+/// A pending cancellation of one of the watched orders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancellation {
+    pub order_hash: H256,
+    pub tx_hash: H256,
+}
+
+/// Returns the hash of the order cancelled by the tx, if it calls `cancelOrder_` on the
+/// `exchange`
+pub fn cancelled_order(exchange: Address, tx: &Transaction) -> Option<H256> {
+    if tx.to != Some(exchange) {
+        return None;
+    }
+    let function = contracts::OPENSEA_ABI.function("cancelOrder_").ok()?;
+    let input = tx.input.as_ref();
+    if input.get(..4)? != function.short_signature() {
+        return None;
+    }
+    let mut tokens = function.decode_input(&input[4..]).ok()?.into_iter();
+    let mut next = || tokens.next();
+    let addrs = next()?.into_fixed_array()?;
+    let uints = next()?.into_fixed_array()?;
+    let address = |i: usize| addrs.get(i).cloned()?.into_address();
+    let uint = |i: usize| uints.get(i).cloned()?.into_uint();
+
+    // the signature is not part of the hash
+    let order = MinimalOrder {
+        exchange: address(0)?,
+        maker: address(1)?,
+        taker: address(2)?,
+        fee_recipient: address(3)?,
+        target: address(4)?,
+        static_target: address(5)?,
+        payment_token: address(6)?,
+        maker_relayer_fee: uint(0)?,
+        taker_relayer_fee: uint(1)?,
+        maker_protocol_fee: uint(2)?,
+        taker_protocol_fee: uint(3)?,
+        base_price: uint(4)?,
+        current_price: uint(4)?,
+        extra: uint(5)?,
+        listing_time: uint(6)?,
+        expiration_time: uint(7)?,
+        salt: uint(8)?,
+        fee_method: wyvern_enum(next()?)?,
+        side: wyvern_enum(next()?)?,
+        sale_kind: wyvern_enum(next()?)?,
+        how_to_call: wyvern_enum(next()?)?,
+        calldata: next()?.into_bytes()?.into(),
+        replacement_pattern: next()?.into_bytes()?.into(),
+        static_extradata: next()?.into_bytes()?.into(),
+        v: 0,
+        r: H256::zero(),
+        s: H256::zero(),
+    };
+    Some(order.hash())
+}
+
+/// The Wyvern enums are (de)serialized as their `u8` discriminant
+fn wyvern_enum<T: DeserializeOwned>(token: Token) -> Option<T> {
+    serde_json::from_value(token.into_uint()?.low_u64().into()).ok()
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Resolves with the first pending cancellation of one of the `orders`. Never
+    /// resolves if there are none or if the provider cannot watch the pending txs.
+    pub(crate) async fn next_cancellation(&self, orders: &[H256]) -> Cancellation {
+        if orders.is_empty() {
+            return futures::future::pending().await;
+        }
+        let pending_txs = match self.provider.watch_pending_transactions().await {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                say!("Could not watch the pending txs for cancellations: {}", err);
+                return futures::future::pending().await;
+            }
+        };
+        let exchange = self.contracts.address();
+        // the pending txs are buffered by the node's filter between the polls, so they
+        // are looked up concurrently to keep up with the mempool
+        let mut lookups = pending_txs
+            .map(|tx_hash| async move { (tx_hash, self.provider.get_transaction(tx_hash).await) })
+            .buffer_unordered(LOOKUP_BATCH);
+        while let Some((tx_hash, tx)) = lookups.next().await {
+            let tx = match tx {
+                Ok(Some(tx)) => tx,
+                _ => continue,
+            };
+            if let Some(order_hash) = cancelled_order(exchange, &tx) {
+                if orders.contains(&order_hash) {
+                    return Cancellation {
+                        order_hash,
+                        tx_hash,
+                    };
+                }
+            }
+        }
+        futures::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::providers::Provider;
+    use std::sync::Arc;
+
+    #[test]
+    fn decodes_cancellations() {
+        let sell = crate::testing::order();
+        let sell = MinimalOrder::from(sell);
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let call = client.cancel_order(sell.clone());
+
+        let mut tx = Transaction {
+            to: Some(client.contracts.address()),
+            input: call.calldata().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            cancelled_order(client.contracts.address(), &tx),
+            Some(sell.hash())
+        );
+        assert_eq!(cancelled_order(Address::random(), &tx), None);
+        tx.input = vec![0; 4].into();
+        assert_eq!(cancelled_order(client.contracts.address(), &tx), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Cleanup of the stale orders of one's own account, see
+//! [`Client::cancel_expired_orders`]. The expired orders are rejected by the exchange
+//! already, so only the ones which could become fillable again need an on-chain cancel.
+use ethers::{contract::builders::ContractCall, providers::Middleware, types::Address};
+
+use crate::{
+    types::{MinimalOrder, Order, OrderSide},
+    unique_orders, unix_timestamp, Client, ClientError, OrderProvider,
+};
+
+/// An on-chain cancel of a stale order, to be sent by its maker
+#[derive(Clone)]
+pub struct PreparedCancel<M> {
+    pub call: ContractCall<M, ()>,
+    pub order: Order,
+}
+
+/// The stale orders of a maker
+#[derive(Clone)]
+pub struct OrderCleanup<M> {
+    /// The cancels of the orders marked invalid by the API (e.g. as the maker moved the
+    /// token or revoked an approval) before they expired. They become fillable again
+    /// once the maker restores the token or the approval.
+    pub cancels: Vec<PreparedCancel<M>>,
+    /// The expired orders, which cannot be matched anymore and need no cancel
+    pub expired: Vec<Order>,
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Sorts the maker's `orders` into the ones which need a cancel and the expired ones
+    /// at the `now` unix timestamp. The orders already cancelled or filled are skipped.
+    pub fn cleanup_orders(&self, orders: Vec<Order>, now: u64) -> OrderCleanup<M> {
+        let mut cleanup = OrderCleanup {
+            cancels: Vec::new(),
+            expired: Vec::new(),
+        };
+        for order in orders {
+            if order.cancelled || order.finalized {
+                continue;
+            }
+            if order.expiration_time != 0 && order.expiration_time <= now {
+                cleanup.expired.push(order);
+            } else if order.marked_invalid {
+                let call = self.cancel_order(MinimalOrder::from(order.clone()));
+                cleanup.cancels.push(PreparedCancel { call, order });
+            }
+        }
+        cleanup
+    }
+}
+
+impl<M: Middleware> Client<M> {
+    /// Returns the cancels of the `maker`'s stale listings and bids, queried via the
+    /// account orders endpoint, along with its expired orders. The calls must be sent by
+    /// the maker.
+    pub async fn cancel_expired_orders(
+        &self,
+        maker: Address,
+    ) -> Result<OrderCleanup<M>, ClientError> {
+        let mut orders = self.api.get_account_orders(maker, OrderSide::Sell).await?;
+        orders.extend(self.api.get_account_orders(maker, OrderSide::Buy).await?);
+        let orders = unique_orders(orders)
+            .into_iter()
+            .filter(|order| order.maker.address == maker)
+            .collect();
+        Ok(self.cleanup_orders(orders, unix_timestamp()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::providers::Provider;
+    use std::sync::Arc;
+
+    #[test]
+    fn cancels_the_invalid_orders() {
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let order = crate::testing::order();
+        let now = 1_000_000;
+        let with = |update: &dyn Fn(&mut Order)| {
+            let mut order = order.clone();
+            order.expiration_time = now + 1;
+            update(&mut order);
+            order
+        };
+
+        let open = with(&|_| {});
+        let expired = with(&|order| order.expiration_time = now);
+        let invalid = with(&|order| order.marked_invalid = true);
+        let never_expiring = with(&|order| {
+            order.expiration_time = 0;
+            order.marked_invalid = true;
+        });
+        let cancelled = with(&|order| {
+            order.expiration_time = now;
+            order.cancelled = true;
+        });
+        let cleanup = client.cleanup_orders(
+            vec![open, expired.clone(), invalid, never_expiring, cancelled],
+            now,
+        );
+
+        assert_eq!(cleanup.expired.len(), 1);
+        assert_eq!(cleanup.expired[0].expiration_time, expired.expiration_time);
+        assert_eq!(cleanup.cancels.len(), 2);
+        for cancel in &cleanup.cancels {
+            assert!(cancel.order.marked_invalid);
+            assert_eq!(cancel.call.tx.from(), Some(&order.maker.address));
+        }
+    }
+}
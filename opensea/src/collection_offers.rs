@@ -0,0 +1,156 @@
+//! Selling a token to a collection or trait offer, see
+//! [`Client::accept_collection_offer`]. Unlike the Wyvern bids of [`Client::accept_best_offers`],
+//! these are Seaport orders, filled via the call returned by the API's fulfillment data.
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, TransactionRequest, H256, U256},
+};
+
+use crate::{
+    api::collection_offers::{CollectionOffer, FulfillmentTransaction, Tip},
+    contracts::NFT,
+    ownership::TokenStandard,
+    Client, ClientError,
+};
+
+/// The tx selling a token to a collection or trait offer, to be sent by the seller
+#[derive(Clone, Debug, PartialEq)]
+pub struct AcceptedOffer {
+    pub tx: TransactionRequest,
+    pub order_hash: H256,
+    pub nft: Address,
+    pub token_id: U256,
+    /// What the offer pays for its whole quantity, in its currency, if reported
+    pub price: Option<U256>,
+}
+
+impl AcceptedOffer {
+    /// The sale of the token to the offer via the Seaport call of its fulfillment
+    pub fn new(
+        offer: &CollectionOffer,
+        token_id: U256,
+        seller: Address,
+        transaction: &FulfillmentTransaction,
+    ) -> Result<Self, ClientError> {
+        let tx = TransactionRequest::new()
+            .from(seller)
+            .to(transaction.to)
+            .value(transaction.value)
+            .data(transaction.calldata()?);
+        Ok(Self {
+            tx,
+            order_hash: offer.order_hash,
+            nft: offer.criteria.contract.address,
+            token_id,
+            price: offer.price.as_ref().map(|price| price.value),
+        })
+    }
+}
+
+impl<M: Middleware> Client<M> {
+    /// Builds the tx selling the `token_id` to the collection (or trait) offer, with the
+    /// client's wallet as seller (see [`Client::with_signer`]). Errors with
+    /// `ClientError::NotHeld` if the seller does not hold the token. The seller must
+    /// have approved the Seaport conduit of the offer for the collection. The `tips` are
+    /// paid by the seller on top of the order's fees, see [`FulfillmentTransaction::with_tips`].
+    pub async fn accept_collection_offer(
+        &self,
+        offer: &CollectionOffer,
+        token_id: U256,
+        tips: &[Tip],
+    ) -> Result<AcceptedOffer, ClientError> {
+        let seller = self.signer.as_ref().ok_or(ClientError::NoSigner)?.address();
+        let nft = offer.criteria.contract.address;
+        let contract = NFT::new(nft, self.provider.clone());
+        let held = match self.token_standard(nft).await {
+            Some(TokenStandard::Erc1155) => !contract
+                .balance_of(seller, token_id)
+                .call()
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?
+                .is_zero(),
+            _ => {
+                contract
+                    .owner_of(token_id)
+                    .call()
+                    .await
+                    .map_err(|err| ClientError::Contract(err.to_string()))?
+                    == seller
+            }
+        };
+        if !held {
+            return Err(ClientError::NotHeld {
+                seller,
+                nft,
+                token_id,
+            });
+        }
+
+        let fulfillment = self
+            .api
+            .get_fulfillment_data(offer, seller, nft, token_id)
+            .await?;
+        say!(
+            "[Token Id = {:?}] Accepting the offer {:?} via {}",
+            token_id,
+            offer.order_hash,
+            fulfillment.protocol
+        );
+        let transaction = fulfillment.fulfillment_data.transaction.with_tips(tips)?;
+        AcceptedOffer::new(offer, token_id, seller, &transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpenSeaApiError;
+
+    #[test]
+    fn sells_via_the_fulfillment() {
+        let offer: CollectionOffer = serde_json::from_value(serde_json::json!({
+            "order_hash": H256::random(),
+            "chain": "ethereum",
+            "criteria": {
+                "collection": { "slug": "boredapeyachtclub" },
+                "contract": { "address": Address::random() }
+            },
+            "price": { "currency": "WETH", "decimals": 18, "value": "10000000000000000" },
+            "protocol_data": {},
+            "protocol_address": Address::random()
+        }))
+        .unwrap();
+        assert!(!offer.is_trait_offer());
+        let seller = Address::random();
+        let transaction = FulfillmentTransaction {
+            function: "fulfillBasicOrder(bytes)".to_owned(),
+            chain: 1,
+            to: offer.protocol_address,
+            value: U256::zero(),
+            input_data: serde_json::json!({}),
+        };
+        assert!(matches!(
+            AcceptedOffer::new(&offer, 87.into(), seller, &transaction),
+            Err(ClientError::OpenSeaApiError(
+                OpenSeaApiError::UnsupportedFulfillment(_)
+            ))
+        ));
+
+        let transaction = FulfillmentTransaction {
+            function: crate::api::collection_offers::match_advanced_orders(),
+            input_data: serde_json::json!({
+                "orders": [],
+                "criteriaResolvers": [],
+                "fulfillments": [],
+                "recipient": seller
+            }),
+            ..transaction
+        };
+        let accepted = AcceptedOffer::new(&offer, 87.into(), seller, &transaction).unwrap();
+        assert_eq!(accepted.tx.from, Some(seller));
+        assert_eq!(accepted.tx.to, Some(offer.protocol_address.into()));
+        assert_eq!(accepted.nft, offer.criteria.contract.address);
+        assert_eq!(accepted.price, Some(U256::exp10(16)));
+    }
+}
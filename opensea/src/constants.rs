@@ -8,9 +8,14 @@ pub const API_BASE_RINKEBY: &str = "https://rinkeby-api.opensea.io";
 pub const SITE_HOST_MAINNET: &str = "https://opensea.io";
 pub const SITE_HOST_RINKEBY: &str = "https://rinkeby.opensea.io";
 
+/// The gas limit of an `atomicMatch_` call
+pub const ATOMIC_MATCH_GAS: u64 = 300_000;
+
 use ethers::types::Address;
 use once_cell::sync::Lazy;
 
+use crate::types::Network;
+
 pub static OPENSEA_FEE_RECIPIENT: Lazy<Address> = Lazy::new(|| {
     "0x5b3256965e7c3cf26e11fcaf296dfc8807c01073"
         .parse()
@@ -22,3 +27,203 @@ pub static OPENSEA_ADDRESS: Lazy<Address> = Lazy::new(|| {
         .parse()
         .unwrap()
 });
+
+pub static OPENSEA_ADDRESS_RINKEBY: Lazy<Address> = Lazy::new(|| {
+    "0x5206e78b21ce315ce284fb24cf05e0585a93b1d9"
+        .parse()
+        .unwrap()
+});
+
+/// The LooksRare exchange on mainnet
+pub static LOOKSRARE_EXCHANGE_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0x59728544b08ab483533076417fbbb2fd0b17ce3a"
+        .parse()
+        .unwrap()
+});
+
+/// Returns the OpenSea Wyvern exchange deployed on the chain, if known
+pub fn exchange_address(chain_id: u64) -> Option<Address> {
+    Deployment::for_chain(chain_id).map(|deployment| deployment.exchange)
+}
+
+/// Wyvern Exchange v2.3, which succeeded [`OPENSEA_ADDRESS`]. Its orders are signed as
+/// EIP-712 typed data, which this crate does not support yet.
+pub static WYVERN_V2_3_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0x7f268357a8c2552623316e2562d90e642bb538e5"
+        .parse()
+        .unwrap()
+});
+
+/// The Wyvern atomicizer, batching the transfers of the bundle orders in one call
+pub static ATOMICIZER_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xc99f70bfd82fb7c8f8191fdfbfb735606b15e5c5"
+        .parse()
+        .unwrap()
+});
+
+/// OpenSea's `MerkleValidator`, the target of the orders transferring their token via
+/// a delegate call instead of calling the collection directly
+pub static MERKLE_VALIDATOR_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xbaf2127b49fc93cbca6269fade0f7f31df4c88a7"
+        .parse()
+        .unwrap()
+});
+
+/// The contracts of OpenSea's Wyvern deployment on a network, and the tokens it trades
+/// against, see [`Deployment::of`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deployment {
+    /// The exchange of the orders served by the orderbook
+    pub exchange: Address,
+    /// See [`WYVERN_V2_3_ADDRESS`]
+    pub exchange_v2_3: Address,
+    /// Spends the ERC20 payments of the orders, so it must be approved by the takers
+    pub token_transfer_proxy: Address,
+    /// Maps each user to their `OwnableDelegateProxy`
+    pub proxy_registry: Address,
+    /// `None` if unknown
+    pub atomicizer: Option<Address>,
+    /// `None` if unknown
+    pub merkle_validator: Option<Address>,
+    pub fee_recipient: Address,
+    pub weth: Address,
+}
+
+pub static MAINNET: Lazy<Deployment> = Lazy::new(|| Deployment {
+    exchange: *OPENSEA_ADDRESS,
+    exchange_v2_3: *WYVERN_V2_3_ADDRESS,
+    token_transfer_proxy: *TOKEN_TRANSFER_PROXY_ADDRESS,
+    proxy_registry: *PROXY_REGISTRY_ADDRESS,
+    atomicizer: Some(*ATOMICIZER_ADDRESS),
+    merkle_validator: Some(*MERKLE_VALIDATOR_ADDRESS),
+    fee_recipient: *OPENSEA_FEE_RECIPIENT,
+    weth: *WETH_ADDRESS,
+});
+
+pub static RINKEBY: Lazy<Deployment> = Lazy::new(|| {
+    let address = |address: &str| address.parse().unwrap();
+    Deployment {
+        exchange: *OPENSEA_ADDRESS_RINKEBY,
+        exchange_v2_3: address("0xdd54d660178b28f6033a953b0e55073cfa7e3744"),
+        token_transfer_proxy: address("0x82d102457854c985221249f86659c9d6cf12aa72"),
+        proxy_registry: address("0xf57b2c51ded3a29e6891aba85459d600256cf317"),
+        atomicizer: None,
+        merkle_validator: Some(address("0x45b594792a5cdc008d0de1c1d69faa3d16b3ddc1")),
+        // the same as on mainnet
+        fee_recipient: *OPENSEA_FEE_RECIPIENT,
+        weth: address("0xc778417e063141139fce010982780140aa0cd5ab"),
+    }
+});
+
+impl Deployment {
+    /// The deployment on the network, `None` on the L2s which only have Seaport
+    pub fn of(network: Network) -> Option<Self> {
+        match network {
+            Network::Mainnet => Some(*MAINNET),
+            Network::Rinkeby => Some(*RINKEBY),
+            Network::Arbitrum | Network::Optimism => None,
+        }
+    }
+
+    pub fn for_chain(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            1 => Some(*MAINNET),
+            4 => Some(*RINKEBY),
+            _ => None,
+        }
+    }
+
+    /// The deployment of the exchange (of either version), which an order is signed for
+    pub fn of_exchange(exchange: Address) -> Option<Self> {
+        [*MAINNET, *RINKEBY].iter().copied().find(|deployment| {
+            deployment.exchange == exchange || deployment.exchange_v2_3 == exchange
+        })
+    }
+}
+
+/// Seaport 1.1, deployed at the same address on all the supported chains
+pub static SEAPORT_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0x00000000006c3852cbef3e08e8df289169ede581"
+        .parse()
+        .unwrap()
+});
+
+/// OpenSea's Seaport conduit, which must be approved to transfer the listed tokens
+pub static OPENSEA_CONDUIT_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0x1e0049783f008a0085193e00003d00cd54003c71"
+        .parse()
+        .unwrap()
+});
+
+/// Returns the Seaport exchange deployed on the chain, if known
+pub fn seaport_address(chain_id: u64) -> Option<Address> {
+    match chain_id {
+        1 | 4 | 10 | 42161 => Some(*SEAPORT_ADDRESS),
+        _ => None,
+    }
+}
+
+/// Returns OpenSea's Seaport conduit deployed on the chain, if known
+pub fn conduit_address(chain_id: u64) -> Option<Address> {
+    match chain_id {
+        1 | 4 | 10 | 42161 => Some(*OPENSEA_CONDUIT_ADDRESS),
+        _ => None,
+    }
+}
+
+/// The Wyvern proxy registry, mapping each user to their `OwnableDelegateProxy`
+pub static PROXY_REGISTRY_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xa5409ec958c83c3f309868babaca7c86dcb077c1"
+        .parse()
+        .unwrap()
+});
+
+/// The Wyvern token transfer proxy, which must be approved to spend ERC20 payment tokens
+pub static TOKEN_TRANSFER_PROXY_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xe5c783ee536cf5e63e792988335c4255169be4e1"
+        .parse()
+        .unwrap()
+});
+
+/// OpenSea's shared storefront (OPENSTORE), an ERC1155 collection
+pub static SHARED_STOREFRONT_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0x495f947276749ce646f68ac8c248420045cb7b5e"
+        .parse()
+        .unwrap()
+});
+
+/// The Multicall contract used for batching read-only calls
+pub static MULTICALL_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xeefba1e63905ef1d7acba5a8513c70307c1ce441"
+        .parse()
+        .unwrap()
+});
+
+/// Multicall3, deployed at the same address on most chains. It can aggregate reverting
+/// calls, unlike [`MULTICALL_ADDRESS`]
+pub static MULTICALL3_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xca11bde05977b3631167028862be2a173976ca11"
+        .parse()
+        .unwrap()
+});
+
+/// Wrapped Ether
+pub static WETH_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+        .parse()
+        .unwrap()
+});
+
+/// Uniswap V3 `SwapRouter`
+pub static UNISWAP_V3_ROUTER_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xe592427a0aece4641a3d7b8f0e47e61dbc4e7bcb"
+        .parse()
+        .unwrap()
+});
+
+/// Uniswap V3 `Quoter`
+pub static UNISWAP_V3_QUOTER_ADDRESS: Lazy<Address> = Lazy::new(|| {
+    "0xb27308f9f90d607463bb33ea1bebb41c27ce5ab6"
+        .parse()
+        .unwrap()
+});
@@ -6,6 +6,99 @@ abigen!(
         function transferFrom(address from, address to, uint256 tokenId) public returns (bool)
         function safeTransferFrom(address,address,uint256,uint256,bytes) public returns (bool)
         function atomicMatch_(address[14] addrs,uint[18] uints, uint8[8] feeMethodsSidesKindsHowToCalls, bytes calldataBuy, bytes calldataSell, bytes replacementPatternBuy, bytes replacementPatternSell, bytes staticExtradataBuy, bytes staticExtradataSell, uint8[2] vs, bytes32[5] rssMetadata) public payable"
+        function ordersCanMatch_(address[14] addrs, uint[18] uints, uint8[8] feeMethodsSidesKindsHowToCalls, bytes calldataBuy, bytes calldataSell, bytes replacementPatternBuy, bytes replacementPatternSell, bytes staticExtradataBuy, bytes staticExtradataSell) public view returns (bool)
+        function calculateCurrentPrice_(address[7] addrs, uint[9] uints, uint8 feeMethod, uint8 side, uint8 saleKind, uint8 howToCall, bytes calldata, bytes replacementPattern, bytes staticExtradata) public view returns (uint)
+        function staticCall(address target, bytes calldata, bytes extradata) public view returns (bool)
+        function approveOrder_(address[7] addrs, uint[9] uints, uint8 feeMethod, uint8 side, uint8 saleKind, uint8 howToCall, bytes calldata, bytes replacementPattern, bytes staticExtradata, bool orderbookInclusionDesired) public
+        function cancelOrder_(address[7] addrs, uint[9] uints, uint8 feeMethod, uint8 side, uint8 saleKind, uint8 howToCall, bytes calldata, bytes replacementPattern, bytes staticExtradata, uint8 v, bytes32 r, bytes32 s) public
+        event OrdersMatched(bytes32 buyHash, bytes32 sellHash, address indexed maker, address indexed taker, uint256 price, bytes32 indexed metadata)
+        event OrderCancelled(bytes32 indexed hash)
     ]"#,
     event_derives(serde::Deserialize, serde::Serialize)
 );
+
+abigen!(
+    ERC20,
+    r#"[
+        function allowance(address owner, address spender) view returns (uint256)
+        function approve(address spender, uint256 amount) returns (bool)
+        function balanceOf(address owner) view returns (uint256)
+    ]"#
+);
+
+abigen!(
+    NFT,
+    r#"[
+        function ownerOf(uint256) view returns (address)
+        function balanceOf(address,uint256) view returns (uint256)
+        function isApprovedForAll(address owner, address operator) view returns (bool)
+        function supportsInterface(bytes4 interfaceId) view returns (bool)
+        function totalSupply() view returns (uint256)
+        function tokenByIndex(uint256 index) view returns (uint256)
+        function setApprovalForAll(address operator, bool approved)
+        function transferFrom(address from, address to, uint256 tokenId)
+        function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes data)
+        event Transfer(address indexed from, address indexed to, uint256 indexed tokenId)
+        event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value)
+    ]"#
+);
+
+abigen!(
+    ProxyRegistry,
+    r#"[
+        function proxies(address) view returns (address)
+        function registerProxy() returns (address)
+        function delegateProxyImplementation() view returns (address)
+    ]"#
+);
+
+abigen!(
+    OwnableDelegateProxy,
+    r#"[
+        function implementation() view returns (address)
+        function revoked() view returns (bool)
+    ]"#
+);
+
+abigen!(
+    UniswapV3Quoter,
+    r#"[
+        function quoteExactOutputSingle(address tokenIn, address tokenOut, uint24 fee, uint256 amountOut, uint160 sqrtPriceLimitX96) returns (uint256 amountIn)
+    ]"#
+);
+
+abigen!(
+    PurchaseAggregator,
+    r#"[
+        constructor(address _exchange)
+        function exchange() view returns (address)
+        function batchBuy(bytes[] calldata _calls, uint256[] calldata _values) external payable
+    ]"#
+);
+
+abigen!(
+    Briber,
+    r#"[
+        function verifyOwnershipAndPay721(address _nftContract, address _owner, uint256[] calldata _nftIds) external payable
+        function verifyOwnershipAndPay1155(address _nftContract, address _owner, uint256[] calldata _nftIds, uint256[] calldata _expectedBalances ) external payable
+        function verifyAndPay(address[] calldata _targets, bytes[] calldata _data, uint256[] calldata _comparisons, bytes32[] calldata _expected) external payable
+    ]"#
+);
+
+abigen!(
+    LooksRareExchange,
+    r#"[
+        struct MakerOrder { bool isOrderAsk; address signer; address collection; uint256 price; uint256 tokenId; uint256 amount; address strategy; address currency; uint256 nonce; uint256 startTime; uint256 endTime; uint256 minPercentageToAsk; bytes params; uint8 v; bytes32 r; bytes32 s; }
+        struct TakerOrder { bool isOrderAsk; address taker; uint256 price; uint256 tokenId; uint256 minPercentageToAsk; bytes params; }
+        function matchAskWithTakerBidUsingETHAndWETH(TakerOrder takerBid, MakerOrder makerAsk) external payable
+    ]"#
+);
+
+abigen!(
+    Multicall3,
+    r#"[
+        struct Call3 { address target; bool allowFailure; bytes callData; }
+        struct Call3Result { bool success; bytes returnData; }
+        function aggregate3(Call3[] calls) payable returns (Call3Result[] returnData)
+    ]"#
+);
@@ -0,0 +1,116 @@
+//! Time-boxing of the purchases sent via the mempool, see
+//! [`SnipePlan::deadline`](crate::SnipePlan::deadline). A purchase still pending after
+//! the deadline is replaced by a 0-value self-transfer with the same nonce, so that it
+//! does not land at a stale price minutes later.
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, TransactionRequest, H256, U256},
+};
+
+use crate::{Client, ClientError, Event, OrderProvider};
+
+/// The percentage by which the gas price of a cancellation exceeds the cancelled tx's.
+/// The nodes only accept replacements paying at least 10% more.
+const REPLACEMENT_FEE_BUMP: u64 = 20;
+
+/// When the pending purchases are given up on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deadline {
+    /// The last block the purchases may be mined in
+    Block(u64),
+    /// The unix timestamp of the last block the purchases may be mined in
+    Timestamp(u64),
+}
+
+impl Deadline {
+    /// Whether the txs which are not mined in the block are past the deadline
+    pub fn has_passed(&self, block: u64, timestamp: u64) -> bool {
+        match *self {
+            Deadline::Block(deadline) => block >= deadline,
+            Deadline::Timestamp(deadline) => timestamp >= deadline,
+        }
+    }
+}
+
+/// A 0-value self-transfer of the `wallet` replacing the `tx`, with its nonce and a higher
+/// gas price. `None` if the tx has no nonce, as it could not be replaced.
+pub fn cancellation(wallet: Address, tx: &TransactionRequest) -> Option<TransactionRequest> {
+    let gas_price = tx.gas_price.unwrap_or_default();
+    Some(TransactionRequest {
+        from: Some(wallet),
+        to: Some(wallet.into()),
+        gas: Some(21_000.into()),
+        gas_price: Some(gas_price + gas_price * REPLACEMENT_FEE_BUMP / 100 + U256::one()),
+        value: Some(U256::zero()),
+        data: None,
+        nonce: Some(tx.nonce?),
+    })
+}
+
+impl<M: Middleware + 'static, P: OrderProvider> Client<M, P> {
+    /// Sends the cancellations of the pending txs, skipping those which cannot be sent
+    pub(crate) async fn cancel_pending<S: Signer + 'static>(
+        &self,
+        provider: &SignerMiddleware<std::sync::Arc<M>, S>,
+        pending: Vec<(H256, TransactionRequest)>,
+    ) -> Result<Vec<H256>, ClientError> {
+        let wallet = provider.address();
+        let mut cancellations = Vec::new();
+        for (tx_hash, tx) in pending {
+            let cancellation = match cancellation(wallet, &tx) {
+                Some(cancellation) => cancellation,
+                None => {
+                    say!("Tx {:?} has no nonce, it cannot be cancelled", tx_hash);
+                    continue;
+                }
+            };
+            match provider.send_transaction(cancellation, None).await {
+                Ok(pending_tx) => {
+                    say!(
+                        "Tx {:?} missed the deadline, cancelling it with {:?}",
+                        tx_hash,
+                        *pending_tx
+                    );
+                    self.emit(Event::TxReplaced {
+                        tx_hash,
+                        replacement: *pending_tx,
+                    })
+                    .await?;
+                    cancellations.push(*pending_tx);
+                }
+                // e.g. the tx was mined meanwhile, so its nonce is used
+                Err(err) => say!("Could not cancel tx {:?}: {}", tx_hash, err),
+            }
+        }
+        Ok(cancellations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancels_with_a_higher_gas_price() {
+        assert!(Deadline::Block(10).has_passed(10, 0));
+        assert!(!Deadline::Block(10).has_passed(9, 2_000_000_000));
+        assert!(Deadline::Timestamp(1_000).has_passed(1, 1_000));
+        assert!(!Deadline::Timestamp(1_000).has_passed(1, 999));
+
+        let wallet = Address::random();
+        let tx = TransactionRequest::pay(Address::random(), 100)
+            .gas(200_000)
+            .gas_price(100)
+            .nonce(7);
+        let cancellation = cancellation(wallet, &tx).unwrap();
+        assert_eq!(cancellation.nonce, Some(7.into()));
+        assert_eq!(cancellation.to, Some(wallet.into()));
+        assert_eq!(cancellation.value, Some(0.into()));
+        assert_eq!(cancellation.gas_price, Some(121.into()));
+
+        let tx = TransactionRequest { nonce: None, ..tx };
+        assert!(super::cancellation(wallet, &tx).is_none());
+    }
+}
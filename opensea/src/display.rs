@@ -0,0 +1,206 @@
+//! Human-readable one-liners of the orders, for logging: `Display` of [`Order`] and
+//! [`MinimalOrder`], also available as their `summary()`. The known addresses are shown
+//! by their labels, see [`address_book`](crate::address_book).
+//!
+//! The human-readable messages of the library are printed via [`say!`](crate::say), to
+//! the stdout unless moved to the stderr by [`messages_to_stderr`].
+use chrono::{TimeZone, Utc};
+use ethers::types::{Address, U256};
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    address_book::format_address,
+    payment_tokens::PaymentTokens,
+    types::{Metadata, MinimalOrder, Order, OrderSide, SaleKind},
+};
+
+/// Whether the messages go to the stderr, see [`messages_to_stderr`]
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Prints the human-readable messages to the stderr from now on, e.g. to keep the
+/// stdout for machine-readable output
+pub fn messages_to_stderr() {
+    MESSAGES_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// The stdout, or the stderr after [`messages_to_stderr`]
+pub fn message_writer() -> Box<dyn Write> {
+    if MESSAGES_TO_STDERR.load(Ordering::Relaxed) {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Writes the message as a line of the [`message_writer`], see [`say!`](crate::say)
+#[doc(hidden)]
+pub fn say(message: fmt::Arguments) {
+    let _ = writeln!(message_writer(), "{}", message);
+}
+
+/// Formats the amount of a token with `decimals` decimals, without trailing zeros, e.g.
+/// `1500000000000000000` with 18 decimals as `1.5`
+pub fn format_amount(amount: U256, decimals: u8) -> String {
+    let unit = U256::exp10(decimals as usize);
+    let (int, frac) = (amount / unit, amount % unit);
+    if frac.is_zero() {
+        return int.to_string();
+    }
+    let frac = format!("{:0>width$}", frac.to_string(), width = decimals as usize);
+    format!("{}.{}", int, frac.trim_end_matches('0'))
+}
+
+/// The first and last 4 hex digits of the address, e.g. `0x3ee4…c20b`
+pub fn short_address(address: Address) -> String {
+    let hex = format!("{:?}", address);
+    format!("{}…{}", &hex[..6], &hex[hex.len() - 4..])
+}
+
+/// The price in its payment token, e.g. `1.5 ETH`. The amounts of the tokens unknown
+/// to [`PaymentTokens::default`] are shown in their smallest unit.
+fn format_price(amount: U256, payment_token: Address) -> String {
+    match PaymentTokens::default().get(payment_token) {
+        Some(token) => format!("{} {}", format_amount(amount, token.decimals), token.symbol),
+        None => format!("{} of token {}", amount, format_address(payment_token)),
+    }
+}
+
+/// The expiration timestamp in UTC
+fn format_expiration(expiration_time: u64) -> String {
+    if expiration_time == 0 {
+        return "never expires".to_owned();
+    }
+    match Utc.timestamp_opt(expiration_time as i64, 0).single() {
+        Some(date) => format!("expires {} UTC", date.format("%Y-%m-%d %H:%M")),
+        None => format!("expires at {}", expiration_time),
+    }
+}
+
+fn format_sale_kind(sale_kind: SaleKind, english_auction: bool) -> &'static str {
+    match sale_kind {
+        SaleKind::DutchAuction => "dutch auction",
+        SaleKind::FixedPrice if english_auction => "english auction",
+        SaleKind::FixedPrice => "fixed price",
+    }
+}
+
+fn format_side(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "Bid on",
+        OrderSide::Sell => "Listing of",
+    }
+}
+
+impl Order {
+    /// A one-liner of the order, e.g. `Listing of 0x76be…8e77 #87 at 80 ETH by
+    /// 0x3ee4…c20b, fixed price, never expires`
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", format_side(self.side))?;
+        match self.metadata {
+            Some(Metadata::Asset(ref metadata)) => write!(
+                f,
+                "{} #{}",
+                format_address(metadata.asset.address),
+                metadata.asset.id
+            )?,
+            Some(Metadata::Bundle { ref bundle }) => {
+                write!(f, "a bundle of {} assets", bundle.assets.len())?
+            }
+            _ => f.write_str("a bundle")?,
+        }
+        if self.quantity > U256::one() {
+            write!(f, " x{}", self.quantity)?;
+        }
+        write!(
+            f,
+            " at {} by {}, {}, {}",
+            format_price(self.current_price, self.payment_token),
+            format_address(self.maker.address),
+            format_sale_kind(self.sale_kind, self.is_english_auction()),
+            format_expiration(self.expiration_time)
+        )
+    }
+}
+
+impl MinimalOrder {
+    /// A one-liner of the order, see [`Order::summary`]
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for MinimalOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            format_side(self.side),
+            format_address(self.nft())
+        )?;
+        if let Some(token_id) = self.token_id() {
+            write!(f, " #{}", token_id)?;
+        }
+        let quantity = self.quantity();
+        if quantity > U256::one() {
+            write!(f, " x{}", quantity)?;
+        }
+        let english_auction = self.side == OrderSide::Sell
+            && self.sale_kind == SaleKind::FixedPrice
+            && self.fee_recipient.is_zero();
+        write!(
+            f,
+            " at {} by {}, {}, {}",
+            format_price(self.current_price, self.payment_token),
+            format_address(self.maker),
+            format_sale_kind(self.sale_kind, english_auction),
+            format_expiration(self.expiration_time.low_u64())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_orders() {
+        assert_eq!(format_amount(U256::exp10(18) * 3 / 2, 18), "1.5");
+        assert_eq!(format_amount(U256::from(25_000_001), 6), "25.000001");
+        assert_eq!(format_amount(U256::zero(), 18), "0");
+
+        let mut order = crate::testing::order();
+        assert_eq!(
+            order.summary(),
+            "Listing of 0x76be…8e77 #87 at 80 ETH by 0x3ee4…c20b, fixed price, never expires"
+        );
+        let minimal = MinimalOrder::from(order.clone());
+        assert_eq!(
+            minimal.summary(),
+            order
+                .summary()
+                .replace("0x76be…8e77", &short_address(minimal.target))
+        );
+
+        order.side = OrderSide::Buy;
+        order.sale_kind = SaleKind::DutchAuction;
+        order.expiration_time = 1_629_300_315;
+        order.payment_token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        order.current_price = U256::from(25_500_000);
+        assert_eq!(
+            order.to_string(),
+            "Bid on 0x76be…8e77 #87 at 25.5 USDC by 0x3ee4…c20b, dutch auction, expires 2021-08-18 15:25 UTC"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Gas price escalation of the txs sent via the mempool, see
+//! [`SnipePlan::escalator`](crate::SnipePlan::escalator). The txs still pending are
+//! rebroadcast with the same nonce at a bumped gas price, until they are mined, reach the
+//! ceiling or miss the [`Deadline`].
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::Middleware,
+    signers::Signer,
+    types::{BlockId, TransactionReceipt, TransactionRequest, H256, U256},
+};
+use futures::StreamExt;
+use std::sync::Arc;
+
+use crate::{Client, ClientError, Deadline, Event, OrderProvider};
+
+/// The nodes only accept replacements paying at least 10% more
+const MIN_BUMP_PERCENT: u64 = 10;
+
+/// Rebroadcasts the pending txs every `every_blocks` blocks with their gas price bumped
+/// by `bump_percent`, up to `max_gas_price`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Escalator {
+    pub every_blocks: u64,
+    /// At least 10, as the nodes reject smaller bumps
+    pub bump_percent: u64,
+    /// The gas price the txs are never rebroadcast above, in Wei
+    pub max_gas_price: U256,
+}
+
+impl Escalator {
+    pub fn new(every_blocks: u64, bump_percent: u64, max_gas_price: U256) -> Self {
+        Self {
+            every_blocks,
+            bump_percent,
+            max_gas_price,
+        }
+    }
+
+    /// The next gas price of a tx sent at `gas_price`, `None` once above the ceiling
+    pub fn bump(&self, gas_price: U256) -> Option<U256> {
+        let percent = self.bump_percent.max(MIN_BUMP_PERCENT);
+        let bumped = gas_price + gas_price * percent / 100 + U256::one();
+        if bumped > self.max_gas_price {
+            return None;
+        }
+        Some(bumped)
+    }
+
+    /// Whether the txs last broadcast in block `since` are due for a rebroadcast at `block`
+    pub fn is_due(&self, since: u64, block: u64) -> bool {
+        block >= since + self.every_blocks.max(1)
+    }
+}
+
+/// A tx sent via the mempool, along with the hashes of all its broadcasts
+struct PendingTx {
+    tx: TransactionRequest,
+    hashes: Vec<H256>,
+}
+
+impl PendingTx {
+    /// The hash of the last broadcast
+    fn hash(&self) -> H256 {
+        *self.hashes.last().expect("sent at least once")
+    }
+}
+
+impl<M: Middleware + 'static, P: OrderProvider> Client<M, P> {
+    /// Watches the `sent` txs at each block until they are mined, rebroadcasting the
+    /// pending ones with the `escalator`'s bumped gas price and replacing them with
+    /// cancellations once the `deadline` has passed. Returns the receipts of the mined
+    /// `purchases`, by the hash each was first sent with, whichever of its broadcasts
+    /// was mined.
+    pub(crate) async fn watch_pending<S: Signer + 'static>(
+        &self,
+        provider: &SignerMiddleware<Arc<M>, S>,
+        sent: Vec<(H256, TransactionRequest)>,
+        purchases: &[H256],
+        escalator: Option<Escalator>,
+        deadline: Option<Deadline>,
+    ) -> Result<Vec<(H256, TransactionReceipt)>, ClientError> {
+        let provider_err = |err: M::Error| ClientError::Provider(err.to_string());
+        let mut pending: Vec<_> = sent
+            .into_iter()
+            .map(|(tx_hash, tx)| PendingTx {
+                tx,
+                hashes: vec![tx_hash],
+            })
+            .collect();
+        let mut blocks = self.provider.watch_blocks().await.map_err(provider_err)?;
+        let mut broadcast_at = None;
+        let mut mined = Vec::new();
+        while let Some(block_hash) = blocks.next().await {
+            let block = match self
+                .provider
+                .get_block(BlockId::Hash(block_hash))
+                .await
+                .map_err(provider_err)?
+            {
+                Some(block) => block,
+                None => continue,
+            };
+
+            // any of the broadcasts may be the one mined
+            let mut still_pending = Vec::new();
+            'txs: for tx in pending {
+                for tx_hash in &tx.hashes {
+                    let receipt = self
+                        .provider
+                        .get_transaction_receipt(*tx_hash)
+                        .await
+                        .map_err(provider_err)?;
+                    if let Some(receipt) = receipt {
+                        if purchases.contains(&tx.hashes[0]) {
+                            mined.push((tx.hashes[0], receipt));
+                        }
+                        continue 'txs;
+                    }
+                }
+                still_pending.push(tx);
+            }
+            pending = still_pending;
+            if pending.is_empty() {
+                return Ok(mined);
+            }
+
+            let number = block.number.unwrap_or_default().as_u64();
+            if let Some(deadline) = deadline {
+                if deadline.has_passed(number, block.timestamp.as_u64()) {
+                    let pending = pending.into_iter().map(|tx| (tx.hash(), tx.tx)).collect();
+                    self.cancel_pending(provider, pending).await?;
+                    return Ok(mined);
+                }
+            }
+
+            let escalator = match escalator {
+                Some(escalator) => escalator,
+                None => continue,
+            };
+            let since = *broadcast_at.get_or_insert(number);
+            if !escalator.is_due(since, number) {
+                continue;
+            }
+            broadcast_at = Some(number);
+            for pending_tx in pending.iter_mut() {
+                self.rebroadcast(provider, pending_tx, &escalator).await?;
+            }
+        }
+        Ok(mined)
+    }
+
+    /// Sends the tx again with a bumped gas price, unless it reached the ceiling
+    async fn rebroadcast<S: Signer + 'static>(
+        &self,
+        provider: &SignerMiddleware<Arc<M>, S>,
+        pending: &mut PendingTx,
+        escalator: &Escalator,
+    ) -> Result<(), ClientError> {
+        let tx_hash = pending.hash();
+        // without a nonce, the node would not replace the tx
+        let gas_price = match (
+            pending.tx.nonce,
+            escalator.bump(pending.tx.gas_price.unwrap_or_default()),
+        ) {
+            (Some(_), Some(gas_price)) => gas_price,
+            _ => return Ok(()),
+        };
+        let tx = TransactionRequest {
+            gas_price: Some(gas_price),
+            ..pending.tx.clone()
+        };
+        match provider.send_transaction(tx.clone(), None).await {
+            Ok(replacement) => {
+                say!(
+                    "Tx {:?} is still pending, rebroadcast at {} Wei per gas as {:?}",
+                    tx_hash,
+                    gas_price,
+                    *replacement
+                );
+                self.emit(Event::TxReplaced {
+                    tx_hash,
+                    replacement: *replacement,
+                })
+                .await?;
+                pending.tx = tx;
+                pending.hashes.push(*replacement);
+            }
+            // e.g. the tx was mined meanwhile, so its nonce is used
+            Err(err) => say!("Could not rebroadcast tx {:?}: {}", tx_hash, err),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bumps_up_to_the_ceiling() {
+        let escalator = Escalator::new(2, 20, 150.into());
+        assert_eq!(escalator.bump(100.into()), Some(121.into()));
+        assert_eq!(escalator.bump(121.into()), Some(146.into()));
+        assert_eq!(escalator.bump(146.into()), None);
+
+        // bumps below the nodes' minimum are raised to it
+        let escalator = Escalator::new(2, 1, 1000.into());
+        assert_eq!(escalator.bump(100.into()), Some(111.into()));
+
+        assert!(!escalator.is_due(10, 11));
+        assert!(escalator.is_due(10, 12));
+    }
+}
@@ -0,0 +1,232 @@
+use ethers::types::{Address, Eip1559TransactionRequest, H256, U256, U64};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{BundleSimulationReport, ClientError};
+
+/// A significant action taken by the library or the CLI
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A sell order was selected to be matched
+    OrderSelected {
+        order_hash: H256,
+        token: Address,
+        token_id: U256,
+        maker: Address,
+        price: U256,
+    },
+    /// A selected sell order was skipped as it can no longer be filled
+    OrderSkipped { order_hash: H256, reason: String },
+    /// A transaction was built with its nonce and fees, and is about to be signed
+    TxBuilt { tx: Box<Eip1559TransactionRequest> },
+    /// A transaction was signed and is about to be submitted
+    TxSigned { tx_hash: H256, nonce: Option<U256> },
+    /// A transaction was sent via the mempool
+    TxSent { tx_hash: H256, nonce: Option<U256> },
+    /// A bundle was simulated by the relay, before being sent to it
+    BundleSimulated {
+        simulation: Box<BundleSimulationReport>,
+    },
+    /// A bundle is about to be sent to the relay
+    BundleSent { target_block: U64, num_txs: usize },
+    /// A pending tx cancels one of the orders being bought, so the bundle was aborted,
+    /// see [`SnipePlan::watch_cancellations`](crate::SnipePlan::watch_cancellations)
+    CancellationDetected { order_hash: H256, tx_hash: H256 },
+    /// A pending tx was replaced, either by itself at a bumped gas price (see
+    /// [`SnipePlan::escalator`](crate::SnipePlan::escalator)) or by a 0-value
+    /// self-transfer after the deadline (see [`SnipePlan::deadline`](crate::SnipePlan::deadline))
+    TxReplaced { tx_hash: H256, replacement: H256 },
+    /// A purchase tx was mined (or its bundle included) and succeeded
+    FillConfirmed { tx_hash: H256, block: Option<U64> },
+    /// A purchase got the confirmations of
+    /// [`Finality::confirmations`](crate::Finality::confirmations), so is final
+    PurchaseFinal { tx_hash: Option<H256>, block: U64 },
+    /// The block a purchase was mined in was reorged out, see
+    /// [`Client::snipe_final`](crate::Client::snipe_final)
+    PurchaseReorged { tx_hash: Option<H256>, block: U64 },
+    /// A token being bought was transferred to the recipient, see
+    /// [`SnipePlan::watch_transfers`](crate::SnipePlan::watch_transfers)
+    TransferReceived {
+        nft: Address,
+        token_id: U256,
+        tx_hash: Option<H256>,
+    },
+}
+
+/// The response of an external system to an event which requires an acknowledgement
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum Verdict {
+    Approve,
+    Veto { reason: String },
+}
+
+/// An event sent over the feed. If `ack` is set, the action is paused until
+/// the consumer responds via [`EventEnvelope::respond`].
+#[derive(Debug)]
+pub struct EventEnvelope {
+    pub event: Event,
+    ack: Option<oneshot::Sender<Verdict>>,
+}
+
+impl EventEnvelope {
+    pub fn requires_ack(&self) -> bool {
+        self.ack.is_some()
+    }
+
+    /// Approves or vetoes the action. No-op for events which do not require an ack.
+    pub fn respond(self, verdict: Verdict) {
+        if let Some(ack) = self.ack {
+            let _ = ack.send(verdict);
+        }
+    }
+}
+
+/// Structured stream of the actions taken, for consumption by external
+/// risk / compliance systems. Cloning it shares the underlying channel.
+#[derive(Clone, Debug)]
+pub struct EventFeed {
+    tx: mpsc::UnboundedSender<EventEnvelope>,
+    /// If set, each event waits for a verdict for at most this long. A missing
+    /// verdict is treated as a veto.
+    ack_timeout: Option<Duration>,
+}
+
+impl EventFeed {
+    /// Returns a feed which does not wait for acknowledgements, and its receiving end
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<EventEnvelope>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                tx,
+                ack_timeout: None,
+            },
+            rx,
+        )
+    }
+
+    /// Requires a verdict for every event, waiting at most `timeout` for it
+    pub fn with_acks(mut self, timeout: Duration) -> Self {
+        self.ack_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects to a Unix domain socket served by an external system and forwards
+    /// each event to it as a JSON line. If acks are enabled, a JSON `Verdict` line
+    /// is read back for each event.
+    pub async fn connect_unix(
+        path: impl AsRef<Path>,
+        ack_timeout: Option<Duration>,
+    ) -> Result<Self, ClientError> {
+        let stream = UnixStream::connect(path)
+            .await
+            .map_err(|err| ClientError::EventFeed(err.to_string()))?;
+        let (feed, rx) = Self::channel();
+        tokio::spawn(forward_unix(stream, rx));
+        Ok(Self {
+            ack_timeout,
+            ..feed
+        })
+    }
+
+    /// Emits the event, returning `ClientError::Vetoed` if the consumer vetoed it
+    pub async fn emit(&self, event: Event) -> Result<(), ClientError> {
+        let timeout = match self.ack_timeout {
+            Some(timeout) => timeout,
+            None => {
+                let _ = self.tx.send(EventEnvelope { event, ack: None });
+                return Ok(());
+            }
+        };
+
+        let (ack, verdict) = oneshot::channel();
+        self.tx
+            .send(EventEnvelope {
+                event,
+                ack: Some(ack),
+            })
+            .map_err(|_| ClientError::EventFeed("feed closed".to_owned()))?;
+
+        match tokio::time::timeout(timeout, verdict).await {
+            Ok(Ok(Verdict::Approve)) => Ok(()),
+            Ok(Ok(Verdict::Veto { reason })) => Err(ClientError::Vetoed(reason)),
+            Ok(Err(_)) => Err(ClientError::Vetoed("no verdict received".to_owned())),
+            Err(_) => Err(ClientError::Vetoed("verdict timed out".to_owned())),
+        }
+    }
+}
+
+async fn forward_unix(stream: UnixStream, mut rx: mpsc::UnboundedReceiver<EventEnvelope>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(envelope) = rx.recv().await {
+        let mut line = match serde_json::to_string(&envelope.event) {
+            Ok(line) => line,
+            Err(_) => continue,
+        };
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            // dropping the envelope vetoes it
+            return;
+        }
+
+        if envelope.requires_ack() {
+            let verdict = match lines.next_line().await {
+                Ok(Some(line)) => serde_json::from_str(&line).unwrap_or_else(|err| Verdict::Veto {
+                    reason: format!("invalid verdict: {}", err),
+                }),
+                _ => return,
+            };
+            envelope.respond(verdict);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn consumer_can_veto_actions() {
+        let (feed, mut rx) = EventFeed::channel();
+        let feed = feed.with_acks(Duration::from_secs(1));
+
+        let consumer = tokio::spawn(async move {
+            let envelope = rx.recv().await.unwrap();
+            assert!(matches!(
+                envelope.event,
+                Event::BundleSent { num_txs: 3, .. }
+            ));
+            envelope.respond(Verdict::Veto {
+                reason: "too expensive".to_owned(),
+            });
+        });
+
+        let res = feed
+            .emit(Event::BundleSent {
+                target_block: 1.into(),
+                num_txs: 3,
+            })
+            .await;
+        consumer.await.unwrap();
+        assert!(matches!(res, Err(ClientError::Vetoed(reason)) if reason == "too expensive"));
+    }
+
+    #[test]
+    fn events_serialize_tagged() {
+        let event = Event::FillConfirmed {
+            tx_hash: H256::zero(),
+            block: Some(5.into()),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "fill_confirmed");
+        let verdict: Verdict = serde_json::from_str(r#"{"verdict":"approve"}"#).unwrap();
+        assert_eq!(verdict, Verdict::Approve);
+    }
+}
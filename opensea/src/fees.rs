@@ -0,0 +1,171 @@
+//! EIP-1559 fee estimation strategies
+use async_trait::async_trait;
+use ethers::{
+    providers::Middleware,
+    types::{BlockNumber, U256},
+};
+
+use crate::ClientError;
+
+/// The fees of a transaction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fees {
+    /// The max basefee the transaction is willing to pay, excluding the priority fee
+    pub max_base_fee: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+impl Fees {
+    /// The `max_fee_per_gas` of the transaction
+    pub fn max_fee_per_gas(&self) -> U256 {
+        self.max_base_fee + self.max_priority_fee_per_gas
+    }
+}
+
+/// Estimates the fees required for a transaction to stay includable over the next blocks
+#[async_trait]
+pub trait FeeEstimator: Send + Sync {
+    /// Returns the fees for a transaction to be includable in any of the next `blocks` blocks
+    async fn estimate<M: Middleware>(
+        &self,
+        provider: &M,
+        blocks: usize,
+    ) -> Result<Fees, ClientError>;
+}
+
+/// Returns the max basefee `blocks` blocks in the future, given that the basefee
+/// can increase by at most 12.5% per block
+pub fn project_base_fee(base_fee: U256, blocks: usize) -> U256 {
+    let mut max_base_fee = base_fee;
+    for _ in 0..blocks {
+        max_base_fee *= 1125;
+        max_base_fee /= 1000;
+    }
+    max_base_fee
+}
+
+/// The max basefee reachable within the next blocks, starting from the latest block's
+/// basefee. Pays no priority fee.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BaseFeeProjection;
+
+#[async_trait]
+impl FeeEstimator for BaseFeeProjection {
+    async fn estimate<M: Middleware>(
+        &self,
+        provider: &M,
+        blocks: usize,
+    ) -> Result<Fees, ClientError> {
+        let block = provider
+            .get_block(BlockNumber::Latest)
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?
+            .ok_or_else(|| ClientError::Provider("No latest block found".to_owned()))?;
+        let base_fee = block
+            .base_fee_per_gas
+            .ok_or_else(|| ClientError::Provider("No basefee found".to_owned()))?;
+        Ok(Fees {
+            max_base_fee: project_base_fee(base_fee, blocks),
+            max_priority_fee_per_gas: U256::zero(),
+        })
+    }
+}
+
+/// Estimates the fees via `eth_feeHistory`: the basefee of the pending block projected
+/// over the next blocks, and the average over the last `history_blocks` blocks of the
+/// `percentile`-th priority fee paid in each of them
+#[derive(Clone, Copy, Debug)]
+pub struct FeeHistoryEstimator {
+    pub history_blocks: u64,
+    pub percentile: f64,
+}
+
+impl Default for FeeHistoryEstimator {
+    fn default() -> Self {
+        Self {
+            history_blocks: 10,
+            percentile: 50.0,
+        }
+    }
+}
+
+#[async_trait]
+impl FeeEstimator for FeeHistoryEstimator {
+    async fn estimate<M: Middleware>(
+        &self,
+        provider: &M,
+        blocks: usize,
+    ) -> Result<Fees, ClientError> {
+        let history = provider
+            .fee_history(self.history_blocks, BlockNumber::Latest, &[self.percentile])
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+
+        // the last basefee is the one of the pending block
+        let pending_base_fee = *history
+            .base_fee_per_gas
+            .last()
+            .ok_or_else(|| ClientError::Provider("Empty fee history".to_owned()))?;
+        let rewards = history
+            .reward
+            .iter()
+            .filter_map(|rewards| rewards.first())
+            .collect::<Vec<_>>();
+        let priority_fee = if rewards.is_empty() {
+            U256::zero()
+        } else {
+            rewards
+                .iter()
+                .fold(U256::zero(), |acc, reward| acc + **reward)
+                / rewards.len()
+        };
+
+        Ok(Fees {
+            max_base_fee: project_base_fee(pending_base_fee, blocks.saturating_sub(1)),
+            max_priority_fee_per_gas: priority_fee,
+        })
+    }
+}
+
+/// Always returns the same fees
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedFees(pub Fees);
+
+#[async_trait]
+impl FeeEstimator for FixedFees {
+    async fn estimate<M: Middleware>(&self, _: &M, _: usize) -> Result<Fees, ClientError> {
+        Ok(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+
+    #[test]
+    fn projects_base_fee() {
+        assert_eq!(project_base_fee(1000.into(), 0), 1000.into());
+        assert_eq!(project_base_fee(1000.into(), 2), 1265.into());
+    }
+
+    #[tokio::test]
+    async fn estimates_from_fee_history() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(serde_json::json!({
+            "oldestBlock": "0x1",
+            "baseFeePerGas": ["0x64", "0x64", "0x3e8"],
+            "gasUsedRatio": [0.5, 0.5],
+            "reward": [["0xa"], ["0x14"]],
+        }))
+        .unwrap();
+
+        let fees = FeeHistoryEstimator::default()
+            .estimate(&provider, 2)
+            .await
+            .unwrap();
+        assert_eq!(fees.max_base_fee, 1125.into());
+        assert_eq!(fees.max_priority_fee_per_gas, 15.into());
+        assert_eq!(fees.max_fee_per_gas(), 1140.into());
+    }
+}
@@ -0,0 +1,215 @@
+//! Spreading the purchase of a quantity of an ERC1155 token across several of its sell
+//! orders, see [`plan_fills`]. Buying the quantity from each order would over-buy.
+use ethers::types::U256;
+use thiserror::Error;
+
+use crate::{constants, types::Order};
+
+/// The max quantity [`plan_fills`] plans the purchase of, as its cost grows with it
+pub const MAX_FILL_QUANTITY: u64 = 10_000;
+
+/// A sell order bought whole
+#[derive(Clone, Debug)]
+pub struct Fill {
+    pub order: Order,
+    /// The quantity listed by the order
+    pub quantity: U256,
+    /// The price of the order with the taker fees and the gas, see
+    /// [`Order::effective_cost`]
+    pub cost: U256,
+}
+
+/// The fills of the sell orders buying exactly the requested quantity
+#[derive(Clone, Debug)]
+pub struct FillPlan {
+    /// In the order of the given sell orders
+    pub fills: Vec<Fill>,
+    /// The sum of the costs of the fills
+    pub total_cost: U256,
+}
+
+impl FillPlan {
+    pub fn quantity(&self) -> U256 {
+        self.fills
+            .iter()
+            .fold(U256::zero(), |acc, fill| acc + fill.quantity)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FillError {
+    #[error("Invalid quantity 0 requested")]
+    ZeroQuantity,
+    #[error("Only {available} tokens are listed, {requested} requested")]
+    InsufficientQuantity { requested: U256, available: U256 },
+    #[error("Cannot plan buying {requested} tokens, at most {max} at once")]
+    QuantityTooLarge { requested: U256, max: U256 },
+    #[error(
+        "No combination of the listings adds up to exactly {0} tokens, which are bought whole"
+    )]
+    NoExactFill(U256),
+}
+
+/// Plans buying exactly `quantity` tokens out of the sell orders at the minimum cost,
+/// with a purchase costing `gas_price` times the gas of an `atomicMatch_`. Wyvern only
+/// fills orders whole, so the plan is the cheapest set of orders whose quantities add
+/// up to `quantity`, of at most [`MAX_FILL_QUANTITY`]. The orders must be of the same
+/// token.
+///
+/// The plan is exact, in `O(orders × quantity)`.
+pub fn plan_fills(
+    orders: &[Order],
+    quantity: U256,
+    gas_price: U256,
+) -> Result<FillPlan, FillError> {
+    if quantity.is_zero() {
+        return Err(FillError::ZeroQuantity);
+    }
+    if quantity > MAX_FILL_QUANTITY.into() {
+        return Err(FillError::QuantityTooLarge {
+            requested: quantity,
+            max: MAX_FILL_QUANTITY.into(),
+        });
+    }
+    let available = orders.iter().fold(U256::zero(), |acc, order| {
+        acc.saturating_add(order.quantity)
+    });
+    if available < quantity {
+        return Err(FillError::InsufficientQuantity {
+            requested: quantity,
+            available,
+        });
+    }
+    let target = quantity.as_usize();
+    let gas = gas_price * constants::ATOMIC_MATCH_GAS;
+    let cost = |order: &Order| order.effective_cost(U256::zero()).total() + gas;
+
+    // `best[q]`: the min cost of buying exactly `q` tokens out of the orders seen so far,
+    // `taken[i][q]`: whether the order `i` is bought in that optimum
+    let mut best: Vec<Option<U256>> = vec![None; target + 1];
+    best[0] = Some(U256::zero());
+    let mut taken = Vec::with_capacity(orders.len());
+    for order in orders {
+        let mut order_taken = vec![false; target + 1];
+        if !order.quantity.is_zero() && order.quantity <= quantity {
+            let listed = order.quantity.as_usize();
+            let order_cost = cost(order);
+            let mut next = best.clone();
+            for q in listed..=target {
+                let prev = match best[q - listed] {
+                    Some(prev) => prev,
+                    None => continue,
+                };
+                if next[q].is_none_or(|current| prev + order_cost < current) {
+                    next[q] = Some(prev + order_cost);
+                    order_taken[q] = true;
+                }
+            }
+            best = next;
+        }
+        taken.push(order_taken);
+    }
+
+    let total_cost = best[target].ok_or(FillError::NoExactFill(quantity))?;
+    // walk back from the last order
+    let mut fills = Vec::new();
+    let mut q = target;
+    for (order, order_taken) in orders.iter().zip(taken).rev() {
+        if order_taken[q] {
+            fills.push(Fill {
+                order: order.clone(),
+                quantity: order.quantity,
+                cost: cost(order),
+            });
+            q -= order.quantity.as_usize();
+        }
+    }
+    fills.reverse();
+    Ok(FillPlan { fills, total_cost })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(quantity: u64, price: u64) -> Order {
+        let mut order = crate::testing::order();
+        order.quantity = quantity.into();
+        order.current_price = price.into();
+        order.taker_relayer_fee = U256::zero();
+        order.taker_protocol_fee = U256::zero();
+        order
+    }
+
+    fn quantities(plan: &FillPlan) -> Vec<u64> {
+        plan.fills
+            .iter()
+            .map(|fill| fill.quantity.as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn plans_the_cheapest_exact_fill() {
+        // unit prices of 10, 12, 9 and 9
+        let orders = vec![order(5, 50), order(10, 120), order(4, 36), order(3, 27)];
+
+        let plan = plan_fills(&orders, 7.into(), U256::zero()).unwrap();
+        assert_eq!(quantities(&plan), vec![4, 3]);
+        assert_eq!(plan.total_cost, (36 + 27).into());
+        assert_eq!(plan.quantity(), 7.into());
+
+        let plan = plan_fills(&orders, 9.into(), U256::zero()).unwrap();
+        assert_eq!(quantities(&plan), vec![5, 4]);
+        assert_eq!(plan.total_cost, (50 + 36).into());
+
+        let plan = plan_fills(&orders, 10.into(), U256::zero()).unwrap();
+        assert_eq!(quantities(&plan), vec![10]);
+    }
+
+    #[test]
+    fn weighs_the_gas_of_each_purchase() {
+        let orders = vec![order(6, 60), order(3, 27), order(3, 27)];
+        let plan = plan_fills(&orders, 6.into(), U256::zero()).unwrap();
+        assert_eq!(quantities(&plan), vec![3, 3]);
+        // the gas of a second purchase outweighs the cheaper units
+        let plan = plan_fills(&orders, 6.into(), U256::one()).unwrap();
+        assert_eq!(quantities(&plan), vec![6]);
+        assert_eq!(
+            plan.total_cost,
+            U256::from(60) + constants::ATOMIC_MATCH_GAS
+        );
+    }
+
+    #[test]
+    fn rejects_unfillable_quantities() {
+        let orders = vec![order(4, 40), order(2, 20)];
+        assert_eq!(
+            quantities(&plan_fills(&orders, 6.into(), U256::zero()).unwrap()),
+            vec![4, 2]
+        );
+        assert_eq!(
+            plan_fills(&orders, 3.into(), U256::zero()).unwrap_err(),
+            FillError::NoExactFill(3.into())
+        );
+        assert_eq!(
+            plan_fills(&orders, 7.into(), U256::zero()).unwrap_err(),
+            FillError::InsufficientQuantity {
+                requested: 7.into(),
+                available: 6.into()
+            }
+        );
+        assert_eq!(
+            plan_fills(&orders, U256::zero(), U256::zero()).unwrap_err(),
+            FillError::ZeroQuantity
+        );
+        // checked before allocating anything
+        let huge = vec![order(u64::MAX, 1)];
+        assert_eq!(
+            plan_fills(&huge, U256::exp10(9), U256::zero()).unwrap_err(),
+            FillError::QuantityTooLarge {
+                requested: U256::exp10(9),
+                max: MAX_FILL_QUANTITY.into()
+            }
+        );
+    }
+}
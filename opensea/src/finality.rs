@@ -0,0 +1,500 @@
+//! Reorg awareness of the purchases, see [`Finality`]. A purchase mined in a block which
+//! gets reorged out may not be mined again (e.g. the listing was bought by someone else
+//! in the new chain), so it is only final after some confirmations, and checked by the
+//! ownership of the tokens then.
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{BlockId, BlockNumber, H256, U256, U64},
+};
+use std::time::Duration;
+
+use crate::{
+    fees::FeeEstimator,
+    ownership::{Ownership, OwnershipQuery, TokenStandard},
+    snipe::{IncludedBundle, SnipeError, SnipeOutcome, SnipePlan, SnipeTarget},
+    Client, ClientError, Event, OrderProvider,
+};
+
+/// The confirmations after which a purchase is final, and how many times to snipe again
+/// the targets whose purchase was dropped by a reorg
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finality {
+    /// The blocks in the canonical chain from the one the purchase was mined in, itself
+    /// included
+    pub confirmations: u64,
+    pub retries: usize,
+    /// How often the chain is polled while waiting for the confirmations
+    pub poll_interval: Duration,
+}
+
+impl Finality {
+    pub fn new(confirmations: u64) -> Self {
+        Self {
+            confirmations,
+            retries: 0,
+            poll_interval: Duration::from_secs(3),
+        }
+    }
+
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+
+/// Whether a mined purchase stayed in the canonical chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PurchaseConfirmation {
+    /// Got the confirmations in the block
+    Final { block: U64 },
+    /// The block it was mined in was reorged out, and it was not mined again
+    Reorged { block: U64 },
+    /// It was never mined: the node dropped it, or another tx used its nonce
+    Dropped,
+}
+
+/// The result of [`Client::snipe_final`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinalOutcome {
+    /// The outcome of each snipe, the first one then the retries
+    pub attempts: Vec<SnipeOutcome>,
+    /// The targets which are still not owned by the recipient once their purchases are
+    /// final: those which were not bought, and those lost to a reorg after the last retry
+    pub lost: Vec<SnipeTarget>,
+}
+
+impl<M: Middleware + 'static, P: OrderProvider> Client<M, P> {
+    /// Waits for the tx to be mined and get the `finality`'s confirmations, emitting
+    /// [`Event::PurchaseFinal`] or [`Event::PurchaseReorged`]. A tx re-mined in another
+    /// block after a reorg is followed there. A tx which the node dropped, or whose
+    /// nonce was used by another tx, is never mined so stops being waited for.
+    pub async fn confirm_tx(
+        &self,
+        tx_hash: H256,
+        finality: &Finality,
+    ) -> Result<PurchaseConfirmation, ClientError> {
+        let mut mined_in = None;
+        loop {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(provider_err)?;
+            let mined = receipt.and_then(|receipt| receipt.block_number.zip(receipt.block_hash));
+            match (mined, mined_in) {
+                (Some((block, hash)), _) => {
+                    mined_in = Some(block);
+                    if self.is_final(block, hash, finality).await? {
+                        return self.confirmed(Some(tx_hash), block).await;
+                    }
+                }
+                // the receipt of a tx reorged out is gone, unless it is mined again
+                (None, Some(block)) => return self.reorged(Some(tx_hash), block).await,
+                (None, None) => {
+                    if self.is_dropped(tx_hash).await? {
+                        say!("The purchase {:?} was dropped", tx_hash);
+                        return Ok(PurchaseConfirmation::Dropped);
+                    }
+                }
+            }
+            tokio::time::sleep(finality.poll_interval).await;
+        }
+    }
+
+    /// Waits for the block a bundle was included in to get the `finality`'s
+    /// confirmations, emitting [`Event::PurchaseFinal`] or [`Event::PurchaseReorged`] if
+    /// another block replaced it meanwhile
+    pub async fn confirm_block(
+        &self,
+        block: U64,
+        finality: &Finality,
+    ) -> Result<PurchaseConfirmation, ClientError> {
+        let mut hash = None;
+        loop {
+            let canonical = self.block_hash(block).await?;
+            match (hash, canonical) {
+                (Some(hash), Some(canonical)) if hash != canonical => {
+                    return self.reorged(None, block).await
+                }
+                (None, Some(canonical)) => hash = Some(canonical),
+                _ => {}
+            }
+            if let Some(hash) = hash {
+                if self.is_final(block, hash, finality).await? {
+                    return self.confirmed(None, block).await;
+                }
+            }
+            tokio::time::sleep(finality.poll_interval).await;
+        }
+    }
+
+    /// Waits for the purchases of the snipe's outcome to be final
+    pub async fn confirm_outcome(
+        &self,
+        outcome: &SnipeOutcome,
+        finality: &Finality,
+    ) -> Result<Vec<PurchaseConfirmation>, ClientError> {
+        let mut confirmations = Vec::new();
+        match outcome {
+            SnipeOutcome::Sent(sent) => {
+                for sent in sent {
+                    confirmations.push(self.confirm_tx(sent.tx_hash, finality).await?);
+                }
+            }
+            SnipeOutcome::Included(bundle) => {
+                confirmations.push(self.confirm_block(bundle.block, finality).await?)
+            }
+            SnipeOutcome::IncludedBundles(bundles) => {
+                for bundle in bundles.iter().flatten() {
+                    confirmations.push(self.confirm_block(bundle.block, finality).await?);
+                }
+            }
+            // nothing was bought
+            SnipeOutcome::DryRun(_) | SnipeOutcome::BidsPlaced(_) => {}
+        }
+        Ok(confirmations)
+    }
+
+    /// Executes the plan as [`Client::snipe`], then waits for its purchases to be final
+    /// and snipes again the targets whose purchase was reorged out and which the
+    /// recipient does not own then, up to the `finality`'s retries. An ERC1155 target
+    /// counts as owned with any balance.
+    pub async fn snipe_final<S, F>(
+        &self,
+        plan: &SnipePlan<F>,
+        signer: S,
+        finality: Finality,
+    ) -> Result<FinalOutcome, SnipeError>
+    where
+        S: Signer + Clone + 'static,
+        F: FeeEstimator + Clone,
+    {
+        let recipient = plan.recipient_or(signer.address());
+        let mut plan = plan.clone();
+        let mut attempts = Vec::new();
+        // the targets not bought by the previous attempts, which are not retried
+        let mut lost = Vec::new();
+        loop {
+            let outcome = self.snipe(&plan, signer.clone()).await?;
+            let bought = !matches!(
+                outcome,
+                SnipeOutcome::DryRun(_) | SnipeOutcome::BidsPlaced(_)
+            );
+            let confirmations = self.confirm_outcome(&outcome, &finality).await?;
+            let reorged = reorged_token_ids(&outcome, &confirmations);
+            attempts.push(outcome);
+            if !bought {
+                return Ok(FinalOutcome { attempts, lost });
+            }
+
+            let (retried, not_bought): (Vec<_>, Vec<_>) = self
+                .unowned_targets(&plan, recipient)
+                .await?
+                .into_iter()
+                .partition(|target| reorged.contains(&target.token_id));
+            lost.extend(not_bought);
+            if retried.is_empty() || attempts.len() > finality.retries {
+                lost.extend(retried);
+                return Ok(FinalOutcome { attempts, lost });
+            }
+            say!(
+                "{} targets were lost to a reorg, sniping them again",
+                retried.len()
+            );
+            plan = plan.retry(retried);
+        }
+    }
+
+    /// Whether the unmined tx will never be: the node does not know it, or its sender
+    /// already used its nonce
+    async fn is_dropped(&self, tx_hash: H256) -> Result<bool, ClientError> {
+        let tx = match self
+            .provider
+            .get_transaction(tx_hash)
+            .await
+            .map_err(provider_err)?
+        {
+            Some(tx) => tx,
+            None => return Ok(true),
+        };
+        let nonce = self
+            .provider
+            .get_transaction_count(tx.from, Some(BlockNumber::Latest.into()))
+            .await
+            .map_err(provider_err)?;
+        if nonce <= tx.nonce {
+            return Ok(false);
+        }
+        // unless the tx itself was mined meanwhile
+        Ok(self
+            .provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(provider_err)?
+            .is_none())
+    }
+
+    /// The targets of the plan the recipient does not own
+    async fn unowned_targets<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        recipient: ethers::types::Address,
+    ) -> Result<Vec<SnipeTarget>, ClientError> {
+        let mut lost = Vec::new();
+        for nft in plan.collections() {
+            let targets: Vec<_> = plan
+                .snipe_targets()
+                .iter()
+                .filter(|target| plan.collection(target) == nft)
+                .collect();
+            let ids: Vec<_> = targets.iter().map(|target| target.token_id).collect();
+            let query = match self.token_standard(nft).await {
+                Some(TokenStandard::Erc1155) => OwnershipQuery::Erc1155 { holder: recipient },
+                _ => OwnershipQuery::Erc721,
+            };
+            let snapshot = self.ownership_snapshot(nft, &ids, query).await?;
+            lost.extend(
+                targets
+                    .into_iter()
+                    .filter(|target| !is_owned(snapshot.get(&target.token_id), recipient))
+                    .cloned(),
+            );
+        }
+        Ok(lost)
+    }
+
+    /// Whether the block is still the canonical one at its height, with enough blocks
+    /// on top of it
+    async fn is_final(
+        &self,
+        block: U64,
+        hash: H256,
+        finality: &Finality,
+    ) -> Result<bool, ClientError> {
+        let head = self
+            .provider
+            .get_block_number()
+            .await
+            .map_err(provider_err)?;
+        let confirmations = (head + 1).saturating_sub(block).as_u64();
+        if confirmations < finality.confirmations {
+            return Ok(false);
+        }
+        Ok(self.block_hash(block).await? == Some(hash))
+    }
+
+    async fn block_hash(&self, block: U64) -> Result<Option<H256>, ClientError> {
+        Ok(self
+            .provider
+            .get_block(BlockId::Number(BlockNumber::Number(block)))
+            .await
+            .map_err(provider_err)?
+            .and_then(|block| block.hash))
+    }
+
+    async fn confirmed(
+        &self,
+        tx_hash: Option<H256>,
+        block: U64,
+    ) -> Result<PurchaseConfirmation, ClientError> {
+        // the purchase already happened, a veto is meaningless
+        let _ = self.emit(Event::PurchaseFinal { tx_hash, block }).await;
+        Ok(PurchaseConfirmation::Final { block })
+    }
+
+    async fn reorged(
+        &self,
+        tx_hash: Option<H256>,
+        block: U64,
+    ) -> Result<PurchaseConfirmation, ClientError> {
+        say!(
+            "The purchase {:?} mined in block {} was reorged out",
+            tx_hash,
+            block
+        );
+        let _ = self.emit(Event::PurchaseReorged { tx_hash, block }).await;
+        Ok(PurchaseConfirmation::Reorged { block })
+    }
+}
+
+/// The tokens bought by the purchases of the `outcome` which were reorged out, given
+/// their `confirmations` as returned by [`Client::confirm_outcome`]
+fn reorged_token_ids(outcome: &SnipeOutcome, confirmations: &[PurchaseConfirmation]) -> Vec<U256> {
+    let is_reorged = |confirmation: &PurchaseConfirmation| {
+        matches!(confirmation, PurchaseConfirmation::Reorged { .. })
+    };
+    let bundle_ids = |bundle: &IncludedBundle| -> Vec<U256> {
+        bundle
+            .simulation
+            .txs
+            .iter()
+            .filter_map(|tx| tx.token_id)
+            .collect()
+    };
+    match outcome {
+        SnipeOutcome::Sent(sent) => sent
+            .iter()
+            .zip(confirmations)
+            .filter(|(_, confirmation)| is_reorged(confirmation))
+            .filter_map(|(sent, _)| sent.token_id)
+            .collect(),
+        SnipeOutcome::Included(bundle) => match confirmations.first() {
+            Some(confirmation) if is_reorged(confirmation) => bundle_ids(bundle),
+            _ => Vec::new(),
+        },
+        SnipeOutcome::IncludedBundles(bundles) => bundles
+            .iter()
+            .flatten()
+            .zip(confirmations)
+            .filter(|(_, confirmation)| is_reorged(confirmation))
+            .flat_map(|(bundle, _)| bundle_ids(bundle))
+            .collect(),
+        SnipeOutcome::DryRun(_) | SnipeOutcome::BidsPlaced(_) => Vec::new(),
+    }
+}
+
+fn is_owned(ownership: Option<&Ownership>, recipient: ethers::types::Address) -> bool {
+    match ownership {
+        Some(Ownership::Owner(owner)) => *owner == recipient,
+        Some(Ownership::Balance(balance)) => !balance.is_zero(),
+        None => false,
+    }
+}
+
+fn provider_err<E: std::fmt::Display>(err: E) -> ClientError {
+    ClientError::Provider(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockOrderProvider, SentTx};
+    use ethers::{
+        providers::Provider,
+        types::{Address, Block, Transaction, TransactionReceipt},
+    };
+    use std::sync::Arc;
+
+    fn block(number: u64, hash: H256) -> Block<H256> {
+        Block {
+            number: Some(number.into()),
+            hash: Some(hash),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn confirms_or_detects_reorgs() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let finality = Finality::new(3).poll_interval(Duration::from_millis(1));
+        let (tx_hash, hash) = (H256::random(), H256::random());
+        let receipt = TransactionReceipt {
+            transaction_hash: tx_hash,
+            block_number: Some(10.into()),
+            block_hash: Some(hash),
+            ..Default::default()
+        };
+
+        // served last pushed first: mined with 2 confirmations, then 3
+        mock.push(block(10, hash)).unwrap();
+        mock.push(U64::from(12)).unwrap();
+        mock.push(receipt.clone()).unwrap();
+        mock.push(U64::from(11)).unwrap();
+        mock.push(receipt.clone()).unwrap();
+        assert_eq!(
+            client.confirm_tx(tx_hash, &finality).await.unwrap(),
+            PurchaseConfirmation::Final { block: 10.into() }
+        );
+
+        // mined, then gone
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        mock.push(U64::from(10)).unwrap();
+        mock.push(receipt).unwrap();
+        assert_eq!(
+            client.confirm_tx(tx_hash, &finality).await.unwrap(),
+            PurchaseConfirmation::Reorged { block: 10.into() }
+        );
+
+        // another block replaced the bundle's
+        mock.push(block(10, H256::random())).unwrap();
+        mock.push(U64::from(10)).unwrap();
+        mock.push(block(10, hash)).unwrap();
+        assert_eq!(
+            client.confirm_block(10.into(), &finality).await.unwrap(),
+            PurchaseConfirmation::Reorged { block: 10.into() }
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_waiting_for_dropped_txs() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let finality = Finality::new(3).poll_interval(Duration::from_millis(1));
+        let tx = Transaction {
+            hash: H256::random(),
+            from: Address::random(),
+            nonce: 5.into(),
+            ..Default::default()
+        };
+
+        // served last pushed first: pending with its nonce unused, then replaced by
+        // another tx with the same nonce
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        mock.push(U256::from(6)).unwrap();
+        mock.push(tx.clone()).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        mock.push(U256::from(5)).unwrap();
+        mock.push(tx.clone()).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        assert_eq!(
+            client.confirm_tx(tx.hash, &finality).await.unwrap(),
+            PurchaseConfirmation::Dropped
+        );
+
+        // unknown to the node
+        mock.push(Option::<Transaction>::None).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        assert_eq!(
+            client.confirm_tx(tx.hash, &finality).await.unwrap(),
+            PurchaseConfirmation::Dropped
+        );
+    }
+
+    #[test]
+    fn retries_only_the_reorged_purchases() {
+        let sent = |token_id: Option<u64>| SentTx {
+            tx_hash: H256::random(),
+            token_id: token_id.map(U256::from),
+        };
+        let outcome = SnipeOutcome::Sent(vec![sent(None), sent(Some(1)), sent(Some(2))]);
+        let confirmations = [
+            PurchaseConfirmation::Final { block: 10.into() },
+            PurchaseConfirmation::Reorged { block: 10.into() },
+            PurchaseConfirmation::Dropped,
+        ];
+        assert_eq!(
+            reorged_token_ids(&outcome, &confirmations),
+            vec![U256::one()]
+        );
+    }
+
+    #[test]
+    fn checks_the_ownership_of_the_recipient() {
+        let recipient = Address::random();
+        assert!(is_owned(Some(&Ownership::Owner(recipient)), recipient));
+        assert!(!is_owned(
+            Some(&Ownership::Owner(Address::random())),
+            recipient
+        ));
+        assert!(is_owned(Some(&Ownership::Balance(2.into())), recipient));
+        assert!(!is_owned(Some(&Ownership::Balance(0.into())), recipient));
+        assert!(!is_owned(None, recipient));
+    }
+}
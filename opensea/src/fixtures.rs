@@ -0,0 +1,209 @@
+//! Recording and replaying of API responses, so that the API types can be
+//! exercised against real payloads without network access
+use async_trait::async_trait;
+use ethers::types::H256;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    api::{
+        find_order, parse_assets, parse_orders, parse_orders_lenient, AssetsRequest, OpenSeaApi,
+        OpenSeaApiError, OrderRequest,
+    },
+    order_provider::OrderProvider,
+    types::{CollectionAsset, MinimalOrder, Order},
+};
+
+/// Environment variable selecting the mode of [`FixtureProvider::from_env`]
+pub const FIXTURES_MODE_ENV: &str = "OPENSEA_FIXTURES";
+
+/// Order provider which either records the API's responses to a directory, or
+/// replays previously recorded ones from it
+#[derive(Clone, Debug)]
+pub struct FixtureProvider {
+    /// Set when recording
+    api: Option<OpenSeaApi>,
+    dir: PathBuf,
+}
+
+impl FixtureProvider {
+    /// Queries the API, saving each raw response under `dir`
+    pub fn record(api: OpenSeaApi, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            api: Some(api),
+            dir: dir.into(),
+        }
+    }
+
+    /// Serves the responses previously recorded under `dir`
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            api: None,
+            dir: dir.into(),
+        }
+    }
+
+    /// Records if `OPENSEA_FIXTURES=record` is set, replays otherwise
+    pub fn from_env(api: OpenSeaApi, dir: impl Into<PathBuf>) -> Self {
+        match std::env::var(FIXTURES_MODE_ENV).as_deref() {
+            Ok("record") => Self::record(api, dir),
+            _ => Self::replay(dir),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.api.is_some()
+    }
+
+    fn path(&self, req: &OrderRequest) -> PathBuf {
+        // the first pages keep the names of the responses recorded before pagination
+        let offset = match req.offset {
+            0 => String::new(),
+            offset => format!("-{}", offset),
+        };
+        self.dir.join(format!(
+            "orders-{:?}-{}-{}-{}{}.json",
+            req.contract_address,
+            req.token_ids.join(","),
+            u8::from(req.side),
+            req.limit,
+            offset
+        ))
+    }
+}
+
+#[async_trait]
+impl OrderProvider for FixtureProvider {
+    async fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        let path = self.path(&req);
+        let text = match self.api {
+            Some(ref api) => {
+                let text = api.get_orders_raw(req).await?;
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(&path, &text)?;
+                text
+            }
+            None => std::fs::read_to_string(&path)?,
+        };
+        parse_orders_lenient(&text, false)
+    }
+
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        let path = self.dir.join(format!("order-{:?}.json", order_hash));
+        let text = match self.api {
+            Some(ref api) => {
+                let text = api.get_order_by_hash_raw(order_hash).await?;
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(&path, &text)?;
+                text
+            }
+            None => std::fs::read_to_string(&path)?,
+        };
+        find_order(parse_orders_lenient(&text, false)?, order_hash)
+    }
+
+    async fn get_assets(
+        &self,
+        req: AssetsRequest,
+    ) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        let path = self.dir.join(format!(
+            "assets-{:?}-{}-{}.json",
+            req.contract_address, req.offset, req.limit
+        ));
+        let text = match self.api {
+            Some(ref api) => {
+                let text = api.get_assets_raw(req).await?;
+                std::fs::create_dir_all(&self.dir)?;
+                std::fs::write(&path, &text)?;
+                text
+            }
+            None => std::fs::read_to_string(&path)?,
+        };
+        parse_assets(&text)
+    }
+
+    async fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        // submissions are not recorded, and are dropped when replaying
+        match self.api {
+            Some(ref api) => api.post_order(order).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses every `.json` orders response under `dir`, returning the orders of each file.
+/// Unlike the provider, errors with `OpenSeaApiError::PartialParse` on any malformed order.
+pub fn parse_dir(dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, Vec<Order>)>, OpenSeaApiError> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().map(|ext| ext == "json").unwrap_or(false));
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let orders = parse_orders(&std::fs::read_to_string(&path)?)?;
+            Ok((path, orders))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{HowToCall, OrderSide, SaleKind};
+    use ethers::types::Address;
+
+    /// Variants of a single order response, edited by hand to cover the edge cases of
+    /// the API's payloads. They were not recorded, so their hashes and metadata are not
+    /// those of real orders.
+    const ORDERS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/synthetic-orders");
+
+    #[test]
+    fn parses_synthetic_orders() {
+        let parsed = parse_dir(ORDERS_DIR).unwrap();
+        assert!(!parsed.is_empty());
+        assert!(parsed
+            .iter()
+            .any(|(_, orders)| orders.iter().any(|order| order.maker.user.is_none())));
+        assert!(parsed.iter().any(|(_, orders)| orders
+            .iter()
+            .any(|order| order.fee_recipient.address.is_zero())));
+        assert!(parsed.iter().any(|(_, orders)| orders
+            .iter()
+            .any(|order| order.sale_kind == SaleKind::DutchAuction)));
+        // the listings transferring via the `MerkleValidator`
+        assert!(parsed.iter().any(|(_, orders)| orders
+            .iter()
+            .any(|order| order.how_to_call == HowToCall::DelegateCall)));
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_responses() {
+        let dir = std::env::temp_dir().join(format!("opensea-fixtures-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let provider = FixtureProvider::replay(&dir);
+        let req = OrderRequest {
+            side: OrderSide::Sell,
+            token_ids: vec!["87".to_owned()],
+            contract_address: Address::zero(),
+            limit: 50,
+            offset: 0,
+        };
+
+        // nothing recorded yet
+        assert!(matches!(
+            provider.get_orders(req.clone()).await,
+            Err(OpenSeaApiError::Io(_))
+        ));
+
+        let response =
+            std::fs::read_to_string(Path::new(ORDERS_DIR).join("erc1155-approved-on-chain.json"))
+                .unwrap();
+        std::fs::write(provider.path(&req), response).unwrap();
+        let orders = provider.get_orders(req).await.unwrap();
+        assert_eq!(orders.len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+//! The gas limit of the purchases, see [`GasConfig`]. An `atomicMatch_` uses more or
+//! less gas depending on the collection (e.g. the hooks of its transfers) and on the
+//! asset schema, so a fixed limit either wastes the balance it reserves or runs out.
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Address, BlockNumber, H256, U256},
+};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::{constants, types::MinimalOrder, Client, ClientError};
+
+/// The gas used by the purchase of a sell order
+pub trait GasOracle: fmt::Debug + Send + Sync {
+    /// `None` if the oracle cannot tell, e.g. without data on the order's collection
+    fn gas_limit(&self, sell: &MinimalOrder) -> Option<U256>;
+}
+
+/// The same gas limit for all the orders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedGas(pub U256);
+
+impl Default for FixedGas {
+    fn default() -> Self {
+        Self(constants::ATOMIC_MATCH_GAS.into())
+    }
+}
+
+impl GasOracle for FixedGas {
+    fn gas_limit(&self, _: &MinimalOrder) -> Option<U256> {
+        Some(self.0)
+    }
+}
+
+/// The gas used by a past purchase of a token of the collection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasSample {
+    pub nft: Address,
+    /// `ERC721` or `ERC1155`, if known
+    pub schema: Option<&'static str>,
+    pub gas_used: U256,
+}
+
+/// The headroom [`HistoricalGas`] adds to the medians by default, as half of the
+/// purchases use more gas than the median and a purchase out of gas still pays for it
+pub const DEFAULT_GAS_MARGIN_BPS: u64 = 2_500;
+
+/// The median gas used by the past purchases of the order's collection, or else of its
+/// asset schema, plus a margin ([`DEFAULT_GAS_MARGIN_BPS`] unless set). Built from the
+/// recent sales via [`Client::historical_gas`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HistoricalGas {
+    collections: HashMap<Address, U256>,
+    schemas: HashMap<&'static str, U256>,
+    margin_bps: u64,
+}
+
+impl HistoricalGas {
+    pub fn from_samples(samples: impl IntoIterator<Item = GasSample>) -> Self {
+        let mut collections = HashMap::<_, Vec<_>>::new();
+        let mut schemas = HashMap::<_, Vec<_>>::new();
+        for sample in samples {
+            collections
+                .entry(sample.nft)
+                .or_default()
+                .push(sample.gas_used);
+            if let Some(schema) = sample.schema {
+                schemas.entry(schema).or_default().push(sample.gas_used);
+            }
+        }
+        Self {
+            collections: collections
+                .into_iter()
+                .map(|(nft, gas)| (nft, median(gas)))
+                .collect(),
+            schemas: schemas
+                .into_iter()
+                .map(|(schema, gas)| (schema, median(gas)))
+                .collect(),
+            margin_bps: DEFAULT_GAS_MARGIN_BPS,
+        }
+    }
+
+    /// Adds these basis points to the medians, e.g. 2000 for 20% of headroom
+    pub fn margin_bps(mut self, margin_bps: u64) -> Self {
+        self.margin_bps = margin_bps;
+        self
+    }
+
+    /// The median gas used by the purchases of the collection, without the margin
+    pub fn collection(&self, nft: Address) -> Option<U256> {
+        self.collections.get(&nft).copied()
+    }
+}
+
+impl GasOracle for HistoricalGas {
+    fn gas_limit(&self, sell: &MinimalOrder) -> Option<U256> {
+        let gas = self
+            .collection(sell.nft())
+            .or_else(|| self.schemas.get(sell.schema()?).copied())?;
+        Some(gas + gas * self.margin_bps / 10_000)
+    }
+}
+
+fn median(mut gas: Vec<U256>) -> U256 {
+    gas.sort();
+    let mid = gas.len() / 2;
+    if gas.len().is_multiple_of(2) {
+        (gas[mid - 1] + gas[mid]) / 2
+    } else {
+        gas[mid]
+    }
+}
+
+/// How the gas limit of a purchase is set: by the oracle if it can tell, else by
+/// `eth_estimateGas` if enabled and successful, else the default
+#[derive(Clone, Debug)]
+pub struct GasConfig {
+    pub oracle: Option<Arc<dyn GasOracle>>,
+    pub estimate: bool,
+    pub default: U256,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            oracle: None,
+            estimate: false,
+            default: constants::ATOMIC_MATCH_GAS.into(),
+        }
+    }
+}
+
+impl GasConfig {
+    pub fn oracle(mut self, oracle: impl GasOracle + 'static) -> Self {
+        self.oracle = Some(Arc::new(oracle));
+        self
+    }
+
+    pub fn estimate(mut self, estimate: bool) -> Self {
+        self.estimate = estimate;
+        self
+    }
+
+    /// The gas limit of the purchase of the `sell` order via the `call`
+    pub async fn gas_limit<M: Middleware>(
+        &self,
+        sell: &MinimalOrder,
+        call: &ContractCall<M, ()>,
+    ) -> U256 {
+        if let Some(gas) = self
+            .oracle
+            .as_ref()
+            .and_then(|oracle| oracle.gas_limit(sell))
+        {
+            return gas;
+        }
+        if self.estimate {
+            match call.estimate_gas().await {
+                Ok(gas) => return gas,
+                Err(err) => say!("Could not estimate the gas of the purchase: {}", err),
+            }
+        }
+        self.default
+    }
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Samples the gas used by the purchases of the collections' tokens within the block
+    /// range, up to the `max_samples` latest per collection, for [`HistoricalGas`]. The
+    /// txs matching several orders (e.g. of aggregators) are left out.
+    pub async fn historical_gas(
+        &self,
+        collections: &[Address],
+        from_block: impl Into<BlockNumber>,
+        to_block: impl Into<BlockNumber>,
+        max_samples: usize,
+    ) -> Result<HistoricalGas, ClientError> {
+        let (from_block, to_block) = (from_block.into(), to_block.into());
+        let mut samples = Vec::new();
+        for &nft in collections {
+            let sales = self.recent_sales(nft, from_block, to_block).await?;
+            let mut matches = HashMap::<H256, usize>::new();
+            for sale in &sales {
+                *matches.entry(sale.tx_hash).or_default() += 1;
+            }
+            let schema = self
+                .token_standard(nft)
+                .await
+                .map(|standard| standard.schema());
+            let txs = sales
+                .iter()
+                .rev()
+                .filter(|sale| matches[&sale.tx_hash] == 1)
+                .take(max_samples);
+            for sale in txs {
+                let receipt = self
+                    .provider
+                    .get_transaction_receipt(sale.tx_hash)
+                    .await
+                    .map_err(|err| ClientError::Provider(err.to_string()))?;
+                if let Some(gas_used) = receipt.and_then(|receipt| receipt.gas_used) {
+                    samples.push(GasSample {
+                        nft,
+                        schema,
+                        gas_used,
+                    });
+                }
+            }
+        }
+        Ok(HistoricalGas::from_samples(samples))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuyArgs, MockOrderProvider};
+    use ethers::providers::Provider;
+
+    #[test]
+    fn takes_the_median_of_the_collection_or_schema() {
+        let sell = MinimalOrder::from(crate::testing::order());
+        let other = Address::random();
+        let sample = |nft, gas_used: u64| GasSample {
+            nft,
+            schema: sell.schema(),
+            gas_used: gas_used.into(),
+        };
+
+        let oracle = HistoricalGas::from_samples(vec![
+            sample(sell.target, 150_000),
+            sample(sell.target, 90_000),
+            sample(sell.target, 200_000),
+        ]);
+        assert_eq!(oracle.collection(sell.target), Some(150_000.into()));
+        assert_eq!(oracle.gas_limit(&sell), Some(187_500.into()));
+        let oracle = oracle.margin_bps(2_000);
+        assert_eq!(oracle.gas_limit(&sell), Some(180_000.into()));
+
+        // the collection has no sales, but others of its schema do
+        let oracle =
+            HistoricalGas::from_samples(vec![sample(other, 100_000), sample(other, 120_000)]);
+        assert_eq!(oracle.collection(sell.target), None);
+        assert_eq!(oracle.gas_limit(&sell), Some(137_500.into()));
+
+        assert_eq!(HistoricalGas::default().gas_limit(&sell), None);
+    }
+
+    #[test]
+    fn sets_the_gas_from_the_oracle_else_the_estimate_else_the_default() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let buy = sell
+            .match_sell(BuyArgs {
+                token_id: asset.id,
+                taker,
+                token: asset.address,
+                recipient: taker,
+                timestamp: Some(0),
+                quantity: None,
+                max_price: None,
+                total_budget: None,
+            })
+            .unwrap();
+        let sell = MinimalOrder::from(sell);
+        let (provider, mock) = Provider::mocked();
+        let mut client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let gas_of = |client: &Client<_, _>| {
+            futures::executor::block_on(client.atomic_match(buy.clone(), sell.clone()))
+                .map(|call| *call.tx.gas().unwrap())
+                .unwrap()
+        };
+
+        assert_eq!(gas_of(&client), constants::ATOMIC_MATCH_GAS.into());
+
+        client.gas = GasConfig::default().estimate(true);
+        mock.push(U256::from(210_000)).unwrap();
+        assert_eq!(gas_of(&client), 210_000.into());
+        // the estimate fails, as no response is mocked
+        assert_eq!(gas_of(&client), constants::ATOMIC_MATCH_GAS.into());
+
+        // the oracle knows the collection, so no estimate is needed
+        let oracle = HistoricalGas::from_samples(vec![GasSample {
+            nft: sell.target,
+            schema: sell.schema(),
+            gas_used: 120_000.into(),
+        }]);
+        client.gas = GasConfig::default().oracle(oracle).estimate(true);
+        assert_eq!(gas_of(&client), 150_000.into());
+    }
+}
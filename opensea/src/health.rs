@@ -0,0 +1,195 @@
+//! Startup checks of the client's setup, see [`Client::health_check`]. A node on the
+//! wrong chain, a missing exchange contract or a rejected API key are better reported
+//! before anything is signed than as the failure of a half-sent run.
+use ethers::{providers::Middleware, signers::Signer, types::Address};
+use serde::Serialize;
+use std::fmt;
+
+use crate::{units::format_eth, Client};
+
+/// The outcome of a check, with what was found
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed(String),
+    Failed(String),
+    /// Not applicable, e.g. the balance without an account
+    Skipped,
+}
+
+impl CheckStatus {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, CheckStatus::Failed(_))
+    }
+}
+
+/// The outcome of each check of [`Client::health_check`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    /// The node answers, on the chain of the API's network
+    pub rpc: CheckStatus,
+    /// The exchange contract has code at its address
+    pub exchange: CheckStatus,
+    /// The API answers a trivial query
+    pub api: CheckStatus,
+    /// The account has a balance
+    pub balance: CheckStatus,
+}
+
+impl HealthReport {
+    pub fn checks(&self) -> [(&'static str, &CheckStatus); 4] {
+        [
+            ("rpc", &self.rpc),
+            ("exchange", &self.exchange),
+            ("api", &self.api),
+            ("balance", &self.balance),
+        ]
+    }
+
+    /// Whether none of the checks failed
+    pub fn is_healthy(&self) -> bool {
+        self.checks().iter().all(|(_, status)| !status.is_failed())
+    }
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, status) in self.checks().iter() {
+            match status {
+                CheckStatus::Passed(detail) => writeln!(f, "{:<10} ok: {}", name, detail)?,
+                CheckStatus::Failed(reason) => writeln!(f, "{:<10} FAILED: {}", name, reason)?,
+                CheckStatus::Skipped => writeln!(f, "{:<10} skipped", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: Middleware> Client<M> {
+    /// Checks the setup of the client before it is used, with its signer's balance if
+    /// it has one (see [`Client::with_signer`]). Never errors: the failed checks are
+    /// reported, see [`HealthReport::is_healthy`].
+    pub async fn health_check(&self) -> HealthReport {
+        self.health_check_of(self.signer.as_ref().map(Signer::address))
+            .await
+    }
+
+    /// Same as [`Client::health_check`], with the balance of the `account` sending the
+    /// txs, e.g. when they are signed outside of the client
+    pub async fn health_check_of(&self, account: Option<Address>) -> HealthReport {
+        let onchain = async {
+            (
+                self.check_rpc().await,
+                self.check_exchange().await,
+                self.check_balance(account).await,
+            )
+        };
+        let api = async {
+            match self.api.get_payment_tokens().await {
+                Ok(tokens) => CheckStatus::Passed(format!("{} payment tokens", tokens.len())),
+                Err(err) => CheckStatus::Failed(err.to_string()),
+            }
+        };
+        let ((rpc, exchange, balance), api) = futures::join!(onchain, api);
+        HealthReport {
+            rpc,
+            exchange,
+            api,
+            balance,
+        }
+    }
+
+    async fn check_rpc(&self) -> CheckStatus {
+        let network = self.api.network();
+        match self.provider.get_chainid().await {
+            Ok(chain_id) if chain_id.as_u64() == network.chain_id() => {
+                CheckStatus::Passed(format!("chain {} ({:?})", chain_id, network))
+            }
+            Ok(chain_id) => CheckStatus::Failed(format!(
+                "the node is on chain {}, expected chain {} of {:?}",
+                chain_id,
+                network.chain_id(),
+                network
+            )),
+            Err(err) => CheckStatus::Failed(format!("the node is unreachable: {}", err)),
+        }
+    }
+
+    async fn check_exchange(&self) -> CheckStatus {
+        let exchange = self.contracts.address();
+        match self.provider.get_code(exchange, None).await {
+            Ok(code) if !code.as_ref().is_empty() => {
+                CheckStatus::Passed(format!("{:?} has code", exchange))
+            }
+            Ok(_) => CheckStatus::Failed(format!("no contract at {:?}", exchange)),
+            Err(err) => CheckStatus::Failed(err.to_string()),
+        }
+    }
+
+    async fn check_balance(&self, account: Option<Address>) -> CheckStatus {
+        let account = match account {
+            Some(account) => account,
+            None => return CheckStatus::Skipped,
+        };
+        match self.provider.get_balance(account, None).await {
+            Ok(balance) if balance.is_zero() => {
+                CheckStatus::Failed(format!("{:?} has no ETH", account))
+            }
+            Ok(balance) => {
+                CheckStatus::Passed(format!("{:?} holds {}", account, format_eth(balance)))
+            }
+            Err(err) => CheckStatus::Failed(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::OpenSeaApiConfig, OpenSeaApi};
+    use ethers::{
+        providers::Provider,
+        types::{Bytes, U256, U64},
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn reports_each_check() {
+        let (provider, mock) = Provider::mocked();
+        // nothing listens there
+        let cfg = OpenSeaApiConfig {
+            api_base: Some("http://127.0.0.1:1/".parse().unwrap()),
+            ..Default::default()
+        };
+        let client = Client::with_api(Arc::new(provider), OpenSeaApi::new(cfg).unwrap());
+        let account = Address::random();
+
+        // served last pushed first: the chain id, the code then the balance
+        mock.push(U256::exp10(17)).unwrap();
+        mock.push(Bytes::from(vec![0x60, 0x80])).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let report = client.health_check_of(Some(account)).await;
+        assert!(matches!(report.rpc, CheckStatus::Passed(_)));
+        assert!(matches!(report.exchange, CheckStatus::Passed(_)));
+        assert!(report.api.is_failed());
+        assert_eq!(
+            report.balance,
+            CheckStatus::Passed(format!("{:?} holds 0.1 ETH", account))
+        );
+        assert!(!report.is_healthy());
+
+        mock.push(U256::zero()).unwrap();
+        mock.push(Bytes::default()).unwrap();
+        mock.push(U64::from(5)).unwrap();
+        let report = client.health_check_of(Some(account)).await;
+        assert!(report.rpc.is_failed());
+        assert!(report.exchange.is_failed());
+        assert!(report.balance.is_failed());
+
+        mock.push(Bytes::from(vec![0x60])).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let report = client.health_check().await;
+        assert_eq!(report.balance, CheckStatus::Skipped);
+        assert!(report.to_string().contains("balance    skipped"));
+    }
+}
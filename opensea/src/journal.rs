@@ -0,0 +1,200 @@
+//! A journal of the purchases sent via the mempool and of their status, persisted
+//! after each change so that an interrupted plan can be resumed without buying its
+//! tokens twice, see [`SnipePlan::resume`](crate::SnipePlan::resume)
+use ethers::types::{Address, TransactionReceipt, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// A purchase tx which was sent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub nft: Address,
+    pub token_id: U256,
+    pub order_hash: H256,
+    pub tx_hash: H256,
+    pub nonce: Option<U256>,
+    /// `Sent` in the journals written before the status was recorded
+    #[serde(default)]
+    pub status: JournalStatus,
+}
+
+/// Whether a sent purchase tx was mined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Not known to be mined yet: pending, dropped or replaced
+    #[default]
+    Sent,
+    Mined,
+    /// Mined but reverted
+    Failed,
+}
+
+impl JournalStatus {
+    /// The status of the mined tx of the `receipt`
+    pub fn of(receipt: &TransactionReceipt) -> Self {
+        if receipt.status == Some(1.into()) {
+            JournalStatus::Mined
+        } else {
+            JournalStatus::Failed
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("Could not persist the journal: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid journal file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The purchases sent so far, persisted as JSON at its path after each of them.
+/// Cloning it shares the entries.
+#[derive(Clone, Debug)]
+pub struct BuyJournal {
+    path: PathBuf,
+    entries: Arc<Mutex<Vec<JournalEntry>>>,
+}
+
+impl BuyJournal {
+    /// Loads the entries persisted at `path`, if any
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// The purchases sent so far, in the order they were sent
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// The number of purchases of the token sent so far
+    pub fn sent(&self, nft: Address, token_id: U256) -> usize {
+        self.count(nft, token_id, |_| true)
+    }
+
+    /// The number of purchases of the token mined successfully, which are not bought
+    /// again when resuming
+    pub fn mined(&self, nft: Address, token_id: U256) -> usize {
+        self.count(nft, token_id, |status| status == JournalStatus::Mined)
+    }
+
+    /// The purchases not known to be mined yet
+    pub fn pending(&self) -> Vec<JournalEntry> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.status == JournalStatus::Sent)
+            .collect()
+    }
+
+    fn count(&self, nft: Address, token_id: U256, status: impl Fn(JournalStatus) -> bool) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.nft == nft && entry.token_id == token_id && status(entry.status))
+            .count()
+    }
+
+    /// Records the purchase and persists the journal
+    pub fn record(&self, entry: JournalEntry) -> Result<(), JournalError> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        self.persist(&entries)
+    }
+
+    /// Sets the status of the purchase sent by the tx and persists the journal
+    pub fn update(&self, tx_hash: H256, status: JournalStatus) -> Result<(), JournalError> {
+        let mut entries = self.entries.lock().unwrap();
+        for entry in entries.iter_mut().filter(|entry| entry.tx_hash == tx_hash) {
+            entry.status = status;
+        }
+        self.persist(&entries)
+    }
+
+    fn persist(&self, entries: &[JournalEntry]) -> Result<(), JournalError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // written to a temporary file first, so that a crash never leaves it truncated
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(entries)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persists_the_sent_purchases() {
+        let dir = std::env::temp_dir().join(format!("opensea-journal-{}", std::process::id()));
+        let path = dir.join("journal.json");
+        let nft = Address::random();
+
+        let journal = BuyJournal::open(&path).unwrap();
+        let mut hashes = Vec::new();
+        for token_id in [1, 1, 2] {
+            let tx_hash = H256::random();
+            hashes.push(tx_hash);
+            journal
+                .clone()
+                .record(JournalEntry {
+                    nft,
+                    token_id: token_id.into(),
+                    order_hash: H256::random(),
+                    tx_hash,
+                    nonce: Some(token_id.into()),
+                    status: JournalStatus::Sent,
+                })
+                .unwrap();
+        }
+        journal.update(hashes[0], JournalStatus::Mined).unwrap();
+        journal.update(hashes[2], JournalStatus::Failed).unwrap();
+
+        let journal = BuyJournal::open(&path).unwrap();
+        assert_eq!(journal.entries().len(), 3);
+        assert_eq!(journal.sent(nft, 1.into()), 2);
+        assert_eq!(journal.sent(nft, 2.into()), 1);
+        assert_eq!(journal.sent(nft, 3.into()), 0);
+        assert_eq!(journal.sent(Address::random(), 1.into()), 0);
+        // only the mined purchases are done
+        assert_eq!(journal.mined(nft, 1.into()), 1);
+        assert_eq!(journal.mined(nft, 2.into()), 0);
+        let pending: Vec<_> = journal
+            .pending()
+            .iter()
+            .map(|entry| entry.tx_hash)
+            .collect();
+        assert_eq!(pending, vec![hashes[1]]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_the_journals_without_status() {
+        let text = r#"[{
+            "nft": "0x0000000000000000000000000000000000000001",
+            "token_id": "0x1",
+            "order_hash": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "tx_hash": "0x0000000000000000000000000000000000000000000000000000000000000003",
+            "nonce": null
+        }]"#;
+        let entries: Vec<JournalEntry> = serde_json::from_str(text).unwrap();
+        assert_eq!(entries[0].status, JournalStatus::Sent);
+    }
+}
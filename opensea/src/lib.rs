@@ -1,172 +1,1346 @@
+/// Prints a human-readable message line like `println!`, to the stdout unless moved to
+/// the stderr, see [`display::messages_to_stderr`]
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::display::say(format_args!($($arg)*))
+    };
+}
+
 pub mod constants;
+pub use constants::Deployment;
 
 pub mod types;
 use api::OpenSeaApiConfig;
 use ethers::{
     contract::builders::ContractCall,
-    prelude::{Address, U256},
+    prelude::{Address, H256, U256},
     providers::Middleware,
-    utils::parse_units,
+    signers::LocalWallet,
+};
+pub use types::{BuyArgs, EffectiveCost, PreparedBuy, PreparedSale};
+use types::{
+    CollectionAsset, Network, Order, OrderMatchError, OrderSide, SignatureError, TraitFilter,
 };
-pub use types::BuyArgs;
-use types::Order;
 
 pub mod api;
-pub use api::{OpenSeaApi, OpenSeaApiError, OrderRequest};
+pub use api::{AssetsRequest, OpenSeaApi, OpenSeaApiError, OrderRequest, SignedOrder};
 
 mod contracts;
-pub use contracts::OpenSea;
+pub use contracts::{
+    Briber, LooksRareExchange, OpenSea, OwnableDelegateProxy, ProxyRegistry, PurchaseAggregator,
+    ERC20, NFT,
+};
+
+pub mod abort;
+pub use abort::{AbortHandle, AbortReason, SnipeProgress, SnipeStage};
+
+pub mod address_book;
+pub use address_book::AddressBook;
+
+pub mod aggregator;
+pub use aggregator::deploy_aggregator;
+
+pub mod analytics;
+pub use analytics::DailyStats;
+
+pub mod allocation;
+pub use allocation::{CapitalAllocator, OrderReservation};
+
+mod approvals;
+pub use approvals::{UnfulfillableReason, UserProxy};
+
+mod auction;
+
+pub mod payment_tokens;
+pub use payment_tokens::{PaymentToken, PaymentTokens};
+
+pub mod ownership;
+pub use ownership::{Ownership, OwnershipQuery, TokenStandard};
+
+pub mod onchain;
+
+pub mod fees;
+pub use fees::{BaseFeeProjection, FeeEstimator, FeeHistoryEstimator, Fees, FixedFees};
+
+pub mod display;
+
+pub mod finality;
+pub use finality::{FinalOutcome, Finality, PurchaseConfirmation};
+
+pub mod fills;
+pub use fills::{plan_fills, Fill, FillError, FillPlan};
+
+pub mod gas;
+pub use gas::{FixedGas, GasConfig, GasOracle, GasSample, HistoricalGas};
+
+pub mod units;
+
+pub mod time_source;
+pub use time_source::TimeSource;
+
+pub mod events;
+pub use events::{Event, EventFeed, Verdict};
+
+pub mod health;
+pub use health::{CheckStatus, HealthReport};
+
+pub mod journal;
+pub use journal::BuyJournal;
+
+mod simulate;
+
+pub mod sim_export;
+pub use sim_export::{anvil_script, tenderly_bundle};
+
+pub mod cancellations;
+pub use cancellations::Cancellation;
+
+pub mod cleanup;
+pub use cleanup::{OrderCleanup, PreparedCancel};
+
+pub mod deadline;
+pub use deadline::Deadline;
+
+pub mod escalator;
+pub use escalator::Escalator;
+
+pub mod bundle_simulation;
+pub use bundle_simulation::{BundleSimulationReport, TxSimulation};
+
+pub mod bribe;
+pub use bribe::{BribeChecks, BribeStrategy};
+
+pub mod scheduler;
+pub use scheduler::{BuyIntent, Scheduler};
+
+pub mod snipe;
+pub use snipe::{
+    Atomicity, Bribe, IncludedBundle, ListingWindow, SentTx, SnipeError, SnipeOutcome, SnipePlan,
+    SnipeStrategy, SnipeTarget,
+};
+
+pub mod recording;
+pub use recording::{Recording, RunRecorder};
+
+pub mod report;
+pub use report::{Purchase, PurchaseReport, PurchaseStatus};
+
+pub mod sales;
+pub use sales::Sale;
+
+pub mod snapshot;
+pub use snapshot::{orderbook_snapshot, OrderBookDiff, OrderBookSnapshot, OrderbookRow};
+
+pub mod static_call;
+pub use static_call::StaticCall;
+
+pub mod storefront;
+pub use storefront::SharedStorefrontId;
+
+pub mod summary;
+pub use summary::{PlannedPurchase, SnipeSummary};
+
+pub mod swap;
+pub use simulate::decode_revert_reason;
+pub use swap::{PaymentPreview, PaymentSwap, SwapConfig};
+
+pub mod trait_floor;
+pub use trait_floor::{trait_floor, TraitFloor};
+
+pub mod transfers;
+pub use transfers::TransferWatch;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+mod offers;
+
+pub mod collection_offers;
+pub use collection_offers::AcceptedOffer;
+
+pub mod order_provider;
+pub use order_provider::{MockOrderProvider, OrderProvider};
+
+pub mod metrics;
+
+pub mod monitor;
+pub use monitor::{abort_on_price_moves, PriceEvent, PriceMonitor};
+
+pub mod listing;
+pub use listing::{sign_listing, ListingArgs, ListingMode};
+
+pub mod relist;
+pub use relist::RelistConfig;
+
+pub mod safe_mode;
+pub use safe_mode::{SafeMode, SafeModeViolation};
+
+pub mod looksrare;
+pub use looksrare::{LooksRare, LooksRareOrder};
+
+pub mod marketplace;
+pub use marketplace::{Listing, MarketOrder, Marketplace};
+
+pub mod nonce;
+pub use nonce::{NonceAllocator, NonceReservation};
 
-use std::sync::Arc;
+pub mod wyvern_error;
+pub use wyvern_error::WyvernError;
+
+mod requote;
+
+pub mod validation;
+
+mod wallet;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use thiserror::Error;
 use types::MinimalOrder;
 
-pub async fn get_n_cheapest_orders(
-    api: &OpenSeaApi,
+/// The max page size of the assets API
+const ASSETS_PAGE_SIZE: u64 = 50;
+
+/// The max number of tokens whose orders are queried at once
+pub const MAX_TOKEN_IDS_PER_QUERY: usize = 50;
+
+/// The listings kept by [`get_n_cheapest_orders_with`] besides the ones which can be
+/// bought right away
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListingFilter {
+    /// Keep the expired, cancelled, finalized or invalid orders, see
+    /// [`Order::is_fillable`]
+    pub include_stale: bool,
+    /// Keep the English auctions, which can only be bid on, see
+    /// [`Order::is_english_auction`]
+    pub include_english_auctions: bool,
+    /// Only the listings whose current price is above it are kept, to filter out the
+    /// "noise" listings which exist in OpenSea. Defaults to 1e16 Wei (0.01 ETH), set it
+    /// to zero to keep cheap listings such as low-value ERC1155s.
+    pub min_price: U256,
+    /// Skip the listings of the assets which cannot be resold on OpenSea, e.g. flagged
+    /// as stolen (see [`CollectionAsset::is_transfer_locked`]). Requires querying the
+    /// assets API.
+    pub exclude_flagged: bool,
+}
+
+impl Default for ListingFilter {
+    fn default() -> Self {
+        Self {
+            include_stale: false,
+            include_english_auctions: false,
+            min_price: U256::exp10(16),
+            exclude_flagged: false,
+        }
+    }
+}
+
+/// How [`get_best_orders`] ranks the orders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderOrdering {
+    /// The best ones for the taker first: the cheapest asks, or the highest bids
+    Best,
+    /// The cheapest first. The price of the asks includes the taker fees, see
+    /// [`Order::effective_cost`].
+    PriceAscending,
+    /// The most expensive first
+    PriceDescending,
+    /// The most recently listed first
+    Newest,
+}
+
+/// Returns the `num` best orders of the token on the `side`, ranked by the `ordering`:
+/// asks (`OrderSide::Sell`) to buy the token, or bids (`OrderSide::Buy`) to accept.
+/// The stale orders and English auctions are skipped.
+pub async fn get_best_orders<P: OrderProvider>(
+    api: &P,
     contract_address: Address,
     token_id: U256,
+    side: OrderSide,
     num: usize,
+    ordering: OrderOrdering,
+) -> Result<Vec<Order>, ClientError> {
+    let req = OrderRequest {
+        side,
+        token_ids: vec![token_id.to_string()],
+        contract_address,
+        limit: 50,
+        offset: 0,
+    };
+    let now = unix_timestamp();
+    let mut orders = unique_orders(api.get_orders(req).await?)
+        .into_iter()
+        .filter(|order| order.is_fillable(now) && !order.is_english_auction())
+        .collect::<Vec<_>>();
+    sort_orders(&mut orders, side, ordering);
+    orders.truncate(num);
+    Ok(orders)
+}
+
+/// Returns the `num` cheapest sell orders of the token which can be bought, see
+/// [`get_n_cheapest_orders_with`], and [`get_n_cheapest_orders_across`] for the listings
+/// of other marketplaces as well
+pub async fn get_n_cheapest_orders<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_id: U256,
+    num: usize,
+) -> Result<Vec<Order>, ClientError> {
+    get_n_cheapest_orders_with(
+        api,
+        contract_address,
+        token_id,
+        num,
+        ListingFilter::default(),
+    )
+    .await
+}
+
+/// Returns the `num` cheapest sell orders of the token, by price including the taker
+/// fees (see [`Order::effective_cost`]), whose breakdown is returned as their
+/// [`Order::ranked_cost`]. The stale orders and English auctions are skipped unless
+/// included by the `filter`.
+pub async fn get_n_cheapest_orders_with<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_id: U256,
+    num: usize,
+    filter: ListingFilter,
 ) -> Result<Vec<Order>, ClientError> {
     // get the order
     let req = OrderRequest {
-        side: 1,
-        token_id: token_id.to_string(),
+        side: OrderSide::Sell,
+        token_ids: vec![token_id.to_string()],
         contract_address,
         // use max limit
         limit: 50,
+        offset: 0,
     };
+    let orders = unique_orders(api.get_orders(req).await?);
+    if filter.exclude_flagged
+        && !flagged_tokens(api, contract_address, &[token_id])
+            .await?
+            .is_empty()
+    {
+        return Ok(Vec::new());
+    }
+    Ok(cheapest_orders(orders, num, filter, unix_timestamp()))
+}
 
-    // get the cheapest orders above 1e16 Wei. Used to filter out "noise"
-    // bids which exist in OpenSea, presumably a bug?
-    let mut orders = api
-        .get_orders(req)
-        .await?
+/// Returns the `num` cheapest listings of the token across the marketplaces, e.g. the
+/// [`Client`] for OpenSea and [`LooksRare`], by their prices normalized by the `tokens`
+/// (see [`Listing::normalized_price`]). The listings in unknown payment tokens come
+/// last, and the marketplaces whose listings cannot be queried are skipped.
+pub async fn get_n_cheapest_orders_across<M: Middleware>(
+    marketplaces: &[&dyn Marketplace<M>],
+    nft: Address,
+    token_id: U256,
+    num: usize,
+    tokens: &PaymentTokens,
+) -> Vec<Listing> {
+    let mut listings = Vec::new();
+    for marketplace in marketplaces {
+        match marketplace.get_listings(nft, token_id, num).await {
+            Ok(found) => listings.extend(found),
+            Err(err) => say!(
+                "Skipping the listings of {} on {}: {}",
+                token_id,
+                marketplace.name(),
+                err
+            ),
+        }
+    }
+    // `None` sorts first, so the unknown tokens are sorted as the highest prices
+    listings.sort_by_key(|listing| {
+        let price = listing.normalized_price(tokens);
+        (price.is_none(), price)
+    });
+    listings.truncate(num);
+    listings
+}
+
+/// Returns the `num` cheapest sell orders of each of the tokens which can be bought, see
+/// [`get_n_cheapest_orders_by_token_with`]
+pub async fn get_n_cheapest_orders_by_token<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_ids: &[U256],
+    num: usize,
+) -> Result<HashMap<U256, Vec<Order>>, ClientError> {
+    get_n_cheapest_orders_by_token_with(
+        api,
+        contract_address,
+        token_ids,
+        num,
+        ListingFilter::default(),
+    )
+    .await
+}
+
+/// Returns the `num` cheapest sell orders of each of the tokens, querying the orders of
+/// up to [`MAX_TOKEN_IDS_PER_QUERY`] tokens at once and paging through the orders of
+/// each query, see [`OrderProvider::get_all_orders`]. Tokens without listings kept by the `filter`
+/// are omitted. The orders returned by several queries, e.g. if a token id is given
+/// twice, are only kept once.
+pub async fn get_n_cheapest_orders_by_token_with<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_ids: &[U256],
+    num: usize,
+    filter: ListingFilter,
+) -> Result<HashMap<U256, Vec<Order>>, ClientError> {
+    cheapest_orders_by_token_at(
+        api,
+        contract_address,
+        token_ids,
+        num,
+        filter,
+        unix_timestamp(),
+    )
+    .await
+}
+
+/// Same as [`get_n_cheapest_orders_by_token_with`], keeping the orders fillable at the
+/// `now` unix timestamp, e.g. the time of a recorded run, see [`recording`]
+pub(crate) async fn cheapest_orders_by_token_at<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_ids: &[U256],
+    num: usize,
+    filter: ListingFilter,
+    now: u64,
+) -> Result<HashMap<U256, Vec<Order>>, ClientError> {
+    let mut by_token: HashMap<U256, Vec<Order>> = HashMap::new();
+    for chunk in token_ids.chunks(MAX_TOKEN_IDS_PER_QUERY) {
+        let req = OrderRequest {
+            side: OrderSide::Sell,
+            token_ids: chunk.iter().map(|id| id.to_string()).collect(),
+            contract_address,
+            limit: 50,
+            offset: 0,
+        };
+        for order in api.get_all_orders(req).await? {
+            if let Some(metadata) = order.asset_metadata() {
+                by_token.entry(metadata.asset.id).or_default().push(order);
+            }
+        }
+    }
+    let flagged = if filter.exclude_flagged {
+        let ids: Vec<_> = by_token.keys().copied().collect();
+        flagged_tokens(api, contract_address, &ids).await?
+    } else {
+        HashSet::new()
+    };
+    Ok(by_token
         .into_iter()
-        .filter(|order| order.base_price > parse_units("1", 16).unwrap())
+        .filter(|(id, _)| !flagged.contains(id))
+        .map(|(id, orders)| {
+            let orders = cheapest_orders(unique_orders(orders), num, filter, now);
+            (id, orders)
+        })
+        .filter(|(_, orders)| !orders.is_empty())
+        .collect())
+}
+
+/// Returns the ids of the tokens of the collection whose traits match all the `filters`,
+/// paging through the assets API
+pub async fn token_ids_by_traits<P: OrderProvider>(
+    api: &P,
+    collection: Address,
+    filters: &[TraitFilter],
+) -> Result<Vec<U256>, ClientError> {
+    let mut ids = Vec::new();
+    let mut offset = 0;
+    // the assets API does not serve offsets past 10k
+    while offset < 10_000 {
+        let assets = api
+            .get_assets(AssetsRequest {
+                contract_address: collection,
+                token_ids: Vec::new(),
+                offset,
+                limit: ASSETS_PAGE_SIZE,
+            })
+            .await?;
+        let last_page = (assets.len() as u64) < ASSETS_PAGE_SIZE;
+        ids.extend(
+            assets
+                .into_iter()
+                .filter(|asset| filters.iter().all(|filter| filter.matches(asset)))
+                .map(|asset| asset.token_id),
+        );
+        if last_page {
+            break;
+        }
+        offset += ASSETS_PAGE_SIZE;
+    }
+    Ok(ids)
+}
+
+/// Returns the tokens which cannot be resold on OpenSea, see
+/// [`CollectionAsset::is_transfer_locked`]
+async fn flagged_tokens<P: OrderProvider>(
+    api: &P,
+    contract_address: Address,
+    token_ids: &[U256],
+) -> Result<HashSet<U256>, ClientError> {
+    let mut flagged = HashSet::new();
+    for chunk in token_ids.chunks(ASSETS_PAGE_SIZE as usize) {
+        let assets = api
+            .get_assets(AssetsRequest {
+                contract_address,
+                token_ids: chunk.to_vec(),
+                offset: 0,
+                limit: ASSETS_PAGE_SIZE,
+            })
+            .await?;
+        flagged.extend(
+            assets
+                .into_iter()
+                .filter(CollectionAsset::is_transfer_locked)
+                .map(|asset| asset.token_id),
+        );
+    }
+    Ok(flagged)
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Sorts the orders of the `side` by the `ordering`
+fn sort_orders(orders: &mut [Order], side: OrderSide, ordering: OrderOrdering) {
+    // the gas of a purchase is the same for all listings, so it does not change the order
+    if side == OrderSide::Sell {
+        for order in orders.iter_mut() {
+            order.ranked_cost = Some(order.effective_cost(U256::zero()));
+        }
+    }
+    let price = |order: &Order| {
+        order
+            .ranked_cost
+            .map_or(order.current_price, |cost| cost.total())
+    };
+    match (ordering, side) {
+        (OrderOrdering::Best, OrderSide::Sell) | (OrderOrdering::PriceAscending, _) => {
+            orders.sort_by_key(price)
+        }
+        (OrderOrdering::Best, OrderSide::Buy) | (OrderOrdering::PriceDescending, _) => {
+            orders.sort_by_key(|order| std::cmp::Reverse(price(order)))
+        }
+        (OrderOrdering::Newest, _) => {
+            orders.sort_by_key(|order| std::cmp::Reverse(order.listing_time))
+        }
+    }
+}
+
+/// Removes the repeated orders, by `order_hash`, keeping the first occurrence of each.
+/// Overlapping queries (pages, batches of token ids) may return the same order twice,
+/// which would then be bought twice.
+pub fn unique_orders(orders: Vec<Order>) -> Vec<Order> {
+    let mut seen = HashSet::with_capacity(orders.len());
+    orders
+        .into_iter()
+        .filter(|order| seen.insert(order.order_hash))
+        .collect()
+}
+
+fn cheapest_orders(orders: Vec<Order>, num: usize, filter: ListingFilter, now: u64) -> Vec<Order> {
+    // the current price, as the price of Dutch auctions declines from their base price
+    let mut orders = orders
+        .into_iter()
+        .filter(|order| order.current_price > filter.min_price)
+        .filter(|order| filter.include_stale || order.is_fillable(now))
+        .filter(|order| filter.include_english_auctions || !order.is_english_auction())
         .collect::<Vec<_>>();
-    orders.sort_by(|o1, o2| o1.current_price.cmp(&o2.current_price));
+    sort_orders(&mut orders, OrderSide::Sell, OrderOrdering::Best);
 
     // get at most `orders.len()` items
     let len = std::cmp::min(num, orders.len());
-    Ok(orders.into_iter().take(len).collect())
+    orders.into_iter().take(len).collect()
+}
+
+/// Checks that the requested quantity can be bought out of the sell order
+fn validate_quantity(sell: &Order, requested: Option<U256>) -> Result<(), ClientError> {
+    let requested = match requested {
+        Some(requested) => requested,
+        None => return Ok(()),
+    };
+    let available = sell.quantity;
+    if requested.is_zero() || requested > available {
+        return Err(ClientError::InvalidQuantity {
+            requested,
+            available,
+        });
+    }
+    // Wyvern matches listings whole
+    if requested < available {
+        return Err(ClientError::PartialFillUnsupported {
+            requested,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// The addresses, uints and uint8s of a Wyvern match, in the format the contracts
+/// expect them (`atomicMatch_`, `ordersCanMatch_`)
+pub(crate) fn match_args(
+    buy: &MinimalOrder,
+    sell: &MinimalOrder,
+) -> ([Address; 14], [U256; 18], [U256; 8]) {
+    let addrs = [
+        buy.exchange,
+        buy.maker,
+        buy.taker,
+        buy.fee_recipient,
+        buy.target,
+        buy.static_target,
+        buy.payment_token,
+        sell.exchange,
+        sell.maker,
+        sell.taker,
+        sell.fee_recipient,
+        sell.target,
+        sell.static_target,
+        sell.payment_token,
+    ];
+    let uints = [
+        buy.maker_relayer_fee,
+        buy.taker_relayer_fee,
+        buy.maker_protocol_fee,
+        buy.taker_protocol_fee,
+        buy.base_price,
+        buy.extra,
+        buy.listing_time,
+        buy.expiration_time,
+        buy.salt,
+        sell.maker_relayer_fee,
+        sell.taker_relayer_fee,
+        sell.maker_protocol_fee,
+        sell.taker_protocol_fee,
+        sell.base_price,
+        sell.extra,
+        sell.listing_time,
+        sell.expiration_time,
+        sell.salt,
+    ];
+
+    // passing it u8 returns an InvalidData error due to ethabi interpreting
+    // them wrongly, so we need to convert them to u256
+    // to work :shrug:
+    let methods = [
+        U256::from(buy.fee_method),
+        buy.side.into(),
+        buy.sale_kind.into(),
+        buy.how_to_call.into(),
+        sell.fee_method.into(),
+        sell.side.into(),
+        sell.sale_kind.into(),
+        sell.how_to_call.into(),
+    ];
+    (addrs, uints, methods)
+}
+
+/// The `vs` and `rssMetadata` arguments of `atomicMatch_`: the `v`s of the buy and sell
+/// orders, then their `r`s and `s`s followed by the metadata. The order sent by its
+/// maker is not signed, and no metadata is attached.
+pub(crate) fn signature_args(buy: &MinimalOrder, sell: &MinimalOrder) -> ([U256; 2], [H256; 5]) {
+    // `uint8`s are passed as U256s, see `match_args`
+    let vs = [buy.v.into(), sell.v.into()];
+    let rss_metadata = [buy.r, buy.s, sell.r, sell.s, H256::zero()];
+    (vs, rss_metadata)
+}
+
+/// The addresses and uints of a single order, in the format the contracts expect them
+/// (`cancelOrder_`, `calculateCurrentPrice_`)
+fn order_args(order: &MinimalOrder) -> ([Address; 7], [U256; 9]) {
+    let addrs = [
+        order.exchange,
+        order.maker,
+        order.taker,
+        order.fee_recipient,
+        order.target,
+        order.static_target,
+        order.payment_token,
+    ];
+    let uints = [
+        order.maker_relayer_fee,
+        order.taker_relayer_fee,
+        order.maker_protocol_fee,
+        order.taker_protocol_fee,
+        order.base_price,
+        order.extra,
+        order.listing_time,
+        order.expiration_time,
+        order.salt,
+    ];
+    (addrs, uints)
+}
+
+/// Checks that buying a listing at `price`, after having spent `spent` in the same
+/// call, stays within the limits of the args
+pub(crate) fn validate_price(args: &BuyArgs, price: U256, spent: U256) -> Result<(), ClientError> {
+    if let Some(limit) = args.max_price {
+        if price > limit {
+            return Err(ClientError::PriceAboveLimit {
+                token_id: args.token_id,
+                price,
+                limit,
+            });
+        }
+    }
+    if let Some(budget) = args.total_budget {
+        if spent + price > budget {
+            return Err(ClientError::BudgetExceeded {
+                required: spent + price,
+                budget,
+            });
+        }
+    }
+    Ok(())
 }
 
 #[derive(Clone)]
-pub struct Client<M> {
-    pub api: OpenSeaApi,
+pub struct Client<M, P = OpenSeaApi> {
+    /// The source of the orders, see [`OrderProvider`]
+    pub api: P,
     pub contracts: OpenSea<M>,
+    /// The addresses of the Wyvern deployment, mainnet's unless set by [`Client::new`]
+    /// or [`Client::with_deployment`]
+    pub deployment: Deployment,
+    /// The provider used for all on-chain calls
+    pub provider: Arc<M>,
+    /// Nonce allocator shared by all clones of this client
+    pub nonces: NonceAllocator,
+    /// Optional feed of the actions taken, see [`EventFeed`]
+    pub events: Option<EventFeed>,
+    /// Whether [`Client::buy`] skips the listings which fail
+    /// [`Client::verify_fulfillable`] or [`Client::verify_static_calls`]. Enabled by
+    /// default.
+    pub check_fulfillable: bool,
+    /// Optional wallet signing and sending the buy calls, see [`Client::with_signer`]
+    pub signer: Option<LocalWallet>,
+    /// The listings considered when buying, see [`ListingFilter`]
+    pub listing_filter: ListingFilter,
+    /// Whether [`Client::buy`] double-checks the API's `current_price` of the listings
+    /// against [`Client::current_price_onchain`], paying the on-chain price when they
+    /// differ by more than these basis points. Disabled by default, as it takes an
+    /// `eth_call` per listing.
+    pub onchain_price_tolerance_bps: Option<u64>,
+    /// The collections and makers [`Client::buy`] may buy from, see [`SafeMode`]
+    pub safe_mode: SafeMode,
+    /// The listing time of the buy orders whose `BuyArgs::timestamp` is unset, the
+    /// latest block's by default
+    pub time_source: TimeSource,
+    /// How the gas limit of the purchases is set, see [`GasConfig`]
+    pub gas: GasConfig,
 }
 
 #[derive(Debug, Error)]
 pub enum ClientError {
     #[error(transparent)]
     OpenSeaApiError(#[from] OpenSeaApiError),
+    #[error(transparent)]
+    Wyvern(#[from] WyvernError),
+    #[error("Simulation failed: {0}")]
+    Simulation(String),
+    #[error("Provider error: {0}")]
+    Provider(String),
+    #[error("Contract call failed: {0}")]
+    Contract(String),
+    #[error("No Wyvern proxy registered for {0:?}")]
+    ProxyNotRegistered(Address),
+    #[error(transparent)]
+    InvalidSignature(#[from] SignatureError),
+    #[error(transparent)]
+    OrderMatch(#[from] OrderMatchError),
+    #[error(transparent)]
+    Fill(#[from] FillError),
+    #[error("Invalid quantity {requested} requested, {available} available")]
+    InvalidQuantity { requested: U256, available: U256 },
+    #[error("Listings are bought whole, not {requested} out of {available}")]
+    PartialFillUnsupported { requested: U256, available: U256 },
+    #[error("Listing of token {token_id} costs {price} Wei, above the limit of {limit} Wei")]
+    PriceAboveLimit {
+        token_id: U256,
+        price: U256,
+        limit: U256,
+    },
+    #[error("Buying requires {required} Wei, above the budget of {budget} Wei")]
+    BudgetExceeded { required: U256, budget: U256 },
+    #[error("Order {order_hash:?} cannot be filled: {reason}")]
+    Unfulfillable {
+        order_hash: H256,
+        reason: UnfulfillableReason,
+    },
+    #[error("Order {0:?} is not an English auction")]
+    NotEnglishAuction(H256),
+    #[error("Bid of {bid} is below the minimum of {minimum}")]
+    BidTooLow { bid: U256, minimum: U256 },
+    #[error("Signer error: {0}")]
+    Signer(String),
+    #[error("No signer configured, see `Client::with_signer`")]
+    NoSigner,
+    #[error("{seller:?} does not hold the token {token_id} of {nft:?}")]
+    NotHeld {
+        seller: Address,
+        nft: Address,
+        token_id: U256,
+    },
+    #[error("Action vetoed: {0}")]
+    Vetoed(String),
+    #[error("Event feed error: {0}")]
+    EventFeed(String),
+    #[error("Price of order {order_hash:?} moved from {quoted} to {current}")]
+    PriceMoved {
+        order_hash: H256,
+        quoted: U256,
+        current: U256,
+    },
+    #[error("Order {0:?} is no longer listed or cannot be filled")]
+    ListingUnavailable(H256),
+    #[error("Order {order_hash:?} is rejected by the safe mode: {reason}")]
+    Disallowed {
+        order_hash: H256,
+        reason: SafeModeViolation,
+    },
+    #[error("{marketplace} error: {message}")]
+    Marketplace {
+        marketplace: &'static str,
+        message: String,
+    },
+    #[error("The provider is on chain {actual}, expected chain {expected} of {network:?}")]
+    WrongChain {
+        network: Network,
+        expected: u64,
+        actual: u64,
+    },
 }
 
 impl<M: Middleware> Client<M> {
-    pub fn new(provider: Arc<M>, cfg: OpenSeaApiConfig) -> Self {
+    /// Instantiates the client for the configured network, erroring with
+    /// `ClientError::WrongChain` if the provider is on another chain. Use
+    /// [`Client::with_api`] to skip the check, e.g. on a local fork.
+    pub async fn new(provider: Arc<M>, cfg: OpenSeaApiConfig) -> Result<Self, ClientError> {
+        let network = cfg.network;
+        let mut client = Self::with_api(provider, OpenSeaApi::new(cfg)?);
+        let actual = client.chain_id().await?;
+        if actual != network.chain_id() {
+            return Err(ClientError::WrongChain {
+                network,
+                expected: network.chain_id(),
+                actual,
+            });
+        }
+        if let Some(deployment) = network.deployment() {
+            client = client.with_deployment(deployment);
+        }
+        Ok(client)
+    }
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Instantiates the client with an already constructed order provider, e.g. an
+    /// API built via [`OpenSeaApi::with_client`] or a [`MockOrderProvider`]
+    pub fn with_api(provider: Arc<M>, api: P) -> Self {
         Self {
-            api: OpenSeaApi::new(cfg),
-            contracts: OpenSea::new(*constants::OPENSEA_ADDRESS, provider),
+            api,
+            contracts: OpenSea::new(*constants::OPENSEA_ADDRESS, provider.clone()),
+            deployment: *constants::MAINNET,
+            provider,
+            nonces: NonceAllocator::new(),
+            events: None,
+            check_fulfillable: true,
+            signer: None,
+            listing_filter: ListingFilter::default(),
+            onchain_price_tolerance_bps: None,
+            safe_mode: SafeMode::default(),
+            time_source: TimeSource::default(),
+            gas: GasConfig::default(),
+        }
+    }
+
+    /// Sets how the gas limit of the purchases is set, e.g. from the recent sales of
+    /// their collections, see [`Client::historical_gas`]. The limit of a purchase is
+    /// its collection's median plus a margin of [`gas::DEFAULT_GAS_MARGIN_BPS`] (25%),
+    /// unless set via [`HistoricalGas::margin_bps`].
+    pub fn with_gas(mut self, gas: GasConfig) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Targets the exchange of the deployment, e.g. [`constants::RINKEBY`] on a fork of
+    /// Rinkeby, with its proxies and tokens
+    pub fn with_deployment(mut self, deployment: Deployment) -> Self {
+        self.contracts = OpenSea::new(deployment.exchange, self.provider.clone());
+        self.deployment = deployment;
+        self
+    }
+
+    /// Sets where the listing times of the buy orders come from, see [`TimeSource`]
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Sets the listing time of the buy orders from the client's time source, unless
+    /// the `args` set it
+    pub(crate) async fn with_listing_time(&self, args: BuyArgs) -> Result<BuyArgs, ClientError> {
+        if args.timestamp.is_some() {
+            return Ok(args);
         }
+        let timestamp = self
+            .time_source
+            .listing_time(self.provider.as_ref())
+            .await?;
+        Ok(BuyArgs {
+            timestamp: Some(timestamp),
+            ..args
+        })
     }
 
-    pub async fn buy(
+    /// Sets the feed to which all significant actions are reported
+    pub fn with_events(mut self, events: EventFeed) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Emits the event to the feed, if any. Errors with `ClientError::Vetoed`
+    /// if the consumer vetoed the action.
+    pub async fn emit(&self, event: Event) -> Result<(), ClientError> {
+        match self.events {
+            Some(ref events) => events.emit(event).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn buy(&self, args: BuyArgs, n: usize) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        say!(
+            "Querying Opensea API for {} orders of token {}",
+            n,
+            args.token_id
+        );
+        let sells = self.cheapest_listings(args.token, args.token_id, n).await?;
+        let args = self.with_listing_time(args).await?;
+        let chain_id = self.chain_id().await?;
+        let mut spent = U256::zero();
+        self.buy_orders(&args, sells, chain_id, &mut spent).await
+    }
+
+    /// Returns the buy calls for the `n` cheapest listings among the tokens of the
+    /// `args.token` collection whose traits match all the `filters`, skipping the ones
+    /// listed above `args.max_price`. The `args.token_id` is ignored.
+    ///
+    /// The matching tokens are resolved via the assets API, and each of them is then
+    /// queried for its cheapest listing.
+    pub async fn buy_by_traits(
         &self,
         args: BuyArgs,
+        filters: &[TraitFilter],
         n: usize,
-    ) -> Result<Vec<ContractCall<M, ()>>, ClientError> {
-        println!(
-            "Querying Opensea API for {} orders of token {}",
-            n, args.token_id
+    ) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        let ids = self.token_ids_by_traits(args.token, filters).await?;
+        say!(
+            "Found {} tokens matching the traits, querying their listings",
+            ids.len()
         );
-        let sells = get_n_cheapest_orders(&self.api, args.token, args.token_id, n).await?;
 
-        let mut calls = Vec::new();
+        let mut listings = Vec::new();
+        for token_id in ids {
+            let sell = self.cheapest_listings(args.token, token_id, 1).await?;
+            match (sell.into_iter().next(), args.max_price) {
+                (Some(sell), Some(max)) if sell.current_price > max => {}
+                (Some(sell), _) => listings.push((token_id, sell)),
+                (None, _) => {}
+            }
+        }
+        listings.sort_by_key(|(_, sell)| sell.effective_cost(U256::zero()).total());
+        if listings.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let args = self.with_listing_time(args).await?;
+        let chain_id = self.chain_id().await?;
+        let listings: Vec<_> = listings
+            .into_iter()
+            .take(n)
+            .map(|(token_id, sell)| {
+                let args = BuyArgs {
+                    token_id,
+                    ..args.clone()
+                };
+                (args, sell)
+            })
+            .collect();
+        let mut verified = self.preflight_listings(&listings).await;
+        let mut buys = Vec::new();
+        let mut spent = U256::zero();
+        for (args, sell) in listings {
+            buys.extend(
+                self.buy_verified_orders(&args, vec![sell], chain_id, &mut spent, &mut verified)
+                    .await?,
+            );
+        }
+        Ok(buys)
+    }
+
+    /// Preflights the listings of the `args` tokens in one batch, see
+    /// [`Client::preflight`], returning the outcome of each by hash. The listings whose
+    /// schema is yet to be detected (see [`Client::with_detected_schema`]) or which cannot
+    /// be matched are left out, to be checked on their own when bought. Empty unless
+    /// `check_fulfillable`.
+    pub(crate) async fn preflight_listings(
+        &self,
+        listings: &[(BuyArgs, Order)],
+    ) -> HashMap<H256, Result<(), ClientError>> {
+        if !self.check_fulfillable {
+            return HashMap::new();
+        }
+        let orders: Vec<_> = listings
+            .iter()
+            .filter(|(_, sell)| {
+                sell.asset_metadata()
+                    .map(|metadata| {
+                        !storefront::is_shared_storefront(metadata.asset.address)
+                            && (metadata.schema == "ERC721" || metadata.schema == "ERC1155")
+                    })
+                    .unwrap_or(false)
+            })
+            .filter_map(|(args, sell)| Some((sell.clone(), sell.match_sell(args.clone()).ok()?)))
+            .collect();
+        if orders.is_empty() {
+            return HashMap::new();
+        }
+        let results = self.preflight(&orders, None).await;
+        orders
+            .iter()
+            .map(|(sell, _)| sell.order_hash)
+            .zip(results)
+            .collect()
+    }
+
+    /// Returns the `n` cheapest listings of the token kept by the client's `listing_filter`
+    pub(crate) async fn cheapest_listings(
+        &self,
+        token: Address,
+        token_id: U256,
+        n: usize,
+    ) -> Result<Vec<Order>, ClientError> {
+        get_n_cheapest_orders_with(&self.api, token, token_id, n, self.listing_filter).await
+    }
+
+    /// Returns the ids of the tokens of the collection whose traits match all the `filters`
+    pub async fn token_ids_by_traits(
+        &self,
+        collection: Address,
+        filters: &[TraitFilter],
+    ) -> Result<Vec<U256>, ClientError> {
+        token_ids_by_traits(&self.api, collection, filters).await
+    }
+
+    /// Builds the buy calls for the given sell orders of `args.token_id`, tracking the
+    /// amount `spent` against the budget. With `args.quantity`, exactly that quantity is
+    /// bought across the sell orders, see [`plan_fills`]: the orders skipped once planned
+    /// (disallowed, vetoed or stale) are replaced by planning the rest of the quantity
+    /// again.
+    async fn buy_orders(
+        &self,
+        args: &BuyArgs,
+        sells: Vec<Order>,
+        chain_id: u64,
+        spent: &mut U256,
+    ) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        let listings: Vec<_> = sells
+            .iter()
+            .map(|sell| (args.clone(), sell.clone()))
+            .collect();
+        let mut verified = self.preflight_listings(&listings).await;
+        self.buy_verified_orders(args, sells, chain_id, spent, &mut verified)
+            .await
+    }
+
+    /// Same as [`Client::buy_orders`], the outcomes of the checks of the listings
+    /// already preflighted being taken from `verified`, see [`Client::preflight_listings`]
+    pub(crate) async fn buy_verified_orders(
+        &self,
+        args: &BuyArgs,
+        sells: Vec<Order>,
+        chain_id: u64,
+        spent: &mut U256,
+        verified: &mut HashMap<H256, Result<(), ClientError>>,
+    ) -> Result<Vec<PreparedBuy<M>>, ClientError> {
+        let mut buys = Vec::new();
+        let quantity = match args.quantity {
+            Some(quantity) => quantity,
+            None => {
+                for sell in sells {
+                    let sell = self.with_detected_schema(sell).await;
+                    let checked = verified.remove(&sell.order_hash);
+                    if let Some(buy) = self
+                        .prepare_buy(args, sell, chain_id, spent, checked)
+                        .await?
+                    {
+                        buys.push(buy);
+                    }
+                }
+                return Ok(buys);
+            }
+        };
+
+        // a quantity is spread across the sells, rather than bought from each of them
+        let gas_price = self
+            .provider
+            .get_gas_price()
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+        let mut candidates = Vec::with_capacity(sells.len());
         for sell in sells {
-            println!(
-                "[Token Id = {:?}] Maker: {:?}. Price: {:?}",
-                args.token_id, sell.maker.address, sell.current_price,
+            candidates.push(self.with_detected_schema(sell).await);
+        }
+        let mut remaining = quantity;
+        while !remaining.is_zero() {
+            let plan = plan_fills(&candidates, remaining, gas_price)?;
+            for fill in plan.fills {
+                candidates.retain(|sell| sell.order_hash != fill.order.order_hash);
+                let args = &BuyArgs {
+                    quantity: Some(fill.quantity),
+                    ..args.clone()
+                };
+                // checked for the quantity of the fill
+                match self
+                    .prepare_buy(args, fill.order, chain_id, spent, None)
+                    .await?
+                {
+                    Some(buy) => {
+                        remaining -= buy.quantity;
+                        buys.push(buy);
+                    }
+                    // plan the rest again without it
+                    None => break,
+                }
+            }
+        }
+        Ok(buys)
+    }
+
+    /// Builds the buy call of the sell order, `None` if it is skipped: disallowed by the
+    /// safe mode, vetoed or no longer fulfillable
+    /// Its fulfillability is preflighted on its own, unless already `verified`.
+    async fn prepare_buy(
+        &self,
+        args: &BuyArgs,
+        sell: Order,
+        chain_id: u64,
+        spent: &mut U256,
+        verified: Option<Result<(), ClientError>>,
+    ) -> Result<Option<PreparedBuy<M>>, ClientError> {
+        say!("[Token Id = {:?}] {}", args.token_id, sell);
+        if let Err(reason) = self.safe_mode.check_order(&sell, args.token) {
+            say!(
+                "[Token Id = {:?}] Skipping disallowed order {:?}: {}",
+                args.token_id,
+                sell.order_hash,
+                reason
             );
+            self.emit(Event::OrderSkipped {
+                order_hash: sell.order_hash,
+                reason: reason.to_string(),
+            })
+            .await?;
+            return Ok(None);
+        }
 
-            // make its corresponding buy
-            let buy = sell.match_sell(args.clone());
-            let sell = MinimalOrder::from(sell);
-            let call = self.atomic_match(buy, sell).await?;
-            calls.push(call);
+        let selected = self
+            .emit(Event::OrderSelected {
+                order_hash: sell.order_hash,
+                token: args.token,
+                token_id: args.token_id,
+                maker: sell.maker.address,
+                price: sell.current_price,
+            })
+            .await;
+        if let Err(ClientError::Vetoed(reason)) = selected {
+            say!(
+                "[Token Id = {:?}] Skipping vetoed order {:?}: {}",
+                args.token_id,
+                sell.order_hash,
+                reason
+            );
+            return Ok(None);
+        }
+        selected?;
+
+        validate_quantity(&sell, args.quantity)?;
+        let quantity = args.quantity.unwrap_or(sell.quantity);
+
+        // make its corresponding buy
+        let minimal = MinimalOrder::from(sell.clone());
+        let buy = self
+            .with_onchain_price(sell.match_sell(args.clone())?, &minimal)
+            .await?;
+        // the price may have risen since the listings were last seen
+        validate_price(args, buy.current_price, *spent)?;
+        if self.check_fulfillable {
+            let verified = match verified {
+                Some(verified) => verified,
+                None => self
+                    .preflight(&[(sell.clone(), buy.clone())], None)
+                    .await
+                    .pop()
+                    .unwrap_or(Ok(())),
+            };
+            let verified = match verified {
+                Ok(()) => self.verify_static_calls(&buy, &minimal).await,
+                err => err,
+            };
+            match verified {
+                Err(ClientError::Unfulfillable { reason, .. }) => {
+                    say!(
+                        "[Token Id = {:?}] Skipping stale order {:?}: {}",
+                        args.token_id,
+                        sell.order_hash,
+                        reason
+                    );
+                    self.emit(Event::OrderSkipped {
+                        order_hash: sell.order_hash,
+                        reason: reason.to_string(),
+                    })
+                    .await?;
+                    return Ok(None);
+                }
+                res => res?,
+            }
         }
+        *spent += buy.current_price;
+        let expected_price = buy.current_price;
+        // orders approved on-chain carry no signature
+        if !sell.approved_on_chain {
+            minimal.verify_signature(chain_id)?;
+        }
+        let call = self.atomic_match(buy, minimal).await?;
+        metrics::buy_attempted();
+        Ok(Some(PreparedBuy {
+            call,
+            order: sell,
+            token_id: args.token_id,
+            quantity,
+            expected_price,
+        }))
+    }
 
-        Ok(calls)
+    /// Replaces the asset schema of the order with the detected token standard of its
+    /// collection, if it is not one [`Order::match_sell`] supports (e.g. missing).
+    /// The listings of the shared storefront are ERC1155s of the quantity transferred
+    /// by their calldata.
+    async fn with_detected_schema(&self, mut sell: Order) -> Order {
+        let listed = MinimalOrder::from(sell.clone());
+        if let Some(metadata) = sell.asset_metadata_mut() {
+            if storefront::is_shared_storefront(metadata.asset.address) {
+                metadata.schema = "ERC1155".to_owned();
+                if listed.schema() == Some("ERC1155") {
+                    sell.quantity = listed.quantity();
+                }
+            } else if metadata.schema != "ERC721" && metadata.schema != "ERC1155" {
+                if let Some(standard) = self.token_standard(metadata.asset.address).await {
+                    metadata.schema = standard.schema().to_owned();
+                }
+            }
+        }
+        sell
     }
 
     pub async fn buy_one(&self, args: BuyArgs) -> Result<ContractCall<M, ()>, ClientError> {
-        let sell = get_n_cheapest_orders(&self.api, args.token, args.token_id, 1).await?[0].clone();
+        let sell = self.cheapest_listings(args.token, args.token_id, 1).await?[0].clone();
+        let sell = self.with_detected_schema(sell).await;
+        let args = self.with_listing_time(args).await?;
+        self.safe_mode
+            .check_order(&sell, args.token)
+            .map_err(|reason| ClientError::Disallowed {
+                order_hash: sell.order_hash,
+                reason,
+            })?;
+        self.emit(Event::OrderSelected {
+            order_hash: sell.order_hash,
+            token: args.token,
+            token_id: args.token_id,
+            maker: sell.maker.address,
+            price: sell.current_price,
+        })
+        .await?;
+        validate_quantity(&sell, args.quantity)?;
+
         // make its corresponding buy
-        let buy = sell.match_sell(args.clone());
-        let sell = MinimalOrder::from(sell);
+        let minimal = MinimalOrder::from(sell.clone());
+        let buy = self
+            .with_onchain_price(sell.match_sell(args.clone())?, &minimal)
+            .await?;
+        validate_price(&args, buy.current_price, U256::zero())?;
+        if self.check_fulfillable {
+            let quantity = args.quantity.unwrap_or(sell.quantity);
+            self.verify_fulfillable(&sell, quantity).await?;
+            self.verify_static_calls(&buy, &minimal).await?;
+        }
+        let approved_on_chain = sell.approved_on_chain;
+        let sell = minimal;
+        if !approved_on_chain {
+            sell.verify_signature(self.chain_id().await?)?;
+        }
+        metrics::buy_attempted();
         self.atomic_match(buy, sell).await
     }
 
+    /// Returns the buy call of a signed sell order which does not originate from the
+    /// API, e.g. built via [`MinimalOrder::sell`]. The order is validated and its
+    /// signature verified, but the maker's ownership and approvals are not checked.
+    pub async fn buy_external(
+        &self,
+        sell: MinimalOrder,
+        args: BuyArgs,
+    ) -> Result<ContractCall<M, ()>, ClientError> {
+        self.safe_mode
+            .check_collection(args.token)
+            .and_then(|_| self.safe_mode.check_maker(sell.maker))
+            .map_err(|reason| ClientError::Disallowed {
+                order_hash: sell.hash(),
+                reason,
+            })?;
+        let args = self.with_listing_time(args).await?;
+        let buy = sell.match_sell(args.clone())?;
+        validate_price(&args, buy.current_price, U256::zero())?;
+        sell.verify_signature(self.chain_id().await?)?;
+        metrics::buy_attempted();
+        self.atomic_match(buy, sell).await
+    }
+
+    async fn chain_id(&self) -> Result<u64, ClientError> {
+        let chain_id = self
+            .provider
+            .get_chainid()
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+        Ok(chain_id.as_u64())
+    }
+
     pub async fn atomic_match(
         &self,
         buy: MinimalOrder,
         sell: MinimalOrder,
     ) -> Result<ContractCall<M, ()>, ClientError> {
-        // make the arguments in the format the contracts expect them
-        let addrs = [
-            buy.exchange,
-            buy.maker,
-            buy.taker,
-            buy.fee_recipient,
-            buy.target,
-            buy.static_target,
-            buy.payment_token,
-            sell.exchange,
-            sell.maker,
-            sell.taker,
-            sell.fee_recipient,
-            sell.target,
-            sell.static_target,
-            sell.payment_token,
-        ];
-        let uints = [
-            buy.maker_relayer_fee,
-            buy.taker_relayer_fee,
-            buy.maker_protocol_fee,
-            buy.taker_protocol_fee,
-            buy.base_price,
-            buy.extra,
-            buy.listing_time,
-            buy.expiration_time,
-            buy.salt,
-            sell.maker_relayer_fee,
-            sell.taker_relayer_fee,
-            sell.maker_protocol_fee,
-            sell.taker_protocol_fee,
-            sell.base_price,
-            sell.extra,
-            sell.listing_time,
-            sell.expiration_time,
-            sell.salt,
-        ];
-
-        // passing it u8 returns an InvalidData error due to ethabi interpreting
-        // them wrongly, so we need to convert them to u256
-        // to work :shrug:
-        let methods = [
-            ethers::types::U256::from(buy.fee_method),
-            buy.side.into(),
-            buy.sale_kind.into(),
-            buy.how_to_call.into(),
-            sell.fee_method.into(),
-            sell.side.into(),
-            sell.sale_kind.into(),
-            sell.how_to_call.into(),
-        ];
-        let vs: [U256; 2] = [0.into(), sell.v.into()];
-
-        // TODO: This should be [H256; 5] in Abigen
-        let rss_metadata = [[0; 32], [0; 32], sell.r.0, sell.s.0, [0; 32]];
+        let (addrs, uints, methods) = match_args(&buy, &sell);
+        let (vs, rss_metadata) = signature_args(&buy, &sell);
 
         // get the call
         let call = self
@@ -191,126 +1365,1013 @@ impl<M: Middleware> Client<M> {
             )
             .unwrap();
 
-        // set the value
-        let call = call.value(buy.current_price);
+        // set the value, sent by the buyer. ERC20 payments are pulled via the
+        // token transfer proxy instead
+        let call = call.from(buy.maker);
+        let call = if buy.payment_token.is_zero() {
+            call.value(buy.current_price)
+        } else {
+            call
+        };
 
         // set the gas
-        // let gas = call.estimate_gas().await.expect("could not estimate gas");
-        // TODO: Why does gas estimation not work?
-        let call = call.gas(300_000);
+        let gas = self.gas.gas_limit(&sell, &call).await;
+        let call = call.gas(gas);
 
         Ok(call)
     }
+
+    /// Returns the price the exchange charges for the order at the latest block, via its
+    /// `calculateCurrentPrice_` view. Unlike the API's `current_price`, it is up to date
+    /// for Dutch auctions.
+    pub async fn current_price_onchain(&self, order: &MinimalOrder) -> Result<U256, ClientError> {
+        let (addrs, uints) = order_args(order);
+        // same as in `atomic_match`, the uint8s must be passed as u256
+        self.contracts
+            .method::<_, U256>(
+                "calculateCurrentPrice_",
+                (
+                    addrs,
+                    uints,
+                    U256::from(order.fee_method),
+                    U256::from(order.side),
+                    U256::from(order.sale_kind),
+                    U256::from(order.how_to_call),
+                    order.calldata.to_vec(),
+                    order.replacement_pattern.to_vec(),
+                    order.static_extradata.to_vec(),
+                ),
+            )
+            .expect("calculateCurrentPrice_ is in the ABI")
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))
+    }
+
+    /// Sets the price paid by the buy to the on-chain price of the `sell` if the API's
+    /// differs from it by more than `onchain_price_tolerance_bps`
+    async fn with_onchain_price(
+        &self,
+        mut buy: MinimalOrder,
+        sell: &MinimalOrder,
+    ) -> Result<MinimalOrder, ClientError> {
+        let tolerance_bps = match self.onchain_price_tolerance_bps {
+            Some(tolerance_bps) => tolerance_bps,
+            None => return Ok(buy),
+        };
+        let (quoted, onchain) = (sell.current_price, self.current_price_onchain(sell).await?);
+        let change = if onchain > quoted {
+            onchain - quoted
+        } else {
+            quoted - onchain
+        };
+        if quoted.is_zero() || change * 10_000 <= quoted * tolerance_bps {
+            return Ok(buy);
+        }
+        say!(
+            "[Order {:?}] The API quotes {:?} but the exchange charges {:?}, paying the latter",
+            sell.hash(),
+            quoted,
+            onchain
+        );
+        buy.current_price = onchain;
+        Ok(buy)
+    }
+
+    /// Approves the order on-chain instead of signing it, after which it can be matched
+    /// with an empty signature. The call must be sent by the order's maker, and
+    /// `orderbook_inclusion_desired` only asks the orderbooks to index the order.
+    pub fn approve_order(
+        &self,
+        order: MinimalOrder,
+        orderbook_inclusion_desired: bool,
+    ) -> ContractCall<M, ()> {
+        let (addrs, uints) = order_args(&order);
+
+        // same as in `atomic_match`, the uint8s must be passed as u256
+        self.contracts
+            .method(
+                "approveOrder_",
+                (
+                    addrs,
+                    uints,
+                    U256::from(order.fee_method),
+                    U256::from(order.side),
+                    U256::from(order.sale_kind),
+                    U256::from(order.how_to_call),
+                    order.calldata.to_vec(),
+                    order.replacement_pattern.to_vec(),
+                    order.static_extradata.to_vec(),
+                    orderbook_inclusion_desired,
+                ),
+            )
+            .unwrap()
+            .from(order.maker)
+    }
+
+    /// Cancels the order on-chain. The call must be sent by the order's maker.
+    pub fn cancel_order(&self, order: MinimalOrder) -> ContractCall<M, ()> {
+        let (addrs, uints) = order_args(&order);
+
+        // same as in `atomic_match`, the uint8s must be passed as u256
+        self.contracts
+            .method(
+                "cancelOrder_",
+                (
+                    addrs,
+                    uints,
+                    U256::from(order.fee_method),
+                    U256::from(order.side),
+                    U256::from(order.sale_kind),
+                    U256::from(order.how_to_call),
+                    order.calldata.to_vec(),
+                    order.replacement_pattern.to_vec(),
+                    order.static_extradata.to_vec(),
+                    U256::from(order.v),
+                    order.r,
+                    order.s,
+                ),
+            )
+            .unwrap()
+            .from(order.maker)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{convert::TryFrom, sync::Arc};
+    use std::sync::Arc;
 
-    use ethers::{prelude::BlockNumber, providers::Provider, types::Address, utils::parse_units};
+    use ethers::{providers::Provider, types::Address};
 
     use super::*;
-    use crate::api::OpenSeaApiConfig;
+    use crate::{api::OpenSeaApiConfig, types::FeeMethod};
+    use ethers::{
+        abi::Token,
+        providers::MockProvider,
+        types::{Block, Bytes, U64},
+    };
+
+    /// Pushes the responses of the calls of the preflight (see [`Client::preflight`]) of
+    /// an ERC1155 listing without Multicall3, in reverse order as the mock serves the
+    /// last pushed response first
+    fn push_fulfillable(mock: &MockProvider, balance: U256) {
+        let encode = |token| Bytes::from(ethers::abi::encode(&[token]));
+        let implementation = encode(Token::Address(Address::random()));
+        // the state of the proxy: isApprovedForAll, revoked, implementation
+        mock.push(encode(Token::Bool(true))).unwrap();
+        mock.push(encode(Token::Bool(false))).unwrap();
+        mock.push(implementation.clone()).unwrap();
+        // ordersCanMatch_, proxies, balanceOf, delegateProxyImplementation
+        mock.push(encode(Token::Bool(true))).unwrap();
+        mock.push(encode(Token::Address(Address::random())))
+            .unwrap();
+        mock.push(encode(Token::Uint(balance))).unwrap();
+        mock.push(implementation).unwrap();
+        // the code of Multicall3
+        mock.push(Bytes::default()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn gets_cheapest_orders_by_token() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let api = MockOrderProvider::new(vec![sell]);
+
+        let orders = get_n_cheapest_orders_by_token(&api, asset.address, &[asset.id, 0.into()], 5)
+            .await
+            .unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[&asset.id].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pages_the_orders_of_each_chunk() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        // more listings of the token than fit in a page
+        let orders: Vec<_> = (0..120)
+            .map(|_| {
+                let mut order = sell.clone();
+                order.order_hash = H256::random();
+                order
+            })
+            .collect();
+        let api = MockOrderProvider::new(orders);
 
-    ethers::contract::abigen!(
-        NFT,
-        r#"[
-        function ownerOf(uint256) view returns (address)
-        function balanceOf(address,uint256) view returns (uint256)
-    ]"#
-    );
+        let by_token = get_n_cheapest_orders_by_token(&api, asset.address, &[asset.id], 200)
+            .await
+            .unwrap();
+        assert_eq!(by_token[&asset.id].len(), 120);
+    }
+
+    #[tokio::test]
+    async fn deduplicates_orders() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let mut other = sell.clone();
+        other.order_hash = H256::random();
+        let orders = vec![sell.clone(), other.clone(), sell.clone()];
+        let unique = unique_orders(orders.clone());
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].order_hash, sell.order_hash);
+        assert_eq!(unique[1].order_hash, other.order_hash);
+
+        let api = MockOrderProvider::new(orders);
+        let cheapest = get_n_cheapest_orders(&api, asset.address, asset.id, 5)
+            .await
+            .unwrap();
+        assert_eq!(cheapest.len(), 2);
+
+        // the token is queried in two batches
+        let mut ids = vec![asset.id; MAX_TOKEN_IDS_PER_QUERY + 1];
+        ids[0] = 0.into();
+        let by_token = get_n_cheapest_orders_by_token(&api, asset.address, &ids, 5)
+            .await
+            .unwrap();
+        assert_eq!(by_token[&asset.id].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn filters_dust_listings_by_current_price() {
+        let mut sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        // a Dutch auction which declined below the default threshold
+        sell.base_price = U256::exp10(17);
+        sell.current_price = U256::exp10(15);
+        let api = MockOrderProvider::new(vec![sell]);
+
+        let orders = get_n_cheapest_orders(&api, asset.address, asset.id, 5)
+            .await
+            .unwrap();
+        assert!(orders.is_empty());
+
+        let filter = ListingFilter {
+            min_price: U256::zero(),
+            ..Default::default()
+        };
+        let orders = get_n_cheapest_orders_with(&api, asset.address, asset.id, 5, filter)
+            .await
+            .unwrap();
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn excludes_flagged_assets() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let flagged: CollectionAsset = serde_json::from_value(serde_json::json!({
+            "token_id": asset.id.to_string(),
+            "supports_wyvern": true,
+            "is_flagged": true,
+        }))
+        .unwrap();
+        let api = MockOrderProvider::new(vec![sell]).with_assets(vec![flagged]);
+
+        let filter = ListingFilter {
+            exclude_flagged: true,
+            ..Default::default()
+        };
+        let orders = get_n_cheapest_orders_with(&api, asset.address, asset.id, 1, filter)
+            .await
+            .unwrap();
+        assert!(orders.is_empty());
+        let orders =
+            get_n_cheapest_orders_by_token_with(&api, asset.address, &[asset.id], 1, filter)
+                .await
+                .unwrap();
+        assert!(orders.is_empty());
+
+        let orders = get_n_cheapest_orders(&api, asset.address, asset.id, 1)
+            .await
+            .unwrap();
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sorts_listings_by_effective_cost() {
+        let cheap = crate::testing::order();
+        let asset = cheap.asset_metadata().unwrap().asset.clone();
+        let price = U256::exp10(17);
+        let mut with_fees = cheap.clone();
+        with_fees.fee_method = FeeMethod::SplitFee;
+        with_fees.current_price = price;
+        with_fees.taker_relayer_fee = 250.into();
+        let mut without_fees = cheap;
+        without_fees.order_hash = H256::random();
+        without_fees.current_price = price + price / 100;
+        without_fees.taker_relayer_fee = U256::zero();
+
+        let api = MockOrderProvider::new(vec![with_fees.clone(), without_fees.clone()]);
+        let orders = get_n_cheapest_orders(&api, asset.address, asset.id, 2)
+            .await
+            .unwrap();
+        assert_eq!(orders[0].current_price, without_fees.current_price);
+        let ranked = orders[1].ranked_cost().unwrap();
+        assert_eq!(ranked.relayer_fee, price / 40);
+        assert!(ranked.gas.is_zero());
+
+        let gas_price = U256::exp10(9);
+        let cost = orders[1].effective_cost(gas_price);
+        assert_eq!(cost.relayer_fee, price / 40);
+        assert_eq!(cost.gas, gas_price * constants::ATOMIC_MATCH_GAS);
+        assert_eq!(cost.total(), price + price / 40 + cost.gas);
+    }
+
+    #[tokio::test]
+    async fn encodes_atomic_match_args_in_their_slots() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let buy = sell
+            .match_sell(BuyArgs {
+                token_id: asset.id,
+                taker,
+                token: asset.address,
+                recipient: taker,
+                timestamp: Some(0),
+                quantity: None,
+                max_price: None,
+                total_budget: None,
+            })
+            .unwrap();
+        let sell = MinimalOrder::from(sell);
+
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let call = client
+            .atomic_match(buy.clone(), sell.clone())
+            .await
+            .unwrap();
+        let calldata = call.calldata().unwrap();
+        let function = client.contracts.abi().function("atomicMatch_").unwrap();
+        assert_eq!(calldata.as_ref()[..4], function.short_signature());
+        let tokens = function.decode_input(&calldata.as_ref()[4..]).unwrap();
+
+        let fixed_array = |token: &Token| token.clone().into_fixed_array().unwrap();
+        let addrs = fixed_array(&tokens[0]);
+        assert_eq!(addrs[1], Token::Address(taker));
+        assert_eq!(addrs[7], Token::Address(sell.exchange));
+        assert_eq!(addrs[8], Token::Address(sell.maker));
+        let uints = fixed_array(&tokens[1]);
+        assert_eq!(uints[4], Token::Uint(buy.base_price));
+        assert_eq!(uints[13], Token::Uint(sell.base_price));
+        assert_eq!(uints[17], Token::Uint(sell.salt));
+        let methods = fixed_array(&tokens[2]);
+        assert_eq!(methods[1], Token::Uint(U256::from(OrderSide::Buy)));
+        assert_eq!(methods[5], Token::Uint(U256::from(OrderSide::Sell)));
+        assert_eq!(tokens[3], Token::Bytes(buy.calldata.to_vec()));
+        assert_eq!(tokens[4], Token::Bytes(sell.calldata.to_vec()));
+        assert_eq!(tokens[5], Token::Bytes(buy.replacement_pattern.to_vec()));
+        assert_eq!(tokens[6], Token::Bytes(sell.replacement_pattern.to_vec()));
+        assert_eq!(
+            fixed_array(&tokens[9]),
+            vec![Token::Uint(0.into()), Token::Uint(sell.v.into())]
+        );
+        let fixed_bytes = |hash: H256| Token::FixedBytes(hash.as_bytes().to_vec());
+        assert_eq!(
+            fixed_array(&tokens[10]),
+            vec![
+                fixed_bytes(H256::zero()),
+                fixed_bytes(H256::zero()),
+                fixed_bytes(sell.r),
+                fixed_bytes(sell.s),
+                fixed_bytes(H256::zero()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_the_chain_of_the_provider() {
+        let (provider, mock) = Provider::mocked();
+        let provider = Arc::new(provider);
+        mock.push(U64::from(10)).unwrap();
+        let cfg = OpenSeaApiConfig {
+            network: Network::Optimism,
+            ..Default::default()
+        };
+        assert!(Client::new(provider.clone(), cfg).await.is_ok());
+
+        mock.push(U64::from(10)).unwrap();
+        let err = Client::new(provider, OpenSeaApiConfig::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            ClientError::WrongChain {
+                expected: 1,
+                actual: 10,
+                ..
+            }
+        ));
+        assert_eq!("arbitrum".parse::<Network>().unwrap().chain_id(), 42161);
+        assert_eq!(
+            constants::seaport_address(10),
+            Some(*constants::SEAPORT_ADDRESS)
+        );
+    }
 
     #[tokio::test]
-    #[ignore]
-    async fn can_buy_an_nft() {
-        let provider = Provider::try_from("http://localhost:8545").unwrap();
+    async fn targets_the_deployment_of_the_network() {
+        let (provider, mock) = Provider::mocked();
         let provider = Arc::new(provider);
+        mock.push(U64::from(4)).unwrap();
+        let cfg = OpenSeaApiConfig {
+            network: Network::Rinkeby,
+            ..Default::default()
+        };
+        let client = Client::new(provider.clone(), cfg).await.unwrap();
+        assert_eq!(client.deployment, *constants::RINKEBY);
+        assert_eq!(
+            client.contracts.address(),
+            *constants::OPENSEA_ADDRESS_RINKEBY
+        );
+        assert_eq!(
+            client.proxy_registry().address(),
+            constants::RINKEBY.proxy_registry
+        );
+
+        // the L2s have no Wyvern exchange
+        mock.push(U64::from(10)).unwrap();
+        let cfg = OpenSeaApiConfig {
+            network: Network::Optimism,
+            ..Default::default()
+        };
+        let client = Client::new(provider, cfg).await.unwrap();
+        assert_eq!(Network::Optimism.deployment(), None);
+        assert_eq!(client.deployment, *constants::MAINNET);
+        assert_eq!(
+            constants::exchange_address(1),
+            Some(constants::MAINNET.exchange)
+        );
+    }
+
+    #[tokio::test]
+    async fn ranks_the_best_orders_of_both_sides() {
+        let ask = crate::testing::order();
+        let asset = ask.asset_metadata().unwrap().asset.clone();
+        let mut low_bid = ask.clone();
+        low_bid.order_hash = H256::random();
+        low_bid.side = OrderSide::Buy;
+        low_bid.current_price = U256::exp10(16);
+        low_bid.listing_time += 1;
+        let mut high_bid = low_bid.clone();
+        high_bid.order_hash = H256::random();
+        high_bid.current_price = U256::exp10(17);
+        high_bid.listing_time -= 1;
+        let mut stale_bid = high_bid.clone();
+        stale_bid.order_hash = H256::random();
+        stale_bid.current_price = U256::exp10(18);
+        stale_bid.cancelled = true;
+
+        let api = MockOrderProvider::new(vec![
+            ask.clone(),
+            low_bid.clone(),
+            high_bid.clone(),
+            stale_bid,
+        ]);
+        let (id, nft) = (asset.id, asset.address);
+        let bids = get_best_orders(&api, nft, id, OrderSide::Buy, 5, OrderOrdering::Best)
+            .await
+            .unwrap();
+        let prices = bids.iter().map(|bid| bid.current_price).collect::<Vec<_>>();
+        assert_eq!(prices, vec![high_bid.current_price, low_bid.current_price]);
+
+        let bids = get_best_orders(&api, nft, id, OrderSide::Buy, 1, OrderOrdering::Newest)
+            .await
+            .unwrap();
+        assert_eq!(bids[0].current_price, low_bid.current_price);
 
-        let accounts = provider.get_accounts().await.unwrap();
-        let taker = accounts[0].clone();
-        let id = 1126.into();
+        let asks = get_best_orders(&api, nft, id, OrderSide::Sell, 5, OrderOrdering::Best)
+            .await
+            .unwrap();
+        assert_eq!(asks.len(), 1);
+        assert_eq!(asks[0].side, OrderSide::Sell);
+    }
+
+    #[tokio::test]
+    async fn buys_external_orders() {
+        use ethers::signers::Signer;
 
-        let address = "0x91f7bb6900d65d004a659f34205beafc3b4e136c"
-            .parse::<Address>()
+        let maker = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let (nft, taker) = (Address::random(), Address::random());
+        let abi = ethers::contract::BaseContract::from(contracts::OPENSEA_ABI.clone());
+        let calldata = abi
+            .encode_with_selector(
+                ethers::utils::id("safeTransferFrom(address,address,uint256,uint256,bytes)"),
+                (
+                    maker.address(),
+                    Address::zero(),
+                    U256::from(7),
+                    U256::from(4),
+                    Vec::<u8>::new(),
+                ),
+            )
             .unwrap();
-        let nft = NFT::new(address, provider.clone());
+        let mut pattern = vec![0; calldata.as_ref().len()];
+        pattern[4 + 32..4 + 64].copy_from_slice(&[0xff; 32]);
+        let price = U256::exp10(18);
+        let sell = MinimalOrder::sell(
+            *constants::OPENSEA_ADDRESS,
+            maker.address(),
+            nft,
+            calldata,
+            pattern.into(),
+            price,
+            250,
+        );
+        assert_eq!(sell.schema(), Some("ERC1155"));
+        assert_eq!(sell.quantity(), 4.into());
+        let signature = maker.sign_message(sell.hash()).await.unwrap();
+        let sell = sell.with_signature(signature);
 
-        let block = provider
-            .get_block(BlockNumber::Latest)
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let args = BuyArgs {
+            token_id: 7.into(),
+            taker,
+            token: nft,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        // the listing is bought whole, at its price
+        let call = client
+            .buy_external(sell.clone(), args.clone())
             .await
+            .unwrap();
+        assert_eq!(call.tx.value(), Some(&price));
+
+        // the buy is listed at the time of the client's source, as for the API listings
+        mock.push(U64::from(1)).unwrap();
+        let call = client
+            .with_time_source(TimeSource::Manual(42))
+            .buy_external(
+                sell.clone(),
+                BuyArgs {
+                    timestamp: None,
+                    ..args.clone()
+                },
+            )
+            .await
+            .unwrap();
+        let calldata = call.calldata().unwrap();
+        let inputs = contracts::OPENSEA_ABI
+            .function("atomicMatch_")
             .unwrap()
+            .decode_input(&calldata.as_ref()[4..])
             .unwrap();
-        let timestamp = block.timestamp.as_u64();
+        let uints = inputs[1].clone().into_fixed_array().unwrap();
+        // the buy's listing time, after its fees, base price and extra
+        assert_eq!(uints[6].clone().into_uint(), Some(42.into()));
+
+        assert!(matches!(
+            sell.match_sell(BuyArgs {
+                quantity: Some(1.into()),
+                ..args.clone()
+            }),
+            Err(OrderMatchError::PartialFill { .. })
+        ));
 
-        // set up the args
+        let buy = sell.match_sell(args.clone()).unwrap();
+        assert!(matches!(
+            buy.match_sell(args),
+            Err(OrderMatchError::InvalidOrder { .. })
+        ));
+    }
+
+    #[test]
+    fn cancels_orders() {
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let sell = crate::testing::order();
+        let maker = sell.maker.address;
+
+        let call = client.cancel_order(sell.into());
+        assert_eq!(call.tx.from(), Some(&maker));
+        let selector = ethers::utils::id(
+            "cancelOrder_(address[7],uint256[9],uint8,uint8,uint8,uint8,bytes,bytes,bytes,uint8,bytes32,bytes32)",
+        );
+        assert_eq!(call.calldata().unwrap().as_ref()[..4], selector[..]);
+    }
+
+    #[test]
+    fn approves_orders() {
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let sell = crate::testing::order();
+        let maker = sell.maker.address;
+
+        let call = client.approve_order(sell.into(), true);
+        assert_eq!(call.tx.from(), Some(&maker));
+        let selector = ethers::utils::id(
+            "approveOrder_(address[7],uint256[9],uint8,uint8,uint8,uint8,bytes,bytes,bytes,bool)",
+        );
+        assert_eq!(call.calldata().unwrap().as_ref()[..4], selector[..]);
+    }
+
+    #[tokio::test]
+    async fn pays_the_onchain_price() {
+        let (provider, mock) = Provider::mocked();
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let price = sell.current_price;
+        let api = MockOrderProvider::new(vec![sell]);
+        let mut client = Client::with_api(Arc::new(provider), api);
+        client.check_fulfillable = false;
+        client.onchain_price_tolerance_bps = Some(100);
+        let taker = Address::random();
         let args = BuyArgs {
-            token_id: id,
+            token_id: asset.id,
             taker,
-            token: address,
+            token: asset.address,
             recipient: taker,
-            timestamp: Some(timestamp - 100),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
         };
 
-        // instantiate the client
-        let client = Client::new(provider.clone(), OpenSeaApiConfig::default());
+        // calculateCurrentPrice_, chainId, in reverse order
+        let encode = |price| Bytes::from(ethers::abi::encode(&[Token::Uint(price)]));
+        let declined = price * 9 / 10;
+        mock.push(encode(declined)).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let buys = client.buy(args.clone(), 1).await.unwrap();
+        assert_eq!(buys[0].call.tx.value(), Some(&declined));
+        assert_eq!(buys[0].expected_price, declined);
 
-        // execute the call
-        let call = client.buy(args, 1).await.unwrap()[0].clone();
-        let call = call.gas_price(parse_units(100, 9).unwrap());
-        let sent = call.send().await.unwrap();
+        // within the tolerance, the API's price is kept
+        mock.push(encode(price - price / 200)).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let buys = client.buy(args, 1).await.unwrap();
+        assert_eq!(buys[0].call.tx.value(), Some(&price));
+    }
+
+    #[tokio::test]
+    async fn replans_the_fills_of_skipped_orders() {
+        let (provider, mock) = Provider::mocked();
+        let listing = |price: u64| {
+            let mut sell = crate::testing::order();
+            sell.order_hash = H256::random();
+            sell.maker.address = Address::random();
+            sell.quantity = 2.into();
+            sell.current_price = U256::exp10(16) * price;
+            sell.base_price = sell.current_price;
+            sell.asset_metadata_mut().unwrap().schema = "ERC1155".to_owned();
+            sell
+        };
+        let (denied, fallback) = (listing(10), listing(15));
+        let api = MockOrderProvider::new(vec![denied.clone(), fallback.clone(), listing(20)]);
+        let mut client = Client::with_api(Arc::new(provider), api);
+        client.check_fulfillable = false;
+        client.safe_mode.denied_makers.insert(denied.maker.address);
+        let asset = denied.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: Some(2.into()),
+            max_price: None,
+            total_budget: None,
+        };
 
-        // wait for it to be confirmed
-        let _receipt = sent.await.unwrap();
-        // check the owner matches
-        let owner = nft.owner_of(id).call().await.unwrap();
-        assert_eq!(owner, taker);
+        // gasPrice, chainId, in reverse order
+        mock.push(U256::exp10(9)).unwrap();
+        mock.push(U64::from(1)).unwrap();
+        let buys = client.buy(args, 3).await.unwrap();
+        assert_eq!(buys.len(), 1);
+        assert_eq!(buys[0].order.order_hash, fallback.order_hash);
+        assert_eq!(buys[0].quantity, 2.into());
     }
 
     #[tokio::test]
-    #[ignore]
-    async fn can_buy_an_erc1155() {
-        let provider = Provider::try_from("http://localhost:8545").unwrap();
-        let provider = Arc::new(provider);
+    async fn detects_missing_schemas() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let mut sell = crate::testing::order();
+        sell.asset_metadata_mut().unwrap().schema = String::new();
 
-        let accounts = provider.get_accounts().await.unwrap();
+        // supportsInterface(ERC721), supportsInterface(ERC1155), in reverse order
+        let encode = |token| Bytes::from(ethers::abi::encode(&[token]));
+        mock.push(encode(Token::Bool(true))).unwrap();
+        mock.push(encode(Token::Bool(false))).unwrap();
+        let sell = client.with_detected_schema(sell).await;
+        assert_eq!(sell.asset_metadata().unwrap().schema, "ERC721");
+    }
 
-        let taker = accounts[0].clone();
+    #[tokio::test]
+    async fn buys_from_mock_provider() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        push_fulfillable(&mock, 100.into());
+        mock.push(U64::from(1)).unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let client = Client::with_api(Arc::new(provider), api);
 
-        let address = "0x47e22659d9ae152975e6cbfa2eed5dc8b75ac545"
-            .parse::<Address>()
-            .unwrap();
-        let nft = NFT::new(address, provider.clone());
-        let token_id = 1.into();
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
 
-        let block = provider
-            .get_block(BlockNumber::Latest)
-            .await
+        let buys = client.buy(args.clone(), 5).await.unwrap();
+        assert_eq!(buys.len(), 1);
+        assert_eq!(buys[0].call.tx.from(), Some(&taker));
+        assert_eq!(buys[0].call.tx.value(), Some(&sell.current_price));
+        assert_eq!(buys[0].order.order_hash, sell.order_hash);
+        assert_eq!(buys[0].token_id, asset.id);
+        assert_eq!(buys[0].expected_price, sell.current_price);
+
+        // no orders for other tokens
+        let args = BuyArgs {
+            token_id: 0.into(),
+            ..args
+        };
+        assert!(client.buy(args, 5).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn buys_and_sends_with_signer() {
+        let (provider, mock) = Provider::mocked();
+        let tx_hash = H256::random();
+        // sendRawTransaction, feeHistory (requested twice by ethers, with either
+        // encoding of the block count), latest block, unsupported createAccessList,
+        // nonce
+        mock.push(tx_hash).unwrap();
+        let fee_history = serde_json::json!({
+            "baseFeePerGas": [],
+            "gasUsedRatio": [],
+            "oldestBlock": "0x1",
+            "reward": [["0x1"]],
+        });
+        mock.push(fee_history.clone()).unwrap();
+        mock.push(fee_history).unwrap();
+        mock.push(Block::<H256> {
+            base_fee_per_gas: Some(1.into()),
+            ..Default::default()
+        })
+        .unwrap();
+        mock.push(U256::zero()).unwrap();
+        mock.push(U256::zero()).unwrap();
+        push_fulfillable(&mock, 100.into());
+        mock.push(U64::from(1)).unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let client = Client::with_api(Arc::new(provider), api);
+
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker: Address::zero(),
+            token: asset.address,
+            recipient: Address::random(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        assert!(matches!(
+            client.buy_and_send(args.clone(), 1).await,
+            Err(ClientError::NoSigner)
+        ));
+
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let client = client.with_signer(wallet);
+        assert_eq!(client.buy_and_send(args, 1).await.unwrap(), vec![tx_hash]);
+    }
+
+    #[tokio::test]
+    async fn buys_by_traits() {
+        let (provider, mock) = Provider::mocked();
+        push_fulfillable(&mock, 100.into());
+        mock.push(U64::from(1)).unwrap();
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let assets = serde_json::from_value(serde_json::json!([
+            { "token_id": "0", "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": asset.id.to_string(), "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": "1", "traits": [{ "trait_type": "Fur", "value": "Brown" }] },
+        ]))
+        .unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON)
             .unwrap()
-            .unwrap();
-        let timestamp = block.timestamp.as_u64();
+            .with_assets(assets);
+        let client = Client::with_api(Arc::new(provider), api);
 
-        // set up the args
+        let taker = Address::random();
         let args = BuyArgs {
-            token_id,
+            token_id: 0.into(),
             taker,
-            token: address,
+            token: asset.address,
             recipient: taker,
-            timestamp: Some(timestamp - 100),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
         };
+        let filters = ["Fur=Gold".parse().unwrap()];
+        assert_eq!(
+            client
+                .token_ids_by_traits(asset.address, &filters)
+                .await
+                .unwrap(),
+            vec![0.into(), asset.id]
+        );
 
-        // instantiate the client
-        let client = Client::new(provider.clone(), OpenSeaApiConfig::default());
+        // only the matching token with a listing is bought
+        let buys = client
+            .buy_by_traits(args.clone(), &filters, 5)
+            .await
+            .unwrap();
+        assert_eq!(buys.len(), 1);
+        assert_eq!(buys[0].call.tx.value(), Some(&sell.current_price));
 
-        // execute the call
-        let call = client.buy(args, 1).await.unwrap()[0].clone();
-        let call = call.gas_price(parse_units(100, 9).unwrap());
-        let sent = call.send().await.unwrap();
+        // listings above the max price are skipped
+        let args = BuyArgs {
+            max_price: Some(sell.current_price - 1),
+            ..args
+        };
+        assert!(client
+            .buy_by_traits(args, &filters, 5)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn skips_unfulfillable_orders() {
+        let (provider, mock) = Provider::mocked();
+        // the maker sold their tokens elsewhere
+        push_fulfillable(&mock, 0.into());
+        mock.push(U64::from(1)).unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let (events, mut rx) = EventFeed::channel();
+        let client = Client::with_api(Arc::new(provider), api).with_events(events);
+
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker: Address::random(),
+            token: asset.address,
+            recipient: Address::random(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        assert!(client.buy(args, 1).await.unwrap().is_empty());
 
-        // wait for it to be confirmed
-        let receipt = sent.await.unwrap();
-        dbg!(receipt);
-        // check the owner matches
-        let num = nft.balance_of(taker, token_id).call().await.unwrap();
-        assert_eq!(num, 1.into());
+        assert!(matches!(
+            rx.recv().await.unwrap().event,
+            Event::OrderSelected { .. }
+        ));
+        assert!(matches!(
+            rx.recv().await.unwrap().event,
+            Event::OrderSkipped { order_hash, .. } if order_hash == sell.order_hash
+        ));
+    }
+
+    #[tokio::test]
+    async fn skips_disallowed_orders() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let (events, mut rx) = EventFeed::channel();
+        let mut client = Client::with_api(Arc::new(provider), api).with_events(events);
+
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        client.safe_mode.denied_makers.insert(sell.maker.address);
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker: Address::random(),
+            token: asset.address,
+            recipient: Address::random(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        assert!(client.buy(args.clone(), 1).await.unwrap().is_empty());
+        assert!(matches!(
+            rx.recv().await.unwrap().event,
+            Event::OrderSkipped { order_hash, .. } if order_hash == sell.order_hash
+        ));
+
+        // the single buy fails instead
+        client.safe_mode = SafeMode {
+            allowed_collections: Some(HashSet::new()),
+            ..SafeMode::default()
+        };
+        assert!(matches!(
+            client.buy_one(args).await,
+            Err(ClientError::Disallowed {
+                reason: SafeModeViolation::CollectionNotAllowed(token),
+                ..
+            }) if token == asset.address
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_revoked_proxies() {
+        let (provider, mock) = Provider::mocked();
+        let encode = |token| Bytes::from(ethers::abi::encode(&[token]));
+        let implementation = encode(Token::Address(Address::random()));
+        // revoked, delegateProxyImplementation, implementation, proxies, balanceOf
+        mock.push(encode(Token::Bool(true))).unwrap();
+        mock.push(implementation.clone()).unwrap();
+        mock.push(implementation).unwrap();
+        mock.push(encode(Token::Address(Address::random())))
+            .unwrap();
+        mock.push(encode(Token::Uint(100.into()))).unwrap();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+
+        let sell = crate::testing::order();
+        let err = client
+            .verify_fulfillable(&sell, sell.quantity)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Unfulfillable {
+                reason: UnfulfillableReason::ProxyRevoked(maker),
+                ..
+            } if maker == sell.maker.address
+        ));
+    }
+
+    #[tokio::test]
+    async fn records_failed_ownership_checks() {
+        // the ownership call errors
+        let (provider, _mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+
+        let sell = crate::testing::order();
+        let err = client
+            .verify_fulfillable(&sell, sell.quantity)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Unfulfillable {
+                order_hash,
+                reason: UnfulfillableReason::CheckFailed(_),
+            } if order_hash == sell.order_hash
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_listings_above_limits() {
+        let (provider, mock) = Provider::mocked();
+        for _ in 0..2 {
+            mock.push(U256::from(1)).unwrap();
+        }
+        let api = MockOrderProvider::from_json(crate::testing::ORDER_JSON).unwrap();
+        let mut client = Client::with_api(Arc::new(provider), api);
+        // the limits are checked without any call
+        client.check_fulfillable = false;
+
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker: Address::random(),
+            token: asset.address,
+            recipient: Address::random(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: Some(sell.current_price - 1),
+            total_budget: None,
+        };
+        assert!(matches!(
+            client.buy(args.clone(), 1).await,
+            Err(ClientError::PriceAboveLimit { price, .. }) if price == sell.current_price
+        ));
+
+        let args = BuyArgs {
+            max_price: None,
+            total_budget: Some(sell.current_price / 2),
+            ..args
+        };
+        assert!(matches!(
+            client.buy(args, 1).await,
+            Err(ClientError::BudgetExceeded { .. })
+        ));
     }
 }
@@ -0,0 +1,190 @@
+//! Fixed price listings signed with the seller's wallet and submitted to the orderbook.
+//! In [`ListingMode::Offline`] the chain is never touched, e.g. to pre-sign the listings
+//! of items which are still being minted.
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, Bytes, U256},
+    utils::id,
+};
+
+use crate::{
+    contracts, time_source,
+    types::{MinimalOrder, OrderMatchError},
+    Client, ClientError, OrderProvider, StaticCall, TokenStandard, UnfulfillableReason,
+};
+
+/// A fixed price ETH listing of `quantity` tokens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListingArgs {
+    pub token: Address,
+    pub token_id: U256,
+    pub standard: TokenStandard,
+    /// Always 1 for ERC721s
+    pub quantity: U256,
+    /// The price of all the tokens, in Wei
+    pub price: U256,
+    /// The fee of the seller to OpenSea, in basis points of the price, see
+    /// [`MinimalOrder::sell`]
+    pub fee_bps: u64,
+    /// The unix timestamp the listing expires at, or 0 if it never does
+    pub expiration_time: u64,
+    /// The unix timestamp the listing starts at, now if `None`
+    pub timestamp: Option<u64>,
+    /// The predicate which must hold for the listing to be filled, see [`StaticCall`]
+    pub static_call: Option<StaticCall>,
+}
+
+/// Whether [`Client::create_listing`] checks that the listing can be filled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListingMode {
+    /// Errors if the seller has no Wyvern proxy or has not approved it for the
+    /// collection, see [`Client::register_proxy`] and [`Client::ensure_nft_approval`]
+    Checked,
+    /// Never touches the chain, only warning that the proxy and approval are not
+    /// checked. The listing cannot be filled until they are in place.
+    Offline,
+}
+
+impl ListingArgs {
+    /// The unsigned sell order of `maker` on the `exchange`. The calldata transfers the
+    /// token(s) from the maker to the zero address, which the buyer replaces.
+    pub fn sell_order(
+        &self,
+        exchange: Address,
+        maker: Address,
+    ) -> Result<MinimalOrder, OrderMatchError> {
+        let abi = ethers::contract::BaseContract::from(contracts::OPENSEA_ABI.clone());
+        let calldata: Bytes = match self.standard {
+            TokenStandard::Erc721 => abi.encode_with_selector(
+                id("transferFrom(address,address,uint256)"),
+                (maker, Address::zero(), self.token_id),
+            )?,
+            TokenStandard::Erc1155 => abi.encode_with_selector(
+                id("safeTransferFrom(address,address,uint256,uint256,bytes)"),
+                (
+                    maker,
+                    Address::zero(),
+                    self.token_id,
+                    self.quantity,
+                    Vec::<u8>::new(),
+                ),
+            )?,
+        };
+        // `to` is the 2nd word of both `transferFrom` and `safeTransferFrom`
+        let mut replacement_pattern = vec![0; calldata.as_ref().len()];
+        replacement_pattern[4 + 32..4 + 2 * 32].copy_from_slice(&[0xff; 32]);
+
+        let listing_time = self
+            .timestamp
+            .unwrap_or_else(time_source::local_listing_time);
+        Ok(MinimalOrder {
+            listing_time: listing_time.into(),
+            expiration_time: self.expiration_time.into(),
+            ..MinimalOrder::sell(
+                exchange,
+                maker,
+                self.token,
+                calldata,
+                replacement_pattern.into(),
+                self.price,
+                self.fee_bps,
+            )
+        }
+        .with_static_call(self.static_call.clone()))
+    }
+}
+
+/// Signs the listing on the `exchange` with the `signer` as seller, without touching
+/// the chain nor the orderbook
+pub async fn sign_listing<S: Signer>(
+    exchange: Address,
+    args: &ListingArgs,
+    signer: &S,
+) -> Result<MinimalOrder, ClientError> {
+    let sell = args.sell_order(exchange, signer.address())?;
+    let signature = signer
+        .sign_message(sell.hash().as_bytes())
+        .await
+        .map_err(|err| ClientError::Signer(err.to_string()))?;
+    Ok(sell.with_signature(signature))
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Signs the listing with the `signer` as seller and submits it to the orderbook.
+    /// Returns the submitted listing.
+    pub async fn create_listing<S: Signer>(
+        &self,
+        args: &ListingArgs,
+        signer: &S,
+        mode: ListingMode,
+    ) -> Result<MinimalOrder, ClientError> {
+        let maker = signer.address();
+        let sell = sign_listing(self.contracts.address(), args, signer).await?;
+        match mode {
+            ListingMode::Checked => {
+                if self.ensure_nft_approval(args.token, maker).await?.is_some() {
+                    return Err(ClientError::Unfulfillable {
+                        order_hash: sell.hash(),
+                        reason: UnfulfillableReason::NotApproved(maker),
+                    });
+                }
+            }
+            ListingMode::Offline => say!(
+                "[Token Id = {:?}] Warning: the Wyvern proxy of {:?} and its approval are not checked, the listing cannot be filled without them",
+                args.token_id, maker
+            ),
+        }
+        self.api.post_order(sell.clone()).await?;
+        Ok(sell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants, MockOrderProvider};
+    use ethers::{providers::Provider, signers::LocalWallet};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn lists_offline() {
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let args = ListingArgs {
+            token: Address::random(),
+            token_id: 7.into(),
+            standard: TokenStandard::Erc1155,
+            quantity: 3.into(),
+            price: U256::exp10(18),
+            fee_bps: 750,
+            expiration_time: 2_000_000_000,
+            timestamp: Some(1_000_000_000),
+            static_call: None,
+        };
+
+        // the mocked provider has no responses, so any call to the chain would fail
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let sell = client
+            .create_listing(&args, &wallet, ListingMode::Offline)
+            .await
+            .unwrap();
+        assert!(client
+            .create_listing(&args, &wallet, ListingMode::Checked)
+            .await
+            .is_err());
+        let posted = client.api.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].hash(), sell.hash());
+
+        sell.validate().unwrap();
+        sell.verify_signature(1).unwrap();
+        assert_eq!(sell.exchange, *constants::OPENSEA_ADDRESS);
+        assert_eq!(sell.maker, wallet.address());
+        assert_eq!(sell.schema(), Some("ERC1155"));
+        assert_eq!(sell.token_id(), Some(7.into()));
+        assert_eq!(sell.quantity(), 3.into());
+        assert_eq!(sell.expiration_time, 2_000_000_000.into());
+        assert_eq!(sell.maker_relayer_fee, 750.into());
+    }
+}
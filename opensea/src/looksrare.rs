@@ -0,0 +1,246 @@
+//! LooksRare listings, queried from its public API and filled through its exchange
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Address, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    constants,
+    contracts::{LooksRareExchange, MakerOrder, TakerOrder},
+    types::u256_from_dec_str,
+    validate_price, BuyArgs, ClientError,
+};
+
+const API_URL: &str = "https://api.looksrare.org/api/v1";
+
+/// The most listings returned by the API per request
+const MAX_LISTINGS: usize = 150;
+
+/// The gas limit of a `matchAskWithTakerBidUsingETHAndWETH` call
+const MATCH_GAS: u64 = 250_000;
+
+/// A maker order as returned by the LooksRare API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LooksRareOrder {
+    pub hash: H256,
+    pub collection_address: Address,
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub token_id: U256,
+    pub is_order_ask: bool,
+    pub signer: Address,
+    /// The execution strategy, e.g. the fixed price one
+    pub strategy: Address,
+    /// WETH for the asks
+    pub currency_address: Address,
+    pub amount: u64,
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub price: U256,
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub nonce: U256,
+    pub start_time: u64,
+    pub end_time: u64,
+    /// In basis points of the price
+    pub min_percentage_to_ask: u64,
+    /// Hex encoded, possibly empty
+    #[serde(default)]
+    pub params: String,
+    pub v: u8,
+    pub r: H256,
+    pub s: H256,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct OrdersResponse {
+    success: bool,
+    message: Option<String>,
+    data: Option<Vec<LooksRareOrder>>,
+}
+
+/// Client of the LooksRare API and exchange
+#[derive(Clone)]
+pub struct LooksRare<M> {
+    client: reqwest::Client,
+    pub exchange: LooksRareExchange<M>,
+}
+
+impl<M: Middleware> LooksRare<M> {
+    pub fn new(provider: Arc<M>) -> Self {
+        Self::with_client(reqwest::Client::new(), provider)
+    }
+
+    /// Instantiates the client with an already configured HTTP client
+    pub fn with_client(client: reqwest::Client, provider: Arc<M>) -> Self {
+        Self {
+            client,
+            exchange: LooksRareExchange::new(*constants::LOOKSRARE_EXCHANGE_ADDRESS, provider),
+        }
+    }
+
+    /// Returns the `n` cheapest valid asks of the token
+    pub async fn get_asks(
+        &self,
+        nft: Address,
+        token_id: U256,
+        n: usize,
+    ) -> Result<Vec<LooksRareOrder>, ClientError> {
+        let query = [
+            ("isOrderAsk", "true".to_owned()),
+            ("collection", format!("{:?}", nft)),
+            ("tokenId", token_id.to_string()),
+            ("status[]", "VALID".to_owned()),
+            ("sort", "PRICE_ASC".to_owned()),
+            ("pagination[first]", n.min(MAX_LISTINGS).to_string()),
+        ];
+        let res = self
+            .client
+            .get(format!("{}/orders", API_URL))
+            .query(&query)
+            .send()
+            .await
+            .map_err(looksrare_error)?;
+        let text = res.text().await.map_err(looksrare_error)?;
+        parse_orders(&text)
+    }
+
+    /// Returns the call buying the ask for `args.taker` with ETH. The exchange sends the
+    /// token to the taker, which must be the sender, so a different `args.recipient` is
+    /// refused.
+    pub fn match_ask(
+        &self,
+        ask: &LooksRareOrder,
+        args: &BuyArgs,
+    ) -> Result<ContractCall<M, ()>, ClientError> {
+        if !ask.is_order_ask {
+            return Err(looksrare_error("the order is not an ask"));
+        }
+        if args.recipient != args.taker {
+            return Err(looksrare_error(format!(
+                "the token can only be sent to the taker {:?}, not to {:?}",
+                args.taker, args.recipient
+            )));
+        }
+        validate_price(args, ask.price, U256::zero())?;
+        let params = hex::decode(ask.params.trim_start_matches("0x")).map_err(looksrare_error)?;
+
+        let taker_bid = TakerOrder {
+            is_order_ask: false,
+            taker: args.taker,
+            price: ask.price,
+            token_id: ask.token_id,
+            min_percentage_to_ask: ask.min_percentage_to_ask.into(),
+            params: Vec::new(),
+        };
+        let maker_ask = MakerOrder {
+            is_order_ask: true,
+            signer: ask.signer,
+            collection: ask.collection_address,
+            price: ask.price,
+            token_id: ask.token_id,
+            amount: ask.amount.into(),
+            strategy: ask.strategy,
+            currency: ask.currency_address,
+            nonce: ask.nonce,
+            start_time: ask.start_time.into(),
+            end_time: ask.end_time.into(),
+            min_percentage_to_ask: ask.min_percentage_to_ask.into(),
+            params,
+            v: ask.v,
+            r: ask.r.0,
+            s: ask.s.0,
+        };
+        Ok(self
+            .exchange
+            .match_ask_with_taker_bid_using_eth_and_weth(taker_bid, maker_ask)
+            .from(args.taker)
+            .value(ask.price)
+            .gas(MATCH_GAS))
+    }
+}
+
+fn parse_orders(text: &str) -> Result<Vec<LooksRareOrder>, ClientError> {
+    let res: OrdersResponse = serde_json::from_str(text).map_err(looksrare_error)?;
+    match res.data {
+        Some(orders) if res.success => Ok(orders),
+        _ => Err(looksrare_error(
+            res.message.unwrap_or_else(|| "unknown error".to_owned()),
+        )),
+    }
+}
+
+fn looksrare_error(err: impl ToString) -> ClientError {
+    ClientError::Marketplace {
+        marketplace: "looksrare",
+        message: err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+
+    #[test]
+    fn matches_asks() {
+        let text = r#"{
+            "success": true,
+            "message": null,
+            "data": [{
+                "hash": "0x3e2ad4d0a5b0aab7ae3bb7ba3fd2c7d9b56ad6d79be8e8d0ea69e8a9a0c2d7fb",
+                "collectionAddress": "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d",
+                "tokenId": "7",
+                "isOrderAsk": true,
+                "signer": "0x3ee4c73b42c89d5e99d19400fd14950f4e87c20b",
+                "strategy": "0x56244bb70cbd3ea9dc8007399f61dfc065190031",
+                "currencyAddress": "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+                "amount": 1,
+                "price": "90000000000000000000",
+                "nonce": "12",
+                "startTime": 1660989600,
+                "endTime": 1663668000,
+                "minPercentageToAsk": 8500,
+                "params": "",
+                "status": "VALID",
+                "signature": "0x",
+                "v": 27,
+                "r": "0x5c369f5a19a33838b80af6f823eb224c192c937a7e9227929a8ad07963b2a279",
+                "s": "0x5c369f5a19a33838b80af6f823eb224c192c937a7e9227929a8ad07963b2a279"
+            }]
+        }"#;
+        let asks = parse_orders(text).unwrap();
+        assert_eq!(asks[0].price, U256::exp10(18) * 90);
+
+        let (provider, _) = Provider::mocked();
+        let looksrare = LooksRare::new(Arc::new(provider));
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: 7.into(),
+            taker,
+            token: asks[0].collection_address,
+            recipient: taker,
+            timestamp: None,
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let call = looksrare.match_ask(&asks[0], &args).unwrap();
+        assert_eq!(call.tx.value(), Some(&asks[0].price));
+        assert_eq!(call.tx.from(), Some(&taker));
+        let args = BuyArgs {
+            recipient: Address::random(),
+            ..args
+        };
+        assert!(matches!(
+            looksrare.match_ask(&asks[0], &args),
+            Err(ClientError::Marketplace { .. })
+        ));
+
+        assert!(matches!(
+            parse_orders(r#"{"success": false, "message": "Too many requests", "data": null}"#),
+            Err(ClientError::Marketplace { .. })
+        ));
+    }
+}
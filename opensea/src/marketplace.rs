@@ -0,0 +1,246 @@
+//! Listings across marketplaces, to buy the cheapest listing of a token wherever it is
+//! listed. Implemented by [`Client`] for OpenSea and by [`LooksRare`].
+use async_trait::async_trait;
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{Address, U256},
+};
+
+use crate::{
+    looksrare::{LooksRare, LooksRareOrder},
+    types::Order,
+    BuyArgs, Client, ClientError, OrderProvider,
+};
+
+/// The order behind a [`Listing`], as returned by its marketplace
+#[derive(Clone, Debug)]
+pub enum MarketOrder {
+    OpenSea(Box<Order>),
+    LooksRare(Box<LooksRareOrder>),
+}
+
+/// A listing of a token on a marketplace
+#[derive(Clone, Debug)]
+pub struct Listing {
+    /// The name of the marketplace, see [`Marketplace::name`]
+    pub marketplace: &'static str,
+    pub nft: Address,
+    pub token_id: U256,
+    pub maker: Address,
+    /// What the taker pays, including the taker fees, in the payment token, see
+    /// [`Listing::normalized_price`] to compare them
+    pub price: U256,
+    /// The zero address is ETH
+    pub payment_token: Address,
+    pub order: MarketOrder,
+}
+
+/// A marketplace whose listings can be bought on-chain
+#[async_trait]
+pub trait Marketplace<M: Middleware>: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns up to `n` of the cheapest listings of the token
+    async fn get_listings(
+        &self,
+        nft: Address,
+        token_id: U256,
+        n: usize,
+    ) -> Result<Vec<Listing>, ClientError>;
+
+    /// Returns the call buying the listing, which must be one of this marketplace's
+    async fn build_fulfillment_call(
+        &self,
+        listing: &Listing,
+        args: &BuyArgs,
+    ) -> Result<ContractCall<M, ()>, ClientError>;
+}
+
+fn wrong_marketplace(marketplace: &'static str) -> ClientError {
+    ClientError::Marketplace {
+        marketplace,
+        message: "the listing is from another marketplace".to_owned(),
+    }
+}
+
+#[async_trait]
+impl<M: Middleware, P: OrderProvider> Marketplace<M> for Client<M, P> {
+    fn name(&self) -> &'static str {
+        "opensea"
+    }
+
+    async fn get_listings(
+        &self,
+        nft: Address,
+        token_id: U256,
+        n: usize,
+    ) -> Result<Vec<Listing>, ClientError> {
+        let orders = self.cheapest_listings(nft, token_id, n).await?;
+        Ok(orders
+            .into_iter()
+            .map(|order| Listing {
+                marketplace: "opensea",
+                nft,
+                token_id,
+                maker: order.maker.address,
+                price: order.effective_cost(U256::zero()).total(),
+                payment_token: order.payment_token,
+                order: MarketOrder::OpenSea(Box::new(order)),
+            })
+            .collect())
+    }
+
+    async fn build_fulfillment_call(
+        &self,
+        listing: &Listing,
+        args: &BuyArgs,
+    ) -> Result<ContractCall<M, ()>, ClientError> {
+        let order = match listing.order {
+            MarketOrder::OpenSea(ref order) => order.as_ref().clone(),
+            _ => return Err(wrong_marketplace("opensea")),
+        };
+        let order_hash = order.order_hash;
+        let chain_id = self.chain_id().await?;
+        let mut spent = U256::zero();
+        self.buy_orders(args, vec![order], chain_id, &mut spent)
+            .await?
+            .pop()
+            .map(|buy| buy.call)
+            .ok_or(ClientError::ListingUnavailable(order_hash))
+    }
+}
+
+#[async_trait]
+impl<M: Middleware> Marketplace<M> for LooksRare<M> {
+    fn name(&self) -> &'static str {
+        "looksrare"
+    }
+
+    async fn get_listings(
+        &self,
+        nft: Address,
+        token_id: U256,
+        n: usize,
+    ) -> Result<Vec<Listing>, ClientError> {
+        let asks = self.get_asks(nft, token_id, n).await?;
+        Ok(asks
+            .into_iter()
+            .map(|ask| Listing {
+                marketplace: "looksrare",
+                nft,
+                token_id,
+                maker: ask.signer,
+                price: ask.price,
+                // paid with ETH
+                payment_token: Address::zero(),
+                order: MarketOrder::LooksRare(Box::new(ask)),
+            })
+            .collect())
+    }
+
+    async fn build_fulfillment_call(
+        &self,
+        listing: &Listing,
+        args: &BuyArgs,
+    ) -> Result<ContractCall<M, ()>, ClientError> {
+        match listing.order {
+            MarketOrder::LooksRare(ref ask) => self.match_ask(ask, args),
+            _ => Err(wrong_marketplace("looksrare")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_n_cheapest_orders_across, MockOrderProvider, PaymentTokens};
+    use ethers::{providers::Provider, types::U64};
+    use std::sync::Arc;
+
+    /// Serves the listings of another venue
+    struct StubMarketplace(Vec<Listing>);
+
+    #[async_trait]
+    impl<M: Middleware> Marketplace<M> for StubMarketplace {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn get_listings(
+            &self,
+            _: Address,
+            _: U256,
+            _: usize,
+        ) -> Result<Vec<Listing>, ClientError> {
+            Ok(self.0.clone())
+        }
+
+        async fn build_fulfillment_call(
+            &self,
+            _: &Listing,
+            _: &BuyArgs,
+        ) -> Result<ContractCall<M, ()>, ClientError> {
+            Err(wrong_marketplace("stub"))
+        }
+    }
+
+    #[tokio::test]
+    async fn aggregates_the_cheapest_listings() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let mut client = Client::with_api(
+            Arc::new(provider),
+            MockOrderProvider::new(vec![sell.clone()]),
+        );
+        client.check_fulfillable = false;
+
+        let mut cheaper = client
+            .get_listings(asset.address, asset.id, 1)
+            .await
+            .unwrap()[0]
+            .clone();
+        cheaper.marketplace = "stub";
+        cheaper.price = sell.current_price - 1;
+        // fewer units, but of a token with fewer decimals
+        let mut usdc = cheaper.clone();
+        usdc.payment_token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        usdc.price = U256::exp10(8);
+        // in a token whose decimals are unknown
+        let mut unknown = cheaper.clone();
+        unknown.payment_token = Address::random();
+        unknown.price = U256::one();
+        let stub = StubMarketplace(vec![usdc, unknown, cheaper]);
+
+        let tokens = PaymentTokens::default();
+        let listings =
+            get_n_cheapest_orders_across(&[&client, &stub], asset.address, asset.id, 3, &tokens)
+                .await;
+        assert_eq!(listings.len(), 3);
+        assert_eq!(listings[0].marketplace, "stub");
+        assert_eq!(listings[0].price, sell.current_price - 1);
+        assert_eq!(listings[1].marketplace, "opensea");
+        assert!(listings[2].payment_token != Address::zero());
+
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let call = client
+            .build_fulfillment_call(&listings[1], &args)
+            .await
+            .unwrap();
+        assert_eq!(call.tx.value(), Some(&sell.current_price));
+    }
+}
@@ -0,0 +1,174 @@
+//! Prometheus metrics of the API client and of the buy / snipe paths, recorded when the
+//! `metrics` feature is enabled and exposed via [`registry`] for long-lived services.
+//! Without the feature recording them is a no-op.
+use ethers::types::U256;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+mod recorder {
+    use once_cell::sync::Lazy;
+    use prometheus::{
+        core::Collector, Counter, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts,
+        Registry,
+    };
+
+    pub(super) struct Metrics {
+        pub registry: Registry,
+        pub api_requests: IntCounterVec,
+        pub api_latency: HistogramVec,
+        pub api_rate_limited: IntCounter,
+        pub orders_fetched: IntCounter,
+        pub buys_attempted: IntCounter,
+        pub buys_succeeded: IntCounter,
+        pub bundle_simulations: IntCounterVec,
+        pub gas_paid: Counter,
+    }
+
+    fn register<C: Collector + Clone + 'static>(registry: &Registry, collector: C) -> C {
+        registry
+            .register(Box::new(collector.clone()))
+            .expect("metrics are registered once");
+        collector
+    }
+
+    pub(super) static METRICS: Lazy<Metrics> = Lazy::new(|| {
+        let registry =
+            Registry::new_custom(Some("opensea".to_owned()), None).expect("valid prefix");
+        Metrics {
+            api_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "api_requests_total",
+                        "OpenSea API requests, by endpoint and HTTP status",
+                    ),
+                    &["endpoint", "status"],
+                )
+                .unwrap(),
+            ),
+            api_latency: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "api_request_duration_seconds",
+                        "Latency of the OpenSea API requests, by endpoint",
+                    ),
+                    &["endpoint"],
+                )
+                .unwrap(),
+            ),
+            api_rate_limited: register(
+                &registry,
+                IntCounter::new(
+                    "api_rate_limited_total",
+                    "OpenSea API requests rejected with a 429",
+                )
+                .unwrap(),
+            ),
+            orders_fetched: register(
+                &registry,
+                IntCounter::new("orders_fetched_total", "Orders returned by the API").unwrap(),
+            ),
+            buys_attempted: register(
+                &registry,
+                IntCounter::new(
+                    "buys_attempted_total",
+                    "Listings for which a buy call was built",
+                )
+                .unwrap(),
+            ),
+            buys_succeeded: register(
+                &registry,
+                IntCounter::new(
+                    "buys_succeeded_total",
+                    "Purchase txs which succeeded on-chain, mined or in an included bundle",
+                )
+                .unwrap(),
+            ),
+            bundle_simulations: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "bundle_simulations_total",
+                        "Bundle simulations, by outcome (ok / error)",
+                    ),
+                    &["outcome"],
+                )
+                .unwrap(),
+            ),
+            gas_paid: register(
+                &registry,
+                Counter::new(
+                    "gas_paid_wei_total",
+                    "Gas fees of the included bundles as simulated, in Wei",
+                )
+                .unwrap(),
+            ),
+            registry,
+        }
+    });
+}
+
+/// The registry of the metrics, e.g. to be served on a `/metrics` endpoint
+#[cfg(feature = "metrics")]
+pub fn registry() -> &'static prometheus::Registry {
+    &recorder::METRICS.registry
+}
+
+/// A request to the API `endpoint`, with the HTTP status of its response if any
+pub(crate) fn api_request(endpoint: &str, status: Option<u16>, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = &recorder::METRICS;
+        let status = status.map_or_else(|| "error".to_owned(), |status| status.to_string());
+        metrics
+            .api_requests
+            .with_label_values(&[endpoint, status.as_str()])
+            .inc();
+        metrics
+            .api_latency
+            .with_label_values(&[endpoint])
+            .observe(elapsed.as_secs_f64());
+        if status == "429" {
+            metrics.api_rate_limited.inc();
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (endpoint, status, elapsed);
+}
+
+pub(crate) fn orders_fetched(num: usize) {
+    #[cfg(feature = "metrics")]
+    recorder::METRICS.orders_fetched.inc_by(num as u64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = num;
+}
+
+pub(crate) fn buy_attempted() {
+    #[cfg(feature = "metrics")]
+    recorder::METRICS.buys_attempted.inc();
+}
+
+pub(crate) fn buys_succeeded(num: usize) {
+    #[cfg(feature = "metrics")]
+    recorder::METRICS.buys_succeeded.inc_by(num as u64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = num;
+}
+
+pub(crate) fn bundle_simulated(ok: bool) {
+    #[cfg(feature = "metrics")]
+    recorder::METRICS
+        .bundle_simulations
+        .with_label_values(&[if ok { "ok" } else { "error" }])
+        .inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = ok;
+}
+
+pub(crate) fn gas_paid(wei: U256) {
+    #[cfg(feature = "metrics")]
+    recorder::METRICS.gas_paid.inc_by(wei.low_u128() as f64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = wei;
+}
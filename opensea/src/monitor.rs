@@ -0,0 +1,460 @@
+//! Polling of a collection's listings, streaming the price changes
+use ethers::types::{Address, U256};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::broadcast, task::JoinHandle, time::Instant};
+
+use crate::{
+    abort::{AbortHandle, AbortReason},
+    api::{OpenSeaApiError, OrderRequest},
+    order_provider::OrderProvider,
+    snapshot::{orderbook_rows, orderbook_snapshot, OrderbookRow},
+    types::OrderSide,
+    unix_timestamp, MAX_TOKEN_IDS_PER_QUERY,
+};
+
+/// The max number of events buffered for slow subscribers
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A change of the cheapest listing of a token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PriceEvent {
+    /// The token got listed
+    NewListing(OrderbookRow),
+    /// The cheapest listing of the token got cheaper
+    PriceDrop {
+        old_price: U256,
+        listing: OrderbookRow,
+    },
+    /// The token is no longer listed
+    Delisted(OrderbookRow),
+    /// The cheapest listing of the token expired. Its replacement, if any, follows as a
+    /// `NewListing`.
+    Expired(OrderbookRow),
+}
+
+/// Polls the listings of a collection, keeping track of the cheapest one of each token,
+/// and broadcasts the changes between polls to all subscribers. The listings are also
+/// dropped the moment they expire, rather than at the next poll, see
+/// [`PriceMonitor::expire`].
+#[derive(Debug)]
+pub struct PriceMonitor<P> {
+    api: Arc<P>,
+    collection: Address,
+    interval: Duration,
+    cheapest: HashMap<U256, OrderbookRow>,
+    tx: broadcast::Sender<PriceEvent>,
+}
+
+impl<P: OrderProvider + 'static> PriceMonitor<P> {
+    pub fn new(api: Arc<P>, collection: Address, interval: Duration) -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            api,
+            collection,
+            interval,
+            cheapest: HashMap::new(),
+            tx,
+        }
+    }
+
+    /// Returns a receiver of the events of all the following polls
+    pub fn subscribe(&self) -> broadcast::Receiver<PriceEvent> {
+        self.tx.subscribe()
+    }
+
+    /// The cheapest listing of each token, as of the last poll
+    pub fn cheapest(&self) -> &HashMap<U256, OrderbookRow> {
+        &self.cheapest
+    }
+
+    /// The time left until the cheapest listing of the token expires, `None` if it is not
+    /// listed or its listing does not expire
+    pub fn time_to_expiry(&self, token_id: U256) -> Option<Duration> {
+        self.cheapest
+            .get(&token_id)?
+            .time_to_expiry(unix_timestamp())
+    }
+
+    /// The unix timestamp at which the first of the tracked listings expires
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.cheapest
+            .values()
+            .map(|row| row.expiration)
+            .filter(|expiration| *expiration != 0)
+            .min()
+    }
+
+    /// Fetches the listings, broadcasts and returns the changes since the last poll.
+    /// All listings are new on the first poll. The expired listings still returned by
+    /// the API are ignored. The tracked tokens missing from the orderbook's pages are
+    /// queried directly before being reported as delisted, as the pages may shift while
+    /// they are queried.
+    pub async fn poll(&mut self) -> Result<Vec<PriceEvent>, OpenSeaApiError> {
+        self.poll_at(unix_timestamp()).await
+    }
+
+    /// Drops the listings which expired, broadcasting an `Expired` event for each, then
+    /// polls for their replacements if any expired
+    pub async fn expire(&mut self) -> Result<Vec<PriceEvent>, OpenSeaApiError> {
+        self.expire_at(unix_timestamp()).await
+    }
+
+    async fn poll_at(&mut self, now: u64) -> Result<Vec<PriceEvent>, OpenSeaApiError> {
+        let rows = orderbook_snapshot(self.api.as_ref(), self.collection, OrderSide::Sell).await?;
+        let mut cheapest = HashMap::new();
+        insert_cheapest(&mut cheapest, rows, now);
+
+        let missing = self
+            .cheapest
+            .keys()
+            .filter(|token_id| !cheapest.contains_key(token_id))
+            .map(|token_id| token_id.to_string())
+            .collect::<Vec<_>>();
+        for chunk in missing.chunks(MAX_TOKEN_IDS_PER_QUERY) {
+            let req = OrderRequest {
+                side: OrderSide::Sell,
+                token_ids: chunk.to_vec(),
+                contract_address: self.collection,
+                limit: 50,
+                offset: 0,
+            };
+            let rows = orderbook_rows(self.api.get_all_orders(req).await?);
+            insert_cheapest(&mut cheapest, rows, now);
+        }
+
+        let events = diff(&self.cheapest, &cheapest);
+        self.cheapest = cheapest;
+        self.broadcast(&events);
+        Ok(events)
+    }
+
+    async fn expire_at(&mut self, now: u64) -> Result<Vec<PriceEvent>, OpenSeaApiError> {
+        let expired = self
+            .cheapest
+            .values()
+            .filter(|row| row.is_expired(now))
+            .map(|row| row.token_id)
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut events = expired
+            .into_iter()
+            .filter_map(|token_id| self.cheapest.remove(&token_id))
+            .map(PriceEvent::Expired)
+            .collect::<Vec<_>>();
+        events.sort_by_key(token_id);
+        self.broadcast(&events);
+        events.extend(self.poll_at(now).await?);
+        Ok(events)
+    }
+
+    fn broadcast(&self, events: &[PriceEvent]) {
+        for event in events {
+            // there may be no subscribers
+            let _ = self.tx.send(event.clone());
+        }
+    }
+
+    /// Polls at the configured interval, and as soon as a tracked listing expires, until
+    /// the task is aborted. Failed polls are logged and retried at the next tick.
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut next_poll = Instant::now();
+            loop {
+                // a timer for the first listing to expire, refreshed after each poll
+                let expiry = self.next_expiry().map(|expiration| {
+                    let left = expiration.saturating_sub(unix_timestamp());
+                    Instant::now() + Duration::from_secs(left)
+                });
+                let result = match expiry {
+                    Some(expiry) if expiry < next_poll => {
+                        tokio::time::sleep_until(expiry).await;
+                        self.expire().await
+                    }
+                    _ => {
+                        tokio::time::sleep_until(next_poll).await;
+                        next_poll += self.interval;
+                        self.poll().await
+                    }
+                };
+                if let Err(err) = result {
+                    say!(
+                        "Could not poll the listings of {:?}: {}",
+                        self.collection,
+                        err
+                    );
+                }
+            }
+        })
+    }
+}
+
+/// Aborts the snipe of the `token_ids` of the `nft` collection once the cheapest
+/// listing of one of them moves, as streamed by a [`PriceMonitor`] of the collection:
+/// once it gets cheaper, delisted or expires, the price of the reason being zero in
+/// the latter cases. Resolves once aborted, or once the monitor stopped.
+pub async fn abort_on_price_moves(
+    mut events: broadcast::Receiver<PriceEvent>,
+    nft: Address,
+    token_ids: &[U256],
+    abort: &AbortHandle,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            // the events of a slow subscriber are dropped, but the later ones still
+            // report the moves
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let price = match event {
+            PriceEvent::NewListing(_) => continue,
+            PriceEvent::PriceDrop { ref listing, .. } => listing.price,
+            PriceEvent::Delisted(_) | PriceEvent::Expired(_) => U256::zero(),
+        };
+        let token_id = token_id(&event);
+        if token_ids.contains(&token_id) {
+            abort.abort(AbortReason::PriceMoved {
+                nft,
+                token_id,
+                price,
+            });
+            return;
+        }
+    }
+}
+
+/// Keeps the cheapest of the unexpired `rows` of each token, the rows being sorted by
+/// price for each token
+fn insert_cheapest(cheapest: &mut HashMap<U256, OrderbookRow>, rows: Vec<OrderbookRow>, now: u64) {
+    for row in rows.into_iter().filter(|row| !row.is_expired(now)) {
+        cheapest.entry(row.token_id).or_insert(row);
+    }
+}
+
+fn token_id(event: &PriceEvent) -> U256 {
+    match event {
+        PriceEvent::NewListing(row) | PriceEvent::Delisted(row) | PriceEvent::Expired(row) => {
+            row.token_id
+        }
+        PriceEvent::PriceDrop { listing, .. } => listing.token_id,
+    }
+}
+
+fn diff(old: &HashMap<U256, OrderbookRow>, new: &HashMap<U256, OrderbookRow>) -> Vec<PriceEvent> {
+    let mut events = Vec::new();
+    for (token_id, listing) in new {
+        match old.get(token_id) {
+            None => events.push(PriceEvent::NewListing(listing.clone())),
+            Some(prev) if listing.price < prev.price => events.push(PriceEvent::PriceDrop {
+                old_price: prev.price,
+                listing: listing.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (token_id, listing) in old {
+        if !new.contains_key(token_id) {
+            events.push(PriceEvent::Delisted(listing.clone()));
+        }
+    }
+    events.sort_by_key(token_id);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(token_id: u64, price: u64) -> OrderbookRow {
+        OrderbookRow {
+            token_id: token_id.into(),
+            price: price.into(),
+            payment_token: Address::zero(),
+            maker: Address::zero(),
+            expiration: 0,
+        }
+    }
+
+    fn book(rows: &[OrderbookRow]) -> HashMap<U256, OrderbookRow> {
+        rows.iter().map(|row| (row.token_id, row.clone())).collect()
+    }
+
+    #[test]
+    fn diffs_cheapest_listings() {
+        let old = book(&[row(1, 100), row(2, 100), row(3, 100)]);
+        let new = book(&[row(1, 90), row(2, 110), row(4, 50)]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                PriceEvent::PriceDrop {
+                    old_price: 100.into(),
+                    listing: row(1, 90),
+                },
+                PriceEvent::Delisted(row(3, 100)),
+                PriceEvent::NewListing(row(4, 50)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_on_the_moves_of_the_targets() {
+        let nft = Address::random();
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let abort = AbortHandle::new();
+        let targets = [2.into(), 3.into()];
+        let trigger = abort_on_price_moves(tx.subscribe(), nft, &targets, &abort);
+        // neither a new listing of a target nor a move of another token abort
+        tx.send(PriceEvent::NewListing(row(2, 100))).unwrap();
+        tx.send(PriceEvent::Delisted(row(1, 100))).unwrap();
+        tx.send(PriceEvent::PriceDrop {
+            old_price: 100.into(),
+            listing: row(3, 90),
+        })
+        .unwrap();
+        trigger.await;
+        assert_eq!(
+            abort.reason(),
+            Some(AbortReason::PriceMoved {
+                nft,
+                token_id: 3.into(),
+                price: 90.into(),
+            })
+        );
+
+        let abort = AbortHandle::new();
+        let rx = tx.subscribe();
+        tx.send(PriceEvent::Expired(row(2, 100))).unwrap();
+        abort_on_price_moves(rx, nft, &[2.into()], &abort).await;
+        assert!(matches!(
+            abort.reason(),
+            Some(AbortReason::PriceMoved { price, .. }) if price.is_zero()
+        ));
+
+        // stops with the monitor
+        let abort = AbortHandle::new();
+        let rx = tx.subscribe();
+        drop(tx);
+        abort_on_price_moves(rx, nft, &[2.into()], &abort).await;
+        assert!(!abort.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn broadcasts_new_listings() {
+        let order = crate::testing::order();
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let api = Arc::new(crate::MockOrderProvider::new(vec![order]));
+
+        let mut monitor = PriceMonitor::new(api, asset.address, Duration::from_secs(1));
+        let mut rx = monitor.subscribe();
+        assert_eq!(monitor.poll().await.unwrap().len(), 1);
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            PriceEvent::NewListing(row) if row.token_id == asset.id
+        ));
+
+        // nothing changed
+        assert!(monitor.poll().await.unwrap().is_empty());
+        assert_eq!(monitor.cheapest().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn drops_expired_listings() {
+        let mut json = crate::testing::order_json();
+        let expiration = 1_000_000;
+        json["expiration_time"] = expiration.into();
+        let order: crate::types::Order = serde_json::from_value(json).unwrap();
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let api = Arc::new(crate::MockOrderProvider::new(vec![order]));
+
+        let mut monitor = PriceMonitor::new(api, asset.address, Duration::from_secs(1));
+        assert_eq!(monitor.poll_at(0).await.unwrap().len(), 1);
+        assert_eq!(monitor.next_expiry(), Some(expiration));
+        assert_eq!(
+            monitor.cheapest()[&asset.id].time_to_expiry(expiration - 60),
+            Some(Duration::from_secs(60))
+        );
+        assert!(monitor.expire_at(expiration - 1).await.unwrap().is_empty());
+
+        // the API still returns the expired listing, which is not a replacement
+        let mut rx = monitor.subscribe();
+        let events = monitor.expire_at(expiration).await.unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [PriceEvent::Expired(row)] if row.token_id == asset.id
+        ));
+        assert!(matches!(rx.recv().await.unwrap(), PriceEvent::Expired(_)));
+        assert!(monitor.cheapest().is_empty());
+        assert_eq!(monitor.next_expiry(), None);
+    }
+
+    /// Serves the listings of the collection over several pages, missing the listing of
+    /// the `shifted` token as if it moved to a page already queried
+    struct ShiftingPages {
+        api: crate::MockOrderProvider,
+        shifted: std::sync::Mutex<Option<U256>>,
+    }
+
+    #[async_trait::async_trait]
+    impl OrderProvider for ShiftingPages {
+        async fn get_orders(
+            &self,
+            req: OrderRequest,
+        ) -> Result<Vec<crate::types::Order>, OpenSeaApiError> {
+            let shifted = *self.shifted.lock().unwrap();
+            let mut orders = self.api.get_orders(req.clone()).await?;
+            if req.token_ids.is_empty() {
+                orders.retain(|order| Some(order.asset_metadata().unwrap().asset.id) != shifted);
+            }
+            Ok(orders)
+        }
+
+        async fn get_order_by_hash(
+            &self,
+            order_hash: ethers::types::H256,
+        ) -> Result<crate::types::Order, OpenSeaApiError> {
+            self.api.get_order_by_hash(order_hash).await
+        }
+
+        async fn post_order(
+            &self,
+            order: crate::types::MinimalOrder,
+        ) -> Result<(), OpenSeaApiError> {
+            self.api.post_order(order).await
+        }
+
+        async fn get_assets(
+            &self,
+            req: crate::api::AssetsRequest,
+        ) -> Result<Vec<crate::types::CollectionAsset>, OpenSeaApiError> {
+            self.api.get_assets(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn confirms_the_delistings() {
+        let order = crate::testing::order();
+        let collection = order.asset_metadata().unwrap().asset.address;
+        let orders = (0..60u64)
+            .map(|id| {
+                let mut order = order.clone();
+                order.order_hash = ethers::types::H256::from_low_u64_be(id);
+                order.asset_metadata_mut().unwrap().asset.id = id.into();
+                order
+            })
+            .collect();
+        let api = Arc::new(ShiftingPages {
+            api: crate::MockOrderProvider::new(orders),
+            shifted: Default::default(),
+        });
+
+        let mut monitor = PriceMonitor::new(api.clone(), collection, Duration::from_secs(1));
+        // past the first page
+        assert_eq!(monitor.poll().await.unwrap().len(), 60);
+
+        *api.shifted.lock().unwrap() = Some(55.into());
+        assert!(monitor.poll().await.unwrap().is_empty());
+        assert_eq!(monitor.cheapest().len(), 60);
+    }
+}
@@ -0,0 +1,335 @@
+use ethers::{
+    providers::Middleware,
+    types::{Address, BlockNumber, U256},
+};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::ClientError;
+
+#[derive(Clone, Debug, Default)]
+struct WalletNonces {
+    /// The next never-reserved nonce
+    next: U256,
+    /// Ranges which were reserved and then released without being used
+    released: Vec<Range<U256>>,
+    /// Distinguishes the counters of the wallet across resets and resyncs, so that the
+    /// reservations of a former counter are not released into the current one
+    generation: u64,
+}
+
+impl WalletNonces {
+    fn reserve(&mut self, len: U256) -> Range<U256> {
+        // prefer re-using the lowest released range that fits, so that no gaps are left
+        self.released.sort_by_key(|range| range.start);
+        if let Some(idx) = self
+            .released
+            .iter()
+            .position(|range| range.end - range.start >= len)
+        {
+            let range = &mut self.released[idx];
+            let start = range.start;
+            range.start += len;
+            if range.start == range.end {
+                self.released.remove(idx);
+            }
+            return start..start + len;
+        }
+
+        let start = self.next;
+        self.next += len;
+        start..self.next
+    }
+
+    fn release(&mut self, range: Range<U256>) {
+        if range.start == range.end {
+            return;
+        }
+        self.released.push(range);
+        self.released.sort_by_key(|range| range.start);
+
+        // coalesce adjacent ranges
+        let mut merged: Vec<Range<U256>> = Vec::with_capacity(self.released.len());
+        for range in self.released.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+
+        // roll back the counter if the tail of the reservations was released
+        if let Some(last) = merged.last() {
+            if last.end == self.next {
+                self.next = last.start;
+                merged.pop();
+            }
+        }
+        self.released = merged;
+    }
+}
+
+/// Async-safe nonce allocator keyed by wallet. Cloning it shares the underlying
+/// state, so that multiple planners building transactions for the same wallet
+/// concurrently never get assigned the same nonce.
+#[derive(Clone, Debug, Default)]
+pub struct NonceAllocator {
+    wallets: Arc<Mutex<HashMap<Address, WalletNonces>>>,
+    /// The last generation of a wallet counter, see [`WalletNonces::generation`]
+    generations: Arc<AtomicU64>,
+}
+
+impl NonceAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Atomically reserves `len` contiguous nonces for `wallet`. The first reservation
+    /// for a wallet initializes its counter from the provider's pending transaction count.
+    pub async fn reserve<M: Middleware>(
+        &self,
+        provider: &M,
+        wallet: Address,
+        len: usize,
+    ) -> Result<NonceReservation, ClientError> {
+        if !self.wallets.lock().unwrap().contains_key(&wallet) {
+            let nonce = provider
+                .get_transaction_count(wallet, Some(BlockNumber::Pending.into()))
+                .await
+                .map_err(|err| ClientError::Provider(err.to_string()))?;
+            // another task may have initialized it in the meantime, keep theirs
+            self.wallets
+                .lock()
+                .unwrap()
+                .entry(wallet)
+                .or_insert_with(|| self.counter(nonce));
+        }
+
+        let (range, generation) = {
+            let mut wallets = self.wallets.lock().unwrap();
+            let nonces = wallets.get_mut(&wallet).expect("wallet was initialized");
+            (nonces.reserve(len.into()), nonces.generation)
+        };
+
+        Ok(NonceReservation {
+            allocator: self.clone(),
+            wallet,
+            generation,
+            range,
+            committed: false,
+        })
+    }
+
+    /// A counter starting at `next`, of a new generation
+    fn counter(&self, next: U256) -> WalletNonces {
+        WalletNonces {
+            next,
+            released: Vec::new(),
+            generation: self.generations.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+
+    /// Forgets the wallet's counter, so that it gets re-fetched from the provider
+    /// on the next reservation (e.g. after transactions were dropped). The outstanding
+    /// reservations are then not released into the new counter.
+    pub fn reset(&self, wallet: Address) {
+        self.wallets.lock().unwrap().remove(&wallet);
+    }
+
+    /// Re-fetches the wallet's counter from the provider's pending transaction count,
+    /// discarding the released ranges, and returns it. Used when transactions built
+    /// with reserved nonces were dropped or failed to send, which would otherwise leave
+    /// the following nonces stranded. The outstanding reservations are then not released
+    /// into the new counter.
+    pub async fn resync<M: Middleware>(
+        &self,
+        provider: &M,
+        wallet: Address,
+    ) -> Result<U256, ClientError> {
+        let nonce = provider
+            .get_transaction_count(wallet, Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+        self.wallets
+            .lock()
+            .unwrap()
+            .insert(wallet, self.counter(nonce));
+        Ok(nonce)
+    }
+
+    /// Releases the range reserved from the `generation` of the wallet's counter, unless
+    /// the counter was reset or resynced since
+    fn release(&self, wallet: Address, generation: u64, range: Range<U256>) {
+        if let Some(nonces) = self.wallets.lock().unwrap().get_mut(&wallet) {
+            if nonces.generation == generation {
+                nonces.release(range);
+            }
+        }
+    }
+}
+
+/// A contiguous range of nonces reserved for a wallet. Unless committed, the range
+/// is released back to the allocator when dropped (e.g. when a plan is abandoned).
+#[derive(Debug)]
+pub struct NonceReservation {
+    allocator: NonceAllocator,
+    wallet: Address,
+    generation: u64,
+    range: Range<U256>,
+    committed: bool,
+}
+
+impl NonceReservation {
+    /// The reserved nonces
+    pub fn nonces(&self) -> impl Iterator<Item = U256> {
+        let start = self.range.start;
+        (0..(self.range.end - start).as_u64()).map(move |i| start + i)
+    }
+
+    pub fn wallet(&self) -> Address {
+        self.wallet
+    }
+
+    pub fn len(&self) -> usize {
+        (self.range.end - self.range.start).as_usize()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Marks the first `used` nonces as consumed, releasing the rest of the range
+    pub fn commit(mut self, used: usize) {
+        let split = std::cmp::min(self.range.start + used, self.range.end);
+        self.allocator
+            .release(self.wallet, self.generation, split..self.range.end);
+        self.committed = true;
+    }
+}
+
+impl Drop for NonceReservation {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.allocator
+                .release(self.wallet, self.generation, self.range.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn allocator(wallet: Address, next: u64) -> NonceAllocator {
+        let allocator = NonceAllocator::new();
+        allocator.wallets.lock().unwrap().insert(
+            wallet,
+            WalletNonces {
+                next: next.into(),
+                ..WalletNonces::default()
+            },
+        );
+        allocator
+    }
+
+    #[test]
+    fn reuses_released_ranges() {
+        let mut nonces = WalletNonces::default();
+        let a = nonces.reserve(3.into());
+        let b = nonces.reserve(2.into());
+        assert_eq!(a, U256::from(0)..U256::from(3));
+        assert_eq!(b, U256::from(3)..U256::from(5));
+
+        // releasing a middle range keeps it for reuse
+        nonces.release(a);
+        assert_eq!(nonces.reserve(2.into()), U256::from(0)..U256::from(2));
+
+        // releasing the tail rolls the counter back, coalescing with the leftover
+        nonces.release(b);
+        assert_eq!(nonces.next, 2.into());
+        assert!(nonces.released.is_empty());
+    }
+
+    #[tokio::test]
+    async fn abandoned_reservations_are_released() {
+        let wallet = Address::random();
+        let allocator = allocator(wallet, 10);
+        let provider = ethers::providers::Provider::try_from("http://localhost:8545").unwrap();
+
+        let first = allocator.reserve(&provider, wallet, 3).await.unwrap();
+        assert_eq!(
+            first.nonces().collect::<Vec<_>>(),
+            vec![10.into(), 11.into(), 12.into()]
+        );
+        first.commit(1);
+
+        let second = allocator.reserve(&provider, wallet, 2).await.unwrap();
+        assert_eq!(
+            second.nonces().collect::<Vec<_>>(),
+            vec![11.into(), 12.into()]
+        );
+        drop(second);
+
+        let third = allocator.reserve(&provider, wallet, 1).await.unwrap();
+        assert_eq!(third.nonces().collect::<Vec<_>>(), vec![11.into()]);
+    }
+
+    #[tokio::test]
+    async fn resyncs_from_pending_count() {
+        let wallet = Address::random();
+        let allocator = allocator(wallet, 10);
+        let (provider, mock) = ethers::providers::Provider::mocked();
+
+        let reservation = allocator.reserve(&provider, wallet, 3).await.unwrap();
+        reservation.commit(3);
+
+        // the 3 txs were dropped, only the first one made it to the mempool
+        mock.push(U256::from(11)).unwrap();
+        assert_eq!(
+            allocator.resync(&provider, wallet).await.unwrap(),
+            11.into()
+        );
+        let next = allocator.reserve(&provider, wallet, 1).await.unwrap();
+        assert_eq!(next.nonces().collect::<Vec<_>>(), vec![11.into()]);
+    }
+
+    #[tokio::test]
+    async fn ignores_the_reservations_of_former_counters() {
+        let wallet = Address::random();
+        let allocator = allocator(wallet, 10);
+        let (provider, mock) = ethers::providers::Provider::mocked();
+
+        let stale = allocator.reserve(&provider, wallet, 3).await.unwrap();
+        // nothing was sent yet
+        mock.push(U256::from(10)).unwrap();
+        allocator.resync(&provider, wallet).await.unwrap();
+        let current = allocator.reserve(&provider, wallet, 3).await.unwrap();
+        assert_eq!(
+            current.nonces().collect::<Vec<_>>(),
+            vec![10.into(), 11.into(), 12.into()]
+        );
+
+        // the nonces of the former counter are not handed out again
+        drop(stale);
+        let next = allocator.reserve(&provider, wallet, 1).await.unwrap();
+        assert_eq!(next.nonces().collect::<Vec<_>>(), vec![13.into()]);
+
+        // nor after a reset
+        mock.push(U256::from(10)).unwrap();
+        allocator.reset(wallet);
+        let fresh = allocator.reserve(&provider, wallet, 2).await.unwrap();
+        assert_eq!(
+            fresh.nonces().collect::<Vec<_>>(),
+            vec![10.into(), 11.into()]
+        );
+        drop(current);
+        let next = allocator.reserve(&provider, wallet, 1).await.unwrap();
+        assert_eq!(next.nonces().collect::<Vec<_>>(), vec![12.into()]);
+    }
+}
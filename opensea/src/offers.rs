@@ -0,0 +1,187 @@
+//! Selling owned tokens to their best bids, e.g. to exit a position at once
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, U256},
+};
+
+use crate::{
+    get_best_orders,
+    types::{MinimalOrder, OrderSide, PreparedSale},
+    Client, ClientError, OrderOrdering, OrderProvider,
+};
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Builds the `atomicMatch_` calls selling each of the tokens to its best bid, with
+    /// the client's wallet as seller (see [`Client::with_signer`]). The expired bids,
+    /// those below `min_price` (in their payment token) and those which cannot be
+    /// matched are skipped, as are the tokens left without any. The calls must be sent
+    /// by the seller, e.g. via [`Client::send_calls`], whose Wyvern proxy must be
+    /// approved for the tokens.
+    pub async fn accept_best_offers(
+        &self,
+        tokens: &[(Address, U256)],
+        min_price: U256,
+    ) -> Result<Vec<PreparedSale<M>>, ClientError> {
+        let seller = self.signer.as_ref().ok_or(ClientError::NoSigner)?.address();
+        let chain_id = self.chain_id().await?;
+        let mut sales = Vec::new();
+        for &(token, token_id) in tokens {
+            // already sorted by price and filtered by expiration
+            let bids = get_best_orders(
+                &self.api,
+                token,
+                token_id,
+                OrderSide::Buy,
+                usize::MAX,
+                OrderOrdering::Best,
+            )
+            .await?;
+
+            let mut matched = None;
+            for bid in bids
+                .into_iter()
+                .filter(|bid| bid.current_price >= min_price)
+            {
+                let buy = MinimalOrder::from(bid.clone());
+                // bids of the collection or of other tokens are not matched
+                if buy.token_id() != Some(token_id) {
+                    continue;
+                }
+                if !bid.approved_on_chain {
+                    if let Err(err) = buy.verify_signature(chain_id) {
+                        say!(
+                            "[Token Id = {:?}] Skipping bid {:?}: {}",
+                            token_id,
+                            bid.order_hash,
+                            err
+                        );
+                        continue;
+                    }
+                }
+                match buy.match_buy(seller, None) {
+                    Ok(sell) => {
+                        matched = Some((bid, buy, sell));
+                        break;
+                    }
+                    Err(err) => say!(
+                        "[Token Id = {:?}] Skipping bid {:?}: {}",
+                        token_id,
+                        bid.order_hash,
+                        err
+                    ),
+                }
+            }
+
+            if let Some((bid, buy, sell)) = matched {
+                say!("[Token Id = {:?}] Accepting {}", token_id, bid);
+                let call = self.atomic_match(buy, sell).await?.from(seller);
+                sales.push(PreparedSale {
+                    call,
+                    price: bid.current_price,
+                    order: bid,
+                    token,
+                    token_id,
+                });
+            }
+        }
+        Ok(sales)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::Order, MockOrderProvider};
+    use ethers::{
+        providers::Provider,
+        signers::LocalWallet,
+        types::{Bytes, U64},
+    };
+    use std::sync::Arc;
+
+    async fn bid(bidder: &LocalWallet, token: Address, token_id: U256, price: U256) -> Order {
+        let mut order = crate::testing::order();
+        order.side = OrderSide::Buy;
+        order.maker.address = bidder.address();
+        order.taker.address = Address::zero();
+        order.target = token;
+        order.payment_token = crate::constants::MAINNET.weth;
+        order.base_price = price;
+        order.current_price = price;
+        order.order_hash = ethers::types::H256::random();
+        let metadata = order.asset_metadata_mut().unwrap();
+        metadata.asset.address = token;
+        metadata.asset.id = token_id;
+        metadata.schema = "ERC721".to_owned();
+
+        let abi = ethers::contract::BaseContract::from(crate::contracts::OPENSEA_ABI.clone());
+        let calldata: Bytes = abi
+            .encode_with_selector(
+                ethers::utils::id("transferFrom(address,address,uint256)"),
+                (Address::zero(), bidder.address(), token_id),
+            )
+            .unwrap();
+        let mut pattern = vec![0; calldata.as_ref().len()];
+        pattern[4..4 + 32].copy_from_slice(&[0xff; 32]);
+        order.calldata = calldata;
+        order.replacement_pattern = pattern.into();
+
+        let signature = bidder
+            .sign_message(MinimalOrder::from(order.clone()).hash())
+            .await
+            .unwrap();
+        order.v = signature.v;
+        signature.r.to_big_endian(order.r.as_bytes_mut());
+        signature.s.to_big_endian(order.s.as_bytes_mut());
+        order
+    }
+
+    #[tokio::test]
+    async fn accepts_the_best_offers() {
+        let bidder = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let seller = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let nft = Address::random();
+        let eth = U256::exp10(18);
+        let bids = vec![
+            bid(&bidder, nft, 1.into(), eth).await,
+            bid(&bidder, nft, 1.into(), eth * 2).await,
+            bid(&bidder, nft, 2.into(), eth / 2).await,
+        ];
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(bids.clone()));
+        assert!(matches!(
+            client.accept_best_offers(&[(nft, 1.into())], eth).await,
+            Err(ClientError::NoSigner)
+        ));
+
+        let client = client.with_signer(seller.clone());
+        let sales = client
+            .accept_best_offers(&[(nft, 1.into()), (nft, 2.into())], eth)
+            .await
+            .unwrap();
+        assert_eq!(sales.len(), 1);
+        let sale = &sales[0];
+        assert_eq!(sale.order.order_hash, bids[1].order_hash);
+        assert_eq!(
+            (sale.token, sale.token_id, sale.price),
+            (nft, 1.into(), eth * 2)
+        );
+        assert_eq!(sale.call.tx.from(), Some(&seller.address()));
+        assert_eq!(sale.call.tx.value(), None);
+
+        // the calldatas match once the bid's replacement pattern is applied
+        let buy = MinimalOrder::from(bids[1].clone());
+        let sell = buy.match_buy(seller.address(), Some(0)).unwrap();
+        assert_eq!(sell.side, OrderSide::Sell);
+        assert_eq!(sell.maker, seller.address());
+        assert!(sell.fee_recipient.is_zero());
+        let mut calldata = buy.calldata.to_vec();
+        calldata[4..4 + 32].copy_from_slice(&sell.calldata.as_ref()[4..4 + 32]);
+        assert_eq!(calldata, sell.calldata.to_vec());
+        assert_eq!(sell.token_id(), Some(1.into()));
+        assert!(sell.match_buy(seller.address(), None).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Ownership of a whole collection read from the chain, to cross-check the owners
+//! reported by the API. Collections implementing ERC721Enumerable are enumerated
+//! through Multicall, the others have their holders reconstructed from their
+//! `Transfer` logs.
+use ethers::{
+    abi::Token,
+    contract::{EthEvent, Multicall},
+    providers::Middleware,
+    types::{Address, BlockNumber, Filter, Log, ValueOrArray, U256},
+};
+use std::{collections::HashMap, convert::TryFrom};
+
+use crate::{
+    constants,
+    contracts::{TransferFilter, NFT},
+    Client, ClientError,
+};
+
+/// Maximum number of calls a `Multicall` can aggregate
+const MAX_MULTICALL_CALLS: usize = 16;
+
+/// The blocks whose `Transfer` logs are queried at once, as nodes cap the block range
+/// or the number of logs of a query. Halved whenever the node rejects a range.
+const LOGS_BLOCK_RANGE: u64 = 100_000;
+
+/// The ERC165 interface id of ERC721Enumerable
+const ERC721_ENUMERABLE_INTERFACE_ID: [u8; 4] = [0x78, 0x0e, 0x9d, 0x63];
+
+/// Replays the ERC721 `Transfer` logs in chain order, returning the token ids held by
+/// each owner (sorted) after the last of them. Burnt tokens (transferred to the zero
+/// address) are dropped, and logs which are not ERC721 transfers are ignored.
+pub fn replay_transfers(logs: &[Log]) -> HashMap<Address, Vec<U256>> {
+    let mut logs = logs.iter().collect::<Vec<_>>();
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    let mut owners = HashMap::new();
+    for log in logs {
+        // ERC20 transfers share the signature, but not the indexed amount
+        if log.topics.len() != 4 {
+            continue;
+        }
+        let raw = ethers::abi::RawLog {
+            topics: log.topics.clone(),
+            data: log.data.to_vec(),
+        };
+        if let Ok(transfer) = TransferFilter::decode_log(&raw) {
+            if transfer.to.is_zero() {
+                owners.remove(&transfer.token_id);
+            } else {
+                owners.insert(transfer.token_id, transfer.to);
+            }
+        }
+    }
+    holdings(owners)
+}
+
+/// Groups the token ids by owner
+fn holdings(owners: HashMap<U256, Address>) -> HashMap<Address, Vec<U256>> {
+    let mut holdings = HashMap::<_, Vec<_>>::new();
+    for (id, owner) in owners {
+        holdings.entry(owner).or_default().push(id);
+    }
+    for ids in holdings.values_mut() {
+        ids.sort_unstable();
+    }
+    holdings
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Returns the token ids held by each owner of the ERC721 `contract` at `block`.
+    /// Enumerates the collection if it implements ERC721Enumerable (`totalSupply`,
+    /// `tokenByIndex` and `ownerOf` batched via Multicall), otherwise replays all of its
+    /// `Transfer` logs up to the block, queried [`LOGS_BLOCK_RANGE`] blocks at a time,
+    /// see [`replay_transfers`].
+    pub async fn collection_owners(
+        &self,
+        contract: Address,
+        block: impl Into<BlockNumber>,
+    ) -> Result<HashMap<Address, Vec<U256>>, ClientError> {
+        let block = block.into();
+        let nft = NFT::new(contract, self.provider.clone());
+        // collections predating ERC165 revert
+        let enumerable = nft
+            .supports_interface(ERC721_ENUMERABLE_INTERFACE_ID)
+            .block(block)
+            .call()
+            .await
+            .unwrap_or_default();
+        if enumerable {
+            return self.enumerate_owners(&nft, block).await;
+        }
+
+        let to = match block {
+            BlockNumber::Number(number) => number.as_u64(),
+            BlockNumber::Earliest => 0,
+            BlockNumber::Latest | BlockNumber::Pending => self
+                .provider
+                .get_block_number()
+                .await
+                .map_err(|err| ClientError::Provider(err.to_string()))?
+                .as_u64(),
+        };
+        let filter = Filter::new()
+            .address(ValueOrArray::Value(contract))
+            .topic0(TransferFilter::signature());
+        let logs = self.get_logs_chunked(&filter, to).await?;
+        Ok(replay_transfers(&logs))
+    }
+
+    /// Returns the logs of the `filter` from the genesis up to the `to` block, halving
+    /// the queried range from [`LOGS_BLOCK_RANGE`] blocks whenever the node rejects it
+    async fn get_logs_chunked(&self, filter: &Filter, to: u64) -> Result<Vec<Log>, ClientError> {
+        let mut logs = Vec::new();
+        let (mut from, mut range) = (0, LOGS_BLOCK_RANGE);
+        while from <= to {
+            let end = to.min(from + range - 1);
+            let chunk = filter.clone().from_block(from).to_block(end);
+            match self.provider.get_logs(&chunk).await {
+                Ok(chunk) => {
+                    logs.extend(chunk);
+                    from = end + 1;
+                }
+                Err(_) if range > 1 => range /= 2,
+                Err(err) => return Err(ClientError::Provider(err.to_string())),
+            }
+        }
+        Ok(logs)
+    }
+
+    async fn enumerate_owners(
+        &self,
+        nft: &NFT<M>,
+        block: BlockNumber,
+    ) -> Result<HashMap<Address, Vec<U256>>, ClientError> {
+        let total_supply = nft
+            .total_supply()
+            .block(block)
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        let total_supply = usize::try_from(total_supply).map_err(|_| {
+            ClientError::Contract(format!("invalid `totalSupply` {}", total_supply))
+        })?;
+        let indices = (0..total_supply).map(U256::from).collect::<Vec<_>>();
+
+        let ids = self
+            .multicall_at(block, &indices, |index| nft.token_by_index(index))
+            .await?
+            .into_iter()
+            .map(|token| token.into_uint())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ClientError::Contract("unexpected `tokenByIndex` result".into()))?;
+        let owners = self
+            .multicall_at(block, &ids, |id| nft.owner_of(id))
+            .await?
+            .into_iter()
+            .map(|token| token.into_address())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ClientError::Contract("unexpected `ownerOf` result".into()))?;
+
+        Ok(holdings(ids.into_iter().zip(owners).collect()))
+    }
+
+    /// Makes the call for each of the `args` at `block`, batched via Multicall (one
+    /// RPC roundtrip per 16 calls)
+    async fn multicall_at<D: ethers::abi::Detokenize>(
+        &self,
+        block: BlockNumber,
+        args: &[U256],
+        call: impl Fn(U256) -> ethers::contract::builders::ContractCall<M, D>,
+    ) -> Result<Vec<Token>, ClientError> {
+        let mut multicall =
+            Multicall::new(self.provider.clone(), Some(*constants::MULTICALL_ADDRESS))
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?
+                .block(block);
+
+        let mut results = Vec::with_capacity(args.len());
+        for chunk in args.chunks(MAX_MULTICALL_CALLS) {
+            multicall.clear_calls();
+            for arg in chunk {
+                multicall.add_call(call(*arg));
+            }
+            match multicall
+                .call::<Token>()
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?
+            {
+                Token::Tuple(tokens) => results.extend(tokens),
+                token => results.push(token),
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::{
+        providers::Provider,
+        types::{Bytes, H256},
+    };
+    use std::sync::Arc;
+
+    fn transfer(block: u64, from: Address, to: Address, id: u64) -> Log {
+        let topic = |address: Address| H256::from(address);
+        let mut id_topic = [0; 32];
+        U256::from(id).to_big_endian(&mut id_topic);
+        Log {
+            address: Address::zero(),
+            topics: vec![
+                TransferFilter::signature(),
+                topic(from),
+                topic(to),
+                id_topic.into(),
+            ],
+            data: Default::default(),
+            block_hash: None,
+            block_number: Some(block.into()),
+            transaction_hash: Some(H256::random()),
+            transaction_index: None,
+            log_index: Some(0.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn replays_transfers_in_chain_order() {
+        let (alice, bob) = (Address::random(), Address::random());
+        let logs = vec![
+            transfer(3, alice, bob, 1),
+            transfer(1, Address::zero(), alice, 1),
+            transfer(1, Address::zero(), alice, 2),
+            transfer(2, Address::zero(), alice, 3),
+            // burnt
+            transfer(4, alice, Address::zero(), 2),
+        ];
+
+        let owners = replay_transfers(&logs);
+        assert_eq!(owners.len(), 2);
+        assert_eq!(owners[&alice], vec![U256::from(3)]);
+        assert_eq!(owners[&bob], vec![U256::one()]);
+    }
+
+    #[tokio::test]
+    async fn reconstructs_owners_of_non_enumerable_collections() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        let alice = Address::random();
+
+        // served last first: the logs after `supportsInterface` returned false
+        mock.push::<Vec<Log>, _>(vec![
+            transfer(1, Address::zero(), alice, 7),
+            transfer(1, Address::zero(), alice, 5),
+        ])
+        .unwrap();
+        mock.push(Bytes::from(ethers::abi::encode(&[Token::Bool(false)])))
+            .unwrap();
+
+        let owners = client
+            .collection_owners(Address::random(), 100)
+            .await
+            .unwrap();
+        assert_eq!(owners[&alice], vec![U256::from(5), U256::from(7)]);
+    }
+
+    #[tokio::test]
+    async fn queries_the_logs_in_chunks() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        let (alice, bob) = (Address::random(), Address::random());
+
+        // the logs of the blocks 200k..=250k, 100k..200k, then 0..100k
+        mock.push::<Vec<Log>, _>(vec![transfer(200_001, alice, bob, 1)])
+            .unwrap();
+        mock.push::<Vec<Log>, _>(vec![]).unwrap();
+        mock.push::<Vec<Log>, _>(vec![transfer(1, Address::zero(), alice, 1)])
+            .unwrap();
+        mock.push(Bytes::from(ethers::abi::encode(&[Token::Bool(false)])))
+            .unwrap();
+
+        let owners = client
+            .collection_owners(Address::random(), 250_000)
+            .await
+            .unwrap();
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[&bob], vec![U256::one()]);
+    }
+
+    #[tokio::test]
+    async fn rejects_an_oversized_total_supply() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+
+        mock.push(Bytes::from(ethers::abi::encode(&[Token::Uint(U256::MAX)])))
+            .unwrap();
+        mock.push(Bytes::from(ethers::abi::encode(&[Token::Bool(true)])))
+            .unwrap();
+
+        assert!(matches!(
+            client.collection_owners(Address::random(), 100).await,
+            Err(ClientError::Contract(_))
+        ));
+    }
+}
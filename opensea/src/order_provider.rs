@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use ethers::types::H256;
+use std::sync::Mutex;
+
+use crate::{
+    api::{
+        find_order, AssetsRequest, OpenSeaApi, OpenSeaApiError, OrderRequest, MAX_ORDERS_OFFSET,
+    },
+    types::{CollectionAsset, MinimalOrder, Order},
+};
+
+/// Source of marketplace orders. Implemented by [`OpenSeaApi`], and by
+/// [`MockOrderProvider`] for deterministic tests without network access.
+#[async_trait]
+pub trait OrderProvider: Send + Sync {
+    /// Returns the orders matching the request
+    async fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError>;
+
+    /// Returns all the orders matching the request, querying its pages of `req.limit`
+    /// orders from `req.offset` until a short one, up to the orderbook's max offset
+    async fn get_all_orders(&self, mut req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        let mut orders = Vec::new();
+        loop {
+            let page = self.get_orders(req.clone()).await?;
+            let last = (page.len() as u64) < req.limit;
+            orders.extend(page);
+            req.offset += req.limit;
+            if last || req.limit == 0 || req.offset >= MAX_ORDERS_OFFSET {
+                return Ok(orders);
+            }
+        }
+    }
+
+    /// Returns the first order matching the request
+    async fn get_order(&self, mut req: OrderRequest) -> Result<Order, OpenSeaApiError> {
+        req.limit = 1;
+        self.get_orders(req.clone())
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(OpenSeaApiError::OrderNotFound {
+                contract: req.contract_address,
+                id: req.token_ids.join(","),
+            })
+    }
+
+    /// Returns the order with the given hash, erroring with
+    /// `OpenSeaApiError::OrderHashNotFound` if it is no longer in the orderbook
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError>;
+
+    /// Submits a signed order to the orderbook
+    async fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError>;
+
+    /// Returns a page of the assets of a collection, along with their traits
+    async fn get_assets(&self, req: AssetsRequest)
+        -> Result<Vec<CollectionAsset>, OpenSeaApiError>;
+}
+
+#[async_trait]
+impl OrderProvider for OpenSeaApi {
+    async fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        OpenSeaApi::get_orders(self, req).await
+    }
+
+    async fn get_order(&self, req: OrderRequest) -> Result<Order, OpenSeaApiError> {
+        OpenSeaApi::get_order(self, req).await
+    }
+
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        OpenSeaApi::get_order_by_hash(self, order_hash).await
+    }
+
+    async fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        OpenSeaApi::post_order(self, order).await
+    }
+
+    async fn get_assets(
+        &self,
+        req: AssetsRequest,
+    ) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        OpenSeaApi::get_assets(self, req).await
+    }
+}
+
+/// Order provider serving canned orders, filtered like the API would
+#[derive(Debug, Default)]
+pub struct MockOrderProvider {
+    orders: Vec<Order>,
+    assets: Vec<CollectionAsset>,
+    posted: Mutex<Vec<MinimalOrder>>,
+}
+
+impl MockOrderProvider {
+    pub fn new(orders: Vec<Order>) -> Self {
+        Self {
+            orders,
+            assets: Vec::new(),
+            posted: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Instantiates the provider with the orders of a JSON fixture, either an
+    /// API response (`{"orders": [..]}`), an array of orders or a single order
+    pub fn from_json(json: &str) -> Result<Self, OpenSeaApiError> {
+        let orders = match serde_json::from_str(json)? {
+            serde_json::Value::Object(mut map) if map.contains_key("orders") => {
+                serde_json::from_value(map["orders"].take())?
+            }
+            value @ serde_json::Value::Array(_) => serde_json::from_value(value)?,
+            value => vec![serde_json::from_value(value)?],
+        };
+        Ok(Self::new(orders))
+    }
+
+    /// Serves the assets from the assets API, regardless of the requested collection.
+    /// The requested token ids are honored.
+    pub fn with_assets(mut self, assets: Vec<CollectionAsset>) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    /// The orders submitted via `post_order`
+    pub fn posted(&self) -> Vec<MinimalOrder> {
+        self.posted.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl OrderProvider for MockOrderProvider {
+    async fn get_orders(&self, req: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        Ok(self
+            .orders
+            .iter()
+            .filter(|order| {
+                order.side == req.side
+                    && order
+                        .asset_metadata()
+                        .map(|metadata| {
+                            metadata.asset.address == req.contract_address
+                                && (req.token_ids.is_empty()
+                                    || req.token_ids.contains(&metadata.asset.id.to_string()))
+                        })
+                        .unwrap_or(false)
+            })
+            .skip(req.offset as usize)
+            .take(req.limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        find_order(self.orders.clone(), order_hash)
+    }
+
+    async fn post_order(&self, order: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        self.posted.lock().unwrap().push(order);
+        Ok(())
+    }
+
+    async fn get_assets(
+        &self,
+        req: AssetsRequest,
+    ) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        Ok(self
+            .assets
+            .iter()
+            .filter(|asset| req.token_ids.is_empty() || req.token_ids.contains(&asset.token_id))
+            .skip(req.offset as usize)
+            .take(req.limit as usize)
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,134 @@
+use ethers::{
+    abi::Token,
+    contract::Multicall,
+    providers::Middleware,
+    types::{Address, U256},
+};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{constants, contracts::NFT, Client, ClientError};
+
+/// Maximum number of calls a `Multicall` can aggregate
+const MAX_MULTICALL_CALLS: usize = 16;
+
+/// The ERC165 interface id of ERC721
+const ERC721_INTERFACE_ID: [u8; 4] = [0x80, 0xac, 0x58, 0xcd];
+
+/// The ERC165 interface id of ERC1155
+const ERC1155_INTERFACE_ID: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+
+/// The token standard of an NFT collection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenStandard {
+    Erc721,
+    Erc1155,
+}
+
+impl TokenStandard {
+    /// The asset schema of the orders of the collection
+    pub fn schema(&self) -> &'static str {
+        match self {
+            TokenStandard::Erc721 => "ERC721",
+            TokenStandard::Erc1155 => "ERC1155",
+        }
+    }
+}
+
+/// Detects the token standard of the collection via ERC165 `supportsInterface`.
+/// Returns `None` if the collection supports neither (or cannot be queried), e.g. for
+/// collections predating ERC165.
+pub async fn token_standard<M: Middleware>(
+    provider: Arc<M>,
+    nft: Address,
+) -> Option<TokenStandard> {
+    let nft = NFT::new(nft, provider);
+    let supports = |interface_id| {
+        let call = nft.supports_interface(interface_id);
+        async move { call.call().await.unwrap_or_default() }
+    };
+    if supports(ERC1155_INTERFACE_ID).await {
+        Some(TokenStandard::Erc1155)
+    } else if supports(ERC721_INTERFACE_ID).await {
+        Some(TokenStandard::Erc721)
+    } else {
+        None
+    }
+}
+
+/// What to query for each token id in an ownership snapshot
+#[derive(Clone, Copy, Debug)]
+pub enum OwnershipQuery {
+    /// `ownerOf(id)` of an ERC721
+    Erc721,
+    /// `balanceOf(holder, id)` of an ERC1155
+    Erc1155 { holder: Address },
+}
+
+/// The ownership state of a token id
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ownership {
+    Owner(Address),
+    Balance(U256),
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// See [`token_standard`]
+    pub async fn token_standard(&self, nft: Address) -> Option<TokenStandard> {
+        token_standard(self.provider.clone(), nft).await
+    }
+
+    /// Queries the ownership of all `ids` of the `nft` collection, batching the reads
+    /// via Multicall (one RPC roundtrip per 16 ids)
+    pub async fn ownership_snapshot(
+        &self,
+        nft: Address,
+        ids: &[U256],
+        query: OwnershipQuery,
+    ) -> Result<HashMap<U256, Ownership>, ClientError> {
+        let nft = NFT::new(nft, self.provider.clone());
+        let mut multicall =
+            Multicall::new(self.provider.clone(), Some(*constants::MULTICALL_ADDRESS))
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?;
+
+        let mut snapshot = HashMap::new();
+        for chunk in ids.chunks(MAX_MULTICALL_CALLS) {
+            multicall.clear_calls();
+            for id in chunk {
+                match query {
+                    OwnershipQuery::Erc721 => multicall.add_call(nft.owner_of(*id)),
+                    OwnershipQuery::Erc1155 { holder } => {
+                        multicall.add_call(nft.balance_of(holder, *id))
+                    }
+                };
+            }
+
+            let results = match multicall
+                .call::<Token>()
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?
+            {
+                Token::Tuple(results) => results,
+                token => vec![token],
+            };
+
+            for (id, result) in chunk.iter().zip(results) {
+                let ownership = match (query, result) {
+                    (OwnershipQuery::Erc721, Token::Address(owner)) => Ownership::Owner(owner),
+                    (OwnershipQuery::Erc1155 { .. }, Token::Uint(balance)) => {
+                        Ownership::Balance(balance)
+                    }
+                    (_, token) => {
+                        return Err(ClientError::Contract(format!(
+                            "unexpected multicall result for token id {}: {:?}",
+                            id, token
+                        )))
+                    }
+                };
+                snapshot.insert(*id, ownership);
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
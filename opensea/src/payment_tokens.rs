@@ -0,0 +1,209 @@
+//! The tokens listings are priced in (ETH, WETH, USDC, DAI...) and their decimals, for
+//! comparing prices across payment tokens
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    marketplace::Listing,
+    snapshot::OrderbookRow,
+    types::{Network, Order},
+    OpenSeaApi, OpenSeaApiError,
+};
+
+/// The decimals prices are normalized to, those of ETH
+pub const NORMALIZED_DECIMALS: u8 = 18;
+
+/// A token listings can be priced in. ETH is the zero address.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentToken {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl PaymentToken {
+    pub fn new(address: Address, symbol: &str, decimals: u8) -> Self {
+        Self {
+            address,
+            symbol: symbol.to_owned(),
+            decimals,
+        }
+    }
+
+    /// Scales the amount of the token (in its smallest unit) to `NORMALIZED_DECIMALS`
+    /// decimals, e.g. 1 USDC (`1_000_000`) to `1e18`
+    pub fn normalize(&self, amount: U256) -> U256 {
+        if self.decimals <= NORMALIZED_DECIMALS {
+            amount * U256::exp10((NORMALIZED_DECIMALS - self.decimals) as usize)
+        } else {
+            amount / U256::exp10((self.decimals - NORMALIZED_DECIMALS) as usize)
+        }
+    }
+}
+
+/// The known payment tokens by address. Defaults to the mainnet ETH, WETH, USDC and
+/// DAI (see [`PaymentTokens::of`] for other networks), and can be extended via
+/// [`PaymentTokens::insert`] or [`PaymentTokens::refresh`].
+#[derive(Clone, Debug)]
+pub struct PaymentTokens {
+    tokens: HashMap<Address, PaymentToken>,
+}
+
+impl Default for PaymentTokens {
+    fn default() -> Self {
+        Self::of(Network::Mainnet)
+    }
+}
+
+impl PaymentTokens {
+    /// A registry without any token
+    pub fn empty() -> Self {
+        Self {
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// The known tokens of the network: ETH, the WETH of its deployment (see
+    /// [`Network::deployment`]) and, on mainnet, USDC and DAI
+    pub fn of(network: Network) -> Self {
+        let mut tokens = Self::empty();
+        tokens.insert(PaymentToken::new(Address::zero(), "ETH", 18));
+        if let Some(deployment) = network.deployment() {
+            tokens.insert(PaymentToken::new(deployment.weth, "WETH", 18));
+        }
+        if network == Network::Mainnet {
+            let stablecoins = [
+                ("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC", 6),
+                ("0x6b175474e89094c44da98b954eedeac495271d0f", "DAI", 18),
+            ];
+            for (address, symbol, decimals) in stablecoins.iter() {
+                tokens.insert(PaymentToken::new(
+                    address.parse().unwrap(),
+                    symbol,
+                    *decimals,
+                ));
+            }
+        }
+        tokens
+    }
+
+    /// Adds the token, replacing any token previously registered at its address
+    pub fn insert(&mut self, token: PaymentToken) {
+        self.tokens.insert(token.address, token);
+    }
+
+    pub fn get(&self, address: Address) -> Option<&PaymentToken> {
+        self.tokens.get(&address)
+    }
+
+    /// Registers the payment tokens supported by OpenSea, keeping the known ones
+    pub async fn refresh(&mut self, api: &OpenSeaApi) -> Result<(), OpenSeaApiError> {
+        for token in api.get_payment_tokens().await? {
+            self.insert(token);
+        }
+        Ok(())
+    }
+
+    /// The amount of the token normalized to `NORMALIZED_DECIMALS` decimals, or `None`
+    /// if the token is unknown
+    pub fn normalize(&self, token: Address, amount: U256) -> Option<U256> {
+        self.get(token).map(|token| token.normalize(amount))
+    }
+}
+
+impl Order {
+    /// The current price, normalized to `NORMALIZED_DECIMALS` decimals so that e.g. USDC
+    /// and DAI prices can be compared and sorted. The prices are not converted between
+    /// tokens of a different value. `None` if the payment token is unknown.
+    pub fn normalized_price(&self, tokens: &PaymentTokens) -> Option<U256> {
+        tokens.normalize(self.payment_token, self.current_price)
+    }
+}
+
+impl OrderbookRow {
+    /// The price normalized as [`Order::normalized_price`]
+    pub fn normalized_price(&self, tokens: &PaymentTokens) -> Option<U256> {
+        tokens.normalize(self.payment_token, self.price)
+    }
+}
+
+impl Listing {
+    /// The price normalized as [`Order::normalized_price`]
+    pub fn normalized_price(&self, tokens: &PaymentTokens) -> Option<U256> {
+        tokens.normalize(self.payment_token, self.price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_prices_across_tokens() {
+        let mut order = crate::testing::order();
+        let tokens = PaymentTokens::default();
+        assert_eq!(order.normalized_price(&tokens), Some(order.current_price));
+
+        // 25 USDC and 25 DAI
+        order.payment_token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        order.current_price = U256::from(25_000_000);
+        let usdc = order.normalized_price(&tokens).unwrap();
+        order.payment_token = "0x6b175474e89094c44da98b954eedeac495271d0f"
+            .parse()
+            .unwrap();
+        order.current_price = U256::exp10(18) * 25;
+        assert_eq!(order.normalized_price(&tokens), Some(usdc));
+
+        order.payment_token = Address::repeat_byte(1);
+        assert_eq!(order.normalized_price(&tokens), None);
+        let mut tokens = tokens;
+        tokens.insert(PaymentToken::new(Address::repeat_byte(1), "WBTC", 8));
+        order.current_price = U256::from(1);
+        assert_eq!(order.normalized_price(&tokens), Some(U256::exp10(10)));
+    }
+
+    #[test]
+    fn registers_the_weth_of_the_network() {
+        let weth = |network: Network| {
+            let tokens = PaymentTokens::of(network);
+            network
+                .deployment()
+                .and_then(|deployment| tokens.get(deployment.weth).cloned())
+                .map(|token| token.symbol)
+        };
+        assert_eq!(weth(Network::Mainnet).as_deref(), Some("WETH"));
+        assert_eq!(weth(Network::Rinkeby).as_deref(), Some("WETH"));
+        let mainnet_weth = crate::constants::MAINNET.weth;
+        assert!(PaymentTokens::of(Network::Rinkeby)
+            .get(mainnet_weth)
+            .is_none());
+        assert!(PaymentTokens::of(Network::Optimism)
+            .get(Address::zero())
+            .is_some());
+    }
+
+    #[test]
+    fn parses_payment_tokens() {
+        let text = r#"[{
+            "id": 3,
+            "symbol": "USDC",
+            "address": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            "image_url": "https://storage.opensea.io/files/749015f009a66abcb3bbb3502ae2f1ce.svg",
+            "name": "USD Coin",
+            "decimals": 6,
+            "eth_price": "0.000310000000000000",
+            "usd_price": "1.000000000000000000"
+        }]"#;
+        let tokens: Vec<PaymentToken> = serde_json::from_str(text).unwrap();
+        assert_eq!(
+            tokens,
+            vec![PaymentTokens::default()
+                .get(tokens[0].address)
+                .unwrap()
+                .clone()]
+        );
+    }
+}
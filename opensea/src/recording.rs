@@ -0,0 +1,353 @@
+//! Recording of a snipe run for post-mortems: the API responses, the events (e.g. the
+//! constructed txs and the bundle simulations) and the results of the run are appended
+//! to a JSON lines file along with their timestamps, see [`RunRecorder`]. The decisions
+//! of the run can then be re-run against the recorded responses, see [`Recording::replay`].
+use async_trait::async_trait;
+use ethers::types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+use crate::{
+    api::{
+        check_status, find_order, parse_assets, parse_orders_lenient, AssetsRequest, OrderRequest,
+    },
+    snipe::{SnipePlan, SnipeTarget},
+    types::{CollectionAsset, MinimalOrder, Order},
+    validate_price, BuyArgs, ClientError, Event, ListingFilter, OpenSeaApi, OpenSeaApiError,
+    OrderProvider,
+};
+
+/// The file of the run, in the directory of the recording
+pub const RUN_FILE: &str = "run.jsonl";
+
+/// What the listings of a run were selected by
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedPlan {
+    pub taker: Address,
+    pub recipient: Address,
+    pub nft: Address,
+    pub targets: Vec<SnipeTarget>,
+    pub max_price: Option<U256>,
+    pub filter: ListingFilter,
+}
+
+/// An entry of the recording
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recorded {
+    /// The plan of the run, recorded before it starts
+    Plan(RecordedPlan),
+    /// A response of the API, successful or not
+    ApiResponse {
+        endpoint: String,
+        status: u16,
+        body: String,
+    },
+    /// An event of the library
+    Event { event: Event },
+    /// A result of the run, e.g. the receipts, as output by the caller
+    Record { record: serde_json::Value },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEntry {
+    /// The unix timestamp of the entry, in milliseconds
+    pub timestamp_ms: u64,
+    pub entry: Recorded,
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("Could not access the recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid recording: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("The recording has no plan")]
+    MissingPlan,
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+/// Appends the entries of a run to the [`RUN_FILE`] of a directory. Cloning it shares
+/// the file.
+#[derive(Clone, Debug)]
+pub struct RunRecorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl RunRecorder {
+    /// Creates the directory if needed, appending to its run file if there is one
+    pub fn create(dir: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.as_ref().join(RUN_FILE))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Appends the entry, timestamped now. A failure to write does not stop the run.
+    pub fn record(&self, entry: Recorded) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default();
+        let entry = RecordedEntry {
+            timestamp_ms,
+            entry,
+        };
+        if let Ok(mut line) = serde_json::to_string(&entry) {
+            line.push('\n');
+            let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    /// Records each response of the `api`
+    pub fn record_api(&self, api: OpenSeaApi) -> OpenSeaApi {
+        let recorder = self.clone();
+        api.with_response_observer(move |res| {
+            recorder.record(Recorded::ApiResponse {
+                endpoint: res.endpoint.to_owned(),
+                status: res.status,
+                body: res.body.to_owned(),
+            })
+        })
+    }
+}
+
+/// A listing of the replayed run, and why it would not be bought
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReplayedOrder {
+    pub nft: Address,
+    pub token_id: U256,
+    pub order_hash: H256,
+    pub price: U256,
+    /// `None` if it would be bought
+    pub rejected: Option<String>,
+}
+
+/// The entries of a recorded run
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub entries: Vec<RecordedEntry>,
+}
+
+impl Recording {
+    /// Loads the run file of the directory
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let file = File::open(dir.as_ref().join(RUN_FILE))?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// The plan of the run, along with when it started
+    pub fn plan(&self) -> Option<(u64, &RecordedPlan)> {
+        self.entries.iter().find_map(|entry| match entry.entry {
+            Recorded::Plan(ref plan) => Some((entry.timestamp_ms, plan)),
+            _ => None,
+        })
+    }
+
+    /// Serves the recorded API responses, in the order they were received
+    pub fn provider(&self) -> ReplayProvider {
+        ReplayProvider::new(&self.entries)
+    }
+
+    /// Re-runs the selection of the listings against the recorded responses, as of the
+    /// start of the run. The on-chain checks of the run (e.g. the on-chain price or the
+    /// balances of the makers) are not replayed, see the recorded events for those.
+    pub async fn replay(&self) -> Result<Vec<ReplayedOrder>, RecordingError> {
+        let (timestamp_ms, recorded) = self.plan().ok_or(RecordingError::MissingPlan)?;
+        let now = timestamp_ms / 1000;
+        let mut plan = SnipePlan::new(recorded.nft).targets(recorded.targets.clone());
+        if let Some(max_price) = recorded.max_price {
+            plan = plan.max_price(max_price);
+        }
+        let listings = plan
+            .listings_at(&self.provider(), recorded.filter, now)
+            .await?;
+
+        let mut replayed = Vec::new();
+        let mut spent = U256::zero();
+        for (target, orders) in plan.target_listings(&listings) {
+            let args = BuyArgs {
+                taker: recorded.taker,
+                recipient: recorded.recipient,
+                token: plan.collection(target),
+                token_id: target.token_id,
+                // as the run does, a bit before the latest block
+                timestamp: Some(now.saturating_sub(100)),
+                quantity: None,
+                max_price: target.max_price.or(recorded.max_price),
+                total_budget: None,
+            };
+            for order in orders {
+                let rejected = match order.match_sell(args.clone()) {
+                    Ok(_) => validate_price(&args, order.current_price, spent).err(),
+                    Err(err) => Some(ClientError::from(err)),
+                };
+                if rejected.is_none() {
+                    spent += order.current_price;
+                }
+                replayed.push(ReplayedOrder {
+                    nft: args.token,
+                    token_id: target.token_id,
+                    order_hash: order.order_hash,
+                    price: order.current_price,
+                    rejected: rejected.map(|err| err.to_string()),
+                });
+            }
+        }
+        Ok(replayed)
+    }
+}
+
+/// Order provider serving the recorded responses of each endpoint in turn. The
+/// throttled responses are skipped, as the request was retried.
+#[derive(Debug)]
+pub struct ReplayProvider {
+    responses: Mutex<HashMap<String, VecDeque<(u16, String)>>>,
+}
+
+impl ReplayProvider {
+    pub fn new(entries: &[RecordedEntry]) -> Self {
+        let mut responses: HashMap<_, VecDeque<_>> = HashMap::new();
+        for entry in entries {
+            if let Recorded::ApiResponse {
+                ref endpoint,
+                status,
+                ref body,
+            } = entry.entry
+            {
+                if status != 429 {
+                    responses
+                        .entry(endpoint.clone())
+                        .or_default()
+                        .push_back((status, body.clone()));
+                }
+            }
+        }
+        Self {
+            responses: Mutex::new(responses),
+        }
+    }
+
+    /// The body of the next recorded response of the endpoint
+    fn next(&self, endpoint: &str) -> Result<String, OpenSeaApiError> {
+        let (status, body) = self
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(endpoint)
+            .and_then(VecDeque::pop_front)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("no recorded {} response left", endpoint),
+                )
+            })?;
+        let status = reqwest::StatusCode::from_u16(status)
+            .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+        check_status(status, body)
+    }
+}
+
+#[async_trait]
+impl OrderProvider for ReplayProvider {
+    async fn get_orders(&self, _: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+        parse_orders_lenient(&self.next("orders")?, false)
+    }
+
+    async fn get_order_by_hash(&self, order_hash: H256) -> Result<Order, OpenSeaApiError> {
+        find_order(
+            parse_orders_lenient(&self.next("orders")?, false)?,
+            order_hash,
+        )
+    }
+
+    async fn post_order(&self, _: MinimalOrder) -> Result<(), OpenSeaApiError> {
+        Ok(())
+    }
+
+    async fn get_assets(&self, _: AssetsRequest) -> Result<Vec<CollectionAsset>, OpenSeaApiError> {
+        parse_assets(&self.next("assets")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_the_recorded_run() {
+        let dir = std::env::temp_dir().join(format!("opensea-recording-{}", std::process::id()));
+        let order = crate::testing::order();
+        let nft = order.asset_metadata().unwrap().asset.address;
+        let plan = RecordedPlan {
+            taker: Address::random(),
+            recipient: Address::random(),
+            nft,
+            targets: vec![SnipeTarget::new(87.into(), 1)],
+            max_price: Some(order.current_price),
+            filter: ListingFilter::default(),
+        };
+        let body = format!(
+            r#"{{"count": 1, "orders": [{}]}}"#,
+            crate::testing::ORDER_JSON
+        );
+
+        let recorder = RunRecorder::create(&dir).unwrap();
+        recorder.record(Recorded::Plan(plan.clone()));
+        recorder.record(Recorded::ApiResponse {
+            endpoint: "orders".to_owned(),
+            status: 429,
+            body: r#"{"detail": "Request was throttled."}"#.to_owned(),
+        });
+        recorder.record(Recorded::ApiResponse {
+            endpoint: "orders".to_owned(),
+            status: 200,
+            body,
+        });
+        let mut recording = Recording::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(recording.entries.len(), 3);
+        assert_eq!(recording.plan().unwrap().1, &plan);
+
+        let replayed = recording.replay().await.unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].order_hash, order.order_hash);
+        assert_eq!(replayed[0].rejected, None);
+
+        // the same responses, with a lower max price
+        if let Recorded::Plan(ref mut plan) = recording.entries[0].entry {
+            plan.max_price = Some(1.into());
+        }
+        let replayed = recording.replay().await.unwrap();
+        assert!(replayed[0].rejected.is_some());
+
+        // no response left to serve
+        let provider = recording.provider();
+        assert!(provider.next("orders").is_ok());
+        assert!(matches!(
+            provider.next("orders"),
+            Err(OpenSeaApiError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+//! Relisting of the bought tokens at a markup once their purchase is confirmed, to flip
+//! them, see [`Client::execute_buys_and_relist`]
+use ethers::{providers::Middleware, signers::Signer, types::U256};
+
+use crate::{
+    constants::Deployment,
+    report::PurchaseStatus,
+    types::{MinimalOrder, Order, OrderMatchError},
+    Client, ClientError, ListingArgs, ListingMode, OrderProvider, PreparedBuy, PurchaseReport,
+    TokenStandard,
+};
+
+/// How the bought tokens are relisted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelistConfig {
+    /// The listing price over the purchase price, in basis points, e.g. 12_000 to list
+    /// at 1.2x
+    pub markup_bps: u64,
+    /// How long the listings last, in seconds
+    pub duration: u64,
+    pub mode: ListingMode,
+}
+
+impl RelistConfig {
+    /// The listing of the `quantity` of `token_id` bought from the `order` for `price`
+    /// Wei, starting at `timestamp`, with the seller fee of the `order`, which covers
+    /// the fees of the collection. The listings are priced in ETH, so the orders priced
+    /// in another token than ETH or WETH are refused.
+    pub fn listing_args(
+        &self,
+        order: &Order,
+        token_id: U256,
+        quantity: U256,
+        price: U256,
+        timestamp: u64,
+    ) -> Result<ListingArgs, OrderMatchError> {
+        let metadata = order
+            .asset_metadata()
+            .ok_or(OrderMatchError::MissingMetadata(order.order_hash))?;
+        let standard = match metadata.resolved_schema() {
+            "ERC721" => TokenStandard::Erc721,
+            "ERC1155" => TokenStandard::Erc1155,
+            schema => return Err(OrderMatchError::UnsupportedSchema(schema.to_owned())),
+        };
+        let weth = Deployment::of_exchange(order.exchange).map(|deployment| deployment.weth);
+        if !order.payment_token.is_zero() && Some(order.payment_token) != weth {
+            return Err(OrderMatchError::UnsupportedPaymentToken {
+                order_hash: order.order_hash,
+                token: order.payment_token,
+            });
+        }
+        Ok(ListingArgs {
+            token: metadata.asset.address,
+            token_id,
+            standard,
+            quantity,
+            price: price * self.markup_bps / 10_000,
+            fee_bps: order.maker_relayer_fee.min(10_000.into()).as_u64(),
+            expiration_time: timestamp + self.duration,
+            timestamp: Some(timestamp),
+            static_call: None,
+        })
+    }
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Lists the `quantity` of `token_id` bought from the `order` for `price` Wei with
+    /// the client's signer, which must be the recipient of the tokens
+    pub async fn relist(
+        &self,
+        order: &Order,
+        token_id: U256,
+        quantity: U256,
+        price: U256,
+        config: &RelistConfig,
+    ) -> Result<MinimalOrder, ClientError> {
+        let signer = self.signer.as_ref().ok_or(ClientError::NoSigner)?;
+        let timestamp = chrono::offset::Local::now().timestamp() as u64;
+        let args = config.listing_args(order, token_id, quantity, price, timestamp)?;
+        self.create_listing(&args, signer, config.mode).await
+    }
+
+    /// Executes the buys as [`Client::execute_buys`], then relists the tokens of each
+    /// filled purchase at its price times the markup. The purchases already happened, so
+    /// the listings which fail are only logged. Returns the posted listings.
+    pub async fn execute_buys_and_relist(
+        &self,
+        buys: Vec<PreparedBuy<M>>,
+        config: &RelistConfig,
+    ) -> Result<(PurchaseReport, Vec<MinimalOrder>), ClientError> {
+        let maker = self.signer.as_ref().ok_or(ClientError::NoSigner)?.address();
+        let (calls, bought): (Vec<_>, Vec<_>) = buys
+            .into_iter()
+            .map(|buy| (buy.call, (buy.order, buy.token_id, buy.quantity)))
+            .unzip();
+        let report = self.execute_buys(calls).await?;
+
+        let mut listings = Vec::new();
+        for ((order, token_id, quantity), purchase) in bought.iter().zip(&report.purchases) {
+            let price = match purchase.status {
+                PurchaseStatus::Filled { price, .. } => price,
+                _ => continue,
+            };
+            match self
+                .relist(order, *token_id, *quantity, price, config)
+                .await
+            {
+                Ok(listing) => {
+                    say!(
+                        "[Token Id = {:?}] Relisted by {:?} for {} Wei",
+                        token_id,
+                        maker,
+                        listing.base_price
+                    );
+                    listings.push(listing);
+                }
+                Err(err) => say!("[Token Id = {:?}] Could not relist: {}", token_id, err),
+            }
+        }
+        Ok((report, listings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::{providers::Provider, signers::LocalWallet};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn relists_at_a_markup() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (provider, _) = Provider::mocked();
+        let mut client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let config = RelistConfig {
+            markup_bps: 12_000,
+            duration: 3600,
+            mode: ListingMode::Offline,
+        };
+
+        let args = config
+            .listing_args(&sell, asset.id, 1.into(), 1000.into(), 1_000_000)
+            .unwrap();
+        assert_eq!(args.token, asset.address);
+        assert_eq!(args.standard, TokenStandard::Erc1155);
+        assert_eq!(args.price, 1200.into());
+        assert_eq!(args.expiration_time, 1_003_600);
+        assert_eq!(args.fee_bps, 1250);
+
+        assert!(matches!(
+            client
+                .relist(&sell, asset.id, 1.into(), 1000.into(), &config)
+                .await,
+            Err(ClientError::NoSigner)
+        ));
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        client.signer = Some(wallet.clone());
+        let listing = client
+            .relist(&sell, asset.id, 1.into(), 1000.into(), &config)
+            .await
+            .unwrap();
+        let posted = client.api.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].hash(), listing.hash());
+        assert_eq!(listing.maker, wallet.address());
+        assert_eq!(listing.token_id(), Some(asset.id));
+        assert_eq!(listing.base_price, 1200.into());
+    }
+
+    #[test]
+    fn relists_only_the_purchases_priced_in_eth() {
+        let mut sell = crate::testing::order();
+        let id = sell.asset_metadata().unwrap().asset.id;
+        let config = RelistConfig {
+            markup_bps: 12_000,
+            duration: 3600,
+            mode: ListingMode::Offline,
+        };
+
+        // WETH is worth its amount of ETH
+        sell.payment_token = Deployment::of_exchange(sell.exchange).unwrap().weth;
+        let args = config
+            .listing_args(&sell, id, 1.into(), 1000.into(), 1_000_000)
+            .unwrap();
+        assert_eq!(args.price, 1200.into());
+
+        let usdc = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        sell.payment_token = usdc;
+        assert!(matches!(
+            config.listing_args(&sell, id, 1.into(), 1000.into(), 1_000_000),
+            Err(OrderMatchError::UnsupportedPaymentToken { token, .. }) if token == usdc
+        ));
+    }
+}
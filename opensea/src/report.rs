@@ -0,0 +1,269 @@
+//! Execution of buy calls and analysis of their receipts
+use ethers::{
+    abi::RawLog,
+    contract::{builders::ContractCall, EthEvent},
+    providers::Middleware,
+    types::{Address, TransactionReceipt, H256, U256, U64},
+};
+
+use crate::{contracts::OrdersMatchedFilter, events::Event, Client, ClientError, OrderProvider};
+
+/// The outcome of an executed buy call
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PurchaseStatus {
+    /// The orders were matched, as per the exchange's `OrdersMatched` event
+    Filled {
+        price: U256,
+        maker: Address,
+        taker: Address,
+    },
+    /// The tx was mined but reverted, with the decoded revert reason
+    Reverted(String),
+    /// The tx was dropped from the mempool before being mined
+    Dropped,
+    /// The tx could not be sent
+    NotSent(String),
+    /// The tx was sent but its receipt could not be fetched, so it may still be mined
+    Unknown(String),
+}
+
+/// A buy call and its outcome
+#[derive(Clone, Debug)]
+pub struct Purchase {
+    /// Unset if the tx was not sent
+    pub tx_hash: Option<H256>,
+    pub block: Option<U64>,
+    /// The gas used times the effective gas price
+    pub gas_cost: U256,
+    /// The ETH sent with the tx
+    pub value: U256,
+    pub status: PurchaseStatus,
+}
+
+impl Purchase {
+    pub fn is_filled(&self) -> bool {
+        matches!(self.status, PurchaseStatus::Filled { .. })
+    }
+}
+
+/// The outcomes of a batch of buy calls
+#[derive(Clone, Debug, Default)]
+pub struct PurchaseReport {
+    /// In the order of the calls
+    pub purchases: Vec<Purchase>,
+    /// The gas cost of all mined txs, including the reverted ones
+    pub gas_spent: U256,
+    /// The ETH sent with the filled purchases. Reverted txs do not transfer their value.
+    pub eth_paid: U256,
+}
+
+impl PurchaseReport {
+    pub fn filled(&self) -> impl Iterator<Item = &Purchase> {
+        self.purchases
+            .iter()
+            .filter(|purchase| purchase.is_filled())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &Purchase> {
+        self.purchases
+            .iter()
+            .filter(|purchase| !purchase.is_filled())
+    }
+
+    fn push(&mut self, purchase: Purchase) {
+        self.gas_spent += purchase.gas_cost;
+        if purchase.is_filled() {
+            self.eth_paid += purchase.value;
+        }
+        self.purchases.push(purchase);
+    }
+}
+
+/// Returns the `OrdersMatched` event emitted by the exchange in the receipt, if any
+pub fn decode_orders_matched(
+    exchange: Address,
+    receipt: &TransactionReceipt,
+) -> Option<OrdersMatchedFilter> {
+    receipt
+        .logs
+        .iter()
+        .filter(|log| log.address == exchange)
+        .find_map(|log| {
+            OrdersMatchedFilter::decode_log(&RawLog {
+                topics: log.topics.clone(),
+                data: log.data.to_vec(),
+            })
+            .ok()
+        })
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Sends the buy calls, waits for all their receipts and reports the outcome of each
+    /// one, along with the total gas spent and ETH paid. Calls which could not be sent,
+    /// reverted or whose receipt could not be fetched are reported rather than returned
+    /// as errors.
+    pub async fn execute_buys(
+        &self,
+        calls: Vec<ContractCall<M, ()>>,
+    ) -> Result<PurchaseReport, ClientError> {
+        // send all the calls first, so that they may land in the same block
+        let mut pending = Vec::with_capacity(calls.len());
+        for call in &calls {
+            let value = call.tx.value().cloned().unwrap_or_default();
+            pending.push((value, call.send().await));
+        }
+
+        let mut report = PurchaseReport::default();
+        for (value, tx) in pending {
+            let tx = match tx {
+                Ok(tx) => tx,
+                Err(err) => {
+                    report.push(Purchase {
+                        tx_hash: None,
+                        block: None,
+                        gas_cost: U256::zero(),
+                        value,
+                        status: PurchaseStatus::NotSent(err.to_string()),
+                    });
+                    continue;
+                }
+            };
+
+            let tx_hash = *tx;
+            let receipt = match tx.await {
+                Ok(Some(receipt)) => receipt,
+                Err(err) => {
+                    report.push(Purchase {
+                        tx_hash: Some(tx_hash),
+                        block: None,
+                        gas_cost: U256::zero(),
+                        value,
+                        status: PurchaseStatus::Unknown(err.to_string()),
+                    });
+                    continue;
+                }
+                Ok(None) => {
+                    report.push(Purchase {
+                        tx_hash: Some(tx_hash),
+                        block: None,
+                        gas_cost: U256::zero(),
+                        value,
+                        status: PurchaseStatus::Dropped,
+                    });
+                    continue;
+                }
+            };
+
+            let status = match self.inspect_receipt(&receipt).await {
+                Err(err) => PurchaseStatus::Reverted(err.to_string()),
+                Ok(()) => match decode_orders_matched(self.contracts.address(), &receipt) {
+                    Some(matched) => PurchaseStatus::Filled {
+                        price: matched.price,
+                        maker: matched.maker,
+                        taker: matched.taker,
+                    },
+                    None => PurchaseStatus::Reverted("no OrdersMatched event".to_owned()),
+                },
+            };
+            if let PurchaseStatus::Filled { .. } = status {
+                // the purchase already happened, a veto is meaningless
+                let _ = self
+                    .emit(Event::FillConfirmed {
+                        tx_hash,
+                        block: receipt.block_number,
+                    })
+                    .await;
+            }
+
+            let gas_cost = receipt.gas_used.unwrap_or_default()
+                * receipt.effective_gas_price.unwrap_or_default();
+            report.push(Purchase {
+                tx_hash: Some(tx_hash),
+                block: receipt.block_number,
+                gas_cost,
+                value,
+                status,
+            });
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{abi::Token, types::Log};
+
+    fn orders_matched_log(exchange: Address, maker: Address, taker: Address, price: U256) -> Log {
+        Log {
+            address: exchange,
+            topics: vec![
+                OrdersMatchedFilter::signature(),
+                maker.into(),
+                taker.into(),
+                H256::zero(),
+            ],
+            data: ethers::abi::encode(&[
+                Token::FixedBytes(vec![1; 32]),
+                Token::FixedBytes(vec![2; 32]),
+                Token::Uint(price),
+            ])
+            .into(),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn decodes_orders_matched() {
+        let exchange = Address::random();
+        let (maker, taker) = (Address::random(), Address::random());
+        let mut receipt = TransactionReceipt {
+            logs: vec![orders_matched_log(exchange, maker, taker, 100.into())],
+            ..Default::default()
+        };
+
+        let matched = decode_orders_matched(exchange, &receipt).unwrap();
+        assert_eq!(matched.maker, maker);
+        assert_eq!(matched.taker, taker);
+        assert_eq!(matched.price, 100.into());
+
+        // events of other contracts are ignored
+        receipt.logs[0].address = Address::random();
+        assert!(decode_orders_matched(exchange, &receipt).is_none());
+    }
+
+    #[test]
+    fn totals_filled_purchases() {
+        let purchase = |gas_cost: u64, status| Purchase {
+            tx_hash: Some(H256::random()),
+            block: Some(1.into()),
+            gas_cost: gas_cost.into(),
+            value: 1000.into(),
+            status,
+        };
+        let mut report = PurchaseReport::default();
+        report.push(purchase(
+            10,
+            PurchaseStatus::Filled {
+                price: 1000.into(),
+                maker: Address::random(),
+                taker: Address::random(),
+            },
+        ));
+        report.push(purchase(5, PurchaseStatus::Reverted("sold".to_owned())));
+        report.push(purchase(0, PurchaseStatus::Unknown("timed out".to_owned())));
+
+        assert_eq!(report.gas_spent, 15.into());
+        assert_eq!(report.eth_paid, 1000.into());
+        assert_eq!(report.filled().count(), 1);
+        assert_eq!(report.failed().count(), 2);
+    }
+}
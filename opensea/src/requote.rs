@@ -0,0 +1,141 @@
+//! Re-quoting of a listing right before its buy call is signed. The `current_price` of a
+//! Dutch auction keeps moving after the listing was fetched, and the listing may have
+//! been sold or cancelled in the meantime.
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{H256, U256},
+};
+
+use crate::{
+    api::OpenSeaApiError, types::MinimalOrder, BuyArgs, Client, ClientError, OrderProvider,
+    PreparedBuy,
+};
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Re-fetches the listing by the hash of `sell` and compares its current price with
+    /// the quoted one. Returns `None` if the price did not move, and
+    /// the rebuilt buy call if it moved by at most `max_change_bps` basis points of the
+    /// quoted price. Errors with `ClientError::PriceMoved` if it moved by more, and with
+    /// `ClientError::ListingUnavailable` if the listing is gone or can no longer be filled.
+    pub async fn requote(
+        &self,
+        sell: &MinimalOrder,
+        args: &BuyArgs,
+        max_change_bps: u64,
+    ) -> Result<Option<ContractCall<M, ()>>, ClientError> {
+        Ok(self
+            .requote_order(sell.hash(), sell.current_price, args, max_change_bps)
+            .await?
+            .map(|buy| buy.call))
+    }
+
+    /// Same as [`Client::requote`] for the listing with the `order_hash` quoted at
+    /// `quoted`, returning the rebuilt buy along with the listing at its current price
+    pub(crate) async fn requote_order(
+        &self,
+        order_hash: H256,
+        quoted: U256,
+        args: &BuyArgs,
+        max_change_bps: u64,
+    ) -> Result<Option<PreparedBuy<M>>, ClientError> {
+        let order = match self.api.get_order_by_hash(order_hash).await {
+            Ok(order) => order,
+            Err(OpenSeaApiError::OrderHashNotFound(_)) => {
+                return Err(ClientError::ListingUnavailable(order_hash))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let current = order.current_price;
+        if quoted == current {
+            return Ok(None);
+        }
+        let change = if current > quoted {
+            current - quoted
+        } else {
+            quoted - current
+        };
+        if change * 10_000 > quoted * max_change_bps {
+            return Err(ClientError::PriceMoved {
+                order_hash,
+                quoted,
+                current,
+            });
+        }
+        say!(
+            "[Token Id = {:?}] Price of {:?} moved from {:?} to {:?}, rebuilding the buy",
+            args.token_id,
+            order_hash,
+            quoted,
+            current
+        );
+
+        let chain_id = self.chain_id().await?;
+        let mut spent = U256::zero();
+        self.buy_orders(args, vec![order], chain_id, &mut spent)
+            .await?
+            .pop()
+            .map(Some)
+            .ok_or(ClientError::ListingUnavailable(order_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::{
+        providers::Provider,
+        types::{Address, U64},
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn rebuilds_calls_when_the_price_moved() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let quoted = crate::testing::order();
+        // the Dutch auction declined by 1%
+        let mut order = quoted.clone();
+        order.current_price = quoted.current_price * 99 / 100;
+        let mut client = Client::with_api(
+            Arc::new(provider),
+            MockOrderProvider::new(vec![order.clone()]),
+        );
+        client.check_fulfillable = false;
+
+        let asset = quoted.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let sell = MinimalOrder::from(quoted.clone());
+
+        assert!(matches!(
+            client.requote(&sell, &args, 50).await,
+            Err(ClientError::PriceMoved { current, .. }) if current == order.current_price
+        ));
+        let call = client.requote(&sell, &args, 200).await.unwrap().unwrap();
+        assert_eq!(call.tx.value(), Some(&order.current_price));
+
+        // unchanged prices keep the call
+        let fresh = MinimalOrder::from(order);
+        assert!(client.requote(&fresh, &args, 0).await.unwrap().is_none());
+
+        // sold or cancelled
+        let mut gone = sell.clone();
+        gone.salt += 1.into();
+        assert!(matches!(
+            client.requote(&gone, &args, 200).await,
+            Err(ClientError::ListingUnavailable(hash)) if hash == gone.hash()
+        ));
+    }
+}
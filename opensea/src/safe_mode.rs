@@ -0,0 +1,113 @@
+//! Allowlists and denylists of the collections and makers the client buys from, see
+//! [`Client::safe_mode`](crate::Client::safe_mode). They are enforced on every listing
+//! about to be bought, so that a tampered ids file or malicious API data cannot make the
+//! client buy from unexpected contracts.
+use ethers::types::Address;
+use std::collections::HashSet;
+use thiserror::Error;
+
+use crate::types::{Metadata, Order};
+
+/// The collections and makers which may be bought from. Everything is allowed by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SafeMode {
+    /// If set, only these collections are bought
+    pub allowed_collections: Option<HashSet<Address>>,
+    pub denied_collections: HashSet<Address>,
+    /// If set, only the listings of these makers are bought
+    pub allowed_makers: Option<HashSet<Address>>,
+    pub denied_makers: HashSet<Address>,
+}
+
+/// Why a listing is not bought in safe mode
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum SafeModeViolation {
+    #[error("the collection {0:?} is not allowlisted")]
+    CollectionNotAllowed(Address),
+    #[error("the collection {0:?} is denylisted")]
+    CollectionDenied(Address),
+    #[error("the maker {0:?} is not allowlisted")]
+    MakerNotAllowed(Address),
+    #[error("the maker {0:?} is denylisted")]
+    MakerDenied(Address),
+}
+
+impl SafeMode {
+    pub fn check_collection(&self, collection: Address) -> Result<(), SafeModeViolation> {
+        if self.denied_collections.contains(&collection) {
+            return Err(SafeModeViolation::CollectionDenied(collection));
+        }
+        match self.allowed_collections {
+            Some(ref allowed) if !allowed.contains(&collection) => {
+                Err(SafeModeViolation::CollectionNotAllowed(collection))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_maker(&self, maker: Address) -> Result<(), SafeModeViolation> {
+        if self.denied_makers.contains(&maker) {
+            return Err(SafeModeViolation::MakerDenied(maker));
+        }
+        match self.allowed_makers {
+            Some(ref allowed) if !allowed.contains(&maker) => {
+                Err(SafeModeViolation::MakerNotAllowed(maker))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the listing bought as a token of the `collection`. The collection of its
+    /// asset is checked as well, as the API may return a listing of another collection
+    /// than the one queried.
+    pub fn check_order(&self, order: &Order, collection: Address) -> Result<(), SafeModeViolation> {
+        self.check_collection(collection)?;
+        for asset in order.metadata.iter().flat_map(Metadata::assets) {
+            self.check_collection(asset.address)?;
+        }
+        self.check_maker(order.maker.address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_the_lists() {
+        let order = crate::testing::order();
+        let collection = order.asset_metadata().unwrap().asset.address;
+        let maker = order.maker.address;
+        let other = Address::random();
+
+        let mut safe_mode = SafeMode::default();
+        assert_eq!(safe_mode.check_order(&order, collection), Ok(()));
+
+        safe_mode.allowed_collections = Some(vec![collection].into_iter().collect());
+        assert_eq!(safe_mode.check_order(&order, collection), Ok(()));
+        // the listing of another collection than the one queried
+        assert_eq!(
+            safe_mode.check_order(&order, other),
+            Err(SafeModeViolation::CollectionNotAllowed(other))
+        );
+
+        safe_mode.denied_makers.insert(maker);
+        assert_eq!(
+            safe_mode.check_order(&order, collection),
+            Err(SafeModeViolation::MakerDenied(maker))
+        );
+        safe_mode.denied_makers.clear();
+        safe_mode.allowed_makers = Some(vec![other].into_iter().collect());
+        assert_eq!(
+            safe_mode.check_order(&order, collection),
+            Err(SafeModeViolation::MakerNotAllowed(maker))
+        );
+
+        // the denylist wins over the allowlist
+        safe_mode.denied_collections.insert(collection);
+        assert_eq!(
+            safe_mode.check_collection(collection),
+            Err(SafeModeViolation::CollectionDenied(collection))
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! On-chain sale history and cancellations, decoded from the exchange's and the
+//! collection's logs
+use ethers::{
+    abi::RawLog,
+    contract::EthEvent,
+    providers::Middleware,
+    types::{Address, BlockNumber, Filter, Log, ValueOrArray, H256, U256, U64},
+};
+
+use crate::{
+    contracts::{OrderCancelledFilter, OrdersMatchedFilter, TransferFilter, TransferSingleFilter},
+    Client, ClientError,
+};
+
+/// A sale of a token of a collection on the exchange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sale {
+    pub token_id: U256,
+    /// In Wei of the order's payment token
+    pub price: U256,
+    pub buyer: Address,
+    pub seller: Address,
+    pub tx_hash: H256,
+    pub block_number: Option<U64>,
+}
+
+/// A transfer of a token of the collection, either an ERC721 `Transfer` or an
+/// ERC1155 `TransferSingle`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TokenTransfer {
+    tx_hash: H256,
+    from: Address,
+    to: Address,
+    token_id: U256,
+}
+
+fn raw_log(log: &Log) -> RawLog {
+    RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    }
+}
+
+fn decode_transfer(log: &Log) -> Option<TokenTransfer> {
+    let tx_hash = log.transaction_hash?;
+    let raw = raw_log(log);
+    if let Ok(transfer) = TransferFilter::decode_log(&raw) {
+        return Some(TokenTransfer {
+            tx_hash,
+            from: transfer.from,
+            to: transfer.to,
+            token_id: transfer.token_id,
+        });
+    }
+    TransferSingleFilter::decode_log(&raw)
+        .ok()
+        .map(|transfer| TokenTransfer {
+            tx_hash,
+            from: transfer.from,
+            to: transfer.to,
+            token_id: transfer.id,
+        })
+}
+
+/// Attributes a token to each match, using the transfer between its maker and taker in
+/// the same tx. Each transfer is attributed at most once, and matches without a
+/// transfer of the collection are skipped.
+fn join_sales(matches: Vec<(OrdersMatchedFilter, Log)>, logs: &[Log]) -> Vec<Sale> {
+    let mut transfers = logs.iter().filter_map(decode_transfer).collect::<Vec<_>>();
+
+    let mut sales = Vec::new();
+    for (matched, log) in matches {
+        let tx_hash = match log.transaction_hash {
+            Some(tx_hash) => tx_hash,
+            None => continue,
+        };
+        // the maker is the seller for listings, the buyer for accepted offers
+        let idx = transfers.iter().position(|transfer| {
+            transfer.tx_hash == tx_hash
+                && ((transfer.from == matched.maker && transfer.to == matched.taker)
+                    || (transfer.from == matched.taker && transfer.to == matched.maker))
+        });
+        if let Some(idx) = idx {
+            let transfer = transfers.remove(idx);
+            sales.push(Sale {
+                token_id: transfer.token_id,
+                price: matched.price,
+                buyer: transfer.to,
+                seller: transfer.from,
+                tx_hash,
+                block_number: log.block_number,
+            });
+        }
+    }
+    sales
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Returns the sales of the collection's tokens on the exchange within the block
+    /// range, decoded from the exchange's `OrdersMatched` logs joined with the
+    /// collection's transfers in the same txs
+    pub async fn recent_sales(
+        &self,
+        collection: Address,
+        from_block: impl Into<BlockNumber>,
+        to_block: impl Into<BlockNumber>,
+    ) -> Result<Vec<Sale>, ClientError> {
+        let (from_block, to_block) = (from_block.into(), to_block.into());
+
+        let filter = Filter::new()
+            .address(ValueOrArray::Value(self.contracts.address()))
+            .topic0(OrdersMatchedFilter::signature())
+            .from_block(from_block)
+            .to_block(to_block);
+        let matches = self
+            .provider
+            .get_logs(&filter)
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?
+            .into_iter()
+            .filter_map(|log| {
+                let matched = OrdersMatchedFilter::decode_log(&raw_log(&log)).ok()?;
+                Some((matched, log))
+            })
+            .collect::<Vec<_>>();
+
+        let filter = Filter::new()
+            .address(ValueOrArray::Value(collection))
+            .topic0(ValueOrArray::Array(vec![
+                TransferFilter::signature(),
+                TransferSingleFilter::signature(),
+            ]))
+            .from_block(from_block)
+            .to_block(to_block);
+        let transfers = self
+            .provider
+            .get_logs(&filter)
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+
+        Ok(join_sales(matches, &transfers))
+    }
+
+    /// Returns the hashes of the orders cancelled on-chain within the block range
+    pub async fn cancelled_orders(
+        &self,
+        from_block: impl Into<BlockNumber>,
+        to_block: impl Into<BlockNumber>,
+    ) -> Result<Vec<H256>, ClientError> {
+        let filter = Filter::new()
+            .address(ValueOrArray::Value(self.contracts.address()))
+            .topic0(OrderCancelledFilter::signature())
+            .from_block(from_block)
+            .to_block(to_block);
+        Ok(self
+            .provider
+            .get_logs(&filter)
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?
+            .iter()
+            .filter_map(|log| OrderCancelledFilter::decode_log(&raw_log(log)).ok())
+            .map(|cancelled| H256::from(cancelled.hash))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(tx_hash: H256, topics: Vec<H256>, data: Vec<u8>) -> Log {
+        Log {
+            address: Address::zero(),
+            topics,
+            data: data.into(),
+            block_hash: None,
+            block_number: Some(1.into()),
+            transaction_hash: Some(tx_hash),
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    fn u256_topic(value: u64) -> H256 {
+        let mut topic = [0; 32];
+        U256::from(value).to_big_endian(&mut topic);
+        topic.into()
+    }
+
+    #[test]
+    fn attributes_transferred_tokens_to_matches() {
+        let (seller, buyer) = (Address::random(), Address::random());
+        let tx_hash = H256::random();
+        let matched = OrdersMatchedFilter {
+            buy_hash: [0; 32],
+            sell_hash: [0; 32],
+            maker: seller,
+            taker: buyer,
+            price: 100.into(),
+            metadata: [0; 32],
+        };
+        let logs = vec![
+            // a transfer of another tx
+            log(
+                H256::random(),
+                vec![
+                    TransferFilter::signature(),
+                    seller.into(),
+                    buyer.into(),
+                    u256_topic(1),
+                ],
+                vec![],
+            ),
+            log(
+                tx_hash,
+                vec![
+                    TransferFilter::signature(),
+                    seller.into(),
+                    buyer.into(),
+                    u256_topic(42),
+                ],
+                vec![],
+            ),
+        ];
+
+        let sales = join_sales(vec![(matched.clone(), logs[1].clone())], &logs);
+        assert_eq!(
+            sales,
+            vec![Sale {
+                token_id: 42.into(),
+                price: 100.into(),
+                buyer,
+                seller,
+                tx_hash,
+                block_number: Some(1.into()),
+            }]
+        );
+
+        // ERC1155 transfers carry the id in the data
+        let data = ethers::abi::encode(&[
+            ethers::abi::Token::Uint(7.into()),
+            ethers::abi::Token::Uint(1.into()),
+        ]);
+        let logs = vec![log(
+            tx_hash,
+            vec![
+                TransferSingleFilter::signature(),
+                Address::random().into(),
+                seller.into(),
+                buyer.into(),
+            ],
+            data,
+        )];
+        let sales = join_sales(vec![(matched, logs[0].clone())], &logs);
+        assert_eq!(sales[0].token_id, 7.into());
+    }
+}
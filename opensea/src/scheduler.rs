@@ -0,0 +1,359 @@
+//! Scheduled buys: intents to buy a token as soon as it gets listed below a max price,
+//! persisted to disk so that they survive restarts. [`Scheduler::run`] consumes the
+//! events of a [`PriceMonitor`](crate::PriceMonitor) and snipes the matching listings.
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, U256},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    fees::FeeEstimator,
+    monitor::PriceEvent,
+    payment_tokens::PaymentTokens,
+    snipe::{SnipeOutcome, SnipePlan, SnipeTarget},
+    Client, ClientError, OrderProvider,
+};
+
+/// An intent to buy a token once it is listed at or below `max_price`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuyIntent {
+    pub id: u64,
+    pub nft: Address,
+    pub token_id: U256,
+    /// In Wei
+    pub max_price: U256,
+}
+
+impl BuyIntent {
+    /// The plan buying the cheapest listing of the token, up to the max price
+    pub fn plan(&self) -> SnipePlan {
+        SnipePlan::new(self.nft)
+            .targets(vec![SnipeTarget::new(self.token_id, 1)])
+            .max_price(self.max_price)
+    }
+
+    /// Whether the event is a listing of the token at or below the max price, once
+    /// normalized to 18 decimals by the `tokens`. Listings in unknown tokens never match.
+    pub fn matches(&self, nft: Address, event: &PriceEvent, tokens: &PaymentTokens) -> bool {
+        let listing = match event {
+            PriceEvent::NewListing(listing) | PriceEvent::PriceDrop { listing, .. } => listing,
+            PriceEvent::Delisted(_) | PriceEvent::Expired(_) => return false,
+        };
+        let price = match listing.normalized_price(tokens) {
+            Some(price) => price,
+            None => return false,
+        };
+        nft == self.nft && listing.token_id == self.token_id && price <= self.max_price
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error("Could not persist the intents: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid intents file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Queue {
+    next_id: u64,
+    intents: Vec<BuyIntent>,
+}
+
+/// The queue of the pending [`BuyIntent`]s, persisted as JSON at its path after each
+/// change
+#[derive(Debug)]
+pub struct Scheduler {
+    path: PathBuf,
+    queue: Queue,
+    tokens: PaymentTokens,
+}
+
+impl Scheduler {
+    /// Loads the intents persisted at `path`, if any
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SchedulerError> {
+        let path = path.as_ref().to_path_buf();
+        let queue = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Queue::default()
+        };
+        Ok(Self {
+            path,
+            queue,
+            tokens: PaymentTokens::default(),
+        })
+    }
+
+    /// Sets the tokens the prices of the listings are normalized by, see
+    /// [`BuyIntent::matches`]
+    pub fn payment_tokens(mut self, tokens: PaymentTokens) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// The pending intents, in the order they were registered
+    pub fn intents(&self) -> &[BuyIntent] {
+        &self.queue.intents
+    }
+
+    /// Registers an intent to buy the token once listed at or below `max_price`,
+    /// returning its id
+    pub fn register(
+        &mut self,
+        nft: Address,
+        token_id: U256,
+        max_price: U256,
+    ) -> Result<u64, SchedulerError> {
+        let id = self.queue.next_id;
+        self.queue.next_id += 1;
+        self.queue.intents.push(BuyIntent {
+            id,
+            nft,
+            token_id,
+            max_price,
+        });
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Removes the intent, returning it if it was pending
+    pub fn cancel(&mut self, id: u64) -> Result<Option<BuyIntent>, SchedulerError> {
+        let index = match self.queue.intents.iter().position(|intent| intent.id == id) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let intent = self.queue.intents.remove(index);
+        self.save()?;
+        Ok(Some(intent))
+    }
+
+    /// The pending intents matched by the event of the `nft` collection
+    pub fn matching(&self, nft: Address, event: &PriceEvent) -> Vec<BuyIntent> {
+        self.queue
+            .intents
+            .iter()
+            .filter(|intent| intent.matches(nft, event, &self.tokens))
+            .cloned()
+            .collect()
+    }
+
+    /// Consumes the price events of the `nft` collection until the channel closes,
+    /// sniping the listings matching an intent with the plan built by `plan` (e.g.
+    /// [`BuyIntent::plan`] with a bribe). The intents are removed once their purchase
+    /// is confirmed (its bundle included or its tx succeeded); the others are logged and
+    /// retried at the next matching event.
+    pub async fn run<M, P, S, F, B>(
+        &mut self,
+        client: &Client<M, P>,
+        signer: S,
+        nft: Address,
+        mut events: broadcast::Receiver<PriceEvent>,
+        plan: B,
+    ) -> Result<(), SchedulerError>
+    where
+        M: Middleware + 'static,
+        P: OrderProvider,
+        S: Signer + Clone + 'static,
+        F: FeeEstimator,
+        B: Fn(&BuyIntent) -> SnipePlan<F>,
+    {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    say!("Scheduler lagged behind, skipped {} price events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            for intent in self.matching(nft, &event) {
+                let outcome =
+                    match client.snipe(&plan(&intent), signer.clone()).await {
+                        Ok(outcome) => outcome,
+                        Err(err) => {
+                            say!(
+                            "Scheduled buy {} of token {} failed, retrying at its next listing: {}",
+                            intent.id, intent.token_id, err
+                        );
+                            continue;
+                        }
+                    };
+                match is_filled(client, &outcome).await {
+                    Ok(true) => {
+                        say!("Scheduled buy {} executed: {:?}", intent.id, outcome);
+                        self.cancel(intent.id)?;
+                    }
+                    Ok(false) => say!(
+                        "Scheduled buy {} of token {} was not filled, retrying at its next listing: {:?}",
+                        intent.id, intent.token_id, outcome
+                    ),
+                    Err(err) => say!(
+                        "Could not check the purchase of scheduled buy {}, retrying at its next listing: {}",
+                        intent.id, err
+                    ),
+                }
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), SchedulerError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // written to a temporary file first, so that a crash never leaves it truncated
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&self.queue)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Whether the snipe bought its target: a bundle of it was included, or one of its
+/// purchase txs succeeded. Dry runs and bids on English auctions buy nothing.
+async fn is_filled<M: Middleware, P>(
+    client: &Client<M, P>,
+    outcome: &SnipeOutcome,
+) -> Result<bool, ClientError> {
+    match outcome {
+        SnipeOutcome::Included(_) => Ok(true),
+        SnipeOutcome::IncludedBundles(bundles) => Ok(bundles.iter().any(Option::is_some)),
+        SnipeOutcome::Sent(sent) => {
+            // the mempool snipe returns once its purchases are mined
+            for sent in sent.iter().filter(|sent| sent.token_id.is_some()) {
+                let receipt = client
+                    .provider
+                    .get_transaction_receipt(sent.tx_hash)
+                    .await
+                    .map_err(|err| ClientError::Provider(err.to_string()))?;
+                if receipt.and_then(|receipt| receipt.status) == Some(1.into()) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        SnipeOutcome::DryRun(_) | SnipeOutcome::BidsPlaced(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{snapshot::OrderbookRow, MockOrderProvider, SentTx};
+    use ethers::{
+        providers::Provider,
+        types::{TransactionReceipt, H256},
+    };
+    use std::sync::Arc;
+
+    fn listing_in(token_id: u64, price: u64, payment_token: Address) -> PriceEvent {
+        PriceEvent::NewListing(OrderbookRow {
+            token_id: token_id.into(),
+            price: price.into(),
+            payment_token,
+            maker: Address::zero(),
+            expiration: 0,
+        })
+    }
+
+    fn listing(token_id: u64, price: u64) -> PriceEvent {
+        listing_in(token_id, price, Address::zero())
+    }
+
+    #[test]
+    fn persists_and_matches_intents() {
+        let dir = std::env::temp_dir().join(format!("opensea-scheduler-{}", std::process::id()));
+        let path = dir.join("intents.json");
+        let nft = Address::random();
+
+        let mut scheduler = Scheduler::open(&path).unwrap();
+        let first = scheduler.register(nft, 1.into(), 100.into()).unwrap();
+        let second = scheduler.register(nft, 2.into(), 100.into()).unwrap();
+        assert_eq!(
+            scheduler.cancel(second).unwrap().unwrap().token_id,
+            2.into()
+        );
+
+        let mut scheduler = Scheduler::open(&path).unwrap();
+        assert_eq!(scheduler.intents().len(), 1);
+        assert_eq!(scheduler.intents()[0].id, first);
+        assert_eq!(scheduler.matching(nft, &listing(1, 100)).len(), 1);
+        assert!(scheduler.matching(nft, &listing(1, 101)).is_empty());
+        assert!(scheduler.matching(nft, &listing(2, 50)).is_empty());
+        assert!(scheduler
+            .matching(Address::random(), &listing(1, 50))
+            .is_empty());
+
+        // the ids are not reused
+        assert_eq!(
+            scheduler.register(nft, 3.into(), 1.into()).unwrap(),
+            second + 1
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn matches_the_normalized_prices() {
+        let nft = Address::random();
+        let intent = BuyIntent {
+            id: 0,
+            nft,
+            token_id: 1.into(),
+            max_price: U256::exp10(18),
+        };
+        let tokens = PaymentTokens::default();
+        let usdc = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+            .parse()
+            .unwrap();
+
+        assert!(intent.matches(nft, &listing_in(1, 10u64.pow(18), weth), &tokens));
+        // 2 USDC are not 2e-12 ETH
+        assert!(!intent.matches(nft, &listing_in(1, 2_000_000, usdc), &tokens));
+        assert!(!intent.matches(nft, &listing_in(1, 1, Address::random()), &tokens));
+    }
+
+    #[tokio::test]
+    async fn cancels_only_the_filled_intents() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        let sent = SnipeOutcome::Sent(vec![
+            SentTx {
+                tx_hash: H256::random(),
+                token_id: None,
+            },
+            SentTx {
+                tx_hash: H256::random(),
+                token_id: Some(1.into()),
+            },
+        ]);
+
+        // the purchase reverted
+        mock.push(TransactionReceipt {
+            status: Some(0.into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!is_filled(&client, &sent).await.unwrap());
+        mock.push(TransactionReceipt {
+            status: Some(1.into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(is_filled(&client, &sent).await.unwrap());
+
+        let missed = SnipeOutcome::IncludedBundles(vec![None]);
+        assert!(!is_filled(&client, &missed).await.unwrap());
+        assert!(!is_filled(&client, &SnipeOutcome::BidsPlaced(Vec::new()))
+            .await
+            .unwrap());
+    }
+}
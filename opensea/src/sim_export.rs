@@ -0,0 +1,333 @@
+//! Exports of prepared buys to external simulators, to evaluate strategies without a
+//! funded key: a Tenderly simulation bundle, or a script of JSON-RPC requests replaying
+//! them on an anvil fork. Both fund the taker with `taker_balance` first, and for the
+//! listings priced in ERC20 tokens also override its token balance and its allowance to
+//! the `TokenTransferProxy` (see [`Erc20Slots`]).
+use ethers::{
+    abi::{self, Token},
+    types::{Address, Bytes, NameOrAddress, H256, U256},
+    utils::keccak256,
+};
+use serde_json::{json, Value};
+
+use crate::{constants, types::PreparedBuy};
+
+/// The storage slots of the `balanceOf` and `allowance` mappings of an ERC20 token,
+/// whose entries are overridden to fund the taker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Erc20Slots {
+    pub balances: u64,
+    pub allowances: u64,
+}
+
+impl Erc20Slots {
+    /// The layout of the known tokens (WETH, USDC and DAI), or else OpenZeppelin's
+    /// `ERC20`, whose mappings are its first two slots
+    pub fn of(token: Address) -> Self {
+        let (balances, allowances) =
+            if token == constants::MAINNET.weth || token == constants::RINKEBY.weth {
+                (3, 4)
+            } else if token == address("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48") {
+                // USDC's `FiatTokenV2`, behind a proxy
+                (9, 10)
+            } else if token == address("0x6b175474e89094c44da98b954eedeac495271d0f") {
+                (2, 3)
+            } else {
+                (0, 1)
+            };
+        Self {
+            balances,
+            allowances,
+        }
+    }
+
+    /// The storage key of the `owner`'s balance
+    pub fn balance(&self, owner: Address) -> H256 {
+        mapping_key(Token::Address(owner), self.balances.into())
+    }
+
+    /// The storage key of the allowance of the `owner` to the `spender`
+    pub fn allowance(&self, owner: Address, spender: Address) -> H256 {
+        let inner = mapping_key(Token::Address(owner), self.allowances.into());
+        mapping_key(
+            Token::Address(spender),
+            U256::from_big_endian(inner.as_bytes()),
+        )
+    }
+}
+
+fn address(address: &str) -> Address {
+    address.parse().unwrap()
+}
+
+/// The storage key of a Solidity mapping's entry
+fn mapping_key(key: Token, slot: U256) -> H256 {
+    keccak256(abi::encode(&[key, Token::Uint(slot)])).into()
+}
+
+/// The storage overrides funding the senders of the buys paid in ERC20 tokens: for
+/// each `(sender, token)`, the balance of their total price and an unlimited allowance
+/// to the `spender`. The values are 32 bytes words, as expected by both simulators.
+fn erc20_overrides<M>(buys: &[PreparedBuy<M>], spender: Address) -> Vec<(Address, H256, H256)> {
+    let mut totals: Vec<((Address, Address), U256)> = Vec::new();
+    for buy in buys {
+        let token = buy.order.payment_token;
+        if token.is_zero() {
+            continue;
+        }
+        let key = (UnsignedCall::from(buy).from, token);
+        match totals.iter_mut().find(|(other, _)| *other == key) {
+            Some((_, total)) => *total += buy.expected_price,
+            None => totals.push((key, buy.expected_price)),
+        }
+    }
+    let word = |value: U256| {
+        let mut word = H256::zero();
+        value.to_big_endian(word.as_bytes_mut());
+        word
+    };
+    totals
+        .into_iter()
+        .flat_map(|((sender, token), total)| {
+            let slots = Erc20Slots::of(token);
+            vec![
+                (token, slots.balance(sender), word(total)),
+                (token, slots.allowance(sender, spender), word(U256::MAX)),
+            ]
+        })
+        .collect()
+}
+
+/// The fields of a buy tx needed to replay it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsignedCall {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub gas: Option<U256>,
+}
+
+impl<M> From<&PreparedBuy<M>> for UnsignedCall {
+    fn from(buy: &PreparedBuy<M>) -> Self {
+        let tx = &buy.call.tx;
+        Self {
+            from: tx.from().copied().unwrap_or_default(),
+            to: match tx.to() {
+                Some(NameOrAddress::Address(address)) => *address,
+                _ => Address::zero(),
+            },
+            value: tx.value().copied().unwrap_or_default(),
+            data: tx.data().cloned().unwrap_or_default(),
+            gas: tx.gas().copied(),
+        }
+    }
+}
+
+/// The body of a request to Tenderly's `simulate-bundle` endpoint, simulating the buys
+/// one after the other on the chain, with the balance of each sender overridden, as
+/// well as their ERC20 balances and allowances to the `token_transfer_proxy`
+pub fn tenderly_bundle<M>(
+    buys: &[PreparedBuy<M>],
+    chain_id: u64,
+    taker_balance: U256,
+    token_transfer_proxy: Address,
+) -> Value {
+    let mut state_objects = serde_json::Map::new();
+    for (token, slot, value) in erc20_overrides(buys, token_transfer_proxy) {
+        let storage = state_objects
+            .entry(format!("{:?}", token))
+            .or_insert_with(|| json!({ "storage": {} }));
+        storage["storage"][format!("{:?}", slot)] = json!(value);
+    }
+    let simulations = buys
+        .iter()
+        .map(UnsignedCall::from)
+        .map(|call| {
+            let mut state_objects = state_objects.clone();
+            state_objects.insert(
+                format!("{:?}", call.from),
+                json!({ "balance": taker_balance.to_string() }),
+            );
+            let mut simulation = json!({
+                "network_id": chain_id.to_string(),
+                "from": call.from,
+                "to": call.to,
+                "input": call.data,
+                "value": call.value.to_string(),
+                "save": true,
+                "state_objects": state_objects,
+            });
+            if let Some(gas) = call.gas {
+                simulation["gas"] = json!(gas.as_u64());
+            }
+            simulation
+        })
+        .collect::<Vec<_>>();
+    json!({ "simulations": simulations })
+}
+
+/// The JSON-RPC requests replaying the buys on an anvil fork: `anvil_setBalance` of
+/// each sender and `anvil_setStorageAt` of their ERC20 balances and allowances to the
+/// `token_transfer_proxy`, then an `eth_sendUnsignedTransaction` per buy
+pub fn anvil_script<M>(
+    buys: &[PreparedBuy<M>],
+    taker_balance: U256,
+    token_transfer_proxy: Address,
+) -> Vec<Value> {
+    let calls = buys.iter().map(UnsignedCall::from).collect::<Vec<_>>();
+    let mut senders = Vec::new();
+    for call in &calls {
+        if !senders.contains(&call.from) {
+            senders.push(call.from);
+        }
+    }
+
+    let mut requests = senders
+        .into_iter()
+        .map(|sender| ("anvil_setBalance", json!([sender, taker_balance])))
+        .collect::<Vec<_>>();
+    for (token, slot, value) in erc20_overrides(buys, token_transfer_proxy) {
+        requests.push(("anvil_setStorageAt", json!([token, slot, value])));
+    }
+    for call in calls {
+        let mut tx = json!({
+            "from": call.from,
+            "to": call.to,
+            "value": call.value,
+            "data": call.data,
+        });
+        if let Some(gas) = call.gas {
+            tx["gas"] = json!(gas);
+        }
+        requests.push(("eth_sendUnsignedTransaction", json!([tx])));
+    }
+    requests
+        .into_iter()
+        .enumerate()
+        .map(|(id, (method, params))| {
+            json!({ "jsonrpc": "2.0", "id": id + 1, "method": method, "params": params })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuyArgs, Client, MockOrderProvider};
+    use ethers::{providers::Provider, types::U64};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn exports_buys() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let mut client = Client::with_api(Arc::new(provider), MockOrderProvider::new(vec![sell]));
+        client.check_fulfillable = false;
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let buys = client.buy(args, 1).await.unwrap();
+        let balance = U256::exp10(20);
+
+        let proxy = client.deployment.token_transfer_proxy;
+        let bundle = tenderly_bundle(&buys, 1, balance, proxy);
+        let simulation = &bundle["simulations"][0];
+        assert_eq!(simulation["network_id"], "1");
+        assert_eq!(simulation["from"], json!(taker));
+        assert_eq!(simulation["to"], json!(client.contracts.address()));
+        assert_eq!(simulation["value"], buys[0].expected_price.to_string());
+        assert_eq!(
+            simulation["state_objects"][format!("{:?}", taker)]["balance"],
+            balance.to_string()
+        );
+
+        let script = anvil_script(&buys, balance, proxy);
+        assert_eq!(script.len(), 2);
+        assert_eq!(script[0]["method"], "anvil_setBalance");
+        assert_eq!(script[0]["params"], json!([taker, balance]));
+        assert_eq!(script[1]["method"], "eth_sendUnsignedTransaction");
+        assert_eq!(
+            script[1]["params"][0]["data"],
+            json!(buys[0].call.calldata().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn funds_the_erc20_payments() {
+        let mut sell = crate::testing::order();
+        let weth = constants::MAINNET.weth;
+        sell.payment_token = weth;
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let mut client = Client::with_api(Arc::new(provider), MockOrderProvider::new(vec![sell]));
+        client.check_fulfillable = false;
+        let taker = Address::random();
+        let args = BuyArgs {
+            token_id: asset.id,
+            taker,
+            token: asset.address,
+            recipient: taker,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let buys = client.buy(args, 1).await.unwrap();
+        let price = buys[0].expected_price;
+        let proxy = client.deployment.token_transfer_proxy;
+        let slots = Erc20Slots::of(weth);
+        assert_eq!(
+            slots,
+            Erc20Slots {
+                balances: 3,
+                allowances: 4
+            }
+        );
+        let (balance_slot, allowance_slot) = (slots.balance(taker), slots.allowance(taker, proxy));
+        let word = |value: U256| {
+            let mut word = H256::zero();
+            value.to_big_endian(word.as_bytes_mut());
+            json!(word)
+        };
+
+        let bundle = tenderly_bundle(&buys, 1, U256::zero(), proxy);
+        let storage = &bundle["simulations"][0]["state_objects"][format!("{:?}", weth)]["storage"];
+        assert_eq!(storage[format!("{:?}", balance_slot)], word(price));
+        assert_eq!(storage[format!("{:?}", allowance_slot)], word(U256::MAX));
+
+        let script = anvil_script(&buys, U256::zero(), proxy);
+        assert_eq!(script.len(), 4);
+        assert_eq!(script[1]["method"], "anvil_setStorageAt");
+        assert_eq!(
+            script[1]["params"],
+            json!([weth, balance_slot, word(price)])
+        );
+        assert_eq!(
+            script[2]["params"],
+            json!([weth, allowance_slot, word(U256::MAX)])
+        );
+    }
+
+    #[test]
+    fn computes_the_mapping_slots() {
+        // `balanceOf` of the zero address in an OpenZeppelin ERC20
+        assert_eq!(
+            Erc20Slots::of(Address::random()).balance(Address::zero()),
+            "0xad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+                .parse()
+                .unwrap()
+        );
+    }
+}
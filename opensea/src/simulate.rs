@@ -0,0 +1,147 @@
+use ethers::{
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, TransactionReceipt,
+        TransactionRequest, U64,
+    },
+};
+
+use crate::{wyvern_error::WyvernError, Client, ClientError};
+
+/// Selector of Solidity's `Error(string)`, used by `require` / `revert` with a message
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Runs the provided call via `eth_call` at the latest block, using the call's
+    /// `from` and `value`. Returns `ClientError::Wyvern` with the decoded revert
+    /// reason if the call would fail on-chain.
+    pub async fn simulate(&self, call: &ContractCall<M, ()>) -> Result<(), ClientError> {
+        self.simulate_tx(&call.tx).await
+    }
+
+    /// Simulates each call in the batch, returning the outcome of each one in order,
+    /// so that the caller can tell which buys would succeed before spending any funds.
+    pub async fn simulate_batch(
+        &self,
+        calls: &[ContractCall<M, ()>],
+    ) -> Vec<Result<(), ClientError>> {
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            results.push(self.simulate(call).await);
+        }
+        results
+    }
+
+    /// Same as [`Client::simulate`], but for an already built transaction
+    pub async fn simulate_tx(&self, tx: &TypedTransaction) -> Result<(), ClientError> {
+        let provider = self.contracts.client();
+        match provider.call(tx, Some(BlockNumber::Latest.into())).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(revert_error(err.to_string())),
+        }
+    }
+
+    /// Inspects a mined receipt. If the transaction failed, it gets replayed via `eth_call`
+    /// on top of the state of its parent block in order to recover the revert reason.
+    pub async fn inspect_receipt(&self, receipt: &TransactionReceipt) -> Result<(), ClientError> {
+        if receipt.status != Some(U64::zero()) {
+            return Ok(());
+        }
+
+        let provider = self.contracts.client();
+        let tx = provider
+            .get_transaction(receipt.transaction_hash)
+            .await
+            .map_err(|err| ClientError::Simulation(err.to_string()))?
+            .ok_or_else(|| {
+                ClientError::Simulation(format!(
+                    "transaction {:?} not found",
+                    receipt.transaction_hash
+                ))
+            })?;
+        let block = tx
+            .block_number
+            .map(|num| num.saturating_sub(1.into()))
+            .map(BlockNumber::Number)
+            .unwrap_or(BlockNumber::Latest);
+
+        let replay: TypedTransaction = TransactionRequest::new()
+            .from(tx.from)
+            .to(tx.to.unwrap_or_default())
+            .value(tx.value)
+            .gas(tx.gas)
+            .data(tx.input)
+            .into();
+        match provider.call(&replay, Some(block.into())).await {
+            Err(err) => Err(revert_error(err.to_string())),
+            // the replay may succeed if the failure depended on the block's ordering
+            Ok(_) => Err(ClientError::Wyvern(WyvernError::Unknown)),
+        }
+    }
+}
+
+/// Converts the error message returned by the node to a `ClientError`, decoding
+/// the revert reason if the node returned any revert data
+fn revert_error(msg: String) -> ClientError {
+    if !msg.contains("revert") {
+        return ClientError::Simulation(msg);
+    }
+
+    let reason = revert_data(&msg)
+        .and_then(|data| decode_revert_reason(&data))
+        .or_else(|| {
+            // some nodes (e.g. hardhat) only return the reason inside the message
+            msg.split("reverted with reason string ")
+                .nth(1)
+                .map(|reason| reason.split('\'').nth(1).unwrap_or(reason).to_owned())
+        });
+    ClientError::Wyvern(reason.into())
+}
+
+/// Extracts the hex encoded revert data from a JSON-RPC error message
+fn revert_data(msg: &str) -> Option<Vec<u8>> {
+    let idx = msg.find("data:")?;
+    let data = &msg[idx..];
+    let start = data.find("0x")? + 2;
+    let hex_str: String = data[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect();
+    hex::decode(hex_str).ok()
+}
+
+/// Decodes the revert reason from an `Error(string)` encoded revert blob
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    if data.len() < 4 || data[..4] != ERROR_STRING_SELECTOR {
+        return None;
+    }
+    let tokens = ethers::abi::decode(&[ethers::abi::ParamType::String], &data[4..]).ok()?;
+    tokens.into_iter().next()?.into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `Error("Order not matchable")` abi-encoded
+    const REVERT: &str = "08c379a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000134f72646572206e6f74206d6174636861626c6500000000000000000000000000";
+
+    #[test]
+    fn decodes_revert_from_node_error() {
+        let msg = format!(
+            "(code: 3, message: execution reverted, data: Some(String(\"0x{}\")))",
+            REVERT
+        );
+        match revert_error(msg) {
+            ClientError::Wyvern(err) => assert_eq!(err, WyvernError::OrdersNotMatchable),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn non_revert_errors_are_not_decoded() {
+        let err = revert_error("connection refused".to_owned());
+        assert!(matches!(err, ClientError::Simulation(_)));
+    }
+}
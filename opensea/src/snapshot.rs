@@ -0,0 +1,306 @@
+//! Exports of the orderbook of a collection
+use ethers::types::{Address, U256};
+use serde::{Serialize, Serializer};
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path, time::Duration};
+
+use crate::{
+    api::{OpenSeaApiError, OrderRequest},
+    order_provider::OrderProvider,
+    types::{Order, OrderSide},
+    unique_orders,
+};
+
+/// The max number of orders the orderbook returns per query
+const MAX_ORDERS: u64 = 50;
+
+/// An order of the orderbook. Prices are in Wei of the payment token (the zero
+/// address is ETH).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OrderbookRow {
+    #[serde(serialize_with = "dec_str")]
+    pub token_id: U256,
+    #[serde(serialize_with = "dec_str")]
+    pub price: U256,
+    pub payment_token: Address,
+    pub maker: Address,
+    /// Unix timestamp, 0 if the order does not expire
+    pub expiration: u64,
+}
+
+impl OrderbookRow {
+    /// Whether the order expired at the `now` unix timestamp
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiration != 0 && self.expiration <= now
+    }
+
+    /// The time left until the order expires at the `now` unix timestamp, `None` if it
+    /// does not expire
+    pub fn time_to_expiry(&self, now: u64) -> Option<Duration> {
+        (self.expiration != 0).then(|| Duration::from_secs(self.expiration.saturating_sub(now)))
+    }
+}
+
+fn dec_str<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+/// Returns the orders of the given side of the collection's orderbook (all its pages),
+/// sorted by token id and price. Orders which are not for a single token (e.g. bundles)
+/// are skipped.
+pub async fn orderbook_snapshot<P: OrderProvider>(
+    api: &P,
+    collection: Address,
+    side: OrderSide,
+) -> Result<Vec<OrderbookRow>, OpenSeaApiError> {
+    let req = OrderRequest {
+        side,
+        token_ids: Vec::new(),
+        contract_address: collection,
+        limit: MAX_ORDERS,
+        offset: 0,
+    };
+    Ok(orderbook_rows(api.get_all_orders(req).await?))
+}
+
+/// The rows of the orders which are for a single token, sorted by token id and price
+pub(crate) fn orderbook_rows(orders: Vec<Order>) -> Vec<OrderbookRow> {
+    let mut rows = unique_orders(orders)
+        .into_iter()
+        .filter_map(|order| {
+            let token_id = order.asset_metadata()?.asset.id;
+            Some(OrderbookRow {
+                token_id,
+                price: order.current_price,
+                payment_token: order.payment_token,
+                maker: order.maker.address,
+                expiration: order.expiration_time,
+            })
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|row| (row.token_id, row.price));
+    rows
+}
+
+/// The listings of a collection at a point in time, to be diffed against a later
+/// snapshot, e.g. to detect undercuts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderBookSnapshot {
+    pub collection: Address,
+    /// Sorted by token id and price
+    pub listings: Vec<OrderbookRow>,
+}
+
+/// The changes between two [`OrderBookSnapshot`]s. A listing is identified by its token
+/// and maker, so that a relisting at another price is a price change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderBookDiff {
+    pub added: Vec<OrderbookRow>,
+    pub removed: Vec<OrderbookRow>,
+    pub price_changed: Vec<PriceChange>,
+}
+
+/// A listing whose price changed, see [`OrderBookDiff`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriceChange {
+    pub old_price: U256,
+    /// As of the newer snapshot
+    pub listing: OrderbookRow,
+}
+
+impl PriceChange {
+    pub fn is_drop(&self) -> bool {
+        self.listing.price < self.old_price
+    }
+}
+
+impl OrderBookSnapshot {
+    /// Fetches the current listings of the collection, see [`orderbook_snapshot`]
+    pub async fn fetch<P: OrderProvider>(
+        api: &P,
+        collection: Address,
+    ) -> Result<Self, OpenSeaApiError> {
+        Ok(Self {
+            collection,
+            listings: orderbook_snapshot(api, collection, OrderSide::Sell).await?,
+        })
+    }
+
+    /// The changes from this snapshot to the newer `other` one
+    pub fn diff(&self, other: &OrderBookSnapshot) -> OrderBookDiff {
+        let (old, new) = (self.by_listing(), other.by_listing());
+        let mut diff = OrderBookDiff::default();
+        for (key, listing) in &new {
+            match old.get(key) {
+                None => diff.added.push((*listing).clone()),
+                Some(prev) if prev.price != listing.price => diff.price_changed.push(PriceChange {
+                    old_price: prev.price,
+                    listing: (*listing).clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|(key, _)| !new.contains_key(key))
+            .map(|(_, listing)| (*listing).clone())
+            .collect();
+        diff
+    }
+
+    /// The cheapest listing of each maker of each token
+    fn by_listing(&self) -> BTreeMap<(U256, Address), &OrderbookRow> {
+        let mut listings = BTreeMap::new();
+        for row in &self.listings {
+            listings
+                .entry((row.token_id, row.maker))
+                .and_modify(|cheapest: &mut &OrderbookRow| {
+                    if row.price < cheapest.price {
+                        *cheapest = row;
+                    }
+                })
+                .or_insert(row);
+        }
+        listings
+    }
+}
+
+impl OrderBookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.price_changed.is_empty()
+    }
+}
+
+/// Writes the rows as CSV, with a header line
+pub fn write_csv<W: Write>(rows: &[OrderbookRow], mut writer: W) -> Result<(), OpenSeaApiError> {
+    writeln!(writer, "token_id,price,payment_token,maker,expiration")?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{:?},{:?},{}",
+            row.token_id, row.price, row.payment_token, row.maker, row.expiration
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the rows as a JSON array
+pub fn write_json<W: Write>(rows: &[OrderbookRow], writer: W) -> Result<(), OpenSeaApiError> {
+    serde_json::to_writer_pretty(writer, rows)?;
+    Ok(())
+}
+
+/// Writes the rows to a CSV file
+pub fn write_csv_file(
+    rows: &[OrderbookRow],
+    path: impl AsRef<Path>,
+) -> Result<(), OpenSeaApiError> {
+    write_csv(rows, File::create(path)?)
+}
+
+/// Writes the rows to a JSON file
+pub fn write_json_file(
+    rows: &[OrderbookRow],
+    path: impl AsRef<Path>,
+) -> Result<(), OpenSeaApiError> {
+    write_json(rows, File::create(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_provider::MockOrderProvider;
+    use ethers::types::H256;
+
+    #[tokio::test]
+    async fn exports_orderbook() {
+        let order = crate::testing::order();
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let api = MockOrderProvider::new(vec![order.clone()]);
+
+        let rows = orderbook_snapshot(&api, asset.address, OrderSide::Sell)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].token_id, asset.id);
+        assert_eq!(rows[0].price, order.current_price);
+        assert!(orderbook_snapshot(&api, asset.address, OrderSide::Buy)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut csv = Vec::new();
+        write_csv(&rows, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let line = csv.lines().nth(1).unwrap();
+        assert!(line.starts_with(&format!("{},{},", asset.id, order.current_price)));
+
+        let mut json = Vec::new();
+        write_json(&rows, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json[0]["price"], order.current_price.to_string());
+    }
+
+    #[tokio::test]
+    async fn pages_through_the_orderbook() {
+        let order = crate::testing::order();
+        let collection = order.asset_metadata().unwrap().asset.address;
+        // two full pages and a short one
+        let orders = (0..120u64)
+            .map(|id| {
+                let mut order = order.clone();
+                order.order_hash = H256::from_low_u64_be(id);
+                order.asset_metadata_mut().unwrap().asset.id = id.into();
+                order
+            })
+            .collect();
+        let api = MockOrderProvider::new(orders);
+
+        let rows = orderbook_snapshot(&api, collection, OrderSide::Sell)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 120);
+        assert_eq!(rows[119].token_id, 119.into());
+    }
+
+    #[test]
+    fn diffs_snapshots() {
+        let (alice, bob) = (Address::random(), Address::random());
+        let row = |token_id: u64, maker, price: u64| OrderbookRow {
+            token_id: token_id.into(),
+            price: price.into(),
+            payment_token: Address::zero(),
+            maker,
+            expiration: 0,
+        };
+        let snapshot = |listings| OrderBookSnapshot {
+            collection: Address::zero(),
+            listings,
+        };
+        let old = snapshot(vec![
+            row(1, alice, 100),
+            row(2, alice, 100),
+            row(3, bob, 100),
+        ]);
+        let new = snapshot(vec![
+            // undercut by another maker
+            row(1, bob, 90),
+            row(1, alice, 100),
+            row(2, alice, 80),
+            // the cheapest listing of a maker is kept
+            row(2, alice, 120),
+        ]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![row(1, bob, 90)]);
+        assert_eq!(diff.removed, vec![row(3, bob, 100)]);
+        assert_eq!(
+            diff.price_changed,
+            vec![PriceChange {
+                old_price: 100.into(),
+                listing: row(2, alice, 80),
+            }]
+        );
+        assert!(diff.price_changed[0].is_drop());
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,2534 @@
+//! Orchestration of purchases of several tokens at once: building the purchase, payment
+//! and bribe transactions with pre-allocated nonces, and submitting them either as a
+//! bundle to MEV relays or via the public mempool.
+use ethers::{
+    core::utils::keccak256,
+    middleware::SignerMiddleware,
+    providers::{Middleware, PendingTransaction},
+    signers::{LocalWallet, Signer},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockNumber, Bytes,
+        Eip1559TransactionRequest, TransactionReceipt, TransactionRequest, H256, U256, U64,
+    },
+};
+use ethers_flashbots::{BundleRequest, FlashbotsMiddleware, PendingBundleError, SimulatedBundle};
+use futures::{
+    future::{join_all, select, Either},
+    Future,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    abort::{AbortHandle, AbortReason, SnipeProgress, SnipeStage},
+    bribe::{BribeChecks, BribeStrategy, Unprofitable},
+    bundle_simulation::BundleSimulationReport,
+    cancellations::Cancellation,
+    cheapest_orders_by_token_at,
+    contracts::Briber,
+    deadline::Deadline,
+    escalator::Escalator,
+    fees::{BaseFeeProjection, FeeEstimator, Fees},
+    journal::{BuyJournal, JournalEntry, JournalError, JournalStatus},
+    metrics,
+    ownership::TokenStandard,
+    summary::{Confirmation, PlannedPurchase, SnipeSummary},
+    transfers::{awaited_tokens, purchased_tokens, TransferWatch},
+    types::{MinimalOrder, Order},
+    units::format_eth,
+    unix_timestamp, BuyArgs, Client, ClientError, Event, ListingFilter, OrderProvider,
+    PaymentPreview, SwapConfig,
+};
+
+/// A token to purchase, and how many of its cheapest listings to take
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnipeTarget {
+    pub token_id: U256,
+    pub quantity: usize,
+    /// The collection of the token, if not the plan's
+    pub nft: Option<Address>,
+    /// Overrides the plan's max price for the listings of this token
+    pub max_price: Option<U256>,
+}
+
+impl SnipeTarget {
+    pub fn new(token_id: U256, quantity: usize) -> Self {
+        Self {
+            token_id,
+            quantity,
+            nft: None,
+            max_price: None,
+        }
+    }
+
+    pub fn nft(mut self, nft: Address) -> Self {
+        self.nft = Some(nft);
+        self
+    }
+
+    pub fn max_price(mut self, max_price: U256) -> Self {
+        self.max_price = Some(max_price);
+        self
+    }
+}
+
+/// The cheapest listings of each token of each collection
+pub(crate) type Listings = HashMap<Address, HashMap<U256, Vec<Order>>>;
+
+/// How the block builder is paid for including the purchases
+#[derive(Clone, Debug)]
+pub enum Bribe {
+    /// Sent in an extra transaction to a deployed `briber.sol` contract, which only
+    /// pays `block.coinbase` if the NFTs were received. The ownership of the NFTs of
+    /// plans spanning several collections is verified via `verifyAndPay`.
+    Contract { address: Address, amount: U256 },
+    /// Same as `Contract`, but the contract only pays if all the `checks` hold instead
+    /// of verifying the ownership of the purchased NFTs
+    Checked {
+        address: Address,
+        amount: U256,
+        checks: BribeChecks,
+    },
+    /// Split evenly across the purchase transactions as their priority fee
+    PriorityFee(U256),
+    /// The priority fee of all the transactions is sized from the expected profit,
+    /// initially for their gas limits and, when sent as a single bundle, again for the
+    /// gas used in its simulation
+    Profit(BribeStrategy),
+}
+
+/// How the transactions are submitted
+#[derive(Clone, Debug)]
+pub enum SnipeStrategy {
+    /// As a bundle sent to all `relays` concurrently, targeting each of the next
+    /// `target_blocks` blocks until it is included. The first relay is also used
+    /// for simulating the bundle.
+    Bundle {
+        relays: Vec<Url>,
+        target_blocks: u64,
+        atomicity: Atomicity,
+    },
+    /// One by one via the public mempool, optionally skipping the purchases which
+    /// fail when simulated
+    Mempool { simulate: bool },
+}
+
+impl Default for SnipeStrategy {
+    fn default() -> Self {
+        SnipeStrategy::Mempool { simulate: false }
+    }
+}
+
+/// Whether the purchases sent as bundles stand or fall together. Split bundles are
+/// submitted one after the other, since they spend consecutive nonces of the signer,
+/// and the bundles which are not included are skipped. The payment swaps / approvals
+/// are then sent as a bundle of their own ahead of the purchases.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Atomicity {
+    /// A single bundle, invalidated by any reverting purchase
+    #[default]
+    AllOrNothing,
+    /// A bundle per purchase
+    BestEffort,
+    /// Bundles of up to `n` purchases
+    Chunked(usize),
+}
+
+impl Atomicity {
+    /// The number of purchases per bundle, out of `num`
+    fn chunk_size(self, num: usize) -> usize {
+        match self {
+            Atomicity::AllOrNothing => num.max(1),
+            Atomicity::BestEffort => 1,
+            Atomicity::Chunked(n) => n.max(1),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid atomicity {0}, expected `all-or-nothing`, `best-effort` or `chunked:<n>`")]
+pub struct InvalidAtomicity(String);
+
+impl FromStr for Atomicity {
+    type Err = InvalidAtomicity;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all-or-nothing" => Ok(Atomicity::AllOrNothing),
+            "best-effort" => Ok(Atomicity::BestEffort),
+            _ => s
+                .strip_prefix("chunked:")
+                .and_then(|n| n.parse().ok())
+                .filter(|n| *n > 0)
+                .map(Atomicity::Chunked)
+                .ok_or_else(|| InvalidAtomicity(s.to_owned())),
+        }
+    }
+}
+
+/// A purchase of several tokens of a collection, or of several collections, see
+/// [`Client::snipe`]
+#[derive(Clone, Debug)]
+pub struct SnipePlan<F = BaseFeeProjection> {
+    nft: Address,
+    erc1155: Option<bool>,
+    recipient: Option<Address>,
+    targets: Vec<SnipeTarget>,
+    budget: Option<U256>,
+    max_price: Option<U256>,
+    total_budget: Option<U256>,
+    bribe: Option<Bribe>,
+    strategy: SnipeStrategy,
+    swap: Option<SwapConfig>,
+    fees: F,
+    dry_run: bool,
+    confirm: Option<Confirmation>,
+    journal: Option<BuyJournal>,
+    watch_cancellations: bool,
+    deadline: Option<Deadline>,
+    escalator: Option<Escalator>,
+    transfers: Option<TransferWatch>,
+    auction_bids: Option<Duration>,
+    abort: Option<AbortHandle>,
+    requote: Option<u64>,
+}
+
+impl SnipePlan {
+    pub fn new(nft: Address) -> Self {
+        Self {
+            nft,
+            erc1155: None,
+            recipient: None,
+            targets: Vec::new(),
+            budget: None,
+            max_price: None,
+            total_budget: None,
+            bribe: None,
+            strategy: SnipeStrategy::default(),
+            swap: None,
+            fees: BaseFeeProjection,
+            dry_run: false,
+            confirm: None,
+            journal: None,
+            watch_cancellations: false,
+            deadline: None,
+            escalator: None,
+            transfers: None,
+            auction_bids: None,
+            abort: None,
+            requote: None,
+        }
+    }
+}
+
+impl<F> SnipePlan<F> {
+    /// Whether the plan's collection is an ERC1155 instead of an ERC721 one. If unset,
+    /// it is detected as for the collections of the targets, see [`Client::token_standard`].
+    /// Collections whose standard cannot be detected are assumed to be ERC721s.
+    pub fn erc1155(mut self, erc1155: bool) -> Self {
+        self.erc1155 = Some(erc1155);
+        self
+    }
+
+    /// The receiver of the NFTs. Defaults to the buyer.
+    pub fn recipient(mut self, recipient: Address) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
+    pub fn targets(mut self, targets: impl IntoIterator<Item = SnipeTarget>) -> Self {
+        self.targets.extend(targets);
+        self
+    }
+
+    /// The maximum Wei to spend across all transactions, bribe included
+    pub fn budget(mut self, budget: U256) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The max price (in Wei) to pay for each listing, see [`BuyArgs::max_price`]
+    pub fn max_price(mut self, max_price: U256) -> Self {
+        self.max_price = Some(max_price);
+        self
+    }
+
+    /// The max price (in Wei) to pay for all the listings of the targets, see
+    /// [`BuyArgs::total_budget`]
+    pub fn total_budget(mut self, total_budget: U256) -> Self {
+        self.total_budget = Some(total_budget);
+        self
+    }
+
+    pub fn bribe(mut self, bribe: Bribe) -> Self {
+        self.bribe = Some(bribe);
+        self
+    }
+
+    pub fn strategy(mut self, strategy: SnipeStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Swap ETH for the ERC20 payment tokens of the listings if the buyer lacks them
+    pub fn swap(mut self, cfg: SwapConfig) -> Self {
+        self.swap = Some(cfg);
+        self
+    }
+
+    /// The fee estimator of the transactions. Defaults to [`BaseFeeProjection`].
+    pub fn fee_estimator<G: FeeEstimator>(self, fees: G) -> SnipePlan<G> {
+        SnipePlan {
+            nft: self.nft,
+            erc1155: self.erc1155,
+            recipient: self.recipient,
+            targets: self.targets,
+            budget: self.budget,
+            total_budget: self.total_budget,
+            max_price: self.max_price,
+            bribe: self.bribe,
+            strategy: self.strategy,
+            swap: self.swap,
+            fees,
+            dry_run: self.dry_run,
+            confirm: self.confirm,
+            journal: self.journal,
+            watch_cancellations: self.watch_cancellations,
+            deadline: self.deadline,
+            escalator: self.escalator,
+            transfers: self.transfers,
+            auction_bids: self.auction_bids,
+            abort: self.abort,
+            requote: self.requote,
+        }
+    }
+
+    /// Build and sign the transactions without submitting them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Called with the summary of the transactions before they are submitted, which
+    /// are only submitted if its future resolves to true. Not called on dry runs. The
+    /// future is dropped if the snipe is aborted while it is pending, e.g. by a Ctrl-C
+    /// at the prompt.
+    pub fn confirm<C, Fut>(mut self, confirm: C) -> Self
+    where
+        C: Fn(&SnipeSummary) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.confirm = Some(Confirmation(Arc::new(move |summary| {
+            Box::pin(confirm(summary))
+        })));
+        self
+    }
+
+    /// Re-fetches the listings by hash right before the txs are sent (after the
+    /// confirmation), rebuilding the purchases of the listings whose price moved by at
+    /// most `max_change_bps` basis points, see [`Client::requote`]. The snipe errors
+    /// without sending anything if a listing moved by more or is gone. Not applied on dry
+    /// runs.
+    pub fn requote(mut self, max_change_bps: u64) -> Self {
+        self.requote = Some(max_change_bps);
+        self
+    }
+
+    /// Records the purchases sent via the mempool in the journal along with whether
+    /// they were mined, and skips the ones it records as mined, e.g. to resume a plan
+    /// whose process crashed halfway. The purchases whose status was not recorded are
+    /// looked up on-chain first, and the nonces of the signer re-synced from the chain.
+    pub fn resume(mut self, journal: BuyJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Watches the pending txs for cancellations of the listings being bought via
+    /// bundles, which sellers send to front-run the purchases. A bundle whose listing is
+    /// being cancelled is replaced by one without its purchase for the following blocks,
+    /// emitting [`Event::CancellationDetected`]. The bundles already sent cannot be
+    /// recalled, but they are not included once the cancellation lands. A bundle left
+    /// without purchases is not sent anymore, and for an all-or-nothing bundle this also
+    /// aborts the [`SnipePlan::abort_handle`]. Requires a provider supporting pending tx
+    /// filters.
+    pub fn watch_cancellations(mut self, watch: bool) -> Self {
+        self.watch_cancellations = watch;
+        self
+    }
+
+    /// Waits for the purchases sent via the mempool to be mined until the deadline,
+    /// replacing the ones still pending after it with 0-value self-transfers at a higher
+    /// gas price, emitting [`Event::TxReplaced`]. The snipe then only returns once the
+    /// txs are mined or replaced.
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Rebroadcasts the purchases sent via the mempool which are still pending with a
+    /// bumped gas price, see [`Escalator`]. The snipe then only returns once the txs are
+    /// mined, or cancelled after the [`SnipePlan::deadline`].
+    pub fn escalator(mut self, escalator: Escalator) -> Self {
+        self.escalator = Some(escalator);
+        self
+    }
+
+    /// Subscribes to the transfers of the tokens being bought to the recipient while the
+    /// txs are submitted, emitting [`Event::TransferReceived`] as soon as each purchase
+    /// lands. Once the txs are submitted, the snipe waits up to the watch's timeout for
+    /// the transfers of the tokens of the included bundles or of the txs sent via the
+    /// mempool, and not at all if the transfers could not be subscribed to.
+    pub fn watch_transfers(mut self, watch: TransferWatch) -> Self {
+        self.transfers = Some(watch);
+        self
+    }
+
+    /// When none of the targets has a listing which can be bought, bids at their max
+    /// price on their English auctions instead, the bids expiring after `expiry`. The
+    /// snipe then returns [`SnipeOutcome::BidsPlaced`]: nothing was bought. Only the
+    /// targets with a max price are bid on. Not applied on dry runs.
+    pub fn bid_on_english_auctions(mut self, expiry: Duration) -> Self {
+        self.auction_bids = Some(expiry);
+        self
+    }
+
+    /// Stops the snipe as soon as the handle is aborted, e.g. on a Ctrl-C, with
+    /// [`SnipeError::Aborted`] reporting how far it went. The txs already sent stay
+    /// pending.
+    pub fn abort_handle(mut self, abort: AbortHandle) -> Self {
+        self.abort = Some(abort);
+        self
+    }
+
+    fn progress(&self, stage: SnipeStage) {
+        if let Some(ref abort) = self.abort {
+            abort.stage(stage);
+        }
+    }
+
+    /// The order bought by each tx, see [`tx_orders`], if the cancellations are watched
+    fn watched_orders(
+        &self,
+        num_payment_txs: usize,
+        purchases: &[PlannedPurchase],
+    ) -> Vec<Option<H256>> {
+        if !self.watch_cancellations {
+            return Vec::new();
+        }
+        tx_orders(num_payment_txs, purchases)
+    }
+
+    /// The number of listings of the target left to buy
+    fn remaining(&self, target: &SnipeTarget) -> usize {
+        match self.journal {
+            Some(ref journal) => target
+                .quantity
+                .saturating_sub(journal.mined(self.collection(target), target.token_id)),
+            None => target.quantity,
+        }
+    }
+
+    pub(crate) fn snipe_targets(&self) -> &[SnipeTarget] {
+        &self.targets
+    }
+
+    pub(crate) fn recipient_or(&self, taker: Address) -> Address {
+        self.recipient.unwrap_or(taker)
+    }
+
+    /// The plan buying the `targets` instead, e.g. the ones lost to a reorg. The journal
+    /// is dropped, it would count their reorged purchases as sent.
+    pub(crate) fn retry(&self, targets: Vec<SnipeTarget>) -> Self
+    where
+        F: Clone,
+    {
+        Self {
+            targets,
+            journal: None,
+            ..self.clone()
+        }
+    }
+
+    fn token_ids(&self) -> Vec<U256> {
+        self.targets.iter().map(|target| target.token_id).collect()
+    }
+
+    pub(crate) fn collection(&self, target: &SnipeTarget) -> Address {
+        target.nft.unwrap_or(self.nft)
+    }
+
+    /// The collections of the targets, in order of first appearance
+    pub(crate) fn collections(&self) -> Vec<Address> {
+        let mut collections = Vec::new();
+        for target in &self.targets {
+            let nft = self.collection(target);
+            if !collections.contains(&nft) {
+                collections.push(nft);
+            }
+        }
+        collections
+    }
+
+    /// The listings to buy of each target, out of the cheapest listings of each token
+    pub(crate) fn target_listings<'a>(
+        &'a self,
+        listings: &'a Listings,
+    ) -> impl Iterator<Item = (&'a SnipeTarget, &'a [Order])> + 'a {
+        self.targets.iter().map(move |target| {
+            let orders = listings
+                .get(&self.collection(target))
+                .and_then(|listings| listings.get(&target.token_id))
+                .map(|orders| &orders[..orders.len().min(self.remaining(target))])
+                .unwrap_or_default();
+            (target, orders)
+        })
+    }
+
+    /// The cheapest listings of the targets fillable at the `now` unix timestamp,
+    /// querying the listings of all the targets of each collection at once
+    pub(crate) async fn listings_at<P: OrderProvider>(
+        &self,
+        api: &P,
+        filter: ListingFilter,
+        now: u64,
+    ) -> Result<Listings, ClientError> {
+        let mut listings = Listings::new();
+        for nft in self.collections() {
+            let targets = self
+                .targets
+                .iter()
+                .filter(|target| self.collection(target) == nft);
+            let ids: Vec<_> = targets.clone().map(|target| target.token_id).collect();
+            let quantity = targets
+                .map(|target| target.quantity)
+                .max()
+                .unwrap_or_default();
+            listings.insert(
+                nft,
+                cheapest_orders_by_token_at(api, nft, &ids, quantity, filter, now).await?,
+            );
+        }
+        Ok(listings)
+    }
+}
+
+/// The average time between two blocks, in seconds
+const BLOCK_TIME: u64 = 12;
+
+/// The time range in which listings can all be filled: after the latest of their
+/// listing times and before the earliest of their expiration times
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ListingWindow {
+    pub opens: u64,
+    pub closes: Option<u64>,
+}
+
+impl ListingWindow {
+    /// The window of all the purchases
+    pub fn of<'a>(purchases: impl IntoIterator<Item = &'a PlannedPurchase>) -> Self {
+        purchases
+            .into_iter()
+            .fold(Self::default(), |window, purchase| Self {
+                opens: window.opens.max(purchase.listing_time),
+                closes: match (window.closes, purchase.expiration_time) {
+                    (closes, 0) => closes,
+                    (Some(closes), expiration) => Some(closes.min(expiration)),
+                    (None, expiration) => Some(expiration),
+                },
+            })
+    }
+
+    /// Whether a block at the `timestamp` can fill the listings. The exchange requires
+    /// it to be strictly after the listing time and before the expiration time.
+    pub fn contains(&self, timestamp: u64) -> bool {
+        timestamp > self.opens && !matches!(self.closes, Some(closes) if timestamp >= closes)
+    }
+
+    /// The blocks after the `head` block (mined at `timestamp`) expected to fall in the
+    /// window, `None` if none will
+    pub fn blocks(&self, head: U64, timestamp: u64) -> Option<(U64, Option<U64>)> {
+        // the first block mined after the listing time
+        let first = head + 1 + self.opens.saturating_sub(timestamp) / BLOCK_TIME;
+        let last = match self.closes {
+            Some(closes) if closes <= timestamp => return None,
+            Some(closes) => Some(head + (closes - timestamp - 1) / BLOCK_TIME),
+            None => None,
+        };
+        match last {
+            Some(last) if last < first => None,
+            last => Some((first, last)),
+        }
+    }
+}
+
+/// A bundle included in a block, along with its simulation before its last submission
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncludedBundle {
+    pub block: U64,
+    pub simulation: BundleSimulationReport,
+}
+
+/// A transaction sent via the mempool
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SentTx {
+    pub tx_hash: H256,
+    /// The token bought by the tx, `None` for the payment txs
+    pub token_id: Option<U256>,
+}
+
+/// The result of a [`SnipePlan`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnipeOutcome {
+    /// The unsigned transactions which would have been submitted, with their sender,
+    /// nonce and fees set
+    DryRun(Vec<Eip1559TransactionRequest>),
+    /// The bundle was included
+    Included(IncludedBundle),
+    /// Each bundle of purchases, if it was included, for bundles split according to
+    /// their [`Atomicity`]
+    IncludedBundles(Vec<Option<IncludedBundle>>),
+    /// The transactions sent via the mempool
+    Sent(Vec<SentTx>),
+    /// Nothing could be bought, the bids were placed on the English auctions of the
+    /// targets instead, see [`SnipePlan::bid_on_english_auctions`]
+    BidsPlaced(Vec<MinimalOrder>),
+}
+
+#[derive(Debug, Error)]
+pub enum SnipeError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("The transactions require {required} Wei, above the budget of {budget} Wei")]
+    OverBudget { required: U256, budget: U256 },
+    #[error("Could not sign transaction: {0}")]
+    Signer(String),
+    #[error("Relay error: {0}")]
+    Relay(String),
+    #[error("No relay configured")]
+    NoRelay,
+    #[error("Bundle was not included in any of the {0} target blocks")]
+    NotIncluded(u64),
+    #[error("The listings expire at {0}, before the next block")]
+    Expired(u64),
+    #[error(
+        "The transactions require up to {required} Wei including gas, {} Wei more than the balance of {balance} Wei",
+        .required - .balance
+    )]
+    InsufficientBalance { required: U256, balance: U256 },
+    #[error("The transactions were not confirmed")]
+    Declined,
+    #[error("Order {order_hash:?} is being cancelled by the pending tx {tx_hash:?}")]
+    Cancelled { order_hash: H256, tx_hash: H256 },
+    #[error("No listings left to buy for any of the targets")]
+    NoListings,
+    #[error("A contract bribe cannot be split across bundles, use a priority fee bribe")]
+    SplitContractBribe,
+    #[error(transparent)]
+    Unprofitable(#[from] Unprofitable),
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+    #[error("Aborted while {:?}: {reason}", .progress.stage)]
+    Aborted {
+        reason: AbortReason,
+        progress: Box<SnipeProgress>,
+    },
+}
+
+impl<M: Middleware + 'static, P: OrderProvider> Client<M, P> {
+    /// Executes the plan, buying the cheapest listings of its targets from the signer.
+    /// See [`SnipePlan::abort_handle`] to stop it midway.
+    pub async fn snipe<S: Signer + 'static, F: FeeEstimator>(
+        &self,
+        plan: &SnipePlan<F>,
+        signer: S,
+    ) -> Result<SnipeOutcome, SnipeError> {
+        let abort = match plan.abort {
+            Some(ref abort) => abort,
+            None => return self.execute_snipe(plan, signer).await,
+        };
+        abort.reset_progress();
+        if let Some(reason) = abort.reason() {
+            return Err(SnipeError::Aborted {
+                reason,
+                progress: Box::new(abort.progress()),
+            });
+        }
+        let taker = signer.address();
+        let pipeline = self.execute_snipe(plan, signer);
+        match select(Box::pin(pipeline), Box::pin(abort.aborted())).await {
+            Either::Left((outcome, _)) => outcome,
+            Either::Right((reason, _)) => {
+                // the pipeline was dropped with its in-flight requests, but the txs it
+                // sent may still land
+                let progress = abort.progress();
+                say!(
+                    "Snipe aborted while {:?} ({}), {} txs sent",
+                    progress.stage,
+                    reason,
+                    progress.sent.len()
+                );
+                self.nonces.resync(self.provider.as_ref(), taker).await?;
+                Err(SnipeError::Aborted {
+                    reason,
+                    progress: Box::new(progress),
+                })
+            }
+        }
+    }
+
+    async fn execute_snipe<S: Signer + 'static, F: FeeEstimator>(
+        &self,
+        plan: &SnipePlan<F>,
+        signer: S,
+    ) -> Result<SnipeOutcome, SnipeError> {
+        if let (
+            SnipeStrategy::Bundle { atomicity, .. },
+            Some(Bribe::Contract { .. }) | Some(Bribe::Checked { .. }),
+        ) = (&plan.strategy, &plan.bribe)
+        {
+            // the bribe contract verifies the purchases of all the bundles
+            if *atomicity != Atomicity::AllOrNothing {
+                return Err(SnipeError::SplitContractBribe);
+            }
+        }
+
+        let taker = signer.address();
+        if let Some(ref journal) = plan.journal {
+            self.resolve_pending(journal).await?;
+            // the txs sent before an interruption may not have been accounted for
+            self.nonces.resync(self.provider.as_ref(), taker).await?;
+            let done = plan
+                .targets
+                .iter()
+                .filter(|target| plan.remaining(target) == 0)
+                .count();
+            say!(
+                "Resuming: {}/{} targets already bought",
+                done,
+                plan.targets.len()
+            );
+        }
+        let block = self
+            .provider
+            .get_block(BlockNumber::Latest)
+            .await
+            .map_err(|err| ClientError::Provider(err.to_string()))?
+            .ok_or_else(|| ClientError::Provider("No latest block found".to_owned()))?;
+        let args = BuyArgs {
+            token_id: 0.into(),
+            taker,
+            token: plan.nft,
+            recipient: plan.recipient_or(taker),
+            timestamp: Some(self.time_source.listing_time_at(block.timestamp.as_u64())),
+            quantity: None,
+            max_price: plan.max_price,
+            total_budget: plan.total_budget,
+        };
+
+        // get the fees for the next 5 blocks, just in case
+        let fees = plan.fees.estimate(self.provider.as_ref(), 5).await?;
+        say!(
+            "Max base fee {:?}, priority fee {:?}",
+            fees.max_base_fee,
+            fees.max_priority_fee_per_gas
+        );
+
+        // 1. construct the transactions w/ pre-calculated nonces
+        let listings = plan
+            .listings_at(&self.api, self.listing_filter, unix_timestamp())
+            .await?;
+        plan.progress(SnipeStage::Building);
+        let mut erc1155s = HashMap::new();
+        for nft in plan.collections() {
+            let erc1155 = match plan.erc1155 {
+                Some(erc1155) if nft == plan.nft => erc1155,
+                _ => self.token_standard(nft).await == Some(TokenStandard::Erc1155),
+            };
+            erc1155s.insert(nft, erc1155);
+        }
+        let payment_txs = match plan.swap {
+            Some(ref cfg) => {
+                self.payment_transactions(plan, &listings, taker, cfg)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        let (payment_txs, mut txs, purchases, next_nonce) = self
+            .purchase_transactions(plan, &listings, fees, &args, payment_txs)
+            .await?;
+        if purchases.is_empty() && plan.targets.iter().any(|target| plan.remaining(target) > 0) {
+            self.nonces.reset(taker);
+            if let (Some(expiry), false) = (plan.auction_bids, plan.dry_run) {
+                let bids = self.english_auction_bids(plan, expiry, &signer).await?;
+                if !bids.is_empty() {
+                    return Ok(SnipeOutcome::BidsPlaced(bids));
+                }
+            }
+            return Err(SnipeError::NoListings);
+        }
+
+        // 2. pay the bribe
+        match plan.bribe {
+            Some(Bribe::Contract { address, amount }) => {
+                say!(
+                    "Adding bribe tx to the bundle. Bribe Receiver {:?}, Amount: {:?}",
+                    address,
+                    amount
+                );
+                txs.push(self.bribe_transaction(
+                    address,
+                    amount,
+                    fees.max_base_fee,
+                    next_nonce,
+                    self.ownership_calldata(plan, &args, address, &erc1155s),
+                ));
+            }
+            Some(Bribe::Checked {
+                address,
+                amount,
+                ref checks,
+            }) => {
+                say!(
+                    "Adding bribe tx with {} checks to the bundle. Bribe Receiver {:?}, Amount: {:?}",
+                    checks.checks().len(),
+                    address,
+                    amount
+                );
+                txs.push(self.bribe_transaction(
+                    address,
+                    amount,
+                    fees.max_base_fee,
+                    next_nonce,
+                    Some(checks.calldata()),
+                ));
+            }
+            Some(Bribe::PriorityFee(amount)) => {
+                let priority_fee_per_tx = amount / plan.targets.len().max(1);
+                say!(
+                    "Splitting bribe across {:?} txs in the bundle. Amount per tx: {:?}",
+                    plan.targets.len(),
+                    priority_fee_per_tx
+                );
+                for tx in txs.iter_mut() {
+                    // the bribe replaces the estimated priority fee
+                    tx.max_fee_per_gas = Some(fees.max_base_fee + priority_fee_per_tx);
+                    tx.max_priority_fee_per_gas = Some(priority_fee_per_tx);
+                }
+            }
+            Some(Bribe::Profit(_)) | None => {}
+        }
+
+        // the payment swaps / approvals must land before the purchases
+        let num_payment_txs = payment_txs.len();
+        let mut txs: Vec<_> = payment_txs.into_iter().chain(txs).collect();
+        if let Some(Bribe::Profit(ref strategy)) = plan.bribe {
+            let costs = self
+                .purchase_costs(plan, &txs, num_payment_txs, &purchases)
+                .await?;
+            let priority_fee =
+                price_for_profit(strategy, &mut txs, &costs, fees.max_base_fee, None)?;
+            say!(
+                "Priority fee for the expected profit: {:?} Wei per gas",
+                priority_fee
+            );
+        }
+        let required = txs
+            .iter()
+            .fold(U256::zero(), |acc, tx| acc + tx.value.unwrap_or_default());
+
+        // 3. send it!
+        let outcome = async {
+            if let Some(budget) = plan.budget {
+                if required > budget {
+                    return Err(SnipeError::OverBudget { required, budget });
+                }
+            }
+            if !plan.dry_run {
+                let balance = self
+                    .provider
+                    .get_balance(taker, None)
+                    .await
+                    .map_err(|err| ClientError::Provider(err.to_string()))?;
+                check_balance(&txs, balance)?;
+            }
+            if let (Some(confirm), false) = (&plan.confirm, plan.dry_run) {
+                if !(confirm.0)(&SnipeSummary::new(purchases.clone(), &txs)).await {
+                    return Err(SnipeError::Declined);
+                }
+            }
+            let mut txs = txs;
+            let mut purchases = purchases;
+            if let (Some(max_change_bps), false) = (plan.requote, plan.dry_run) {
+                self.requote_purchases(
+                    plan,
+                    &args,
+                    &mut txs[num_payment_txs..],
+                    &mut purchases,
+                    max_change_bps,
+                )
+                .await?;
+            }
+            for tx in &txs {
+                self.emit(Event::TxBuilt {
+                    tx: Box::new(tx.clone()),
+                })
+                .await?;
+            }
+            let submitted = self.submit(plan, signer, txs, num_payment_txs, &purchases);
+            match plan.transfers {
+                Some(ref watch) if !plan.dry_run => {
+                    let pending = std::sync::Mutex::new(purchased_tokens(&purchases));
+                    let transfers = self.confirm_transfers(watch, args.recipient, &pending);
+                    let raced = select(Box::pin(submitted), Box::pin(transfers)).await;
+                    match raced {
+                        Either::Left((outcome, transfers)) => {
+                            // only the tokens of what landed are transferred
+                            if let Ok(ref outcome) = outcome {
+                                let awaited = awaited_tokens(outcome, &purchases);
+                                let mut pending = pending.lock().unwrap();
+                                pending.retain(|token| awaited.contains(token));
+                            }
+                            if !pending.lock().unwrap().is_empty() {
+                                let _ = tokio::time::timeout(watch.timeout, transfers).await;
+                            }
+                            outcome
+                        }
+                        // all the tokens were received before the submission returned, or
+                        // the transfers could not be subscribed to
+                        Either::Right(((), submitted)) => submitted.await,
+                    }
+                }
+                _ => submitted.await,
+            }
+        }
+        .await;
+
+        // unless the txs were sent, the reserved nonces were not consumed
+        if !matches!(
+            outcome,
+            Ok(SnipeOutcome::Included(_))
+                | Ok(SnipeOutcome::IncludedBundles(_))
+                | Ok(SnipeOutcome::Sent(_))
+        ) {
+            self.nonces.reset(taker);
+        }
+        outcome
+    }
+
+    /// Bids at their max price on the English auctions of the targets left to buy, see
+    /// [`SnipePlan::bid_on_english_auctions`]. Returns the placed bids.
+    async fn english_auction_bids<S: Signer, F>(
+        &self,
+        plan: &SnipePlan<F>,
+        expiry: Duration,
+        signer: &S,
+    ) -> Result<Vec<MinimalOrder>, ClientError> {
+        let filter = ListingFilter {
+            include_english_auctions: true,
+            ..self.listing_filter
+        };
+        let now = unix_timestamp();
+        let mut bids = Vec::new();
+        for target in &plan.targets {
+            let max_price = match target.max_price.or(plan.max_price) {
+                Some(max_price) if plan.remaining(target) > 0 => max_price,
+                _ => continue,
+            };
+            let nft = plan.collection(target);
+            let listings = cheapest_orders_by_token_at(
+                &self.api,
+                nft,
+                &[target.token_id],
+                usize::MAX,
+                filter,
+                now,
+            )
+            .await?;
+            let auction = listings
+                .get(&target.token_id)
+                .into_iter()
+                .flatten()
+                .find(|order| order.is_english_auction() && order.current_price <= max_price);
+            if let Some(auction) = auction {
+                say!(
+                    "Token {} of {:?} is only listed in an English auction, bidding {}",
+                    target.token_id,
+                    nft,
+                    format_eth(max_price)
+                );
+                let expiration_time = now + expiry.as_secs();
+                bids.push(
+                    self.place_english_auction_bid(auction, max_price, expiration_time, signer)
+                        .await?,
+                );
+            }
+        }
+        Ok(bids)
+    }
+
+    /// Submits the transactions according to the plan's strategy. The bundles only
+    /// target the blocks in which their `purchases` can be filled.
+    async fn submit<S: Signer + 'static, F: FeeEstimator>(
+        &self,
+        plan: &SnipePlan<F>,
+        signer: S,
+        mut txs: Vec<Eip1559TransactionRequest>,
+        num_payment_txs: usize,
+        purchases: &[PlannedPurchase],
+    ) -> Result<SnipeOutcome, SnipeError> {
+        if plan.dry_run {
+            // set the sender so that the txs can be signed elsewhere
+            for tx in txs.iter_mut() {
+                tx.from = Some(signer.address());
+            }
+        }
+        plan.progress(match plan.strategy {
+            // the bundles are simulated before being sent
+            SnipeStrategy::Bundle { .. } | SnipeStrategy::Mempool { simulate: true } => {
+                SnipeStage::Simulating
+            }
+            SnipeStrategy::Mempool { simulate: false } => SnipeStage::Submitting,
+        });
+        let abort = plan.abort.as_ref();
+        let labels = tx_labels(num_payment_txs, purchases);
+        match plan.strategy {
+            SnipeStrategy::Bundle {
+                ref relays,
+                target_blocks,
+                atomicity,
+            } => {
+                if plan.dry_run {
+                    self.sign_bundle(&signer, &txs, &labels).await?;
+                    return Ok(SnipeOutcome::DryRun(txs));
+                }
+                if atomicity != Atomicity::AllOrNothing {
+                    let bundles = self
+                        .send_bundles(
+                            &signer,
+                            &plan.fees,
+                            relays,
+                            txs,
+                            num_payment_txs,
+                            purchases,
+                            atomicity,
+                            target_blocks,
+                            plan.watch_cancellations,
+                            abort,
+                        )
+                        .await?;
+                    return Ok(SnipeOutcome::IncludedBundles(bundles));
+                }
+                // the purchases may have been re-quoted since the fees were first sized
+                let profit = match plan.bribe {
+                    Some(Bribe::Profit(ref strategy)) => Some((
+                        strategy,
+                        self.purchase_costs(plan, &txs, num_payment_txs, purchases)
+                            .await?,
+                    )),
+                    _ => None,
+                };
+                let bundle = match self
+                    .send_bundle(
+                        &signer,
+                        &plan.fees,
+                        relays,
+                        txs,
+                        &labels,
+                        ListingWindow::of(purchases),
+                        &plan.watched_orders(num_payment_txs, purchases),
+                        target_blocks,
+                        profit
+                            .as_ref()
+                            .map(|(strategy, costs)| (*strategy, costs.as_slice())),
+                        abort,
+                    )
+                    .await
+                {
+                    Ok(bundle) => bundle,
+                    Err(SnipeError::Cancelled {
+                        order_hash,
+                        tx_hash,
+                    }) => {
+                        // the whole bundle is void, as are the other snipes of the handle
+                        if let Some(abort) = abort {
+                            abort.abort(AbortReason::CancellationDetected {
+                                order_hash,
+                                tx_hash,
+                            });
+                        }
+                        return Err(SnipeError::Cancelled {
+                            order_hash,
+                            tx_hash,
+                        });
+                    }
+                    Err(err) => return Err(err),
+                };
+                if let Some(abort) = abort {
+                    abort.included(bundle.block);
+                }
+                Ok(SnipeOutcome::Included(bundle))
+            }
+            SnipeStrategy::Mempool { simulate } => {
+                if plan.dry_run {
+                    return Ok(SnipeOutcome::DryRun(txs));
+                }
+                let sent = self
+                    .send_transactions(
+                        signer,
+                        txs,
+                        num_payment_txs,
+                        purchases,
+                        plan.journal.as_ref(),
+                        simulate,
+                        plan.escalator,
+                        plan.deadline,
+                        abort,
+                    )
+                    .await?;
+                Ok(SnipeOutcome::Sent(sent))
+            }
+        }
+    }
+
+    /// Builds the transactions swapping ETH for the ERC20 payment tokens of the cheapest
+    /// listings (and approving them to the Wyvern token transfer proxy), if the taker
+    /// does not hold enough of them
+    async fn payment_transactions<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        listings: &Listings,
+        taker: Address,
+        cfg: &SwapConfig,
+    ) -> Result<Vec<Eip1559TransactionRequest>, ClientError> {
+        // sum the amount required per payment token
+        let mut required: HashMap<Address, U256> = HashMap::new();
+        let mut eth = U256::zero();
+        for (_, orders) in plan.target_listings(listings) {
+            for order in orders {
+                if order.payment_token.is_zero() {
+                    eth += order.current_price;
+                } else {
+                    *required.entry(order.payment_token).or_default() += order.current_price;
+                }
+            }
+        }
+
+        let mut txs = Vec::new();
+        let mut swaps = Vec::new();
+        for (token, amount) in required {
+            if let Some(swap) = self.payment_swap(taker, token, amount, cfg).await? {
+                say!(
+                    "Swapping up to {:?} Wei (quoted {:?} Wei) for {:?} of payment token {:?}",
+                    swap.max_amount_in,
+                    swap.amount_in,
+                    swap.amount_out,
+                    token
+                );
+                txs.push(swap.tx.clone());
+                swaps.push(swap);
+            }
+
+            if let Some(call) = self.ensure_erc20_approval(token, taker, amount).await? {
+                say!("Approving {:?} of payment token {:?}", amount, token);
+                txs.push(into_eip1559(call.tx));
+            }
+        }
+        say!("Effective cost: {}", PaymentPreview::new(eth, &swaps));
+
+        Ok(txs)
+    }
+
+    /// Re-quotes the `purchases` of the plan right before their `txs` are sent, see
+    /// [`SnipePlan::requote`]. The buy calls of the listings whose price moved replace
+    /// the data and value of their txs, keeping their nonce, gas and fees.
+    async fn requote_purchases<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        args: &BuyArgs,
+        txs: &mut [Eip1559TransactionRequest],
+        purchases: &mut [PlannedPurchase],
+        max_change_bps: u64,
+    ) -> Result<(), ClientError> {
+        for (tx, purchase) in txs.iter_mut().zip(purchases.iter_mut()) {
+            let target = plan.targets.iter().find(|target| {
+                plan.collection(target) == purchase.nft && target.token_id == purchase.token_id
+            });
+            let args = BuyArgs {
+                token: purchase.nft,
+                token_id: purchase.token_id,
+                max_price: target
+                    .and_then(|target| target.max_price)
+                    .or(plan.max_price),
+                ..args.clone()
+            };
+            let buy = self
+                .requote_order(purchase.order_hash, purchase.price, &args, max_change_bps)
+                .await?;
+            if let Some(buy) = buy {
+                let rebuilt = into_eip1559(buy.call.tx);
+                tx.data = rebuilt.data;
+                tx.value = rebuilt.value;
+                purchase.price = buy.order.current_price;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the purchase transactions of the plan's targets, preceded by the
+    /// `payment_txs`. Returns them along with the listings they buy and the nonce
+    /// reserved for a bribe tx.
+    async fn purchase_transactions<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        listings: &Listings,
+        fees: Fees,
+        args: &BuyArgs,
+        payment_txs: Vec<Eip1559TransactionRequest>,
+    ) -> Result<
+        (
+            Vec<Eip1559TransactionRequest>,
+            Vec<Eip1559TransactionRequest>,
+            Vec<PlannedPurchase>,
+            U256,
+        ),
+        ClientError,
+    > {
+        let chain_id = self.chain_id().await?;
+        let targets: Vec<_> = plan
+            .target_listings(listings)
+            .map(|(target, orders)| {
+                let mut args = args.clone();
+                args.token = plan.collection(target);
+                args.token_id = target.token_id;
+                args.max_price = target.max_price.or(plan.max_price);
+                (args, orders)
+            })
+            .collect();
+        // the listings of all the targets are checked at once
+        let all_listings: Vec<_> = targets
+            .iter()
+            .flat_map(|(args, orders)| {
+                orders
+                    .iter()
+                    .map(move |order| (args.clone(), order.clone()))
+            })
+            .collect();
+        let mut verified = self.preflight_listings(&all_listings).await;
+
+        let mut spent = U256::zero();
+        let mut calls = Vec::new();
+        let mut purchases = Vec::new();
+        for (args, orders) in targets {
+            // one order at a time, to know which ones were skipped
+            for order in orders {
+                for buy in self
+                    .buy_verified_orders(
+                        &args,
+                        vec![order.clone()],
+                        chain_id,
+                        &mut spent,
+                        &mut verified,
+                    )
+                    .await?
+                {
+                    purchases.push(PlannedPurchase {
+                        nft: args.token,
+                        token_id: buy.token_id,
+                        order_hash: buy.order.order_hash,
+                        price: buy.order.current_price,
+                        payment_token: buy.order.payment_token,
+                        listing_time: buy.order.listing_time,
+                        expiration_time: buy.order.expiration_time,
+                    });
+                    calls.push(buy.call);
+                }
+            }
+        }
+
+        // reserve a nonce for each tx and one more for a potential bribe tx
+        let num_txs = payment_txs.len() + calls.len();
+        let reservation = self
+            .nonces
+            .reserve(self.provider.as_ref(), args.taker, num_txs + 1)
+            .await?;
+        let mut nonces = reservation.nonces();
+
+        let mut payment_txs = payment_txs;
+        for tx in payment_txs.iter_mut() {
+            tx.max_fee_per_gas = Some(fees.max_fee_per_gas());
+            tx.max_priority_fee_per_gas = Some(fees.max_priority_fee_per_gas);
+            tx.nonce = nonces.next();
+        }
+
+        let mut txs = Vec::new();
+        for call in calls {
+            // get the 1559 inner tx to configure the basefee
+            let mut tx = into_eip1559(call.tx);
+
+            // initialize the fees, the priority fee may be replaced by the bribe
+            tx.max_fee_per_gas = Some(fees.max_fee_per_gas());
+            tx.max_priority_fee_per_gas = Some(fees.max_priority_fee_per_gas);
+
+            // set the reserved nonce
+            tx.nonce = nonces.next();
+
+            txs.push(tx)
+        }
+        let next_nonce = nonces.next().expect("reserved a nonce for the bribe tx");
+        // only keep the bribe's nonce if there is a bribe tx
+        let bribe_tx = matches!(
+            plan.bribe,
+            Some(Bribe::Contract { .. }) | Some(Bribe::Checked { .. })
+        );
+        reservation.commit(num_txs + bribe_tx as usize);
+
+        Ok((payment_txs, txs, purchases, next_nonce))
+    }
+
+    /// Builds the tx paying the bribe to the briber contract, with the `data` of the
+    /// call verifying the purchases
+    fn bribe_transaction(
+        &self,
+        briber: Address,
+        amount: U256,
+        max_base_fee: U256,
+        nonce: U256,
+        data: Option<Bytes>,
+    ) -> Eip1559TransactionRequest {
+        let mut tx = Eip1559TransactionRequest::new()
+            .to(briber)
+            // TODO: Can we remove this?
+            .gas(200_000)
+            .max_fee_per_gas(max_base_fee)
+            // use the bumped nonce
+            .nonce(nonce)
+            .value(amount);
+        tx.data = data;
+        tx
+    }
+
+    /// The calldata of the briber call verifying that the recipient received the NFTs,
+    /// given whether each collection is an ERC1155
+    fn ownership_calldata<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        args: &BuyArgs,
+        briber: Address,
+        erc1155s: &HashMap<Address, bool>,
+    ) -> Option<Bytes> {
+        let collections = plan.collections();
+        if collections.len() > 1 {
+            let mut checks = BribeChecks::new();
+            for target in &plan.targets {
+                let nft = plan.collection(target);
+                let (id, quantity) = (target.token_id, target.quantity.into());
+                checks = if erc1155s.get(&nft).copied().unwrap_or_default() {
+                    checks.erc1155_balance(nft, args.recipient, id, quantity)
+                } else {
+                    checks.erc721_owner(nft, id, args.recipient)
+                };
+            }
+            return Some(checks.calldata());
+        }
+
+        // briber.sol has a different method call depending on erc1155 or 721s
+        // being sniped
+        let nft = collections.first().copied().unwrap_or(plan.nft);
+        let briber = Briber::new(briber, self.provider.clone());
+        let ids = plan.token_ids();
+        if erc1155s.get(&nft).copied().unwrap_or_default() {
+            let quantities = plan
+                .targets
+                .iter()
+                .map(|target| target.quantity.into())
+                .collect();
+            briber
+                .verify_ownership_and_pay_1155(nft, args.recipient, ids, quantities)
+                .calldata()
+        } else {
+            briber
+                .verify_ownership_and_pay_721(nft, args.recipient, ids)
+                .calldata()
+        }
+    }
+
+    /// Signs the txs into a bundle, the i-th of them buying the i-th of the `labels`
+    async fn sign_bundle<S: Signer>(
+        &self,
+        signer: &S,
+        txs: &[Eip1559TransactionRequest],
+        labels: &[Option<U256>],
+    ) -> Result<BundleRequest, SnipeError> {
+        let mut bundle = BundleRequest::new();
+        let mut sum = U256::from(0);
+        for (i, tx) in txs.iter().enumerate() {
+            if let Some(id) = labels.get(i).copied().flatten() {
+                say!(
+                    "[TokenId = {:?}] Signing bundle tx with {:?} Wei (max-priority-fee: {:?}, max-total-fee: {:?}, gas-limit: {:?})",
+                    id,
+                    tx.value.unwrap_or_default(),
+                    tx.max_priority_fee_per_gas.unwrap_or_default(),
+                    tx.max_fee_per_gas.unwrap_or_default(),
+                    tx.gas.unwrap_or_default(),
+                );
+            } else {
+                say!(
+                    "Signing payment / bribe tx with {:?} Wei (max-priority-fee: {:?}, max-total-fee: {:?}, gas-limit: {:?})",
+                    tx.value.unwrap_or_default(),
+                    tx.max_priority_fee_per_gas.unwrap_or_default(),
+                    tx.max_fee_per_gas.unwrap_or_default(),
+                    tx.gas.unwrap_or_default(),
+                );
+            }
+
+            sum += tx.value.unwrap_or_default();
+
+            let tx = tx.clone().into();
+            let signature = signer
+                .sign_transaction(&tx)
+                .await
+                .map_err(|err| SnipeError::Signer(err.to_string()))?;
+            let rlp = tx.rlp_signed(signer.chain_id(), &signature);
+            self.emit(Event::TxSigned {
+                tx_hash: keccak256(&rlp).into(),
+                nonce: tx.nonce().cloned(),
+            })
+            .await?;
+            bundle = bundle.push_transaction(rlp);
+        }
+        say!("Total Wei required: {:?}", sum);
+        Ok(bundle)
+    }
+
+    /// Simulates the bundle via the `relay`, reporting its txs attributed to the `labels`
+    async fn simulate_bundle(
+        &self,
+        relay: &FlashbotsMiddleware<Arc<M>, LocalWallet>,
+        bundle: &BundleRequest,
+        labels: &[Option<U256>],
+    ) -> Result<(SimulatedBundle, BundleSimulationReport), SnipeError> {
+        let simulated_bundle = relay.simulate_bundle(bundle).await;
+        metrics::bundle_simulated(simulated_bundle.is_ok());
+        let simulated_bundle =
+            simulated_bundle.map_err(|err| SnipeError::Relay(err.to_string()))?;
+        let simulation = BundleSimulationReport::new(&simulated_bundle, labels);
+        say!(
+            "Simulated bundle at block {:?}: {:?} gas, {:?} Wei to the coinbase ({:?} Wei per gas)",
+            simulation.simulation_block,
+            simulation.gas_used,
+            simulation.coinbase_diff,
+            simulation.effective_gas_price
+        );
+        for tx in simulation.reverted() {
+            say!(
+                "Simulated tx {:?} reverted: {}",
+                tx.tx_hash,
+                tx.error.as_deref().unwrap_or_default()
+            );
+        }
+        self.emit(Event::BundleSimulated {
+            simulation: Box::new(simulation.clone()),
+        })
+        .await?;
+        Ok((simulated_bundle, simulation))
+    }
+
+    /// The cost in Wei of the first txs beyond their gas, aligned with the
+    /// [`tx_labels`]: the value of the purchases paid in ETH and the ETH value of the
+    /// ERC20 payments of the others (see [`Client::eth_value`]). The first
+    /// `num_payment_txs` txs cost nothing, since they fund the ERC20 payments.
+    async fn purchase_costs<F>(
+        &self,
+        plan: &SnipePlan<F>,
+        txs: &[Eip1559TransactionRequest],
+        num_payment_txs: usize,
+        purchases: &[PlannedPurchase],
+    ) -> Result<Vec<U256>, ClientError> {
+        let pool_fee = plan
+            .swap
+            .as_ref()
+            .map_or_else(|| SwapConfig::default().pool_fee, |cfg| cfg.pool_fee);
+        let mut costs = vec![U256::zero(); num_payment_txs];
+        for (tx, purchase) in txs[num_payment_txs..].iter().zip(purchases) {
+            costs.push(if purchase.payment_token.is_zero() {
+                tx.value.unwrap_or_default()
+            } else {
+                self.eth_value(purchase.payment_token, purchase.price, pool_fee)
+                    .await?
+            });
+        }
+        Ok(costs)
+    }
+
+    /// Submits the bundle to all `relays` concurrently, for each of the next `target_blocks`
+    /// blocks until it gets included. Before each submission the bundle is re-priced for the
+    /// basefee of the new head, re-signed and re-simulated against it via the first relay.
+    /// With a `profit` strategy, the priority fee is also re-sized for the simulated gas
+    /// and the costs of the txs (see [`Client::purchase_costs`]), and the re-sized bundle
+    /// simulated again. Only the blocks expected in the `window` of its listings are targeted, stopping
+    /// with `SnipeError::Expired` once they expired. The included bundle is returned
+    /// along with its last simulation, whose txs are attributed to the `labels`.
+    ///
+    /// The pending cancellations of the `orders` bought by the txs (see [`tx_orders`])
+    /// are watched, replacing the bundle with one without the cancelled purchase, or
+    /// stopping with `SnipeError::Cancelled` if none is left.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_bundle<S: Signer, F: FeeEstimator>(
+        &self,
+        signer: &S,
+        fees: &F,
+        relays: &[Url],
+        mut txs: Vec<Eip1559TransactionRequest>,
+        labels: &[Option<U256>],
+        window: ListingWindow,
+        orders: &[Option<H256>],
+        target_blocks: u64,
+        profit: Option<(&BribeStrategy, &[U256])>,
+        abort: Option<&AbortHandle>,
+    ) -> Result<IncludedBundle, SnipeError> {
+        let (mut labels, mut orders) = (labels.to_vec(), orders.to_vec());
+        let mut costs = profit.map(|(_, costs)| costs.to_vec()).unwrap_or_default();
+        let profit = profit.map(|(strategy, _)| strategy);
+        let bundle_signer = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let relays = relays
+            .iter()
+            .map(|url| {
+                let relay = FlashbotsMiddleware::new(
+                    self.provider.clone(),
+                    url.clone(),
+                    bundle_signer.clone(),
+                );
+                (url, relay)
+            })
+            .collect::<Vec<_>>();
+        let simulation_relay = &relays.first().ok_or(SnipeError::NoRelay)?.1;
+
+        // polled along with the relays, the pending txs in between being buffered by
+        // the node's filter
+        let watch = |orders: &[Option<H256>]| {
+            let watched: Vec<_> = orders.iter().flatten().copied().collect();
+            Box::pin(async move { self.next_cancellation(&watched).await })
+        };
+        let mut cancellation = watch(&orders);
+
+        // the gas used by the last simulation of the bundle
+        let mut gas_used = None;
+        for attempt in 1..=target_blocks {
+            let block = self
+                .provider
+                .get_block(BlockNumber::Latest)
+                .await
+                .map_err(|err| ClientError::Provider(err.to_string()))?
+                .ok_or_else(|| ClientError::Provider("No latest block found".to_owned()))?;
+            let num = block
+                .number
+                .ok_or_else(|| ClientError::Provider("No block number found".to_owned()))?;
+            let (target_block, last_block) =
+                match window.blocks(num, block.timestamp.as_u64()) {
+                    Some(blocks) => blocks,
+                    None => {
+                        let expiration = window.closes.unwrap_or_default();
+                        say!(
+                        "[Attempt {}/{}] Current block {:?}. The listings expire at {}, stopping",
+                        attempt, target_blocks, num, expiration
+                    );
+                        return Err(SnipeError::Expired(expiration));
+                    }
+                };
+            if target_block > num + 1 {
+                say!(
+                    "The listings can be filled after {}, from block {:?}",
+                    window.opens,
+                    target_block
+                );
+            }
+            if let Some(last_block) = last_block {
+                say!(
+                    "The listings expire at {}, after block {:?}",
+                    window.closes.unwrap_or_default(),
+                    last_block
+                );
+            }
+
+            let max_base_fee = fees.estimate(self.provider.as_ref(), 1).await?.max_base_fee;
+            match profit {
+                Some(strategy) => {
+                    price_for_profit(strategy, &mut txs, &costs, max_base_fee, gas_used)?;
+                }
+                None => {
+                    for tx in txs.iter_mut() {
+                        tx.max_fee_per_gas =
+                            Some(max_base_fee + tx.max_priority_fee_per_gas.unwrap_or_default());
+                    }
+                }
+            }
+
+            say!(
+                "[Attempt {}/{}] Current block {:?}, max base fee {:?}. Targeting block {:?}",
+                attempt,
+                target_blocks,
+                num,
+                max_base_fee,
+                target_block
+            );
+            let bundle = self
+                .sign_bundle(signer, &txs, &labels)
+                .await?
+                .set_block(target_block)
+                .set_simulation_block(num);
+
+            say!("Simulating bundle");
+            let (mut simulated_bundle, mut simulation) = self
+                .simulate_bundle(simulation_relay, &bundle, &labels)
+                .await?;
+            let bundle = match profit {
+                Some(strategy) => {
+                    gas_used = Some(simulated_bundle.gas_used);
+                    let priority_fee =
+                        price_for_profit(strategy, &mut txs, &costs, max_base_fee, gas_used)?;
+                    say!(
+                        "Priority fee for the simulated gas of {:?}: {:?} Wei per gas",
+                        simulated_bundle.gas_used,
+                        priority_fee
+                    );
+                    let bundle = self
+                        .sign_bundle(signer, &txs, &labels)
+                        .await?
+                        .set_block(target_block)
+                        .set_simulation_block(num);
+                    // the re-sized fees change the coinbase payment and the balance left
+                    say!("Simulating the re-sized bundle");
+                    let (simulated, report) = self
+                        .simulate_bundle(simulation_relay, &bundle, &labels)
+                        .await?;
+                    simulated_bundle = simulated;
+                    simulation = report;
+                    bundle
+                }
+                None => bundle,
+            };
+            if let Some(abort) = abort {
+                abort.stage(SnipeStage::Submitting);
+            }
+            self.emit(Event::BundleSent {
+                target_block,
+                num_txs: txs.len(),
+            })
+            .await?;
+
+            let bundle = &bundle;
+            let sent = join_all(relays.iter().map(|(_, relay)| async move {
+                match relay.send_bundle(bundle).await {
+                    Ok(pending_bundle) => Ok(pending_bundle.await),
+                    Err(err) => Err(err),
+                }
+            }));
+            let results = match select(Box::pin(sent), cancellation.as_mut()).await {
+                Either::Left((results, _)) => results,
+                Either::Right((
+                    Cancellation {
+                        order_hash,
+                        tx_hash,
+                    },
+                    _,
+                )) => {
+                    self.emit(Event::CancellationDetected {
+                        order_hash,
+                        tx_hash,
+                    })
+                    .await?;
+                    let dropped = drop_order(&mut txs, &mut labels, &mut orders, order_hash);
+                    let index = match dropped {
+                        Some(index) => index,
+                        None => {
+                            say!(
+                            "Order {:?} is being cancelled by the pending tx {:?}, aborting the bundle",
+                            order_hash,
+                            tx_hash
+                        );
+                            return Err(SnipeError::Cancelled {
+                                order_hash,
+                                tx_hash,
+                            });
+                        }
+                    };
+                    if index < costs.len() {
+                        costs.remove(index);
+                    }
+                    say!(
+                        "Order {:?} is being cancelled by the pending tx {:?}, replacing the bundle with one without it",
+                        order_hash,
+                        tx_hash
+                    );
+                    // the nonces after the dropped tx were renumbered
+                    self.nonces.reset(signer.address());
+                    gas_used = None;
+                    cancellation = watch(&orders);
+                    continue;
+                }
+            };
+
+            let mut included = false;
+            for ((url, _), res) in relays.iter().zip(results) {
+                match res {
+                    Ok(Ok(res)) => {
+                        say!(
+                            "[Relay {}] Bundle included in block {:?}: {:?}",
+                            url,
+                            target_block,
+                            res
+                        );
+                        included = true;
+                    }
+                    Ok(Err(PendingBundleError::BundleNotIncluded)) => {
+                        say!(
+                            "[Relay {}] Bundle not included in block {:?}",
+                            url,
+                            target_block
+                        );
+                    }
+                    Ok(Err(err)) => say!("[Relay {}] Could not track bundle: {}", url, err),
+                    Err(err) => say!("[Relay {}] Could not send bundle: {}", url, err),
+                }
+            }
+
+            if included {
+                metrics::gas_paid(simulated_bundle.gas_fees);
+                // the payment and bribe txs are not attributed to a token
+                let purchases = simulation.txs.iter().filter(|tx| tx.token_id.is_some());
+                metrics::buys_succeeded(purchases.clone().count());
+                for tx in purchases {
+                    self.emit(Event::FillConfirmed {
+                        tx_hash: tx.tx_hash,
+                        block: Some(target_block),
+                    })
+                    .await?;
+                }
+                return Ok(IncludedBundle {
+                    block: target_block,
+                    simulation,
+                });
+            }
+        }
+
+        Err(SnipeError::NotIncluded(target_blocks))
+    }
+
+    /// Submits the first `num_payment_txs` txs as a bundle, then the purchases in
+    /// bundles sized by the `atomicity`, each via [`Client::send_bundle`]. The bundles
+    /// which are not included are skipped, renumbering the nonces of the following
+    /// ones. Returns each bundle of purchases, if it was included.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_bundles<S: Signer, F: FeeEstimator>(
+        &self,
+        signer: &S,
+        fees: &F,
+        relays: &[Url],
+        mut txs: Vec<Eip1559TransactionRequest>,
+        num_payment_txs: usize,
+        purchases: &[PlannedPurchase],
+        atomicity: Atomicity,
+        target_blocks: u64,
+        watch_cancellations: bool,
+        abort: Option<&AbortHandle>,
+    ) -> Result<Vec<Option<IncludedBundle>>, SnipeError> {
+        let payment_txs: Vec<_> = txs.drain(..num_payment_txs).collect();
+        if !payment_txs.is_empty() {
+            // the purchases depend on the payment, do not send them without it
+            say!("Sending {} payment txs", payment_txs.len());
+            self.send_bundle(
+                signer,
+                fees,
+                relays,
+                payment_txs,
+                &[],
+                ListingWindow::default(),
+                &[],
+                target_blocks,
+                None,
+                abort,
+            )
+            .await?;
+        }
+
+        let size = atomicity.chunk_size(txs.len());
+        let mut bundles = Vec::new();
+        let mut skipped = 0;
+        for (i, chunk) in txs.chunks(size).enumerate() {
+            let mut chunk = chunk.to_vec();
+            for tx in chunk.iter_mut() {
+                tx.nonce = tx.nonce.map(|nonce| nonce - U256::from(skipped));
+            }
+            let num_txs = chunk.len();
+            let chunk_purchases = purchases.get(i * size..).unwrap_or_default();
+            let chunk_purchases = &chunk_purchases[..chunk_purchases.len().min(num_txs)];
+            let labels = tx_labels(0, chunk_purchases);
+            let window = ListingWindow::of(chunk_purchases);
+            let watched = if watch_cancellations {
+                tx_orders(0, chunk_purchases)
+            } else {
+                Vec::new()
+            };
+            say!("[Bundle {}] Sending {} purchase txs", i + 1, num_txs);
+            match self
+                .send_bundle(
+                    signer,
+                    fees,
+                    relays,
+                    chunk,
+                    &labels,
+                    window,
+                    &watched,
+                    target_blocks,
+                    None,
+                    abort,
+                )
+                .await
+            {
+                Ok(bundle) => {
+                    if let Some(abort) = abort {
+                        abort.included(bundle.block);
+                    }
+                    bundles.push(Some(bundle))
+                }
+                Err(err @ SnipeError::NotIncluded(_))
+                | Err(err @ SnipeError::Relay(_))
+                | Err(err @ SnipeError::Expired(_))
+                | Err(err @ SnipeError::Cancelled { .. }) => {
+                    say!("[Bundle {}] Skipping: {}", i + 1, err);
+                    skipped += num_txs;
+                    bundles.push(None);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // the allocator's counter does not account for the renumbering
+        if skipped > 0 {
+            self.nonces.reset(signer.address());
+        }
+        if bundles.iter().all(Option::is_none) {
+            return Err(SnipeError::NotIncluded(target_blocks));
+        }
+        Ok(bundles)
+    }
+
+    /// Records the status of the journal's purchases which were mined since it was last
+    /// written, e.g. after an interruption. The others are bought again.
+    async fn resolve_pending(&self, journal: &BuyJournal) -> Result<(), SnipeError> {
+        for entry in journal.pending() {
+            let receipt = self
+                .provider
+                .get_transaction_receipt(entry.tx_hash)
+                .await
+                .map_err(|err| ClientError::Provider(err.to_string()))?;
+            match receipt {
+                Some(receipt) => journal.update(entry.tx_hash, JournalStatus::of(&receipt))?,
+                None => say!(
+                    "[Token Id = {:?}] Purchase tx {:?} was not mined, buying it again",
+                    entry.token_id,
+                    entry.tx_hash
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the txs one by one via the mempool, the first `num_payment_txs` of them
+    /// being payment swaps / approvals. If `simulate` is set, purchases which fail
+    /// when simulated are skipped. The sent `purchases` are recorded in the `journal`,
+    /// along with their status once mined.
+    /// The txs still pending are then escalated by the `escalator` and cancelled after the
+    /// `deadline`.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_transactions<S: Signer + 'static>(
+        &self,
+        signer: S,
+        txs: Vec<Eip1559TransactionRequest>,
+        num_payment_txs: usize,
+        purchases: &[PlannedPurchase],
+        journal: Option<&BuyJournal>,
+        simulate: bool,
+        escalator: Option<Escalator>,
+        deadline: Option<Deadline>,
+        abort: Option<&AbortHandle>,
+    ) -> Result<Vec<SentTx>, SnipeError> {
+        let wallet = signer.address();
+        let provider = SignerMiddleware::new(self.provider.clone(), signer);
+        let mut sent = Vec::new();
+        let mut sent_txs = Vec::new();
+        let mut txs = txs.into_iter();
+
+        for tx in txs.by_ref().take(num_payment_txs) {
+            let tx: TransactionRequest = tx.into();
+            if let Some(abort) = abort {
+                abort.stage(SnipeStage::Submitting);
+            }
+            let pending_tx = match provider.send_transaction(tx.clone(), None).await {
+                Ok(pending_tx) => pending_tx,
+                Err(err) => {
+                    // the purchases depend on the payment, do not send them
+                    self.nonces.resync(self.provider.as_ref(), wallet).await?;
+                    return Err(ClientError::Provider(err.to_string()).into());
+                }
+            };
+            say!("Sent payment tx {:?}", *pending_tx);
+            if let Some(abort) = abort {
+                abort.sent(*pending_tx);
+            }
+            // the tx is already sent, a veto is meaningless
+            let _ = self
+                .emit(Event::TxSent {
+                    tx_hash: *pending_tx,
+                    nonce: tx.nonce,
+                })
+                .await;
+            sent.push((*pending_tx, tx));
+            sent_txs.push(SentTx {
+                tx_hash: *pending_tx,
+                token_id: None,
+            });
+        }
+
+        // the nonce of the next tx to send. Skipped or failed txs would leave a gap
+        // stranding the following ones, so these are renumbered
+        let mut next_nonce = None;
+        let mut renumbered = false;
+        for (i, mut tx) in txs.enumerate() {
+            // the i-th purchase tx buys the i-th purchase
+            let id = purchases
+                .get(i)
+                .map(|purchase| purchase.token_id)
+                .unwrap_or_default();
+            if let Some(nonce) = next_nonce {
+                tx.nonce = Some(nonce);
+            }
+            if simulate {
+                let typed_tx = TypedTransaction::Eip1559(tx.clone());
+                match self.simulate_tx(&typed_tx).await {
+                    Ok(()) => say!("[Token Id = {:?}] Simulation succeeded", id),
+                    Err(err) => {
+                        say!("[Token Id = {:?}] Simulation failed, skipping: {}", id, err);
+                        next_nonce = next_nonce.or(tx.nonce);
+                        renumbered = true;
+                        continue;
+                    }
+                }
+            }
+
+            let nonce = tx.nonce;
+            let tx: TransactionRequest = tx.into();
+            say!(
+                "[Token Id = {:?}] Sending tx with {:?} Wei ",
+                id,
+                tx.value.unwrap_or_default()
+            );
+            if let Some(abort) = abort {
+                abort.stage(SnipeStage::Submitting);
+            }
+            match provider.send_transaction(tx.clone(), None).await {
+                Ok(pending_tx) => {
+                    say!("[Token Id = {:?}] Sent tx {:?}", id, *pending_tx);
+                    if let Some(abort) = abort {
+                        abort.sent(*pending_tx);
+                    }
+                    let _ = self
+                        .emit(Event::TxSent {
+                            tx_hash: *pending_tx,
+                            nonce,
+                        })
+                        .await;
+                    sent.push((*pending_tx, tx));
+                    sent_txs.push(SentTx {
+                        tx_hash: *pending_tx,
+                        token_id: Some(id),
+                    });
+                    next_nonce = nonce.map(|nonce| nonce + 1);
+                    if let (Some(journal), Some(purchase)) = (journal, purchases.get(i)) {
+                        journal.record(JournalEntry {
+                            nft: purchase.nft,
+                            token_id: purchase.token_id,
+                            order_hash: purchase.order_hash,
+                            tx_hash: *pending_tx,
+                            nonce,
+                            status: JournalStatus::Sent,
+                        })?;
+                    }
+                }
+                Err(err) => {
+                    // the node may or may not have accepted the tx, ask it
+                    say!("[Token Id = {:?}] Failed to send tx, skipping: {}", id, err);
+                    next_nonce = Some(self.nonces.resync(self.provider.as_ref(), wallet).await?);
+                    renumbered = true;
+                }
+            }
+        }
+
+        // the allocator's counter does not account for the renumbering
+        if renumbered {
+            self.nonces.reset(wallet);
+        }
+
+        let purchase_hashes: Vec<_> = sent_txs
+            .iter()
+            .filter(|sent| sent.token_id.is_some())
+            .map(|sent| sent.tx_hash)
+            .collect();
+        let receipts = if escalator.is_some() || deadline.is_some() {
+            self.watch_pending(&provider, sent, &purchase_hashes, escalator, deadline)
+                .await?
+        } else {
+            let mut receipts = Vec::new();
+            for tx_hash in purchase_hashes {
+                let receipt = PendingTransaction::new(tx_hash, self.provider.provider())
+                    .await
+                    .map_err(|err| ClientError::Provider(err.to_string()))?;
+                // a dropped tx has no receipt
+                if let Some(receipt) = receipt {
+                    receipts.push((tx_hash, receipt));
+                }
+            }
+            receipts
+        };
+        for (tx_hash, receipt) in receipts {
+            self.confirm_fill(&receipt).await;
+            if let Some(journal) = journal {
+                journal.update(tx_hash, JournalStatus::of(&receipt))?;
+            }
+        }
+        Ok(sent_txs)
+    }
+
+    /// Emits [`Event::FillConfirmed`] and counts the buy if the purchase tx of the
+    /// `receipt` succeeded
+    pub(crate) async fn confirm_fill(&self, receipt: &TransactionReceipt) {
+        if receipt.status != Some(1.into()) {
+            return;
+        }
+        metrics::buys_succeeded(1);
+        // the purchase already happened, a veto is meaningless
+        let _ = self
+            .emit(Event::FillConfirmed {
+                tx_hash: receipt.transaction_hash,
+                block: receipt.block_number,
+            })
+            .await;
+    }
+}
+
+/// The token bought by each tx of a bundle: `None` for the `num_payment_txs` first
+/// ones, then the token of each of the `purchases`. The trailing bribe tx, if any, is
+/// past the end.
+fn tx_labels(num_payment_txs: usize, purchases: &[PlannedPurchase]) -> Vec<Option<U256>> {
+    std::iter::repeat_n(None, num_payment_txs)
+        .chain(purchases.iter().map(|purchase| Some(purchase.token_id)))
+        .collect()
+}
+
+/// The order bought by each tx of a bundle, as the [`tx_labels`]
+fn tx_orders(num_payment_txs: usize, purchases: &[PlannedPurchase]) -> Vec<Option<H256>> {
+    std::iter::repeat_n(None, num_payment_txs)
+        .chain(purchases.iter().map(|purchase| Some(purchase.order_hash)))
+        .collect()
+}
+
+/// Removes the tx buying the order from the bundle, renumbering the nonces of the
+/// following txs. Returns the index of the removed tx, or `None` if it is the last
+/// purchase left to send.
+fn drop_order(
+    txs: &mut Vec<Eip1559TransactionRequest>,
+    labels: &mut Vec<Option<U256>>,
+    orders: &mut Vec<Option<H256>>,
+    order_hash: H256,
+) -> Option<usize> {
+    let index = orders
+        .iter()
+        .position(|order| *order == Some(order_hash))
+        .filter(|index| *index < txs.len())?;
+    if orders.iter().flatten().count() == 1 {
+        return None;
+    }
+    txs.remove(index);
+    orders.remove(index);
+    if index < labels.len() {
+        labels.remove(index);
+    }
+    for tx in txs[index..].iter_mut() {
+        tx.nonce = tx.nonce.map(|nonce| nonce - 1);
+    }
+    Some(index)
+}
+
+/// Sets the priority fee of the txs to the one the strategy allows for their `gas`,
+/// defaulting to the sum of their gas limits, at up to `max_base_fee`. The first txs
+/// cost their `costs` (see [`Client::purchase_costs`]), the following ones, e.g. the
+/// bribe tx, their value.
+fn price_for_profit(
+    strategy: &BribeStrategy,
+    txs: &mut [Eip1559TransactionRequest],
+    costs: &[U256],
+    max_base_fee: U256,
+    gas: Option<U256>,
+) -> Result<U256, Unprofitable> {
+    let cost = costs.iter().fold(U256::zero(), |acc, cost| acc + cost);
+    let cost = txs
+        .iter()
+        .skip(costs.len())
+        .fold(cost, |acc, tx| acc + tx.value.unwrap_or_default());
+    let gas = gas.unwrap_or_else(|| {
+        txs.iter()
+            .fold(U256::zero(), |acc, tx| acc + tx.gas.unwrap_or_default())
+    });
+    let priority_fee = strategy.priority_fee(cost, gas, max_base_fee)?;
+    for tx in txs.iter_mut() {
+        tx.max_fee_per_gas = Some(max_base_fee + priority_fee);
+        tx.max_priority_fee_per_gas = Some(priority_fee);
+    }
+    Ok(priority_fee)
+}
+
+/// Checks that the balance covers the values of the txs and their gas at the max fee,
+/// since the node would reject the txs (or the relay the bundle) otherwise
+fn check_balance(txs: &[Eip1559TransactionRequest], balance: U256) -> Result<(), SnipeError> {
+    let required = txs.iter().fold(U256::zero(), |acc, tx| {
+        acc + tx.value.unwrap_or_default()
+            + tx.gas.unwrap_or_default() * tx.max_fee_per_gas.unwrap_or_default()
+    });
+    if required > balance {
+        return Err(SnipeError::InsufficientBalance { required, balance });
+    }
+    Ok(())
+}
+
+/// The fees of the txs are set afterwards, so the gas price of the legacy ones is dropped
+fn into_eip1559(tx: TypedTransaction) -> Eip1559TransactionRequest {
+    let (tx, access_list) = match tx {
+        TypedTransaction::Eip1559(inner) => return inner,
+        TypedTransaction::Legacy(tx) => (tx, Default::default()),
+        TypedTransaction::Eip2930(inner) => (inner.tx, inner.access_list),
+    };
+    Eip1559TransactionRequest {
+        from: tx.from,
+        to: tx.to,
+        gas: tx.gas,
+        value: tx.value,
+        data: tx.data,
+        nonce: tx.nonce,
+        access_list,
+        max_priority_fee_per_gas: None,
+        max_fee_per_gas: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{OpenSeaApiError, OrderRequest},
+        MockOrderProvider,
+    };
+    use ethers::providers::Provider;
+    use ethers_flashbots::SimulatedTransaction;
+    use std::sync::Arc;
+
+    #[test]
+    fn reports_the_balance_shortfall() {
+        let tx = |value: u64, gas: u64, max_fee: u64| Eip1559TransactionRequest {
+            value: Some(value.into()),
+            gas: Some(gas.into()),
+            max_fee_per_gas: Some(max_fee.into()),
+            ..Default::default()
+        };
+        let txs = [tx(1000, 10, 5), tx(0, 20, 5)];
+        check_balance(&txs, 1150.into()).unwrap();
+        let err = check_balance(&txs, 1100.into()).unwrap_err();
+        assert!(matches!(
+            err,
+            SnipeError::InsufficientBalance { required, balance }
+                if required == 1150.into() && balance == 1100.into()
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The transactions require up to 1150 Wei including gas, 50 Wei more than the balance of 1100 Wei"
+        );
+    }
+
+    #[test]
+    fn converts_legacy_txs_to_eip1559() {
+        let legacy = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .value(2)
+            .gas(3)
+            .gas_price(4)
+            .nonce(5);
+        let tx = into_eip1559(legacy.into());
+        assert_eq!(tx.to, Some(Address::repeat_byte(1).into()));
+        assert_eq!(
+            (tx.value, tx.gas, tx.nonce),
+            (Some(2.into()), Some(3.into()), Some(5.into()))
+        );
+        assert_eq!(tx.max_fee_per_gas, None);
+    }
+
+    #[test]
+    fn targets_the_blocks_of_the_listing_window() {
+        let purchase = |listing_time, expiration_time| PlannedPurchase {
+            nft: Address::zero(),
+            token_id: 1.into(),
+            order_hash: H256::zero(),
+            price: 1.into(),
+            payment_token: Address::zero(),
+            listing_time,
+            expiration_time,
+        };
+        let window =
+            ListingWindow::of(&[purchase(900, 0), purchase(950, 1_100), purchase(0, 1_050)]);
+        assert_eq!(
+            window,
+            ListingWindow {
+                opens: 950,
+                closes: Some(1_050)
+            }
+        );
+        assert!(!window.contains(950));
+        assert!(window.contains(951));
+        assert!(!window.contains(1_050));
+
+        // head 100 at 1000: block 101 at 1012, ..., block 104 at 1048
+        let head = U64::from(100);
+        assert_eq!(
+            window.blocks(head, 1_000),
+            Some((101.into(), Some(104.into())))
+        );
+        // expires within the next block
+        assert_eq!(window.blocks(head, 1_040), None);
+        // listed in 2 minutes: block 111 at 1132 is the first one after it
+        let window = ListingWindow::of(&[purchase(1_120, 0)]);
+        assert_eq!(window.blocks(head, 1_000), Some((111.into(), None)));
+    }
+
+    #[test]
+    fn labels_the_txs_after_the_payment_ones() {
+        let purchase = |token_id: u64| PlannedPurchase {
+            nft: Address::zero(),
+            token_id: token_id.into(),
+            order_hash: H256::zero(),
+            price: 1.into(),
+            payment_token: Address::random(),
+            listing_time: 0,
+            expiration_time: 0,
+        };
+        let labels = tx_labels(2, &[purchase(7), purchase(9)]);
+        assert_eq!(labels, vec![None, None, Some(7.into()), Some(9.into())]);
+
+        // a swap, an approval, the purchases and the bribe
+        let simulated_tx = |hash: u64| SimulatedTransaction {
+            hash: H256::from_low_u64_be(hash),
+            coinbase_diff: U256::zero(),
+            coinbase_tip: U256::zero(),
+            gas_price: U256::zero(),
+            gas_used: 21_000.into(),
+            gas_fees: U256::zero(),
+            from: Address::random(),
+            to: Some(Address::random()),
+            value: U256::zero(),
+            error: None,
+        };
+        let simulated = SimulatedBundle {
+            hash: H256::zero(),
+            coinbase_diff: U256::zero(),
+            coinbase_tip: U256::zero(),
+            gas_price: U256::zero(),
+            gas_used: 105_000.into(),
+            gas_fees: U256::zero(),
+            simulation_block: 100.into(),
+            transactions: (1..=5).map(simulated_tx).collect(),
+        };
+        let report = BundleSimulationReport::new(&simulated, &labels);
+        let token_ids: Vec<_> = report.txs.iter().map(|tx| tx.token_id).collect();
+        assert_eq!(
+            token_ids,
+            vec![None, None, Some(7.into()), Some(9.into()), None]
+        );
+        assert_eq!(report.overhead_gas(), 63_000.into());
+    }
+
+    #[test]
+    fn prices_the_erc20_payments_for_profit() {
+        // 10 ETH resale value with a 10% margin
+        let eth = U256::exp10(18);
+        let strategy = BribeStrategy::new(eth * 10, 1_000);
+        let base_fee = U256::from(100e9 as u64);
+        let tx = |value: U256| Eip1559TransactionRequest::new().value(value).gas(500_000);
+        // a purchase for 5 ETH worth of held ERC20 tokens and one for 3 ETH
+        let mut txs = vec![tx(U256::zero()), tx(eth * 3)];
+        let costs = [eth * 5, eth * 3];
+
+        // 0.9 ETH left for the tips of the 1M gas, after its 0.1 ETH of base fee
+        let priority_fee = price_for_profit(&strategy, &mut txs, &costs, base_fee, None).unwrap();
+        assert_eq!(priority_fee, U256::from(900e9 as u64));
+        assert_eq!(txs[1].max_priority_fee_per_gas, Some(priority_fee));
+        // the ERC20 payment is not free, unlike when only the values are counted
+        let priority_fee = price_for_profit(&strategy, &mut txs, &[], base_fee, None).unwrap();
+        assert_eq!(priority_fee, U256::from(5_900e9 as u64));
+    }
+
+    #[test]
+    fn drops_the_cancelled_purchase_from_the_bundle() {
+        let purchase = |order: u64| PlannedPurchase {
+            nft: Address::zero(),
+            token_id: order.into(),
+            order_hash: H256::from_low_u64_be(order),
+            price: 1.into(),
+            payment_token: Address::zero(),
+            listing_time: 0,
+            expiration_time: 0,
+        };
+        let purchases = [purchase(7), purchase(9)];
+        // an approval, the purchases and the bribe
+        let mut txs: Vec<_> = (0..4u64)
+            .map(|nonce| Eip1559TransactionRequest::new().nonce(nonce))
+            .collect();
+        let mut labels = tx_labels(1, &purchases);
+        let mut orders = tx_orders(1, &purchases);
+
+        let cancelled = H256::from_low_u64_be(7);
+        assert_eq!(
+            drop_order(&mut txs, &mut labels, &mut orders, cancelled),
+            Some(1)
+        );
+        let nonces: Vec<_> = txs.iter().map(|tx| tx.nonce.unwrap()).collect();
+        assert_eq!(nonces, vec![0.into(), 1.into(), 2.into()]);
+        assert_eq!(labels, vec![None, Some(9.into())]);
+        assert_eq!(orders, vec![None, Some(H256::from_low_u64_be(9))]);
+
+        // the last purchase is not dropped, the bundle being aborted instead
+        let cancelled = H256::from_low_u64_be(9);
+        assert_eq!(
+            drop_order(&mut txs, &mut labels, &mut orders, cancelled),
+            None
+        );
+        assert_eq!(txs.len(), 3);
+    }
+
+    #[test]
+    fn verifies_the_ownership_of_several_collections() {
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let (erc721, erc1155, recipient) =
+            (Address::random(), Address::random(), Address::random());
+        let plan = SnipePlan::new(erc721).targets(vec![
+            SnipeTarget::new(1.into(), 1),
+            SnipeTarget::new(2.into(), 3).nft(erc1155),
+            SnipeTarget::new(3.into(), 1),
+        ]);
+        assert_eq!(plan.collections(), vec![erc721, erc1155]);
+
+        let args = BuyArgs {
+            token_id: 0.into(),
+            taker: recipient,
+            token: erc721,
+            recipient,
+            timestamp: None,
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let erc1155s = vec![(erc721, false), (erc1155, true)].into_iter().collect();
+        let calldata = client.ownership_calldata(&plan, &args, Address::zero(), &erc1155s);
+
+        let checks = BribeChecks::new()
+            .erc721_owner(erc721, 1.into(), recipient)
+            .erc1155_balance(erc1155, recipient, 2.into(), 3.into())
+            .erc721_owner(erc721, 3.into(), recipient);
+        assert_eq!(calldata, Some(checks.calldata()));
+    }
+
+    #[test]
+    fn sizes_the_bundles_by_atomicity() {
+        assert_eq!(
+            "all-or-nothing".parse::<Atomicity>().unwrap(),
+            Atomicity::AllOrNothing
+        );
+        assert_eq!(
+            "best-effort".parse::<Atomicity>().unwrap(),
+            Atomicity::BestEffort
+        );
+        assert_eq!(
+            "chunked:3".parse::<Atomicity>().unwrap(),
+            Atomicity::Chunked(3)
+        );
+        assert!("chunked:0".parse::<Atomicity>().is_err());
+        assert!("chunked".parse::<Atomicity>().is_err());
+
+        assert_eq!(Atomicity::AllOrNothing.chunk_size(5), 5);
+        assert_eq!(Atomicity::BestEffort.chunk_size(5), 1);
+        assert_eq!(Atomicity::Chunked(2).chunk_size(5), 2);
+    }
+
+    #[test]
+    fn skips_the_journaled_purchases() {
+        let dir = std::env::temp_dir().join(format!("opensea-resume-{}", std::process::id()));
+        let journal = BuyJournal::open(dir.join("journal.json")).unwrap();
+        let (nft, other) = (Address::random(), Address::random());
+        for (nft, token_id, status) in [
+            (nft, 1, JournalStatus::Mined),
+            (nft, 2, JournalStatus::Mined),
+            (other, 2, JournalStatus::Mined),
+            // not mined, so bought again
+            (nft, 3, JournalStatus::Sent),
+            (nft, 3, JournalStatus::Failed),
+        ] {
+            journal
+                .record(JournalEntry {
+                    nft,
+                    token_id: token_id.into(),
+                    order_hash: H256::random(),
+                    tx_hash: H256::random(),
+                    nonce: None,
+                    status,
+                })
+                .unwrap();
+        }
+
+        let plan = SnipePlan::new(nft)
+            .targets(vec![
+                SnipeTarget::new(1.into(), 1),
+                SnipeTarget::new(2.into(), 3),
+                SnipeTarget::new(3.into(), 1),
+                SnipeTarget::new(2.into(), 1).nft(other),
+            ])
+            .resume(journal);
+        let remaining: Vec<_> = plan
+            .targets
+            .iter()
+            .map(|target| plan.remaining(target))
+            .collect();
+        assert_eq!(remaining, vec![0, 2, 1, 0]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bids_on_the_english_auctions_of_the_targets() {
+        let mut sell = crate::testing::order();
+        sell.fee_recipient.address = Address::zero();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(
+            Arc::new(provider),
+            MockOrderProvider::new(vec![sell.clone()]),
+        );
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let expiry = Duration::from_secs(3600);
+
+        // no max price to bid
+        let plan = SnipePlan::new(asset.address).targets(vec![SnipeTarget::new(asset.id, 1)]);
+        let bids = client
+            .english_auction_bids(&plan, expiry, &wallet)
+            .await
+            .unwrap();
+        assert!(bids.is_empty());
+
+        // below the auction's price
+        let plan = plan.max_price(sell.current_price - 1);
+        let bids = client
+            .english_auction_bids(&plan, expiry, &wallet)
+            .await
+            .unwrap();
+        assert!(bids.is_empty());
+
+        let plan = plan.max_price(sell.current_price * 2);
+        let bids = client
+            .english_auction_bids(&plan, expiry, &wallet)
+            .await
+            .unwrap();
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].current_price, sell.current_price * 2);
+        assert!(bids[0].expiration_time > U256::from(unix_timestamp()));
+        assert_eq!(client.api.posted().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stops_when_aborted() {
+        let (provider, _) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let abort = AbortHandle::new();
+        let plan = SnipePlan::new(Address::random())
+            .targets(vec![SnipeTarget::new(1.into(), 1)])
+            .abort_handle(abort.clone());
+
+        abort.abort(AbortReason::Interrupted);
+        match client.snipe(&plan, wallet).await {
+            Err(SnipeError::Aborted { reason, progress }) => {
+                assert_eq!(reason, AbortReason::Interrupted);
+                assert_eq!(progress.stage, SnipeStage::Fetching);
+                assert!(progress.sent.is_empty());
+            }
+            res => panic!("not aborted: {:?}", res),
+        }
+    }
+
+    /// Order provider whose queries never resolve, e.g. a hung API
+    struct HungOrderProvider;
+
+    #[async_trait::async_trait]
+    impl OrderProvider for HungOrderProvider {
+        async fn get_orders(&self, _: OrderRequest) -> Result<Vec<Order>, OpenSeaApiError> {
+            futures::future::pending().await
+        }
+
+        async fn get_order_by_hash(&self, _: H256) -> Result<Order, OpenSeaApiError> {
+            futures::future::pending().await
+        }
+
+        async fn post_order(&self, _: MinimalOrder) -> Result<(), OpenSeaApiError> {
+            futures::future::pending().await
+        }
+
+        async fn get_assets(
+            &self,
+            _: crate::api::AssetsRequest,
+        ) -> Result<Vec<crate::types::CollectionAsset>, OpenSeaApiError> {
+            futures::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_when_aborted_midway() {
+        let (provider, mock) = Provider::mocked();
+        // in reverse: the nonces re-synced after the abort, then the latest block for
+        // the fees and for the listing time
+        let block = ethers::types::Block::<H256> {
+            base_fee_per_gas: Some(1.into()),
+            ..Default::default()
+        };
+        mock.push(U256::from(3)).unwrap();
+        mock.push(block.clone()).unwrap();
+        mock.push(block).unwrap();
+        let client = Client::with_api(Arc::new(provider), HungOrderProvider);
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let abort = AbortHandle::new();
+        let plan = SnipePlan::new(Address::random())
+            .targets(vec![SnipeTarget::new(1.into(), 1)])
+            .abort_handle(abort.clone());
+
+        let trigger = abort.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            trigger.abort(AbortReason::CancellationDetected {
+                order_hash: H256::from_low_u64_be(1),
+                tx_hash: H256::from_low_u64_be(2),
+            });
+        });
+        match client.snipe(&plan, wallet).await {
+            Err(SnipeError::Aborted { reason, progress }) => {
+                assert!(matches!(reason, AbortReason::CancellationDetected { .. }));
+                assert_eq!(progress.stage, SnipeStage::Fetching);
+            }
+            res => panic!("not aborted: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Wyvern `staticCall` predicates. An order with a `static_target` is only matched if the
+//! static call of the target with the order's `static_extradata` followed by the matched
+//! calldata succeeds, e.g. to check some on-chain state when the order is filled. The
+//! exchange makes the call after the transfer, so [`Client::verify_static_calls`] can only
+//! tell whether the predicate currently holds.
+use ethers::{
+    abi::{self, Token},
+    providers::Middleware,
+    types::{Address, Bytes},
+    utils::id,
+};
+
+use crate::{approvals::UnfulfillableReason, types::MinimalOrder, Client, ClientError};
+
+/// The `staticCall` predicate of an order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticCall {
+    /// The contract called
+    pub target: Address,
+    /// The data the matched calldata is appended to
+    pub extradata: Bytes,
+}
+
+impl StaticCall {
+    pub fn new(target: Address, extradata: impl Into<Bytes>) -> Self {
+        Self {
+            target,
+            extradata: extradata.into(),
+        }
+    }
+
+    /// A call of the `target`'s function with the leading `args`, e.g.
+    /// `check(uint256,bytes)` with the first `uint256` only. The matched calldata is
+    /// appended to them as is, so the remaining arguments must decode from it.
+    pub fn function(target: Address, signature: &str, args: &[Token]) -> Self {
+        let mut extradata = id(signature).to_vec();
+        extradata.extend(abi::encode(args));
+        Self::new(target, extradata)
+    }
+
+    /// The data the exchange calls the target with for the matched `calldata`
+    pub fn data(&self, calldata: &[u8]) -> Bytes {
+        [self.extradata.as_ref(), calldata].concat().into()
+    }
+}
+
+impl MinimalOrder {
+    /// The `staticCall` predicate of the order, if it has one
+    pub fn static_call(&self) -> Option<StaticCall> {
+        if self.static_target.is_zero() {
+            return None;
+        }
+        Some(StaticCall::new(
+            self.static_target,
+            self.static_extradata.clone(),
+        ))
+    }
+
+    /// Sets the `staticCall` predicate of the order, or removes it if `None`. Changes
+    /// the order's hash, so it must be set before the order is signed.
+    pub fn with_static_call(mut self, static_call: Option<StaticCall>) -> Self {
+        let static_call =
+            static_call.unwrap_or_else(|| StaticCall::new(Address::zero(), Vec::new()));
+        self.static_target = static_call.target;
+        self.static_extradata = static_call.extradata;
+        self
+    }
+}
+
+/// The calldata of the transfer once each order's replacement pattern is applied, which
+/// the exchange passes to the static calls of both orders
+pub fn matched_calldata(buy: &MinimalOrder, sell: &MinimalOrder) -> Bytes {
+    // the buy's calldata is replaced first, then the sell's with the replaced buy's
+    let buy_calldata = guarded_replace(
+        buy.calldata.as_ref(),
+        sell.calldata.as_ref(),
+        buy.replacement_pattern.as_ref(),
+    );
+    guarded_replace(
+        sell.calldata.as_ref(),
+        &buy_calldata,
+        sell.replacement_pattern.as_ref(),
+    )
+    .into()
+}
+
+/// Wyvern's `ArrayUtils.guardedArrayReplace`: the bits of `array` set in the `mask` are
+/// replaced with those of `desired`
+fn guarded_replace(array: &[u8], desired: &[u8], mask: &[u8]) -> Vec<u8> {
+    array
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| match (mask.get(i), desired.get(i)) {
+            (Some(mask), Some(desired)) => (byte & !mask) | (desired & mask),
+            _ => *byte,
+        })
+        .collect()
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Checks that the `staticCall` predicates of the orders, if any, currently succeed
+    /// for their match, via the exchange's `staticCall`. Fails with
+    /// `ClientError::Unfulfillable` for the first one which does not.
+    pub async fn verify_static_calls(
+        &self,
+        buy: &MinimalOrder,
+        sell: &MinimalOrder,
+    ) -> Result<(), ClientError> {
+        let calldata = matched_calldata(buy, sell);
+        for order in &[buy, sell] {
+            let static_call = match order.static_call() {
+                Some(static_call) => static_call,
+                None => continue,
+            };
+            let succeeded = self
+                .contracts
+                .static_call(
+                    static_call.target,
+                    calldata.to_vec(),
+                    static_call.extradata.to_vec(),
+                )
+                .call()
+                .await
+                .map_err(|err| ClientError::Contract(err.to_string()))?;
+            if !succeeded {
+                return Err(ClientError::Unfulfillable {
+                    order_hash: sell.hash(),
+                    reason: UnfulfillableReason::StaticCallFailed(static_call.target),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuyArgs, MockOrderProvider};
+    use ethers::providers::Provider;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn verifies_static_calls() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let taker = Address::random();
+        let buy = sell
+            .match_sell(BuyArgs {
+                token_id: asset.id,
+                taker,
+                token: asset.address,
+                recipient: taker,
+                timestamp: Some(0),
+                quantity: None,
+                max_price: None,
+                total_budget: None,
+            })
+            .unwrap();
+        let sell = MinimalOrder::from(sell);
+
+        // the token is transferred from the seller to the buyer
+        let calldata = matched_calldata(&buy, &sell);
+        assert_eq!(calldata.as_ref()[16..36], sell.maker[..]);
+        assert_eq!(calldata.as_ref()[48..68], taker[..]);
+
+        let predicate = StaticCall::function(
+            Address::random(),
+            "check(uint256,bytes)",
+            &[Token::Uint(7.into())],
+        );
+        assert_eq!(predicate.extradata.as_ref().len(), 4 + 32);
+        assert_eq!(
+            predicate.data(calldata.as_ref()).as_ref()[4 + 32..],
+            calldata.as_ref()[..]
+        );
+        let plain = sell.clone();
+        let sell = sell.with_static_call(Some(predicate.clone()));
+        assert_eq!(sell.static_call(), Some(predicate.clone()));
+        assert_ne!(sell.hash(), plain.hash());
+        assert_eq!(sell.clone().with_static_call(None).hash(), plain.hash());
+
+        // orders without predicates are not checked
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::new(Vec::new()));
+        let encode = |result| Bytes::from(abi::encode(&[Token::Bool(result)]));
+        client.verify_static_calls(&buy, &plain).await.unwrap();
+
+        mock.push(encode(true)).unwrap();
+        client.verify_static_calls(&buy, &sell).await.unwrap();
+        mock.push(encode(false)).unwrap();
+        match client.verify_static_calls(&buy, &sell).await {
+            Err(ClientError::Unfulfillable {
+                order_hash,
+                reason: UnfulfillableReason::StaticCallFailed(target),
+            }) => {
+                assert_eq!(order_hash, sell.hash());
+                assert_eq!(target, predicate.target);
+            }
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! OpenSea's shared storefront (OPENSTORE), the ERC1155 collection in which the items
+//! created on the site without a contract of their own are minted. Its token ids pack
+//! the creator, an index and the max supply of the item, and its items get lazily
+//! minted by the transfer of their first sale.
+use ethers::types::{Address, U256};
+
+use crate::constants;
+
+/// Whether the collection is the shared storefront
+pub fn is_shared_storefront(nft: Address) -> bool {
+    nft == *constants::SHARED_STOREFRONT_ADDRESS
+}
+
+/// A token id of the shared storefront, `creator (160 bits) | index (56 bits) | max
+/// supply (40 bits)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SharedStorefrontId {
+    pub creator: Address,
+    pub index: u64,
+    pub max_supply: u64,
+}
+
+impl From<U256> for SharedStorefrontId {
+    fn from(id: U256) -> Self {
+        let mut bytes = [0; 32];
+        id.to_big_endian(&mut bytes);
+        Self {
+            creator: Address::from_slice(&bytes[..20]),
+            index: (id >> 40).low_u64() & ((1 << 56) - 1),
+            max_supply: id.low_u64() & ((1 << 40) - 1),
+        }
+    }
+}
+
+impl From<SharedStorefrontId> for U256 {
+    fn from(id: SharedStorefrontId) -> Self {
+        (U256::from_big_endian(id.creator.as_bytes()) << 96)
+            | (U256::from(id.index) << 40)
+            | U256::from(id.max_supply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpacks_token_ids() {
+        // the 4th item of its creator, a 1 of 1
+        let id = U256::from_dec_str(
+            "69710270157389188999893699027175589720623844558409151142536691663319699816449",
+        )
+        .unwrap();
+        let unpacked = SharedStorefrontId::from(id);
+        assert_eq!(
+            unpacked.creator,
+            "0x9a1e9d5c56b4e5dcec2bd58e7a37ba8b0e8a1f5d"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert_eq!(unpacked.index, 3);
+        assert_eq!(unpacked.max_supply, 1);
+        assert_eq!(U256::from(unpacked), id);
+    }
+}
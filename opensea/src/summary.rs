@@ -0,0 +1,161 @@
+//! Summary of the funds a [`SnipePlan`](crate::SnipePlan) puts at risk, presented
+//! for confirmation before its transactions are submitted
+use ethers::types::{Address, Eip1559TransactionRequest, H256, U256};
+use futures::future::BoxFuture;
+use std::{fmt, sync::Arc};
+
+use crate::{address_book, display::short_address};
+
+/// A listing to be bought by the plan
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedPurchase {
+    pub nft: Address,
+    pub token_id: U256,
+    pub order_hash: H256,
+    /// In the payment token of the listing
+    pub price: U256,
+    /// The zero address is ETH
+    pub payment_token: Address,
+    /// The unix timestamp the listing can be filled after
+    pub listing_time: u64,
+    /// The unix timestamp the listing expires at, never if 0
+    pub expiration_time: u64,
+}
+
+/// The purchases of a plan and the worst case cost of its transactions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnipeSummary {
+    pub purchases: Vec<PlannedPurchase>,
+    pub num_txs: usize,
+    /// The highest max fee per gas of the txs
+    pub max_fee_per_gas: U256,
+    /// The highest max priority fee per gas of the txs
+    pub max_priority_fee_per_gas: U256,
+    /// The sum of the gas limits of the txs
+    pub gas_limit: U256,
+    /// The cost of the txs if they use all their gas at their max fee
+    pub gas_ceiling: U256,
+    /// The ETH sent with the txs, including the payment swaps and the bribe tx
+    pub value: U256,
+}
+
+impl SnipeSummary {
+    pub fn new(purchases: Vec<PlannedPurchase>, txs: &[Eip1559TransactionRequest]) -> Self {
+        let mut summary = Self {
+            purchases,
+            num_txs: txs.len(),
+            max_fee_per_gas: U256::zero(),
+            max_priority_fee_per_gas: U256::zero(),
+            gas_limit: U256::zero(),
+            gas_ceiling: U256::zero(),
+            value: U256::zero(),
+        };
+        for tx in txs {
+            let gas = tx.gas.unwrap_or_default();
+            let max_fee = tx.max_fee_per_gas.unwrap_or_default();
+            summary.max_fee_per_gas = summary.max_fee_per_gas.max(max_fee);
+            summary.max_priority_fee_per_gas = summary
+                .max_priority_fee_per_gas
+                .max(tx.max_priority_fee_per_gas.unwrap_or_default());
+            summary.gas_limit += gas;
+            summary.gas_ceiling += gas * max_fee;
+            summary.value += tx.value.unwrap_or_default();
+        }
+        summary
+    }
+
+    /// The most ETH the txs can cost, i.e. their value and gas ceiling. Listings
+    /// priced in ERC20 tokens are paid from the taker's balance of the token.
+    pub fn total_at_risk(&self) -> U256 {
+        self.value + self.gas_ceiling
+    }
+}
+
+impl fmt::Display for SnipeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<44} {:<24} {:<28} {:<44} Order",
+            "Collection", "Token Id", "Price (Wei)", "Payment Token"
+        )?;
+        for purchase in &self.purchases {
+            let token = if purchase.payment_token.is_zero() {
+                "ETH".to_owned()
+            } else {
+                labelled(purchase.payment_token)
+            };
+            writeln!(
+                f,
+                "{:<44} {:<24} {:<28} {:<44} {:?}",
+                labelled(purchase.nft),
+                purchase.token_id.to_string(),
+                purchase.price.to_string(),
+                token,
+                purchase.order_hash
+            )?;
+        }
+        writeln!(f, "Transactions:            {}", self.num_txs)?;
+        writeln!(f, "Max fee per gas:         {} Wei", self.max_fee_per_gas)?;
+        writeln!(
+            f,
+            "Max priority fee per gas: {} Wei",
+            self.max_priority_fee_per_gas
+        )?;
+        writeln!(
+            f,
+            "Gas ceiling:             {} Wei ({} gas)",
+            self.gas_ceiling, self.gas_limit
+        )?;
+        writeln!(f, "Value sent:              {} Wei", self.value)?;
+        write!(f, "Total ETH at risk:       {} Wei", self.total_at_risk())
+    }
+}
+
+/// The label of the address (see [`address_book`](crate::address_book)), or the full
+/// address in the summary to be confirmed
+fn labelled(address: Address) -> String {
+    match address_book::label(address) {
+        Some(label) => format!("{} ({})", label, short_address(address)),
+        None => format!("{:?}", address),
+    }
+}
+
+/// Decides whether to submit the txs of a plan, see [`SnipePlan::confirm`](crate::SnipePlan::confirm)
+#[derive(Clone)]
+pub(crate) struct Confirmation(pub(crate) Arc<ConfirmFn>);
+
+type ConfirmFn = dyn Fn(&SnipeSummary) -> BoxFuture<'static, bool> + Send + Sync;
+
+impl fmt::Debug for Confirmation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Confirmation")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_the_worst_case_cost() {
+        let txs = vec![
+            Eip1559TransactionRequest::new()
+                .gas(300_000)
+                .max_fee_per_gas(100)
+                .max_priority_fee_per_gas(2)
+                .value(1_000),
+            Eip1559TransactionRequest::new()
+                .gas(200_000)
+                .max_fee_per_gas(150)
+                .max_priority_fee_per_gas(1)
+                .value(10),
+        ];
+        let summary = SnipeSummary::new(Vec::new(), &txs);
+        assert_eq!(summary.num_txs, 2);
+        assert_eq!(summary.max_fee_per_gas, 150.into());
+        assert_eq!(summary.max_priority_fee_per_gas, 2.into());
+        assert_eq!(summary.gas_limit, 500_000.into());
+        assert_eq!(summary.gas_ceiling, (30_000_000 + 30_000_000).into());
+        assert_eq!(summary.total_at_risk(), (60_000_000 + 1_010).into());
+    }
+}
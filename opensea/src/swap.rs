@@ -0,0 +1,333 @@
+use ethers::{
+    abi::{encode, Token},
+    core::utils::id,
+    providers::Middleware,
+    types::{Address, Bytes, Eip1559TransactionRequest, U256},
+};
+
+use std::fmt;
+
+use crate::{
+    constants,
+    contracts::{UniswapV3Quoter, ERC20},
+    units::format_eth,
+    Client, ClientError,
+};
+
+/// Configuration for swapping ETH to a listing's ERC20 payment token via Uniswap V3
+#[derive(Clone, Debug)]
+pub struct SwapConfig {
+    /// The fee tier of the WETH / payment token pool, in hundredths of a bip
+    pub pool_fee: u32,
+    /// The maximum slippage over the quoted ETH input, in basis points
+    pub slippage_bps: u64,
+    /// The unix timestamp after which the swap reverts
+    pub deadline: u64,
+}
+
+impl Default for SwapConfig {
+    fn default() -> Self {
+        Self {
+            pool_fee: 3000,
+            slippage_bps: 50,
+            deadline: u64::MAX,
+        }
+    }
+}
+
+/// A swap of ETH for the exact amount of a payment token that is missing
+#[derive(Clone, Debug)]
+pub struct PaymentSwap {
+    /// The payment token being bought
+    pub token: Address,
+    /// The amount of the payment token missing from the wallet
+    pub amount_out: U256,
+    /// The quoted amount of ETH required
+    pub amount_in: U256,
+    /// The maximum amount of ETH that may be spent, after slippage
+    pub max_amount_in: U256,
+    /// The transaction executing the swap, refunding any unused ETH
+    pub tx: Eip1559TransactionRequest,
+}
+
+/// Preview of the total cost in ETH of a purchase whose ERC20 payments are swapped from ETH. The
+/// payment tokens already held are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PaymentPreview {
+    /// The price of the listings paid in ETH
+    pub eth: U256,
+    /// The ETH quoted for the swaps
+    pub quoted: U256,
+    /// The maximum ETH spent on the swaps, after slippage
+    pub max_swapped: U256,
+}
+
+impl PaymentPreview {
+    /// The cost of the listings paid in `eth` and of the `swaps`
+    pub fn new(eth: U256, swaps: &[PaymentSwap]) -> Self {
+        swaps.iter().fold(
+            Self {
+                eth,
+                ..Default::default()
+            },
+            |cost, swap| Self {
+                quoted: cost.quoted + swap.amount_in,
+                max_swapped: cost.max_swapped + swap.max_amount_in,
+                ..cost
+            },
+        )
+    }
+
+    /// The cost at the quoted swap prices
+    pub fn total(&self) -> U256 {
+        self.eth + self.quoted
+    }
+
+    /// The cost at the worst swap prices
+    pub fn max_total(&self) -> U256 {
+        self.eth + self.max_swapped
+    }
+}
+
+impl fmt::Display for PaymentPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} in ETH, {} swapped), up to {} after slippage",
+            format_eth(self.total()),
+            format_eth(self.eth),
+            format_eth(self.quoted),
+            format_eth(self.max_total())
+        )
+    }
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// The ETH worth `amount` of the `payment_token`, as quoted by its WETH pool with the
+    /// `pool_fee` tier. WETH is worth as much ETH.
+    pub async fn eth_value(
+        &self,
+        payment_token: Address,
+        amount: U256,
+        pool_fee: u32,
+    ) -> Result<U256, ClientError> {
+        if payment_token.is_zero() || payment_token == self.deployment.weth {
+            return Ok(amount);
+        }
+        let quoter =
+            UniswapV3Quoter::new(*constants::UNISWAP_V3_QUOTER_ADDRESS, self.provider.clone());
+        quoter
+            .quote_exact_output_single(
+                self.deployment.weth,
+                payment_token,
+                pool_fee,
+                amount,
+                U256::zero(),
+            )
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))
+    }
+
+    /// Returns the swap required for `buyer` to be able to pay `amount` of `payment_token`.
+    /// Returns `None` if the payment is in ETH or if the buyer already holds enough of
+    /// the token.
+    pub async fn payment_swap(
+        &self,
+        buyer: Address,
+        payment_token: Address,
+        amount: U256,
+        cfg: &SwapConfig,
+    ) -> Result<Option<PaymentSwap>, ClientError> {
+        if payment_token.is_zero() {
+            return Ok(None);
+        }
+
+        let balance = ERC20::new(payment_token, self.provider.clone())
+            .balance_of(buyer)
+            .call()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        if balance >= amount {
+            return Ok(None);
+        }
+        let amount_out = amount - balance;
+
+        let amount_in = self
+            .eth_value(payment_token, amount_out, cfg.pool_fee)
+            .await?;
+        let max_amount_in = amount_in + amount_in * cfg.slippage_bps / 10_000;
+
+        let data = swap_calldata(
+            self.deployment.weth,
+            buyer,
+            payment_token,
+            amount_out,
+            max_amount_in,
+            cfg,
+        );
+        let tx = Eip1559TransactionRequest::new()
+            .from(buyer)
+            .to(*constants::UNISWAP_V3_ROUTER_ADDRESS)
+            .value(max_amount_in)
+            .data(data);
+
+        Ok(Some(PaymentSwap {
+            token: payment_token,
+            amount_out,
+            amount_in,
+            max_amount_in,
+            tx,
+        }))
+    }
+}
+
+/// Encodes `multicall([exactOutputSingle(..), refundETH()])` on the Uniswap V3 router
+fn swap_calldata(
+    weth: Address,
+    recipient: Address,
+    token_out: Address,
+    amount_out: U256,
+    max_amount_in: U256,
+    cfg: &SwapConfig,
+) -> Bytes {
+    let params = Token::Tuple(vec![
+        Token::Address(weth),
+        Token::Address(token_out),
+        Token::Uint(cfg.pool_fee.into()),
+        Token::Address(recipient),
+        Token::Uint(cfg.deadline.into()),
+        Token::Uint(amount_out),
+        Token::Uint(max_amount_in),
+        Token::Uint(U256::zero()),
+    ]);
+    let swap = with_selector(
+        "exactOutputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+        &[params],
+    );
+    let refund = with_selector("refundETH()", &[]);
+
+    with_selector(
+        "multicall(bytes[])",
+        &[Token::Array(vec![Token::Bytes(swap), Token::Bytes(refund)])],
+    )
+    .into()
+}
+
+fn with_selector(signature: &str, tokens: &[Token]) -> Vec<u8> {
+    let mut data = id(signature).to_vec();
+    data.extend(encode(tokens));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::providers::{MockProvider, Provider};
+    use std::sync::Arc;
+
+    #[test]
+    fn encodes_swap_multicall() {
+        let data = swap_calldata(
+            *constants::WETH_ADDRESS,
+            Address::random(),
+            Address::random(),
+            100.into(),
+            110.into(),
+            &SwapConfig::default(),
+        );
+        // multicall(bytes[])
+        assert_eq!(data.as_ref()[..4], [0xac, 0x96, 0x50, 0xd8]);
+    }
+
+    fn client() -> (
+        Client<Provider<MockProvider>, MockOrderProvider>,
+        MockProvider,
+    ) {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        (client, mock)
+    }
+
+    fn encode_uint(value: u64) -> Bytes {
+        encode(&[Token::Uint(value.into())]).into()
+    }
+
+    #[tokio::test]
+    async fn swaps_the_missing_amount() {
+        let (client, mock) = client();
+        let buyer = Address::random();
+        let token = Address::random();
+        // in reverse: the quote, then the balance
+        mock.push(encode_uint(1_000)).unwrap();
+        mock.push(encode_uint(40)).unwrap();
+
+        let swap = client
+            .payment_swap(buyer, token, 100.into(), &SwapConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(swap.token, token);
+        assert_eq!(swap.amount_out, 60.into());
+        assert_eq!(swap.amount_in, 1_000.into());
+        assert_eq!(swap.max_amount_in, 1_005.into());
+        assert_eq!(swap.tx.value, Some(1_005.into()));
+        assert_eq!(
+            swap.tx.to,
+            Some((*constants::UNISWAP_V3_ROUTER_ADDRESS).into())
+        );
+    }
+
+    #[tokio::test]
+    async fn values_the_payment_tokens_in_eth() {
+        let (client, mock) = client();
+        mock.push(encode_uint(1_000)).unwrap();
+        let value = client
+            .eth_value(Address::random(), 100.into(), 3000)
+            .await
+            .unwrap();
+        assert_eq!(value, 1_000.into());
+
+        // not quoted
+        let weth = client.deployment.weth;
+        let value = client.eth_value(weth, 100.into(), 3000).await.unwrap();
+        assert_eq!(value, 100.into());
+    }
+
+    #[tokio::test]
+    async fn does_not_swap_held_tokens_or_eth() {
+        let (client, mock) = client();
+        let buyer = Address::random();
+        mock.push(encode_uint(100)).unwrap();
+        let swap = client
+            .payment_swap(buyer, Address::random(), 100.into(), &SwapConfig::default())
+            .await
+            .unwrap();
+        assert!(swap.is_none());
+
+        // not even queried
+        let swap = client
+            .payment_swap(buyer, Address::zero(), 100.into(), &SwapConfig::default())
+            .await
+            .unwrap();
+        assert!(swap.is_none());
+    }
+
+    #[test]
+    fn sums_the_effective_cost() {
+        let swap = |amount_in: u64, max_amount_in: u64| PaymentSwap {
+            token: Address::random(),
+            amount_out: 1.into(),
+            amount_in: amount_in.into(),
+            max_amount_in: max_amount_in.into(),
+            tx: Eip1559TransactionRequest::new(),
+        };
+        let cost = PaymentPreview::new(10.into(), &[swap(100, 105), swap(200, 210)]);
+        assert_eq!(cost.quoted, 300.into());
+        assert_eq!(cost.max_swapped, 315.into());
+        assert_eq!(cost.total(), 310.into());
+        assert_eq!(cost.max_total(), 325.into());
+        assert_eq!(PaymentPreview::new(10.into(), &[]).max_total(), 10.into());
+    }
+}
@@ -0,0 +1,331 @@
+//! Harness for integration testing `atomicMatch_` deterministically. The orders of the
+//! API go stale on a forked chain, so [`Listing`] fabricates Wyvern sell orders signed
+//! by a local wallet, e.g. one of [`ANVIL_PRIVATE_KEYS`] whose account made the
+//! proxy approvals via [`approve_maker`]. [`rinkeby_client`] targets the Rinkeby
+//! exchange and orderbook instead.
+use ethers::{
+    abi::Token,
+    core::utils::id,
+    providers::Middleware,
+    signers::{LocalWallet, Signer},
+    types::{Address, Bytes, H256, U256},
+};
+use std::sync::Arc;
+
+use crate::{
+    api::OpenSeaApiConfig,
+    constants,
+    types::{MinimalOrder, Network, Order},
+    Client, ClientError, OpenSeaApi, OrderProvider, TokenStandard,
+};
+
+/// The private keys of the first accounts of anvil's (and hardhat's) default mnemonic
+pub const ANVIL_PRIVATE_KEYS: [&str; 2] = [
+    "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+];
+
+/// The wallet of the `index`th account of anvil's default mnemonic
+pub fn anvil_wallet(index: usize) -> LocalWallet {
+    ANVIL_PRIVATE_KEYS[index].parse().unwrap()
+}
+
+/// The mainnet listing of the `order.json` fixture, as returned by the API
+pub const ORDER_JSON: &str = include_str!("../../order.json");
+
+/// The listing of [`ORDER_JSON`]
+pub fn order() -> Order {
+    serde_json::from_str(ORDER_JSON).unwrap()
+}
+
+/// The raw JSON of [`ORDER_JSON`], to tweak its fields before parsing it
+pub fn order_json() -> serde_json::Value {
+    serde_json::from_str(ORDER_JSON).unwrap()
+}
+
+/// Instantiates a client for the Rinkeby deployment and orderbook
+pub fn rinkeby_client<M: Middleware>(provider: Arc<M>) -> Result<Client<M>, ClientError> {
+    let cfg = OpenSeaApiConfig {
+        network: Network::Rinkeby,
+        ..Default::default()
+    };
+    Ok(Client::with_api(provider, OpenSeaApi::new(cfg)?).with_deployment(*constants::RINKEBY))
+}
+
+/// Registers the Wyvern proxy of `maker` if needed and approves it to transfer their
+/// tokens of the `nft` collection, sending the txs from `maker`, e.g. an unlocked
+/// account of the node.
+pub async fn approve_maker<M: Middleware + 'static, P: OrderProvider>(
+    client: &Client<M, P>,
+    maker: Address,
+    nft: Address,
+) -> Result<(), ClientError> {
+    if let Some(call) = client.register_proxy(maker).await? {
+        let tx = call
+            .send()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        tx.await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+    }
+    if let Some(call) = client.ensure_nft_approval(nft, maker).await? {
+        let tx = call
+            .send()
+            .await
+            .map_err(|err| ClientError::Contract(err.to_string()))?;
+        tx.await
+            .map_err(|err| ClientError::Provider(err.to_string()))?;
+    }
+    Ok(())
+}
+
+/// A fixed price ETH listing fabricated locally, as OpenSea would create it
+#[derive(Clone, Debug)]
+pub struct Listing {
+    pub exchange: Address,
+    pub nft: Address,
+    pub token_id: U256,
+    pub standard: TokenStandard,
+    /// The number of tokens listed, for ERC1155s
+    pub quantity: U256,
+    /// In Wei
+    pub price: U256,
+    pub listing_time: u64,
+    /// Never expires if 0
+    pub expiration_time: u64,
+}
+
+impl Listing {
+    /// A listing of a single token on the mainnet exchange, listed since the epoch
+    pub fn new(nft: Address, token_id: U256, standard: TokenStandard, price: U256) -> Self {
+        Self {
+            exchange: *constants::OPENSEA_ADDRESS,
+            nft,
+            token_id,
+            standard,
+            quantity: U256::one(),
+            price,
+            listing_time: 0,
+            expiration_time: 0,
+        }
+    }
+
+    pub fn exchange(mut self, exchange: Address) -> Self {
+        self.exchange = exchange;
+        self
+    }
+
+    pub fn quantity(mut self, quantity: U256) -> Self {
+        self.quantity = quantity;
+        self
+    }
+
+    pub fn listing_time(mut self, listing_time: u64) -> Self {
+        self.listing_time = listing_time;
+        self
+    }
+
+    pub fn expiration_time(mut self, expiration_time: u64) -> Self {
+        self.expiration_time = expiration_time;
+        self
+    }
+
+    /// Builds the sell order of the listing made by `maker` and signs it, returning it
+    /// as the API would
+    pub async fn sign(&self, maker: &LocalWallet) -> Result<Order, ClientError> {
+        let mut order = self.order(maker.address());
+        let hash = MinimalOrder::from(order.clone()).hash();
+        let signature = maker
+            .sign_message(hash.as_bytes())
+            .await
+            .map_err(|err| ClientError::Signer(err.to_string()))?;
+        order.order_hash = hash;
+        order.v = signature.v;
+        signature.r.to_big_endian(order.r.as_bytes_mut());
+        signature.s.to_big_endian(order.s.as_bytes_mut());
+        Ok(order)
+    }
+
+    /// The unsigned sell order, transferring the token from `maker` to the buyer's
+    /// address set via the replacement pattern
+    fn order(&self, maker: Address) -> Order {
+        let (calldata, schema) = match self.standard {
+            TokenStandard::Erc721 => {
+                let mut calldata = id("transferFrom(address,address,uint256)").to_vec();
+                calldata.extend(ethers::abi::encode(&[
+                    Token::Address(maker),
+                    Token::Address(Address::zero()),
+                    Token::Uint(self.token_id),
+                ]));
+                (calldata, "ERC721")
+            }
+            TokenStandard::Erc1155 => {
+                let mut calldata =
+                    id("safeTransferFrom(address,address,uint256,uint256,bytes)").to_vec();
+                calldata.extend(ethers::abi::encode(&[
+                    Token::Address(maker),
+                    Token::Address(Address::zero()),
+                    Token::Uint(self.token_id),
+                    Token::Uint(self.quantity),
+                    Token::Bytes(Vec::new()),
+                ]));
+                (calldata, "ERC1155")
+            }
+        };
+        // the buyer replaces the `to` address, the second word of the arguments
+        let mut replacement_pattern = vec![0; calldata.len()];
+        replacement_pattern[4 + 32..4 + 64].copy_from_slice(&[0xff; 32]);
+
+        let user = |address: Address| serde_json::json!({ "user": null, "address": address });
+        let order = serde_json::json!({
+            "id": 0,
+            "asset": {},
+            "listing_time": self.listing_time,
+            "expiration_time": self.expiration_time,
+            "order_hash": H256::zero(),
+            "v": 0,
+            "r": H256::zero(),
+            "s": H256::zero(),
+            "base_price": self.price.to_string(),
+            "current_price": self.price.to_string(),
+            "side": 1,
+            "sale_kind": 0,
+            "target": self.nft,
+            "how_to_call": 0,
+            "approved_on_chain": false,
+            "cancelled": false,
+            "finalized": false,
+            "marked_invalid": false,
+            "fee_recipient": user(*constants::OPENSEA_FEE_RECIPIENT),
+            "maker": user(maker),
+            "salt": U256::from(ethers::core::rand::random::<u64>()).to_string(),
+            "payment_token": Address::zero(),
+            "extra": "0",
+            "maker_protocol_fee": "0",
+            "maker_relayer_fee": "250",
+            "maker_referrer_fee": "0",
+            "taker_protocol_fee": "0",
+            "taker_relayer_fee": "0",
+            "calldata": Bytes::from(calldata),
+            "replacement_pattern": Bytes::from(replacement_pattern),
+            "static_target": Address::zero(),
+            "static_extradata": "0x",
+            "exchange": self.exchange,
+            "taker": user(Address::zero()),
+            "quantity": self.quantity.to_string(),
+            "metadata": {
+                "asset": { "id": self.token_id.to_string(), "address": self.nft },
+                "schema": schema,
+            },
+            "fee_method": 1,
+        });
+        serde_json::from_value(order).expect("valid order")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{contracts::NFT, BuyArgs, MockOrderProvider};
+    use ethers::{
+        providers::{Http, JsonRpcClient, Provider},
+        types::{TransactionRequest, U64},
+    };
+    use std::convert::TryFrom;
+
+    #[tokio::test]
+    async fn matches_synthetic_listings() {
+        let maker = anvil_wallet(1);
+        let nft = Address::random();
+        let price = U256::exp10(17);
+        let sell = Listing::new(nft, 7.into(), TokenStandard::Erc1155, price)
+            .quantity(2.into())
+            .sign(&maker)
+            .await
+            .unwrap();
+        MinimalOrder::from(sell.clone())
+            .verify_signature(1)
+            .unwrap();
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(1)).unwrap();
+        let mut client = Client::with_api(Arc::new(provider), MockOrderProvider::new(vec![sell]));
+        client.check_fulfillable = false;
+        let taker = anvil_wallet(0).address();
+        let args = BuyArgs {
+            token_id: 7.into(),
+            taker,
+            token: nft,
+            recipient: taker,
+            timestamp: Some(1),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let call = client.buy(args, 1).await.unwrap().remove(0).call;
+        assert_eq!(call.tx.value(), Some(&price));
+        assert_eq!(call.tx.from(), Some(&taker));
+    }
+
+    /// Requires an anvil fork of mainnet, e.g. `anvil --fork-url <URL>`
+    #[tokio::test]
+    #[ignore]
+    async fn can_match_a_synthetic_listing() {
+        let provider = Arc::new(Provider::<Http>::try_from("http://localhost:8545").unwrap());
+        let client = Client::with_api(provider.clone(), MockOrderProvider::default());
+        let (taker, maker) = (anvil_wallet(0), anvil_wallet(1));
+
+        // hand the token over to the maker, impersonating its owner
+        let address = "0x91f7bb6900d65d004a659f34205beafc3b4e136c"
+            .parse::<Address>()
+            .unwrap();
+        let id = U256::from(1126);
+        let nft = NFT::new(address, provider.clone());
+        let owner = nft.owner_of(id).call().await.unwrap();
+        let node: &Http = (*provider).as_ref();
+        let _: () = node
+            .request("anvil_impersonateAccount", [owner])
+            .await
+            .unwrap();
+        let _: () = node
+            .request("anvil_setBalance", (owner, U256::exp10(18)))
+            .await
+            .unwrap();
+        let data = nft
+            .transfer_from(owner, maker.address(), id)
+            .calldata()
+            .unwrap();
+        let tx = TransactionRequest::new().from(owner).to(address).data(data);
+        provider
+            .send_transaction(tx, None)
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        approve_maker(&client, maker.address(), address)
+            .await
+            .unwrap();
+
+        let price = U256::exp10(17);
+        let sell = Listing::new(address, id, TokenStandard::Erc721, price)
+            .sign(&maker)
+            .await
+            .unwrap();
+        let buy = sell
+            .match_sell(BuyArgs {
+                token_id: id,
+                taker: taker.address(),
+                token: address,
+                recipient: taker.address(),
+                timestamp: None,
+                quantity: None,
+                max_price: None,
+                total_budget: None,
+            })
+            .unwrap();
+        let call = client.atomic_match(buy, sell.into()).await.unwrap();
+        call.send().await.unwrap().await.unwrap();
+
+        assert_eq!(nft.owner_of(id).call().await.unwrap(), taker.address());
+    }
+}
@@ -0,0 +1,98 @@
+//! Where the listing time of the buy orders comes from, see [`TimeSource`]. The exchange
+//! rejects the orders listed after the block they are mined in, so the listing time
+//! is set a bit in the past of the chain's time, which the local clock may be off from.
+use ethers::{providers::Middleware, types::BlockNumber};
+
+use crate::ClientError;
+
+/// How far in the past the listing times are set, to be mined in a block a bit older
+/// than the time they were taken at
+pub const LISTING_TIME_MARGIN_SECS: u64 = 100;
+
+/// The time the listing times of the buy orders are derived from, unless set by
+/// `BuyArgs::timestamp`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The timestamp of the latest block, queried from the provider
+    #[default]
+    Chain,
+    /// The local clock, corrected by `offset_secs` (e.g. negative if it is ahead of the
+    /// chain's)
+    Local { offset_secs: i64 },
+    /// A fixed listing time, used as is, e.g. in tests
+    Manual(u64),
+}
+
+impl TimeSource {
+    /// The listing time of the buy orders, querying the latest block if needed
+    pub async fn listing_time<M: Middleware>(&self, provider: &M) -> Result<u64, ClientError> {
+        let latest = match self {
+            TimeSource::Chain => provider
+                .get_block(BlockNumber::Latest)
+                .await
+                .map_err(|err| ClientError::Provider(err.to_string()))?
+                .ok_or_else(|| ClientError::Provider("No latest block found".to_owned()))?
+                .timestamp
+                .as_u64(),
+            _ => 0,
+        };
+        Ok(self.listing_time_at(latest))
+    }
+
+    /// The listing time of the buy orders, given the timestamp of the latest block
+    pub fn listing_time_at(&self, latest_block_timestamp: u64) -> u64 {
+        match *self {
+            TimeSource::Chain => latest_block_timestamp.saturating_sub(LISTING_TIME_MARGIN_SECS),
+            TimeSource::Local { offset_secs } => {
+                let now = (local_now() as i64).saturating_add(offset_secs).max(0) as u64;
+                now.saturating_sub(LISTING_TIME_MARGIN_SECS)
+            }
+            TimeSource::Manual(timestamp) => timestamp,
+        }
+    }
+}
+
+/// The listing time by the uncorrected local clock, when no time source is at hand
+pub(crate) fn local_listing_time() -> u64 {
+    local_now().saturating_sub(LISTING_TIME_MARGIN_SECS)
+}
+
+fn local_now() -> u64 {
+    chrono::offset::Local::now().timestamp() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        providers::Provider,
+        types::{Block, H256},
+    };
+
+    #[tokio::test]
+    async fn derives_the_listing_time() {
+        let (provider, mock) = Provider::mocked();
+        let block = Block::<H256> {
+            timestamp: 1_000_000.into(),
+            ..Default::default()
+        };
+        mock.push(block).unwrap();
+        assert_eq!(
+            TimeSource::Chain.listing_time(&provider).await.unwrap(),
+            1_000_000 - LISTING_TIME_MARGIN_SECS
+        );
+        // no query
+        assert_eq!(
+            TimeSource::Manual(42)
+                .listing_time(&provider)
+                .await
+                .unwrap(),
+            42
+        );
+
+        let ahead = TimeSource::Local { offset_secs: -3600 }.listing_time_at(0);
+        let local = TimeSource::Local { offset_secs: 0 }.listing_time_at(0);
+        assert!(local - ahead >= 3600 && local - ahead < 3700);
+        assert!(local >= local_listing_time());
+    }
+}
@@ -0,0 +1,188 @@
+//! The floor price of the assets of a collection sharing a trait, see [`trait_floor`]
+use ethers::types::{Address, U256};
+use serde::Serialize;
+
+use crate::{
+    cheapest_orders_by_token_at, order_provider::OrderProvider, token_ids_by_traits,
+    types::TraitFilter, unix_timestamp, ClientError, ListingFilter, OpenSeaApi, PaymentTokens,
+};
+
+/// The cheapest listing of the assets with a trait. Prices are the current prices of
+/// the listings normalized to 18 decimals, see [`Order::normalized_price`](crate::types::Order::normalized_price).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TraitFloor {
+    /// The price of the cheapest listing, `None` if no asset with the trait is listed
+    pub floor: Option<U256>,
+    /// The contract and the token id of the cheapest listing
+    pub floor_asset: Option<(Address, U256)>,
+    /// The number of assets with the trait which are listed
+    pub listed: usize,
+    /// The number of assets with the trait
+    pub assets: usize,
+}
+
+/// Returns the floor of the assets of the collection with the given slug (e.g.
+/// `boredapeyachtclub`) whose `trait_type` trait has the `trait_value` value, both
+/// matched case-insensitively. The listings are the ones kept by the default
+/// [`ListingFilter`], priced in the payment tokens supported by OpenSea.
+pub async fn trait_floor(
+    api: &OpenSeaApi,
+    collection_slug: &str,
+    trait_type: &str,
+    trait_value: &str,
+) -> Result<TraitFloor, ClientError> {
+    let contracts = api.resolve_collection(collection_slug).await?;
+    let filter = TraitFilter {
+        trait_type: trait_type.to_owned(),
+        value: trait_value.to_owned(),
+    };
+    let mut tokens = PaymentTokens::of(api.network());
+    tokens.refresh(api).await?;
+    trait_floor_of(api, &contracts, &filter, ListingFilter::default(), &tokens).await
+}
+
+/// Same as [`trait_floor`], for the assets of the `contracts` and the listings kept by
+/// the `listing_filter`, paging through them. The listings in a payment token unknown
+/// to the `tokens` are skipped.
+pub async fn trait_floor_of<P: OrderProvider>(
+    api: &P,
+    contracts: &[Address],
+    filter: &TraitFilter,
+    listing_filter: ListingFilter,
+    tokens: &PaymentTokens,
+) -> Result<TraitFloor, ClientError> {
+    let now = unix_timestamp();
+    let mut floor = TraitFloor::default();
+    for &contract in contracts {
+        let ids = token_ids_by_traits(api, contract, std::slice::from_ref(filter)).await?;
+        floor.assets += ids.len();
+        // all the listings, as the cheapest in Wei may not be in normalized prices
+        let listings =
+            cheapest_orders_by_token_at(api, contract, &ids, usize::MAX, listing_filter, now)
+                .await?;
+        for (token_id, orders) in listings {
+            let price = match orders
+                .iter()
+                .filter_map(|order| order.normalized_price(tokens))
+                .min()
+            {
+                Some(price) => price,
+                None => continue,
+            };
+            floor.listed += 1;
+            if floor.floor.is_none_or(|floor| price < floor) {
+                floor.floor = Some(price);
+                floor.floor_asset = Some((contract, token_id));
+            }
+        }
+    }
+    Ok(floor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockOrderProvider;
+    use ethers::types::H256;
+
+    #[tokio::test]
+    async fn finds_the_floor_of_the_trait() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        let assets = serde_json::from_value(serde_json::json!([
+            { "token_id": "0", "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": asset.id.to_string(), "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": "1", "traits": [{ "trait_type": "Fur", "value": "Brown" }] },
+        ]))
+        .unwrap();
+        let api = MockOrderProvider::new(vec![sell.clone()]).with_assets(assets);
+        let tokens = PaymentTokens::default();
+
+        let gold = "fur=gold".parse().unwrap();
+        let floor = trait_floor_of(
+            &api,
+            &[asset.address],
+            &gold,
+            ListingFilter::default(),
+            &tokens,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            floor,
+            TraitFloor {
+                floor: Some(sell.current_price),
+                floor_asset: Some((asset.address, asset.id)),
+                listed: 1,
+                assets: 2,
+            }
+        );
+
+        // the only brown asset is not listed
+        let brown = "Fur=Brown".parse().unwrap();
+        let floor = trait_floor_of(
+            &api,
+            &[asset.address],
+            &brown,
+            ListingFilter::default(),
+            &tokens,
+        )
+        .await
+        .unwrap();
+        assert_eq!(floor.floor, None);
+        assert_eq!((floor.listed, floor.assets), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn compares_the_normalized_prices() {
+        let sell = crate::testing::order();
+        let asset = sell.asset_metadata().unwrap().asset.clone();
+        // 100 USDC, fewer units than the 80 ETH of the other listing but more once
+        // normalized
+        let mut usdc = sell.clone();
+        usdc.order_hash = H256::random();
+        usdc.payment_token = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        usdc.base_price = U256::exp10(8);
+        usdc.current_price = U256::exp10(8);
+        usdc.asset_metadata_mut().unwrap().asset.id = 1.into();
+        // in a token without decimals to normalize with
+        let mut unknown = usdc.clone();
+        unknown.order_hash = H256::random();
+        unknown.payment_token = Address::random();
+        unknown.asset_metadata_mut().unwrap().asset.id = 2.into();
+        let assets = serde_json::from_value(serde_json::json!([
+            { "token_id": asset.id.to_string(), "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": "1", "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+            { "token_id": "2", "traits": [{ "trait_type": "Fur", "value": "Gold" }] },
+        ]))
+        .unwrap();
+        let api = MockOrderProvider::new(vec![usdc, unknown, sell.clone()]).with_assets(assets);
+
+        let gold = "fur=gold".parse().unwrap();
+        // the dust threshold is in units of ETH
+        let filter = ListingFilter {
+            min_price: U256::zero(),
+            ..Default::default()
+        };
+        let floor = trait_floor_of(
+            &api,
+            &[asset.address],
+            &gold,
+            filter,
+            &PaymentTokens::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            floor,
+            TraitFloor {
+                floor: Some(sell.current_price),
+                floor_asset: Some((asset.address, asset.id)),
+                listed: 2,
+                assets: 3,
+            }
+        );
+    }
+}
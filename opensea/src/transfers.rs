@@ -0,0 +1,299 @@
+//! Confirmation of the purchases by the transfers of their tokens to the recipient, see
+//! [`SnipePlan::watch_transfers`](crate::SnipePlan::watch_transfers). The `Transfer` /
+//! `TransferSingle` events are pushed by a websocket subscription as soon as the node
+//! sees the block, instead of polling the receipts or the owners of the tokens.
+use ethers::{
+    core::utils::keccak256,
+    providers::{Middleware, Provider, Ws},
+    types::{Address, Filter, Log, ValueOrArray, H256, U256},
+};
+use futures::StreamExt;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{summary::PlannedPurchase, Client, Event, OrderProvider, SnipeOutcome};
+
+/// How long to wait for the transfers once the txs are submitted, by default
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The websocket provider the transfers are subscribed to
+#[derive(Clone, Debug)]
+pub struct TransferWatch {
+    pub provider: Arc<Provider<Ws>>,
+    /// How long to wait for the transfers once the txs are submitted
+    pub timeout: Duration,
+}
+
+impl TransferWatch {
+    pub fn new(provider: Arc<Provider<Ws>>) -> Self {
+        Self {
+            provider,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+fn erc721_transfer() -> H256 {
+    H256::from(keccak256("Transfer(address,address,uint256)"))
+}
+
+fn erc1155_transfer() -> H256 {
+    H256::from(keccak256(
+        "TransferSingle(address,address,address,uint256,uint256)",
+    ))
+}
+
+/// Returns the collection and the id of the token transferred to the `recipient` by the
+/// ERC721 `Transfer` or ERC1155 `TransferSingle` event, if it is one
+pub fn received_token(log: &Log, recipient: Address) -> Option<(Address, U256)> {
+    let to = H256::from(recipient);
+    let topic = *log.topics.first()?;
+    let token_id = if topic == erc721_transfer() && log.topics.get(2) == Some(&to) {
+        U256::from_big_endian(log.topics.get(3)?.as_bytes())
+    } else if topic == erc1155_transfer() && log.topics.get(3) == Some(&to) {
+        // the id is the 1st word of the data, followed by the value
+        U256::from_big_endian(log.data.as_ref().get(..32)?)
+    } else {
+        return None;
+    };
+    Some((log.address, token_id))
+}
+
+/// The collections and ids of the tokens of the `purchases`
+pub(crate) fn purchased_tokens(purchases: &[PlannedPurchase]) -> HashSet<(Address, U256)> {
+    purchases
+        .iter()
+        .map(|purchase| (purchase.nft, purchase.token_id))
+        .collect()
+}
+
+/// The tokens of the `purchases` which the submission may still transfer: the ones
+/// bought by the included bundles or by the txs sent via the mempool
+pub(crate) fn awaited_tokens(
+    outcome: &SnipeOutcome,
+    purchases: &[PlannedPurchase],
+) -> HashSet<(Address, U256)> {
+    let token_ids: HashSet<U256> = match outcome {
+        SnipeOutcome::Included(bundle) => bundle
+            .simulation
+            .txs
+            .iter()
+            .filter_map(|tx| tx.token_id)
+            .collect(),
+        SnipeOutcome::IncludedBundles(bundles) => bundles
+            .iter()
+            .flatten()
+            .flat_map(|bundle| bundle.simulation.txs.iter().filter_map(|tx| tx.token_id))
+            .collect(),
+        SnipeOutcome::Sent(txs) => txs.iter().filter_map(|tx| tx.token_id).collect(),
+        SnipeOutcome::DryRun(_) | SnipeOutcome::BidsPlaced(_) => HashSet::new(),
+    };
+    purchases
+        .iter()
+        .filter(|purchase| token_ids.contains(&purchase.token_id))
+        .map(|purchase| (purchase.nft, purchase.token_id))
+        .collect()
+}
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Emits [`Event::TransferReceived`] for each of the `pending` tokens transferred to
+    /// the `recipient`, removing it, and resolves once none is left. The `pending`
+    /// tokens may be narrowed while watching, e.g. to the ones of the included bundles
+    /// (see [`awaited_tokens`]). Resolves right away if the transfers cannot be
+    /// subscribed to.
+    pub(crate) async fn confirm_transfers(
+        &self,
+        watch: &TransferWatch,
+        recipient: Address,
+        pending: &Mutex<HashSet<(Address, U256)>>,
+    ) {
+        let collections: Vec<_> = {
+            let pending = pending.lock().unwrap();
+            if pending.is_empty() {
+                return;
+            }
+            let collections: HashSet<_> = pending.iter().map(|(nft, _)| *nft).collect();
+            collections.into_iter().collect()
+        };
+        let erc721s = Filter::new()
+            .address(ValueOrArray::Array(collections.clone()))
+            .topic0(erc721_transfer())
+            .topic2(H256::from(recipient));
+        let erc1155s = Filter::new()
+            .address(ValueOrArray::Array(collections))
+            .topic0(erc1155_transfer())
+            .topic3(H256::from(recipient));
+        let subscriptions = futures::future::try_join(
+            watch.provider.subscribe_logs(&erc721s),
+            watch.provider.subscribe_logs(&erc1155s),
+        )
+        .await;
+        let mut logs = match subscriptions {
+            Ok((erc721s, erc1155s)) => futures::stream::select(erc721s, erc1155s),
+            Err(err) => {
+                say!("Could not subscribe to the transfers: {}", err);
+                return;
+            }
+        };
+
+        while let Some(log) = logs.next().await {
+            // reorged out
+            if log.removed == Some(true) {
+                continue;
+            }
+            let token = match received_token(&log, recipient) {
+                Some(token) => token,
+                None => continue,
+            };
+            if !pending.lock().unwrap().remove(&token) {
+                continue;
+            }
+            let (nft, token_id) = token;
+            say!(
+                "[TokenId = {:?}] Received from {:?} in tx {:?}",
+                token_id,
+                nft,
+                log.transaction_hash
+            );
+            // the purchase is already on-chain, a veto has nothing left to stop
+            let _ = self
+                .emit(Event::TransferReceived {
+                    nft,
+                    token_id,
+                    tx_hash: log.transaction_hash,
+                })
+                .await;
+            if pending.lock().unwrap().is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bundle_simulation::{BundleSimulationReport, TxSimulation},
+        snipe::{IncludedBundle, SentTx},
+    };
+    use ethers::types::H256;
+
+    fn purchase(nft: Address, token_id: u64) -> PlannedPurchase {
+        PlannedPurchase {
+            nft,
+            token_id: token_id.into(),
+            order_hash: H256::random(),
+            price: U256::exp10(17),
+            payment_token: Address::zero(),
+            listing_time: 0,
+            expiration_time: 0,
+        }
+    }
+
+    fn included(token_ids: &[Option<u64>]) -> IncludedBundle {
+        let txs = token_ids
+            .iter()
+            .map(|token_id| TxSimulation {
+                tx_hash: H256::random(),
+                token_id: token_id.map(U256::from),
+                gas_used: 0.into(),
+                effective_gas_price: 0.into(),
+                coinbase_diff: 0.into(),
+                error: None,
+            })
+            .collect();
+        IncludedBundle {
+            block: 1.into(),
+            simulation: BundleSimulationReport {
+                simulation_block: 1.into(),
+                gas_used: 0.into(),
+                effective_gas_price: 0.into(),
+                coinbase_diff: 0.into(),
+                txs,
+            },
+        }
+    }
+
+    #[test]
+    fn awaits_the_tokens_of_the_included_bundles() {
+        let nft = Address::random();
+        let purchases = [purchase(nft, 1), purchase(nft, 2), purchase(nft, 3)];
+        let tokens =
+            |ids: &[u64]| -> HashSet<_> { ids.iter().map(|id| (nft, U256::from(*id))).collect() };
+
+        // the bundle of the 2nd purchase was not included
+        let outcome = SnipeOutcome::IncludedBundles(vec![
+            Some(included(&[None, Some(1)])),
+            None,
+            Some(included(&[Some(3)])),
+        ]);
+        assert_eq!(awaited_tokens(&outcome, &purchases), tokens(&[1, 3]));
+
+        // the cancelled 3rd purchase was dropped from the bundle
+        let outcome = SnipeOutcome::Included(included(&[Some(1), Some(2)]));
+        assert_eq!(awaited_tokens(&outcome, &purchases), tokens(&[1, 2]));
+
+        let outcome = SnipeOutcome::Sent(vec![
+            SentTx {
+                tx_hash: H256::random(),
+                token_id: None,
+            },
+            SentTx {
+                tx_hash: H256::random(),
+                token_id: Some(2.into()),
+            },
+        ]);
+        assert_eq!(awaited_tokens(&outcome, &purchases), tokens(&[2]));
+
+        let outcome = SnipeOutcome::DryRun(Vec::new());
+        assert!(awaited_tokens(&outcome, &purchases).is_empty());
+    }
+
+    #[test]
+    fn decodes_received_tokens() {
+        let (nft, recipient) = (Address::random(), Address::random());
+        let word = |value: u64| H256::from_low_u64_be(value);
+        let mut log = Log {
+            address: nft,
+            topics: vec![
+                erc721_transfer(),
+                H256::from(Address::random()),
+                H256::from(recipient),
+                word(87),
+            ],
+            data: Default::default(),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        };
+        assert_eq!(received_token(&log, recipient), Some((nft, 87.into())));
+        // to someone else
+        assert_eq!(received_token(&log, Address::random()), None);
+
+        log.topics = vec![
+            erc1155_transfer(),
+            H256::from(Address::random()),
+            H256::from(Address::random()),
+            H256::from(recipient),
+        ];
+        log.data = [word(42).as_bytes(), word(1).as_bytes()].concat().into();
+        assert_eq!(received_token(&log, recipient), Some((nft, 42.into())));
+
+        log.topics[0] = H256::from(keccak256("Approval(address,address,uint256)"));
+        assert_eq!(received_token(&log, recipient), None);
+    }
+}
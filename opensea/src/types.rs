@@ -1,24 +1,47 @@
-use crate::{constants, contracts};
+use crate::{constants, contracts, storefront, time_source};
 use ethers::{
-    core::utils::id,
-    types::{Address, Bytes, H256, U256},
+    abi::{ParamType, Token},
+    contract::builders::ContractCall,
+    core::utils::{hash_message, id, keccak256},
+    types::{Address, Bytes, Signature, H256, U256},
 };
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::sync::Arc;
+use thiserror::Error;
 
-#[derive(Clone, Debug)]
+/// The chain of the orders. The Wyvern orderbook (API v1) only serves Mainnet and
+/// Rinkeby, the L2s only have Seaport orders (API v2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Network {
     Mainnet,
     Rinkeby,
+    Arbitrum,
+    Optimism,
 }
 
 impl Network {
     pub fn url(&self) -> &str {
         match self {
-            Network::Mainnet => constants::API_BASE_MAINNET,
+            Network::Mainnet | Network::Arbitrum | Network::Optimism => constants::API_BASE_MAINNET,
             Network::Rinkeby => constants::API_BASE_RINKEBY,
         }
     }
 
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Network::Mainnet => 1,
+            Network::Rinkeby => 4,
+            Network::Arbitrum => 42161,
+            Network::Optimism => 10,
+        }
+    }
+
+    /// The contracts of OpenSea's Wyvern exchange on the network, if deployed
+    pub fn deployment(&self) -> Option<constants::Deployment> {
+        constants::Deployment::of(*self)
+    }
+
     pub fn orderbook(&self) -> String {
         let url = self.url();
         format!("{}/wyvern/v{}", url, constants::ORDERBOOK_VERSION)
@@ -28,12 +51,56 @@ impl Network {
         let url = self.url();
         format!("{}/api/v{}", url, constants::ORDERBOOK_VERSION)
     }
+
+    /// The chain's name in the v2 API paths
+    pub fn chain(&self) -> &str {
+        match self {
+            Network::Mainnet => "ethereum",
+            Network::Rinkeby => "rinkeby",
+            Network::Arbitrum => "arbitrum",
+            Network::Optimism => "optimism",
+        }
+    }
+
+    /// The base URL of the given version of the API
+    pub fn api_url(&self, version: ApiVersion) -> String {
+        match version {
+            ApiVersion::V1 => self.api(),
+            ApiVersion::V2 => format!("{}/v2", self.url()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown network {0}, expected `mainnet`, `rinkeby`, `arbitrum` or `optimism`")]
+pub struct UnknownNetwork(String);
+
+impl std::str::FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "ethereum" => Ok(Network::Mainnet),
+            "rinkeby" => Ok(Network::Rinkeby),
+            "arbitrum" => Ok(Network::Arbitrum),
+            "optimism" => Ok(Network::Optimism),
+            _ => Err(UnknownNetwork(s.to_owned())),
+        }
+    }
+}
+
+/// The version of the OpenSea API. V1 serves the legacy Wyvern orders, V2 the
+/// Seaport ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Asset {}
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// The exact arguments required to provide to the smart contract
 pub struct MinimalOrder {
     // addresses involved
@@ -58,10 +125,10 @@ pub struct MinimalOrder {
     pub expiration_time: U256,
     pub salt: U256,
 
-    pub fee_method: u8,
-    pub side: u8,
-    pub sale_kind: u8,
-    pub how_to_call: u8,
+    pub fee_method: FeeMethod,
+    pub side: OrderSide,
+    pub sale_kind: SaleKind,
+    pub how_to_call: HowToCall,
 
     pub calldata: Bytes,
 
@@ -76,6 +143,13 @@ pub struct MinimalOrder {
 
 impl From<Order> for MinimalOrder {
     fn from(order: Order) -> Self {
+        // the exchange ignores the signature of an order approved on-chain, which the API
+        // may still fill with leftovers
+        let (v, r, s) = if order.approved_on_chain {
+            (0, H256::zero(), H256::zero())
+        } else {
+            (order.v as u8, order.r, order.s)
+        };
         Self {
             exchange: order.exchange,
             maker: order.maker.address,
@@ -95,9 +169,9 @@ impl From<Order> for MinimalOrder {
             calldata: order.calldata,
             replacement_pattern: order.replacement_pattern,
             static_extradata: order.static_extradata,
-            v: order.v as u8,
-            r: order.r,
-            s: order.s,
+            v,
+            r,
+            s,
             maker_relayer_fee: order.maker_relayer_fee,
             taker_relayer_fee: order.taker_relayer_fee,
             maker_protocol_fee: order.maker_protocol_fee,
@@ -108,6 +182,281 @@ impl From<Order> for MinimalOrder {
     }
 }
 
+impl MinimalOrder {
+    /// Wyvern's `hashOrder`: the keccak256 of the tightly packed order parameters.
+    /// For orders returned by the API, this is their `order_hash`.
+    pub fn hash(&self) -> H256 {
+        fn uint(buf: &mut Vec<u8>, num: U256) {
+            let mut word = [0; 32];
+            num.to_big_endian(&mut word);
+            buf.extend_from_slice(&word);
+        }
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(self.exchange.as_bytes());
+        buf.extend_from_slice(self.maker.as_bytes());
+        buf.extend_from_slice(self.taker.as_bytes());
+        uint(&mut buf, self.maker_relayer_fee);
+        uint(&mut buf, self.taker_relayer_fee);
+        uint(&mut buf, self.maker_protocol_fee);
+        uint(&mut buf, self.taker_protocol_fee);
+        buf.extend_from_slice(self.fee_recipient.as_bytes());
+        buf.push(self.fee_method.into());
+        buf.push(self.side.into());
+        buf.push(self.sale_kind.into());
+        buf.extend_from_slice(self.target.as_bytes());
+        buf.push(self.how_to_call.into());
+        buf.extend_from_slice(self.calldata.as_ref());
+        buf.extend_from_slice(self.replacement_pattern.as_ref());
+        buf.extend_from_slice(self.static_target.as_bytes());
+        buf.extend_from_slice(self.static_extradata.as_ref());
+        buf.extend_from_slice(self.payment_token.as_bytes());
+        uint(&mut buf, self.base_price);
+        uint(&mut buf, self.extra);
+        uint(&mut buf, self.listing_time);
+        uint(&mut buf, self.expiration_time);
+        uint(&mut buf, self.salt);
+
+        keccak256(buf).into()
+    }
+
+    /// Wyvern's `hashToSign`: the order hash prefixed as a personal message, which
+    /// is what the maker signs
+    pub fn hash_to_sign(&self) -> H256 {
+        hash_message(self.hash())
+    }
+
+    /// Recovers the signer of the order from its (v, r, s) fields and checks that
+    /// it is the order's maker. Also checks that the order is for the known Wyvern
+    /// exchange of the provided chain id, since Wyvern 2.2 signatures are not bound
+    /// to a chain.
+    pub fn verify_signature(&self, exchange_chain_id: u64) -> Result<(), SignatureError> {
+        if let Some(exchange) = constants::exchange_address(exchange_chain_id) {
+            if exchange != self.exchange {
+                return Err(SignatureError::WrongExchange {
+                    chain_id: exchange_chain_id,
+                    exchange: self.exchange,
+                });
+            }
+        }
+
+        let signature = Signature {
+            r: U256::from_big_endian(self.r.as_bytes()),
+            s: U256::from_big_endian(self.s.as_bytes()),
+            v: self.v.into(),
+        };
+        let recovered = signature
+            .recover(self.hash_to_sign())
+            .map_err(|err| SignatureError::Recovery(err.to_string()))?;
+
+        if recovered != self.maker {
+            return Err(SignatureError::SignerMismatch {
+                maker: self.maker,
+                recovered,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("Order exchange {exchange:?} is not the Wyvern exchange of chain {chain_id}")]
+    WrongExchange { chain_id: u64, exchange: Address },
+    #[error("Could not recover signer: {0}")]
+    Recovery(String),
+    #[error("Order signed by {recovered:?} instead of its maker {maker:?}")]
+    SignerMismatch { maker: Address, recovered: Address },
+}
+
+#[derive(Debug, Error)]
+pub enum OrderMatchError {
+    #[error("Unsupported asset schema {0}")]
+    UnsupportedSchema(String),
+    #[error("Could not encode the buy calldata: {0}")]
+    Encode(#[from] ethers::contract::AbiError),
+    #[error("Order {0:?} has no asset metadata")]
+    MissingMetadata(H256),
+    #[error("Order {order_hash:?} is a bundle of {assets} assets, which cannot be matched")]
+    Bundle { order_hash: H256, assets: usize },
+    #[error("Order {0:?} is an English auction, which only OpenSea can match")]
+    EnglishAuction(H256),
+    #[error("Order {order_hash:?} cannot be matched: {reason}")]
+    InvalidOrder { order_hash: H256, reason: String },
+    #[error("Unsupported ERC721 transfer function 0x{}", hex::encode(.0))]
+    UnsupportedTransfer([u8; 4]),
+    #[error("Order {order_hash:?} lists {listed} tokens, which can only be bought whole, not {requested}")]
+    PartialFill {
+        order_hash: H256,
+        requested: U256,
+        listed: U256,
+    },
+    #[error("Order {order_hash:?} delegate calls {target:?}, which is not the MerkleValidator")]
+    UntrustedDelegateCall { order_hash: H256, target: Address },
+    #[error("Order {order_hash:?} is on the Wyvern v2.3 exchange {exchange:?}, whose EIP-712 orders are not supported")]
+    UnsupportedExchange { order_hash: H256, exchange: Address },
+    #[error("Order {order_hash:?} is priced in {token:?}, which cannot be relisted in ETH")]
+    UnsupportedPaymentToken { order_hash: H256, token: Address },
+}
+
+/// The ERC721 transfer functions which the calldata of a listing may call. The buy
+/// calldata must call the same one for the calldatas to match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Erc721Transfer {
+    /// `transferFrom(address,address,uint256)`
+    TransferFrom,
+    /// `safeTransferFrom(address,address,uint256)`
+    SafeTransferFrom,
+    /// `safeTransferFrom(address,address,uint256,bytes)`, whose `data` is the listing's
+    SafeTransferFromWithData,
+}
+
+impl Erc721Transfer {
+    const ALL: [Erc721Transfer; 3] = [
+        Erc721Transfer::TransferFrom,
+        Erc721Transfer::SafeTransferFrom,
+        Erc721Transfer::SafeTransferFromWithData,
+    ];
+
+    pub fn signature(&self) -> &'static str {
+        match self {
+            Erc721Transfer::TransferFrom => "transferFrom(address,address,uint256)",
+            Erc721Transfer::SafeTransferFrom => "safeTransferFrom(address,address,uint256)",
+            Erc721Transfer::SafeTransferFromWithData => {
+                "safeTransferFrom(address,address,uint256,bytes)"
+            }
+        }
+    }
+
+    /// The transfer function called by the `calldata`, if any
+    pub fn from_calldata(calldata: &[u8]) -> Option<Self> {
+        let selector = calldata.get(..4)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|transfer| id(transfer.signature()) == selector)
+    }
+}
+
+/// The functions of OpenSea's `MerkleValidator` (see
+/// [`constants::MERKLE_VALIDATOR_ADDRESS`]) which the calldata of a listing may call
+/// instead of the collection's. The maker's proxy delegate calls the validator, which
+/// transfers the token if it is within the criteria, i.e. its `root` and `proof` (empty
+/// for the listings of a single token). The buy calldata must call the same function,
+/// with the same criteria.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MerkleTransfer {
+    /// `matchERC721UsingCriteria(address,address,address,uint256,bytes32,bytes32[])`
+    Erc721,
+    /// `matchERC721WithSafeTransferUsingCriteria(address,address,address,uint256,bytes32,
+    /// bytes32[])`
+    Erc721WithSafeTransfer,
+    /// `matchERC1155UsingCriteria(address,address,address,uint256,uint256,bytes32,
+    /// bytes32[])`
+    Erc1155,
+}
+
+impl MerkleTransfer {
+    const ALL: [MerkleTransfer; 3] = [
+        MerkleTransfer::Erc721,
+        MerkleTransfer::Erc721WithSafeTransfer,
+        MerkleTransfer::Erc1155,
+    ];
+
+    pub fn signature(&self) -> &'static str {
+        match self {
+            MerkleTransfer::Erc721 => {
+                "matchERC721UsingCriteria(address,address,address,uint256,bytes32,bytes32[])"
+            }
+            MerkleTransfer::Erc721WithSafeTransfer => {
+                "matchERC721WithSafeTransferUsingCriteria(address,address,address,uint256,bytes32,bytes32[])"
+            }
+            MerkleTransfer::Erc1155 => {
+                "matchERC1155UsingCriteria(address,address,address,uint256,uint256,bytes32,bytes32[])"
+            }
+        }
+    }
+
+    /// The validator function called by the `calldata`, if any
+    pub fn from_calldata(calldata: &[u8]) -> Option<Self> {
+        let selector = calldata.get(..4)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|transfer| id(transfer.signature()) == selector)
+    }
+
+    pub fn schema(&self) -> &'static str {
+        match self {
+            MerkleTransfer::Erc721 | MerkleTransfer::Erc721WithSafeTransfer => "ERC721",
+            MerkleTransfer::Erc1155 => "ERC1155",
+        }
+    }
+
+    /// The arguments: `from`, `to`, `token`, `tokenId`, the `amount` for ERC1155s, then
+    /// `root` and `proof`
+    fn params(&self) -> Vec<ParamType> {
+        let mut params = vec![
+            ParamType::Address,
+            ParamType::Address,
+            ParamType::Address,
+            ParamType::Uint(256),
+        ];
+        if *self == MerkleTransfer::Erc1155 {
+            params.push(ParamType::Uint(256));
+        }
+        params.push(ParamType::FixedBytes(32));
+        params.push(ParamType::Array(Box::new(ParamType::FixedBytes(32))));
+        params
+    }
+
+    /// Encodes the call transferring `amount` (ignored for ERC721s) of the token from
+    /// `from` to `to`, with the criteria of the listing's `calldata`
+    fn encode(
+        &self,
+        listing_calldata: &[u8],
+        from: Address,
+        to: Address,
+        token: Address,
+        token_id: U256,
+        amount: U256,
+    ) -> Option<Bytes> {
+        let mut tokens = ethers::abi::decode(&self.params(), listing_calldata.get(4..)?).ok()?;
+        tokens[0] = Token::Address(from);
+        tokens[1] = Token::Address(to);
+        tokens[2] = Token::Address(token);
+        tokens[3] = Token::Uint(token_id);
+        if *self == MerkleTransfer::Erc1155 {
+            tokens[4] = Token::Uint(amount);
+        }
+        let mut calldata = id(self.signature()).to_vec();
+        calldata.extend(ethers::abi::encode(&tokens));
+        Some(calldata.into())
+    }
+}
+
+/// What buying a listing costs the taker, see [`Order::effective_cost`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveCost {
+    /// The current price, in the payment token
+    pub price: U256,
+    /// The taker's share of the relayer fee, in the payment token
+    pub relayer_fee: U256,
+    /// The taker's share of the protocol fee, in the payment token
+    pub protocol_fee: U256,
+    /// The cost of the gas of the purchase, in Wei
+    pub gas: U256,
+}
+
+impl EffectiveCost {
+    /// The sum of the price, fees and gas. The gas is paid in ETH, so this is only a
+    /// total in Wei for ETH listings.
+    pub fn total(&self) -> U256 {
+        self.price + self.relayer_fee + self.protocol_fee + self.gas
+    }
+}
+
 /// The response we get from the API
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Order {
@@ -116,10 +465,13 @@ pub struct Order {
     pub listing_time: u64,
     pub expiration_time: u64,
     pub order_hash: H256,
+    /// The signature is empty (0 or `null`) for the orders approved on-chain, see
+    /// [`Order::approved_on_chain`]
+    #[serde(default, deserialize_with = "null_as_default")]
     pub v: u64,
-    #[serde(deserialize_with = "h256_from_str")]
+    #[serde(default, deserialize_with = "h256_from_str")]
     pub r: H256,
-    #[serde(deserialize_with = "h256_from_str")]
+    #[serde(default, deserialize_with = "h256_from_str")]
     pub s: H256,
 
     #[serde(deserialize_with = "u256_from_dec_str")]
@@ -127,15 +479,19 @@ pub struct Order {
 
     #[serde(deserialize_with = "u256_from_dec_str")]
     pub current_price: U256,
-    pub side: u8,
-    pub sale_kind: u8,
+    pub side: OrderSide,
+    pub sale_kind: SaleKind,
     pub target: Address,
-    pub how_to_call: u8,
+    pub how_to_call: HowToCall,
+    /// The maker approved the order with `approveOrder_` instead of signing it
     pub approved_on_chain: bool,
     pub cancelled: bool,
     pub finalized: bool,
     pub marked_invalid: bool,
+    /// The zero address when the API returns `null`, e.g. for English auctions
+    #[serde(default, deserialize_with = "null_as_default")]
     pub fee_recipient: User,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub maker: User,
 
     #[serde(deserialize_with = "u256_from_dec_str")]
@@ -157,21 +513,35 @@ pub struct Order {
     #[serde(deserialize_with = "u256_from_dec_str")]
     pub taker_relayer_fee: U256,
 
+    #[serde(deserialize_with = "bytes_from_hex")]
     pub calldata: Bytes,
+    #[serde(deserialize_with = "bytes_from_hex")]
     pub replacement_pattern: Bytes,
 
     pub static_target: Address,
+    #[serde(deserialize_with = "bytes_from_hex")]
     pub static_extradata: Bytes,
 
     pub exchange: Address,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub taker: User,
 
     #[serde(deserialize_with = "u256_from_dec_str")]
     pub quantity: U256,
 
-    pub metadata: Metadata,
+    /// Missing for some orders, see [`Metadata`] for the shapes it is parsed in
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+
+    pub fee_method: FeeMethod,
+
+    /// The order as returned by the API, see [`Order::raw`]
+    #[serde(skip)]
+    raw: Option<Arc<serde_json::Value>>,
 
-    pub fee_method: u8,
+    /// The cost the listing was ranked by, see [`Order::ranked_cost`]
+    #[serde(skip)]
+    pub(crate) ranked_cost: Option<EffectiveCost>,
 }
 
 #[derive(Clone, Debug)]
@@ -180,120 +550,1478 @@ pub struct BuyArgs {
     pub recipient: Address,
     pub token: Address,
     pub token_id: U256,
+    /// The listing time of the buy order. The [`Client`](crate::Client) derives it from
+    /// its [`TimeSource`](crate::TimeSource) if unset, [`Order::match_sell`] from the
+    /// local clock.
     pub timestamp: Option<u64>,
+    /// The quantity of ERC1155s to buy. A listing is always bought whole, so when buying
+    /// several listings it is the quantity in total (see [`plan_fills`](crate::plan_fills)).
+    pub quantity: Option<U256>,
+    /// The max price (in Wei) to pay for each listing
+    pub max_price: Option<U256>,
+    /// The max price (in Wei) to pay for all the listings bought in a call
+    pub total_budget: Option<U256>,
+}
+
+/// A buy call along with the listing it fills
+#[derive(Clone)]
+pub struct PreparedBuy<M> {
+    pub call: ContractCall<M, ()>,
+    /// The sell order, as matched
+    pub order: Order,
+    pub token_id: U256,
+    /// The number of tokens bought, always 1 for ERC721s
+    pub quantity: U256,
+    /// What the buy order pays, e.g. the on-chain price if it differs from the API's
+    pub expected_price: U256,
+}
+
+/// An `atomicMatch_` call selling a token to one of its bids, see
+/// [`Client::accept_best_offers`](crate::Client::accept_best_offers)
+#[derive(Clone)]
+pub struct PreparedSale<M> {
+    pub call: ContractCall<M, ()>,
+    /// The bid, as matched
+    pub order: Order,
+    pub token: Address,
+    pub token_id: U256,
+    /// What the bid pays, in its payment token, before the fees
+    pub price: U256,
 }
 
 impl Order {
-    pub fn match_sell(&self, args: BuyArgs) -> MinimalOrder {
-        let mut order = MinimalOrder::from(self.clone());
+    /// The order's JSON as returned by the API, including the fields unknown to this
+    /// crate. Only kept if the API is configured with [`OpenSeaApiConfig::keep_raw`].
+    ///
+    /// [`OpenSeaApiConfig::keep_raw`]: crate::api::OpenSeaApiConfig::keep_raw
+    pub fn raw(&self) -> Option<&serde_json::Value> {
+        self.raw.as_deref()
+    }
+
+    pub(crate) fn with_raw(mut self, raw: serde_json::Value) -> Self {
+        self.raw = Some(Arc::new(raw));
+        self
+    }
+
+    /// The breakdown of the cost which the listing was ranked by when returned by
+    /// [`get_n_cheapest_orders`](crate::get_n_cheapest_orders) and the other listing
+    /// queries. The gas is the same for all listings, so it is left out (zero), see
+    /// [`Order::effective_cost`] to include it.
+    pub fn ranked_cost(&self) -> Option<EffectiveCost> {
+        self.ranked_cost
+    }
+
+    /// Whether the order may still be matched at the `now` unix timestamp: it is not
+    /// expired, cancelled, finalized nor marked invalid
+    pub fn is_fillable(&self, now: u64) -> bool {
+        let expired = self.expiration_time != 0 && self.expiration_time <= now;
+        !(expired || self.cancelled || self.finalized || self.marked_invalid)
+    }
+
+    /// Whether the order is the listing of an English auction. Wyvern has no auction
+    /// sale kind for them: they are fixed price listings without a fee recipient,
+    /// which OpenSea matches with the highest bid once the auction ends. Since the
+    /// exchange requires one of the matched orders to have a fee recipient, they
+    /// cannot be taken by a buy without one.
+    pub fn is_english_auction(&self) -> bool {
+        self.side == OrderSide::Sell
+            && self.sale_kind == SaleKind::FixedPrice
+            && self.fee_recipient.address.is_zero()
+    }
+
+    /// What buying the listing costs: its current price, the taker fees charged on top
+    /// of it by split fee orders (in basis points of the price), and the gas of an
+    /// `atomicMatch_` at `gas_price`
+    pub fn effective_cost(&self, gas_price: U256) -> EffectiveCost {
+        let fee = |bps: U256| match self.fee_method {
+            FeeMethod::SplitFee => self.current_price * bps / 10_000,
+            FeeMethod::ProtocolFee => U256::zero(),
+        };
+        EffectiveCost {
+            price: self.current_price,
+            relayer_fee: fee(self.taker_relayer_fee),
+            protocol_fee: fee(self.taker_protocol_fee),
+            gas: gas_price * constants::ATOMIC_MATCH_GAS,
+        }
+    }
+
+    /// Returns the buy order matching the sell order, to be passed along with it to
+    /// `atomicMatch_`. Errors for English auctions, see [`Order::is_english_auction`].
+    pub fn match_sell(&self, args: BuyArgs) -> Result<MinimalOrder, OrderMatchError> {
+        if self.is_english_auction() {
+            return Err(OrderMatchError::EnglishAuction(self.order_hash));
+        }
+        self.buy_order(args)
+    }
+
+    /// Builds the buy order of the sell order, without a fee recipient. Only orders of
+    /// a single asset can be matched.
+    pub(crate) fn buy_order(&self, args: BuyArgs) -> Result<MinimalOrder, OrderMatchError> {
+        match self.metadata {
+            Some(Metadata::Asset(ref metadata)) => MinimalOrder::from(self.clone()).buy_order(
+                metadata.resolved_schema(),
+                self.quantity,
+                args,
+            ),
+            Some(Metadata::Bundle { ref bundle }) => Err(OrderMatchError::Bundle {
+                order_hash: self.order_hash,
+                assets: bundle.assets.len(),
+            }),
+            Some(Metadata::Unknown(_)) | None => {
+                Err(OrderMatchError::MissingMetadata(self.order_hash))
+            }
+        }
+    }
+
+    /// The metadata of the order's asset, unless it is a bundle or has none
+    pub fn asset_metadata(&self) -> Option<&AssetMetadata> {
+        self.metadata.as_ref().and_then(Metadata::asset)
+    }
+
+    pub fn asset_metadata_mut(&mut self) -> Option<&mut AssetMetadata> {
+        self.metadata.as_mut().and_then(Metadata::asset_mut)
+    }
+}
+
+impl MinimalOrder {
+    /// A fixed price ETH sell order of `maker` with OpenSea as fee recipient of
+    /// `fee_bps` of the price, e.g. to match a listing signed outside of the API. The
+    /// orderbook only accepts listings whose fee covers the collection's OpenSea and
+    /// creator fees. The `calldata` transfers the token from the maker to the zero
+    /// address, which the buyer replaces via the `replacement_pattern`. The other fields
+    /// can be set via struct update syntax, the signature via
+    /// [`MinimalOrder::with_signature`].
+    pub fn sell(
+        exchange: Address,
+        maker: Address,
+        target: Address,
+        calldata: Bytes,
+        replacement_pattern: Bytes,
+        price: U256,
+        fee_bps: u64,
+    ) -> Self {
+        Self {
+            exchange,
+            maker,
+            taker: Address::zero(),
+            fee_recipient: *constants::OPENSEA_FEE_RECIPIENT,
+            target,
+            static_target: Address::zero(),
+            payment_token: Address::zero(),
+            maker_relayer_fee: fee_bps.into(),
+            taker_relayer_fee: U256::zero(),
+            maker_protocol_fee: U256::zero(),
+            taker_protocol_fee: U256::zero(),
+            base_price: price,
+            current_price: price,
+            extra: U256::zero(),
+            listing_time: U256::zero(),
+            expiration_time: U256::zero(),
+            salt: ethers::core::rand::random::<u64>().into(),
+            fee_method: FeeMethod::SplitFee,
+            side: OrderSide::Sell,
+            sale_kind: SaleKind::FixedPrice,
+            how_to_call: HowToCall::Call,
+            calldata,
+            replacement_pattern,
+            static_extradata: Bytes::default(),
+            v: 0,
+            r: H256::zero(),
+            s: H256::zero(),
+        }
+    }
+
+    /// Sets the maker's signature of the order's [`MinimalOrder::hash_to_sign`]
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.v = signature.v as u8;
+        signature.r.to_big_endian(self.r.as_bytes_mut());
+        signature.s.to_big_endian(self.s.as_bytes_mut());
+        self
+    }
+
+    /// The `MerkleValidator` function called by the order's calldata, if it transfers
+    /// the token via the validator: the order delegate calls the validator of the
+    /// deployment of its exchange (which `atomicMatch_` requires to be the one called).
+    /// Other contracts with the same functions are not trusted, as the maker's proxy
+    /// would run their code. The orders of the v2.3 exchange are decoded as well, but
+    /// cannot be matched, see [`OrderMatchError::UnsupportedExchange`].
+    pub fn merkle_transfer(&self) -> Option<MerkleTransfer> {
+        if self.how_to_call != HowToCall::DelegateCall {
+            return None;
+        }
+        let validator = constants::Deployment::of_exchange(self.exchange)?.merkle_validator?;
+        if self.target != validator {
+            return None;
+        }
+        MerkleTransfer::from_calldata(self.calldata.as_ref())
+    }
+
+    /// The collection of the token transferred, the order's target unless it transfers
+    /// it via the `MerkleValidator`
+    pub fn nft(&self) -> Address {
+        match self.merkle_transfer() {
+            // `token` is the 3rd argument of all the validator's functions
+            Some(_) => self
+                .calldata
+                .as_ref()
+                .get(4 + 2 * 32..4 + 3 * 32)
+                .map(|word| Address::from_slice(&word[12..]))
+                .unwrap_or(self.target),
+            None => self.target,
+        }
+    }
+
+    /// The asset schema of the order, from the function called by its calldata
+    pub fn schema(&self) -> Option<&'static str> {
+        let selector = self.calldata.as_ref().get(..4)?;
+        if let Some(transfer) = self.merkle_transfer() {
+            Some(transfer.schema())
+        } else if Erc721Transfer::from_calldata(self.calldata.as_ref()).is_some() {
+            Some("ERC721")
+        } else if selector == id("safeTransferFrom(address,address,uint256,uint256,bytes)") {
+            Some("ERC1155")
+        } else {
+            None
+        }
+    }
+
+    /// The number of tokens transferred by the order's calldata
+    pub fn quantity(&self) -> U256 {
+        match self.schema() {
+            // `amount` is the 4th word of `safeTransferFrom`, the 5th of
+            // `matchERC1155UsingCriteria`
+            Some("ERC1155") => self
+                .calldata
+                .as_ref()
+                .get(self.word(3)..self.word(4))
+                .map(U256::from_big_endian)
+                .unwrap_or_default(),
+            _ => U256::one(),
+        }
+    }
+
+    /// The token id transferred by the order's calldata, the 3rd argument of all the
+    /// `transferFrom` and `safeTransferFrom` functions, the 4th of the `MerkleValidator`'s
+    pub fn token_id(&self) -> Option<U256> {
+        self.schema()?;
+        self.calldata
+            .as_ref()
+            .get(self.word(2)..self.word(3))
+            .map(U256::from_big_endian)
+    }
+
+    /// Errors if the order is on the Wyvern v2.3 exchange: its orders are signed as
+    /// EIP-712 typed data, and `atomic_match` only calls the v2.2 exchange
+    fn check_exchange(&self) -> Result<(), OrderMatchError> {
+        let v2_3 = constants::Deployment::of_exchange(self.exchange)
+            .is_some_and(|deployment| deployment.exchange_v2_3 == self.exchange);
+        if v2_3 {
+            return Err(OrderMatchError::UnsupportedExchange {
+                order_hash: self.hash(),
+                exchange: self.exchange,
+            });
+        }
+        Ok(())
+    }
+
+    /// Errors if the order delegate calls another contract than the `MerkleValidator`,
+    /// since the maker's proxy would run its code
+    fn check_delegate_call(&self) -> Result<(), OrderMatchError> {
+        if self.how_to_call == HowToCall::DelegateCall && self.merkle_transfer().is_none() {
+            return Err(OrderMatchError::UntrustedDelegateCall {
+                order_hash: self.hash(),
+                target: self.target,
+            });
+        }
+        Ok(())
+    }
+
+    /// The offset in the calldata of the `index`th argument of the token transfer,
+    /// shifted by the `token` argument of the `MerkleValidator`'s functions
+    fn word(&self, index: usize) -> usize {
+        let index = match self.merkle_transfer() {
+            Some(_) if index >= 2 => index + 1,
+            _ => index,
+        };
+        4 + index * 32
+    }
+
+    /// Checks that the order is a sell order which `atomicMatch_` can match, without
+    /// checking its signature (see [`MinimalOrder::verify_signature`])
+    pub fn validate(&self) -> Result<(), OrderMatchError> {
+        let order_hash = self.hash();
+        let invalid = |reason: &str| OrderMatchError::InvalidOrder {
+            order_hash,
+            reason: reason.to_owned(),
+        };
+        if self.side != OrderSide::Sell {
+            return Err(invalid("not a sell order"));
+        }
+        if self.calldata.as_ref().len() != self.replacement_pattern.as_ref().len() {
+            return Err(invalid(
+                "the calldata and replacement pattern differ in length",
+            ));
+        }
+        if !self.expiration_time.is_zero() && self.expiration_time <= self.listing_time {
+            return Err(invalid("expires before being listed"));
+        }
+        if self.sale_kind == SaleKind::FixedPrice && self.current_price != self.base_price {
+            return Err(invalid(
+                "the current price of a fixed price order is not its base price",
+            ));
+        }
+        if self.fee_recipient.is_zero() {
+            return Err(OrderMatchError::EnglishAuction(order_hash));
+        }
+        self.check_exchange()?;
+        self.check_delegate_call()
+    }
+
+    /// Returns the buy order matching the sell order, like [`Order::match_sell`] for
+    /// orders which do not originate from the API. The asset schema is detected from
+    /// the calldata.
+    pub fn match_sell(&self, args: BuyArgs) -> Result<MinimalOrder, OrderMatchError> {
+        self.validate()?;
+        let schema = self.schema().ok_or_else(|| OrderMatchError::InvalidOrder {
+            order_hash: self.hash(),
+            reason: "the calldata is neither an ERC721 nor an ERC1155 transfer".to_owned(),
+        })?;
+        self.buy_order(schema, self.quantity(), args)
+    }
+
+    /// Returns the sell order of `seller` matching the bid, to be passed along with it
+    /// to `atomicMatch_` by the seller. The calldata is the bid's with the seller as
+    /// `from`, and the bid pays the fees to its fee recipient.
+    pub fn match_buy(
+        &self,
+        seller: Address,
+        timestamp: Option<u64>,
+    ) -> Result<MinimalOrder, OrderMatchError> {
+        let order_hash = self.hash();
+        let invalid = |reason: &str| OrderMatchError::InvalidOrder {
+            order_hash,
+            reason: reason.to_owned(),
+        };
+        if self.side != OrderSide::Buy {
+            return Err(invalid("not a buy order"));
+        }
+        if self.calldata.as_ref().len() != self.replacement_pattern.as_ref().len() {
+            return Err(invalid(
+                "the calldata and replacement pattern differ in length",
+            ));
+        }
+        if self.token_id().is_none() {
+            return Err(invalid(
+                "the calldata is neither an ERC721 nor an ERC1155 transfer",
+            ));
+        }
+        // the bidder's funds are pulled by the exchange, which ETH cannot be
+        if self.payment_token.is_zero() {
+            return Err(invalid("the bid is paid in ETH"));
+        }
+        if self.fee_recipient.is_zero() {
+            return Err(invalid("the bid has no fee recipient"));
+        }
+
+        let mut order = self.clone();
+        order.side = OrderSide::Sell;
+        order.maker = seller;
+        order.taker = self.maker;
+        order.fee_recipient = Address::zero();
+        order.expiration_time = 0.into();
+        order.extra = 0.into();
+        order.salt = ethers::core::rand::random::<u64>().into();
+
+        // `from` is the 1st word of all the `transferFrom` and `safeTransferFrom` functions
+        let mut calldata = self.calldata.to_vec();
+        calldata[4..4 + 32].copy_from_slice(H256::from(seller).as_bytes());
+        order.replacement_pattern = vec![0; calldata.len()].into();
+        order.calldata = calldata.into();
+
+        let listing_time = timestamp.unwrap_or_else(time_source::local_listing_time);
+        order.listing_time = listing_time.into();
+        order.v = 0;
+        order.r = H256::zero();
+        order.s = H256::zero();
+        Ok(order)
+    }
+
+    /// The `data` passed by the calldata's `safeTransferFrom(address,address,uint256,
+    /// uint256,bytes)`, empty if it is not one
+    fn erc1155_data(&self) -> Vec<u8> {
+        self.transfer_data(&[
+            ParamType::Address,
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Bytes,
+        ])
+    }
+
+    /// The `data` passed by the calldata's `safeTransferFrom(address,address,uint256,
+    /// bytes)`, empty if it is not one
+    fn erc721_data(&self) -> Vec<u8> {
+        self.transfer_data(&[
+            ParamType::Address,
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Bytes,
+        ])
+    }
+
+    /// The last argument of the calldata, if it decodes as the `params`, whose last one
+    /// is `bytes`
+    fn transfer_data(&self, params: &[ParamType]) -> Vec<u8> {
+        self.calldata
+            .as_ref()
+            .get(4..)
+            .and_then(|args| ethers::abi::decode(params, args).ok())
+            .and_then(|mut tokens| tokens.pop())
+            .and_then(Token::into_bytes)
+            .unwrap_or_default()
+    }
+
+    /// Builds the buy order of the sell order of `listed` tokens of the `schema`, without
+    /// a fee recipient
+    fn buy_order(
+        &self,
+        schema: &str,
+        listed: U256,
+        args: BuyArgs,
+    ) -> Result<MinimalOrder, OrderMatchError> {
+        self.check_exchange()?;
+        self.check_delegate_call()?;
+        // Wyvern matches the listing whole, at its price, and finalizes it
+        if let Some(requested) = args.quantity.filter(|requested| *requested != listed) {
+            return Err(OrderMatchError::PartialFill {
+                order_hash: self.hash(),
+                requested,
+                listed,
+            });
+        }
+        let mut order = self.clone();
 
         // buy order
-        order.side = 0;
+        order.side = OrderSide::Buy;
         // the order maker is our taker
         order.maker = args.taker;
-        order.taker = self.maker.address;
-        order.target = args.token;
+        order.taker = self.maker;
+        // the orders transferring via the `MerkleValidator` target it instead
+        if self.merkle_transfer().is_none() {
+            order.target = args.token;
+        }
         order.expiration_time = 0.into();
         order.extra = 0.into();
         order.salt = ethers::core::rand::random::<u64>().into();
         order.fee_recipient = Address::zero(); // *constants::OPENSEA_FEE_RECIPIENT;
+                                               // sent by its maker, so it is not signed
+        order.v = 0;
+        order.r = H256::zero();
+        order.s = H256::zero();
 
-        let schema = &self.metadata.schema;
-        let calldata = if schema == "ERC721" {
-            // TODO: abigen should emit this as a typesafe method over a "Typed" BaseContract
-            let abi = ethers::contract::BaseContract::from(contracts::OPENSEA_ABI.clone());
-            let sig = id("transferFrom(address,address,uint256)");
-            let data = (Address::zero(), args.recipient, args.token_id);
+        let calldata = if let Some(transfer) = self.merkle_transfer() {
+            let quantity = if transfer == MerkleTransfer::Erc1155 {
+                listed
+            } else {
+                U256::one()
+            };
+            let calldata = transfer
+                .encode(
+                    self.calldata.as_ref(),
+                    Address::zero(),
+                    args.recipient,
+                    args.token,
+                    args.token_id,
+                    quantity,
+                )
+                .ok_or_else(|| OrderMatchError::InvalidOrder {
+                    order_hash: self.hash(),
+                    reason: "malformed MerkleValidator calldata".to_owned(),
+                })?;
+
+            // replacement for `from`
+            let mut replacement_pattern = vec![0; calldata.as_ref().len()];
+            replacement_pattern[4..4 + 32].copy_from_slice(&[0xff; 32]);
+            order.replacement_pattern = replacement_pattern.into();
+            calldata
+        } else if schema == "ERC721" {
+            // the listing's transfer function is called, with its `data` if any
+            let selector = self.calldata.as_ref().get(..4).unwrap_or_default();
+            let transfer =
+                Erc721Transfer::from_calldata(self.calldata.as_ref()).ok_or_else(|| {
+                    let mut unsupported = [0; 4];
+                    unsupported[..selector.len()].copy_from_slice(selector);
+                    OrderMatchError::UnsupportedTransfer(unsupported)
+                })?;
+            // encoded by hand, as the ABI has neither of the ERC721 `safeTransferFrom`s
+            let mut tokens = vec![
+                Token::Address(Address::zero()),
+                Token::Address(args.recipient),
+                Token::Uint(args.token_id),
+            ];
+            if transfer == Erc721Transfer::SafeTransferFromWithData {
+                tokens.push(Token::Bytes(self.erc721_data()));
+            }
+            let mut calldata = id(transfer.signature()).to_vec();
+            calldata.extend(ethers::abi::encode(&tokens));
+            let calldata = Bytes::from(calldata);
 
-            order.replacement_pattern = hex::decode("00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap().into();
-            abi.encode_with_selector(sig, data).unwrap()
+            // replacement for `from`
+            let mut replacement_pattern = vec![0; calldata.as_ref().len()];
+            replacement_pattern[4..4 + 32].copy_from_slice(&[0xff; 32]);
+            order.replacement_pattern = replacement_pattern.into();
+            calldata
         } else if schema == "ERC1155" {
-            // safeTransferFrom(address,address,uint256,uint256,bytes), replacement for `from`
-            order.replacement_pattern = hex::decode("00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000").unwrap().into();
+            let quantity = listed;
 
             let abi = ethers::contract::BaseContract::from(contracts::OPENSEA_ABI.clone());
             let sig = id("safeTransferFrom(address,address,uint256,uint256,bytes)");
+            // the `data` must be the sell's for the calldatas to match, e.g. the shared
+            // storefront's listings may pass some
             let data = (
                 Address::zero(),
                 args.recipient,
                 args.token_id,
-                self.quantity,
-                Vec::<u8>::new(),
+                quantity,
+                self.erc1155_data(),
             );
-            abi.encode_with_selector(sig, data).unwrap()
+            let calldata: Bytes = abi.encode_with_selector(sig, data)?;
+
+            // safeTransferFrom(address,address,uint256,uint256,bytes), replacement for `from`
+            let mut replacement_pattern = vec![0; calldata.as_ref().len()];
+            replacement_pattern[4..4 + 32].copy_from_slice(&[0xff; 32]);
+            order.replacement_pattern = replacement_pattern.into();
+            calldata
         } else {
-            panic!("Unsupported schema")
+            return Err(OrderMatchError::UnsupportedSchema(schema.to_owned()));
         };
         order.calldata = calldata;
 
         let listing_time = args
             .timestamp
-            .unwrap_or_else(|| chrono::offset::Local::now().timestamp() as u64 - 100);
+            .unwrap_or_else(time_source::local_listing_time);
         order.listing_time = listing_time.into();
 
-        order
+        Ok(order)
     }
 }
 
+/// The metadata of an order, in whichever of its shapes the API returned. Parsing it
+/// never fails, so that an odd order does not make the rest of a response unusable.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Metadata {
-    asset: AssetId,
-    schema: String,
+#[serde(untagged)]
+pub enum Metadata {
+    /// A single asset, as for most orders
+    Asset(AssetMetadata),
+    /// Several assets, sold at once
+    Bundle { bundle: BundleMetadata },
+    /// Metadata of any other shape, as returned by the API
+    Unknown(serde_json::Value),
+}
+
+impl Metadata {
+    pub fn asset(&self) -> Option<&AssetMetadata> {
+        match self {
+            Metadata::Asset(metadata) => Some(metadata),
+            _ => None,
+        }
+    }
+
+    pub fn asset_mut(&mut self) -> Option<&mut AssetMetadata> {
+        match self {
+            Metadata::Asset(metadata) => Some(metadata),
+            _ => None,
+        }
+    }
+
+    /// The assets of the order: none if the metadata is of an unknown shape
+    pub fn assets(&self) -> &[AssetId] {
+        match self {
+            Metadata::Asset(metadata) => std::slice::from_ref(&metadata.asset),
+            Metadata::Bundle { bundle } => &bundle.assets,
+            Metadata::Unknown(_) => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AssetMetadata {
+    pub asset: AssetId,
+    /// Empty if the API omitted it, in which case the client detects the token standard
+    /// of the collection on-chain before matching the order
+    #[serde(default)]
+    pub schema: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    pub assets: Vec<AssetId>,
+    /// The schema of each asset, if reported
+    #[serde(default)]
+    pub schemas: Vec<String>,
+}
+
+impl AssetMetadata {
+    /// The schema to match the order with. The API reports some items of the shared
+    /// storefront as ERC721s, while they are ERC1155s like all of its items.
+    pub fn resolved_schema(&self) -> &str {
+        if storefront::is_shared_storefront(self.asset.address) {
+            "ERC1155"
+        } else {
+            &self.schema
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AssetId {
     #[serde(deserialize_with = "u256_from_dec_str")]
-    id: U256,
-    address: Address,
+    pub id: U256,
+    pub address: Address,
+    /// The amount of the asset sold, if reported
+    #[serde(
+        default,
+        deserialize_with = "opt_u256_from_dec_str",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub quantity: Option<U256>,
 }
 
 use serde::de;
+
+/// Deserializes a string with `parse`, without allocating it if the deserializer can
+/// lend it, e.g. when parsing from a `&str`
+fn parse_str<'de, D, T, E>(deserializer: D, parse: fn(&str) -> Result<T, E>) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    E: std::fmt::Display,
+{
+    struct StrVisitor<T, E>(fn(&str) -> Result<T, E>);
+
+    impl<'de, T, E: std::fmt::Display> de::Visitor<'de> for StrVisitor<T, E> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string")
+        }
+
+        fn visit_str<Er: de::Error>(self, s: &str) -> Result<T, Er> {
+            (self.0)(s).map_err(Er::custom)
+        }
+    }
+
+    deserializer.deserialize_str(StrVisitor(parse))
+}
+
 pub fn u256_from_dec_str<'de, D>(deserializer: D) -> Result<U256, D::Error>
 where
     D: de::Deserializer<'de>,
 {
-    let s: &str = de::Deserialize::deserialize(deserializer)?;
-    U256::from_dec_str(s).map_err(de::Error::custom)
+    parse_str(deserializer, U256::from_dec_str)
+}
+
+pub fn opt_u256_from_dec_str<'de, D>(deserializer: D) -> Result<Option<U256>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    u256_from_dec_str(deserializer).map(Some)
+}
+
+/// Same as `Bytes`' own deserialization, without the intermediate `String`
+pub fn bytes_from_hex<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    parse_str(deserializer, |s| match s.strip_prefix("0x") {
+        Some(hex) => hex::decode(hex)
+            .map(Bytes::from)
+            .map_err(|err| format!("Invalid hex: {}", err)),
+        None => Err(format!("invalid value {:?}, expected 0x prefix", s)),
+    })
+}
+
+/// Deserializes `null` as the default value, for the fields the API sometimes nulls
+/// instead of omitting
+pub fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    T: Default + de::Deserialize<'de>,
+{
+    let value: Option<T> = de::Deserialize::deserialize(deserializer)?;
+    Ok(value.unwrap_or_default())
+}
+
+/// The API serializes some counts as floats, e.g. `10000.0`
+pub fn u64_from_number<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let n: f64 = de::Deserialize::deserialize(deserializer)?;
+    Ok(n as u64)
 }
 
 use std::str::FromStr;
+/// Parses a hex string, or zero for `null` and any other string
 pub fn h256_from_str<'de, D>(deserializer: D) -> Result<H256, D::Error>
 where
     D: de::Deserializer<'de>,
 {
-    let s: &str = de::Deserialize::deserialize(deserializer)?;
-    if s.starts_with("0x") {
-        H256::from_str(s).map_err(de::Error::custom)
-    } else {
-        Ok(H256::zero())
+    struct OptionVisitor;
+
+    impl<'de> de::Visitor<'de> for OptionVisitor {
+        type Value = H256;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string or null")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<H256, E> {
+            Ok(H256::zero())
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<H256, E> {
+            Ok(H256::zero())
+        }
+
+        fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<H256, D::Error> {
+            parse_str(deserializer, |s| {
+                if s.starts_with("0x") {
+                    H256::from_str(s)
+                } else {
+                    Ok(H256::zero())
+                }
+            })
+        }
     }
+
+    deserializer.deserialize_option(OptionVisitor)
 }
 
+/// An asset of a collection as returned by the assets API, along with its traits
 #[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionAsset {
+    #[serde(deserialize_with = "u256_from_dec_str")]
+    pub token_id: U256,
+    #[serde(default)]
+    pub traits: Vec<AssetTrait>,
+    /// Whether the asset can be traded through the Wyvern exchange
+    #[serde(default = "default_true")]
+    pub supports_wyvern: bool,
+    #[serde(default)]
+    pub is_nsfw: bool,
+    /// Whether OpenSea flagged the asset, e.g. as stolen
+    #[serde(default)]
+    pub is_flagged: bool,
+}
+
+impl CollectionAsset {
+    /// Whether the asset cannot be resold on OpenSea: it was flagged or does not
+    /// support the exchange
+    pub fn is_transfer_locked(&self) -> bool {
+        self.is_flagged || !self.supports_wyvern
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetTrait {
+    pub trait_type: String,
+    /// Either a string or a number
+    pub value: serde_json::Value,
+}
+
+impl AssetTrait {
+    fn value_str(&self) -> String {
+        match self.value {
+            serde_json::Value::String(ref value) => value.clone(),
+            ref value => value.to_string(),
+        }
+    }
+}
+
+/// Matches the assets with a trait of the given type and value (case-insensitive),
+/// parsed from `type=value`, e.g. `Fur=Gold`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraitFilter {
+    pub trait_type: String,
+    pub value: String,
+}
+
+impl TraitFilter {
+    pub fn matches(&self, asset: &CollectionAsset) -> bool {
+        asset.traits.iter().any(|t| {
+            t.trait_type.eq_ignore_ascii_case(&self.trait_type)
+                && t.value_str().eq_ignore_ascii_case(&self.value)
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid trait filter {0}, expected `type=value`")]
+pub struct InvalidTraitFilter(String);
+
+impl FromStr for TraitFilter {
+    type Err = InvalidTraitFilter;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(trait_type), Some(value)) if !trait_type.is_empty() => Ok(Self {
+                trait_type: trait_type.trim().to_owned(),
+                value: value.trim().to_owned(),
+            }),
+            _ => Err(InvalidTraitFilter(s.to_owned())),
+        }
+    }
+}
+
+/// The trading statistics of a collection. Prices and volumes are in ETH.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CollectionStats {
+    pub one_day_volume: f64,
+    pub one_day_change: f64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub one_day_sales: u64,
+    pub one_day_average_price: f64,
+    pub seven_day_volume: f64,
+    pub seven_day_change: f64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub seven_day_sales: u64,
+    pub seven_day_average_price: f64,
+    pub thirty_day_volume: f64,
+    pub thirty_day_change: f64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub thirty_day_sales: u64,
+    pub thirty_day_average_price: f64,
+    pub total_volume: f64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub total_sales: u64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub total_supply: u64,
+    #[serde(deserialize_with = "u64_from_number")]
+    pub num_owners: u64,
+    pub average_price: f64,
+    pub market_cap: f64,
+    /// Unset if nothing is listed
+    pub floor_price: Option<f64>,
+}
+
+impl CollectionStats {
+    /// The floor price in wei, rounded to the gwei
+    pub fn floor_price_wei(&self) -> Option<U256> {
+        self.floor_price
+            .map(|floor| U256::from((floor * 1e9).round() as u128) * U256::exp10(9))
+    }
+}
+
+/// An account as returned by the API. Any of its fields may be missing or `null`, and
+/// the whole object too (see [`null_as_default`]), which is the zero address.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct User {
+    #[serde(default)]
     pub user: Option<Username>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub profile_img_url: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub address: Address,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub config: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Username {
     username: Option<String>,
 }
 
+/// Implements the conversions of a Wyvern enum to its `u8` value, and to `U256`
+/// for the contract calls
+macro_rules! impl_wyvern_enum {
+    ($($name:ident),*) => {
+        $(
+            impl From<$name> for u8 {
+                fn from(value: $name) -> Self {
+                    value as u8
+                }
+            }
+
+            impl From<$name> for U256 {
+                fn from(value: $name) -> Self {
+                    U256::from(value as u8)
+                }
+            }
+        )*
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
 pub enum OrderSide {
-    Buy,
-    Sell,
+    Buy = 0,
+    Sell = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum SaleKind {
+    FixedPrice = 0,
+    DutchAuction = 1,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum FeeMethod {
+    ProtocolFee = 0,
+    SplitFee = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum HowToCall {
+    Call = 0,
+    DelegateCall = 1,
+}
+
+impl_wyvern_enum!(OrderSide, SaleKind, FeeMethod, HowToCall);
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn deser_order() {
-        let _order: Order = serde_json::from_str(include_str!("./../../order.json")).unwrap();
+        let order = crate::testing::order();
+        assert_eq!(order.side, OrderSide::Sell);
+        assert_eq!(order.sale_kind, SaleKind::FixedPrice);
+        assert_eq!(order.how_to_call, HowToCall::Call);
+        assert_eq!(U256::from(order.fee_method), U256::one());
+    }
+
+    #[test]
+    fn deser_order_from_owned_strings() {
+        let text = crate::testing::ORDER_JSON;
+        let borrowed: Order = serde_json::from_str(text).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(text).unwrap();
+        let owned: Order = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(
+            MinimalOrder::from(owned).hash(),
+            MinimalOrder::from(borrowed).hash()
+        );
+
+        value["calldata"] = "f242432a".into();
+        assert!(serde_json::from_value::<Order>(value).is_err());
+    }
+
+    #[test]
+    fn filters_by_traits() {
+        let asset: CollectionAsset = serde_json::from_value(serde_json::json!({
+            "token_id": "87",
+            "traits": [
+                { "trait_type": "Fur", "value": "Gold", "trait_count": 12 },
+                { "trait_type": "Level", "value": 3 },
+            ],
+        }))
+        .unwrap();
+
+        let filter: TraitFilter = "fur=gold".parse().unwrap();
+        assert!(filter.matches(&asset));
+        assert!("Level=3".parse::<TraitFilter>().unwrap().matches(&asset));
+        assert!(!"Fur=Brown".parse::<TraitFilter>().unwrap().matches(&asset));
+        assert!("Fur".parse::<TraitFilter>().is_err());
+    }
+
+    #[test]
+    fn filters_stale_orders() {
+        let mut order = crate::testing::order();
+        assert!(order.is_fillable(u64::MAX));
+
+        order.expiration_time = 100;
+        assert!(order.is_fillable(99));
+        assert!(!order.is_fillable(100));
+
+        order.expiration_time = 0;
+        order.cancelled = true;
+        assert!(!order.is_fillable(0));
+    }
+
+    #[test]
+    fn rejects_english_auctions() {
+        let mut order = crate::testing::order();
+        assert!(!order.is_english_auction());
+
+        order.fee_recipient.address = Address::zero();
+        assert!(order.is_english_auction());
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: order.target,
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        assert!(matches!(
+            order.match_sell(args),
+            Err(OrderMatchError::EnglishAuction(hash)) if hash == order.order_hash
+        ));
+    }
+
+    #[test]
+    fn rejects_partial_fills() {
+        let mut order = crate::testing::order();
+        order.quantity = 4.into();
+        order.asset_metadata_mut().unwrap().schema = "ERC1155".to_owned();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: order.target,
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: Some(1.into()),
+            max_price: None,
+            total_budget: None,
+        };
+        assert!(matches!(
+            order.match_sell(args.clone()),
+            Err(OrderMatchError::PartialFill { requested, listed, .. })
+                if requested == 1.into() && listed == 4.into()
+        ));
+
+        let buy = order
+            .match_sell(BuyArgs {
+                quantity: Some(4.into()),
+                ..args
+            })
+            .unwrap();
+        assert_eq!(buy.base_price, order.base_price);
+        assert_eq!(
+            U256::from_big_endian(&buy.calldata.as_ref()[4 + 96..4 + 128]),
+            4.into()
+        );
+    }
+
+    #[test]
+    fn matches_shared_storefront_listings() {
+        let mut order = crate::testing::order();
+        let metadata = order.asset_metadata_mut().unwrap();
+        metadata.asset.address = *constants::SHARED_STOREFRONT_ADDRESS;
+        // as reported by the API for some of its items
+        metadata.schema = "ERC721".to_owned();
+        assert_eq!(metadata.resolved_schema(), "ERC1155");
+
+        // listed with some `data`
+        let mut calldata = id("safeTransferFrom(address,address,uint256,uint256,bytes)").to_vec();
+        calldata.extend(ethers::abi::encode(&[
+            Token::Address(order.maker.address),
+            Token::Address(Address::zero()),
+            Token::Uint(87.into()),
+            Token::Uint(1.into()),
+            Token::Bytes(vec![1, 2, 3]),
+        ]));
+        let mut pattern = vec![0; calldata.len()];
+        pattern[4 + 32..4 + 64].copy_from_slice(&[0xff; 32]);
+        order.calldata = calldata.into();
+        order.replacement_pattern = pattern.into();
+
+        let recipient = Address::random();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient,
+            token: order.target,
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let buy = order.match_sell(args).unwrap();
+        assert_eq!(buy.schema(), Some("ERC1155"));
+        assert_eq!(buy.erc1155_data(), vec![1, 2, 3]);
+        assert_eq!(
+            buy.replacement_pattern.as_ref().len(),
+            buy.calldata.as_ref().len()
+        );
+        assert_eq!(
+            &buy.calldata.as_ref()[4 + 32..4 + 64],
+            ethers::abi::encode(&[Token::Address(recipient)]).as_slice()
+        );
+    }
+
+    #[test]
+    fn matches_each_erc721_transfer() {
+        let mut order = crate::testing::order();
+        order.asset_metadata_mut().unwrap().schema = "ERC721".to_owned();
+        let recipient = Address::random();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient,
+            token: order.target,
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let mut list = |signature: &str, mut tokens: Vec<Token>| {
+            let mut args = vec![
+                Token::Address(order.maker.address),
+                Token::Address(Address::zero()),
+                Token::Uint(87.into()),
+            ];
+            args.append(&mut tokens);
+            let mut calldata = id(signature).to_vec();
+            calldata.extend(ethers::abi::encode(&args));
+            let mut pattern = vec![0; calldata.len()];
+            pattern[4 + 32..4 + 64].copy_from_slice(&[0xff; 32]);
+            order.calldata = calldata.into();
+            order.replacement_pattern = pattern.into();
+            order.clone()
+        };
+
+        for transfer in Erc721Transfer::ALL.iter() {
+            // listed with some `data`, if the function takes any
+            let (data, tokens) = match transfer {
+                Erc721Transfer::SafeTransferFromWithData => {
+                    (vec![1, 2, 3], vec![Token::Bytes(vec![1, 2, 3])])
+                }
+                _ => (vec![], vec![]),
+            };
+            let order = list(transfer.signature(), tokens);
+            let buy = order.match_sell(args.clone()).unwrap();
+            assert_eq!(buy.schema(), Some("ERC721"));
+            assert_eq!(&buy.calldata.as_ref()[..4], id(transfer.signature()));
+            assert_eq!(
+                &buy.calldata.as_ref()[4 + 32..4 + 64],
+                ethers::abi::encode(&[Token::Address(recipient)]).as_slice()
+            );
+            assert_eq!(buy.erc721_data(), data);
+            assert_eq!(
+                buy.replacement_pattern.as_ref().len(),
+                buy.calldata.as_ref().len()
+            );
+            assert_eq!(&buy.replacement_pattern.as_ref()[4..4 + 32], &[0xff; 32]);
+        }
+
+        // e.g. a custom transfer wrapper
+        let order = list("transferToken(address,address,uint256)", vec![]);
+        assert!(matches!(
+            order.match_sell(args),
+            Err(OrderMatchError::UnsupportedTransfer(selector))
+                if selector == id("transferToken(address,address,uint256)")
+        ));
+    }
+
+    #[test]
+    fn matches_merkle_validator_listings() {
+        let fixture = |json: &str| {
+            let json: serde_json::Value = serde_json::from_str(json).unwrap();
+            serde_json::from_value::<Order>(json["orders"][0].clone()).unwrap()
+        };
+        // as `guardedArrayReplace` does on-chain
+        let replaced = |calldata: &Bytes, pattern: &Bytes, other: &Bytes| {
+            let mut calldata = calldata.to_vec();
+            for (i, byte) in calldata.iter_mut().enumerate() {
+                if pattern.as_ref()[i] == 0xff {
+                    *byte = other.as_ref()[i];
+                }
+            }
+            calldata
+        };
+        let erc721 = fixture(include_str!(
+            "./../fixtures/synthetic-orders/merkle-validator-erc721.json"
+        ));
+        let erc1155 = fixture(include_str!(
+            "./../fixtures/synthetic-orders/merkle-validator-erc1155.json"
+        ));
+
+        for (order, transfer) in [
+            (erc721, MerkleTransfer::Erc721),
+            (erc1155, MerkleTransfer::Erc1155),
+        ] {
+            let sell = MinimalOrder::from(order.clone());
+            let asset = order.asset_metadata().unwrap().asset.clone();
+            assert_eq!(sell.merkle_transfer(), Some(transfer));
+            assert_eq!(sell.target, *constants::MERKLE_VALIDATOR_ADDRESS);
+            assert_eq!(sell.nft(), asset.address);
+            assert_eq!(sell.schema(), Some(transfer.schema()));
+            assert_eq!(sell.token_id(), Some(asset.id));
+            assert_eq!(sell.quantity(), U256::one());
+
+            let recipient = Address::random();
+            let buy = order
+                .match_sell(BuyArgs {
+                    taker: Address::random(),
+                    recipient,
+                    token: asset.address,
+                    token_id: asset.id,
+                    timestamp: Some(0),
+                    quantity: None,
+                    max_price: None,
+                    total_budget: None,
+                })
+                .unwrap();
+            assert_eq!(buy.target, sell.target);
+            assert_eq!(buy.how_to_call, HowToCall::DelegateCall);
+            assert_eq!(buy.merkle_transfer(), Some(transfer));
+            assert_eq!(buy.token_id(), Some(asset.id));
+            assert_eq!(
+                &buy.calldata.as_ref()[4 + 32..4 + 64],
+                ethers::abi::encode(&[Token::Address(recipient)]).as_slice()
+            );
+            assert_eq!(
+                replaced(&buy.calldata, &buy.replacement_pattern, &sell.calldata),
+                replaced(&sell.calldata, &sell.replacement_pattern, &buy.calldata)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_delegate_calls_to_other_contracts() {
+        let json: serde_json::Value = serde_json::from_str(include_str!(
+            "./../fixtures/synthetic-orders/merkle-validator-erc721.json"
+        ))
+        .unwrap();
+        let order = serde_json::from_value::<Order>(json["orders"][0].clone()).unwrap();
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: asset.address,
+            token_id: asset.id,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+
+        // the validator's functions, delegate called on another contract
+        let mut spoofed = MinimalOrder::from(order);
+        let target = Address::random();
+        spoofed.target = target;
+        assert_eq!(spoofed.merkle_transfer(), None);
+        assert!(matches!(
+            spoofed.match_sell(args.clone()),
+            Err(OrderMatchError::UntrustedDelegateCall { target: t, .. }) if t == target
+        ));
+
+        // any other calldata, delegate called
+        let mut order = crate::testing::order();
+        order.how_to_call = HowToCall::DelegateCall;
+        assert!(matches!(
+            order.match_sell(args),
+            Err(OrderMatchError::UntrustedDelegateCall { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_the_orders_of_the_v2_3_exchange() {
+        let json: serde_json::Value = serde_json::from_str(include_str!(
+            "./../fixtures/synthetic-orders/merkle-validator-erc721.json"
+        ))
+        .unwrap();
+        let mut order = serde_json::from_value::<Order>(json["orders"][0].clone()).unwrap();
+        order.exchange = *constants::WYVERN_V2_3_ADDRESS;
+        let asset = order.asset_metadata().unwrap().asset.clone();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: asset.address,
+            token_id: asset.id,
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+
+        // still decoded, but neither signed nor matched the way the crate does it
+        let sell = MinimalOrder::from(order.clone());
+        assert_eq!(sell.merkle_transfer(), Some(MerkleTransfer::Erc721));
+        assert!(matches!(
+            order.match_sell(args.clone()),
+            Err(OrderMatchError::UnsupportedExchange { exchange, .. })
+                if exchange == *constants::WYVERN_V2_3_ADDRESS
+        ));
+        assert!(matches!(
+            sell.match_sell(args),
+            Err(OrderMatchError::UnsupportedExchange { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unmatchable_orders() {
+        let mut order = crate::testing::order();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: order.target,
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+
+        order.asset_metadata_mut().unwrap().schema = "CryptoPunks".to_owned();
+        assert!(matches!(
+            order.match_sell(args.clone()),
+            Err(OrderMatchError::UnsupportedSchema(schema)) if schema == "CryptoPunks"
+        ));
+
+        order.metadata = None;
+        assert!(matches!(
+            order.match_sell(args),
+            Err(OrderMatchError::MissingMetadata(_))
+        ));
+    }
+
+    #[test]
+    fn parses_null_users() {
+        let mut json = crate::testing::order_json();
+        json["fee_recipient"] = serde_json::Value::Null;
+        json["taker"] = serde_json::json!({
+            "user": null,
+            "profile_img_url": null,
+            "address": null,
+            "config": null,
+        });
+        json["maker"]
+            .as_object_mut()
+            .unwrap()
+            .remove("profile_img_url");
+        let order: Order = serde_json::from_value(json).unwrap();
+        assert!(order.fee_recipient.address.is_zero());
+        assert!(order.taker.address.is_zero());
+        assert!(order.taker.user.is_none());
+        assert!(order.maker.profile_img_url.is_empty());
+        assert!(!order.maker.address.is_zero());
+    }
+
+    #[test]
+    fn clears_the_signature_of_orders_approved_on_chain() {
+        let mut json = crate::testing::order_json();
+        json["approved_on_chain"] = false.into();
+        json["v"] = 27.into();
+        json["r"] = format!("{:?}", H256::repeat_byte(1)).into();
+        json["s"] = format!("{:?}", H256::repeat_byte(2)).into();
+        let signed: Order = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(MinimalOrder::from(signed.clone()).v, 27);
+
+        json["approved_on_chain"] = true.into();
+        json["v"] = serde_json::Value::Null;
+        json["r"] = serde_json::Value::Null;
+        let approved: Order = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!((approved.v, approved.r), (0, H256::zero()));
+        assert_eq!(approved.s, signed.s);
+        let minimal = MinimalOrder::from(approved);
+        assert_eq!(
+            (minimal.v, minimal.r, minimal.s),
+            (0, H256::zero(), H256::zero())
+        );
+        // the hash does not cover the signature
+        assert_eq!(minimal.hash(), signed.order_hash);
+    }
+
+    #[test]
+    fn parses_each_metadata_shape() {
+        let mut json = crate::testing::order_json();
+        let args = BuyArgs {
+            taker: Address::random(),
+            recipient: Address::random(),
+            token: Address::random(),
+            token_id: 87.into(),
+            timestamp: Some(0),
+            quantity: None,
+            max_price: None,
+            total_budget: None,
+        };
+        let parse = |metadata| {
+            let mut json = json.clone();
+            json["metadata"] = metadata;
+            serde_json::from_value::<Order>(json).unwrap()
+        };
+
+        let order = parse(serde_json::json!({
+            "asset": { "id": "87", "address": Address::random(), "quantity": "3" }
+        }));
+        let metadata = order.asset_metadata().unwrap();
+        assert_eq!(metadata.asset.quantity, Some(3.into()));
+        assert_eq!(metadata.schema, "");
+
+        let order = parse(serde_json::json!({
+            "bundle": {
+                "assets": [
+                    { "id": "1", "address": Address::random() },
+                    { "id": "2", "address": Address::random() }
+                ],
+                "schemas": ["ERC721", "ERC1155"]
+            }
+        }));
+        assert!(order.asset_metadata().is_none());
+        assert_eq!(order.metadata.as_ref().unwrap().assets().len(), 2);
+        assert!(matches!(
+            order.match_sell(args.clone()),
+            Err(OrderMatchError::Bundle { assets: 2, .. })
+        ));
+
+        let order = parse(serde_json::json!({ "schema": "ERC721" }));
+        assert!(matches!(order.metadata, Some(Metadata::Unknown(_))));
+        assert!(matches!(
+            order.match_sell(args),
+            Err(OrderMatchError::MissingMetadata(_))
+        ));
+
+        json.as_object_mut().unwrap().remove("metadata");
+        let order: Order = serde_json::from_value(json).unwrap();
+        assert!(order.metadata.is_none());
+    }
+
+    #[test]
+    fn hashes_order() {
+        let order = crate::testing::order();
+        let order_hash = order.order_hash;
+        let order = MinimalOrder::from(order);
+        assert_eq!(order.hash(), order_hash);
+        // the `prefixed_hash` field of the API response
+        assert_eq!(
+            order.hash_to_sign(),
+            "0xd7749ec669e68dff88cd7a1293b880b123f5b146a93f2336bbba78c31121e422"
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_order_signature() {
+        use ethers::signers::{LocalWallet, Signer};
+
+        let order = crate::testing::order();
+        let mut order = MinimalOrder::from(order);
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        order.maker = wallet.address();
+
+        let signature = wallet.sign_message(order.hash()).await.unwrap();
+        order.v = signature.v as u8;
+        let mut word = [0; 32];
+        signature.r.to_big_endian(&mut word);
+        order.r = H256::from(word);
+        signature.s.to_big_endian(&mut word);
+        order.s = H256::from(word);
+        order.verify_signature(1).unwrap();
+
+        // wrong exchange for rinkeby
+        assert!(matches!(
+            order.verify_signature(4),
+            Err(SignatureError::WrongExchange { .. })
+        ));
+
+        // tampered order
+        order.base_price += 1.into();
+        assert!(matches!(
+            order.verify_signature(1),
+            Err(SignatureError::SignerMismatch { .. })
+        ));
     }
 }
@@ -0,0 +1,103 @@
+//! ETH amounts as humans write them: [`parse_eth`] for e.g. `0.25` and [`format_eth`]
+//! for e.g. `0.25 ETH`, both exact in `U256` Wei (no float on the way)
+use ethers::types::U256;
+use thiserror::Error;
+
+use crate::display::format_amount;
+
+/// The decimals of ETH
+const ETH_DECIMALS: usize = 18;
+
+/// The decimals [`format_eth`] rounds to
+const DISPLAYED_DECIMALS: usize = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UnitsError {
+    #[error("Invalid amount {0:?}, expected e.g. `0.25`, `0.25eth`, `5gwei` or `1000wei`")]
+    Invalid(String),
+    #[error("Amount {0:?} has more decimals than its unit")]
+    TooPrecise(String),
+    #[error("Amount {0:?} does not fit 256 bits")]
+    Overflow(String),
+}
+
+/// Parses an amount of ETH (e.g. `0.25` or `0.25 ETH`) to Wei. Amounts of Gwei or Wei
+/// are accepted with their unit, e.g. `5gwei` or `1000 wei`.
+pub fn parse_eth(s: &str) -> Result<U256, UnitsError> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (amount, decimals) = if let Some(amount) = lower.strip_suffix("gwei") {
+        (amount, 9)
+    } else if let Some(amount) = lower.strip_suffix("wei") {
+        (amount, 0)
+    } else if let Some(amount) = lower.strip_suffix("eth") {
+        (amount, ETH_DECIMALS)
+    } else {
+        (lower.as_str(), ETH_DECIMALS)
+    };
+
+    let amount = amount.trim();
+    let (int, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    let is_digits = |digits: &str| digits.bytes().all(|byte| byte.is_ascii_digit());
+    if (int.is_empty() && frac.is_empty()) || !is_digits(int) || !is_digits(frac) {
+        return Err(UnitsError::Invalid(s.to_owned()));
+    }
+    if frac.len() > decimals {
+        return Err(UnitsError::TooPrecise(s.to_owned()));
+    }
+    let digits = format!("{}{:0<width$}", int, frac, width = decimals);
+    U256::from_dec_str(&digits).map_err(|_| UnitsError::Overflow(s.to_owned()))
+}
+
+/// Formats an amount of Wei as ETH rounded to 4 decimals, e.g. `0.2346 ETH`. Amounts
+/// rounding to zero are shown as `<0.0001 ETH`.
+pub fn format_eth(wei: U256) -> String {
+    let unit = U256::exp10(ETH_DECIMALS - DISPLAYED_DECIMALS);
+    let rounded = wei.saturating_add(unit / 2) / unit;
+    if rounded.is_zero() && !wei.is_zero() {
+        return format!("<{} ETH", format_amount(1.into(), DISPLAYED_DECIMALS as u8));
+    }
+    format!("{} ETH", format_amount(rounded, DISPLAYED_DECIMALS as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_eth() {
+        let eth = U256::exp10(18);
+        assert_eq!(parse_eth("0.25").unwrap(), eth / 4);
+        assert_eq!(parse_eth("2").unwrap(), eth * 2);
+        assert_eq!(parse_eth(".5 ETH").unwrap(), eth / 2);
+        assert_eq!(parse_eth("5gwei").unwrap(), U256::exp10(9) * 5);
+        assert_eq!(parse_eth("1000 Wei").unwrap(), 1000.into());
+        assert_eq!(
+            parse_eth("0.1234567890123456789"),
+            Err(UnitsError::TooPrecise("0.1234567890123456789".to_owned()))
+        );
+        assert_eq!(
+            parse_eth("1.5wei"),
+            Err(UnitsError::TooPrecise("1.5wei".to_owned()))
+        );
+        for invalid in &["", ".", "-1", "1e18", "0x10", "1,5", "eth"] {
+            assert_eq!(
+                parse_eth(invalid),
+                Err(UnitsError::Invalid(invalid.to_string()))
+            );
+        }
+        assert!(matches!(
+            parse_eth(&"9".repeat(80)),
+            Err(UnitsError::Overflow(_))
+        ));
+
+        assert_eq!(format_eth(eth / 4), "0.25 ETH");
+        assert_eq!(format_eth(eth * 80), "80 ETH");
+        assert_eq!(
+            format_eth(U256::from(123_456_789_000_000_000u64)),
+            "0.1235 ETH"
+        );
+        assert_eq!(format_eth(U256::exp10(13)), "<0.0001 ETH");
+        assert_eq!(format_eth(U256::zero()), "0 ETH");
+        assert!(format_eth(U256::MAX).ends_with(" ETH"));
+    }
+}
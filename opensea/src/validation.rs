@@ -0,0 +1,409 @@
+//! Pre-flight validation of many listings at once. The reads of
+//! [`Client::verify_fulfillable`] and `ordersCanMatch_` take a handful of RPC calls per
+//! listing, so they are batched through Multicall3 instead: one roundtrip for the
+//! ownership, proxies and matching of all listings, and one for the proxies' state and
+//! approvals. On chains (or at blocks) without Multicall3 the calls are made one by one.
+//! The listings of a snipe, and those bought via [`Client::buy`], are all checked this
+//! way before their buy calls are built.
+use ethers::{
+    abi::{Detokenize, Function, Token},
+    contract::builders::ContractCall,
+    providers::Middleware,
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockId, Bytes, NameOrAddress,
+        TransactionRequest, U256,
+    },
+};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    approvals::UnfulfillableReason,
+    constants,
+    contracts::{Call3, Multicall3, OwnableDelegateProxy, NFT},
+    match_args,
+    types::{MinimalOrder, Order},
+    Client, ClientError,
+};
+
+/// Maximum number of calls aggregated by a single `aggregate3`
+const MAX_BATCH_CALLS: usize = 300;
+
+/// Read-only calls, executed through Multicall3 if it is deployed
+struct ReadBatch<M> {
+    provider: Arc<M>,
+    block: Option<BlockId>,
+    multicall3: bool,
+    calls: Vec<(Address, Bytes, Function)>,
+}
+
+impl<M: Middleware> ReadBatch<M> {
+    fn new(provider: Arc<M>, block: Option<BlockId>, multicall3: bool) -> Self {
+        Self {
+            provider,
+            block,
+            multicall3,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Adds the call, returning the index of its result
+    fn push<D>(&mut self, call: ContractCall<M, D>) -> usize {
+        let target = match call.tx.to() {
+            Some(NameOrAddress::Address(address)) => *address,
+            _ => Address::zero(),
+        };
+        let data = call.tx.data().cloned().unwrap_or_default();
+        self.calls.push((target, data, call.function));
+        self.calls.len() - 1
+    }
+
+    /// Returns the decoded output of each call, `None` for the calls which reverted
+    async fn execute(&self) -> Vec<Option<Vec<Token>>> {
+        let mut results = Vec::with_capacity(self.calls.len());
+        for chunk in self.calls.chunks(MAX_BATCH_CALLS) {
+            let outputs = match self.aggregate(chunk).await {
+                Some(outputs) => outputs,
+                None => self.call_each(chunk).await,
+            };
+            results.extend(chunk.iter().zip(outputs).map(|((_, _, function), output)| {
+                output.and_then(|output| function.decode_output(output.as_ref()).ok())
+            }));
+        }
+        results
+    }
+
+    async fn aggregate(&self, chunk: &[(Address, Bytes, Function)]) -> Option<Vec<Option<Bytes>>> {
+        if !self.multicall3 {
+            return None;
+        }
+        let multicall = Multicall3::new(*constants::MULTICALL3_ADDRESS, self.provider.clone());
+        let calls = chunk
+            .iter()
+            .map(|(target, data, _)| Call3 {
+                target: *target,
+                allow_failure: true,
+                call_data: data.to_vec(),
+            })
+            .collect();
+        let mut call = multicall.aggregate_3(calls);
+        call.block = self.block;
+        match call.call().await {
+            Ok(results) => Some(
+                results
+                    .into_iter()
+                    .map(|(success, data)| success.then(|| Bytes::from(data)))
+                    .collect(),
+            ),
+            Err(err) => {
+                say!("Multicall3 failed, making the calls one by one: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn call_each(&self, chunk: &[(Address, Bytes, Function)]) -> Vec<Option<Bytes>> {
+        let mut outputs = Vec::with_capacity(chunk.len());
+        for (target, data, _) in chunk {
+            let tx: TypedTransaction = TransactionRequest::new()
+                .to(*target)
+                .data(data.clone())
+                .into();
+            outputs.push(self.provider.call(&tx, self.block).await.ok());
+        }
+        outputs
+    }
+}
+
+/// Decodes the `index`th result, if the call succeeded
+fn decoded<D: Detokenize>(results: &[Option<Vec<Token>>], index: usize) -> Option<D> {
+    results[index]
+        .clone()
+        .and_then(|tokens| D::from_tokens(tokens).ok())
+}
+
+/// Whether Multicall3 is deployed at the block
+async fn multicall3_deployed<M: Middleware>(provider: &M, block: Option<BlockId>) -> bool {
+    provider
+        .get_code(*constants::MULTICALL3_ADDRESS, block)
+        .await
+        .map(|code| !code.as_ref().is_empty())
+        .unwrap_or(false)
+}
+
+impl<M: Middleware, P> Client<M, P> {
+    /// Validates the sell orders before buying them with their matching buy orders,
+    /// returning the outcome of each in the same order. Performs the checks of
+    /// [`Client::verify_fulfillable`] for the quantity of the buy order, and whether
+    /// the exchange accepts the match (`ordersCanMatch_`), failing with
+    /// `ClientError::Unfulfillable`. The reads are batched, see the [module
+    /// docs](crate::validation).
+    pub async fn preflight(
+        &self,
+        orders: &[(Order, MinimalOrder)],
+        block: Option<BlockId>,
+    ) -> Vec<Result<(), ClientError>> {
+        let multicall3 = multicall3_deployed(self.provider.as_ref(), block).await;
+
+        // the ownership, the makers' proxies and the matching
+        let mut batch = ReadBatch::new(self.provider.clone(), block, multicall3);
+        let implementation = batch.push(self.proxy_registry().delegate_proxy_implementation());
+        let mut proxies = HashMap::new();
+        let mut ownership = Vec::with_capacity(orders.len());
+        let mut can_match = Vec::with_capacity(orders.len());
+        for (sell, buy) in orders {
+            let maker = sell.maker.address;
+            let owner = sell.asset_metadata().and_then(|metadata| {
+                let nft = NFT::new(metadata.asset.address, self.provider.clone());
+                match metadata.resolved_schema() {
+                    "ERC721" => Some(batch.push(nft.owner_of(metadata.asset.id))),
+                    "ERC1155" => Some(batch.push(nft.balance_of(maker, metadata.asset.id))),
+                    _ => None,
+                }
+            });
+            ownership.push(owner);
+            if owner.is_some() && !proxies.contains_key(&maker) {
+                let index = batch.push(self.proxy_registry().proxies(maker));
+                proxies.insert(maker, index);
+            }
+
+            let sell = MinimalOrder::from(sell.clone());
+            let (addrs, uints, methods) = match_args(buy, &sell);
+            let call = self
+                .contracts
+                .method::<_, bool>(
+                    "ordersCanMatch_",
+                    (
+                        addrs,
+                        uints,
+                        methods,
+                        buy.calldata.to_vec(),
+                        sell.calldata.to_vec(),
+                        buy.replacement_pattern.to_vec(),
+                        sell.replacement_pattern.to_vec(),
+                        buy.static_extradata.to_vec(),
+                        sell.static_extradata.to_vec(),
+                    ),
+                )
+                .expect("ordersCanMatch_ is in the ABI");
+            can_match.push(batch.push(call));
+        }
+        let results = batch.execute().await;
+        let implementation: Option<Address> = decoded(&results, implementation);
+        let proxies: HashMap<Address, Address> = proxies
+            .into_iter()
+            .filter_map(|(maker, index)| {
+                decoded::<Address>(&results, index)
+                    .filter(|proxy| !proxy.is_zero())
+                    .map(|proxy| (maker, proxy))
+            })
+            .collect();
+
+        // the state of the proxies and the approvals
+        let mut batch = ReadBatch::new(self.provider.clone(), block, multicall3);
+        let mut proxy_states = HashMap::new();
+        let mut approvals = HashMap::new();
+        for (sell, _) in orders {
+            let maker = sell.maker.address;
+            if let (Some(metadata), Some(proxy)) = (sell.asset_metadata(), proxies.get(&maker)) {
+                proxy_states.entry(maker).or_insert_with(|| {
+                    let contract = OwnableDelegateProxy::new(*proxy, self.provider.clone());
+                    (
+                        batch.push(contract.implementation()),
+                        batch.push(contract.revoked()),
+                    )
+                });
+                let nft = metadata.asset.address;
+                approvals.entry((nft, maker)).or_insert_with(|| {
+                    let contract = NFT::new(nft, self.provider.clone());
+                    batch.push(contract.is_approved_for_all(maker, *proxy))
+                });
+            }
+        }
+        let states = batch.execute().await;
+
+        orders
+            .iter()
+            .zip(ownership)
+            .zip(can_match)
+            .map(|(((sell, buy), owner), can_match)| {
+                let unfulfillable = |reason| ClientError::Unfulfillable {
+                    order_hash: sell.order_hash,
+                    reason,
+                };
+                let failed = |call: &str| {
+                    unfulfillable(UnfulfillableReason::CheckFailed(format!("{} failed", call)))
+                };
+                let maker = sell.maker.address;
+
+                if let (Some(owner), Some(metadata)) = (owner, sell.asset_metadata()) {
+                    if metadata.resolved_schema() == "ERC721" {
+                        // burnt tokens revert
+                        let owner = decoded(&results, owner).unwrap_or_else(Address::zero);
+                        if owner != maker {
+                            return Err(unfulfillable(UnfulfillableReason::NotOwner {
+                                maker,
+                                owner,
+                            }));
+                        }
+                    } else {
+                        let balance = decoded(&results, owner).unwrap_or_else(U256::zero);
+                        let required = buy.quantity();
+                        if balance < required {
+                            return Err(unfulfillable(UnfulfillableReason::InsufficientBalance {
+                                maker,
+                                balance,
+                                required,
+                            }));
+                        }
+                    }
+
+                    let (implementation_index, revoked_index) = *proxy_states
+                        .get(&maker)
+                        .ok_or_else(|| unfulfillable(UnfulfillableReason::NoProxy(maker)))?;
+                    let revoked: bool =
+                        decoded(&states, revoked_index).ok_or_else(|| failed("revoked"))?;
+                    if revoked {
+                        return Err(unfulfillable(UnfulfillableReason::ProxyRevoked(maker)));
+                    }
+                    let proxy_implementation: Address = decoded(&states, implementation_index)
+                        .ok_or_else(|| failed("implementation"))?;
+                    let current =
+                        implementation.ok_or_else(|| failed("delegateProxyImplementation"))?;
+                    if proxy_implementation != current {
+                        return Err(unfulfillable(UnfulfillableReason::ProxyOutdated(maker)));
+                    }
+                    let approved: bool =
+                        decoded(&states, approvals[&(metadata.asset.address, maker)])
+                            .ok_or_else(|| failed("isApprovedForAll"))?;
+                    if !approved {
+                        return Err(unfulfillable(UnfulfillableReason::NotApproved(maker)));
+                    }
+                }
+
+                // reverts if the orders are malformed
+                if !decoded::<bool>(&results, can_match).unwrap_or(false) {
+                    return Err(unfulfillable(UnfulfillableReason::CannotMatch));
+                }
+                Ok(())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{anvil_wallet, Listing},
+        BuyArgs, MockOrderProvider, TokenStandard,
+    };
+    use ethers::{providers::Provider, signers::Signer};
+
+    fn encode(token: Token) -> Vec<u8> {
+        ethers::abi::encode(&[token])
+    }
+
+    async fn order(nft: Address, token_id: u64) -> (Order, MinimalOrder) {
+        let sell = Listing::new(nft, token_id.into(), TokenStandard::Erc721, U256::exp10(17))
+            .sign(&anvil_wallet(1))
+            .await
+            .unwrap();
+        let taker = anvil_wallet(0).address();
+        let buy = sell
+            .match_sell(BuyArgs {
+                token_id: token_id.into(),
+                taker,
+                token: nft,
+                recipient: taker,
+                timestamp: Some(1),
+                quantity: None,
+                max_price: None,
+                total_budget: None,
+            })
+            .unwrap();
+        (sell, buy)
+    }
+
+    #[tokio::test]
+    async fn batches_the_checks_through_multicall3() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        let nft = Address::random();
+        let orders = vec![order(nft, 1).await, order(nft, 2).await];
+        let maker = orders[0].0.maker.address;
+        let (proxy, implementation) = (Address::random(), Address::random());
+
+        let aggregated = |results: Vec<Option<Token>>| {
+            let results = results
+                .into_iter()
+                .map(|result| {
+                    Token::Tuple(vec![
+                        Token::Bool(result.is_some()),
+                        Token::Bytes(result.map(encode).unwrap_or_default()),
+                    ])
+                })
+                .collect();
+            Bytes::from(encode(Token::Array(results)))
+        };
+        // served last first: the proxies' state and approvals of the maker, then the
+        // ownership, proxy and matching of both orders, then the deployment check
+        mock.push(aggregated(vec![
+            Some(Token::Address(implementation)),
+            Some(Token::Bool(false)),
+            Some(Token::Bool(true)),
+        ]))
+        .unwrap();
+        mock.push(aggregated(vec![
+            Some(Token::Address(implementation)),
+            Some(Token::Address(maker)),
+            Some(Token::Address(proxy)),
+            Some(Token::Bool(true)),
+            // the second token was burnt
+            None,
+            None,
+        ]))
+        .unwrap();
+        mock.push(Bytes::from(vec![1])).unwrap();
+
+        let results = client.preflight(&orders, None).await;
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(ClientError::Unfulfillable {
+                reason: UnfulfillableReason::NotOwner { owner, .. },
+                ..
+            }) if owner.is_zero()
+        ));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_individual_calls() {
+        let (provider, mock) = Provider::mocked();
+        let client = Client::with_api(Arc::new(provider), MockOrderProvider::default());
+        let orders = vec![order(Address::random(), 1).await];
+        let maker = orders[0].0.maker.address;
+        let implementation = encode(Token::Address(Address::random()));
+
+        // isApprovedForAll, revoked, implementation, ordersCanMatch_, proxies, ownerOf,
+        // delegateProxyImplementation and the empty code of Multicall3
+        mock.push(Bytes::from(encode(Token::Bool(true)))).unwrap();
+        mock.push(Bytes::from(encode(Token::Bool(false)))).unwrap();
+        mock.push(Bytes::from(implementation.clone())).unwrap();
+        mock.push(Bytes::from(encode(Token::Bool(false)))).unwrap();
+        mock.push(Bytes::from(encode(Token::Address(Address::random()))))
+            .unwrap();
+        mock.push(Bytes::from(encode(Token::Address(maker))))
+            .unwrap();
+        mock.push(Bytes::from(implementation)).unwrap();
+        mock.push(Bytes::default()).unwrap();
+
+        let results = client.preflight(&orders, None).await;
+        assert!(matches!(
+            results[0],
+            Err(ClientError::Unfulfillable {
+                reason: UnfulfillableReason::CannotMatch,
+                ..
+            })
+        ));
+    }
+}
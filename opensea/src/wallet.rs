@@ -0,0 +1,85 @@
+//! Signing and broadcasting of the buy calls with the client's own wallet, for
+//! services embedding the library without a `SignerMiddleware` of their own
+use ethers::{
+    contract::builders::ContractCall,
+    middleware::SignerMiddleware,
+    providers::Middleware,
+    signers::{LocalWallet, Signer},
+    types::{H256, U256},
+};
+
+use crate::{BuyArgs, Client, ClientError, OrderProvider};
+
+impl<M: Middleware, P: OrderProvider> Client<M, P> {
+    /// Sets the wallet used by [`Client::buy_and_send`] and [`Client::send_calls`]
+    pub fn with_signer(mut self, wallet: LocalWallet) -> Self {
+        self.signer = Some(wallet);
+        self
+    }
+
+    /// Same as [`Client::buy`], but signs and broadcasts the buy calls with the
+    /// client's wallet, which replaces `args.taker`. Returns the hashes of the txs.
+    pub async fn buy_and_send(&self, args: BuyArgs, n: usize) -> Result<Vec<H256>, ClientError> {
+        let wallet = self.signer.clone().ok_or(ClientError::NoSigner)?;
+        let args = BuyArgs {
+            taker: wallet.address(),
+            ..args
+        };
+
+        let sells = self.cheapest_listings(args.token, args.token_id, n).await?;
+        let args = self.with_listing_time(args).await?;
+        let chain_id = self.chain_id().await?;
+        let mut spent = U256::zero();
+        let calls = self
+            .buy_orders(&args, sells, chain_id, &mut spent)
+            .await?
+            .into_iter()
+            .map(|buy| buy.call)
+            .collect();
+        self.send_with(wallet.with_chain_id(chain_id), calls).await
+    }
+
+    /// Signs and broadcasts the calls with the client's wallet, in order and with
+    /// consecutive nonces. Returns the hashes of the txs.
+    pub async fn send_calls(
+        &self,
+        calls: Vec<ContractCall<M, ()>>,
+    ) -> Result<Vec<H256>, ClientError> {
+        let wallet = self.signer.clone().ok_or(ClientError::NoSigner)?;
+        let chain_id = self.chain_id().await?;
+        self.send_with(wallet.with_chain_id(chain_id), calls).await
+    }
+
+    /// Stops at the first call which fails to be sent, resyncing the wallet's nonces
+    /// since the following ones were not consumed
+    async fn send_with(
+        &self,
+        wallet: LocalWallet,
+        calls: Vec<ContractCall<M, ()>>,
+    ) -> Result<Vec<H256>, ClientError> {
+        let address = wallet.address();
+        let reservation = self
+            .nonces
+            .reserve(self.provider.as_ref(), address, calls.len())
+            .await?;
+        let provider = SignerMiddleware::new(self.provider.clone(), wallet);
+
+        let mut hashes = Vec::with_capacity(calls.len());
+        for (call, nonce) in calls.into_iter().zip(reservation.nonces()) {
+            let mut tx = call.tx;
+            tx.set_from(address);
+            tx.set_nonce(nonce);
+            match provider.send_transaction(tx, None).await {
+                Ok(pending_tx) => hashes.push(*pending_tx),
+                Err(err) => {
+                    reservation.commit(hashes.len());
+                    self.nonces.resync(self.provider.as_ref(), address).await?;
+                    return Err(ClientError::Provider(err.to_string()));
+                }
+            }
+        }
+
+        reservation.commit(hashes.len());
+        Ok(hashes)
+    }
+}
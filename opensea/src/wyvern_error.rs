@@ -0,0 +1,99 @@
+use thiserror::Error;
+
+use crate::simulate::decode_revert_reason;
+
+/// Known reasons for which the Wyvern exchange's `atomicMatch_` may revert
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum WyvernError {
+    /// The buy and sell orders' parameters do not match (price, target, calldata etc.)
+    #[error("Orders not matchable")]
+    OrdersNotMatchable,
+    /// The order's (v, r, s) signature does not correspond to its maker
+    #[error("Invalid order signature")]
+    InvalidSignature,
+    /// The order was cancelled or already filled
+    #[error("Order cancelled or already finalized")]
+    OrderCancelledOrFinalized,
+    /// The order is not yet listed or has expired
+    #[error("Order is not live (listing time / expiration)")]
+    OrderNotLive,
+    /// The buyer does not hold enough of the payment token (or sent too little ETH)
+    #[error("Insufficient balance of first order")]
+    InsufficientBalance,
+    /// The seller's proxy failed to transfer the asset
+    #[error("Proxy call failed")]
+    ProxyCallFailed,
+    /// The seller has not registered a Wyvern user proxy
+    #[error("Seller has no registered proxy")]
+    NoProxy,
+    /// A revert reason which is not one of the known Wyvern failures
+    #[error("Reverted: {0}")]
+    Other(String),
+    /// The call reverted without any reason. Most of the Wyvern `require`s
+    /// do not have an error message, so this is the most common error.
+    #[error("Reverted without a reason")]
+    Unknown,
+}
+
+impl WyvernError {
+    /// Maps a revert reason string to a known error
+    pub fn from_reason(reason: &str) -> Self {
+        let lower = reason.to_lowercase();
+        if lower.contains("not matchable") {
+            WyvernError::OrdersNotMatchable
+        } else if lower.contains("signature") {
+            WyvernError::InvalidSignature
+        } else if lower.contains("cancelled") || lower.contains("finalized") {
+            WyvernError::OrderCancelledOrFinalized
+        } else if lower.contains("listing time") || lower.contains("expir") {
+            WyvernError::OrderNotLive
+        } else if lower.contains("insufficient") {
+            WyvernError::InsufficientBalance
+        } else if lower.contains("proxy call") || lower.contains("delegatecall") {
+            WyvernError::ProxyCallFailed
+        } else if lower.contains("proxy") {
+            WyvernError::NoProxy
+        } else {
+            WyvernError::Other(reason.to_owned())
+        }
+    }
+
+    /// Maps the raw revert data returned by the node to a known error
+    pub fn from_revert_data(data: &[u8]) -> Self {
+        match decode_revert_reason(data) {
+            Some(reason) => Self::from_reason(&reason),
+            None => WyvernError::Unknown,
+        }
+    }
+}
+
+impl From<Option<String>> for WyvernError {
+    fn from(reason: Option<String>) -> Self {
+        match reason {
+            Some(reason) => Self::from_reason(&reason),
+            None => WyvernError::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_known_reasons() {
+        assert_eq!(
+            WyvernError::from_reason("Order not matchable"),
+            WyvernError::OrdersNotMatchable
+        );
+        assert_eq!(
+            WyvernError::from_reason("Insufficient balance of first order"),
+            WyvernError::InsufficientBalance
+        );
+        assert_eq!(
+            WyvernError::from_reason("foo"),
+            WyvernError::Other("foo".to_owned())
+        );
+        assert_eq!(WyvernError::from_revert_data(&[]), WyvernError::Unknown);
+    }
+}